@@ -80,12 +80,41 @@ pub enum Opcode {
     /// }
     /// ```
     TryConnect = 7,
+
+    /// Complete the registration of a server that presented a public key, by returning the
+    /// signed challenge that was issued in response to its `Register` request.
+    AuthenticatedRegister = 8,
+
+    /// Internal: the signature verification thread reports its verdict on an `AuthenticatedRegister`.
+    /// Only accepted from the name server's own process.
+    AuthenticateResult = 9,
+
+    /// Read-only introspection: describe the registered name at a given index, in name order.
+    /// Any process may ask, so this tells everyone which process owns each name. Which processes
+    /// have connected to a server that authenticated is only told to that server.
+    ///
+    /// # Message Types
+    ///
+    /// * MutableLend of a `NameInfoQuery`; `info` is `None` past the end of the table
+    ListNames = 10,
+
+    /// Read-only introspection: return an entry from the log of recent connection events,
+    /// oldest first. As with `ListNames`, the PID of an event on a server that authenticated
+    /// is only told to that server; everyone else sees 0.
+    ///
+    /// # Message Types
+    ///
+    /// * MutableLend of a `ConnectionLogQuery`; `event` is `None` past the end of the log
+    ConnectionLog = 11,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct Registration {
     pub name: xous_ipc::String<64>,
     pub conn_limit: Option<u32>,
+    /// ed25519 public key of the server. If present, the server must prove it holds the matching
+    /// private key before the registration completes.
+    pub pubkey: Option<[u8; 32]>,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -94,21 +123,109 @@ pub struct Disconnect {
     pub token: [u32; 4],
 }
 
+/// A lookup that only succeeds if the server registered under `name` proved ownership of `pubkey`.
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct AuthenticatedLookup {
     pub name: xous_ipc::String<64>,
-    pub pubkey_id: [u8; 20], // 160-bit pubkey ID encoded in network order (big endian)
-    pub response: [u32; 8],
+    pub pubkey: [u8; 32],
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 #[repr(C)]
 pub struct AuthenticateRequest {
-    pub name: xous_ipc::String<64>, // a copy of the originally requested lookup
-    pub pubkey_id: [u8; 20],        // 160-bit pubkey ID encoded in network order (big endian)
+    pub name: xous_ipc::String<64>, // a copy of the originally requested registration
+    pub pubkey: [u8; 32],           // the public key the challenge must be signed with
     pub challenge: [u32; 4],
 }
 
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct AuthenticateResponse {
+    pub name: xous_ipc::String<64>,
+    pub challenge: [u32; 4],
+    /// ed25519 signature over `auth_message(name, challenge)`
+    pub signature: [u8; 64],
+}
+
+/// A registered name, as seen by `ListNames`. SIDs and disconnect tokens are never reported.
+#[derive(Debug, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct NameInfo {
+    pub name: xous_ipc::String<64>,
+    /// PID of the process that registered the name
+    pub owner: u8,
+    /// if None, unlimited connections allowed
+    pub max_conns: Option<u32>,
+    /// connections made with a plain lookup
+    pub current_conns: u32,
+    /// connections made with an authenticated lookup. These count against `max_conns` just like
+    /// `current_conns` do.
+    pub auth_conns: u32,
+    /// the server registered with a public key and proved that it holds the private key
+    pub authenticated: bool,
+    /// connection attempts turned away because `max_conns` was reached
+    pub refused: u32,
+    /// bitmap of every PID that has been brokered a connection, indexed by PID. Left empty for a
+    /// server that authenticated, unless it is the one asking.
+    pub connected: [u32; 8],
+}
+impl NameInfo {
+    /// Connections that may still be made, or None if the server accepts any number.
+    pub fn remaining_conns(&self) -> Option<u32> {
+        self.max_conns
+            .map(|max| max.saturating_sub(self.current_conns + self.auth_conns))
+    }
+
+    pub fn has_connected(&self, pid: xous::PID) -> bool {
+        let pid = pid.get() as usize;
+        self.connected[pid / 32] & (1 << (pid % 32)) != 0
+    }
+
+    pub fn connected_pids(&self) -> impl Iterator<Item = xous::PID> + '_ {
+        (1..=u8::MAX)
+            .filter_map(xous::PID::new)
+            .filter(move |&pid| self.has_connected(pid))
+    }
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct NameInfoQuery {
+    pub index: u32,
+    pub info: Option<NameInfo>,
+}
+
+/// Something that happened to a connection, as seen by `ConnectionLog`.
+#[derive(Debug, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct ConnectionEvent {
+    /// increases by one with every event, so a gap shows where older events were dropped
+    pub seq: u32,
+    pub name: xous_ipc::String<64>,
+    /// the connecting process, or 0 if it is withheld from the caller (see `Opcode::ConnectionLog`)
+    pub pid: u8,
+    pub kind: ConnectionEventKind,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct ConnectionLogQuery {
+    pub index: u32,
+    pub event: Option<ConnectionEvent>,
+}
+
+const AUTH_DOMAIN: &[u8; 27] = b"xous-names authenticate v1\0";
+pub const AUTH_MESSAGE_LEN: usize = AUTH_DOMAIN.len() + 1 + 64 + 16;
+/// Builds the message that a server signs to prove ownership of its public key. The domain
+/// separator keeps these signatures from being confused with anything else the key signs.
+pub fn auth_message(name: &str, challenge: &[u32; 4]) -> [u8; AUTH_MESSAGE_LEN] {
+    let mut message = [0u8; AUTH_MESSAGE_LEN];
+    let (domain, rest) = message.split_at_mut(AUTH_DOMAIN.len());
+    domain.copy_from_slice(AUTH_DOMAIN);
+    let name_len = name.len().min(64);
+    rest[0] = name_len as u8;
+    rest[1..1 + name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+    for (word, dst) in challenge.iter().zip(rest[1 + 64..].chunks_exact_mut(4)) {
+        dst.copy_from_slice(&word.to_le_bytes());
+    }
+    message
+}
+
 //////////////////////////////////////////////////////////////////////////////////////////////
 // We keep XousServerName around because want to be able to index off the server name, without
 // burdening the Kernel String type with the Hash32 methods
@@ -16,7 +16,7 @@ use crate::api::AuthenticateRequest;
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 #[repr(C)]
 pub enum Return {
-    /// The caller must perform an AuthenticatedLookup using this challenge
+    /// The caller must sign this challenge and return it with an AuthenticatedRegister
     AuthenticateRequest(AuthenticateRequest),
 
     /// The connection failed for some reason
@@ -31,3 +31,19 @@ pub enum Return {
     /// Operation requested was otherwise successful (currently only used by disconnect to ack the disconnect)
     Success,
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+#[repr(C)]
+pub enum ConnectionEventKind {
+    /// A connection was brokered, and counted against the connection limit
+    Connected,
+
+    /// A connection was brokered through an authenticated lookup
+    AuthenticatedConnected,
+
+    /// The connection limit had been reached, so the connection was refused
+    Refused,
+
+    /// A connection was given back with its disconnect token
+    Disconnected,
+}
@@ -4,7 +4,7 @@
 
 pub mod api;
 
-use api::Disconnect;
+use api::{AuthenticateResponse, AuthenticatedLookup, Disconnect};
 use core::fmt::Write;
 use num_traits::ToPrimitive;
 use xous_ipc::{Buffer, String};
@@ -74,6 +74,7 @@ impl XousNames {
         let mut registration = api::Registration {
             name: String::<64>::new(),
             conn_limit: max_conns,
+            pubkey: None,
         };
         // could also do String::from_str() but in this case we want things to fail if the string is too long.
         write!(registration.name, "{}", name).expect("name probably too long");
@@ -94,6 +95,85 @@ impl XousNames {
         }
     }
 
+    /// Register a server with a plaintext `name`, bound to an ed25519 `pubkey`. The name server
+    /// issues a challenge which is handed to `sign`; the registration only completes if the
+    /// returned signature verifies against `pubkey`. Clients can then use
+    /// `request_authenticated_connection()` to make sure they are talking to the holder of the
+    /// private key, and not a squatter on the name.
+    ///
+    /// Connections made with `request_authenticated_connection()` count against `max_conns`.
+    pub fn register_name_authenticated<F>(
+        &self,
+        name: &str,
+        max_conns: Option<u32>,
+        pubkey: &[u8; 32],
+        sign: F,
+    ) -> Result<xous::SID, xous::Error>
+    where
+        F: FnOnce(&[u8]) -> [u8; 64],
+    {
+        let mut registration = api::Registration {
+            name: String::<64>::new(),
+            conn_limit: max_conns,
+            pubkey: Some(*pubkey),
+        };
+        write!(registration.name, "{}", name).expect("name probably too long");
+
+        let mut buf = Buffer::into_buf(registration).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::Register.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let challenge = match buf.to_original().unwrap() {
+            api::Return::AuthenticateRequest(request) => {
+                if &request.pubkey != pubkey {
+                    return Err(xous::Error::InternalError);
+                }
+                request.challenge
+            }
+            api::Return::Failure => return Err(xous::Error::InternalError),
+            _ => unimplemented!("unimplemented return codes"),
+        };
+
+        let response = AuthenticateResponse {
+            name: String::<64>::from_str(name),
+            challenge,
+            signature: sign(&api::auth_message(name, &challenge)),
+        };
+        let mut buf = Buffer::into_buf(response).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::AuthenticatedRegister.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        match buf.to_original().unwrap() {
+            api::Return::SID(sid_raw) => {
+                let sid = sid_raw.into();
+                xous::create_server_with_sid(sid).expect("can't auto-register server");
+                Ok(sid)
+            }
+            api::Return::Failure => Err(xous::Error::AccessDenied),
+            _ => unimplemented!("unimplemented return codes"),
+        }
+    }
+
+    /// Request a connection to the server with `name`, but only if it registered with
+    /// `register_name_authenticated()` and proved ownership of `pubkey`. Returns `AccessDenied`
+    /// if the server is missing, unauthenticated, or bound to a different key.
+    pub fn request_authenticated_connection(
+        &self,
+        name: &str,
+        pubkey: &[u8; 32],
+    ) -> Result<xous::CID, xous::Error> {
+        let lookup = AuthenticatedLookup {
+            name: String::<64>::from_str(name),
+            pubkey: *pubkey,
+        };
+        let mut buf = Buffer::into_buf(lookup).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::AuthenticatedLookup.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+
+        match buf.to_original().unwrap() {
+            api::Return::CID((cid, _)) => Ok(cid),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+
     /// Request a connection to the server with `name`. If the connection is allowed,
     /// a 128-bit token is provided (in the form of a `[u32; 4]`) which can be used
     /// later on to disconnect from the server, effectively decrementing the total
@@ -220,6 +300,51 @@ impl XousNames {
             Err(xous::Error::InternalError)
         }
     }
+
+    /// Lists every registered name in name order, along with who owns it, how many
+    /// connections it has left, and which processes have connected to it. This is
+    /// read-only, and never reveals SIDs or disconnect tokens.
+    ///
+    /// The table is read one entry at a time, so a server that registers or
+    /// unregisters while this runs may be missed.
+    pub fn name_table(&self) -> Result<Vec<api::NameInfo>, xous::Error> {
+        let mut table = Vec::new();
+        loop {
+            let query = api::NameInfoQuery {
+                index: table.len() as u32,
+                info: None,
+            };
+            let mut buf = Buffer::into_buf(query).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.conn, api::Opcode::ListNames.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            let query = buf.to_original::<api::NameInfoQuery, _>().unwrap();
+            match query.info {
+                Some(info) => table.push(info),
+                None => return Ok(table),
+            }
+        }
+    }
+
+    /// Returns the most recent connection events recorded by the name server, oldest first.
+    /// Only a limited number of events are kept; see `api::ConnectionEvent::seq` to tell
+    /// whether any were dropped.
+    pub fn connection_log(&self) -> Result<Vec<api::ConnectionEvent>, xous::Error> {
+        let mut log = Vec::new();
+        loop {
+            let query = api::ConnectionLogQuery {
+                index: log.len() as u32,
+                event: None,
+            };
+            let mut buf = Buffer::into_buf(query).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.conn, api::Opcode::ConnectionLog.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            let query = buf.to_original::<api::ConnectionLogQuery, _>().unwrap();
+            match query.event {
+                Some(event) => log.push(event),
+                None => return Ok(log),
+            }
+        }
+    }
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
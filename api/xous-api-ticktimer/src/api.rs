@@ -70,6 +70,20 @@ pub enum Opcode {
     /// *arg1*: The integer that matches the Condition value
     FreeCondition = 11,
 
+    /// Register a timer that sends a scalar message to a server when it is due
+    ///
+    /// # Arguments
+    ///
+    /// A `TimerRegistration`, lent mutably. The ticktimer fills in its `id`.
+    RegisterTimer = 12,
+
+    /// Cancel a timer created with `RegisterTimer`
+    ///
+    /// # Arguments
+    ///
+    /// *arg1*: The ID of the timer
+    CancelTimer = 13,
+
     /// Invalid call -- an error occurred decoding the opcode
     InvalidCall = u32::MAX as usize,
 }
@@ -78,3 +92,31 @@ pub enum Opcode {
 pub struct VersionString {
     pub version: xous_ipc::String<512>,
 }
+
+/// A request for a timer notification. Whenever the timer is due, the ticktimer
+/// sends a non-blocking scalar message to `sid` with the following layout:
+///
+/// *id*: `opcode`
+/// *arg1*: The ID of the timer
+/// *arg2*: How many times the timer came due since the last notification. This
+/// is normally 1, but ticks are coalesced into a single notification if the
+/// server's queue is full or the ticktimer falls behind.
+///
+/// Time spent suspended does not count towards a timer, so a resume does not
+/// cause a burst of notifications.
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct TimerRegistration {
+    /// The server to notify
+    pub sid: [u32; 4],
+    pub opcode: u32,
+
+    /// Milliseconds until the first notification
+    pub delay_ms: u64,
+
+    /// Milliseconds between notifications, or 0 for a one-shot timer
+    pub period_ms: u64,
+
+    /// Filled in by the ticktimer with the ID of the new timer, or 0 if the
+    /// server could not be reached
+    pub id: u32,
+}
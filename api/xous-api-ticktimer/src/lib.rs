@@ -194,6 +194,92 @@ impl Ticktimer {
         .map(|r| r == xous::Result::Scalar1(0))
         .expect("couldn't notify condition");
     }
+
+    /// Send a scalar message with ID `opcode` to the server `sid` once, after
+    /// `ms` milliseconds. See `api::TimerRegistration` for the message layout.
+    ///
+    /// # Returns:
+    ///
+    ///     * The ID of the timer, which may be passed to `cancel_timer()`
+    pub fn notify_after(&self, sid: xous::SID, opcode: u32, ms: u64) -> Result<usize, Error> {
+        self.register_timer(sid, opcode, ms, 0)
+    }
+
+    /// Send a scalar message with ID `opcode` to the server `sid` every
+    /// `period_ms` milliseconds, starting `period_ms` milliseconds from now.
+    /// Notifications are scheduled relative to when the timer was registered,
+    /// so they do not drift over time. See `api::TimerRegistration` for the
+    /// message layout.
+    ///
+    /// # Returns:
+    ///
+    ///     * The ID of the timer, which may be passed to `cancel_timer()`
+    pub fn notify_every(
+        &self,
+        sid: xous::SID,
+        opcode: u32,
+        period_ms: u64,
+    ) -> Result<usize, Error> {
+        if period_ms == 0 {
+            return Err(Error::InvalidLimit);
+        }
+        self.register_timer(sid, opcode, period_ms, period_ms)
+    }
+
+    fn register_timer(
+        &self,
+        sid: xous::SID,
+        opcode: u32,
+        delay_ms: u64,
+        period_ms: u64,
+    ) -> Result<usize, Error> {
+        let registration = api::TimerRegistration {
+            sid: sid.to_array(),
+            opcode,
+            delay_ms,
+            period_ms,
+            id: 0,
+        };
+        let mut buf = xous_ipc::Buffer::into_buf(registration).or(Err(Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::RegisterTimer.to_u32().unwrap())?;
+        let registration = buf
+            .to_original::<api::TimerRegistration, _>()
+            .or(Err(Error::InternalError))?;
+        match registration.id {
+            0 => Err(Error::ServerNotFound),
+            id => Ok(id as usize),
+        }
+    }
+
+    /// Cancel a timer created with `notify_after()` or `notify_every()`. No new
+    /// notifications are sent once this returns, though one may already be
+    /// waiting in the server's queue.
+    ///
+    /// # Arguments:
+    ///
+    ///     * id: The ID of the timer, as returned when it was registered
+    ///
+    /// # Returns:
+    ///
+    ///     * `Error::DoubleFree` if the timer already finished or was cancelled
+    ///     * `Error::AccessDenied` if the timer was registered by another process
+    pub fn cancel_timer(&self, id: usize) -> Result<(), Error> {
+        let response = send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                api::Opcode::CancelTimer.to_usize().unwrap(),
+                id,
+                0,
+                0,
+                0,
+            ),
+        )?;
+        match response {
+            xous::Result::Scalar1(0) => Ok(()),
+            xous::Result::Scalar1(e) => Err(Error::from_usize(e)),
+            _ => Err(Error::InternalError),
+        }
+    }
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
@@ -1,4 +1,4 @@
-use cbor::{self, cbor_array_vec, cbor_int, cbor_map, cbor_unsigned, destructure_cbor_map};
+use cbor::{self, cbor_array_vec, cbor_int, cbor_map, cbor_map_options, cbor_unsigned, destructure_cbor_map};
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
@@ -49,6 +49,9 @@ pub enum HashAlgorithms {
     SHA1,
     SHA256,
     SHA512,
+    /// Steam Guard codes: HMAC-SHA1 with a five-character alphabetic code.
+    #[serde(rename = "STEAM")]
+    Steam,
 }
 
 impl From<HashAlgorithms> for cbor::Value {
@@ -57,6 +60,7 @@ impl From<HashAlgorithms> for cbor::Value {
             HashAlgorithms::SHA1 => cbor_unsigned!(1),
             HashAlgorithms::SHA256 => cbor_unsigned!(2),
             HashAlgorithms::SHA512 => cbor_unsigned!(3),
+            HashAlgorithms::Steam => cbor_unsigned!(4),
         }
     }
 }
@@ -68,6 +72,7 @@ impl TryFrom<cbor::Value> for HashAlgorithms {
             1 => Ok(HashAlgorithms::SHA1),
             2 => Ok(HashAlgorithms::SHA256),
             3 => Ok(HashAlgorithms::SHA512),
+            4 => Ok(HashAlgorithms::Steam),
             _ => Err(CborConversionError::UnknownAlgorithm(v)),
         }
     }
@@ -81,6 +86,7 @@ impl std::str::FromStr for HashAlgorithms {
             "SHA1" => Ok(HashAlgorithms::SHA1),
             "SHA256" => Ok(HashAlgorithms::SHA256),
             "SHA512" => Ok(HashAlgorithms::SHA512),
+            "STEAM" => Ok(HashAlgorithms::Steam),
             _ => Err(HashFromStrError::UnknownHash),
         }
     }
@@ -97,16 +103,22 @@ pub struct TotpEntry {
     pub name: String,
     #[serde(default)] // if hotp is missing from the JSON representation, it's assumed to be false.
     pub hotp: bool,
+    #[serde(default)]
+    pub issuer: String,
 }
 
 impl From<TotpEntry> for cbor::Value {
     fn from(te: TotpEntry) -> Self {
-        cbor_map! {
+        // issuer is left out when empty, so that backups without one look the same as they always did
+        let issuer = if te.issuer.is_empty() { None } else { Some(te.issuer) };
+        cbor_map_options! {
             cbor_int!(1) => te.step_seconds as i64,
             cbor_int!(2) => te.shared_secret,
             cbor_int!(3) => te.digit_count as i64,
             cbor_int!(4) => te.algorithm,
             cbor_int!(5) => te.name,
+            cbor_int!(6) => te.hotp,
+            cbor_int!(7) => issuer,
         }
     }
 }
@@ -126,6 +138,7 @@ impl TryFrom<cbor::Value> for TotpEntry {
                 4 => algorithm,
                 5 => name,
                 6 => hotp,
+                7 => issuer,
             } = rawmap;
         }
 
@@ -137,6 +150,10 @@ impl TryFrom<cbor::Value> for TotpEntry {
         let hotp = extract_bool(hotp.unwrap_or(
             cbor::Value::Simple(cbor::SimpleValue::FalseValue)
         )).unwrap_or(false);
+        let issuer = match issuer {
+            Some(issuer) => extract_string(issuer)?,
+            None => String::new(),
+        };
 
         Ok(TotpEntry {
             step_seconds,
@@ -145,6 +162,7 @@ impl TryFrom<cbor::Value> for TotpEntry {
             algorithm,
             name,
             hotp,
+            issuer,
         })
     }
 
@@ -552,3 +570,55 @@ fn extract_string(cbor_value: cbor::Value) -> Result<String, CborConversionError
         _ => Err(CborConversionError::BadCbor),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totp_entry_cbor_round_trip() {
+        let entries = TotpEntries(vec![
+            TotpEntry {
+                step_seconds: 30,
+                shared_secret: "JBSWY3DPEHPK3PXP".to_string(),
+                digit_count: 5,
+                algorithm: HashAlgorithms::Steam,
+                name: "Steam:gaben".to_string(),
+                hotp: false,
+                issuer: "Steam".to_string(),
+            },
+            TotpEntry {
+                step_seconds: 42,
+                shared_secret: "GEZDGNBVGY3TQOJQ".to_string(),
+                digit_count: 8,
+                algorithm: HashAlgorithms::SHA512,
+                name: "counter".to_string(),
+                hotp: true,
+                issuer: String::new(),
+            },
+        ]);
+        let value: cbor::Value = (&entries).into();
+        let decoded = TotpEntries::try_from(value).unwrap();
+        assert_eq!(decoded.0.len(), entries.0.len());
+        for (d, e) in decoded.0.iter().zip(entries.0.iter()) {
+            assert_eq!(d.step_seconds, e.step_seconds);
+            assert_eq!(d.shared_secret, e.shared_secret);
+            assert_eq!(d.digit_count, e.digit_count);
+            assert_eq!(cbor::Value::from(d.algorithm.clone()), cbor::Value::from(e.algorithm.clone()));
+            assert_eq!(d.name, e.name);
+            assert_eq!(d.hotp, e.hotp);
+            assert_eq!(d.issuer, e.issuer);
+        }
+    }
+
+    #[test]
+    fn totp_entry_json_defaults() {
+        // backups written before hotp and issuer were added still load
+        let json = r#"[{"step_seconds":30,"shared_secret":"JBSWY3DPEHPK3PXP","digit_count":6,"algorithm":"SHA1","name":"old"}]"#;
+        let entries: TotpEntries = serde_json::from_str(json).unwrap();
+        assert!(!entries.0[0].hotp);
+        assert!(entries.0[0].issuer.is_empty());
+        let steam: HashAlgorithms = serde_json::from_str(r#""STEAM""#).unwrap();
+        assert_eq!(cbor::Value::from(steam), cbor_unsigned!(4));
+    }
+}
@@ -18,7 +18,7 @@ use vault::{
 use crate::{ListItem, ListKey, storage::TotpRecord};
 use crate::{ItemLists, VaultMode, SelectedEntry};
 use crate::storage::{self, PasswordRecord, StorageContent};
-use crate::totp::TotpAlgorithm;
+use crate::totp::{TotpAlgorithm, TotpEntry, hotp_resync, HOTP_RESYNC_WINDOW};
 use persistent_store::store::OPENSK2_DICT;
 
 use vault::env::xous::U2F_APP_DICT;
@@ -40,6 +40,7 @@ pub enum ActionOp {
     MenuAddnew,
     MenuEditStage2,
     MenuDeleteStage2,
+    MenuHotpResyncStage2,
     MenuClose,
     MenuUnlockBasis,
     MenuManageBasis,
@@ -398,6 +399,7 @@ impl<'a> ActionManager<'a> {
                 let mut totp = storage::TotpRecord {
                     version: VAULT_TOTP_REC_VERSION,
                     name: description,
+                    issuer: String::new(),
                     secret: validated_secret,
                     algorithm: TotpAlgorithm::HmacSha1,
                     digits: 6,
@@ -445,8 +447,9 @@ impl<'a> ActionManager<'a> {
                 ) {
                     Ok(candidate) => {
                         let attr = candidate.attributes().expect("couldn't get key attributes");
+                        // records hold secrets, so make sure the old ciphertext is overwritten right away
                         match self.pddb.borrow()
-                        .delete_key(
+                        .delete_key_paranoid(
                             dictionary,
                             entry.key_guid.as_str().unwrap_or("UTF8-error"),
                             Some(&attr.basis)
@@ -832,6 +835,81 @@ impl<'a> ActionManager<'a> {
         }
     }
 
+    /// Recovers an HOTP record whose count has drifted from the server's: the user enters two
+    /// consecutive codes from the server, and the count is moved to just past them.
+    pub(crate) fn menu_hotp_resync(&mut self, entry: SelectedEntry) {
+        if entry.mode != VaultMode::Totp {
+            self.modals.show_notification(t!("vault.hotp.resync_not_hotp", locales::LANG), None).ok();
+            return;
+        }
+        let choice = storage::ContentKind::TOTP;
+        let key_guid = entry.key_guid.as_str().unwrap();
+        let mut storage = self.storage.borrow_mut();
+        let mut record: storage::TotpRecord = match storage.get_record(&choice, key_guid) {
+            Ok(record) => record,
+            Err(error) => {
+                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                return;
+            }
+        };
+        if !record.is_hotp {
+            self.modals.show_notification(t!("vault.hotp.resync_not_hotp", locales::LANG), None).ok();
+            return;
+        }
+
+        let codes = match self.modals
+            .alert_builder(t!("vault.hotp.resync_prompt", locales::LANG))
+            .field(None, Some(count_validator))
+            .field(None, Some(count_validator))
+            .field(Some(HOTP_RESYNC_WINDOW.to_string()), Some(count_validator))
+            .build()
+        {
+            Ok(codes) => codes,
+            _ => {log::error!("HOTP resync entry failed"); return}
+        };
+        let window = codes.content()[2].content.as_str().unwrap().parse::<u64>().unwrap_or(HOTP_RESYNC_WINDOW);
+        let hotp = TotpEntry {
+            step_seconds: 1,
+            shared_secret: base32::decode(base32::Alphabet::RFC4648 { padding: false }, &record.secret)
+                .unwrap_or(vec![]),
+            digit_count: record.digits as u8,
+            algorithm: record.algorithm,
+        };
+        let found = match hotp_resync(
+            &hotp,
+            record.timestep,
+            codes.content()[0].content.as_str().unwrap(),
+            codes.content()[1].content.as_str().unwrap(),
+            window,
+        ) {
+            Ok(found) => found,
+            Err(e) => {
+                self.report_err(t!("vault.error.record_error", locales::LANG), Some(e));
+                return;
+            }
+        };
+        match found {
+            Some(count) => {
+                log::info!("HOTP count resynchronized from {} to {}", record.timestep, count);
+                record.timestep = count;
+                match storage.update(&choice, key_guid, &mut record) {
+                    Ok(_) => {
+                        // the count is part of the item's cached `extra` field
+                        let li = make_totp_item_from_record(key_guid, record);
+                        self.item_lists.lock().unwrap().insert_unique(self.mode_cache, li);
+                        self.modals.show_notification(
+                            &format!("{}{}", t!("vault.hotp.resync_ok", locales::LANG), count), None
+                        ).ok();
+                    }
+                    Err(e) => self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                }
+            }
+            None => {
+                self.modals.show_notification(t!("vault.hotp.resync_failed", locales::LANG), None).ok();
+            }
+        }
+    }
+
     fn yes_no_approval(&self, query: &str) -> bool {
         self.modals.add_list(
             vec![t!("vault.yes", locales::LANG), t!("vault.no", locales::LANG)]
@@ -1442,6 +1520,7 @@ impl<'a> ActionManager<'a> {
                     version: VAULT_TOTP_REC_VERSION,
                     secret: base32::encode(base32::Alphabet::RFC4648 { padding: false }, &secret_bytes),
                     name,
+                    issuer: String::new(),
                     algorithm: TotpAlgorithm::HmacSha1,
                     notes,
                     digits: 6,
@@ -1460,6 +1539,7 @@ impl<'a> ActionManager<'a> {
                 version: VAULT_TOTP_REC_VERSION,
                 secret: "I65VU7K5ZQL7WB4E".to_string(),
                 name: "totp@authenticationtest.com".to_string(),
+                issuer: String::new(),
                 algorithm: TotpAlgorithm::HmacSha1,
                 notes: "Predefined test".to_string(),
                 digits: 6,
@@ -172,6 +172,13 @@ fn main() -> ! {
                         manager.menu_edit(entry); // this is responsible for updating the item cache
                         manager.deactivate();
                     },
+                    Some(ActionOp::MenuHotpResyncStage2) => {
+                        let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                        let entry = buffer.to_original::<SelectedEntry, _>().unwrap();
+                        manager.activate();
+                        manager.menu_hotp_resync(entry); // this is responsible for updating the item cache
+                        manager.deactivate();
+                    },
                     Some(ActionOp::MenuUnlockBasis) => {
                         manager.activate();
                         manager.unlock_basis();
@@ -677,6 +684,18 @@ fn main() -> ! {
                     allow_totp_rendering.store(true, Ordering::SeqCst);
                 }
             }
+            Some(VaultOp::MenuHotpResyncStage1) => {
+                // stage 1 happens here because the filtered list and selection entry are in the responsive UX section.
+                if let Some(entry) = vaultux.selected_entry() {
+                    let buf = Buffer::into_buf(entry).expect("IPC error");
+                    buf.send(actions_conn, ActionOp::MenuHotpResyncStage2.to_u32().unwrap()).expect("messaging error");
+                } else {
+                    // this will block redraws
+                    allow_totp_rendering.store(false, Ordering::SeqCst);
+                    modals.show_notification(t!("vault.error.nothing_selected", locales::LANG), None).ok();
+                    allow_totp_rendering.store(true, Ordering::SeqCst);
+                }
+            }
             Some(VaultOp::MenuReadoutMode) => {
                 modals.dynamic_notification(Some(t!("vault.readout_switchover", locales::LANG)), None).ok();
                 vaultux.readout_mode(true);
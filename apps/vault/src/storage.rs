@@ -307,7 +307,7 @@ impl Manager {
 
         let basis = self.basis_for_key(&settings.dict, key_name)?;
         self.pddb
-            .delete_key(&settings.dict, key_name, Some(&basis))
+            .delete_key_paranoid(&settings.dict, key_name, Some(&basis))
             .map_err(|error| Error::IoError(error))
     }
 }
@@ -318,6 +318,8 @@ pub struct TotpRecord {
     // as base32, RFC4648 no padding
     pub secret: String,
     pub name: String,
+    // the `issuer` parameter of the otpauth:// URI the record came from, if any
+    pub issuer: String,
     pub algorithm: TotpAlgorithm,
     pub notes: String,
     pub digits: u32,
@@ -368,6 +370,7 @@ impl StorageContent for TotpRecord {
                     }
                     "secret" => pr.secret.push_str(data),
                     "name" => pr.name.push_str(data),
+                    "issuer" => pr.issuer.push_str(data),
                     "algorithm" => {
                         pr.algorithm = match TotpAlgorithm::try_from(data) {
                             Ok(a) => a,
@@ -429,13 +432,15 @@ impl StorageContent for TotpRecord {
     }
     fn to_vec(&self) -> Vec<u8> {
         format!(
-            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
+            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
             "version",
             self.version,
             "secret",
             self.secret,
             "name",
             self.name,
+            "issuer",
+            self.issuer,
             "algorithm",
             self.algorithm,
             "notes",
@@ -469,6 +474,7 @@ impl TryFrom<Vec<u8>> for TotpRecord {
             version: VAULT_TOTP_REC_VERSION,
             secret: String::new(),
             name: String::new(),
+            issuer: String::new(),
             algorithm: TotpAlgorithm::HmacSha1,
             notes: String::new(),
             digits: 0,
@@ -490,6 +496,7 @@ impl TryFrom<Vec<u8>> for TotpRecord {
                     }
                     "secret" => pr.secret.push_str(data),
                     "name" => pr.name.push_str(data),
+                    "issuer" => pr.issuer.push_str(data),
                     "algorithm" => {
                         pr.algorithm = match TotpAlgorithm::try_from(data) {
                             Ok(a) => a,
@@ -552,13 +559,15 @@ impl TryFrom<Vec<u8>> for TotpRecord {
 impl From<TotpRecord> for Vec<u8> {
     fn from(tr: TotpRecord) -> Self {
         format!(
-            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
+            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
             "version",
             tr.version,
             "secret",
             tr.secret,
             "name",
             tr.name,
+            "issuer",
+            tr.issuer,
             "algorithm",
             tr.algorithm,
             "notes",
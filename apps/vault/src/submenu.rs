@@ -22,6 +22,13 @@ pub fn create_submenu(vault_conn: xous::CID, actions_conn: xous::CID, menu_mgr:
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
+    menu_items.push(MenuItem {
+        name: xous_ipc::String::from_str(t!("vault.menu_hotp_resync", locales::LANG)),
+        action_conn: Some(vault_conn),
+        action_opcode: VaultOp::MenuHotpResyncStage1.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
     menu_items.push(MenuItem {
         name: xous_ipc::String::from_str(t!("vault.menu_unlock_basis", locales::LANG)),
         action_conn: Some(actions_conn),
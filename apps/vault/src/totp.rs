@@ -17,6 +17,8 @@ pub enum TotpAlgorithm {
     HmacSha1,
     HmacSha256,
     HmacSha512,
+    /// Steam Guard: HMAC-SHA1, but the code is spelled in `STEAM_ALPHABET` instead of decimal digits.
+    Steam,
     None,
 }
 
@@ -32,6 +34,7 @@ impl std::fmt::Debug for TotpAlgorithm {
             TotpAlgorithm::HmacSha1 => write!(f, "SHA1"),
             TotpAlgorithm::HmacSha256 => write!(f, "SHA256"),
             TotpAlgorithm::HmacSha512 => write!(f, "SHA512"),
+            TotpAlgorithm::Steam => write!(f, "STEAM"),
             TotpAlgorithm::None => write!(f, "None"),
         }
     }
@@ -44,6 +47,7 @@ impl TryFrom<&str> for TotpAlgorithm {
             "SHA1" => Ok(TotpAlgorithm::HmacSha1),
             "SHA256" => Ok(TotpAlgorithm::HmacSha256),
             "SHA512" => Ok(TotpAlgorithm::HmacSha512),
+            "STEAM" => Ok(TotpAlgorithm::Steam),
             _ => Err(xous::Error::InvalidString)
         }
     }
@@ -54,6 +58,7 @@ impl core::fmt::Display for TotpAlgorithm {
             TotpAlgorithm::HmacSha1 => write!(f, "SHA1"),
             TotpAlgorithm::HmacSha256 => write!(f, "SHA256"),
             TotpAlgorithm::HmacSha512 => write!(f, "SHA512"),
+            TotpAlgorithm::Steam => write!(f, "STEAM"),
             TotpAlgorithm::None => write!(f, "None"),
         }
     }
@@ -98,33 +103,44 @@ fn unpack_u64(v: u64) -> [u8; 8] {
     bytes
 }
 
+/// How far past the stored HOTP count `hotp_resync()` searches, unless the user asks otherwise.
+pub const HOTP_RESYNC_WINDOW: u64 = 100;
+/// The furthest `hotp_resync()` will search, whatever it is asked for. Every count in the window
+/// costs two HMACs on the UI thread, so this keeps a mistyped window from hanging the vault.
+pub const HOTP_RESYNC_WINDOW_MAX: u64 = 1000;
+
 fn generate_hmac_bytes(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<Vec<u8>, Error> {
-    let mut computed_hmac = Vec::new();
     let checked_step = if totp_entry.step_seconds == 0 {
         log::warn!("totp step_seconds was 0, this would cause a div-by-zero; forcing to 1. Check that this is not an HOTP record?");
         1
     } else {
         totp_entry.step_seconds
     };
+    generate_counter_hmac(unix_timestamp / checked_step, totp_entry)
+}
+
+/// The HOTP computation from RFC 4226; TOTP is HOTP with a counter derived from the time.
+fn generate_counter_hmac(counter: u64, totp_entry: &TotpEntry) -> Result<Vec<u8>, Error> {
+    let mut computed_hmac = Vec::new();
     match totp_entry.algorithm {
         // The OpenTitan HMAC core does not support hmac-sha1. Fall back to
         // a software implementation.
-        TotpAlgorithm::HmacSha1 => {
+        TotpAlgorithm::HmacSha1 | TotpAlgorithm::Steam => {
             let mut mac: Hmac<Sha1> = Hmac::new_from_slice(&totp_entry.shared_secret)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
+            mac.update(&unpack_u64(counter));
             let hash: &[u8] = &mac.finalize().into_bytes();
             computed_hmac.extend_from_slice(hash);
         }
         // note: sha256/sha512 implementations not yet tested, as we have yet to find a site that uses this to test against.
         TotpAlgorithm::HmacSha256 => {
             let mut mac: Hmac<sha2::Sha256> = Hmac::new_from_slice(&totp_entry.shared_secret)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
+            mac.update(&unpack_u64(counter));
             let hash: &[u8] = &mac.finalize().into_bytes();
             computed_hmac.extend_from_slice(hash);
         }
         TotpAlgorithm::HmacSha512 => {
             let mut mac: Hmac<sha2::Sha512> = Hmac::new_from_slice(&totp_entry.shared_secret)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
+            mac.update(&unpack_u64(counter));
             let hash: &[u8] = &mac.finalize().into_bytes();
             computed_hmac.extend_from_slice(hash);
         }
@@ -136,21 +152,66 @@ fn generate_hmac_bytes(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<Ve
     Ok(computed_hmac)
 }
 
-pub fn generate_totp_code(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<String, Error> {
-    let hash = generate_hmac_bytes(unix_timestamp, totp_entry)?;
+/// The characters Steam Guard codes are spelled with. The first character of a code is its least
+/// significant "digit".
+const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+/// Steam Guard codes are always this long, whatever the record's digit count says.
+const STEAM_CODE_LEN: u8 = 5;
+
+fn truncate_hmac(hash: &[u8], digit_count: u8, algorithm: TotpAlgorithm) -> String {
     let offset: usize = (hash.last().unwrap_or(&0) & 0xf) as usize;
-    let binary: u64 = (((hash[offset] & 0x7f) as u64) << 24)
+    let mut binary: u64 = (((hash[offset] & 0x7f) as u64) << 24)
         | ((hash[offset + 1] as u64) << 16)
         | ((hash[offset + 2] as u64) << 8)
         | (hash[offset + 3] as u64);
 
-    let truncated_code = format!(
+    if let TotpAlgorithm::Steam = algorithm {
+        let mut code = String::with_capacity(STEAM_CODE_LEN as usize);
+        for _ in 0..STEAM_CODE_LEN {
+            code.push(STEAM_ALPHABET[(binary % STEAM_ALPHABET.len() as u64) as usize] as char);
+            binary /= STEAM_ALPHABET.len() as u64;
+        }
+        return code;
+    }
+    format!(
         "{:01$}",
-        binary % (10_u64.pow(totp_entry.digit_count as u32)),
-        totp_entry.digit_count as usize
-    );
+        binary % (10_u64.pow(digit_count as u32)),
+        digit_count as usize
+    )
+}
+
+pub fn generate_totp_code(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<String, Error> {
+    let hash = generate_hmac_bytes(unix_timestamp, totp_entry)?;
+    Ok(truncate_hmac(&hash, totp_entry.digit_count, totp_entry.algorithm))
+}
+
+/// Generates the HOTP code for `counter`. `step_seconds` is ignored.
+pub fn generate_hotp_code(counter: u64, totp_entry: &TotpEntry) -> Result<String, Error> {
+    let hash = generate_counter_hmac(counter, totp_entry)?;
+    Ok(truncate_hmac(&hash, totp_entry.digit_count, totp_entry.algorithm))
+}
 
-    Ok(truncated_code)
+/// Finds where an HOTP sequence has drifted to, given two consecutive codes from it. Counts from
+/// `count` through `count + window` are searched for `first`, followed immediately by `second`;
+/// `window` is clamped to `HOTP_RESYNC_WINDOW_MAX`. Returns the count that comes after `second`,
+/// which is what the record should store so that the next code it produces is the one the server
+/// expects; or `None` if the codes weren't found.
+pub fn hotp_resync(totp_entry: &TotpEntry, count: u64, first: &str, second: &str, window: u64) -> Result<Option<u64>, Error> {
+    let (first, second) = (first.trim(), second.trim());
+    if window > HOTP_RESYNC_WINDOW_MAX {
+        log::warn!("HOTP resync window of {} clamped to {}", window, HOTP_RESYNC_WINDOW_MAX);
+    }
+    let window = window.min(HOTP_RESYNC_WINDOW_MAX);
+    let last = count.saturating_add(window).min(u64::MAX - 2);
+    let mut code = generate_hotp_code(count, totp_entry)?;
+    for candidate in count..=last {
+        let next = generate_hotp_code(candidate + 1, totp_entry)?;
+        if code == first && next == second {
+            return Ok(Some(candidate + 2));
+        }
+        code = next;
+    }
+    Ok(None)
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
@@ -200,4 +261,125 @@ pub(crate) fn pumper(
             xous::destroy_server(sid).ok();
         }
     });
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4226, Appendix D
+    const RFC4226_SECRET: &[u8] = b"12345678901234567890";
+    const RFC4226_CODES: [&str; 10] = [
+        "755224", "287082", "359152", "969429", "338314",
+        "254676", "287922", "162583", "399871", "520489",
+    ];
+
+    fn rfc4226_entry() -> TotpEntry {
+        TotpEntry {
+            step_seconds: 30,
+            shared_secret: RFC4226_SECRET.to_vec(),
+            digit_count: 6,
+            algorithm: TotpAlgorithm::HmacSha1,
+        }
+    }
+
+    #[test]
+    fn hotp_test_vectors() {
+        let entry = rfc4226_entry();
+        for (count, &expected) in RFC4226_CODES.iter().enumerate() {
+            assert_eq!(generate_hotp_code(count as u64, &entry).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn totp_is_hotp_of_time_step() {
+        let entry = rfc4226_entry();
+        for (count, &expected) in RFC4226_CODES.iter().enumerate() {
+            assert_eq!(generate_totp_code(count as u64 * 30 + 29, &entry).unwrap(), expected);
+        }
+    }
+
+    // RFC 6238, Appendix B
+    const RFC6238_TIMES: [u64; 6] = [59, 1111111109, 1111111111, 1234567890, 2000000000, 20000000000];
+
+    fn rfc6238_entry(algorithm: TotpAlgorithm) -> TotpEntry {
+        let seed: &[u8] = match algorithm {
+            TotpAlgorithm::HmacSha256 => b"12345678901234567890123456789012",
+            TotpAlgorithm::HmacSha512 => b"1234567890123456789012345678901234567890123456789012345678901234",
+            _ => b"12345678901234567890",
+        };
+        TotpEntry {
+            step_seconds: 30,
+            shared_secret: seed.to_vec(),
+            digit_count: 8,
+            algorithm,
+        }
+    }
+
+    #[test]
+    fn totp_test_vectors() {
+        let vectors = [
+            (TotpAlgorithm::HmacSha1, ["94287082", "07081804", "14050471", "89005924", "69279037", "65353130"]),
+            (TotpAlgorithm::HmacSha256, ["46119246", "68084774", "67062674", "91819424", "90698825", "77737706"]),
+            (TotpAlgorithm::HmacSha512, ["90693936", "25091201", "99943326", "93441116", "38618901", "47863826"]),
+        ];
+        for (algorithm, codes) in vectors.iter() {
+            let entry = rfc6238_entry(*algorithm);
+            for (&time, &expected) in RFC6238_TIMES.iter().zip(codes.iter()) {
+                assert_eq!(generate_totp_code(time, &entry).unwrap(), expected, "{:?} at {}", algorithm, time);
+            }
+        }
+    }
+
+    #[test]
+    fn steam_test_vectors() {
+        // the RFC 6238 SHA1 seed, spelled out the way the Steam mobile app does it
+        let mut entry = rfc6238_entry(TotpAlgorithm::Steam);
+        let codes = ["PV9M4", "PY4YB", "5PP3V", "VHHQY", "9N776", "R5DMB"];
+        for (&time, &expected) in RFC6238_TIMES.iter().zip(codes.iter()) {
+            assert_eq!(generate_totp_code(time, &entry).unwrap(), expected, "STEAM at {}", time);
+        }
+        // the digit count doesn't change the length of a Steam code
+        entry.digit_count = 6;
+        assert_eq!(generate_totp_code(59, &entry).unwrap(), "PV9M4");
+    }
+
+    #[test]
+    fn algorithm_names_round_trip() {
+        for algorithm in [TotpAlgorithm::HmacSha1, TotpAlgorithm::HmacSha256, TotpAlgorithm::HmacSha512, TotpAlgorithm::Steam].iter() {
+            let name = algorithm.to_string();
+            assert_eq!(TotpAlgorithm::try_from(name.as_str()).unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn resync_finds_drifted_count() {
+        let entry = rfc4226_entry();
+        // the record thinks it is at 1, but the server has moved on to 7
+        assert_eq!(hotp_resync(&entry, 1, RFC4226_CODES[5], RFC4226_CODES[6], 10).unwrap(), Some(7));
+        // codes at the very edge of the window are still found
+        assert_eq!(hotp_resync(&entry, 1, RFC4226_CODES[6], RFC4226_CODES[7], 5).unwrap(), Some(8));
+        assert_eq!(hotp_resync(&entry, 0, RFC4226_CODES[0], RFC4226_CODES[1], 0).unwrap(), Some(2));
+        // surrounding whitespace from the text entry is ignored
+        assert_eq!(hotp_resync(&entry, 0, " 969429", "338314 ", 10).unwrap(), Some(5));
+    }
+
+    #[test]
+    fn resync_rejects_codes_outside_window() {
+        let entry = rfc4226_entry();
+        assert_eq!(hotp_resync(&entry, 1, RFC4226_CODES[6], RFC4226_CODES[7], 4).unwrap(), None);
+        // the counter never runs backwards
+        assert_eq!(hotp_resync(&entry, 4, RFC4226_CODES[2], RFC4226_CODES[3], 10).unwrap(), None);
+        // both codes have to match, in order
+        assert_eq!(hotp_resync(&entry, 0, RFC4226_CODES[3], RFC4226_CODES[2], 10).unwrap(), None);
+        assert_eq!(hotp_resync(&entry, 0, RFC4226_CODES[3], RFC4226_CODES[5], 10).unwrap(), None);
+    }
+
+    #[test]
+    fn resync_window_is_clamped() {
+        let entry = rfc4226_entry();
+        let first = generate_hotp_code(HOTP_RESYNC_WINDOW_MAX + 1, &entry).unwrap();
+        let second = generate_hotp_code(HOTP_RESYNC_WINDOW_MAX + 2, &entry).unwrap();
+        assert_eq!(hotp_resync(&entry, 1, &first, &second, u64::MAX).unwrap(), Some(HOTP_RESYNC_WINDOW_MAX + 3));
+        assert_eq!(hotp_resync(&entry, 0, &first, &second, u64::MAX).unwrap(), None);
+    }
+}
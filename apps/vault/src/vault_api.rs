@@ -36,6 +36,7 @@ pub enum VaultOp {
     MenuChangeFont,
     MenuDeleteStage1,
     MenuEditStage1,
+    MenuHotpResyncStage1,
     MenuAutotype,
     MenuReadoutMode,
     MenuAutotypeRate,
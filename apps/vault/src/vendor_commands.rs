@@ -263,17 +263,19 @@ fn handle_restore(data: Vec<u8>, xns: &xous_names::XousNames) -> Result<Vec<u8>,
                 let totp = TotpRecord {
                     version: 1,
                     name: elem.name,
+                    issuer: elem.issuer,
                     secret: elem.shared_secret,
                     algorithm: match elem.algorithm {
                         backup::HashAlgorithms::SHA1 => TotpAlgorithm::HmacSha1,
                         backup::HashAlgorithms::SHA256 => TotpAlgorithm::HmacSha256,
                         backup::HashAlgorithms::SHA512 => TotpAlgorithm::HmacSha512,
+                        backup::HashAlgorithms::Steam => TotpAlgorithm::Steam,
                     },
                     digits: elem.digit_count,
                     timestep: elem.step_seconds,
                     ctime: 0, // Will be filled in later by storage::new_totp_record();
                     notes: t!("vault.notes", locales::LANG).to_string(),
-                    is_hotp: false,
+                    is_hotp: elem.hotp,
                 };
                 entries.push(Box::new(totp));
             }
@@ -358,10 +360,12 @@ fn handle_backup(
                         TotpAlgorithm::HmacSha1 => backup::HashAlgorithms::SHA1,
                         TotpAlgorithm::HmacSha256 => backup::HashAlgorithms::SHA256,
                         TotpAlgorithm::HmacSha512 => backup::HashAlgorithms::SHA512,
+                        TotpAlgorithm::Steam => backup::HashAlgorithms::Steam,
                         _ => panic!("invalid algorithm"),
                     },
                     name: raw_code.name,
                     hotp: raw_code.is_hotp,
+                    issuer: raw_code.issuer,
                 });
             }
 
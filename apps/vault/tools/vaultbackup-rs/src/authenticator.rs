@@ -5,6 +5,10 @@ use protobuf::Message;
 include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
 
 fn set_issuer(t: &mut backup::TotpEntry, issuer: String) {
+    if issuer.is_empty() {
+        return;
+    }
+    t.issuer = issuer.clone();
     let mut issuer = issuer;
     issuer.push_str(":");
     if !t.name.starts_with(&issuer) {
@@ -13,6 +17,9 @@ fn set_issuer(t: &mut backup::TotpEntry, issuer: String) {
     }
 }
 
+/// Parses an `otpauth://` URI of type `totp`, `hotp` or `steam`. `digits`, `period`, `algorithm`,
+/// `issuer` and (for HOTP) `counter` are all carried over; anything not given takes the value
+/// Google Authenticator would assume.
 pub fn otpauth_to_entry(uri: &url::Url) -> Result<backup::TotpEntry, anyhow::Error> {
     let mut t = backup::TotpEntry::default();
 
@@ -20,6 +27,20 @@ pub fn otpauth_to_entry(uri: &url::Url) -> Result<backup::TotpEntry, anyhow::Err
     t.digit_count = 6;
     t.step_seconds = 30;
     t.name = uri.path()[1..].to_string();
+    match uri.host_str() {
+        Some("totp") => (),
+        Some("hotp") => {
+            t.hotp = true;
+            // HOTP records keep their count in step_seconds
+            t.step_seconds = 0;
+        }
+        Some("steam") => t.algorithm = backup::HashAlgorithms::Steam,
+        _ => bail!("unsupported OTP type in URI: {}", uri),
+    }
+    // an issuer given as a label prefix counts, unless the issuer parameter says otherwise
+    if let Some((issuer, _)) = t.name.split_once(':') {
+        t.issuer = issuer.to_string();
+    }
 
     for (k, v) in uri.query_pairs() {
         match k.as_ref() {
@@ -30,28 +51,49 @@ pub fn otpauth_to_entry(uri: &url::Url) -> Result<backup::TotpEntry, anyhow::Err
                 set_issuer(&mut t, v.to_string());
             }
             "algorithm" => {
-                t.algorithm = backup::HashAlgorithms::from_str(&v)?;
+                t.algorithm = backup::HashAlgorithms::from_str(&v.to_uppercase())?;
             }
             "digits" => {
                 t.digit_count = v.parse::<u32>()?;
             }
             "period" => {
-                t.step_seconds = v.parse::<u64>()?;
+                if !t.hotp {
+                    t.step_seconds = v.parse::<u64>()?;
+                }
+            }
+            "counter" => {
+                if t.hotp {
+                    t.step_seconds = v.parse::<u64>()?;
+                }
+            }
+            "encoder" if v.eq_ignore_ascii_case("steam") => {
+                t.algorithm = backup::HashAlgorithms::Steam;
             }
             k => {
                 bail!("unexpected parameter {} in URI: {}", k, uri)
             }
         }
     }
+    if let backup::HashAlgorithms::Steam = t.algorithm {
+        t.digit_count = 5;
+    }
 
     Ok(t)
 }
 
 fn migration_payload_to_entry(param: otpauth_migration::migration_payload::OtpParameters) -> Result<backup::TotpEntry, anyhow::Error> {
-    match param.type_.enum_value_or_default() {
-        otpauth_migration::migration_payload::OtpType::OTP_TYPE_TOTP => {
+    let otp_type = param.type_.enum_value_or_default();
+    match otp_type {
+        otpauth_migration::migration_payload::OtpType::OTP_TYPE_TOTP |
+        otpauth_migration::migration_payload::OtpType::OTP_TYPE_HOTP => {
             let mut t = backup::TotpEntry::default();
-            t.step_seconds = 30;
+            if otp_type == otpauth_migration::migration_payload::OtpType::OTP_TYPE_HOTP {
+                // HOTP records keep their count in step_seconds
+                t.hotp = true;
+                t.step_seconds = param.counter;
+            } else {
+                t.step_seconds = 30;
+            }
             match param.digits.enum_value_or_default() {
                 otpauth_migration::migration_payload::DigitCount::DIGIT_COUNT_UNSPECIFIED =>
                     t.digit_count = 6,
@@ -77,8 +119,6 @@ fn migration_payload_to_entry(param: otpauth_migration::migration_payload::OtpPa
             t.shared_secret = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &param.secret);
             Ok(t)
         }
-        otpauth_migration::migration_payload::OtpType::OTP_TYPE_HOTP =>
-            Err(anyhow!("OTP_TYPE_HOTP not supported")),
         otpauth_migration::migration_payload::OtpType::OTP_TYPE_UNSPECIFIED =>
             Err(anyhow!("OTP_TYPE_UNSPECIFIED not supported")),
     }
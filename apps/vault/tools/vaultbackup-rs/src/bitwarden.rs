@@ -46,6 +46,35 @@ impl std::fmt::Display for LoginSanificationError {
 impl std::error::Error for LoginSanificationError {}
 
 impl Login {
+    /// Converts the `totp` field of a login into a vault entry named `name`. Bitwarden stores either
+    /// a full `otpauth://` URI, a `steam://` prefixed secret, or a bare base32 secret that takes
+    /// the usual SHA1, six digit, 30 second defaults.
+    pub fn totp_entry(&self, name: &str) -> Result<Option<backup::TotpEntry>, anyhow::Error> {
+        let totp = match self.totp.as_ref() {
+            Some(totp) => totp.trim(),
+            None => return Ok(None),
+        };
+        let mut t = if totp.starts_with("otpauth://") {
+            crate::authenticator::otpauth_to_entry(&url::Url::parse(totp)?)?
+        } else {
+            let mut t = backup::TotpEntry::default();
+            t.digit_count = 6;
+            t.step_seconds = 30;
+            t.shared_secret = match totp.strip_prefix("steam://") {
+                Some(secret) => {
+                    t.algorithm = backup::HashAlgorithms::Steam;
+                    t.digit_count = 5;
+                    secret.to_string()
+                }
+                None => totp.to_string(),
+            };
+            t
+        };
+        t.name = name.to_string();
+        Ok(Some(t))
+    }
+
+
     pub fn sane(&self) -> Result<(), LoginSanificationError> {
         if self.username.is_none() {
             return Err(LoginSanificationError::BadUsername);
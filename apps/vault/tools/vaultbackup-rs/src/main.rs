@@ -188,20 +188,10 @@ fn main() -> Result<()> {
 
                         passwords.0.push(pw);
 
-                        if login.totp.is_some() {
-                            let totp = login.totp.as_ref().unwrap();
-
-                            let mut t = backup::TotpEntry::default();
-                            t.name = item.name.clone();
-                            t.algorithm = backup::HashAlgorithms::SHA256;
-                            t.digit_count = 6;
-                            t.step_seconds = 30;
-                            t.shared_secret = totp
-                                .strip_prefix("otpauth://totp/")
-                                .unwrap_or(&totp)
-                                .to_string();
-
-                            totps.0.push(t);
+                        match login.totp_entry(&item.name) {
+                            Ok(Some(t)) => totps.0.push(t),
+                            Ok(None) => (),
+                            Err(err) => log::error!("(non-fatal) entry {} has an unusable TOTP: {}", idx, err),
                         }
                     }
 
@@ -225,7 +215,9 @@ fn main() -> Result<()> {
                     for uri in std::io::BufReader::new(f).lines() {
                         let uri = url::Url::parse(&uri?)?;
                         match (uri.scheme(), uri.host_str()) {
-                            ("otpauth", Some("totp")) => {
+                            ("otpauth", Some("totp")) |
+                            ("otpauth", Some("hotp")) |
+                            ("otpauth", Some("steam")) => {
                                 totps.0.push(authenticator::otpauth_to_entry(&uri)?)
                             }
                             ("otpauth-migration", Some("offline")) => {
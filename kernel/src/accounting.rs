@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Running totals of how much CPU time and IPC each process uses. These are
+//! kept outside of `SystemServices` so the tables land in `.bss` rather than
+//! being part of the initialized process table.
+
+use crate::arch::process::MAX_THREAD;
+use xous_kernel::{PID, TID};
+
+#[derive(Copy, Clone)]
+pub struct ThreadUsage {
+    /// Number of times this thread was handed a quantum by the scheduler
+    pub quanta: usize,
+
+    /// Number of times this thread was switched to, for any reason
+    pub context_switches: usize,
+}
+
+#[derive(Copy, Clone)]
+pub struct ProcessUsage {
+    pub threads: [ThreadUsage; MAX_THREAD + 1],
+
+    /// Messages this process sent to any server
+    pub messages_sent: usize,
+
+    /// Messages delivered to any server owned by this process
+    pub messages_received: usize,
+
+    /// Bytes this process has lent out through `Borrow` and `MutableBorrow` messages
+    pub bytes_lent: usize,
+}
+
+impl ProcessUsage {
+    const fn new() -> Self {
+        ProcessUsage {
+            threads: [ThreadUsage {
+                quanta: 0,
+                context_switches: 0,
+            }; MAX_THREAD + 1],
+            messages_sent: 0,
+            messages_received: 0,
+            bytes_lent: 0,
+        }
+    }
+
+    pub fn quanta(&self) -> usize {
+        self.threads
+            .iter()
+            .fold(0, |acc, t| acc.wrapping_add(t.quanta))
+    }
+
+    pub fn context_switches(&self) -> usize {
+        self.threads
+            .iter()
+            .fold(0, |acc, t| acc.wrapping_add(t.context_switches))
+    }
+}
+
+crate::per_process_table!(USAGE: ProcessUsage = ProcessUsage::new());
+
+fn with_thread<F>(pid: PID, tid: TID, f: F)
+where
+    F: FnOnce(&mut ThreadUsage),
+{
+    with_process(pid, |usage| {
+        if let Some(thread) = usage.threads.get_mut(tid) {
+            f(thread)
+        }
+    });
+}
+
+/// Return a copy of the counters for the given process.
+pub fn usage(pid: PID) -> ProcessUsage {
+    with_process(pid, |usage| *usage).unwrap_or(ProcessUsage::new())
+}
+
+/// Clear all counters for a process. Called when a PID is (re)allocated.
+pub fn reset(pid: PID) {
+    with_process(pid, |usage| *usage = ProcessUsage::new());
+}
+
+/// The scheduler gave `pid:tid` a quantum of its own.
+pub fn charge_quantum(pid: PID, tid: TID) {
+    with_thread(pid, tid, |t| t.quanta = t.quanta.wrapping_add(1));
+}
+
+/// `pid:tid` became the running thread.
+pub fn context_switch(pid: PID, tid: TID) {
+    with_thread(pid, tid, |t| {
+        t.context_switches = t.context_switches.wrapping_add(1)
+    });
+}
+
+/// A message went from `sender` to a server owned by `receiver`, lending
+/// `lent` bytes along with it.
+pub fn message(sender: PID, receiver: PID, lent: usize) {
+    with_process(sender, |usage| {
+        usage.messages_sent = usage.messages_sent.wrapping_add(1);
+        usage.bytes_lent = usage.bytes_lent.wrapping_add(lent);
+    });
+    with_process(receiver, |usage| {
+        usage.messages_received = usage.messages_received.wrapping_add(1)
+    });
+}
@@ -3,13 +3,77 @@
 
 pub const PAGE_SIZE: usize = 4096;
 use crate::mem::MemoryManager;
-use xous_kernel::{Error, MemoryFlags, PID};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use xous_kernel::{Error, MemoryFlags, MemoryRange, PID};
 
 pub const DEFAULT_HEAP_BASE: usize = 0x2000_0000;
 pub const DEFAULT_MESSAGE_BASE: usize = 0x4000_0000;
 pub const DEFAULT_BASE: usize = 0x6000_0000;
 
-pub const USER_AREA_END: usize = 0xff00_0000;
+/// Hosted processes hand the kernel addresses from their own heap, which may
+/// be anywhere in the host's address space.
+pub const USER_AREA_END: usize = usize::MAX & !(PAGE_SIZE - 1);
+
+/// Hosted processes allocate their own memory, so the "physical" pages backing
+/// anonymous mappings come from this region. It only exists so that each page
+/// has a unique address to hand back from `virt_to_phys()`.
+pub const HOSTED_RAM_BASE: usize = 0x4000_0000;
+pub const HOSTED_RAM_SIZE: usize = 0x4000_0000;
+
+/// A page that the kernel has mapped into a process.
+#[derive(Copy, Clone, Debug)]
+struct Page {
+    phys: usize,
+    flags: MemoryFlags,
+
+    /// The page has been lent to another process
+    shared: bool,
+}
+
+// Every mapping in the system, keyed by `(pid, virtual page)`.
+std::thread_local!(static PAGES: RefCell<BTreeMap<(usize, usize), Page>> = RefCell::new(BTreeMap::new()));
+
+// Memory attached to a syscall is copied into the kernel, so keep track of
+// where it lives in the calling process.
+std::thread_local!(static CALLER_MEMORY: Cell<Option<MemoryRange>> = Cell::new(None));
+
+// Kernel copies of lent memory, and the address each was lent from.
+std::thread_local!(static LENT_COPIES: RefCell<BTreeMap<usize, usize>> = RefCell::new(BTreeMap::new()));
+
+/// Note where the memory attached to the syscall about to be handled lives in
+/// the calling process.
+pub fn set_caller_memory(range: Option<MemoryRange>) {
+    CALLER_MEMORY.with(|caller_memory| caller_memory.set(range));
+}
+
+/// Translate the kernel's copy of the memory attached to the current syscall
+/// back into the caller's address for it.
+pub fn caller_address(copy: usize) -> usize {
+    CALLER_MEMORY
+        .with(|caller_memory| caller_memory.get())
+        .map_or(copy, |range| range.as_ptr() as usize)
+}
+
+/// Remember that `copy` holds memory lent from `origin`, so that it can be
+/// found again once the borrower returns it.
+pub fn remember_lend(copy: usize, origin: usize) {
+    LENT_COPIES.with(|lent| lent.borrow_mut().insert(copy, origin));
+}
+
+/// Look up, and forget, the address `copy` was lent from.
+pub fn forget_lend(copy: usize) -> Option<usize> {
+    LENT_COPIES.with(|lent| lent.borrow_mut().remove(&copy))
+}
+
+fn page_key(pid: PID, virt: usize) -> (usize, usize) {
+    (pid.get() as usize, virt & !(PAGE_SIZE - 1))
+}
+
+fn current_page(virt: usize) -> Option<Page> {
+    let key = page_key(crate::arch::process::current_pid(), virt);
+    PAGES.with(|pages| pages.borrow().get(&key).copied())
+}
 
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
 pub struct MemoryMapping {
@@ -19,15 +83,17 @@ pub struct MemoryMapping {
 pub const DEFAULT_MEMORY_MAPPING: MemoryMapping = MemoryMapping { pid: 0 };
 
 impl MemoryMapping {
-    /// Get the currently active memory mapping.  Note that the actual root pages
-    /// may be found at virtual address `PAGE_TABLE_ROOT_OFFSET`.
+    /// Get the currently active memory mapping. In hosted mode this is
+    /// simply the mapping of the current process.
     pub fn current() -> MemoryMapping {
-        MemoryMapping { pid: 0 }
+        MemoryMapping {
+            pid: crate::arch::process::current_pid().get() as usize,
+        }
     }
 
     /// Get the "PID" (actually, ASID) from the current mapping
     pub fn get_pid(self) -> PID {
-        unimplemented!()
+        PID::new(self.pid as u8).expect("memory mapping was never allocated")
     }
 
     /// Set this mapping as the systemwide mapping.
@@ -40,87 +106,241 @@ impl MemoryMapping {
         Ok(())
     }
 
-    /// Does nothing in hosted mode.
-    pub unsafe fn allocate(&mut self, _pid: PID) -> Result<(), xous_kernel::Error> {
+    /// Give `pid` a fresh address space, forgetting anything a previous
+    /// process with the same PID left mapped.
+    pub unsafe fn allocate(&mut self, pid: PID) -> Result<(), xous_kernel::Error> {
+        self.pid = pid.get() as usize;
+        PAGES.with(|pages| {
+            pages
+                .borrow_mut()
+                .retain(|&(owner, _), _| owner != pid.get() as usize)
+        });
         Ok(())
     }
 
+    /// Reserve `addr` in this process. Hosted processes touch their memory
+    /// without the kernel noticing, so the page is backed right away rather
+    /// than on first access.
     pub fn reserve_address(
         &mut self,
-        _mm: &mut MemoryManager,
-        _addr: usize,
-        _flags: MemoryFlags,
+        mm: &mut MemoryManager,
+        addr: usize,
+        flags: MemoryFlags,
     ) -> Result<(), Error> {
+        let pid = self.get_pid();
+        let key = page_key(pid, addr);
+        if PAGES.with(|pages| pages.borrow().contains_key(&key)) {
+            return Ok(());
+        }
+        let phys = mm.alloc_page(pid)?;
+        PAGES.with(|pages| {
+            pages.borrow_mut().insert(
+                key,
+                Page {
+                    phys,
+                    flags: flags & (MemoryFlags::R | MemoryFlags::W | MemoryFlags::X),
+                    shared: false,
+                },
+            )
+        });
         Ok(())
     }
 }
 
 /// Determine whether a virtual address has been mapped
-pub fn address_available(_virt: usize) -> bool {
-    true
+pub fn address_available(virt: usize) -> bool {
+    current_page(virt).is_none()
+}
+
+/// Pages of `pid` that the kernel has mapped and that overlap `virt..virt + len`.
+/// Memory the process allocated by itself, such as its heap or stack, is not
+/// tracked and will not be listed.
+pub fn mapped_pages(pid: PID, virt: usize, len: usize) -> Vec<usize> {
+    let start = page_key(pid, virt);
+    let end = page_key(pid, virt + len + PAGE_SIZE - 1);
+    PAGES.with(|pages| {
+        pages
+            .borrow()
+            .range(start..end)
+            .map(|(&(_, page), _)| page)
+            .collect()
+    })
 }
 
 pub fn map_page_inner(
     _mm: &mut MemoryManager,
-    _pid: PID,
-    _phys: usize,
-    _virt: usize,
-    _req_flags: MemoryFlags,
+    pid: PID,
+    phys: usize,
+    virt: usize,
+    req_flags: MemoryFlags,
     _map_user: bool,
 ) -> Result<(), xous_kernel::Error> {
-    unimplemented!()
+    PAGES.with(|pages| {
+        let mut pages = pages.borrow_mut();
+        let key = page_key(pid, virt);
+        if pages.contains_key(&key) {
+            return Err(Error::MemoryInUse);
+        }
+        pages.insert(
+            key,
+            Page {
+                phys,
+                flags: req_flags & (MemoryFlags::R | MemoryFlags::W | MemoryFlags::X),
+                shared: false,
+            },
+        );
+        Ok(())
+    })
 }
 
+/// Move a page out of the current process. The receiving process gets a copy
+/// of the data over its connection, so the page is mapped into `dest_pid` at
+/// `dest_addr` purely for bookkeeping.
 pub fn move_page_inner(
     _mm: &mut MemoryManager,
     _src_space: &MemoryMapping,
-    _src_addr: *mut u8,
-    _dest_pid: PID,
+    src_addr: *mut u8,
+    dest_pid: PID,
     _dest_space: &MemoryMapping,
-    _dest_addr: *mut u8,
+    dest_addr: *mut u8,
 ) -> Result<(), Error> {
-    unimplemented!()
+    let src_key = page_key(crate::arch::process::current_pid(), src_addr as usize);
+    PAGES.with(|pages| {
+        let mut pages = pages.borrow_mut();
+        let page = match pages.get(&src_key) {
+            Some(page) if !page.shared => *page,
+            _ => return Err(Error::BadAddress),
+        };
+        let dest_key = page_key(dest_pid, dest_addr as usize);
+        if pages.contains_key(&dest_key) {
+            return Err(Error::MemoryInUse);
+        }
+        pages.remove(&src_key);
+        pages.insert(dest_key, page);
+        Ok(())
+    })
+}
+
+/// Determine if a page has been lent.
+pub fn page_is_lent(src_addr: *mut u8) -> bool {
+    current_page(src_addr as usize).map_or(false, |page| page.shared)
 }
 
+/// Mark the given page in the current process as being lent. The borrower
+/// receives a copy of the data rather than the page itself, so nothing is
+/// mapped into `dest_pid`.
+///
+/// # Errors
+///
+/// * **ShareViolation**: The page isn't mapped, or is already lent
 pub fn lend_page_inner(
     _mm: &mut MemoryManager,
     _src_space: &MemoryMapping,
-    _src_addr: *mut u8,
+    src_addr: *mut u8,
     _dest_pid: PID,
     _dest_space: &MemoryMapping,
     _dest_addr: *mut u8,
     _mutable: bool,
 ) -> Result<usize, Error> {
-    unimplemented!()
+    let key = page_key(crate::arch::process::current_pid(), src_addr as usize);
+    PAGES.with(|pages| match pages.borrow_mut().get_mut(&key) {
+        Some(page) if !page.shared => {
+            page.shared = true;
+            Ok(page.phys)
+        }
+        _ => Err(Error::ShareViolation),
+    })
 }
 
+/// Return a page that `dest_pid` lent out, making it usable by that process
+/// again.
+///
+/// # Errors
+///
+/// * **ShareViolation**: The page was not lent by `dest_pid`
 pub fn return_page_inner(
     _mm: &mut MemoryManager,
     _src_space: &MemoryMapping,
     _src_addr: *mut u8,
-    _dest_pid: PID,
+    dest_pid: PID,
     _dest_space: &MemoryMapping,
-    _dest_addr: *mut u8,
+    dest_addr: *mut u8,
 ) -> Result<usize, Error> {
-    unimplemented!()
+    let key = page_key(dest_pid, dest_addr as usize);
+    PAGES.with(|pages| match pages.borrow_mut().get_mut(&key) {
+        Some(page) if page.shared => {
+            page.shared = false;
+            Ok(page.phys)
+        }
+        _ => Err(Error::ShareViolation),
+    })
 }
 
+/// Ummap the given page from the current process.
+///
+/// # Returns
+///
+/// The physical address for the page that was just unmapped. Pages the
+/// kernel never mapped are passed through unchanged.
 pub fn unmap_page_inner(_mm: &mut MemoryManager, virt: usize) -> Result<usize, Error> {
-    Ok(virt)
+    let key = page_key(crate::arch::process::current_pid(), virt);
+    Ok(PAGES
+        .with(|pages| pages.borrow_mut().remove(&key))
+        .map_or(virt, |page| page.phys))
 }
 
+/// Pages are always owned by the user in hosted mode.
 pub fn hand_page_to_user(_virt: *mut u8) -> Result<(), Error> {
-    unimplemented!()
+    Ok(())
 }
 
+/// Translate an address in the current process. Addresses the kernel never
+/// mapped, such as the process' own heap, are passed through unchanged.
+///
+/// # Errors
+///
+/// * **ShareViolation**: The page is currently lent to another process
 pub fn virt_to_phys(virt: usize) -> Result<usize, Error> {
-    Ok(virt)
+    match current_page(virt) {
+        Some(page) if page.shared => Err(Error::ShareViolation),
+        Some(page) => Ok(page.phys),
+        None => Ok(virt),
+    }
 }
 
-pub fn page_flags(_virt: usize) -> Option<MemoryFlags> {
-    None
+/// Get the `MemoryFlags` for the requested virtual address.
+///
+/// # Returns
+///
+/// * **None**: The page is not mapped or is shared
+/// * **Some(MemoryFlags)**: The flags the page was mapped with
+pub fn page_flags(virt: usize) -> Option<MemoryFlags> {
+    current_page(virt)
+        .filter(|page| !page.shared && !page.flags.is_empty())
+        .map(|page| page.flags)
 }
 
-pub fn update_page_flags(_virt: usize, _flags: MemoryFlags) -> Result<(), xous_kernel::Error> {
-    Ok(())
-}
\ No newline at end of file
+pub fn update_page_flags(virt: usize, flags: MemoryFlags) -> Result<(), xous_kernel::Error> {
+    let valid = MemoryFlags::R | MemoryFlags::W | MemoryFlags::X;
+
+    // The resulting flags must actually be valid
+    if (flags & valid).is_empty() {
+        return Err(Error::MemoryInUse);
+    }
+
+    let key = page_key(crate::arch::process::current_pid(), virt);
+    PAGES.with(|pages| {
+        let mut pages = pages.borrow_mut();
+        let page = pages.get_mut(&key).ok_or(Error::BadAddress)?;
+        if page.shared {
+            return Err(Error::ShareViolation);
+        }
+
+        // Flags may only be stripped, never added back
+        if !(!page.flags & flags & valid).is_empty() {
+            return Err(Error::ShareViolation);
+        }
+        page.flags = page.flags & flags & valid;
+        Ok(())
+    })
+}
@@ -19,10 +19,11 @@ use crate::services::SystemServices;
 
 use crossbeam_channel::{unbounded, Receiver, RecvError, RecvTimeoutError, Sender};
 
-use xous_kernel::{ProcessInit, ProcessKey, Result, SysCall, ThreadInit, PID, TID};
+use xous_kernel::{MemoryRange, ProcessInit, ProcessKey, Result, SysCall, ThreadInit, PID, TID};
 
 enum ThreadMessage {
-    SysCall(PID, TID, SysCall),
+    /// A syscall, along with where any memory attached to it lives in the caller
+    SysCall(PID, TID, SysCall, Option<MemoryRange>),
     NewConnection(TcpStream, ProcessKey),
 }
 
@@ -147,7 +148,8 @@ fn handle_connection(
                 }
             };
 
-            if let Some(mem) = call.memory() {
+            let caller_memory = call.memory();
+            if let Some(mem) = caller_memory {
                 let mut data = vec![0u8; mem.len()];
                 if conn.read_exact(&mut data).is_err() {
                     return;
@@ -173,7 +175,7 @@ fn handle_connection(
             }
 
             sender
-                .send(ThreadMessage::SysCall(pid, thread_id, call))
+                .send(ThreadMessage::SysCall(pid, thread_id, call, caller_memory))
                 .unwrap();
         }
     }
@@ -209,6 +211,7 @@ fn handle_connection(
         pid,
         1,
         xous_kernel::SysCall::TerminateProcess(0),
+        None,
     ))
     .unwrap();
 }
@@ -463,10 +466,11 @@ pub fn idle() -> bool {
                     .unwrap();
                 }
             }
-            ThreadMessage::SysCall(pid, thread_id, call) => {
+            ThreadMessage::SysCall(pid, thread_id, call, caller_memory) => {
                 // let measurement_start = std::time::Instant::now();
                 // println!("KERNEL({}): Received syscall {:?}", pid, call);
                 crate::arch::process::set_current_pid(pid);
+                crate::arch::mem::set_caller_memory(caller_memory);
                 // println!("KERNEL({}): Now running as the new process", pid);
 
                 // If the call being made is to terminate the current process, we need to know
@@ -363,7 +363,13 @@ pub extern "C" fn trap_handler(
 
                 // Note that we report the current `epc` here without manipulation --
                 // the debugger will unpatch the opcode and re-issue the instruction.
-                crate::debug::gdb::report_stop(pid, tid, epc);
+                if !crate::debug::gdb::report_stop(pid, tid, epc) {
+                    // The thread was only stepping off a breakpoint, and the instruction
+                    // at `epc` has been restored. Let it carry on.
+                    ArchProcess::with_current_mut(|process| {
+                        crate::arch::syscall::resume(current_pid().get() == 1, process.current_thread())
+                    })
+                }
 
                 // Pause for debugging, which switches to the parent process
                 SystemServices::with_mut(|ss| {
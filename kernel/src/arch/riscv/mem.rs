@@ -560,6 +560,62 @@ pub fn poke_memory<T>(addr: *mut T, val: T) -> Result<(), xous_kernel::Error> {
     Ok(())
 }
 
+/// Give the current process its own copy, in RAM, of the page containing `addr`
+/// if that page is currently backed by something else, such as XIP flash. The
+/// copy is mapped in place of the original with the same permissions, so the
+/// process can't tell the difference, but it can now be patched with `poke_memory()`.
+///
+/// # Returns
+///
+/// `true` if a copy was made, or `false` if the page was already in RAM.
+///
+/// # Errors
+///
+/// * BadAddress - The address is not mapped
+/// * OutOfMemory - No RAM was available for the copy
+#[cfg(feature = "gdb-stub")]
+pub fn privatize_page(addr: usize) -> Result<bool, xous_kernel::Error> {
+    let virt = addr & !0xfff;
+    let entry = pagetable_entry(virt)?;
+    let current_entry = unsafe { entry.read_volatile() };
+    if current_entry & MMUFlags::VALID.bits() == 0 {
+        return Err(xous_kernel::Error::BadAddress);
+    }
+    let old_phys = (current_entry >> 10) << 12;
+    if MemoryManager::with(|mm| mm.is_main_memory(old_phys as *mut u8)) {
+        return Ok(false);
+    }
+
+    MemoryManager::with_mut(|mm| {
+        let pid = crate::arch::process::current_pid();
+        let new_phys = mm.alloc_page(pid)?;
+
+        // Borrow a spare address to fill in the new page from, since the
+        // original address still refers to the old one.
+        let scratch = mm.find_virtual_address(
+            core::ptr::null_mut(),
+            PAGE_SIZE,
+            xous_kernel::MemoryType::Default,
+        )? as usize;
+        map_page_inner(mm, pid, new_phys, scratch, MemoryFlags::R | MemoryFlags::W, false)?;
+        unsafe {
+            sstatus::set_sum();
+            core::ptr::copy_nonoverlapping(virt as *const u32, scratch as *mut u32, PAGE_SIZE / 4);
+            sstatus::clear_sum();
+        }
+        unmap_page_inner(mm, scratch)?;
+
+        // Point the original address at the copy, keeping its permissions.
+        let ppn1 = (new_phys >> 22) & ((1 << 12) - 1);
+        let ppn0 = (new_phys >> 12) & ((1 << 10) - 1);
+        unsafe {
+            entry.write_volatile((ppn1 << 20) | (ppn0 << 10) | (current_entry & 0x3ff));
+            flush_mmu();
+        }
+        Ok(true)
+    })
+}
+
 /// Map the given page to the specified process table.  If necessary,
 /// allocate a new page.
 ///
@@ -44,7 +44,13 @@ impl ProcessPid for XousTarget {
     fn pid(&self) -> Option<xous_kernel::PID> {
         self.pid
     }
+    /// Stop debugging the current process, removing any breakpoints that are still
+    /// patched into it.
     fn take_pid(&mut self) -> Option<xous_kernel::PID> {
+        if self.pid.is_some() {
+            self.unpatch_stepi(Tid::new(1).unwrap()).ok();
+            self.clear_sw_breakpoints();
+        }
         self.pid.take()
     }
 }
@@ -260,31 +266,47 @@ fn process_character(byte: u8) {
     unsafe { GDB_STATE = Some(XousDebugState { target, server }) };
 }
 
-pub fn report_stop(_pid: xous_kernel::PID, tid: xous_kernel::TID, _pc: usize) {
+/// Report that a thread has hit a breakpoint.
+///
+/// Returns `false` if the stop was only the thread stepping off a breakpoint while
+/// continuing. In that case nothing is reported to GDB, the original instruction is
+/// back in place, and the thread should carry on from `pc`.
+pub fn report_stop(_pid: xous_kernel::PID, tid: xous_kernel::TID, pc: usize) -> bool {
     let Some(XousDebugState {
         mut target,
         server: gdb,
     }) = (unsafe { GDB_STATE.take() }) else {
         println!("No GDB!");
-        return;
+        return true;
     };
 
+    let stepped = target.is_stepping();
     target.unpatch_stepi(Tid::new(tid).unwrap()).ok();
+    match target.finish_step_over() {
+        Ok(true) => {
+            unsafe { GDB_STATE = Some(XousDebugState { target, server: gdb }) };
+            return false;
+        }
+        Ok(false) => {}
+        Err(e) => println!("Unable to restore breakpoint: {}", e),
+    }
 
     let GdbStubStateMachine::Running(inner) = gdb else {
         println!("GDB state machine was in an invalid state");
-        return;
+        return true;
     };
 
-    let Ok(new_gdb) = inner.report_stop(
-        &mut target,
+    let reason = if !stepped && target.is_sw_breakpoint(pc as u32) {
+        MultiThreadStopReason::SwBreak(Tid::new(tid).unwrap())
+    } else {
         MultiThreadStopReason::SignalWithThread {
             signal: Signal::EXC_BREAKPOINT,
             tid: Tid::new(tid).unwrap(),
         }
-    ) else {
+    };
+    let Ok(new_gdb) = inner.report_stop(&mut target, reason) else {
             println!("Unable to report stop");
-            return;
+            return true;
     };
 
     unsafe {
@@ -293,6 +315,20 @@ pub fn report_stop(_pid: xous_kernel::PID, tid: xous_kernel::TID, _pc: usize) {
             server: new_gdb,
         })
     };
+    true
+}
+
+/// Called before a process's memory is released. If it's the process being debugged,
+/// the instructions its breakpoints replaced are put back and the breakpoints are
+/// forgotten.
+pub fn report_process_exit(pid: xous_kernel::PID) {
+    let Some(state) = (unsafe { GDB_STATE.as_mut() }) else {
+        return;
+    };
+    if state.target.pid() == Some(pid) {
+        state.target.unpatch_stepi(Tid::new(1).unwrap()).ok();
+        state.target.clear_sw_breakpoints();
+    }
 }
 
 pub fn report_terminated(pid: xous_kernel::PID) {
@@ -4,21 +4,19 @@ use gdbstub::target::ext::breakpoints::{Breakpoints, SwBreakpoint, SwBreakpointO
 use super::XousTarget;
 
 impl Breakpoints for XousTarget {
-    /// Indicate that we do not support software breakpoints. If we ever decide
-    /// to add breakpoint support, we will need to implement this ourselves. However,
-    /// by reporting `None` here we allow GDB to manage breakpoints for us.
+    /// Software breakpoints are managed by the kernel rather than by GDB, because GDB
+    /// can't write to text that is read-only or executed in place.
     fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
-        // Some(self)
-        None
+        Some(self)
     }
 }
 
 impl SwBreakpoint for XousTarget {
-    fn add_sw_breakpoint(&mut self, _addr: u32, _kind: usize) -> TargetResult<bool, Self> {
-        Ok(false)
+    fn add_sw_breakpoint(&mut self, addr: u32, kind: usize) -> TargetResult<bool, Self> {
+        Ok(self.insert_sw_breakpoint(addr, kind)?)
     }
 
-    fn remove_sw_breakpoint(&mut self, _addr: u32, _kind: usize) -> TargetResult<bool, Self> {
-        Ok(false)
+    fn remove_sw_breakpoint(&mut self, addr: u32, _kind: usize) -> TargetResult<bool, Self> {
+        Ok(self.remove_sw_breakpoint_at(addr)?)
     }
 }
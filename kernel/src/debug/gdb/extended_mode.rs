@@ -1,15 +1,14 @@
-use gdbstub::common::{Pid, Tid};
+use gdbstub::common::Pid;
 use gdbstub::target;
 use gdbstub::target::ext::extended_mode::{AttachKind, ExtendedMode, ShouldTerminate};
 use gdbstub::target::{TargetError, TargetResult};
 
-use super::XousTarget;
+use super::{ProcessPid, XousTarget};
 use core::convert::TryInto;
 
 impl ExtendedMode for XousTarget {
     fn attach(&mut self, new_pid: Pid) -> TargetResult<(), Self> {
-        if let Some(previous_pid) = self.pid.take() {
-            self.unpatch_stepi(Tid::new(1).unwrap()).ok();
+        if let Some(previous_pid) = self.take_pid() {
             crate::services::SystemServices::with_mut(|system_services| {
                 system_services
                     .resume_process_from_debug(previous_pid)
@@ -1,7 +1,7 @@
 use gdbstub::target;
 use gdbstub::target::ext::monitor_cmd::MonitorCmd;
 
-use super::XousTarget;
+use super::{ProcessPid, XousTarget};
 
 impl MonitorCmd for XousTarget {
     fn handle_monitor_cmd(
@@ -19,7 +19,7 @@ impl MonitorCmd for XousTarget {
                 // Parse the new PID. If it isn't a valid string, then this will be None
                 let new_pid =
                     xous_kernel::PID::new(u8::from_str_radix(pid_str, 10).unwrap_or_default());
-                if let Some(previous_pid) = self.pid {
+                if let Some(previous_pid) = self.take_pid() {
                     crate::services::SystemServices::with_mut(|system_services| {
                         system_services
                             .resume_process_from_debug(previous_pid)
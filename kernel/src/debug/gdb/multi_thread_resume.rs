@@ -1,10 +1,22 @@
 use gdbstub::common::{Signal, Tid};
-use gdbstub::target::ext::base::multithread::{MultiThreadResume, MultiThreadSingleStepOps};
+use gdbstub::target::ext::base::multithread::{MultiThreadBase, MultiThreadResume, MultiThreadSingleStepOps};
 
 use super::XousTarget;
 
 impl MultiThreadResume for XousTarget {
     fn resume(&mut self) -> Result<(), Self::Error> {
+        // A thread that stopped on a breakpoint would hit it again straight away, so
+        // step it off first. Only one thread can be stepped at a time, so this is
+        // skipped if GDB has already asked for a step.
+        if self.pid.is_some() && !self.is_stepping() {
+            let mut threads = 0usize;
+            self.list_active_threads(&mut |tid| threads |= 1 << tid.get())?;
+            for tid in 1..usize::BITS as usize {
+                if threads & (1 << tid) != 0 && self.step_off_sw_breakpoint(Tid::new(tid).unwrap(), true)? {
+                    break;
+                }
+            }
+        }
         if let Some(pid) = self.pid {
             crate::services::SystemServices::with_mut(|system_services| {
                 system_services.resume_process_from_debug(pid).unwrap()
@@ -15,7 +15,10 @@ impl MultiThreadSingleStep for XousTarget {
         //     "Performing a single step -- Setting resume action {:?} for tid {:?}",
         //     _signal, tid
         // );
-        self.patch_stepi(tid)?;
+        // Stepping off a breakpoint patches the step itself
+        if !self.step_off_sw_breakpoint(tid, false)? {
+            self.patch_stepi(tid)?;
+        }
         Ok(())
     }
 }
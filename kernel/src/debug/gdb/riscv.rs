@@ -10,6 +10,7 @@ use gdbstub::target::ext::base::single_register_access::SingleRegisterAccess;
 use gdbstub::target::Target;
 use gdbstub_arch::riscv::reg::id::RiscvRegId;
 
+#[derive(Clone, Copy, PartialEq)]
 enum Opcode {
     Opcode16(u16),
     Opcode32(u32),
@@ -24,6 +25,7 @@ impl core::fmt::LowerHex for Opcode {
     }
 }
 
+#[derive(Clone, Copy)]
 pub(crate) struct PatchedInstruction {
     /// The address that was patched
     pc: u32,
@@ -32,15 +34,46 @@ pub(crate) struct PatchedInstruction {
     previous: Opcode,
 }
 
+/// A software breakpoint that has been taken out so that a thread sitting on it
+/// can execute the instruction it replaced.
+#[derive(Clone, Copy)]
+struct StepOver {
+    /// The address of the breakpoint, which goes back in once the step is done
+    pc: u32,
+
+    /// `true` if the thread is continuing, in which case the step isn't reported to GDB
+    resume: bool,
+}
+
+/// How many software breakpoints may be set in the process being debugged at once
+const MAX_SW_BREAKPOINTS: usize = 32;
+
+/// `c.ebreak`, which replaces 16-bit instructions
+const C_EBREAK: u16 = 0x9002;
+
+/// `ebreak`, which replaces 32-bit instructions
+const EBREAK: u32 = 0x0010_0073;
+
 pub(crate) struct XousTargetInner {
     /// When doing a `stepi` we patch the instruction with an illegal instruction
     /// and store the previous value here.
     step_patch: Option<PatchedInstruction>,
+
+    /// Software breakpoints patched into the process being debugged, along with
+    /// the instructions they replaced.
+    sw_breakpoints: [Option<PatchedInstruction>; MAX_SW_BREAKPOINTS],
+
+    /// Set while a breakpoint is lifted to step a thread off of it.
+    step_over: Option<StepOver>,
 }
 
 impl Default for XousTargetInner {
     fn default() -> Self {
-        XousTargetInner { step_patch: None }
+        XousTargetInner {
+            step_patch: None,
+            sw_breakpoints: [None; MAX_SW_BREAKPOINTS],
+            step_over: None,
+        }
     }
 }
 
@@ -105,10 +138,19 @@ impl XousTarget {
         };
 
         let (existing, new_opcode) = match existing_opcode_type {
-            OpcodeType::Rv16 => (Opcode::Opcode16(existing as u16), Opcode::Opcode16(0x9002)), // c.ebreak
-            OpcodeType::Rv32 => (Opcode::Opcode32(existing), Opcode::Opcode32(0x0010_0073)), // c.ebreak c.ebreak
+            OpcodeType::Rv16 => (Opcode::Opcode16(existing as u16), Opcode::Opcode16(C_EBREAK)),
+            OpcodeType::Rv32 => (Opcode::Opcode32(existing), Opcode::Opcode32(EBREAK)),
         };
 
+        // XIP text can't be written to, so step through a private copy of it
+        self.privatize_text(
+            new_pc,
+            match existing_opcode_type {
+                OpcodeType::Rv16 => 2,
+                OpcodeType::Rv32 => 4,
+            },
+        )?;
+
         match new_opcode {
             Opcode::Opcode16(val) => self
                 .write_addrs(new_pc, &val.to_le_bytes(), tid)
@@ -360,3 +402,194 @@ impl XousTarget {
         }
     }
 }
+
+/// Software breakpoints. GDB would otherwise write the `ebreak` itself, which fails on
+/// text that is read-only or executed in place from flash, so breakpoints are patched
+/// in through the process's page tables instead, onto a private copy of the page if
+/// need be.
+impl XousTarget {
+    /// Replace the instruction at `addr` with an `ebreak`. `kind` is the size of the
+    /// instruction as reported by GDB: 2 for compressed instructions, 4 otherwise.
+    ///
+    /// Returns `false` if the breakpoint could not be set.
+    pub fn insert_sw_breakpoint(
+        &mut self,
+        addr: u32,
+        kind: usize,
+    ) -> Result<bool, <XousTarget as Target>::Error> {
+        if self.pid.is_none() || (kind != 2 && kind != 4) {
+            return Ok(false);
+        }
+        if self.inner.sw_breakpoints.iter().flatten().any(|bp| bp.pc == addr) {
+            return Ok(true);
+        }
+        let Some(slot) = self.inner.sw_breakpoints.iter().position(|bp| bp.is_none()) else {
+            println!("All {} breakpoints are in use", MAX_SW_BREAKPOINTS);
+            return Ok(false);
+        };
+
+        self.privatize_text(addr, kind)?;
+        let previous = self.read_opcode(addr, kind)?;
+        let ebreak = ebreak_for(previous);
+        self.write_opcode(addr, ebreak)?;
+
+        // Writes to memory that can't be patched are silently dropped, so check.
+        if self.read_opcode(addr, kind)? != ebreak {
+            println!("Unable to patch breakpoint into {:08x}", addr);
+            return Ok(false);
+        }
+        self.inner.sw_breakpoints[slot] = Some(PatchedInstruction { pc: addr, previous });
+        Ok(true)
+    }
+
+    /// Put back the instruction that the breakpoint at `addr` replaced.
+    ///
+    /// Returns `false` if there is no breakpoint at that address.
+    pub fn remove_sw_breakpoint_at(&mut self, addr: u32) -> Result<bool, <XousTarget as Target>::Error> {
+        let Some(slot) = self
+            .inner
+            .sw_breakpoints
+            .iter()
+            .position(|bp| bp.map(|bp| bp.pc == addr).unwrap_or(false)) else {
+            return Ok(false);
+        };
+        let bp = self.inner.sw_breakpoints[slot].take().unwrap();
+        self.write_opcode(bp.pc, bp.previous)?;
+        Ok(true)
+    }
+
+    /// Remove every software breakpoint from the process being debugged. This is done
+    /// before detaching from a process, and before its memory is released.
+    pub fn clear_sw_breakpoints(&mut self) {
+        self.inner.step_over = None;
+        for slot in 0..MAX_SW_BREAKPOINTS {
+            if let Some(bp) = self.inner.sw_breakpoints[slot].take() {
+                self.write_opcode(bp.pc, bp.previous).ok();
+            }
+        }
+    }
+
+    /// Returns `true` if a software breakpoint is set at `pc`.
+    pub fn is_sw_breakpoint(&self, pc: u32) -> bool {
+        self.inner.sw_breakpoints.iter().flatten().any(|bp| bp.pc == pc)
+    }
+
+    /// Returns `true` if a `stepi` is in progress.
+    pub fn is_stepping(&self) -> bool {
+        self.inner.step_patch.is_some()
+    }
+
+    /// If `tid` is stopped on a software breakpoint, lift the breakpoint and arrange
+    /// for the thread to stop again after one instruction, at which point
+    /// `finish_step_over()` puts the breakpoint back. Otherwise, do nothing.
+    ///
+    /// `resume` indicates that the thread is continuing rather than single-stepping,
+    /// so the intermediate stop shouldn't be reported to GDB.
+    ///
+    /// Returns `true` if the thread was on a breakpoint.
+    pub fn step_off_sw_breakpoint(
+        &mut self,
+        tid: Tid,
+        resume: bool,
+    ) -> Result<bool, <XousTarget as Target>::Error> {
+        let mut pc = [0u8; core::mem::size_of::<u32>()];
+        self.read_register(tid, RiscvRegId::Pc, &mut pc)
+            .or(Err("unable to read register"))?;
+        let pc = u32::from_le_bytes(pc);
+
+        let Some(bp) = self.inner.sw_breakpoints.iter().flatten().find(|bp| bp.pc == pc).copied() else {
+            return Ok(false);
+        };
+        self.write_opcode(bp.pc, bp.previous)?;
+        self.patch_stepi(tid)?;
+        self.inner.step_over = Some(StepOver { pc, resume });
+        Ok(true)
+    }
+
+    /// Called when a thread stops. If a breakpoint was lifted by `step_off_sw_breakpoint()`,
+    /// put it back.
+    ///
+    /// Returns `true` if the thread was only stepping off a breakpoint on its way to
+    /// continuing, and should carry on without stopping.
+    pub fn finish_step_over(&mut self) -> Result<bool, <XousTarget as Target>::Error> {
+        let Some(step_over) = self.inner.step_over.take() else {
+            return Ok(false);
+        };
+        // The breakpoint may have been removed in the meantime.
+        if let Some(bp) = self.inner.sw_breakpoints.iter().flatten().find(|bp| bp.pc == step_over.pc).copied() {
+            self.write_opcode(bp.pc, ebreak_for(bp.previous))?;
+        }
+        Ok(step_over.resume)
+    }
+
+    /// Make sure that the text at `addr` can be patched, by giving the process a
+    /// private RAM copy of any page it touches that isn't already in RAM.
+    fn privatize_text(&self, addr: u32, len: usize) -> Result<(), <XousTarget as Target>::Error> {
+        let Some(debugging_pid) = self.pid else {
+            return Err("no process is being debugged");
+        };
+        crate::services::SystemServices::with(|system_services| {
+            let current_pid = system_services.current_pid();
+            system_services
+                .get_process(debugging_pid)
+                .unwrap()
+                .activate()
+                .unwrap();
+
+            let result = crate::arch::mem::privatize_page(addr as usize)
+                .and_then(|_| crate::arch::mem::privatize_page(addr as usize + len - 1));
+
+            // Restore the previous PID
+            system_services
+                .get_process(current_pid)
+                .unwrap()
+                .activate()
+                .unwrap();
+            result.map(|_| ()).or(Err("unable to copy text page to RAM"))
+        })
+    }
+
+    fn read_opcode(&mut self, addr: u32, kind: usize) -> Result<Opcode, <XousTarget as Target>::Error> {
+        let tid = Tid::new(1).unwrap();
+        if kind == 2 {
+            let mut opcode = [0u8; core::mem::size_of::<u16>()];
+            self.read_addrs(addr, &mut opcode, tid)
+                .or(Err("unable to read memory"))?;
+            Ok(Opcode::Opcode16(u16::from_le_bytes(opcode)))
+        } else {
+            let mut opcode = [0u8; core::mem::size_of::<u32>()];
+            self.read_addrs(addr, &mut opcode, tid)
+                .or(Err("unable to read memory"))?;
+            Ok(Opcode::Opcode32(u32::from_le_bytes(opcode)))
+        }
+    }
+
+    fn write_opcode(&mut self, addr: u32, opcode: Opcode) -> Result<(), <XousTarget as Target>::Error> {
+        let tid = Tid::new(1).unwrap();
+        match opcode {
+            Opcode::Opcode16(val) => self
+                .write_addrs(addr, &val.to_le_bytes(), tid)
+                .or(Err("unable to write memory"))?,
+            Opcode::Opcode32(val) => self
+                .write_addrs(addr, &val.to_le_bytes(), tid)
+                .or(Err("unable to write memory"))?,
+        }
+        unsafe {
+            core::arch::asm!(
+                "
+            fence.i
+            fence
+        "
+            )
+        };
+        Ok(())
+    }
+}
+
+/// The breakpoint instruction that fits in place of `opcode`.
+fn ebreak_for(opcode: Opcode) -> Opcode {
+    match opcode {
+        Opcode::Opcode16(_) => Opcode::Opcode16(C_EBREAK),
+        Opcode::Opcode32(_) => Opcode::Opcode32(EBREAK),
+    }
+}
@@ -19,13 +19,6 @@ impl Target for XousTarget {
         Some(self)
     }
 
-    /// Opt in to having GDB handle breakpoints for us. This allows for an unlimited number
-    /// of breakpoints without having us keep track of the breakpoints ourselves, but
-    /// doesn't work with XIP programs.
-    fn guard_rail_implicit_sw_breakpoints(&self) -> bool {
-        true
-    }
-
     fn guard_rail_single_step_gdb_behavior(&self) -> SingleStepGdbBehavior {
         SingleStepGdbBehavior::Required
     }
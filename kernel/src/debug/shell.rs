@@ -217,6 +217,51 @@ fn handle_character(b: u8) {
                 }
             });
         }
+        b'u' => {
+            println!("Process usage:");
+            crate::services::SystemServices::with(|system_services| {
+                println!(
+                    " pid |     quanta |   switches |  msgs sent |  msgs recv | lent (k) | process"
+                );
+                for process in &system_services.processes {
+                    if !process.free() {
+                        let usage = crate::accounting::usage(process.pid);
+                        println!(
+                            " {:3} | {:10} | {:10} | {:10} | {:10} | {:8} | {}",
+                            process.pid,
+                            usage.quanta(),
+                            usage.context_switches(),
+                            usage.messages_sent,
+                            usage.messages_received,
+                            usage.bytes_lent / 1024,
+                            system_services.process_name(process.pid).unwrap_or("")
+                        );
+                        for (tid, thread) in usage.threads.iter().enumerate() {
+                            if thread.context_switches != 0 {
+                                println!(
+                                    "   :{:<2}| {:10} | {:10} |",
+                                    tid, thread.quanta, thread.context_switches
+                                );
+                            }
+                        }
+                    }
+                }
+                println!("Server usage:");
+                println!(" idx | pid |  msgs recv | lent (k) | process");
+                for (idx, server) in system_services.servers.iter().enumerate() {
+                    if let Some(s) = server {
+                        println!(
+                            " {:3} | {:3} | {:10} | {:8} | {}",
+                            idx,
+                            s.pid,
+                            s.messages_received,
+                            s.bytes_lent / 1024,
+                            system_services.process_name(s.pid).unwrap_or("")
+                        );
+                    }
+                }
+            });
+        }
         b'h' => print_help(),
         _ => {}
     }
@@ -233,4 +278,5 @@ fn print_help() {
     println!(" P  | print all processes and threads");
     println!(" r  | report RAM usage of all processes");
     println!(" s  | print all allocated servers");
+    println!(" u  | report CPU and message usage of all processes and servers");
 }
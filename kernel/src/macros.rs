@@ -47,3 +47,37 @@ macro_rules! filled_array {
 
     [$e:expr; $n:tt] => { filled_array!(@accum ($n, $e) -> ()) };
 }
+
+/// Declares `$name`, a table holding one `$ty` per process, along with
+/// `with_process()`, which runs a closure on the entry for a PID and returns
+/// its result, or `None` if the PID is out of range. Hosted kernels under test
+/// run one per thread, so there the table is thread-local.
+#[macro_export]
+macro_rules! per_process_table {
+    ($name:ident: $ty:ty = $init:expr) => {
+        #[cfg(not(baremetal))]
+        std::thread_local!(static $name: core::cell::RefCell<[$ty; $crate::arch::process::MAX_PROCESS_COUNT]> =
+            core::cell::RefCell::new([$init; $crate::arch::process::MAX_PROCESS_COUNT]));
+
+        #[cfg(baremetal)]
+        static mut $name: [$ty; $crate::arch::process::MAX_PROCESS_COUNT] =
+            [$init; $crate::arch::process::MAX_PROCESS_COUNT];
+
+        fn with_process<F, R>(pid: xous_kernel::PID, f: F) -> Option<R>
+        where
+            F: FnOnce(&mut $ty) -> R,
+        {
+            let idx = pid.get() as usize - 1;
+            // Unsafe is required since we're accessing a static mut array. The
+            // kernel only touches this from a syscall or IRQ context, which is
+            // never re-entered.
+            #[cfg(baremetal)]
+            unsafe {
+                $name.get_mut(idx).map(f)
+            }
+
+            #[cfg(not(baremetal))]
+            $name.with(|table| table.borrow_mut().get_mut(idx).map(f))
+        }
+    };
+}
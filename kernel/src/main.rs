@@ -14,6 +14,7 @@ mod debug;
 #[cfg(all(test, not(baremetal)))]
 mod test;
 
+mod accounting;
 mod arch;
 
 #[macro_use]
@@ -23,6 +24,7 @@ mod irq;
 mod macros;
 mod mem;
 mod platform;
+mod priority;
 mod server;
 mod services;
 mod syscall;
@@ -67,7 +69,8 @@ pub unsafe extern "C" fn init(
 }
 
 /// Loop through the SystemServices list to determine the next PID to be run.
-/// If no process is ready, return `None`.
+/// Processes with higher-priority threads ready are preferred, otherwise
+/// processes are picked round-robin. If no process is ready, return `None`.
 fn next_pid_to_run(last_pid: Option<PID>) -> Option<PID> {
     // PIDs are 1-indexed but arrays are 0-indexed.  By not subtracting
     // 1 from the PID when we use it as an array index, we automatically
@@ -75,15 +78,18 @@ fn next_pid_to_run(last_pid: Option<PID>) -> Option<PID> {
     let next_pid = last_pid.map(|v| v.get() as usize).unwrap_or(1);
 
     SystemServices::with(|system_services| {
+        let mut ready = [(PID::new(1).unwrap(), 0usize); services::MAX_PROCESS_COUNT];
+        let mut ready_count = 0;
         for process in system_services.processes[next_pid..]
             .iter()
             .chain(system_services.processes[..next_pid].iter())
         {
             if process.runnable() {
-                return Some(process.pid);
+                ready[ready_count] = (process.pid, process.ready_threads());
+                ready_count += 1;
             }
         }
-        None
+        priority::pick_process(&ready[..ready_count])
     })
 }
 
@@ -57,6 +57,8 @@ impl Default for MemoryManager {
 
 #[cfg(not(baremetal))]
 std::thread_local!(static MEMORY_MANAGER: core::cell::RefCell<MemoryManager> = core::cell::RefCell::new(MemoryManager::default()));
+#[cfg(not(baremetal))]
+std::thread_local!(static MEMORY_ALLOCATIONS: core::cell::RefCell<std::collections::BTreeMap<usize, PID>> = core::cell::RefCell::new(std::collections::BTreeMap::new()));
 
 #[cfg(baremetal)]
 static mut MEMORY_MANAGER: MemoryManager = MemoryManager::default_hack();
@@ -65,6 +67,43 @@ static mut MEMORY_ALLOCATIONS: &mut [Option<PID>] = &mut [];
 #[cfg(baremetal)]
 static mut EXTRA_REGIONS: &[MemoryRangeExtra] = &[];
 
+/// Modify the memory tracking table to note which process owns
+/// the specified address.
+fn update_owner(
+    owner_addr: &mut Option<PID>,
+    pid: PID,
+    action: ClaimReleaseMove,
+) -> Result<(), xous_kernel::Error> {
+    if let Some(current_pid) = *owner_addr {
+        if current_pid != pid {
+            // klog!(
+            //     "In claim_or_release({}, {}, {:?}) -- addr is owned by {} not {}",
+            //     owner_addr.map(|v| v.get()).unwrap_or_default(),
+            //     pid,
+            //     action,
+            //     current_pid,
+            //     pid
+            // );
+            if let ClaimReleaseMove::Move(existing_pid) = action {
+                if existing_pid != current_pid {
+                    return Err(xous_kernel::Error::MemoryInUse);
+                }
+            } else {
+                return Err(xous_kernel::Error::MemoryInUse);
+            }
+        }
+    }
+    match action {
+        ClaimReleaseMove::Claim | ClaimReleaseMove::Move(_) => {
+            *owner_addr = Some(pid);
+        }
+        ClaimReleaseMove::Release => {
+            *owner_addr = None;
+        }
+    }
+    Ok(())
+}
+
 /// Initialize the memory map.
 /// This will go through memory and map anything that the kernel is
 /// using to process 1, then allocate a pagetable for this process
@@ -303,6 +342,27 @@ impl MemoryManager {
         Err(xous_kernel::Error::OutOfMemory)
     }
 
+    /// Hosted processes allocate their own memory, so this only picks an
+    /// unused page from the fake RAM region to stand in for it.
+    #[cfg(not(baremetal))]
+    pub fn alloc_page(&mut self, pid: PID) -> Result<usize, xous_kernel::Error> {
+        use crate::arch::mem::{HOSTED_RAM_BASE, HOSTED_RAM_SIZE};
+        let end_point = HOSTED_RAM_SIZE / PAGE_SIZE;
+        let starting_point = self.last_ram_page;
+        MEMORY_ALLOCATIONS.with(|allocations| {
+            let mut allocations = allocations.borrow_mut();
+            for index in (starting_point..end_point).chain(0..starting_point) {
+                let page = HOSTED_RAM_BASE + index * PAGE_SIZE;
+                if let std::collections::btree_map::Entry::Vacant(entry) = allocations.entry(page) {
+                    entry.insert(pid);
+                    self.last_ram_page = (index + 1) % end_point;
+                    return Ok(page);
+                }
+            }
+            Err(xous_kernel::Error::OutOfMemory)
+        })
+    }
+
     /// Find a virtual address in the current process that is big enough
     /// to fit `size` bytes.
     pub fn find_virtual_address(
@@ -661,11 +721,29 @@ impl MemoryManager {
     #[cfg(not(baremetal))]
     fn claim_release_move(
         &mut self,
-        _addr: *mut usize,
-        _pid: PID,
-        _action: ClaimReleaseMove,
+        addr: *mut usize,
+        pid: PID,
+        action: ClaimReleaseMove,
     ) -> Result<(), xous_kernel::Error> {
-        Ok(())
+        let addr = addr as usize;
+
+        // Ensure the address lies on a page boundary
+        if addr & 0xfff != 0 {
+            return Err(xous_kernel::Error::BadAlignment);
+        }
+
+        // There is no physical memory map in hosted mode, so any address may be
+        // claimed as long as no other process owns it.
+        MEMORY_ALLOCATIONS.with(|allocations| {
+            let mut allocations = allocations.borrow_mut();
+            let mut owner = allocations.get(&addr).copied();
+            update_owner(&mut owner, pid, action)?;
+            match owner {
+                Some(owner) => allocations.insert(addr, owner),
+                None => allocations.remove(&addr),
+            };
+            Ok(())
+        })
     }
 
     #[cfg(baremetal)]
@@ -675,42 +753,6 @@ impl MemoryManager {
         pid: PID,
         action: ClaimReleaseMove,
     ) -> Result<(), xous_kernel::Error> {
-        /// Modify the memory tracking table to note which process owns
-        /// the specified address.
-        fn action_inner(
-            owner_addr: &mut Option<PID>,
-            pid: PID,
-            action: ClaimReleaseMove,
-        ) -> Result<(), xous_kernel::Error> {
-            if let Some(current_pid) = *owner_addr {
-                if current_pid != pid {
-                    // klog!(
-                    //     "In claim_or_release({}, {}, {:?}) -- addr is owned by {} not {}",
-                    //     owner_addr.map(|v| v.get()).unwrap_or_default(),
-                    //     pid,
-                    //     action,
-                    //     current_pid,
-                    //     pid
-                    // );
-                    if let ClaimReleaseMove::Move(existing_pid) = action {
-                        if existing_pid != current_pid {
-                            return Err(xous_kernel::Error::MemoryInUse);
-                        }
-                    } else {
-                        return Err(xous_kernel::Error::MemoryInUse);
-                    }
-                }
-            }
-            match action {
-                ClaimReleaseMove::Claim | ClaimReleaseMove::Move(_) => {
-                    *owner_addr = Some(pid);
-                }
-                ClaimReleaseMove::Release => {
-                    *owner_addr = None;
-                }
-            }
-            Ok(())
-        }
         let addr = addr as usize;
 
         // Ensure the address lies on a page boundary
@@ -722,7 +764,7 @@ impl MemoryManager {
         // Happy path: The address is in main RAM
         if addr >= self.ram_start && addr < self.ram_start + self.ram_size {
             offset += (addr - self.ram_start) / PAGE_SIZE;
-            return unsafe { action_inner(&mut MEMORY_ALLOCATIONS[offset], pid, action) };
+            return unsafe { update_owner(&mut MEMORY_ALLOCATIONS[offset], pid, action) };
         }
 
         offset += self.ram_size / PAGE_SIZE;
@@ -747,7 +789,7 @@ impl MemoryManager {
                     // -------------------------------
 
                     offset += (addr - (region.mem_start as usize)) / PAGE_SIZE;
-                    return action_inner(&mut MEMORY_ALLOCATIONS[offset], pid, action);
+                    return update_owner(&mut MEMORY_ALLOCATIONS[offset], pid, action);
                 }
                 offset += region.mem_size as usize / PAGE_SIZE;
             }
@@ -820,6 +862,10 @@ impl MemoryManager {
                 }
             }
         }
+
+        #[cfg(not(baremetal))]
+        MEMORY_ALLOCATIONS
+            .with(|allocations| allocations.borrow_mut().retain(|_, owner| *owner != _pid));
     }
 
     /// Adjust the flags on the given memory range. This allows for stripping flags from a memory
@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Thread priorities. When choosing which thread to run, the scheduler picks
+//! the highest-priority ready thread, going round-robin among threads of equal
+//! priority. To avoid starvation, a thread that has been passed over in favour
+//! of a higher-priority thread `STARVATION_LIMIT` times in a row is run next,
+//! regardless of its priority. The same rules apply when choosing between
+//! processes, where a process takes the priority of its best ready thread.
+
+use crate::arch::process::MAX_THREAD;
+use xous_kernel::{ThreadPriority, PID, TID};
+
+/// How many times a ready thread or process may be passed over for a
+/// higher-priority one before it is run anyway.
+pub const STARVATION_LIMIT: u8 = 8;
+
+#[derive(Copy, Clone)]
+struct ProcessPriorities {
+    threads: [ThreadPriority; MAX_THREAD + 1],
+
+    /// How many times each thread has been passed over in a row
+    thread_skips: [u8; MAX_THREAD + 1],
+
+    /// How many times the process as a whole has been passed over in a row
+    process_skips: u8,
+
+    /// Whether threads of this process may be raised above `Normal`
+    privileged: bool,
+}
+
+impl ProcessPriorities {
+    const fn new(privileged: bool) -> Self {
+        ProcessPriorities {
+            threads: [ThreadPriority::Normal; MAX_THREAD + 1],
+            thread_skips: [0; MAX_THREAD + 1],
+            process_skips: 0,
+            privileged,
+        }
+    }
+}
+
+// Processes loaded at boot never pass through `reset()`, so every slot starts
+// out privileged.
+crate::per_process_table!(PRIORITIES: ProcessPriorities = ProcessPriorities::new(true));
+
+/// Priority used for comparisons, taking starvation into account.
+fn effective(priority: ThreadPriority, skips: u8) -> u8 {
+    if skips >= STARVATION_LIMIT {
+        ThreadPriority::Realtime as u8 + 1
+    } else {
+        priority as u8
+    }
+}
+
+/// Pick a thread from `thread_mask` to follow `current_thread`. The choice
+/// among equals is delegated to `round_robin`.
+pub fn pick_thread(
+    thread_mask: usize,
+    current_thread: TID,
+    priorities: &[ThreadPriority],
+    skips: &mut [u8],
+    round_robin: fn(usize, TID) -> TID,
+) -> TID {
+    let ready = || (0..priorities.len()).filter(move |tid| thread_mask & (1 << *tid) != 0);
+
+    let best = ready()
+        .map(|tid| effective(priorities[tid], skips[tid]))
+        .max()
+        .unwrap_or(0);
+    let candidates = ready()
+        .filter(|&tid| effective(priorities[tid], skips[tid]) == best)
+        .fold(0, |mask, tid| mask | (1 << tid));
+    let chosen = if candidates == 0 {
+        round_robin(thread_mask, current_thread)
+    } else {
+        round_robin(candidates, current_thread)
+    };
+
+    for tid in ready() {
+        if tid == chosen {
+            skips[tid] = 0;
+        } else if priorities[tid] < priorities[chosen] {
+            skips[tid] = skips[tid].saturating_add(1);
+        }
+    }
+    chosen
+}
+
+/// Pick the next thread of `pid` to run from `thread_mask`.
+pub fn next_thread(
+    pid: PID,
+    thread_mask: usize,
+    current_thread: TID,
+    round_robin: fn(usize, TID) -> TID,
+) -> TID {
+    with_process(pid, |p| {
+        pick_thread(
+            thread_mask,
+            current_thread,
+            &p.threads,
+            &mut p.thread_skips,
+            round_robin,
+        )
+    })
+    .unwrap_or_else(|| round_robin(thread_mask, current_thread))
+}
+
+/// Pick a process to run from `ready`, a list of `(pid, ready thread mask)`
+/// pairs given in round-robin order.
+pub fn pick_process(ready: &[(PID, usize)]) -> Option<PID> {
+    let score = |pid: PID, mask: usize| {
+        with_process(pid, |p| {
+            let best = (0..p.threads.len())
+                .filter(|tid| mask & (1 << *tid) != 0)
+                .map(|tid| p.threads[tid])
+                .max()
+                .unwrap_or(ThreadPriority::Normal);
+            (best, effective(best, p.process_skips))
+        })
+        .unwrap_or((ThreadPriority::Normal, ThreadPriority::Normal as u8))
+    };
+
+    // Take the first process with the highest score, so ties stay round-robin.
+    let mut chosen: Option<(PID, ThreadPriority, u8)> = None;
+    for &(pid, mask) in ready {
+        let (base, eff) = score(pid, mask);
+        if chosen.map(|(_, _, best)| eff > best).unwrap_or(true) {
+            chosen = Some((pid, base, eff));
+        }
+    }
+
+    let (chosen_pid, chosen_base, _) = chosen?;
+    for &(pid, mask) in ready {
+        let (base, _) = score(pid, mask);
+        with_process(pid, |p| {
+            if pid == chosen_pid {
+                p.process_skips = 0;
+            } else if base < chosen_base {
+                p.process_skips = p.process_skips.saturating_add(1);
+            }
+        });
+    }
+    Some(chosen_pid)
+}
+
+pub fn get(pid: PID, tid: TID) -> ThreadPriority {
+    with_process(pid, |p| p.threads.get(tid).copied())
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// Set the priority of `pid:tid`, returning the previous priority. Only
+/// privileged processes may raise a thread above `Normal`.
+pub fn set(
+    pid: PID,
+    tid: TID,
+    priority: ThreadPriority,
+) -> Result<ThreadPriority, xous_kernel::Error> {
+    with_process(pid, |p| {
+        if priority > ThreadPriority::Normal && !p.privileged {
+            return Err(xous_kernel::Error::AccessDenied);
+        }
+        let slot = p
+            .threads
+            .get_mut(tid)
+            .ok_or(xous_kernel::Error::ThreadNotAvailable)?;
+        let previous = *slot;
+        *slot = priority;
+        p.thread_skips[tid] = 0;
+        Ok(previous)
+    })
+    .unwrap_or(Err(xous_kernel::Error::ProcessNotFound))
+}
+
+/// Give a new thread the priority of the thread that created it.
+pub fn inherit(pid: PID, parent: TID, child: TID) {
+    let priority = get(pid, parent);
+    set(pid, child, priority).ok();
+}
+
+/// Put every thread of a process back at `Normal`. Called when a PID is (re)allocated;
+/// `privileged` decides whether the new process may use `High` and `Realtime`.
+pub fn reset(pid: PID, privileged: bool) {
+    with_process(pid, |p| *p = ProcessPriorities::new(privileged));
+}
@@ -37,6 +37,12 @@ pub struct Server {
     /// this message. If there are no available contexts, then messages will
     /// need to be queued.
     ready_threads: usize,
+
+    /// Number of messages that have been sent to this server
+    pub messages_received: usize,
+
+    /// Number of bytes that have been lent to this server along with messages
+    pub bytes_lent: usize,
 }
 
 pub struct SenderID {
@@ -301,6 +307,8 @@ impl Server {
             tail_generation: 0,
             queue,
             ready_threads: 0,
+            messages_received: 0,
+            bytes_lent: 0,
         });
         Ok(())
     }
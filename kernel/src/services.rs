@@ -236,6 +236,16 @@ impl Process {
         )
     }
 
+    /// The threads of this process that are waiting to be run. Processes
+    /// that are not `Ready` report no threads.
+    pub fn ready_threads(&self) -> usize {
+        if let ProcessState::Ready(x) = self.state {
+            x
+        } else {
+            0
+        }
+    }
+
     /// This process slot is unallocated and may be turn into a process
     pub fn free(&self) -> bool {
         matches!(self.state, ProcessState::Free)
@@ -459,7 +469,7 @@ impl SystemServices {
     ) -> Result<ProcessStartup, xous_kernel::Error> {
         let mut entry_idx = None;
         let mut new_pid = None;
-        let _ppid = crate::arch::process::current_pid();
+        let ppid = crate::arch::process::current_pid();
 
         for (idx, entry) in self.processes.iter_mut().enumerate() {
             if entry.state != ProcessState::Free {
@@ -469,6 +479,10 @@ impl SystemServices {
             new_pid = Some(pid_from_usize(idx + 1)?);
             entry.pid = new_pid.unwrap();
             entry.ppid = PID::new(1).unwrap();
+            crate::accounting::reset(entry.pid);
+            // Only processes started by the kernel itself may use the
+            // elevated priorities; anything spawned at runtime may not.
+            crate::priority::reset(entry.pid, ppid.get() == 1);
             entry.state = ProcessState::Allocated;
             unsafe {
                 entry
@@ -493,11 +507,11 @@ impl SystemServices {
             // this process.
             entry.state = ProcessState::Ready(1 << INITIAL_TID);
         }
-        // entry.ppid = _ppid;
+        // entry.ppid = ppid;
         klog!(
             "created new process for PID {} with PPID {}",
             new_pid,
-            _ppid
+            ppid
         );
         return Ok(startup);
     }
@@ -831,6 +845,16 @@ impl SystemServices {
         }
     }
 
+    /// Pick the next thread of `pid` to run out of `thread_mask`, preferring
+    /// higher-priority threads and using `find_next_thread()` to go
+    /// round-robin among threads of the same priority.
+    pub fn schedule_thread(pid: PID, thread_mask: usize, current_thread: usize) -> usize {
+        if thread_mask == 0 {
+            panic!("no threads were available to run");
+        }
+        crate::priority::next_thread(pid, thread_mask, current_thread, Self::find_next_thread)
+    }
+
     /// Set the "current thread" of a given process. It is designed
     /// to set where the next thread will run in order to avoid starving threads
     /// when messages are passed around.
@@ -918,7 +942,7 @@ impl SystemServices {
             }
             ProcessState::Ready(ready_threads) => {
                 let new_thread = tid.unwrap_or_else(|| {
-                    Self::find_next_thread(ready_threads, process.current_thread)
+                    Self::schedule_thread(pid, ready_threads, process.current_thread)
                 });
 
                 if ready_threads & (1 << new_thread) == 0 {
@@ -936,7 +960,7 @@ impl SystemServices {
                 let ready_threads = ready_threads | (1 << process.current_thread);
 
                 let new_thread = tid.unwrap_or_else(|| {
-                    Self::find_next_thread(ready_threads, process.current_thread)
+                    Self::schedule_thread(pid, ready_threads, process.current_thread)
                 });
 
                 // Ensure the specified context is ready to run, or is
@@ -954,6 +978,7 @@ impl SystemServices {
             }
         };
         // log_process_update(file!(), line!(), process, old_state);
+        crate::accounting::context_switch(pid, process.current_thread);
 
         // println!(
         //     "switch_to_thread({}:{:?}): New state is {:?} Thread is ",
@@ -1107,7 +1132,7 @@ impl SystemServices {
                         new.state
                     );
                     if new_tid == 0 {
-                        new_tid = Self::find_next_thread(x, new.current_thread);
+                        new_tid = Self::schedule_thread(new_pid, x, new.current_thread);
                     }
                     if x & (1 << new_tid) == 0 {
                         println!(
@@ -1262,7 +1287,7 @@ impl SystemServices {
                 // thread.  If that is not runnable, do a round-robin
                 // search for the next available thread.
                 if new_tid == 0 {
-                    new_tid = Self::find_next_thread(x, new.current_thread);
+                    new_tid = Self::schedule_thread(new_pid, x, new.current_thread);
                 }
 
                 if x & (1 << new_tid) == 0 {
@@ -1284,6 +1309,7 @@ impl SystemServices {
 
         // Restore the previous thread, if one exists.
         ArchProcess::current().set_tid(new_tid)?;
+        crate::accounting::context_switch(new_pid, new_tid);
 
         klog!(
             "Activated process {}:{}, new state: {:?}",
@@ -1403,15 +1429,35 @@ impl SystemServices {
         .map(|val| val as *mut usize)
     }
 
+    /// Hosted processes receive a copy of the data over their connection, so
+    /// the only thing left to do is to remove any pages the kernel mapped from
+    /// the sending process, just as they would disappear on hardware.
     #[cfg(not(baremetal))]
     pub fn send_memory(
         &mut self,
         src_virt: *mut usize,
-        _dest_pid: PID,
+        dest_pid: PID,
         _dest_virt: *mut usize,
-        _len: usize,
+        len: usize,
     ) -> Result<*mut usize, xous_kernel::Error> {
-        Ok(src_virt)
+        let current_pid = self.current_pid();
+        if current_pid == dest_pid {
+            return Ok(src_virt);
+        }
+        let origin = crate::arch::mem::caller_address(src_virt as usize);
+        let pages = crate::arch::mem::mapped_pages(current_pid, origin, len);
+        if pages
+            .iter()
+            .any(|&page| crate::arch::mem::page_is_lent(page as *mut u8))
+        {
+            return Err(xous_kernel::Error::ShareViolation);
+        }
+        crate::mem::MemoryManager::with_mut(|mm| {
+            for page in pages {
+                mm.unmap_page(page as *mut usize)?;
+            }
+            Ok(src_virt)
+        })
     }
 
     /// Lend memory from one process to another.
@@ -1531,16 +1577,48 @@ impl SystemServices {
         .map(|val| val as *mut usize)
     }
 
+    /// Hosted processes lend memory by copying it over their connection.
+    /// Pages the kernel mapped are still marked as lent, so that the same
+    /// sharing rules apply as on hardware.
     #[cfg(not(baremetal))]
     pub fn lend_memory(
         &mut self,
         src_virt: *mut usize,
-        _dest_pid: PID,
-        _dest_virt: *mut usize,
-        _len: usize,
-        _mutable: bool,
+        dest_pid: PID,
+        dest_virt: *mut usize,
+        len: usize,
+        mutable: bool,
     ) -> Result<*mut usize, xous_kernel::Error> {
-        Ok(src_virt)
+        let current_pid = self.current_pid();
+        if current_pid == dest_pid {
+            return Ok(src_virt);
+        }
+        let origin = crate::arch::mem::caller_address(src_virt as usize);
+        let pages = crate::arch::mem::mapped_pages(current_pid, origin, len);
+        if pages
+            .iter()
+            .any(|&page| crate::arch::mem::page_is_lent(page as *mut u8))
+        {
+            return Err(xous_kernel::Error::ShareViolation);
+        }
+        let src_mapping = self.get_process(current_pid)?.mapping;
+        let dest_mapping = self.get_process(dest_pid)?.mapping;
+        crate::mem::MemoryManager::with_mut(|mm| {
+            for &page in &pages {
+                mm.lend_page(
+                    &src_mapping,
+                    page as *mut u8,
+                    dest_pid,
+                    &dest_mapping,
+                    dest_virt as *mut u8,
+                    mutable,
+                )?;
+            }
+            if !pages.is_empty() {
+                crate::arch::mem::remember_lend(src_virt as usize, origin);
+            }
+            Ok(src_virt)
+        })
     }
 
     /// Return memory from one process back to another
@@ -1640,13 +1718,32 @@ impl SystemServices {
         src_virt: *mut usize,
         dest_pid: PID,
         dest_tid: TID,
-        _dest_virt: *mut usize,
+        dest_virt: *mut usize,
         len: usize,
         // buf: MemoryRange,
     ) -> Result<*mut usize, xous_kernel::Error> {
         let buf = unsafe { MemoryRange::new(src_virt as usize, len) }?;
         let buf = buf.as_slice();
         let current_pid = self.current_pid();
+
+        // Let the lender use any pages the kernel mapped again. `dest_virt`
+        // is the kernel's copy of the memory, so find where it came from.
+        if let Some(origin) = crate::arch::mem::forget_lend(dest_virt as usize) {
+            let src_mapping = self.get_process(current_pid)?.mapping;
+            let dest_mapping = self.get_process(dest_pid)?.mapping;
+            crate::mem::MemoryManager::with_mut(|mm| {
+                for page in crate::arch::mem::mapped_pages(dest_pid, origin, len) {
+                    mm.unlend_page(
+                        &src_mapping,
+                        src_virt as *mut u8,
+                        dest_pid,
+                        &dest_mapping,
+                        page as *mut u8,
+                    )?;
+                }
+                Ok(())
+            })?;
+        }
         {
             let target_process = self.get_process(dest_pid)?;
             target_process.activate()?;
@@ -2118,6 +2215,16 @@ impl SystemServices {
         })
     }
 
+    /// Update the usage counters of the server at `sidx`, its owner, and the
+    /// `sender` for a message that was just delivered.
+    pub fn account_message(&mut self, sidx: usize, sender: PID, lent: usize) {
+        if let Some(server) = self.server_from_sidx_mut(sidx) {
+            server.messages_received = server.messages_received.wrapping_add(1);
+            server.bytes_lent = server.bytes_lent.wrapping_add(lent);
+            crate::accounting::message(sender, server.pid, lent);
+        }
+    }
+
     /// Switch to the server's memory space and add the message to its server
     /// queue
     pub fn queue_server_message(
@@ -2196,6 +2303,11 @@ impl SystemServices {
 
     /// Terminate the given process. Returns the process' parent PID.
     pub fn terminate_process(&mut self, target_pid: PID) -> Result<PID, xous_kernel::Error> {
+        // 0. If a debugger has breakpoints in this process, take them out while its
+        //    memory is still mapped.
+        #[cfg(all(baremetal, feature = "gdb-stub"))]
+        crate::debug::gdb::report_process_exit(target_pid);
+
         // To terminate a process, we must perform the following:
         //
         // 1. If we have any client connections, remove them.
@@ -102,6 +102,12 @@ fn send_message(pid: PID, tid: TID, cid: CID, message: Message) -> SysCallResult
             }
         };
 
+        // Note how much memory is being lent, for accounting purposes.
+        let lent = match &message {
+            Message::MutableBorrow(msg) | Message::Borrow(msg) => msg.buf.len(),
+            _ => 0,
+        };
+
         // Translate memory messages from the client process to the server
         // process. Additionally, determine whether the call is blocking. If
         // so, switch to the server context right away.
@@ -184,6 +190,7 @@ fn send_message(pid: PID, tid: TID, cid: CID, message: Message) -> SysCallResult
             } else {
                 0
             };
+            ss.account_message(sidx, pid, lent);
             let sender = SenderID::new(sidx, sender_idx, Some(pid));
             klog!(
                 "server connection data: sidx: {}, idx: {}, server pid: {}",
@@ -285,6 +292,7 @@ fn send_message(pid: PID, tid: TID, cid: CID, message: Message) -> SysCallResult
         // returns an error.
         let _queue_idx = ss.queue_server_message(sidx, pid, tid, message, client_address)?;
         klog!("queued into index {:x}", _queue_idx);
+        ss.account_message(sidx, pid, lent);
 
         // Park this context if it's blocking.  This is roughly
         // equivalent to a "Yield".
@@ -475,9 +483,7 @@ fn return_result(
                 return Err(xous_kernel::Error::DoubleFree);
             }
             WaitingMessage::MovedMemory => {
-                klog!(
-                    "WARNING: Tried to wait on a scalar message that was actually moved memory"
-                );
+                klog!("WARNING: Tried to wait on a scalar message that was actually moved memory");
                 return Err(xous_kernel::Error::DoubleFree);
             }
             WaitingMessage::None => {
@@ -605,9 +611,7 @@ fn reply_and_receive_next(
                 }
             }
             WaitingMessage::MovedMemory => {
-                klog!(
-                    "WARNING: Tried to wait on a scalar message that was actually moved memory"
-                );
+                klog!("WARNING: Tried to wait on a scalar message that was actually moved memory");
                 return Err(xous_kernel::Error::DoubleFree);
             }
             WaitingMessage::None => {
@@ -745,12 +749,21 @@ fn receive_message(pid: PID, tid: TID, sid: SID, blocking: ExecutionType) -> Sys
 }
 
 pub fn handle(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallResult {
-    klog!("KERNEL({}:{}): Syscall {:x?}, in_irq={}", pid, tid, call, in_irq);
+    klog!(
+        "KERNEL({}:{}): Syscall {:x?}, in_irq={}",
+        pid,
+        tid,
+        call,
+        in_irq
+    );
     // let call_string = format!("{:x?}", call);
     // let start_time = std::time::Instant::now();
     #[allow(clippy::let_and_return)]
     let result = if in_irq && !call.can_call_from_interrupt() {
-        klog!("[!] Called {:?} that's cannot be called from the interrupt handler!", call);
+        klog!(
+            "[!] Called {:?} that's cannot be called from the interrupt handler!",
+            call
+        );
         Err(xous_kernel::Error::InvalidSyscall)
     } else {
         handle_inner(pid, tid, in_irq, call)
@@ -946,6 +959,7 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
             //     new_context, new_pid, pid, tid
             // );
             let new_tid = ss.activate_process_thread(tid, new_pid, new_tid, true)?;
+            crate::accounting::charge_quantum(new_pid, new_tid);
             ORIGINAL_PID.store(new_pid.get(), Relaxed);
             ORIGINAL_TID.store(new_tid, Relaxed);
             Ok(xous_kernel::Result::ResumeProcess)
@@ -992,6 +1006,8 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
         }),
         SysCall::CreateThread(thread_init) => SystemServices::with_mut(|ss| {
             ss.create_thread(pid, thread_init).map(|new_tid| {
+                crate::priority::inherit(pid, tid, new_tid);
+
                 // Set the return value of the existing thread to be the new thread ID
                 if cfg!(target_os = "xous") {
                     // Immediately switch to the new thread
@@ -1134,6 +1150,57 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
             }),
             _ => Err(xous_kernel::Error::InvalidLimit),
         },
+        SysCall::GetUsage(kind, target, index) => SystemServices::with(|ss| match kind {
+            UsageKind::Process | UsageKind::Thread => {
+                if target == 0 || target > arch::process::MAX_PROCESS_COUNT {
+                    return Err(xous_kernel::Error::ProcessNotFound);
+                }
+                let target_pid = pid_from_usize(target)?;
+                if ss.get_process(target_pid)?.free() {
+                    return Err(xous_kernel::Error::ProcessNotFound);
+                }
+                let usage = crate::accounting::usage(target_pid);
+                if kind == UsageKind::Process {
+                    Ok(xous_kernel::Result::Scalar5(
+                        usage.quanta(),
+                        usage.context_switches(),
+                        usage.messages_sent,
+                        usage.messages_received,
+                        usage.bytes_lent,
+                    ))
+                } else {
+                    let thread = usage
+                        .threads
+                        .get(index)
+                        .ok_or(xous_kernel::Error::InvalidThread)?;
+                    Ok(xous_kernel::Result::Scalar5(
+                        thread.quanta,
+                        thread.context_switches,
+                        0,
+                        0,
+                        0,
+                    ))
+                }
+            }
+            UsageKind::Server => match ss.servers.get(target) {
+                Some(Some(server)) => Ok(xous_kernel::Result::Scalar5(
+                    server.pid.get() as usize,
+                    server.messages_received,
+                    server.bytes_lent,
+                    0,
+                    0,
+                )),
+                Some(None) => Ok(xous_kernel::Result::Scalar5(0, 0, 0, 0, 0)),
+                None => Err(xous_kernel::Error::ServerNotFound),
+            },
+        }),
+        SysCall::SetThreadPriority(target_tid, priority) => {
+            if !ArchProcess::current().thread_exists(target_tid) {
+                return Err(xous_kernel::Error::ThreadNotAvailable);
+            }
+            crate::priority::set(pid, target_tid, priority)
+                .map(|previous| xous_kernel::Result::Scalar1(previous as usize))
+        }
         #[cfg(feature = "v2p")]
         SysCall::VirtToPhys(vaddr) => {
             let phys_addr = crate::arch::mem::virt_to_phys(vaddr as usize);
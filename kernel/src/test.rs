@@ -336,6 +336,85 @@ fn send_scalar_message() {
     main_thread.join().expect("couldn't join kernel process");
 }
 
+#[test]
+fn usage_counters() {
+    let main_thread = start_kernel(SERVER_SPEC);
+    let (server_addr_send, server_addr_recv) = unbounded();
+    let test_bytes = b"Hello, world!";
+
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "usage_counters server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+            server_addr_send.send(sid).unwrap();
+            for _ in 0..2 {
+                let envelope =
+                    xous_kernel::receive_message(sid).expect("couldn't receive messages");
+                if let xous_kernel::Message::Borrow(m) = envelope.body {
+                    xous_kernel::return_memory(envelope.sender, m.buf).unwrap();
+                }
+            }
+
+            let pid = xous_kernel::current_pid().unwrap();
+            let usage = xous_kernel::process_usage(pid).expect("couldn't get process usage");
+            assert_eq!(usage.messages_received, 2);
+            assert_eq!(usage.messages_sent, 0);
+
+            let mut slot = 0;
+            let mut found = None;
+            while let Ok(server) = xous_kernel::server_usage(slot) {
+                if let Some(server) = server.filter(|s| s.pid == pid) {
+                    found = Some(server);
+                }
+                slot += 1;
+            }
+            let server = found.expect("server wasn't listed");
+            assert_eq!(server.messages_received, 2);
+            assert!(server.bytes_lent >= test_bytes.len());
+        },
+    ))
+    .expect("couldn't start server");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "usage_counters client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+            xous_kernel::send_message(
+                conn,
+                xous_kernel::Message::Scalar(xous_kernel::ScalarMessage {
+                    id: 1,
+                    arg1: 2,
+                    arg2: 3,
+                    arg3: 4,
+                    arg4: 5,
+                }),
+            )
+            .expect("couldn't send message");
+            xous_kernel::carton::Carton::from_bytes(test_bytes)
+                .lend(conn, 0)
+                .expect("couldn't lend message to server");
+
+            let pid = xous_kernel::current_pid().unwrap();
+            let usage = xous_kernel::process_usage(pid).expect("couldn't get process usage");
+            assert_eq!(usage.messages_sent, 2);
+            assert_eq!(usage.messages_received, 0);
+            assert!(usage.bytes_lent >= test_bytes.len());
+            assert_eq!(
+                xous_kernel::thread_usage(pid, 64),
+                Err(xous_kernel::Error::InvalidThread)
+            );
+        },
+    ))
+    .expect("couldn't start client");
+
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
 #[test]
 fn try_receive_message() {
     // Start the server in another thread
@@ -984,6 +1063,168 @@ fn send_repeat_mutableborrow_message() {
     main_thread.join().expect("couldn't join kernel process");
 }
 
+#[test]
+fn map_memory_syscalls() {
+    let main_thread = start_kernel(SERVER_SPEC);
+    let (claimed_send, claimed_recv) = unbounded();
+    let (checked_send, checked_recv) = unbounded();
+    let (released_send, released_recv) = unbounded();
+
+    // An address outside of the fake RAM region, standing in for a peripheral
+    const CSR_BASE: usize = 0xf000_0000;
+
+    let first = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "map_memory_syscalls first",
+        move || {
+            use xous_kernel::{Error, MemoryAddress, MemoryFlags};
+            let rw = MemoryFlags::R | MemoryFlags::W;
+
+            assert_eq!(
+                xous_kernel::map_memory(None, None, 100, rw),
+                Err(Error::BadAlignment)
+            );
+
+            let range = xous_kernel::map_memory(None, None, 8192, rw).expect("couldn't map memory");
+            let bytes = unsafe { core::slice::from_raw_parts_mut(range.as_mut_ptr(), range.len()) };
+            bytes.fill(0xa5);
+
+            // Every page is backed by its own physical page.
+            let first_page = xous_kernel::virt_to_phys(range.as_ptr() as usize).unwrap();
+            let second_page = xous_kernel::virt_to_phys(range.as_ptr() as usize + 4096).unwrap();
+            assert_ne!(first_page, second_page);
+
+            // Flags may be stripped, but never added back.
+            xous_kernel::update_memory_flags(range, MemoryFlags::R).expect("couldn't strip flags");
+            assert_eq!(
+                xous_kernel::update_memory_flags(range, rw),
+                Err(Error::MemoryInUse)
+            );
+            xous_kernel::unmap_memory(range).expect("couldn't unmap memory");
+
+            // Claim a physical page so the other process can't have it.
+            let csr = xous_kernel::map_memory(MemoryAddress::new(CSR_BASE), None, 4096, rw)
+                .expect("couldn't map csr");
+            assert_eq!(
+                xous_kernel::virt_to_phys(csr.as_ptr() as usize),
+                Ok(CSR_BASE)
+            );
+            claimed_send.send(()).unwrap();
+            checked_recv.recv().unwrap();
+            xous_kernel::unmap_memory(csr).expect("couldn't unmap csr");
+            released_send.send(()).unwrap();
+        },
+    ))
+    .expect("couldn't start first process");
+
+    let second = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "map_memory_syscalls second",
+        move || {
+            use xous_kernel::{Error, MemoryAddress, MemoryFlags};
+            let rw = MemoryFlags::R | MemoryFlags::W;
+
+            claimed_recv.recv().unwrap();
+            assert_eq!(
+                xous_kernel::map_memory(MemoryAddress::new(CSR_BASE), None, 4096, rw),
+                Err(Error::MemoryInUse)
+            );
+            checked_send.send(()).unwrap();
+
+            released_recv.recv().unwrap();
+            let csr = xous_kernel::map_memory(MemoryAddress::new(CSR_BASE), None, 4096, rw)
+                .expect("couldn't map released csr");
+            xous_kernel::unmap_memory(csr).expect("couldn't unmap csr");
+        },
+    ))
+    .expect("couldn't start second process");
+
+    crate::wait_process_as_thread(first).expect("couldn't join first process");
+    crate::wait_process_as_thread(second).expect("couldn't join second process");
+
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn lent_memory_is_protected() {
+    let main_thread = start_kernel(SERVER_SPEC);
+    let (server_addr_send, server_addr_recv) = unbounded();
+    let (holding_send, holding_recv) = unbounded();
+    let (checked_send, checked_recv) = unbounded();
+
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "lent_memory_is_protected server",
+        move || {
+            let sid = xous_kernel::create_server_with_address(b"lent_memory_prot")
+                .expect("couldn't create test server");
+            server_addr_send.send(sid).unwrap();
+            let envelope = xous_kernel::receive_message(sid).expect("couldn't receive messages");
+            let message = envelope.body;
+            if let xous_kernel::Message::MutableBorrow(m) = message {
+                // Hold on to the memory until the client has checked it can't touch it.
+                holding_send.send(()).unwrap();
+                checked_recv.recv().unwrap();
+                xous_kernel::return_memory(envelope.sender, m.buf).unwrap();
+            } else {
+                panic!("unexpected message type");
+            }
+        },
+    ))
+    .expect("couldn't start server");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "lent_memory_is_protected client",
+        move || {
+            use xous_kernel::{Error, MemoryFlags, MemoryMessage, Message};
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+            let range = xous_kernel::map_memory(None, None, 4096, MemoryFlags::R | MemoryFlags::W)
+                .expect("couldn't map memory");
+            let message = MemoryMessage {
+                id: 0,
+                buf: range,
+                offset: None,
+                valid: None,
+            };
+
+            let checker = xous_kernel::create_thread(move || {
+                holding_recv.recv().unwrap();
+                assert_eq!(
+                    xous_kernel::virt_to_phys(range.as_ptr() as usize),
+                    Err(Error::BadAddress)
+                );
+                assert_eq!(
+                    xous_kernel::update_memory_flags(range, MemoryFlags::R),
+                    Err(Error::MemoryInUse)
+                );
+                assert_eq!(
+                    xous_kernel::try_send_message(conn, Message::Borrow(message)),
+                    Err(Error::ShareViolation)
+                );
+                checked_send.send(()).unwrap();
+            })
+            .expect("couldn't spawn checker thread");
+
+            xous_kernel::send_message(conn, Message::MutableBorrow(message))
+                .expect("couldn't lend memory");
+            xous_kernel::wait_thread(checker).expect("couldn't wait for checker");
+
+            // Once returned, the memory belongs to the client again.
+            assert!(xous_kernel::virt_to_phys(range.as_ptr() as usize).is_ok());
+            xous_kernel::update_memory_flags(range, MemoryFlags::R).expect("couldn't strip flags");
+            xous_kernel::unmap_memory(range).expect("couldn't unmap memory");
+        },
+    ))
+    .expect("couldn't start client");
+
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
 // #[cfg(feature = "report-memory")]
 // #[test]
 // fn measure_memory_usage() {
@@ -1177,3 +1418,200 @@ fn process_restart_server() {
 
     main_thread.join().expect("couldn't join kernel process");
 }
+
+#[test]
+fn thread_priority_ordering() {
+    use crate::priority::pick_thread;
+    use crate::services::SystemServices;
+    use xous_kernel::ThreadPriority;
+
+    let mut priorities = [ThreadPriority::Normal; crate::arch::process::MAX_THREAD + 1];
+    let mut skips = [0u8; crate::arch::process::MAX_THREAD + 1];
+    priorities[3] = ThreadPriority::High;
+    priorities[5] = ThreadPriority::Low;
+
+    let next = |mask, current, p: &[ThreadPriority], s: &mut [u8]| {
+        pick_thread(mask, current, p, s, SystemServices::find_next_thread)
+    };
+
+    // The highest-priority ready thread wins, no matter where round-robin is.
+    for current in 0..8 {
+        let mut skips = [0u8; crate::arch::process::MAX_THREAD + 1];
+        assert_eq!(next(0b10_1100, current, &priorities[..], &mut skips[..]), 3);
+    }
+
+    // Threads of equal priority take turns.
+    assert_eq!(next(0b1_0100, 2, &priorities[..], &mut skips[..]), 4);
+    assert_eq!(next(0b1_0100, 4, &priorities[..], &mut skips[..]), 2);
+
+    // A lone low-priority thread still runs.
+    assert_eq!(next(0b10_0000, 2, &priorities[..], &mut skips[..]), 5);
+
+    // With everything at the same priority, this is plain round-robin.
+    let normal = [ThreadPriority::Normal; crate::arch::process::MAX_THREAD + 1];
+    let mut current = 2;
+    for _ in 0..20 {
+        let expected = SystemServices::find_next_thread(0b1110_0100, current);
+        current = next(0b1110_0100, current, &normal[..], &mut skips[..]);
+        assert_eq!(current, expected);
+    }
+}
+
+#[test]
+fn thread_priority_starvation() {
+    use crate::priority::{pick_thread, STARVATION_LIMIT};
+    use crate::services::SystemServices;
+    use xous_kernel::ThreadPriority;
+
+    let mut priorities = [ThreadPriority::Normal; crate::arch::process::MAX_THREAD + 1];
+    let mut skips = [0u8; crate::arch::process::MAX_THREAD + 1];
+    priorities[2] = ThreadPriority::Realtime;
+    priorities[3] = ThreadPriority::High;
+    priorities[5] = ThreadPriority::Low;
+
+    // Threads 2 and 3 are always ready, and would monopolise the CPU under
+    // strict priority scheduling. Every thread must still run regularly.
+    let mask = 0b10_1110;
+    let max_gap = STARVATION_LIMIT as usize + 4;
+    let mut last_run = [0usize; crate::arch::process::MAX_THREAD + 1];
+    let mut current = 1;
+    for round in 1..500 {
+        current = pick_thread(
+            mask,
+            current,
+            &priorities,
+            &mut skips,
+            SystemServices::find_next_thread,
+        );
+        last_run[current] = round;
+        for tid in [1, 2, 3, 5].iter() {
+            assert!(
+                round - last_run[*tid] <= max_gap,
+                "thread {} didn't run between rounds {} and {}",
+                tid,
+                last_run[*tid],
+                round
+            );
+        }
+    }
+
+    // The realtime thread should still get the lion's share.
+    let mut runs = [0usize; crate::arch::process::MAX_THREAD + 1];
+    for _ in 0..500 {
+        current = pick_thread(
+            mask,
+            current,
+            &priorities,
+            &mut skips,
+            SystemServices::find_next_thread,
+        );
+        runs[current] += 1;
+    }
+    assert!(runs[2] > 250);
+}
+
+#[test]
+fn process_priority_starvation() {
+    use crate::priority::{pick_process, set, STARVATION_LIMIT};
+    use xous_kernel::{ThreadPriority, PID};
+
+    // These tables are per-thread in hosted mode, so this doesn't need a kernel.
+    let busy = PID::new(7).unwrap();
+    let idle = PID::new(8).unwrap();
+    set(busy, 2, ThreadPriority::High).unwrap();
+
+    let ready = [(idle, 1 << 2), (busy, 1 << 2)];
+    let mut last_idle = 0;
+    let mut busy_runs = 0;
+    for round in 1..200 {
+        match pick_process(&ready).unwrap() {
+            pid if pid == idle => last_idle = round,
+            _ => busy_runs += 1,
+        }
+        assert!(round - last_idle <= STARVATION_LIMIT as usize + 1);
+    }
+    assert!(busy_runs > 150);
+
+    // Only ready threads count towards a process' priority.
+    let ready = [(idle, 1 << 2), (busy, 1 << 3)];
+    assert_eq!(pick_process(&ready), Some(idle));
+}
+
+#[test]
+fn unprivileged_priority_limit() {
+    use crate::priority::{get, reset, set};
+    use xous_kernel::{ThreadPriority, PID};
+
+    // A process spawned at runtime may lower its threads, but not raise them
+    // above `Normal`.
+    let pid = PID::new(9).unwrap();
+    reset(pid, false);
+    assert_eq!(set(pid, 2, ThreadPriority::Low), Ok(ThreadPriority::Normal));
+    assert_eq!(
+        set(pid, 2, ThreadPriority::High),
+        Err(xous_kernel::Error::AccessDenied)
+    );
+    assert_eq!(
+        set(pid, 2, ThreadPriority::Realtime),
+        Err(xous_kernel::Error::AccessDenied)
+    );
+    assert_eq!(get(pid, 2), ThreadPriority::Low);
+
+    reset(pid, true);
+    assert_eq!(
+        set(pid, 2, ThreadPriority::High),
+        Ok(ThreadPriority::Normal)
+    );
+}
+
+#[test]
+fn set_thread_priority() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let xous_process = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "set_thread_priority process",
+        move || {
+            use xous_kernel::ThreadPriority;
+            let tid = xous_kernel::current_tid().unwrap();
+            assert_eq!(
+                xous_kernel::set_thread_priority(tid, ThreadPriority::High),
+                Ok(ThreadPriority::Normal)
+            );
+            assert_eq!(
+                xous_kernel::set_thread_priority(tid, ThreadPriority::Realtime),
+                Ok(ThreadPriority::High)
+            );
+            assert_eq!(
+                xous_kernel::set_thread_priority(64, ThreadPriority::Low),
+                Err(xous_kernel::Error::ThreadNotAvailable)
+            );
+
+            // New threads start with the priority of their creator.
+            let (send, recv) = unbounded();
+            let child = xous_kernel::create_thread(move || {
+                let tid = xous_kernel::current_tid().unwrap();
+                send.send((
+                    tid,
+                    xous_kernel::set_thread_priority(tid, ThreadPriority::Normal),
+                ))
+                .unwrap();
+            })
+            .expect("couldn't spawn thread");
+            xous_kernel::wait_thread(child).expect("couldn't wait for thread");
+            let (child_tid, previous) = recv.recv().unwrap();
+            assert_eq!(previous, Ok(ThreadPriority::Realtime));
+
+            // A thread that has exited can no longer be adjusted.
+            assert_eq!(
+                xous_kernel::set_thread_priority(child_tid, ThreadPriority::Low),
+                Err(xous_kernel::Error::ThreadNotAvailable)
+            );
+        },
+    ))
+    .expect("couldn't start process");
+
+    xous_kernel::wait_process_as_thread(xous_process).expect("couldn't join process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
@@ -0,0 +1,70 @@
+//! The runner shared by the hosted mode test harnesses, such as `ui-test` and `net-test`.
+//!
+//! A harness sets up whatever its tests drive, and hands them to [`run`] along with its tag.
+//! Each result is logged between bookends, which `xtask` picks out of the log:
+//!
+//! ```text
+//! _|TT|_UITEST.PASS,main_menu,_|TE|_
+//! _|TT|_UITEST.FAIL,vault_new_item,timed out waiting for 'Add new item'_|TE|_
+//! _|TT|_UITEST.DONE,2,1_|TE|_
+//! ```
+
+/// The outcome of a test: why it failed, if it did
+pub type TestResult = Result<(), String>;
+
+/// A test, and the name it's reported and filtered by
+pub type Test<T> = (&'static str, fn(&mut T) -> TestResult);
+
+/// Runs `tests` in order on `context`, reports the results under `tag`, and then shuts the system
+/// down. If the environment variable `filter_env` is set, only the tests in its comma-separated
+/// list are run.
+pub fn run<T>(tag: &str, filter_env: &str, tests: &[Test<T>], context: &mut T) -> ! {
+    let filter = std::env::var(filter_env).ok();
+    let (mut passed, mut failed) = (0, 0);
+    for (name, test) in tests.iter() {
+        if let Some(filter) = &filter {
+            if !filter.split(',').any(|f| f.trim() == *name) {
+                continue;
+            }
+        }
+        log::info!("{}|running {}", tag, name);
+        match test(context) {
+            Ok(()) => {
+                passed += 1;
+                log::info!(
+                    "{}{}.PASS,{},{}",
+                    xous::BOOKEND_START,
+                    tag,
+                    name,
+                    xous::BOOKEND_END
+                );
+            }
+            Err(reason) => {
+                failed += 1;
+                // the report has to stay on one line of the log
+                let reason = reason.replace('\n', " ");
+                log::info!(
+                    "{}{}.FAIL,{},{}{}",
+                    xous::BOOKEND_START,
+                    tag,
+                    name,
+                    reason,
+                    xous::BOOKEND_END
+                );
+            }
+        }
+    }
+    log::info!(
+        "{}{}.DONE,{},{}{}",
+        xous::BOOKEND_START,
+        tag,
+        passed,
+        failed,
+        xous::BOOKEND_END
+    );
+
+    // give the log a moment to drain before everything goes away
+    ticktimer_server::Ticktimer::new().unwrap().sleep_ms(500).unwrap();
+    xous::rsyscall(xous::SysCall::Shutdown).expect("couldn't shut down");
+    xous::terminate_process(0)
+}
@@ -55,6 +55,19 @@ pub struct UserPrefs {
     pub headset_volume: u32,
     pub autotype_rate: usize,
     pub lefty_mode: bool,
+    /// Send DNS queries over TLS (RFC 7858) to `dns_tls_servers`, instead of in the clear to the
+    /// servers DHCP hands out.
+    pub dns_over_tls: bool,
+    /// Comma-separated `address[:port]#name` entries, where `name` is what the server's
+    /// certificate has to be issued to. Empty picks the resolver's built-in list.
+    pub dns_tls_servers: String,
+    /// Check DNSSEC signatures on every answer, up to the root zone's key. Names in zones that
+    /// aren't signed won't resolve while this is on.
+    ///
+    /// Denial of existence (NSEC and NSEC3) is not validated yet, so a forged "no such name"
+    /// or empty answer is still accepted. That can keep a name from resolving, but can't point
+    /// it somewhere else.
+    pub dnssec_validation: bool,
 }
 
 pub struct Manager {
@@ -105,6 +118,22 @@ impl Manager {
         }
     }
 
+    /// Calls `callback` whenever the preference `key`, named after its `UserPrefs` field, is
+    /// changed. The subscription lasts as long as this `Manager`.
+    pub fn watch(
+        &self,
+        key: &str,
+        callback: impl Fn() + 'static + Send + Sync,
+    ) -> Result<(), Error> {
+        self.pddb_handle.watch(
+            PREFS_DICT,
+            Some(key),
+            Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS),
+            move |_| callback(),
+        )?;
+        Ok(())
+    }
+
     pub fn store_i64(&self, value: i64, key: &str) -> Result<(), Error> {
         let offset_bytes = value.to_le_bytes();
 
@@ -0,0 +1,680 @@
+//! Stands in for the EC's half of the WLAN link in hosted mode, so the `net` service can
+//! exchange frames and take DHCP leases without any hardware.
+//!
+//! The EC is emulated at the level of the words that `main.rs` trades with it, so everything
+//! above this -- the `com` opcodes, `net`'s smoltcp device, its interrupt handling and the
+//! connection manager -- runs the same code that it does on a device. What sits at the far end
+//! of the link is pluggable, and is picked at startup from the environment:
+//!
+//! * `XOUS_NET_BACKEND=switch` connects the link to a virtual switch, where stand-ins for the
+//!   gateway and DNS server answer ARP, pings, DNS queries, and UDP and TCP echo (port 7)
+//! * `XOUS_NET_BACKEND=pcap` replays the frames in the capture named by `XOUS_NET_PCAP`
+//!   (pcap or pcapng, Ethernet only), with their recorded spacing, once the link comes up
+//! * `XOUS_NET_BACKEND=none`, or leaving it unset, keeps the old stub that has no link at all
+//! * `XOUS_NET_SCRIPT` names a script that sets up the network the link is on
+//!
+//! A network script has one setting per line:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! ssid xous-hosted               the access point that scans find, and joins always reach
+//! lease 10.0.2.15/24 gateway 10.0.2.2 dns 10.0.2.3 time 3600
+//!                                a DHCP lease; if there are several, each renewal (at half
+//!                                the lease time) hands out the next one
+//! host example.com 93.184.216.34 an A or AAAA record for the DNS stand-in
+//! device 00:11:22:33:44:55       the MAC of the device a capture was taken on: frames it sent
+//!                                aren't replayed, and frames to it are readdressed to us
+//! ```
+//!
+//! Without a script, the link is on the network in the first example `lease` line.
+
+mod pcap;
+mod switch;
+
+use com_rs::serdes::{Ipv4Conf, STR_32_WORDS, STR_64_WORDS};
+use com_rs::*;
+use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const BACKEND_ENV: &str = "XOUS_NET_BACKEND";
+pub const PCAP_ENV: &str = "XOUS_NET_PCAP";
+pub const SCRIPT_ENV: &str = "XOUS_NET_SCRIPT";
+
+/// The MAC that `main.rs` reports in hosted mode, whatever the EC says
+pub const MAC: [u8; 6] = [0, 0, 1, 0, 2, 0];
+/// Version tag reported to `com` and the connection manager
+const EC_SW_TAG: &str = "v0.9.9-0";
+/// Signal strength of the virtual access point
+const RSSI_DBM: u8 = 50;
+/// Time from associating to holding a lease
+const DHCP_DELAY: Duration = Duration::from_millis(200);
+/// Time an SSID scan takes
+const SCAN_DELAY: Duration = Duration::from_millis(500);
+/// How often the link is checked for frames that are due
+const TICK: Duration = Duration::from_millis(10);
+/// Received frames the EC holds on to before dropping them
+const RX_QUEUE_DEPTH: usize = 64;
+/// The frame send and fetch verbs carry the frame length in their low bits
+const FRAME_LEN_MASK: u16 = 0x07FF;
+/// Answer to a read when the EC has nothing to say
+const NO_DATA: u16 = 0xDEAD;
+
+/// The far end of the link, which gets the frames the device sends and comes up with frames
+/// for it to receive.
+pub trait Link: Send {
+    /// Takes a frame that the device sent while holding `lease`
+    fn send(&mut self, frame: &[u8], lease: &Lease);
+    /// Returns the next frame for the device, if one is due `uptime` after the link came up
+    fn receive(&mut self, uptime: Duration) -> Option<Vec<u8>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lease {
+    pub addr: Ipv4Addr,
+    pub prefix_len: u8,
+    pub gateway: Ipv4Addr,
+    pub dns: [Option<Ipv4Addr>; 2],
+    pub time: Duration,
+}
+impl Lease {
+    fn mask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::MAX.checked_shl(32 - self.prefix_len as u32).unwrap_or(0))
+    }
+    fn to_conf(self) -> Ipv4Conf {
+        let dns = |n: usize| self.dns[n].map(|a| a.octets()).unwrap_or([0; 4]);
+        Ipv4Conf {
+            dhcp: DhcpState::Bound,
+            mac: MAC,
+            addr: self.addr.octets(),
+            gtwy: self.gateway.octets(),
+            mask: self.mask().octets(),
+            dns1: dns(0),
+            dns2: dns(1),
+        }
+    }
+}
+impl Default for Lease {
+    fn default() -> Lease {
+        Lease {
+            addr: Ipv4Addr::new(10, 0, 2, 15),
+            prefix_len: 24,
+            gateway: Ipv4Addr::new(10, 0, 2, 2),
+            dns: [Some(Ipv4Addr::new(10, 0, 2, 3)), None],
+            time: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    pub ssid: String,
+    pub leases: Vec<Lease>,
+    pub hosts: Vec<(String, IpAddr)>,
+    pub device: Option<[u8; 6]>,
+}
+impl Default for Script {
+    fn default() -> Script {
+        Script { ssid: "xous-hosted".to_string(), leases: vec![], hosts: vec![], device: None }
+    }
+}
+impl Script {
+    pub fn parse(text: &str) -> Result<Script, String> {
+        let mut script = Script::default();
+        for (number, line) in text.lines().enumerate() {
+            script.parse_line(line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        if script.leases.is_empty() {
+            script.leases.push(Lease::default());
+        }
+        Ok(script)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+        let mut words = line.split_whitespace();
+        match words.next().unwrap() {
+            "ssid" => {
+                let ssid = line["ssid".len()..].trim();
+                if ssid.is_empty() || ssid.len() > 32 {
+                    return Err(format!("'{}' isn't a valid SSID", ssid));
+                }
+                self.ssid = ssid.to_string();
+            }
+            "lease" => {
+                let cidr = words.next().ok_or("lease needs an address")?;
+                let (addr, prefix_len) = cidr.split_once('/').ok_or("lease address needs a prefix length")?;
+                let mut lease = Lease {
+                    addr: parse(addr)?,
+                    prefix_len: parse(prefix_len)?,
+                    dns: [None, None],
+                    ..Lease::default()
+                };
+                if lease.prefix_len > 32 {
+                    return Err(format!("'{}' isn't a valid prefix length", prefix_len));
+                }
+                // unless it's given, the gateway is the first address on the network
+                lease.gateway = Ipv4Addr::from((u32::from(lease.addr) & u32::from(lease.mask())).wrapping_add(1));
+                while let Some(key) = words.next() {
+                    let value = words.next().ok_or_else(|| format!("'{}' needs a value", key))?;
+                    match key {
+                        "gateway" => lease.gateway = parse(value)?,
+                        "dns" => match lease.dns.iter_mut().find(|d| d.is_none()) {
+                            Some(slot) => *slot = Some(parse(value)?),
+                            None => return Err("a lease has at most two DNS servers".to_string()),
+                        },
+                        "time" => lease.time = Duration::from_secs(parse(value)?),
+                        _ => return Err(format!("unknown lease setting '{}'", key)),
+                    }
+                }
+                self.leases.push(lease);
+            }
+            "host" => match (words.next(), words.next(), words.next()) {
+                (Some(name), Some(addr), None) => {
+                    self.hosts.push((name.trim_end_matches('.').to_ascii_lowercase(), parse(addr)?))
+                }
+                _ => return Err("host needs a name and an address".to_string()),
+            },
+            "device" => match (words.next().map(parse_mac), words.next()) {
+                (Some(Some(mac)), None) => self.device = Some(mac),
+                _ => return Err("device needs a MAC address".to_string()),
+            },
+            other => return Err(format!("unknown setting '{}'", other)),
+        }
+        Ok(())
+    }
+}
+
+fn parse<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("'{}' isn't valid here", s))
+}
+
+fn parse_mac(s: &str) -> Option<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut parts = s.split(':');
+    for byte in mac.iter_mut() {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(mac)
+}
+
+/// Where the words sent to the EC are going, when they aren't commands
+#[derive(Debug, PartialEq)]
+enum Payload {
+    Command,
+    Skip(usize),
+    IntMask,
+    IntAck,
+    Frame { len: usize, data: Vec<u8> },
+}
+
+/// The state of the emulated EC's WLAN side
+pub struct Ec {
+    link: Box<dyn Link>,
+    script: Script,
+    payload: Payload,
+    readback: VecDeque<u16>,
+    int_mask: u16,
+    pending: u16,
+    rx: VecDeque<Vec<u8>>,
+    powered: bool,
+    /// When the link was associated
+    up_since: Option<Instant>,
+    /// Index into `script.leases` of the lease held, and when it's due for renewal
+    lease: Option<(usize, Instant)>,
+    scan_done: Option<Instant>,
+}
+
+impl Ec {
+    pub fn new(link: Box<dyn Link>, script: Script, now: Instant) -> Ec {
+        let mut ec = Ec {
+            link,
+            script,
+            payload: Payload::Command,
+            readback: VecDeque::new(),
+            int_mask: 0,
+            pending: 0,
+            rx: VecDeque::new(),
+            powered: true,
+            up_since: None,
+            lease: None,
+            scan_done: None,
+        };
+        // the virtual access point is the only one around, so there's no need to wait for a join
+        ec.associate(now);
+        ec
+    }
+
+    fn associate(&mut self, now: Instant) {
+        if self.powered && self.up_since.is_none() {
+            log::info!("COM|hosted: associated with {}", self.script.ssid);
+            self.up_since = Some(now);
+        }
+    }
+
+    fn disassociate(&mut self) {
+        if self.up_since.take().is_some() {
+            log::info!("COM|hosted: left {}", self.script.ssid);
+            self.lease = None;
+            self.rx.clear();
+            self.pending = (self.pending & !INT_WLAN_RX_READY) | INT_WLAN_DISCONNECT;
+        }
+    }
+
+    fn held_lease(&self) -> Option<&Lease> {
+        self.lease.map(|(index, _)| &self.script.leases[index])
+    }
+
+    fn config(&self) -> Ipv4Conf {
+        match self.held_lease() {
+            Some(lease) => lease.to_conf(),
+            None => Ipv4Conf { mac: MAC, ..Ipv4Conf::default() },
+        }
+    }
+
+    fn link_state(&self) -> LinkState {
+        match (self.powered, self.up_since) {
+            (false, _) => LinkState::ResetHold,
+            (true, Some(_)) => LinkState::Connected,
+            (true, None) => LinkState::Disconnected,
+        }
+    }
+
+    /// Moves time along to `now`, and returns true if that raised an interrupt.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let before = self.pending;
+        if let Some(up_since) = self.up_since {
+            match self.lease {
+                None if now >= up_since + DHCP_DELAY => {
+                    let lease = self.script.leases[0];
+                    log::info!("COM|hosted: leased {}/{}", lease.addr, lease.prefix_len);
+                    self.lease = Some((0, now + lease.time / 2));
+                    self.pending |= INT_WLAN_IPCONF_UPDATE;
+                }
+                Some((index, renew_at)) if now >= renew_at => {
+                    let index = (index + 1).min(self.script.leases.len() - 1);
+                    let lease = self.script.leases[index];
+                    log::info!("COM|hosted: renewed lease as {}/{}", lease.addr, lease.prefix_len);
+                    self.lease = Some((index, now + lease.time / 2));
+                    self.pending |= INT_WLAN_IPCONF_UPDATE;
+                }
+                _ => (),
+            }
+            if self.lease.is_some() {
+                while let Some(frame) = self.link.receive(now - up_since) {
+                    if self.rx.len() < RX_QUEUE_DEPTH {
+                        self.rx.push_back(frame);
+                    } else {
+                        log::warn!("COM|hosted: receive queue is full, dropping a frame");
+                    }
+                }
+            }
+        }
+        if let Some(done) = self.scan_done {
+            if now >= done {
+                self.scan_done = None;
+                self.pending |= INT_WLAN_SSID_UPDATE | INT_WLAN_SSID_FINISHED;
+            }
+        }
+        if !self.rx.is_empty() {
+            self.pending |= INT_WLAN_RX_READY;
+        }
+        (self.pending & !before) & self.int_mask != 0
+    }
+
+    /// Takes a word sent to the EC and returns the word read back, and whether an interrupt is
+    /// left to raise.
+    pub fn txrx(&mut self, tx: u16, now: Instant) -> (u16, bool) {
+        match std::mem::replace(&mut self.payload, Payload::Command) {
+            Payload::Command => (),
+            Payload::Skip(words) => {
+                if words > 1 {
+                    self.payload = Payload::Skip(words - 1);
+                }
+                return (NO_DATA, false);
+            }
+            Payload::IntMask => {
+                self.int_mask = tx;
+                return (NO_DATA, self.pending & self.int_mask != 0);
+            }
+            Payload::IntAck => {
+                self.pending &= !tx;
+                if !self.rx.is_empty() {
+                    self.pending |= INT_WLAN_RX_READY;
+                }
+                return (NO_DATA, self.pending & self.int_mask != 0);
+            }
+            Payload::Frame { len, mut data } => {
+                data.extend_from_slice(&tx.to_be_bytes());
+                if data.len() < len {
+                    self.payload = Payload::Frame { len, data };
+                } else {
+                    data.truncate(len);
+                    if let Some(lease) = self.up_since.and(self.held_lease().copied()) {
+                        self.link.send(&data, &lease);
+                        // the answer is usually ready straight away
+                        return (NO_DATA, self.poll(now));
+                    }
+                }
+                return (NO_DATA, false);
+            }
+        }
+
+        if tx == ComState::LINK_READ.verb {
+            return (self.readback.pop_front().unwrap_or(NO_DATA), false);
+        }
+        self.readback.clear();
+        match tx {
+            _ if tx & !FRAME_LEN_MASK == ComState::NET_FRAME_SEND_0.verb => {
+                let len = (tx & FRAME_LEN_MASK) as usize;
+                if len > 0 {
+                    self.payload = Payload::Frame { len, data: Vec::with_capacity(len + 1) };
+                }
+            }
+            _ if tx & !FRAME_LEN_MASK == ComState::NET_FRAME_FETCH_0.verb => {
+                let mut frame = self.rx.pop_front().unwrap_or_default();
+                frame.resize(((tx & FRAME_LEN_MASK) as usize + 1) & !1, 0);
+                self.readback.extend(frame.chunks(2).map(|w| u16::from_be_bytes([w[0], w[1]])));
+                if self.rx.is_empty() {
+                    self.pending &= !INT_WLAN_RX_READY;
+                }
+            }
+            _ if tx == ComState::LINK_GET_INTERRUPT.verb => {
+                let rxlen = self.rx.front().map(|f| f.len() as u16).unwrap_or(0);
+                self.readback.extend([self.pending & self.int_mask, rxlen].iter());
+            }
+            _ if tx == ComState::LINK_ACK_INTERRUPT.verb => self.payload = Payload::IntAck,
+            _ if tx == ComState::LINK_SET_INTMASK.verb => self.payload = Payload::IntMask,
+            _ if tx == ComState::LINK_GET_INTMASK.verb => self.readback.push_back(self.int_mask),
+            _ if tx == ComState::EC_GIT_REV.verb => self.readback.extend([0, 0, 0].iter()),
+            _ if tx == ComState::EC_SW_TAG.verb => {
+                self.readback.push_back(EC_SW_TAG.len() as u16);
+                self.readback.extend(string_words(EC_SW_TAG, ComState::EC_SW_TAG.r_words as usize - 1));
+            }
+            // followed by two 64-bit seeds
+            _ if tx == ComState::TRNG_SEED.verb => self.payload = Payload::Skip(8),
+            _ if tx == ComState::WLAN_ON.verb => {
+                self.powered = true;
+                self.associate(now);
+            }
+            _ if tx == ComState::WLAN_OFF.verb => {
+                self.disassociate();
+                self.powered = false;
+            }
+            _ if tx == ComState::WLAN_SET_SSID.verb => self.payload = Payload::Skip(STR_32_WORDS),
+            _ if tx == ComState::WLAN_SET_PASS.verb => self.payload = Payload::Skip(STR_64_WORDS),
+            _ if tx == ComState::WLAN_JOIN.verb => self.associate(now),
+            _ if tx == ComState::WLAN_LEAVE.verb => self.disassociate(),
+            _ if tx == ComState::WLAN_GET_RSSI.verb => self.readback.push_back(self.rssi()),
+            _ if tx == ComState::WLAN_SYNC_STATE.verb => {
+                let dhcp = self.config().encode_u16()[0];
+                self.readback.extend([self.link_state() as u16, dhcp].iter());
+            }
+            _ if tx == ComState::WLAN_GET_IPV4_CONF.verb => self.readback.extend(self.config().encode_u16().iter()),
+            _ if tx == ComState::WLAN_BIN_STATUS.verb => {
+                self.readback.extend([self.rssi(), self.link_state() as u16].iter());
+                self.readback.extend(self.config().encode_u16().iter());
+                let ssid = if self.up_since.is_some() { self.script.ssid.as_str() } else { "" };
+                self.readback.push_back(ssid.len() as u16);
+                self.readback.extend(string_words(ssid, 16));
+            }
+            _ if tx == ComState::SSID_SCAN_ON.verb => self.scan_done = Some(now + SCAN_DELAY),
+            _ if tx == ComState::SSID_SCAN_OFF.verb => self.scan_done = None,
+            _ if tx == ComState::SSID_FETCH_STR.verb => {
+                // eight records of an RSSI byte, a length byte and 32 bytes of name
+                let ssid = &self.script.ssid;
+                self.readback.push_back(u16::from_le_bytes([RSSI_DBM, ssid.len() as u8]));
+                self.readback.extend(string_words(ssid, 16));
+                self.readback.extend([0; 7 * 17].iter());
+            }
+            _ => log::trace!("COM|hosted: ignoring EC command 0x{:04x}", tx),
+        }
+        (NO_DATA, false)
+    }
+
+    fn rssi(&self) -> u16 {
+        if self.up_since.is_some() {
+            110 - RSSI_DBM as u16
+        } else {
+            0xFF00
+        }
+    }
+}
+
+/// Packs a string into `words` little-endian words, padded with zeroes
+fn string_words(s: &str, words: usize) -> impl Iterator<Item = u16> + '_ {
+    s.as_bytes()
+        .chunks(2)
+        .map(|c| u16::from_le_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+        .chain(std::iter::repeat(0))
+        .take(words)
+}
+
+#[derive(Clone, Debug)]
+pub enum Backend {
+    Switch,
+    Pcap(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub struct HostedNetConfig {
+    pub backend: Backend,
+    pub script: Option<PathBuf>,
+}
+impl HostedNetConfig {
+    /// Returns the configuration if a link backend was selected, or `None` if the EC should
+    /// stay a stub.
+    pub fn from_env() -> Option<HostedNetConfig> {
+        let backend = match std::env::var(BACKEND_ENV).ok().as_deref() {
+            None | Some("none") => return None,
+            Some("switch") => Backend::Switch,
+            Some("pcap") => Backend::Pcap(PathBuf::from(
+                std::env::var(PCAP_ENV).unwrap_or_else(|_| panic!("{} needs {} to be set", BACKEND_ENV, PCAP_ENV)),
+            )),
+            Some(other) => panic!("Unknown network backend '{}': expected none, switch or pcap", other),
+        };
+        Some(HostedNetConfig { backend, script: std::env::var(SCRIPT_ENV).ok().map(PathBuf::from) })
+    }
+}
+
+/// The emulated EC, and what it needs to raise interrupts
+pub struct HostedNet {
+    ec: Arc<Mutex<Ec>>,
+    llio: llio::Llio,
+}
+impl HostedNet {
+    pub fn new(config: HostedNetConfig) -> HostedNet {
+        let script = match &config.script {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| Script::parse(&text))
+                .unwrap_or_else(|e| panic!("COM|hosted: bad network script {}: {}", path.display(), e)),
+            None => Script::parse("").unwrap(),
+        };
+        let link: Box<dyn Link> = match &config.backend {
+            Backend::Switch => Box::new(switch::Switch::new(script.hosts.clone())),
+            Backend::Pcap(path) => Box::new(
+                pcap::Replay::open(path, script.device)
+                    .unwrap_or_else(|e| panic!("COM|hosted: can't replay {}: {}", path.display(), e)),
+            ),
+        };
+        log::info!("COM|hosted: WLAN link is {:?}", config.backend);
+        let ec = Arc::new(Mutex::new(Ec::new(link, script, Instant::now())));
+        std::thread::Builder::new()
+            .name("hosted_net".into())
+            .spawn({
+                let ec = Arc::clone(&ec);
+                move || {
+                    let xns = xous_names::XousNames::new().unwrap();
+                    let llio = llio::Llio::new(&xns);
+                    loop {
+                        std::thread::sleep(TICK);
+                        let raise = ec.lock().unwrap().poll(Instant::now());
+                        if raise {
+                            llio.com_event_raise().ok();
+                        }
+                    }
+                }
+            })
+            .unwrap();
+        let xns = xous_names::XousNames::new().unwrap();
+        HostedNet { ec, llio: llio::Llio::new(&xns) }
+    }
+
+    pub fn txrx(&mut self, tx: u16) -> u16 {
+        let (rx, raise) = self.ec.lock().unwrap().txrx(tx, Instant::now());
+        if raise {
+            self.llio.com_event_raise().ok();
+        }
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A link that echoes every frame back
+    struct Mirror(VecDeque<Vec<u8>>);
+    impl Link for Mirror {
+        fn send(&mut self, frame: &[u8], _lease: &Lease) {
+            self.0.push_back(frame.to_vec());
+        }
+        fn receive(&mut self, _uptime: Duration) -> Option<Vec<u8>> {
+            self.0.pop_front()
+        }
+    }
+
+    fn read(ec: &mut Ec, verb: u16, words: usize, now: Instant) -> Vec<u16> {
+        ec.txrx(verb, now);
+        (0..words).map(|_| ec.txrx(ComState::LINK_READ.verb, now).0).collect()
+    }
+
+    fn read_config(ec: &mut Ec, now: Instant) -> Ipv4Conf {
+        let mut raw = Ipv4Conf::default().encode_u16();
+        let words = read(ec, ComState::WLAN_GET_IPV4_CONF.verb, raw.len(), now);
+        raw.copy_from_slice(&words);
+        Ipv4Conf::decode_u16(&raw)
+    }
+
+    #[test]
+    fn scripts_parse() {
+        let script = Script::parse(
+            "# a network\n\
+             ssid test net\n\
+             lease 192.168.1.20/24 gateway 192.168.1.1 dns 192.168.1.1 dns 9.9.9.9 time 60\n\
+             lease 192.168.1.21/16\n\
+             host Example.com. 93.184.216.34\n\
+             host example.com 2001:db8::1\n\
+             device 00:11:22:aa:bb:cc\n",
+        )
+        .unwrap();
+        assert_eq!(script.ssid, "test net");
+        assert_eq!(script.leases.len(), 2);
+        assert_eq!(script.leases[0].dns, [Some(Ipv4Addr::new(192, 168, 1, 1)), Some(Ipv4Addr::new(9, 9, 9, 9))]);
+        assert_eq!(script.leases[0].time, Duration::from_secs(60));
+        assert_eq!(script.leases[1].mask(), Ipv4Addr::new(255, 255, 0, 0));
+        assert_eq!(script.leases[1].gateway, Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(script.leases[1].dns, [None, None]);
+        assert_eq!(script.hosts[0], ("example.com".to_string(), "93.184.216.34".parse().unwrap()));
+        assert_eq!(script.hosts[1].1, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(script.device, Some([0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc]));
+
+        assert_eq!(Script::parse("").unwrap().leases, vec![Lease::default()]);
+        assert!(Script::parse("lease 10.0.0.2").is_err());
+        assert!(Script::parse("lease 10.0.0.2/33").is_err());
+        assert!(Script::parse("lease 10.0.0.2/8 dns 1.1.1.1 dns 1.0.0.1 dns 8.8.8.8").is_err());
+        assert!(Script::parse("host example.com").is_err());
+        assert!(Script::parse("device 00:11:22:33:44").is_err());
+        assert!(Script::parse("dance").is_err());
+    }
+
+    #[test]
+    fn leases_and_renews() {
+        let script = Script::parse("lease 10.1.0.2/24 time 10\nlease 10.1.0.3/24 time 10").unwrap();
+        let start = Instant::now();
+        let mut ec = Ec::new(Box::new(Mirror(VecDeque::new())), script, start);
+        ec.txrx(ComState::LINK_SET_INTMASK.verb, start);
+        ec.txrx(INT_WLAN_IPCONF_UPDATE | INT_WLAN_RX_READY, start);
+
+        assert!(!ec.poll(start));
+        assert_eq!(read_config(&mut ec, start).addr, [0; 4]);
+        assert!(ec.poll(start + DHCP_DELAY));
+        assert_eq!(read(&mut ec, ComState::LINK_GET_INTERRUPT.verb, 2, start), vec![INT_WLAN_IPCONF_UPDATE, 0]);
+        let conf = read_config(&mut ec, start);
+        assert_eq!((conf.dhcp, conf.mac, conf.addr), (DhcpState::Bound, MAC, [10, 1, 0, 2]));
+        ec.txrx(ComState::LINK_ACK_INTERRUPT.verb, start);
+        assert_eq!(ec.txrx(INT_WLAN_IPCONF_UPDATE, start), (NO_DATA, false));
+
+        // renewal comes at half the lease time, and moves on to the next lease
+        assert!(!ec.poll(start + DHCP_DELAY + Duration::from_secs(4)));
+        assert!(ec.poll(start + DHCP_DELAY + Duration::from_secs(5)));
+        let conf = read_config(&mut ec, start);
+        assert_eq!(conf.addr, [10, 1, 0, 3]);
+
+        // leaving takes the lease away
+        ec.txrx(ComState::WLAN_LEAVE.verb, start);
+        let conf = read_config(&mut ec, start);
+        assert_eq!((conf.addr, conf.mac), ([0; 4], MAC));
+        assert_eq!(read(&mut ec, ComState::WLAN_SYNC_STATE.verb, 1, start), vec![LinkState::Disconnected as u16]);
+    }
+
+    #[test]
+    fn frames_go_both_ways() {
+        let start = Instant::now();
+        let mut ec = Ec::new(Box::new(Mirror(VecDeque::new())), Script::parse("").unwrap(), start);
+        ec.txrx(ComState::LINK_SET_INTMASK.verb, start);
+        ec.txrx(INT_WLAN_RX_READY, start);
+
+        // frames sent before there's a lease go nowhere
+        ec.txrx(ComState::NET_FRAME_SEND_0.verb | 2, start);
+        ec.txrx(0x0102, start);
+        assert!(!ec.poll(start));
+
+        let now = start + DHCP_DELAY;
+        ec.poll(now);
+        ec.txrx(ComState::NET_FRAME_SEND_0.verb | 3, now);
+        assert_eq!(ec.txrx(0x0102, now), (NO_DATA, false));
+        assert_eq!(ec.txrx(0x0300, now), (NO_DATA, true));
+        ec.txrx(ComState::NET_FRAME_SEND_0.verb | 1, now);
+        ec.txrx(0x0400, now);
+        assert_eq!(read(&mut ec, ComState::LINK_GET_INTERRUPT.verb, 2, now), vec![INT_WLAN_RX_READY, 3]);
+        assert_eq!(read(&mut ec, ComState::NET_FRAME_FETCH_0.verb | 3, 2, now), vec![0x0102, 0x0300]);
+        // the interrupt comes straight back while there are frames left
+        ec.txrx(ComState::LINK_ACK_INTERRUPT.verb, now);
+        assert_eq!(ec.txrx(INT_WLAN_RX_READY, now), (NO_DATA, true));
+        assert_eq!(read(&mut ec, ComState::LINK_GET_INTERRUPT.verb, 2, now), vec![INT_WLAN_RX_READY, 1]);
+        assert_eq!(read(&mut ec, ComState::NET_FRAME_FETCH_0.verb | 1, 1, now), vec![0x0400]);
+        ec.txrx(ComState::LINK_ACK_INTERRUPT.verb, now);
+        assert_eq!(ec.txrx(INT_WLAN_RX_READY, now), (NO_DATA, false));
+        assert_eq!(read(&mut ec, ComState::LINK_GET_INTERRUPT.verb, 2, now), vec![0, 0]);
+    }
+
+    #[test]
+    fn scans_find_the_ssid() {
+        let start = Instant::now();
+        let mut ec = Ec::new(Box::new(Mirror(VecDeque::new())), Script::parse("ssid abc").unwrap(), start);
+        ec.txrx(ComState::LINK_SET_INTMASK.verb, start);
+        ec.txrx(INT_WLAN_SSID_FINISHED, start);
+        ec.txrx(ComState::SSID_SCAN_ON.verb, start);
+        assert!(!ec.poll(start));
+        assert!(ec.poll(start + SCAN_DELAY));
+        let records = read(&mut ec, ComState::SSID_FETCH_STR.verb, 8 * 17, start);
+        assert_eq!(records[0..3], [u16::from_le_bytes([RSSI_DBM, 3]), 0x6261, 0x0063]);
+        assert!(records[3..].iter().all(|&w| w == 0));
+        // payload words aren't commands, even if they look like one
+        ec.txrx(ComState::TRNG_SEED.verb, start);
+        for _ in 0..8 {
+            ec.txrx(ComState::SSID_SCAN_ON.verb, start);
+        }
+        assert!(!ec.poll(start + SCAN_DELAY * 2));
+    }
+}
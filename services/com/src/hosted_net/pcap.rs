@@ -0,0 +1,285 @@
+//! Replays the frames in a capture as though they had come in over the link, keeping the spacing
+//! they were captured with. Both the classic pcap format and pcapng are read, as long as the
+//! frames are Ethernet.
+
+use super::{Lease, Link, MAC};
+use crate::api::NET_MTU;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::path::Path;
+use std::time::Duration;
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const ETHERNET_HEADER_LEN: usize = 14;
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_OPTION_TSRESOL: u16 = 9;
+const PCAPNG_OPTION_END: u16 = 0;
+
+pub struct Replay {
+    /// Frames, and when they're due relative to the link coming up
+    frames: VecDeque<(Duration, Vec<u8>)>,
+}
+
+impl Replay {
+    /// Loads a capture. If the capture was taken on a device with the MAC `device`, the frames
+    /// that it sent are left out, and the frames sent to it are readdressed to us.
+    pub fn open(path: &Path, device: Option<[u8; 6]>) -> Result<Replay, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        Ok(Replay::new(read_capture(&bytes)?, device))
+    }
+
+    fn new(frames: Vec<(Duration, Vec<u8>)>, device: Option<[u8; 6]>) -> Replay {
+        let start = frames.first().map(|(time, _)| *time).unwrap_or_default();
+        let mut replay = VecDeque::new();
+        for (time, mut frame) in frames {
+            if frame.len() < ETHERNET_HEADER_LEN || frame.len() > NET_MTU {
+                log::warn!("COM|hosted: not replaying a {}-byte frame", frame.len());
+                continue;
+            }
+            if let Some(device) = device {
+                if frame[6..12] == device {
+                    continue;
+                }
+                if frame[0..6] == device {
+                    frame[0..6].copy_from_slice(&MAC);
+                }
+            }
+            replay.push_back((time.checked_sub(start).unwrap_or_default(), frame));
+        }
+        log::info!("COM|hosted: replaying {} frames", replay.len());
+        Replay { frames: replay }
+    }
+}
+
+impl Link for Replay {
+    fn send(&mut self, frame: &[u8], _lease: &Lease) {
+        log::trace!("COM|hosted: replay ignores sent frame {:x?}", frame);
+    }
+
+    fn receive(&mut self, uptime: Duration) -> Option<Vec<u8>> {
+        if self.frames.front()?.0 <= uptime {
+            self.frames.pop_front().map(|(_, frame)| frame)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the time and contents of each frame in a pcap or pcapng capture.
+pub fn read_capture(bytes: &[u8]) -> Result<Vec<(Duration, Vec<u8>)>, String> {
+    if bytes.len() < 4 {
+        Err("the capture is empty".to_string())
+    } else if u32::from_le_bytes(bytes[0..4].try_into().unwrap()) == PCAPNG_SECTION_HEADER {
+        read_pcapng(bytes)
+    } else {
+        read_pcap(bytes)
+    }
+}
+
+/// Reads a word at `at`, if there's one there.
+fn word(bytes: &[u8], at: usize, big_endian: bool) -> Result<u32, String> {
+    let raw = bytes.get(at..at + 4).ok_or("the capture is truncated")?.try_into().unwrap();
+    Ok(if big_endian { u32::from_be_bytes(raw) } else { u32::from_le_bytes(raw) })
+}
+
+fn half_word(bytes: &[u8], at: usize, big_endian: bool) -> Result<u16, String> {
+    let raw = bytes.get(at..at + 2).ok_or("the capture is truncated")?.try_into().unwrap();
+    Ok(if big_endian { u16::from_be_bytes(raw) } else { u16::from_le_bytes(raw) })
+}
+
+fn read_pcap(bytes: &[u8]) -> Result<Vec<(Duration, Vec<u8>)>, String> {
+    let (big_endian, nanos) = match word(bytes, 0, false)? {
+        0xA1B2_C3D4 => (false, false),
+        0xA1B2_3C4D => (false, true),
+        0xD4C3_B2A1 => (true, false),
+        0x4D3C_B2A1 => (true, true),
+        _ => return Err("this isn't a pcap or pcapng capture".to_string()),
+    };
+    let linktype = word(bytes, 20, big_endian)? & 0xFFFF;
+    if linktype != LINKTYPE_ETHERNET {
+        return Err(format!("the capture has link type {}, not Ethernet", linktype));
+    }
+    let mut frames = Vec::new();
+    let mut at = 24;
+    while at < bytes.len() {
+        let secs = word(bytes, at, big_endian)?;
+        let fraction = word(bytes, at + 4, big_endian)?;
+        let len = word(bytes, at + 8, big_endian)? as usize;
+        let frame = bytes.get(at + 16..at + 16 + len).ok_or("the capture is truncated")?;
+        let nanos = if nanos { fraction } else { fraction.saturating_mul(1000) };
+        frames.push((Duration::new(secs as u64, nanos), frame.to_vec()));
+        at += 16 + len;
+    }
+    Ok(frames)
+}
+
+fn read_pcapng(bytes: &[u8]) -> Result<Vec<(Duration, Vec<u8>)>, String> {
+    let mut frames = Vec::new();
+    let mut big_endian = false;
+    // the timestamp ticks per second of each interface in the section
+    let mut interfaces: Vec<u64> = Vec::new();
+    let mut at = 0;
+    while at < bytes.len() {
+        let kind = word(bytes, at, big_endian)?;
+        if kind == PCAPNG_SECTION_HEADER {
+            big_endian = match word(bytes, at + 8, false)? {
+                PCAPNG_BYTE_ORDER_MAGIC => false,
+                magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+                _ => return Err("a pcapng section header is corrupt".to_string()),
+            };
+            interfaces.clear();
+        }
+        let len = word(bytes, at + 4, big_endian)? as usize;
+        if len < 12 || len & 3 != 0 {
+            return Err(format!("a pcapng block has a length of {}", len));
+        }
+        let block = bytes.get(at..at + len).ok_or("the capture is truncated")?;
+        match kind {
+            PCAPNG_INTERFACE_DESCRIPTION => {
+                let linktype = half_word(block, 8, big_endian)? as u32;
+                if linktype != LINKTYPE_ETHERNET {
+                    return Err(format!("capture interface {} has link type {}, not Ethernet", interfaces.len(), linktype));
+                }
+                interfaces.push(ticks_per_second(&block[..len - 4], big_endian)?);
+            }
+            PCAPNG_ENHANCED_PACKET => {
+                let interface = word(block, 8, big_endian)? as usize;
+                let ticks = (word(block, 12, big_endian)? as u64) << 32 | word(block, 16, big_endian)? as u64;
+                let captured = word(block, 20, big_endian)? as usize;
+                let frame = block.get(28..28 + captured).ok_or("a pcapng packet is truncated")?;
+                let rate = *interfaces.get(interface).ok_or("a pcapng packet is from an unknown interface")?;
+                let nanos = (ticks % rate) as u128 * 1_000_000_000 / rate as u128;
+                frames.push((Duration::new(ticks / rate, nanos as u32), frame.to_vec()));
+            }
+            // statistics, name resolution and the like don't matter for a replay
+            _ => (),
+        }
+        at += len;
+    }
+    Ok(frames)
+}
+
+/// Finds the timestamp resolution in the options of an interface description block, which
+/// is a microsecond unless it says otherwise.
+fn ticks_per_second(block: &[u8], big_endian: bool) -> Result<u64, String> {
+    let mut at = 16;
+    while at + 4 <= block.len() {
+        let code = half_word(block, at, big_endian)?;
+        let len = half_word(block, at + 2, big_endian)? as usize;
+        if code == PCAPNG_OPTION_END {
+            break;
+        }
+        if code == PCAPNG_OPTION_TSRESOL {
+            let resolution = *block.get(at + 4).ok_or("a pcapng interface is truncated")?;
+            let ticks = if resolution & 0x80 == 0 {
+                10u64.checked_pow(resolution as u32)
+            } else {
+                2u64.checked_pow((resolution & 0x7F) as u32)
+            };
+            return ticks.ok_or_else(|| format!("a pcapng interface has a resolution of {:#x}", resolution));
+        }
+        at += 4 + ((len + 3) & !3);
+    }
+    Ok(1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(dst: u8, src: u8) -> Vec<u8> {
+        let mut frame = vec![0u8; 60];
+        frame[0..6].copy_from_slice(&[2, 0, 0, 0, 0, dst]);
+        frame[6..12].copy_from_slice(&[2, 0, 0, 0, 0, src]);
+        frame[12..14].copy_from_slice(&[0x08, 0x00]);
+        frame
+    }
+
+    fn pcap(big_endian: bool, frames: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let w = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let mut bytes = Vec::new();
+        for v in [0xA1B2_C3D4, 0x0004_0002, 0, 0, 65535, LINKTYPE_ETHERNET].iter() {
+            bytes.extend_from_slice(&w(*v));
+        }
+        for (secs, micros, frame) in frames {
+            for v in [*secs, *micros, frame.len() as u32, frame.len() as u32].iter() {
+                bytes.extend_from_slice(&w(*v));
+            }
+            bytes.extend_from_slice(frame);
+        }
+        bytes
+    }
+
+    fn block(kind: u32, body: &[u8]) -> Vec<u8> {
+        let len = (12 + body.len() + 3) & !3;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&kind.to_le_bytes());
+        bytes.extend_from_slice(&(len as u32).to_le_bytes());
+        bytes.extend_from_slice(body);
+        bytes.resize(len - 4, 0);
+        bytes.extend_from_slice(&(len as u32).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn reads_pcap() {
+        let first = frame(1, 2);
+        let second = frame(2, 1);
+        for &big_endian in [false, true].iter() {
+            let bytes = pcap(big_endian, &[(100, 999_999, &first), (101, 500_000, &second)]);
+            let frames = read_capture(&bytes).unwrap();
+            assert_eq!(frames, vec![
+                (Duration::new(100, 999_999_000), first.clone()),
+                (Duration::new(101, 500_000_000), second.clone()),
+            ]);
+        }
+        let mut truncated = pcap(false, &[(0, 0, &first)]);
+        truncated.pop();
+        assert!(read_capture(&truncated).is_err());
+        assert!(read_capture(b"not a capture").is_err());
+    }
+
+    #[test]
+    fn reads_pcapng() {
+        let first = frame(1, 2);
+        let mut bytes = block(PCAPNG_SECTION_HEADER, &[0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        // nanosecond timestamps
+        bytes.extend(block(PCAPNG_INTERFACE_DESCRIPTION, &[1, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0]));
+        let ticks: u64 = 5_000_000_123;
+        let mut packet = Vec::new();
+        for v in [0, (ticks >> 32) as u32, ticks as u32, first.len() as u32, first.len() as u32].iter() {
+            packet.extend_from_slice(&v.to_le_bytes());
+        }
+        packet.extend_from_slice(&first);
+        bytes.extend(block(PCAPNG_ENHANCED_PACKET, &packet));
+        // a block that doesn't matter
+        bytes.extend(block(5, &[0; 8]));
+        assert_eq!(read_capture(&bytes).unwrap(), vec![(Duration::new(5, 123), first)]);
+
+        // a packet with no interface to say what it is
+        let mut orphan = bytes[..28].to_vec();
+        orphan.extend(block(PCAPNG_ENHANCED_PACKET, &packet));
+        assert!(read_capture(&orphan).is_err());
+    }
+
+    #[test]
+    fn replays_on_time() {
+        let ours = [2, 0, 0, 0, 0, 1];
+        let frames = vec![
+            (Duration::from_secs(10), frame(1, 9)),
+            (Duration::from_millis(10_500), frame(9, 1)),
+            (Duration::from_secs(12), frame(0xFF, 9)),
+        ];
+        let mut replay = Replay::new(frames, Some(ours));
+        assert_eq!(replay.frames.len(), 2);
+        let first = replay.receive(Duration::from_secs(0)).unwrap();
+        assert_eq!(first[0..6], MAC);
+        assert_eq!(replay.receive(Duration::from_millis(1999)), None);
+        assert_eq!(replay.receive(Duration::from_secs(2)).unwrap()[5], 0xFF);
+        assert_eq!(replay.receive(Duration::from_secs(100)), None);
+    }
+}
@@ -0,0 +1,509 @@
+//! A virtual switch with stand-ins for the hosts that a device expects on its network. The
+//! gateway and DNS servers of the lease answer ARP and pings, and echo UDP and TCP sent to port 7;
+//! the DNS servers also answer queries for the hosts in the network script. TCP connections to
+//! other ports are refused, and everything else is dropped.
+
+use super::{Lease, Link};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4};
+use std::time::Duration;
+
+/// The MAC that the stand-ins all answer from
+pub const STAND_IN_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ARP_REQUEST: u16 = 1;
+const ARP_REPLY: u16 = 2;
+const IP_PROTOCOL_ICMP: u8 = 1;
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const DNS_PORT: u16 = 53;
+const ECHO_PORT: u16 = 7;
+
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_AAAA: u16 = 28;
+const DNS_TYPE_ANY: u16 = 255;
+const DNS_CLASS_IN: u16 = 1;
+const DNS_TTL: u32 = 60;
+const DNS_RCODE_NXDOMAIN: u16 = 3;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+/// The sequence number the TCP stand-in starts every connection from
+const TCP_ISN: u32 = 0x5EED_0000;
+/// The receive window the TCP stand-in advertises
+const TCP_WINDOW: u16 = 8192;
+
+/// A connection to the TCP echo stand-in. Nothing is lost on the switch, so the stand-in never has
+/// to send anything again, and only keeps track of where each side has got to.
+struct TcpConnection {
+    /// Sequence number of the next byte the stand-in sends
+    seq: u32,
+    /// Sequence number of the next byte expected from the device
+    ack: u32,
+    /// Whether the stand-in has closed its side
+    fin_sent: bool,
+}
+
+pub struct Switch {
+    /// The names the DNS stand-in knows about
+    hosts: Vec<(String, IpAddr)>,
+    /// Open TCP connections, by the device's end and then the stand-in's
+    connections: HashMap<(SocketAddrV4, SocketAddrV4), TcpConnection>,
+    outbox: VecDeque<Vec<u8>>,
+}
+
+impl Switch {
+    pub fn new(hosts: Vec<(String, IpAddr)>) -> Switch {
+        Switch { hosts, connections: HashMap::new(), outbox: VecDeque::new() }
+    }
+
+    /// The frame a stand-in sends back in answer to `frame`, if any.
+    fn answer(&mut self, frame: &[u8], lease: &Lease) -> Option<Vec<u8>> {
+        let ethertype = u16::from_be_bytes(frame.get(12..14)?.try_into().unwrap());
+        let reply = match ethertype {
+            ETHERTYPE_ARP => arp_reply(&frame[14..], lease)?,
+            ETHERTYPE_IPV4 => self.ipv4_reply(&frame[14..], lease)?,
+            _ => return None,
+        };
+        let mut answer = Vec::with_capacity(14 + reply.len());
+        answer.extend_from_slice(&frame[6..12]);
+        answer.extend_from_slice(&STAND_IN_MAC);
+        answer.extend_from_slice(&ethertype.to_be_bytes());
+        answer.extend_from_slice(&reply);
+        Some(answer)
+    }
+
+    fn ipv4_reply(&mut self, packet: &[u8], lease: &Lease) -> Option<Vec<u8>> {
+        let header_len = (*packet.first()? as usize & 0x0F) * 4;
+        let total_len = u16::from_be_bytes(packet.get(2..4)?.try_into().unwrap()) as usize;
+        if packet[0] >> 4 != 4 || header_len < 20 || total_len < header_len {
+            return None;
+        }
+        let body = packet.get(header_len..total_len)?;
+        let src = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+        let dst = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+        if dst != lease.gateway && !lease.dns.contains(&Some(dst)) {
+            return None;
+        }
+        match packet[9] {
+            IP_PROTOCOL_ICMP if body.first() == Some(&ICMP_ECHO_REQUEST) && body.len() >= 8 => {
+                let mut reply = body.to_vec();
+                reply[0] = ICMP_ECHO_REPLY;
+                reply[2..4].copy_from_slice(&[0, 0]);
+                let sum = checksum(&reply, 0);
+                reply[2..4].copy_from_slice(&sum.to_be_bytes());
+                Some(ipv4(dst, src, IP_PROTOCOL_ICMP, &reply))
+            }
+            IP_PROTOCOL_UDP if body.len() >= 8 => {
+                let src_port = u16::from_be_bytes(body[0..2].try_into().unwrap());
+                let dst_port = u16::from_be_bytes(body[2..4].try_into().unwrap());
+                let data = body.get(8..u16::from_be_bytes(body[4..6].try_into().unwrap()) as usize)?;
+                let reply = match dst_port {
+                    DNS_PORT if lease.dns.contains(&Some(dst)) => self.dns_reply(data)?,
+                    ECHO_PORT => data.to_vec(),
+                    _ => return None,
+                };
+                Some(ipv4(dst, src, IP_PROTOCOL_UDP, &udp(dst, src, dst_port, src_port, &reply)))
+            }
+            IP_PROTOCOL_TCP => Some(ipv4(dst, src, IP_PROTOCOL_TCP, &self.tcp_reply(src, dst, body)?)),
+            _ => None,
+        }
+    }
+
+    /// Answers a TCP segment from `src` to the stand-in at `dst`: the echo port accepts
+    /// connections, sends back whatever it gets, and closes its side when the device does.
+    fn tcp_reply(&mut self, src: Ipv4Addr, dst: Ipv4Addr, segment: &[u8]) -> Option<Vec<u8>> {
+        let src_port = u16::from_be_bytes(segment.get(0..2)?.try_into().unwrap());
+        let dst_port = u16::from_be_bytes(segment.get(2..4)?.try_into().unwrap());
+        let seq = u32::from_be_bytes(segment.get(4..8)?.try_into().unwrap());
+        let ack = u32::from_be_bytes(segment.get(8..12)?.try_into().unwrap());
+        let flags = *segment.get(13)?;
+        let data = segment.get((segment[12] >> 4) as usize * 4..)?;
+        let reply = |seq: u32, ack: u32, flags: u8, data: &[u8]| tcp(dst, src, dst_port, src_port, seq, ack, flags, data);
+        let key = (SocketAddrV4::new(src, src_port), SocketAddrV4::new(dst, dst_port));
+
+        if flags & TCP_RST != 0 {
+            self.connections.remove(&key);
+            return None;
+        }
+        if flags & TCP_SYN != 0 {
+            if dst_port != ECHO_PORT {
+                return Some(reply(0, seq.wrapping_add(1), TCP_RST | TCP_ACK, &[]));
+            }
+            // a SYN that's sent again gets the same answer as the first one
+            let connection = self.connections.entry(key).or_insert(TcpConnection {
+                seq: TCP_ISN.wrapping_add(1),
+                ack: seq.wrapping_add(1),
+                fin_sent: false,
+            });
+            return Some(reply(TCP_ISN, connection.ack, TCP_SYN | TCP_ACK, &[]));
+        }
+        let connection = match self.connections.get_mut(&key) {
+            Some(connection) => connection,
+            None if flags & TCP_ACK != 0 => return Some(reply(ack, 0, TCP_RST, &[])),
+            None => return None,
+        };
+        let fin = flags & TCP_FIN != 0;
+        if seq != connection.ack {
+            // a segment that was already taken, sent again: say how far the stand-in has got
+            if data.is_empty() && !fin {
+                return None;
+            }
+            return Some(reply(connection.seq, connection.ack, TCP_ACK, &[]));
+        }
+        if data.is_empty() && !fin {
+            // once both sides have closed, this is the last word on the connection
+            if connection.fin_sent && ack == connection.seq {
+                self.connections.remove(&key);
+            }
+            return None;
+        }
+        connection.ack = connection.ack.wrapping_add(data.len() as u32 + fin as u32);
+        let mut flags = TCP_ACK;
+        if !data.is_empty() {
+            flags |= TCP_PSH;
+        }
+        if fin {
+            flags |= TCP_FIN;
+            connection.fin_sent = true;
+        }
+        let segment = reply(connection.seq, connection.ack, flags, data);
+        connection.seq = connection.seq.wrapping_add(data.len() as u32 + fin as u32);
+        Some(segment)
+    }
+
+    /// Answers a standard query with one question, from the hosts in the network script.
+    fn dns_reply(&self, query: &[u8]) -> Option<Vec<u8>> {
+        let flags = u16::from_be_bytes(query.get(2..4)?.try_into().unwrap());
+        let questions = u16::from_be_bytes(query.get(4..6)?.try_into().unwrap());
+        // responses, and anything other than a standard query, go unanswered
+        if flags & 0xF800 != 0 || questions != 1 {
+            return None;
+        }
+        let mut labels = Vec::new();
+        let mut at = 12;
+        loop {
+            let len = *query.get(at)? as usize;
+            at += 1;
+            if len == 0 {
+                break;
+            }
+            // names in a question are never compressed
+            if len & 0xC0 != 0 {
+                return None;
+            }
+            labels.push(std::str::from_utf8(query.get(at..at + len)?).ok()?.to_ascii_lowercase());
+            at += len;
+        }
+        let qtype = u16::from_be_bytes(query.get(at..at + 2)?.try_into().unwrap());
+        let qclass = u16::from_be_bytes(query.get(at + 2..at + 4)?.try_into().unwrap());
+        let question = &query[12..at + 4];
+        let name = labels.join(".");
+
+        let mut answers: Vec<(u16, Vec<u8>)> = Vec::new();
+        for (_, addr) in self.hosts.iter().filter(|(host, _)| *host == name) {
+            let answer = match addr {
+                IpAddr::V4(addr) => (DNS_TYPE_A, addr.octets().to_vec()),
+                IpAddr::V6(addr) => (DNS_TYPE_AAAA, addr.octets().to_vec()),
+            };
+            if (qtype == answer.0 || qtype == DNS_TYPE_ANY) && (qclass == DNS_CLASS_IN || qclass == DNS_TYPE_ANY) {
+                answers.push(answer);
+            }
+        }
+        let known = self.hosts.iter().any(|(host, _)| *host == name);
+        // a response that's authoritative, with recursion available, copying the recursion desired flag
+        let mut flags = 0x8480 | (flags & 0x0100);
+        if !known {
+            flags |= DNS_RCODE_NXDOMAIN;
+        }
+
+        let mut reply = Vec::new();
+        reply.extend_from_slice(&query[0..2]);
+        for field in [flags, 1, answers.len() as u16, 0, 0].iter() {
+            reply.extend_from_slice(&field.to_be_bytes());
+        }
+        reply.extend_from_slice(question);
+        for (kind, data) in answers {
+            // the name is a pointer back to the question
+            reply.extend_from_slice(&[0xC0, 12]);
+            reply.extend_from_slice(&kind.to_be_bytes());
+            reply.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+            reply.extend_from_slice(&DNS_TTL.to_be_bytes());
+            reply.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            reply.extend_from_slice(&data);
+        }
+        Some(reply)
+    }
+}
+
+impl Link for Switch {
+    fn send(&mut self, frame: &[u8], lease: &Lease) {
+        if let Some(answer) = self.answer(frame, lease) {
+            self.outbox.push_back(answer);
+        }
+    }
+
+    fn receive(&mut self, _uptime: Duration) -> Option<Vec<u8>> {
+        self.outbox.pop_front()
+    }
+}
+
+/// Answers a request for the address of a stand-in.
+fn arp_reply(packet: &[u8], lease: &Lease) -> Option<Vec<u8>> {
+    let packet = packet.get(..28)?;
+    // Ethernet and IPv4 addresses, of 6 and 4 bytes
+    if packet[0..6] != [0, 1, 0x08, 0x00, 6, 4] || u16::from_be_bytes([packet[6], packet[7]]) != ARP_REQUEST {
+        return None;
+    }
+    let target = Ipv4Addr::new(packet[24], packet[25], packet[26], packet[27]);
+    if target != lease.gateway && !lease.dns.contains(&Some(target)) {
+        return None;
+    }
+    let mut reply = packet[0..6].to_vec();
+    reply.extend_from_slice(&ARP_REPLY.to_be_bytes());
+    reply.extend_from_slice(&STAND_IN_MAC);
+    reply.extend_from_slice(&packet[24..28]);
+    reply.extend_from_slice(&packet[8..18]);
+    Some(reply)
+}
+
+fn ipv4(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x45, 0];
+    packet.extend_from_slice(&(20 + body.len() as u16).to_be_bytes());
+    // no identification, don't fragment, a TTL of 64
+    packet.extend_from_slice(&[0, 0, 0x40, 0, 64, protocol, 0, 0]);
+    packet.extend_from_slice(&src.octets());
+    packet.extend_from_slice(&dst.octets());
+    let sum = checksum(&packet, 0);
+    packet[10..12].copy_from_slice(&sum.to_be_bytes());
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn udp(src: Ipv4Addr, dst: Ipv4Addr, src_port: u16, dst_port: u16, data: &[u8]) -> Vec<u8> {
+    let len = 8 + data.len() as u16;
+    let mut datagram = Vec::new();
+    for field in [src_port, dst_port, len, 0].iter() {
+        datagram.extend_from_slice(&field.to_be_bytes());
+    }
+    datagram.extend_from_slice(data);
+    let pseudo_header = sum_words(&src.octets()) + sum_words(&dst.octets()) + IP_PROTOCOL_UDP as u32 + len as u32;
+    // a checksum of zero means there isn't one, so it's sent as all ones instead
+    let sum = match checksum(&datagram, pseudo_header) {
+        0 => 0xFFFF,
+        sum => sum,
+    };
+    datagram[6..8].copy_from_slice(&sum.to_be_bytes());
+    datagram
+}
+
+#[allow(clippy::too_many_arguments)]
+fn tcp(src: Ipv4Addr, dst: Ipv4Addr, src_port: u16, dst_port: u16, seq: u32, ack: u32, flags: u8, data: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(20 + data.len());
+    segment.extend_from_slice(&src_port.to_be_bytes());
+    segment.extend_from_slice(&dst_port.to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&ack.to_be_bytes());
+    // a header of five words, without options
+    segment.extend_from_slice(&[5 << 4, flags]);
+    segment.extend_from_slice(&TCP_WINDOW.to_be_bytes());
+    // the checksum, and the urgent pointer
+    segment.extend_from_slice(&[0, 0, 0, 0]);
+    segment.extend_from_slice(data);
+    let pseudo_header =
+        sum_words(&src.octets()) + sum_words(&dst.octets()) + IP_PROTOCOL_TCP as u32 + segment.len() as u32;
+    let sum = checksum(&segment, pseudo_header);
+    segment[16..18].copy_from_slice(&sum.to_be_bytes());
+    segment
+}
+
+fn sum_words(bytes: &[u8]) -> u32 {
+    bytes.chunks(2).map(|w| u16::from_be_bytes([w[0], *w.get(1).unwrap_or(&0)]) as u32).sum()
+}
+
+/// The Internet checksum of `bytes`, starting from the sum `initial`
+fn checksum(bytes: &[u8], initial: u32) -> u16 {
+    let mut sum = initial + sum_words(bytes);
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::MAC;
+    use super::*;
+
+    const OUR_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 15);
+    const DNS_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 3);
+
+    fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = STAND_IN_MAC.to_vec();
+        frame.extend_from_slice(&MAC);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn switch() -> Switch {
+        Switch::new(vec![
+            ("example.com".to_string(), "93.184.216.34".parse().unwrap()),
+            ("example.com".to_string(), "2001:db8::1".parse().unwrap()),
+            ("v6only.example".to_string(), "2001:db8::2".parse().unwrap()),
+        ])
+    }
+
+    fn exchange(switch: &mut Switch, frame: &[u8]) -> Option<Vec<u8>> {
+        switch.send(frame, &Lease::default());
+        let answer = switch.receive(Duration::from_secs(0));
+        if let Some(answer) = &answer {
+            assert_eq!(answer[0..6], MAC);
+            assert_eq!(answer[6..12], STAND_IN_MAC);
+            assert_eq!(answer[12..14], frame[12..14]);
+        }
+        answer
+    }
+
+    /// Sends a UDP datagram to `dst`, and returns the data of the reply
+    fn udp_exchange(switch: &mut Switch, dst: Ipv4Addr, port: u16, data: &[u8]) -> Option<Vec<u8>> {
+        let request = ipv4(OUR_ADDR, dst, IP_PROTOCOL_UDP, &udp(OUR_ADDR, dst, 4321, port, data));
+        let answer = exchange(switch, &ethernet(ETHERTYPE_IPV4, &request))?;
+        let packet = &answer[14..];
+        assert_eq!(checksum(&packet[..20], 0), 0);
+        assert_eq!(packet[12..16], dst.octets());
+        assert_eq!(packet[16..20], OUR_ADDR.octets());
+        let datagram = &packet[20..];
+        let pseudo_header = sum_words(&packet[12..20]) + IP_PROTOCOL_UDP as u32 + datagram.len() as u32;
+        assert_eq!(checksum(datagram, pseudo_header), 0);
+        assert_eq!(datagram[0..4], [(port >> 8) as u8, port as u8, 0x10, 0xE1]);
+        Some(datagram[8..].to_vec())
+    }
+
+    /// Sends a TCP segment from port 4321 to `port` on the gateway, and returns the header fields
+    /// and the data of the reply
+    fn tcp_exchange(switch: &mut Switch, port: u16, seq: u32, ack: u32, flags: u8, data: &[u8]) -> Option<(u32, u32, u8, Vec<u8>)> {
+        let gateway = Ipv4Addr::new(10, 0, 2, 2);
+        let request = ipv4(OUR_ADDR, gateway, IP_PROTOCOL_TCP, &tcp(OUR_ADDR, gateway, 4321, port, seq, ack, flags, data));
+        let answer = exchange(switch, &ethernet(ETHERTYPE_IPV4, &request))?;
+        let segment = &answer[14 + 20..];
+        let pseudo_header = sum_words(&answer[14 + 12..14 + 20]) + IP_PROTOCOL_TCP as u32 + segment.len() as u32;
+        assert_eq!(checksum(segment, pseudo_header), 0);
+        assert_eq!(segment[0..4], [(port >> 8) as u8, port as u8, 0x10, 0xE1]);
+        let seq = u32::from_be_bytes(segment[4..8].try_into().unwrap());
+        let ack = u32::from_be_bytes(segment[8..12].try_into().unwrap());
+        Some((seq, ack, segment[13], segment[20..].to_vec()))
+    }
+
+    fn dns_query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+        let mut query = id.to_be_bytes().to_vec();
+        query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.split('.') {
+            query.push(label.len() as u8);
+            query.extend_from_slice(label.as_bytes());
+        }
+        query.push(0);
+        query.extend_from_slice(&qtype.to_be_bytes());
+        query.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+        query
+    }
+
+    #[test]
+    fn answers_arp_for_stand_ins() {
+        let mut switch = switch();
+        let request = |target: Ipv4Addr| {
+            let mut arp = vec![0, 1, 0x08, 0x00, 6, 4, 0, 1];
+            arp.extend_from_slice(&MAC);
+            arp.extend_from_slice(&OUR_ADDR.octets());
+            arp.extend_from_slice(&[0; 6]);
+            arp.extend_from_slice(&target.octets());
+            ethernet(ETHERTYPE_ARP, &arp)
+        };
+        let answer = exchange(&mut switch, &request(Ipv4Addr::new(10, 0, 2, 2))).unwrap();
+        assert_eq!(answer[14 + 6..14 + 8], [0, 2]);
+        assert_eq!(answer[14 + 8..14 + 14], STAND_IN_MAC);
+        assert_eq!(answer[14 + 14..14 + 18], [10, 0, 2, 2]);
+        assert_eq!(answer[14 + 18..14 + 24], MAC);
+        assert_eq!(answer[14 + 24..14 + 28], OUR_ADDR.octets());
+        assert!(exchange(&mut switch, &request(DNS_ADDR)).is_some());
+        assert!(exchange(&mut switch, &request(Ipv4Addr::new(10, 0, 2, 99))).is_none());
+    }
+
+    #[test]
+    fn answers_pings_and_echoes() {
+        let mut switch = switch();
+        let mut ping = vec![ICMP_ECHO_REQUEST, 0, 0, 0, 0x12, 0x34, 0, 1, b'h', b'i'];
+        let sum = checksum(&ping, 0);
+        ping[2..4].copy_from_slice(&sum.to_be_bytes());
+        let request = ipv4(OUR_ADDR, DNS_ADDR, IP_PROTOCOL_ICMP, &ping);
+        let answer = exchange(&mut switch, &ethernet(ETHERTYPE_IPV4, &request)).unwrap();
+        let pong = &answer[14 + 20..];
+        assert_eq!(pong[0], ICMP_ECHO_REPLY);
+        assert_eq!(pong[4..], ping[4..]);
+        assert_eq!(checksum(pong, 0), 0);
+
+        assert_eq!(udp_exchange(&mut switch, Ipv4Addr::new(10, 0, 2, 2), ECHO_PORT, b"hello"), Some(b"hello".to_vec()));
+        // nobody is listening on other ports or other addresses
+        assert_eq!(udp_exchange(&mut switch, Ipv4Addr::new(10, 0, 2, 2), 9, b"hello"), None);
+        assert_eq!(udp_exchange(&mut switch, Ipv4Addr::new(1, 1, 1, 1), ECHO_PORT, b"hello"), None);
+    }
+
+    #[test]
+    fn echoes_tcp() {
+        let mut switch = switch();
+        let (isn, ack, flags, _) = tcp_exchange(&mut switch, ECHO_PORT, 100, 0, TCP_SYN, &[]).unwrap();
+        assert_eq!((ack, flags), (101, TCP_SYN | TCP_ACK));
+        // the handshake's last ACK needs no answer
+        assert_eq!(tcp_exchange(&mut switch, ECHO_PORT, 101, isn + 1, TCP_ACK, &[]), None);
+
+        let echo = tcp_exchange(&mut switch, ECHO_PORT, 101, isn + 1, TCP_ACK | TCP_PSH, b"hello").unwrap();
+        assert_eq!(echo, (isn + 1, 106, TCP_ACK | TCP_PSH, b"hello".to_vec()));
+        // data that's sent again is acknowledged, but not echoed twice
+        let again = tcp_exchange(&mut switch, ECHO_PORT, 101, isn + 1, TCP_ACK | TCP_PSH, b"hello").unwrap();
+        assert_eq!(again, (isn + 6, 106, TCP_ACK, vec![]));
+
+        // closing the connection closes the stand-in's side too
+        let fin = tcp_exchange(&mut switch, ECHO_PORT, 106, isn + 6, TCP_ACK | TCP_FIN, &[]).unwrap();
+        assert_eq!(fin, (isn + 6, 107, TCP_ACK | TCP_FIN, vec![]));
+        assert_eq!(tcp_exchange(&mut switch, ECHO_PORT, 107, isn + 7, TCP_ACK, &[]), None);
+        assert!(switch.connections.is_empty());
+        // so anything more on it is reset
+        let (_, _, flags, _) = tcp_exchange(&mut switch, ECHO_PORT, 107, isn + 7, TCP_ACK, b"late").unwrap();
+        assert_eq!(flags, TCP_RST);
+
+        // nobody is listening on other ports
+        let (_, ack, flags, _) = tcp_exchange(&mut switch, 9, 500, 0, TCP_SYN, &[]).unwrap();
+        assert_eq!((ack, flags), (501, TCP_RST | TCP_ACK));
+    }
+
+    #[test]
+    fn answers_dns_queries() {
+        let mut switch = switch();
+        let reply = udp_exchange(&mut switch, DNS_ADDR, DNS_PORT, &dns_query(0xBEEF, "Example.COM", DNS_TYPE_A)).unwrap();
+        let question_len = 13 + 4;
+        assert_eq!(reply[0..12], [0xBE, 0xEF, 0x85, 0x80, 0, 1, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(reply[12..12 + question_len], dns_query(0xBEEF, "Example.COM", DNS_TYPE_A)[12..]);
+        assert_eq!(reply[12 + question_len..], [0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 93, 184, 216, 34]);
+
+        let reply = udp_exchange(&mut switch, DNS_ADDR, DNS_PORT, &dns_query(1, "example.com", DNS_TYPE_AAAA)).unwrap();
+        assert_eq!(reply[6..8], [0, 1]);
+        assert_eq!(reply[reply.len() - 16..], "2001:db8::1".parse::<std::net::Ipv6Addr>().unwrap().octets());
+
+        // a name that exists, without a record of the type asked for
+        let reply = udp_exchange(&mut switch, DNS_ADDR, DNS_PORT, &dns_query(2, "v6only.example", DNS_TYPE_A)).unwrap();
+        assert_eq!(reply[2..8], [0x85, 0x80, 0, 1, 0, 0]);
+        // a name that doesn't
+        let reply = udp_exchange(&mut switch, DNS_ADDR, DNS_PORT, &dns_query(3, "nowhere.example", DNS_TYPE_A)).unwrap();
+        assert_eq!(reply[2..8], [0x85, 0x83, 0, 1, 0, 0]);
+        // only the DNS servers answer DNS
+        assert_eq!(udp_exchange(&mut switch, Ipv4Addr::new(10, 0, 2, 2), DNS_PORT, &dns_query(4, "example.com", DNS_TYPE_A)), None);
+    }
+}
@@ -3,6 +3,8 @@
 
 mod api;
 use api::*;
+#[cfg(not(target_os = "xous"))]
+mod hosted_net;
 
 use num_traits::{ToPrimitive, FromPrimitive};
 
@@ -252,12 +254,15 @@ mod implementation {
     use crate::api::BattStats;
     use crate::return_battstats;
     use crate::WorkRequest;
+    use crate::hosted_net::{HostedNet, HostedNetConfig};
     use com_rs::*;
     use log::error;
 
     pub struct XousCom {
         pub workqueue: Vec<WorkRequest>,
         busy: bool,
+        /// Set when the WLAN link is emulated, see `hosted_net`
+        net: Option<HostedNet>,
     }
 
     impl XousCom {
@@ -265,21 +270,25 @@ mod implementation {
             XousCom {
                 workqueue: Vec::new(),
                 busy: false,
+                net: HostedNetConfig::from_env().map(HostedNet::new),
             }
         }
         pub fn init(&mut self) {}
         pub fn suspend(&self) {}
         pub fn resume(&self) {}
 
-        pub fn txrx(&mut self, _tx: u16) -> u16 {
-            0xDEAD as u16
+        pub fn txrx(&mut self, tx: u16) -> u16 {
+            match &mut self.net {
+                Some(net) => net.txrx(tx),
+                None => 0xDEAD as u16,
+            }
         }
 
-        pub fn wait_txrx(&mut self, _tx: u16, _timeout: Option<u32>) -> u16 {
-            0xDEAD as u16
+        pub fn wait_txrx(&mut self, tx: u16, _timeout: Option<u32>) -> u16 {
+            self.txrx(tx)
         }
-        pub fn try_wait_txrx(&mut self, _tx: u16, _timeout: u32) -> Option<u16> {
-            None
+        pub fn try_wait_txrx(&mut self, tx: u16, _timeout: u32) -> Option<u16> {
+            self.net.as_mut().map(|net| net.txrx(tx))
         }
 
         pub fn get_battstats(&mut self) -> BattStats {
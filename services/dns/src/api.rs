@@ -3,6 +3,8 @@
 pub(crate) const SERVER_NAME_DNS: &str = "_DNS Resolver Middleware_";
 use net::NetIpAddr;
 use rkyv::{Archive, Deserialize, Serialize};
+use std::convert::TryInto;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 #[allow(dead_code)]
 pub(crate) const DNS_NAME_LENGTH_LIMIT: usize = 256;
@@ -48,7 +50,178 @@ pub(crate) enum Opcode {
     ///
     ///     * 4: Ipv4 Address -- 4 octets follow, for a total of 5 bytes
     ///     * 6: Ipv6 Address -- 16 octets follow, for a total of 17 bytes
+    ///
+    /// Ipv4 addresses are listed before Ipv6 addresses.
     RawLookup = 6,
+
+    /// Look up records of a given type, following CNAMEs. Like `RawLookup`, the query is
+    /// a `MutableBorrow` of a `&str` with `valid` set to its length, and the `offset`
+    /// field carries the `DnsRecordType` to look up.
+    ///
+    /// The result has the same header as `RawLookup`: `0` and the number of records, or
+    /// `1` and a `DnsResponseCode`. Each record is then laid out as:
+    ///
+    ///     * type: u16, big endian -- a `DnsRecordType`
+    ///     * ttl: u32, big endian
+    ///     * length: u16, big endian -- the number of data bytes that follow
+    ///     * data: 4 octets for A, 16 for AAAA, the name for CNAME, the length-prefixed
+    ///       character-strings for TXT, and the priority, weight and port (u16s, big
+    ///       endian) followed by the target name for SRV
+    ///
+    /// Names are dotted, without a trailing dot. Records that don't fit in the buffer
+    /// are left off. Results are not cached.
+    RawRecordLookup = 7,
+
+    /// Re-read the DNS-over-TLS and DNSSEC settings from the user's preferences, and flush the
+    /// cache. Ignored while the config is frozen.
+    ReloadConfig = 8,
+}
+
+/// The record types that can be looked up with `RawRecordLookup`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+#[repr(u16)]
+pub enum DnsRecordType {
+    A = 1,
+    Cname = 5,
+    Txt = 16,
+    Aaaa = 28,
+    Srv = 33,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsRecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(std::string::String),
+    /// The record's character-strings, in order
+    Txt(Vec<Vec<u8>>),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: std::string::String,
+    },
+}
+impl DnsRecordData {
+    pub fn record_type(&self) -> DnsRecordType {
+        match self {
+            DnsRecordData::A(_) => DnsRecordType::A,
+            DnsRecordData::Aaaa(_) => DnsRecordType::Aaaa,
+            DnsRecordData::Cname(_) => DnsRecordType::Cname,
+            DnsRecordData::Txt(_) => DnsRecordType::Txt,
+            DnsRecordData::Srv { .. } => DnsRecordType::Srv,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    /// Seconds the record may be cached for
+    pub ttl: u32,
+    pub data: DnsRecordData,
+}
+
+/// Lays out `records` as the body of a `RawRecordLookup` response, after the two header bytes.
+/// Returns how many of them fit in `buf`.
+#[allow(dead_code)]
+pub(crate) fn encode_records(records: &[DnsRecord], buf: &mut [u8]) -> usize {
+    let mut index = 0;
+    let mut count = 0;
+    for record in records.iter().take(u8::MAX as usize) {
+        let mut data = Vec::new();
+        match &record.data {
+            DnsRecordData::A(addr) => data.extend_from_slice(&addr.octets()),
+            DnsRecordData::Aaaa(addr) => data.extend_from_slice(&addr.octets()),
+            DnsRecordData::Cname(name) => data.extend_from_slice(name.as_bytes()),
+            DnsRecordData::Txt(strings) => {
+                for s in strings {
+                    data.push(s.len() as u8);
+                    data.extend_from_slice(s);
+                }
+            }
+            DnsRecordData::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                data.extend_from_slice(&priority.to_be_bytes());
+                data.extend_from_slice(&weight.to_be_bytes());
+                data.extend_from_slice(&port.to_be_bytes());
+                data.extend_from_slice(target.as_bytes());
+            }
+        }
+        let end = index + 8 + data.len();
+        if end > buf.len() || data.len() > u16::MAX as usize {
+            break;
+        }
+        buf[index..index + 2].copy_from_slice(&(record.data.record_type() as u16).to_be_bytes());
+        buf[index + 2..index + 6].copy_from_slice(&record.ttl.to_be_bytes());
+        buf[index + 6..index + 8].copy_from_slice(&(data.len() as u16).to_be_bytes());
+        buf[index + 8..end].copy_from_slice(&data);
+        index = end;
+        count += 1;
+    }
+    count
+}
+
+/// Reads the records out of a whole `RawRecordLookup` response, header included.
+#[allow(dead_code)]
+pub(crate) fn decode_records(buf: &[u8]) -> Result<Vec<DnsRecord>, DnsResponseCode> {
+    use num_traits::FromPrimitive;
+    use DnsResponseCode::FormatError;
+    match buf.get(0..2) {
+        Some(&[0, _]) => (),
+        Some(&[_, code]) => {
+            return Err(FromPrimitive::from_u8(code).unwrap_or(DnsResponseCode::UnknownError))
+        }
+        _ => return Err(FormatError),
+    }
+    let field = |start: usize, len: usize| buf.get(start..start + len).ok_or(FormatError);
+    let name = |data: &[u8]| std::string::String::from_utf8_lossy(data).into_owned();
+    let mut records = Vec::new();
+    let mut index = 2;
+    for _ in 0..buf[1] {
+        let rtype = u16::from_be_bytes(field(index, 2)?.try_into().unwrap());
+        let ttl = u32::from_be_bytes(field(index + 2, 4)?.try_into().unwrap());
+        let len = u16::from_be_bytes(field(index + 6, 2)?.try_into().unwrap()) as usize;
+        let data = field(index + 8, len)?;
+        index += 8 + len;
+        let data = match FromPrimitive::from_u16(rtype).ok_or(FormatError)? {
+            DnsRecordType::A => {
+                let octets: [u8; 4] = data.try_into().or(Err(FormatError))?;
+                DnsRecordData::A(Ipv4Addr::from(octets))
+            }
+            DnsRecordType::Aaaa => {
+                let octets: [u8; 16] = data.try_into().or(Err(FormatError))?;
+                DnsRecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            DnsRecordType::Cname => DnsRecordData::Cname(name(data)),
+            DnsRecordType::Txt => {
+                let mut strings = Vec::new();
+                let mut i = 0;
+                while i < data.len() {
+                    let len = data[i] as usize;
+                    strings.push(data.get(i + 1..i + 1 + len).ok_or(FormatError)?.to_vec());
+                    i += 1 + len;
+                }
+                DnsRecordData::Txt(strings)
+            }
+            DnsRecordType::Srv => {
+                if data.len() < 6 {
+                    return Err(FormatError);
+                }
+                DnsRecordData::Srv {
+                    priority: u16::from_be_bytes([data[0], data[1]]),
+                    weight: u16::from_be_bytes([data[2], data[3]]),
+                    port: u16::from_be_bytes([data[4], data[5]]),
+                    target: name(&data[6..]),
+                }
+            }
+        };
+        records.push(DnsRecord { ttl, data });
+    }
+    Ok(records)
 }
 
 #[derive(
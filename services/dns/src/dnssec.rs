@@ -0,0 +1,747 @@
+//! DNSSEC validation (RFC 4033, 4034 and 4035) of the answers the upstream server gives us.
+//!
+//! The upstream server still does the recursion. We ask it to send the signatures along with
+//! the answers, and check them ourselves all the way up to one of the root zone's key signing
+//! keys, which are pinned below. That way neither the server nor anyone between us and it can hand
+//! out forged answers.
+//!
+//! Validation is strict: every RRset in an answer has to be signed by a key that chains up to
+//! the root, so names in zones that aren't signed don't resolve at all. Denial of existence
+//! (NSEC and NSEC3) isn't checked, so a forged "no such name" or empty answer gets through;
+//! that can keep a name from resolving, but can't point it somewhere else.
+
+use crate::api::*;
+use crate::message::{name_to_wire, QueryClass, RawRecord};
+use ring::{digest, signature};
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+pub(crate) const TYPE_DS: u16 = 43;
+pub(crate) const TYPE_RRSIG: u16 = 46;
+pub(crate) const TYPE_DNSKEY: u16 = 48;
+
+/// DNSKEY flag for a key that signs its zone's data
+const FLAG_ZONE_KEY: u16 = 0x0100;
+/// The only value allowed in the DNSKEY protocol field
+const PROTOCOL_DNSSEC: u8 = 3;
+
+const ALG_RSASHA256: u8 = 8;
+const ALG_RSASHA512: u8 = 10;
+const ALG_ECDSAP256SHA256: u8 = 13;
+const ALG_ECDSAP384SHA384: u8 = 14;
+const ALG_ED25519: u8 = 15;
+
+const DIGEST_SHA256: u8 = 2;
+const DIGEST_SHA384: u8 = 4;
+
+/// Key tags and digests of the root zone's key signing keys KSK-2017 and KSK-2024, as published
+/// by IANA at https://data.iana.org/root-anchors/root-anchors.xml. Both are pinned so that
+/// validation keeps working across the rollover from one to the other.
+const ROOT_KSKS: [(u16, [u8; 32]); 2] = [
+    (
+        20326,
+        [
+            0xE0, 0x6D, 0x44, 0xB8, 0x0B, 0x8F, 0x1D, 0x39, 0xA9, 0x5C, 0x0B, 0x0D, 0x7C, 0x65,
+            0xD0, 0x84, 0x58, 0xE8, 0x80, 0x40, 0x9B, 0xBC, 0x68, 0x34, 0x57, 0x10, 0x42, 0x37,
+            0xC7, 0xF8, 0xEC, 0x8D,
+        ],
+    ),
+    (
+        38696,
+        [
+            0x68, 0x3D, 0x2D, 0x0A, 0xCB, 0x8C, 0x9B, 0x71, 0x2A, 0x19, 0x48, 0xB2, 0x7F, 0x74,
+            0x12, 0x19, 0x29, 0x8D, 0x0A, 0x45, 0x0D, 0x61, 0x2C, 0x48, 0x3A, 0xF4, 0x44, 0xA4,
+            0xC0, 0xFB, 0x2B, 0x16,
+        ],
+    ),
+];
+
+/// What a zone's parent vouches for: the digest of one of the zone's keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Ds {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: Vec<u8>,
+}
+
+impl Ds {
+    /// The trust anchors that every chain has to end at
+    pub fn root_anchors() -> Vec<Ds> {
+        ROOT_KSKS
+            .iter()
+            .map(|(key_tag, digest)| Ds {
+                key_tag: *key_tag,
+                algorithm: ALG_RSASHA256,
+                digest_type: DIGEST_SHA256,
+                digest: digest.to_vec(),
+            })
+            .collect()
+    }
+
+    fn parse(rdata: &[u8]) -> Option<Ds> {
+        Some(Ds {
+            key_tag: u16::from_be_bytes(rdata.get(0..2)?.try_into().unwrap()),
+            algorithm: *rdata.get(2)?,
+            digest_type: *rdata.get(3)?,
+            digest: rdata[4..].to_vec(),
+        })
+    }
+
+    /// Whether `key`, a key of `zone`, is the one this refers to
+    fn matches(&self, zone: &str, key: &Dnskey) -> bool {
+        if self.key_tag != key.key_tag || self.algorithm != key.algorithm {
+            return false;
+        }
+        let algorithm = match self.digest_type {
+            DIGEST_SHA256 => &digest::SHA256,
+            DIGEST_SHA384 => &digest::SHA384,
+            _ => return false,
+        };
+        let mut ctx = digest::Context::new(algorithm);
+        ctx.update(&name_to_wire(zone));
+        ctx.update(&key.rdata);
+        ctx.finish().as_ref() == &self.digest[..]
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Dnskey {
+    algorithm: u8,
+    key_tag: u16,
+    public_key: Vec<u8>,
+    rdata: Vec<u8>,
+}
+
+impl Dnskey {
+    /// Parses the rdata of a DNSKEY record, keeping only the keys that may sign zone data.
+    fn parse(rdata: &[u8]) -> Option<Dnskey> {
+        let flags = u16::from_be_bytes(rdata.get(0..2)?.try_into().unwrap());
+        if flags & FLAG_ZONE_KEY == 0 || *rdata.get(2)? != PROTOCOL_DNSSEC {
+            return None;
+        }
+        Some(Dnskey {
+            algorithm: *rdata.get(3)?,
+            key_tag: key_tag(rdata),
+            public_key: rdata[4..].to_vec(),
+            rdata: rdata.to_vec(),
+        })
+    }
+}
+
+/// The key tag of a DNSKEY, from RFC 4034 appendix B
+fn key_tag(rdata: &[u8]) -> u16 {
+    let mut acc: u32 = 0;
+    for (i, &b) in rdata.iter().enumerate() {
+        acc += if i & 1 == 0 {
+            (b as u32) << 8
+        } else {
+            b as u32
+        };
+    }
+    acc += (acc >> 16) & 0xFFFF;
+    (acc & 0xFFFF) as u16
+}
+
+#[derive(Debug)]
+struct Rrsig {
+    type_covered: u16,
+    algorithm: u8,
+    labels: u8,
+    original_ttl: u32,
+    expiration: u32,
+    inception: u32,
+    key_tag: u16,
+    signer: String,
+    /// The rdata up to the signature, with the signer's name in canonical form
+    signed_fields: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl Rrsig {
+    fn parse(rdata: &[u8]) -> Option<Rrsig> {
+        let fixed = rdata.get(0..18)?;
+        // the signer's name is never compressed (RFC 4034 section 3.1.7)
+        let mut labels = Vec::new();
+        let mut index = 18;
+        loop {
+            let len = *rdata.get(index)? as usize;
+            if len == 0 {
+                break;
+            }
+            if len & 0xC0 != 0 {
+                return None;
+            }
+            labels.push(String::from_utf8_lossy(
+                rdata.get(index + 1..index + 1 + len)?,
+            ));
+            index += 1 + len;
+        }
+        let signer = labels.join(".");
+        let mut signed_fields = fixed.to_vec();
+        signed_fields.extend_from_slice(&name_to_wire(&signer));
+        Some(Rrsig {
+            type_covered: u16::from_be_bytes(fixed[0..2].try_into().unwrap()),
+            algorithm: fixed[2],
+            labels: fixed[3],
+            original_ttl: u32::from_be_bytes(fixed[4..8].try_into().unwrap()),
+            expiration: u32::from_be_bytes(fixed[8..12].try_into().unwrap()),
+            inception: u32::from_be_bytes(fixed[12..16].try_into().unwrap()),
+            key_tag: u16::from_be_bytes(fixed[16..18].try_into().unwrap()),
+            signer,
+            signed_fields,
+            signature: rdata[index + 1..].to_vec(),
+        })
+    }
+
+    /// Whether `now` is inside the validity period, in serial number arithmetic (RFC 1982),
+    /// as the times are only 32 bits
+    fn is_current(&self, now: u32) -> bool {
+        now.wrapping_sub(self.inception) as i32 >= 0
+            && self.expiration.wrapping_sub(now) as i32 >= 0
+    }
+
+    /// Lays out the data this signs over, for the RRset `rrset` of type `rtype` owned by `owner`
+    /// (RFC 4034 section 3.1.8.1).
+    fn signed_data(&self, owner: &str, rtype: u16, rrset: &[&RawRecord]) -> Option<Vec<u8>> {
+        let labels: Vec<&str> = owner.split('.').filter(|l| !l.is_empty()).collect();
+        let signed_labels = self.labels as usize;
+        if signed_labels > labels.len() {
+            return None;
+        }
+        // an answer made from a wildcard is signed under the wildcard's name
+        let owner = if signed_labels < labels.len() {
+            format!("*.{}", labels[labels.len() - signed_labels..].join("."))
+        } else {
+            owner.to_string()
+        };
+        let owner = name_to_wire(&owner);
+        let mut rdatas: Vec<&[u8]> = rrset.iter().map(|r| &r.rdata[..]).collect();
+        rdatas.sort();
+        rdatas.dedup();
+
+        let mut data = self.signed_fields.clone();
+        for rdata in rdatas {
+            data.extend_from_slice(&owner);
+            data.extend_from_slice(&rtype.to_be_bytes());
+            data.extend_from_slice(&(QueryClass::IN as u16).to_be_bytes());
+            data.extend_from_slice(&self.original_ttl.to_be_bytes());
+            data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            data.extend_from_slice(rdata);
+        }
+        Some(data)
+    }
+
+    /// Checks the signature over `rrset` with `key`.
+    fn verify(&self, key: &Dnskey, owner: &str, rtype: u16, rrset: &[&RawRecord]) -> bool {
+        if key.key_tag != self.key_tag || key.algorithm != self.algorithm {
+            return false;
+        }
+        match self.signed_data(owner, rtype, rrset) {
+            Some(data) => verify_signature(key, &data, &self.signature),
+            None => false,
+        }
+    }
+}
+
+fn verify_signature(key: &Dnskey, data: &[u8], sig: &[u8]) -> bool {
+    let ecdsa_point = || {
+        let mut point = vec![0x04]; // uncompressed
+        point.extend_from_slice(&key.public_key);
+        point
+    };
+    let result = match key.algorithm {
+        ALG_RSASHA256 | ALG_RSASHA512 => {
+            // the exponent length is one byte, or zero and then two bytes (RFC 3110)
+            let k = &key.public_key;
+            let (len, start) = match k.get(0) {
+                Some(0) if k.len() > 3 => (u16::from_be_bytes([k[1], k[2]]) as usize, 3),
+                Some(&len) => (len as usize, 1),
+                None => return false,
+            };
+            if k.len() <= start + len {
+                return false;
+            }
+            let components = signature::RsaPublicKeyComponents {
+                e: &k[start..start + len],
+                n: &k[start + len..],
+            };
+            let params = if key.algorithm == ALG_RSASHA256 {
+                &signature::RSA_PKCS1_2048_8192_SHA256
+            } else {
+                &signature::RSA_PKCS1_2048_8192_SHA512
+            };
+            components.verify(params, data, sig)
+        }
+        ALG_ECDSAP256SHA256 => {
+            signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, ecdsa_point())
+                .verify(data, sig)
+        }
+        ALG_ECDSAP384SHA384 => {
+            signature::UnparsedPublicKey::new(&signature::ECDSA_P384_SHA384_FIXED, ecdsa_point())
+                .verify(data, sig)
+        }
+        ALG_ED25519 => signature::UnparsedPublicKey::new(&signature::ED25519, &key.public_key)
+            .verify(data, sig),
+        alg => {
+            log::warn!("DNSSEC algorithm {} is not supported", alg);
+            return false;
+        }
+    };
+    result.is_ok()
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+/// Whether `name` is `zone` or below it
+fn in_zone(name: &str, zone: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    let zone = zone.trim_end_matches('.').to_ascii_lowercase();
+    zone.is_empty() || name == zone || name.ends_with(&format!(".{}", zone))
+}
+
+/// Whether time `a` is before time `b`, in serial number arithmetic
+fn serial_before(a: u32, b: u32) -> bool {
+    (b.wrapping_sub(a) as i32) > 0
+}
+
+/// Asks the upstream server for the records of a type, returning the answer section
+pub(crate) type Fetch<'a> = dyn FnMut(&str, u16) -> Result<Vec<RawRecord>, DnsResponseCode> + 'a;
+
+struct ZoneKeys {
+    keys: Vec<Dnskey>,
+    /// When the keys have to be checked again
+    expires: u32,
+}
+
+/// Checks answers against a set of trust anchors, remembering the zone keys it has checked along
+/// the way.
+pub(crate) struct Validator {
+    anchors: Vec<Ds>,
+    zones: HashMap<String, ZoneKeys>,
+}
+
+impl Validator {
+    /// `anchors` are the root keys to trust; the root key set has to be signed by any one of them.
+    pub fn new(anchors: Vec<Ds>) -> Validator {
+        Validator {
+            anchors,
+            zones: HashMap::new(),
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.zones.clear();
+    }
+
+    /// Checks that every RRset in `answers` is signed by a key that chains up to a trust
+    /// anchor. `now` is in seconds since the epoch. Failure is `ServerFailure`, which is what a
+    /// validating server would answer with.
+    pub fn validate(
+        &mut self,
+        answers: &[RawRecord],
+        now: u32,
+        fetch: &mut Fetch,
+    ) -> Result<(), DnsResponseCode> {
+        let mut rrsets: Vec<(String, u16)> = Vec::new();
+        for r in answers.iter().filter(|r| r.rtype != TYPE_RRSIG) {
+            if !rrsets
+                .iter()
+                .any(|(name, rtype)| *rtype == r.rtype && same_name(name, &r.name))
+            {
+                rrsets.push((r.name.clone(), r.rtype));
+            }
+        }
+        for (name, rtype) in rrsets {
+            self.verify_rrset(answers, &name, rtype, now, fetch)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the signatures on the `rtype` RRset of `owner` in `records`. Returns when the
+    /// signature that checked out expires.
+    fn verify_rrset(
+        &mut self,
+        records: &[RawRecord],
+        owner: &str,
+        rtype: u16,
+        now: u32,
+        fetch: &mut Fetch,
+    ) -> Result<u32, DnsResponseCode> {
+        let owned = |r: &&RawRecord| same_name(&r.name, owner);
+        let rrset: Vec<&RawRecord> = records
+            .iter()
+            .filter(owned)
+            .filter(|r| r.rtype == rtype)
+            .collect();
+        let sigs: Vec<Rrsig> = records
+            .iter()
+            .filter(owned)
+            .filter(|r| r.rtype == TYPE_RRSIG)
+            .filter_map(|r| Rrsig::parse(&r.rdata))
+            .filter(|sig| sig.type_covered == rtype)
+            .collect();
+        for sig in sigs {
+            // a zone's data is signed by the zone itself, except for the DS records at its top,
+            // which belong to the parent
+            if !in_zone(owner, &sig.signer) || (rtype == TYPE_DS && same_name(owner, &sig.signer)) {
+                continue;
+            }
+            if !sig.is_current(now) {
+                log::debug!("signature on {} type {} has expired", owner, rtype);
+                continue;
+            }
+            let keys = self.zone_keys(&sig.signer, now, fetch)?;
+            if keys.iter().any(|key| sig.verify(key, owner, rtype, &rrset)) {
+                return Ok(sig.expiration);
+            }
+        }
+        log::warn!("no valid signature on {} type {}", owner, rtype);
+        Err(DnsResponseCode::ServerFailure)
+    }
+
+    /// Returns the keys of `zone`, checking them against the DS records in its parent (or the
+    /// trust anchors, for the root) if they haven't been already.
+    fn zone_keys(
+        &mut self,
+        zone: &str,
+        now: u32,
+        fetch: &mut Fetch,
+    ) -> Result<Vec<Dnskey>, DnsResponseCode> {
+        let zone = zone.trim_end_matches('.').to_ascii_lowercase();
+        if let Some(cached) = self.zones.get(&zone) {
+            if serial_before(now, cached.expires) {
+                return Ok(cached.keys.clone());
+            }
+        }
+
+        let mut expires = now.wrapping_add(u32::MAX / 2);
+        let trusted: Vec<Ds> = if zone.is_empty() {
+            self.anchors.clone()
+        } else {
+            let records = fetch(&zone, TYPE_DS)?;
+            expires = self.verify_rrset(&records, &zone, TYPE_DS, now, fetch)?;
+            records
+                .iter()
+                .filter(|r| r.rtype == TYPE_DS && same_name(&r.name, &zone))
+                .filter_map(|r| Ds::parse(&r.rdata))
+                .collect()
+        };
+
+        let records = fetch(&zone, TYPE_DNSKEY)?;
+        let rrset: Vec<&RawRecord> = records
+            .iter()
+            .filter(|r| r.rtype == TYPE_DNSKEY && same_name(&r.name, &zone))
+            .collect();
+        let keys: Vec<Dnskey> = rrset
+            .iter()
+            .filter_map(|r| Dnskey::parse(&r.rdata))
+            .collect();
+        let entry_keys: Vec<&Dnskey> = keys
+            .iter()
+            .filter(|key| trusted.iter().any(|ds| ds.matches(&zone, key)))
+            .collect();
+        if entry_keys.is_empty() {
+            log::warn!("no key of zone '{}' matches its DS records", zone);
+            return Err(DnsResponseCode::ServerFailure);
+        }
+        // the key set has to be signed by one of the keys the parent vouches for
+        let sig = records
+            .iter()
+            .filter(|r| r.rtype == TYPE_RRSIG && same_name(&r.name, &zone))
+            .filter_map(|r| Rrsig::parse(&r.rdata))
+            .filter(|sig| sig.type_covered == TYPE_DNSKEY && same_name(&sig.signer, &zone))
+            .filter(|sig| sig.is_current(now))
+            .find(|sig| {
+                entry_keys
+                    .iter()
+                    .any(|key| sig.verify(key, &zone, TYPE_DNSKEY, &rrset))
+            })
+            .ok_or_else(|| {
+                log::warn!("key set of zone '{}' is not validly signed", zone);
+                DnsResponseCode::ServerFailure
+            })?;
+
+        let ttl = rrset
+            .iter()
+            .map(|r| r.ttl)
+            .chain(std::iter::once(sig.original_ttl))
+            .min()
+            .unwrap_or(0);
+        for limit in [sig.expiration, now.saturating_add(ttl)] {
+            if serial_before(limit, expires) {
+                expires = limit;
+            }
+        }
+        log::debug!("zone '{}' has {} trusted key(s)", zone, keys.len());
+        self.zones.insert(
+            zone,
+            ZoneKeys {
+                keys: keys.clone(),
+                expires,
+            },
+        );
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::net::Ipv4Addr;
+
+    const NOW: u32 = 1_700_000_000;
+    const DAY: u32 = 86_400;
+
+    struct Zone {
+        name: &'static str,
+        key: Ed25519KeyPair,
+    }
+
+    impl Zone {
+        fn new(name: &'static str, seed: u8) -> Zone {
+            Zone {
+                name,
+                key: Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap(),
+            }
+        }
+
+        fn dnskey(&self) -> Vec<u8> {
+            // a key signing key: the zone key and secure entry point flags are set
+            let mut rdata = vec![0x01, 0x01, PROTOCOL_DNSSEC, ALG_ED25519];
+            rdata.extend_from_slice(self.key.public_key().as_ref());
+            rdata
+        }
+
+        fn ds(&self) -> Vec<u8> {
+            let mut rdata = key_tag(&self.dnskey()).to_be_bytes().to_vec();
+            rdata.extend_from_slice(&[ALG_ED25519, DIGEST_SHA256]);
+            let mut ctx = digest::Context::new(&digest::SHA256);
+            ctx.update(&name_to_wire(self.name));
+            ctx.update(&self.dnskey());
+            rdata.extend_from_slice(ctx.finish().as_ref());
+            rdata
+        }
+
+        /// Signs the records in `rrset`, which all have the same owner and type
+        fn sign(&self, rrset: &[RawRecord], labels: u8, expiration: u32) -> RawRecord {
+            let mut rdata = rrset[0].rtype.to_be_bytes().to_vec();
+            rdata.extend_from_slice(&[ALG_ED25519, labels]);
+            rdata.extend_from_slice(&3600u32.to_be_bytes());
+            rdata.extend_from_slice(&expiration.to_be_bytes());
+            rdata.extend_from_slice(&(NOW - DAY).to_be_bytes());
+            rdata.extend_from_slice(&key_tag(&self.dnskey()).to_be_bytes());
+            rdata.extend_from_slice(&name_to_wire(self.name));
+            let refs: Vec<&RawRecord> = rrset.iter().collect();
+            let data = Rrsig::parse(&rdata)
+                .unwrap()
+                .signed_data(&rrset[0].name, rrset[0].rtype, &refs)
+                .unwrap();
+            rdata.extend_from_slice(self.key.sign(&data).as_ref());
+            record(&rrset[0].name, TYPE_RRSIG, rdata)
+        }
+    }
+
+    fn record(name: &str, rtype: u16, rdata: Vec<u8>) -> RawRecord {
+        RawRecord {
+            name: name.into(),
+            rtype,
+            ttl: 3600,
+            rdata,
+        }
+    }
+
+    /// A stand-in for the upstream resolver, serving a signed root, `test` and `example.test`
+    struct StandIn {
+        records: Vec<RawRecord>,
+        queries: usize,
+    }
+
+    impl StandIn {
+        fn new(root: &Zone, tld: &Zone, domain: &Zone) -> StandIn {
+            let mut stand_in = StandIn {
+                records: Vec::new(),
+                queries: 0,
+            };
+            stand_in.add(root, &[record("", TYPE_DNSKEY, root.dnskey())], 0);
+            stand_in.add(root, &[record("test", TYPE_DS, tld.ds())], 1);
+            stand_in.add(tld, &[record("test", TYPE_DNSKEY, tld.dnskey())], 1);
+            stand_in.add(tld, &[record("example.test", TYPE_DS, domain.ds())], 2);
+            let keys = [record("example.test", TYPE_DNSKEY, domain.dnskey())];
+            stand_in.add(domain, &keys, 2);
+            let a = |last| {
+                record(
+                    "www.example.test",
+                    1,
+                    Ipv4Addr::new(192, 0, 2, last).octets().to_vec(),
+                )
+            };
+            stand_in.add(domain, &[a(1), a(2)], 3);
+            let txt = [record("*.example.test", 16, b"\x05hello".to_vec())];
+            stand_in.add(domain, &txt, 2);
+            stand_in
+        }
+
+        fn add(&mut self, signer: &Zone, rrset: &[RawRecord], labels: u8) {
+            self.records.push(signer.sign(rrset, labels, NOW + DAY));
+            self.records.extend_from_slice(rrset);
+        }
+
+        /// Answers like a recursive server would, including a wildcard expansion
+        fn fetch(&mut self, name: &str, rtype: u16) -> Result<Vec<RawRecord>, DnsResponseCode> {
+            self.queries += 1;
+            let covers = |r: &RawRecord| {
+                r.rtype == rtype
+                    || (r.rtype == TYPE_RRSIG
+                        && Rrsig::parse(&r.rdata).unwrap().type_covered == rtype)
+            };
+            let mut answers: Vec<RawRecord> = self
+                .records
+                .iter()
+                .filter(|r| same_name(&r.name, name) && covers(r))
+                .cloned()
+                .collect();
+            if answers.is_empty() {
+                let wildcard = format!("*.{}", name.splitn(2, '.').nth(1).unwrap_or(""));
+                for r in self
+                    .records
+                    .iter()
+                    .filter(|r| r.name == wildcard && covers(r))
+                {
+                    answers.push(RawRecord {
+                        name: name.into(),
+                        ..r.clone()
+                    });
+                }
+            }
+            Ok(answers)
+        }
+    }
+
+    fn zones() -> (Zone, Zone, Zone) {
+        (
+            Zone::new("", 1),
+            Zone::new("test", 2),
+            Zone::new("example.test", 3),
+        )
+    }
+
+    fn anchor(root: &Zone) -> Vec<Ds> {
+        vec![Ds::parse(&root.ds()).unwrap()]
+    }
+
+    #[test]
+    fn validates_a_signed_chain() {
+        let (root, tld, domain) = zones();
+        let mut stand_in = StandIn::new(&root, &tld, &domain);
+        let mut validator = Validator::new(anchor(&root));
+
+        let answers = stand_in.fetch("WWW.Example.Test", 1).unwrap();
+        assert_eq!(answers.len(), 3);
+        stand_in.queries = 0;
+        let mut fetch = |name: &str, rtype: u16| stand_in.fetch(name, rtype);
+        assert!(validator.validate(&answers, NOW, &mut fetch).is_ok());
+        // DNSKEY for the three zones, and DS for the two below the root
+        assert_eq!(stand_in.queries, 5);
+
+        // the keys are remembered until they expire
+        let mut fetch = |name: &str, rtype: u16| stand_in.fetch(name, rtype);
+        assert!(validator.validate(&answers, NOW + 60, &mut fetch).is_ok());
+        assert_eq!(stand_in.queries, 5);
+
+        let answers = stand_in.fetch("anything.example.test", 16).unwrap();
+        let mut fetch = |name: &str, rtype: u16| stand_in.fetch(name, rtype);
+        assert!(validator.validate(&answers, NOW + 60, &mut fetch).is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_answers() {
+        let (root, tld, domain) = zones();
+        let mut stand_in = StandIn::new(&root, &tld, &domain);
+        let answers = stand_in.fetch("www.example.test", 1).unwrap();
+        let mut check = |answers: &[RawRecord], anchor: Vec<Ds>, now| {
+            let mut fetch = |name: &str, rtype: u16| stand_in.fetch(name, rtype);
+            Validator::new(anchor).validate(answers, now, &mut fetch)
+        };
+        assert!(check(&answers, anchor(&root), NOW).is_ok());
+
+        // an address that was swapped out
+        let mut forged = answers.clone();
+        let a = forged.iter_mut().find(|r| r.rtype == 1).unwrap();
+        a.rdata = vec![203, 0, 113, 1];
+        assert!(check(&forged, anchor(&root), NOW).is_err());
+
+        // an address that wasn't signed at all
+        let unsigned: Vec<RawRecord> = answers.iter().filter(|r| r.rtype == 1).cloned().collect();
+        assert!(check(&unsigned, anchor(&root), NOW).is_err());
+
+        // a chain that doesn't end at the anchor
+        assert!(check(&answers, anchor(&Zone::new("", 9)), NOW).is_err());
+        // signatures that have expired
+        assert!(check(&answers, anchor(&root), NOW + 2 * DAY).is_err());
+    }
+
+    #[test]
+    fn rejects_keys_the_parent_does_not_vouch_for() {
+        let (root, tld, domain) = zones();
+        let mut stand_in = StandIn::new(&root, &tld, &domain);
+        // example.test switches to a key that test has no DS record for
+        let rogue = Zone::new("example.test", 4);
+        stand_in.records.retain(|r| {
+            !(r.name == "example.test" && r.rtype != TYPE_DS) && r.name != "www.example.test"
+        });
+        stand_in.add(
+            &rogue,
+            &[record("example.test", TYPE_DNSKEY, rogue.dnskey())],
+            2,
+        );
+        let a = record("www.example.test", 1, vec![203, 0, 113, 1]);
+        stand_in.add(&rogue, &[a], 3);
+
+        let answers = stand_in.fetch("www.example.test", 1).unwrap();
+        let mut fetch = |name: &str, rtype: u16| stand_in.fetch(name, rtype);
+        assert!(Validator::new(anchor(&root))
+            .validate(&answers, NOW, &mut fetch)
+            .is_err());
+    }
+
+    #[test]
+    fn pins_both_root_ksks() {
+        let anchors = Ds::root_anchors();
+        let tags: Vec<u16> = anchors.iter().map(|ds| ds.key_tag).collect();
+        assert_eq!(tags, [20326, 38696]);
+        assert!(anchors
+            .iter()
+            .all(|ds| ds.algorithm == ALG_RSASHA256 && ds.digest_type == DIGEST_SHA256));
+    }
+
+    #[test]
+    fn follows_a_root_key_rollover() {
+        // The root publishes the retiring and the new key, but only the new one signs the key
+        // set, as it is after the switch from KSK-2017 to KSK-2024.
+        let (root, tld, domain) = zones();
+        let retiring = Zone::new("", 5);
+        let mut stand_in = StandIn::new(&root, &tld, &domain);
+        stand_in.records.retain(|r| !r.name.is_empty());
+        let keys = [
+            record("", TYPE_DNSKEY, retiring.dnskey()),
+            record("", TYPE_DNSKEY, root.dnskey()),
+        ];
+        stand_in.add(&root, &keys, 0);
+
+        let answers = stand_in.fetch("www.example.test", 1).unwrap();
+        let mut check = |anchors: Vec<Ds>| {
+            let mut fetch = |name: &str, rtype: u16| stand_in.fetch(name, rtype);
+            Validator::new(anchors).validate(&answers, NOW, &mut fetch)
+        };
+        let both = vec![anchor(&retiring).remove(0), anchor(&root).remove(0)];
+        assert!(check(both).is_ok());
+        assert!(check(anchor(&root)).is_ok());
+        // a key set signed only by the new key doesn't validate with just the retiring one
+        assert!(check(anchor(&retiring)).is_err());
+    }
+}
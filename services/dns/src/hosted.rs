@@ -1,6 +1,6 @@
 use net::NetIpAddr;
 use std::net::ToSocketAddrs;
-use crate::DnsResponseCode;
+use crate::{DnsRecord, DnsRecordType, DnsResponseCode};
 
 #[derive(Debug)]
 pub struct Dns {
@@ -34,8 +34,16 @@ impl Dns {
             }
         }
     }
+    pub fn lookup_records(&self, name: &str, rtype: DnsRecordType) -> Result<Vec<DnsRecord>, DnsResponseCode> {
+        log::warn!("DNS record lookup of {:?} for {} not implemented in hosted mode!", rtype, name);
+        Err(DnsResponseCode::NotImplemented)
+    }
     pub fn flush_cache(&self) -> Result<(), xous::Error> {
         log::warn!("DNS cache flush not implemented in hosted mode!");
         Ok(())
     }
+    pub fn reload_config(&self) -> Result<(), xous::Error> {
+        log::warn!("DNS config reload not implemented in hosted mode!");
+        Ok(())
+    }
 }
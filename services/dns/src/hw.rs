@@ -8,6 +8,12 @@ use std::net::IpAddr;
 
 use crate::api::*;
 
+/// Page-aligned buffer for `RawRecordLookup`, which is lent to the resolver
+#[repr(C, align(4096))]
+struct RecordLookup {
+    raw: [u8; 4096],
+}
+
 #[derive(Debug)]
 pub struct Dns {
     conn: CID,
@@ -39,12 +45,46 @@ impl Dns {
             }
         }
     }
+    /// Looks up the records of type `rtype` for `name`, following CNAMEs. This is the way to get
+    /// at AAAA, TXT and SRV records, e.g. `_matrix._tcp.<domain>` for Matrix server discovery.
+    pub fn lookup_records(&self, name: &str, rtype: DnsRecordType) -> Result<Vec<DnsRecord>, DnsResponseCode> {
+        if name.len() == 0 || name.len() > DNS_NAME_LENGTH_LIMIT {
+            return Err(DnsResponseCode::FormatError);
+        }
+        let mut request = RecordLookup { raw: [0u8; 4096] };
+        request.raw[..name.len()].copy_from_slice(name.as_bytes());
+        let buf = unsafe {
+            xous::MemoryRange::new(
+                &mut request as *mut RecordLookup as usize,
+                core::mem::size_of::<RecordLookup>(),
+            )
+            .or(Err(DnsResponseCode::UnknownError))?
+        };
+        xous::send_message(
+            self.conn,
+            xous::Message::new_lend_mut(
+                Opcode::RawRecordLookup.to_usize().unwrap(),
+                buf,
+                xous::MemoryAddress::new(rtype as usize),
+                xous::MemorySize::new(name.len()),
+            ),
+        ).or(Err(DnsResponseCode::UnknownError))?;
+        decode_records(&request.raw)
+    }
     pub fn flush_cache(&self) -> Result<(), xous::Error> {
         xous::send_message(
             self.conn,
             xous::Message::new_scalar(Opcode::Flush.to_usize().unwrap(), 0, 0, 0, 0)
         ).map(|_| ())
     }
+    /// Has the resolver pick up changes to the `dns_over_tls`, `dns_tls_servers` and
+    /// `dnssec_validation` preferences. Ignored while the config is frozen.
+    pub fn reload_config(&self) -> Result<(), xous::Error> {
+        xous::send_message(
+            self.conn,
+            xous::Message::new_scalar(Opcode::ReloadConfig.to_usize().unwrap(), 0, 0, 0, 0)
+        ).map(|_| ())
+    }
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
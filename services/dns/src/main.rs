@@ -2,8 +2,14 @@
 #![cfg_attr(target_os = "none", no_main)]
 
 mod api;
+mod dnssec;
+mod message;
 mod time; // why is this here? because it's the only place it'll fit. :-/
+mod transport;
 use api::*;
+use dnssec::{Ds, Validator};
+use message::*;
+use transport::TlsServer;
 
 use net::NetIpAddr;
 use num_traits::*;
@@ -12,306 +18,35 @@ use xous::msg_scalar_unpack;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io::ErrorKind;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
-use std::time::Duration;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::thread;
 use xous_ipc::{Buffer, String};
 
-// KISS DNS
-
-// The DNS implementation here is based on https://github.com/vinc/moros/blob/43ac7cdc8ccc860dc1b6f0f060b5dbcd01424c03/src/usr/host.rs
-// MOROS is MIT licensed.
-// See RFC 1035 for implementation details
-
-#[repr(u16)]
-enum QueryType {
-    A = 1,
-    // NS = 2,
-    // MD = 3,
-    // MF = 4,
-    // CNAME = 5,
-    // SOA = 6,
-    // MX = 15,
-    // TXT = 16,
-}
-
-#[repr(u16)]
-enum QueryClass {
-    IN = 1,
-}
-
-struct Message {
-    pub datagram: Vec<u8>,
-}
-
-const FLAG_RD: u16 = 0x0100; // Recursion desired
-
-impl Message {
-    pub fn from(datagram: &[u8]) -> Self {
-        Self {
-            datagram: Vec::from(datagram),
-        }
-    }
-
-    pub fn query(qname: &str, qtype: QueryType, qclass: QueryClass, id: u16) -> Self {
-        let mut datagram = Vec::new();
-
-        for b in id.to_be_bytes().iter() {
-            datagram.push(*b); // Transaction ID
-        }
-        for b in FLAG_RD.to_be_bytes().iter() {
-            datagram.push(*b); // Flags
-        }
-        for b in (1 as u16).to_be_bytes().iter() {
-            datagram.push(*b); // Questions
-        }
-        for _ in 0..6 {
-            datagram.push(0); // Answer + Authority + Additional
-        }
-        for label in qname.split('.') {
-            datagram.push(label.len() as u8); // QNAME label length
-            for b in label.bytes() {
-                datagram.push(b); // QNAME label bytes
-            }
-        }
-        datagram.push(0); // Root null label
-        for b in (qtype as u16).to_be_bytes().iter() {
-            datagram.push(*b); // QTYPE
-        }
-        for b in (qclass as u16).to_be_bytes().iter() {
-            datagram.push(*b); // QCLASS
-        }
-
-        Self { datagram }
-    }
-
-    pub fn id(&self) -> u16 {
-        u16::from_be_bytes(self.datagram[0..2].try_into().unwrap())
-    }
-
-    pub fn header(&self) -> u16 {
-        u16::from_be_bytes(self.datagram[2..4].try_into().unwrap())
-    }
-
-    pub fn is_response(&self) -> bool {
-        if (self.header() & (1 << 15)) == 0 {
-            false
-        } else {
-            true
-        }
-    }
-
-    fn fast_foward_name(&self, start: usize) -> Result<usize, DnsResponseCode> {
-        use DnsResponseCode::FormatError;
-        let mut index = start;
-        loop {
-            log::trace!("cname index: {}", index);
-            if *(self.datagram.get(index).ok_or(FormatError)?) == 0 {
-                index += 1;
-                break;
-            } else {
-                index += *(self.datagram.get(index).ok_or(FormatError)?) as usize;
-                index += 1;
-            }
-        }
-        Ok(index)
-    }
-
-    pub fn parse_response(&self) -> Result<HashMap<IpAddr, u32>, DnsResponseCode> {
-        use DnsResponseCode::FormatError;
-        log::trace!("parsing packet: {:?}", self.datagram);
-
-        let mut map = HashMap::<IpAddr, u32>::new();
-        // ASSUME: the query ID and response bit fields have already been checked
-        // and that the rcode is valid
-        let qdcount = u16::from_be_bytes(self.datagram[4..6].try_into().unwrap());
-        let ancount = u16::from_be_bytes(self.datagram[6..8].try_into().unwrap());
-
-        let mut index = 12;
-        // fast forward past the qname
-        for queries in 0..qdcount {
-            log::trace!("parsing query{}, index {}", queries, index);
-            index = self.fast_foward_name(index)?;
-            log::trace!("fast forward through qname to {}", index);
-            // index is now at qtype
-            let qtype = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            // A = 1, AAAA = 28
-            if qtype != 1 && qtype != 28 {
-                log::error!("Problem parsing qname, qtype is not 1 or 28: {}", qtype);
-                return Err(FormatError);
-            }
-            index += 2;
-            let qclass = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            if qclass != 1 {
-                log::error!("Problem parsing qname, qclass is not 1: {}", qclass);
-                return Err(FormatError);
-            }
-            index += 2;
-        }
-        // index is now at the aname section
-        for aname in 0..ancount {
-            log::trace!("parsing aname{}, index {}", aname, index);
-            // first check to see if we're dealing with a pointer or a name
-            if self.datagram[index] >= 0xc0 {
-                // pointer
-                index += 1;
-                if self.datagram[index] != 0xc {
-                    log::error!(
-                        "Found aname pointer, but value does not conform to length of aname header"
-                    );
-                    return Err(FormatError);
-                }
-                index += 1;
-            } else {
-                // name, fast forward past the name
-                index = self.fast_foward_name(index)?;
-                log::trace!("fast forward aname to {}", index);
-            }
-            // index is now at type
-            let atype = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            // A = 1, AAAA = 28
-            if atype != 1 && atype != 28 {
-                log::error!("Problem parsing aname, type is not 1 or 28: {}", atype);
-                return Err(FormatError);
-            }
-            index += 2;
-            let aclass = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            if aclass != 1 {
-                log::error!("Problem parsing aname, aclass is not 1: {}", aclass);
-                return Err(FormatError);
-            }
-            index += 2;
-            // this is our TTL
-            let ttl = u32::from_be_bytes(self.datagram[index..index + 4].try_into().unwrap());
-            log::trace!("got ttl: {}", ttl);
-            index += 4;
-            // this is the payload length
-            let addr_len = u16::from_be_bytes(self.datagram[index..index + 2].try_into().unwrap());
-            index += 2;
-            match addr_len {
-                // ipv4
-                4 => {
-                    if atype != 1 {
-                        log::error!("Got a 4-byte address, but ATYPE != A (1)");
-                        return Err(FormatError);
-                    }
-                    // this copy happens because I can't figure out how to get Ipv4Addr::from() to realize it's casting from a [u8;4]
-                    let mut rdata: [u8; 4] = [0; 4];
-                    for (&src, dst) in self.datagram[index..index + 4].iter().zip(rdata.iter_mut())
-                    {
-                        *dst = src;
-                    }
-                    let addr = IpAddr::V4(Ipv4Addr::from(rdata));
-                    index += 4;
-                    map.insert(addr, ttl);
-                }
-                // ipv6
-                16 => {
-                    if atype != 28 {
-                        log::error!("Got a 16-byte address, but ATYPE != AAAA (28)");
-                        return Err(FormatError);
-                    }
-                    // this copy happens because I can't figure out how to get Ipv6Addr::from() to realize it's casting from a [u8;4]
-                    let mut rdata: [u8; 16] = [0; 16];
-                    for (&src, dst) in self.datagram[index..index + 16]
-                        .iter()
-                        .zip(rdata.iter_mut())
-                    {
-                        *dst = src;
-                    }
-                    let addr = IpAddr::V6(Ipv6Addr::from(rdata));
-                    index += 16;
-                    map.insert(addr, ttl);
-                }
-                _ => {
-                    log::error!("Length field does not match a known record type");
-                    return Err(FormatError);
-                }
-            }
-        }
+/// How long to wait on a server before giving up on a query
+const QUERY_TIMEOUT: Duration = Duration::from_millis(10_000);
 
-        Ok(map)
-    }
-
-    /*
-         example response for: betrusted.io->185.199.111.153
-    Header:
-          61, ca,   id
-          81, 80,   header
-          0, 1,     qdcount
-          0, 4,     ancount
-          0, 0,     nscount
-          0, 0,     arcount
-    qname:
-          9,        length 9
-          62, 65, 74, 72, 75, 73, 74, 65, 64,    "betrusted"
-          2,        length 2
-          69, 6f,   "io"
-          0,        end of name
-    qtype:
-          0, 1,     type A
-    qclass:
-          0, 1,     type IN
-    aname0:
-          c0,       name is a pointer (any value > 192 is a pointer)
-          c,        offset of 12 from start of aname0
-          0, 1,     type A
-          0, 1,     class IN
-          0, 0, e, 10,   0xe10 = 3600 seconds TTL
-          0, 4,     4 bytes address
-          b9, c7, 6c, 99,  address
-    aname1:
-          c0,       name is a pointer
-          c,
-          0, 1,     type A
-          0, 1,     class IN
-          0, 0, e, 10,  TTL
-          0, 4,     4 byte address
-          b9, c7, 6d, 99,  address
-    aname2:
-          c0,
-          c,
-          0, 1,
-          0, 1,
-          0, 0, e, 10,
-          0, 4,
-          b9, c7, 6e, 99,
-    aname3:
-          c0,
-          c,
-          0, 1,
-          0, 1,
-          0, 0, e, 10,
-          0, 4,
-          b9, c7, 6f, 99
-         */
-
-    /*
-    pub fn is_query(&self) -> bool {
-        !self.is_response()
-    }
-    */
-
-    pub fn rcode(&self) -> DnsResponseCode {
-        match (self.header() >> 11) & 0xF {
-            0 => DnsResponseCode::NoError,
-            1 => DnsResponseCode::FormatError,
-            2 => DnsResponseCode::ServerFailure,
-            3 => DnsResponseCode::NameError,
-            4 => DnsResponseCode::NotImplemented,
-            5 => DnsResponseCode::Refused,
-            _ => DnsResponseCode::UnknownError,
-        }
-    }
+/// How queries go out, as set in the user's preferences
+struct Privacy {
+    /// Servers to query over TLS. If this is empty, queries go out in the clear, over UDP to
+    /// the DHCP-provided servers.
+    tls_servers: Vec<TlsServer>,
+    dnssec: bool,
 }
 
 pub struct Resolver {
     /// DnsServerManager is a service of the Net crate that automatically updates the DNS server list
     mgr: net::protocols::DnsServerManager,
     socket: UdpSocket,
-    buf: [u8; DNS_PKT_MAX_LEN],
+    buf: [u8; EDNS_PAYLOAD_LEN],
     trng: trng::Trng,
     freeze: bool,
+    pddb_poller: pddb::PddbMountPoller,
+    /// `None` until the preferences have been read, which can only be done once the PDDB is mounted
+    privacy: Option<Privacy>,
+    tls_config: Option<Arc<rustls::ClientConfig>>,
+    validator: Validator,
 }
 impl Resolver {
     pub fn new(xns: &xous_names::XousNames) -> Resolver {
@@ -321,8 +56,7 @@ impl Resolver {
             format!("0.0.0.0:{}", local_port),
         )
         .expect("couldn't create socket for DNS resolver");
-        let timeout = Duration::from_millis(10_000); // 10 seconds for DNS to resolve by default
-        socket.set_read_timeout(Some(timeout)).unwrap();
+        socket.set_read_timeout(Some(QUERY_TIMEOUT)).unwrap();
         socket.set_nonblocking(false).unwrap(); // we want this to block.
                                                 // we /could/ do a non-blocking DNS resolver, but...what would you do in the meantime??
                                                 // blocking is probably what we actually want this time.
@@ -331,9 +65,13 @@ impl Resolver {
             mgr: net::protocols::DnsServerManager::register(&xns)
                 .expect("Couldn't register the DNS server list auto-manager"),
             socket,
-            buf: [0; DNS_PKT_MAX_LEN],
+            buf: [0; EDNS_PAYLOAD_LEN],
             trng,
             freeze: false,
+            pddb_poller: pddb::PddbMountPoller::new(),
+            privacy: None,
+            tls_config: None,
+            validator: Validator::new(Ds::root_anchors()),
         }
     }
     pub fn add_server(&mut self, addr: IpAddr) {
@@ -356,41 +94,211 @@ impl Resolver {
     pub fn trng_u32(&self) -> u32 {
         self.trng.get_u32().unwrap()
     }
-    pub fn resolve(&mut self, name: &str) -> Result<HashMap<IpAddr, u32>, DnsResponseCode> {
+    /// Reads the DNS-over-TLS and DNSSEC preferences, once the PDDB is mounted. Until then,
+    /// queries go out over UDP to the DHCP-provided servers, without DNSSEC.
+    fn privacy(&mut self) -> Option<&Privacy> {
+        if self.privacy.is_none() && self.pddb_poller.is_mounted_nonblocking() {
+            let prefs = userprefs::Manager::new();
+            let tls_servers = if prefs.dns_over_tls_or_default().unwrap_or(false) {
+                let list = prefs.dns_tls_servers_or_default().unwrap_or_default();
+                let mut servers = TlsServer::parse_list(&list);
+                if servers.is_empty() {
+                    servers = TlsServer::parse_list(transport::DEFAULT_TLS_SERVERS);
+                }
+                servers
+            } else {
+                Vec::new()
+            };
+            let dnssec = prefs.dnssec_validation_or_default().unwrap_or(false);
+            log::info!(
+                "DNS over TLS to {} server(s), DNSSEC validation {}",
+                tls_servers.len(),
+                if dnssec { "on" } else { "off" }
+            );
+            self.privacy = Some(Privacy {
+                tls_servers,
+                dnssec,
+            });
+        }
+        self.privacy.as_ref()
+    }
+    /// Reads the preferences if that hasn't been done yet and now can be. Returns `true` if they
+    /// were read just now: anything resolved before then went out over UDP without DNSSEC, so
+    /// it shouldn't be answered from the cache any more.
+    pub fn load_privacy(&mut self) -> bool {
+        self.privacy.is_none() && self.privacy().is_some()
+    }
+    /// Has the preferences read again before the next query. Returns `false`, and changes
+    /// nothing, if the config is frozen.
+    pub fn reload_config(&mut self) -> bool {
+        if self.freeze {
+            log::info!("DNS config is frozen, not reloading the preferences");
+            return false;
+        }
+        self.privacy = None;
+        self.tls_config = None;
+        self.validator.flush();
+        true
+    }
+    fn dnssec(&mut self) -> bool {
+        self.privacy().map(|p| p.dnssec).unwrap_or(false)
+    }
+    /// Sends `query` to one of the DHCP-provided servers over UDP.
+    fn exchange_udp(&mut self, query: &Message) -> Result<Message, DnsResponseCode> {
         if let Some(dns_address) = self.mgr.get_random() {
             let dns_port = 53;
             let server = SocketAddr::new(dns_address, dns_port);
 
-            let qname = name;
-            let qtype = QueryType::A;
-            let qclass = QueryClass::IN;
-            let query = Message::query(qname, qtype, qclass, self.trng.get_u32().unwrap() as u16);
-
             self.socket
                 .send_to(&query.datagram, &server)
                 .map_err(|_| DnsResponseCode::NetworkError)?;
 
-            match self.socket.recv(&mut self.buf) {
-                Ok(len) => {
-                    let message = Message::from(&self.buf[..len]);
-                    if message.id() == query.id() && message.is_response() {
-                        return match message.rcode() {
-                            DnsResponseCode::NoError => message.parse_response(),
-                            rcode => Err(rcode),
-                        };
-                    } else {
-                        Err(DnsResponseCode::NetworkError)
-                    }
-                }
+            let response = match self.socket.recv(&mut self.buf) {
+                Ok(len) => Message::from(&self.buf[..len]),
                 Err(e) => match e.kind() {
-                    ErrorKind::WouldBlock => Err(DnsResponseCode::NetworkError),
-                    _ => Err(DnsResponseCode::UnknownError),
+                    ErrorKind::WouldBlock => return Err(DnsResponseCode::NetworkError),
+                    _ => return Err(DnsResponseCode::UnknownError),
                 },
+            };
+            if response.is_truncated() && response.id() == query.id() {
+                // the answer didn't fit in a datagram, which is common once DNSSEC records
+                // come along, so it has to be asked for again over TCP (RFC 7766)
+                log::debug!(
+                    "UDP response from {} was truncated, retrying over TCP",
+                    server
+                );
+                return transport::exchange_tcp(server, &query.datagram, QUERY_TIMEOUT)
+                    .map(|response| Message::from(&response));
             }
+            Ok(response)
         } else {
             Err(DnsResponseCode::NoServerSpecified)
         }
     }
+    /// Sends `query` over TLS, starting with a random one of the configured servers and
+    /// trying the others in turn. There's no falling back to UDP, as that would give away the
+    /// name the TLS was there to hide.
+    fn exchange_tls(
+        &mut self,
+        query: &Message,
+        servers: &[TlsServer],
+    ) -> Result<Message, DnsResponseCode> {
+        let config = match &self.tls_config {
+            Some(config) => config.clone(),
+            None => {
+                let roots = tls::Tls::new().root_store();
+                if roots.is_empty() {
+                    log::warn!("no trusted TLS certificates, so no DNS-over-TLS server can be authenticated");
+                }
+                let config = transport::client_config(roots);
+                self.tls_config = Some(config.clone());
+                config
+            }
+        };
+        let first = self.trng_u32() as usize;
+        let mut result = Err(DnsResponseCode::NoServerSpecified);
+        for i in 0..servers.len() {
+            let server = &servers[(first + i) % servers.len()];
+            result = transport::exchange(&config, server, &query.datagram, QUERY_TIMEOUT)
+                .map(|response| Message::from(&response));
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+    /// Sends one query, and returns the response. The response carries the DNSSEC records
+    /// for its answers if validation is on.
+    fn query(&mut self, name: &str, qtype: u16) -> Result<Message, DnsResponseCode> {
+        let qclass = QueryClass::IN;
+        let mut query = Message::query(name, qtype, qclass, self.trng.get_u32().unwrap() as u16);
+        if self.dnssec() {
+            query.request_dnssec();
+        }
+
+        let tls_servers = self
+            .privacy()
+            .map(|p| p.tls_servers.clone())
+            .unwrap_or_default();
+        let response = if tls_servers.is_empty() {
+            self.exchange_udp(&query)?
+        } else {
+            self.exchange_tls(&query, &tls_servers)?
+        };
+
+        if response.datagram.len() < 12 || response.id() != query.id() || !response.is_response() {
+            return Err(DnsResponseCode::NetworkError);
+        }
+        if response.is_truncated() {
+            log::warn!("response about {} was truncated", name);
+        }
+        match response.rcode() {
+            DnsResponseCode::NoError => Ok(response),
+            rcode => Err(rcode),
+        }
+    }
+    /// Checks the DNSSEC signatures on the answers in `response`, fetching the keys they chain
+    /// up through.
+    fn validate(&mut self, response: &Message) -> Result<(), DnsResponseCode> {
+        let answers = response.raw_answers()?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as u32)
+            .unwrap_or(0);
+        // the validator can't be borrowed from `self` while it queries through `self`
+        let mut validator =
+            std::mem::replace(&mut self.validator, Validator::new(Ds::root_anchors()));
+        let result = validator.validate(&answers, now, &mut |name: &str, rtype: u16| {
+            self.query(name, rtype)?.raw_answers()
+        });
+        self.validator = validator;
+        result
+    }
+    /// Looks up the records of type `qtype` for `name`, following CNAMEs. Servers normally
+    /// answer with the whole chain, but if one stops partway, the rest is asked for.
+    pub fn lookup(
+        &mut self,
+        name: &str,
+        qtype: DnsRecordType,
+    ) -> Result<Vec<DnsRecord>, DnsResponseCode> {
+        let mut name = name.trim_end_matches('.').to_string();
+        for _ in 0..MAX_CNAME_CHAIN {
+            let response = self.query(&name, qtype as u16)?;
+            if self.dnssec() {
+                self.validate(&response)?;
+            }
+            let records = response.parse_response()?;
+            let (answers, end) = follow_cnames(&records, &name, qtype);
+            if !answers.is_empty() || end.eq_ignore_ascii_case(&name) {
+                return Ok(answers);
+            }
+            log::debug!("following CNAME chain from {} to {}", name, end);
+            name = end;
+        }
+        log::warn!("CNAME chain for {} is too long", name);
+        Err(DnsResponseCode::ServerFailure)
+    }
+    /// Resolves `name` to its IPv4 and IPv6 addresses. A failed AAAA lookup doesn't fail the
+    /// whole resolution, as long as the A lookup worked.
+    pub fn resolve(&mut self, name: &str) -> Result<HashMap<IpAddr, u32>, DnsResponseCode> {
+        let mut map = HashMap::<IpAddr, u32>::new();
+        for record in self.lookup(name, DnsRecordType::A)? {
+            if let DnsRecordData::A(addr) = record.data {
+                map.insert(IpAddr::V4(addr), record.ttl);
+            }
+        }
+        match self.lookup(name, DnsRecordType::Aaaa) {
+            Ok(records) => {
+                for record in records {
+                    if let DnsRecordData::Aaaa(addr) = record.data {
+                        map.insert(IpAddr::V6(addr), record.ttl);
+                    }
+                }
+            }
+            Err(e) => log::debug!("AAAA lookup of {} failed: {:?}", name, e),
+        }
+        Ok(map)
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -442,8 +350,10 @@ fn fill_response(mut env: xous::MessageEnvelope, entries: &HashMap<IpAddr, u32>)
     }
     *i.next()? = entry_count.try_into().ok()?;
 
-    // Start filling in the addreses
-    for addr in entries.keys() {
+    // Start filling in the addreses, IPv4 first as that's what callers are most likely to reach
+    let ipv4 = entries.keys().filter(|a| a.is_ipv4());
+    let ipv6 = entries.keys().filter(|a| a.is_ipv6());
+    for addr in ipv4.chain(ipv6).take(entry_count) {
         match addr {
             &IpAddr::V4(a) => {
                 // IPv4
@@ -454,10 +364,10 @@ fn fill_response(mut env: xous::MessageEnvelope, entries: &HashMap<IpAddr, u32>)
             }
             &IpAddr::V6(a) => {
                 // IPv6
+                *i.next()? = 6;
                 for entry in a.octets() {
                     *i.next()? = entry;
                 }
-                *i.next()? = 6;
             }
         }
     }
@@ -469,6 +379,31 @@ fn fill_response(mut env: xous::MessageEnvelope, entries: &HashMap<IpAddr, u32>)
     None
 }
 
+fn fill_records(mut env: xous::MessageEnvelope, records: &[DnsRecord]) -> Option<()> {
+    let mem = env.body.memory_message_mut()?;
+
+    let s: &mut [u8] = mem.buf.as_slice_mut();
+    let count = encode_records(records, s.get_mut(2..)?);
+    s[0] = 0;
+    s[1] = count as u8;
+    None
+}
+
+/// Picks one of `entries` at random, preferring IPv4 addresses to IPv6 ones.
+fn pick_address(entries: &HashMap<IpAddr, u32>, rand: u32) -> Option<IpAddr> {
+    let ipv4: Vec<&IpAddr> = entries.keys().filter(|a| a.is_ipv4()).collect();
+    let candidates = if ipv4.len() > 0 {
+        ipv4
+    } else {
+        entries.keys().collect()
+    };
+    if candidates.len() > 0 {
+        Some(*candidates[rand as usize % candidates.len()])
+    } else {
+        None
+    }
+}
+
 fn fill_error(mut env: xous::MessageEnvelope, code: DnsResponseCode) -> Option<()> {
     let mem = env.body.memory_message_mut()?;
 
@@ -507,6 +442,22 @@ fn main() -> ! {
     // the `u32` value is the TTL of the IpAddr
     let mut dns_cache = HashMap::<std::string::String, HashMap<IpAddr, u32>>::new();
 
+    // pick up changes to the DNS preferences as they are made. The watches last as long as
+    // `prefs` does, which is as long as the resolver runs.
+    let prefs = userprefs::Manager::new();
+    let reload_cid = xous::connect(dns_sid).unwrap();
+    for key in ["dns_over_tls", "dns_tls_servers", "dnssec_validation"] {
+        prefs
+            .watch(key, move || {
+                xous::send_message(
+                    reload_cid,
+                    xous::Message::new_scalar(Opcode::ReloadConfig.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .ok();
+            })
+            .expect("couldn't watch the DNS preferences");
+    }
+
     // build a thread that pings the UpdateTtl function once every few minutes to expire the DNS cache
     thread::spawn({
         let local_cid = xous::connect(dns_sid).unwrap();
@@ -545,6 +496,9 @@ fn main() -> ! {
                             continue;
                         }
                         log::trace!("performing a lookup of {}", owned_name);
+                        if resolver.load_privacy() {
+                            dns_cache.clear();
+                        }
                         // Try to get the result out of the DNS cache
                         if let Some(entries) = dns_cache.get(&owned_name) {
                             fill_response(msg, entries);
@@ -571,6 +525,30 @@ fn main() -> ! {
                     }
                 };
             }
+            Some(Opcode::RawRecordLookup) => {
+                let rtype = msg
+                    .body
+                    .memory_message()
+                    .and_then(|m| m.offset)
+                    .and_then(|offset| DnsRecordType::from_usize(offset.get()));
+                match (name_from_msg(&msg).map(|s| s.to_owned()), rtype) {
+                    (Ok(owned_name), Some(rtype)) => {
+                        log::trace!("looking up {:?} records of {}", rtype, owned_name);
+                        match resolver.lookup(&owned_name, rtype) {
+                            Ok(records) => fill_records(msg, &records),
+                            Err(e) => fill_error(msg, e),
+                        };
+                    }
+                    (Err(e), _) => {
+                        log::error!("unable to do record lookup: {:?}", e);
+                        fill_error(msg, DnsResponseCode::NameError);
+                    }
+                    (Ok(_), None) => {
+                        log::error!("record lookup of an unsupported type");
+                        fill_error(msg, DnsResponseCode::NotImplemented);
+                    }
+                }
+            }
             Some(Opcode::Lookup) => {
                 let mut buf = unsafe {
                     Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
@@ -579,19 +557,18 @@ fn main() -> ! {
                     .to_original::<String<DNS_NAME_LENGTH_LIMIT>, _>()
                     .unwrap();
                 let name_std = std::string::String::from(name.as_str().unwrap());
+                if resolver.load_privacy() {
+                    dns_cache.clear();
+                }
                 if let Some(cache_entry) = dns_cache.get(&name_std) {
                     // pick a random entry
-                    let rand = resolver.trng_u32() as usize % cache_entry.len();
-                    for (index, (ip_addr, _)) in cache_entry.iter().enumerate() {
-                        if rand == index {
-                            log::debug!("DNS cached: {}->{:?}", name, ip_addr);
-                            let response = DnsResponse {
-                                addr: Some(NetIpAddr::from(*ip_addr)),
-                                code: DnsResponseCode::NoError,
-                            };
-                            buf.replace(response).unwrap();
-                            break;
-                        }
+                    if let Some(ip_addr) = pick_address(cache_entry, resolver.trng_u32()) {
+                        log::debug!("DNS cached: {}->{:?}", name, ip_addr);
+                        let response = DnsResponse {
+                            addr: Some(NetIpAddr::from(ip_addr)),
+                            code: DnsResponseCode::NoError,
+                        };
+                        buf.replace(response).unwrap();
                     }
                 } else {
                     match resolver.resolve(name.as_str().unwrap()) {
@@ -604,16 +581,14 @@ fn main() -> ! {
                                 let cache_entry = dns_cache.get(&name_std).unwrap();
 
                                 // pick a random entry from the query response
-                                let rand = resolver.trng_u32() as usize % cache_entry.len();
-                                for (index, (ip_addr, _)) in cache_entry.iter().enumerate() {
-                                    if rand == index {
-                                        let response = DnsResponse {
-                                            addr: Some(NetIpAddr::from(*ip_addr)),
-                                            code: DnsResponseCode::NoError,
-                                        };
-                                        buf.replace(response).unwrap();
-                                        break;
-                                    }
+                                if let Some(ip_addr) =
+                                    pick_address(cache_entry, resolver.trng_u32())
+                                {
+                                    let response = DnsResponse {
+                                        addr: Some(NetIpAddr::from(ip_addr)),
+                                        code: DnsResponseCode::NoError,
+                                    };
+                                    buf.replace(response).unwrap();
                                 }
                             } else {
                                 // no names found
@@ -683,6 +658,12 @@ fn main() -> ! {
             Some(Opcode::ThawConfig) => {
                 resolver.set_freeze_config(false);
             }
+            Some(Opcode::ReloadConfig) => {
+                // answers cached under the old settings may not have been checked as the new ones want
+                if resolver.reload_config() {
+                    dns_cache.clear();
+                }
+            }
             Some(Opcode::Quit) => {
                 log::warn!("got quit!");
                 break;
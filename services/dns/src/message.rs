@@ -0,0 +1,704 @@
+// KISS DNS
+
+// The DNS implementation here is based on https://github.com/vinc/moros/blob/43ac7cdc8ccc860dc1b6f0f060b5dbcd01424c03/src/usr/host.rs
+// MOROS is MIT licensed.
+// See RFC 1035 for implementation details, and RFC 2782 for SRV records
+
+use crate::api::*;
+use num_traits::*;
+use std::cmp::min;
+use std::convert::TryInto;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+#[repr(u16)]
+pub(crate) enum QueryClass {
+    IN = 1,
+}
+
+const FLAG_RD: u16 = 0x0100; // Recursion desired
+const FLAG_TC: u16 = 0x0200; // Truncated
+
+const TYPE_OPT: u16 = 41;
+/// UDP payload size offered in the OPT record. This is the size recommended by DNS flag day
+/// 2020, which keeps responses clear of IP fragmentation.
+pub(crate) const EDNS_PAYLOAD_LEN: usize = 1232;
+/// The "DNSSEC OK" bit (RFC 3225), in the OPT record's TTL field
+const EDNS_DO: u32 = 0x8000;
+
+/// Compression pointers followed within one name before it's taken to be a loop
+const MAX_NAME_POINTERS: usize = 32;
+/// CNAMEs followed from the name asked about before giving up on the chain
+pub(crate) const MAX_CNAME_CHAIN: usize = 8;
+
+/// A resource record from the answer section of a response
+#[derive(Debug)]
+pub(crate) struct Record {
+    pub name: String,
+    pub ttl: u32,
+    /// `None` for record types we don't decode
+    pub data: Option<DnsRecordData>,
+}
+
+/// A resource record from the answer section with its data left in wire format, for DNSSEC.
+/// Names in the data of CNAME and SRV records are expanded and lower-cased, as the canonical
+/// form (RFC 4034 section 6.2) needs.
+#[derive(Debug, Clone)]
+pub(crate) struct RawRecord {
+    pub name: String,
+    pub rtype: u16,
+    pub ttl: u32,
+    pub rdata: Vec<u8>,
+}
+
+/// Where a record sits in the answer section
+struct Answer {
+    name: String,
+    rtype: u16,
+    ttl: u32,
+    start: usize,
+    len: usize,
+}
+
+pub(crate) struct Message {
+    pub datagram: Vec<u8>,
+}
+
+impl Message {
+    pub fn from(datagram: &[u8]) -> Self {
+        Self {
+            datagram: Vec::from(datagram),
+        }
+    }
+
+    pub fn query(qname: &str, qtype: u16, qclass: QueryClass, id: u16) -> Self {
+        let mut datagram = Vec::new();
+
+        for b in id.to_be_bytes().iter() {
+            datagram.push(*b); // Transaction ID
+        }
+        for b in FLAG_RD.to_be_bytes().iter() {
+            datagram.push(*b); // Flags
+        }
+        for b in (1 as u16).to_be_bytes().iter() {
+            datagram.push(*b); // Questions
+        }
+        for _ in 0..6 {
+            datagram.push(0); // Answer + Authority + Additional
+        }
+        // an empty label would end the name early, so skip them (e.g. from a trailing dot)
+        for label in qname.split('.').filter(|l| !l.is_empty()) {
+            datagram.push(label.len() as u8); // QNAME label length
+            for b in label.bytes() {
+                datagram.push(b); // QNAME label bytes
+            }
+        }
+        datagram.push(0); // Root null label
+        for b in qtype.to_be_bytes().iter() {
+            datagram.push(*b); // QTYPE
+        }
+        for b in (qclass as u16).to_be_bytes().iter() {
+            datagram.push(*b); // QCLASS
+        }
+
+        Self { datagram }
+    }
+
+    /// Adds an OPT record (RFC 6891) to a query made by `query()`, asking for the DNSSEC
+    /// records that go with the answers.
+    pub fn request_dnssec(&mut self) {
+        self.datagram[10..12].copy_from_slice(&1u16.to_be_bytes()); // Additional
+        self.datagram.push(0); // Root name
+        self.datagram.extend_from_slice(&TYPE_OPT.to_be_bytes());
+        // the payload size goes in the class field, and the flags in the TTL field
+        self.datagram
+            .extend_from_slice(&(EDNS_PAYLOAD_LEN as u16).to_be_bytes());
+        self.datagram.extend_from_slice(&EDNS_DO.to_be_bytes());
+        self.datagram.extend_from_slice(&0u16.to_be_bytes()); // No options
+    }
+
+    pub fn id(&self) -> u16 {
+        u16::from_be_bytes(self.datagram[0..2].try_into().unwrap())
+    }
+
+    pub fn header(&self) -> u16 {
+        u16::from_be_bytes(self.datagram[2..4].try_into().unwrap())
+    }
+
+    pub fn is_response(&self) -> bool {
+        if (self.header() & (1 << 15)) == 0 {
+            false
+        } else {
+            true
+        }
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.header() & FLAG_TC != 0
+    }
+
+    fn u16_at(&self, index: usize) -> Result<u16, DnsResponseCode> {
+        let bytes = self
+            .datagram
+            .get(index..index + 2)
+            .ok_or(DnsResponseCode::FormatError)?;
+        Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u32_at(&self, index: usize) -> Result<u32, DnsResponseCode> {
+        let bytes = self
+            .datagram
+            .get(index..index + 4)
+            .ok_or(DnsResponseCode::FormatError)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Reads the name that starts at `start`, following compression pointers (RFC 1035 section
+    /// 4.1.4). Returns the dotted name and the index just past where it is stored at `start`.
+    fn read_name(&self, start: usize) -> Result<(String, usize), DnsResponseCode> {
+        use DnsResponseCode::FormatError;
+        let mut name = String::new();
+        let mut index = start;
+        let mut end = None;
+        let mut pointers = 0;
+        loop {
+            let len = *(self.datagram.get(index).ok_or(FormatError)?) as usize;
+            match len & 0xC0 {
+                0x00 if len == 0 => break,
+                0x00 => {
+                    let label = self
+                        .datagram
+                        .get(index + 1..index + 1 + len)
+                        .ok_or(FormatError)?;
+                    if !name.is_empty() {
+                        name.push('.');
+                    }
+                    name.push_str(&String::from_utf8_lossy(label));
+                    if name.len() > DNS_NAME_LENGTH_LIMIT {
+                        return Err(FormatError);
+                    }
+                    index += 1 + len;
+                }
+                0xC0 => {
+                    pointers += 1;
+                    if pointers > MAX_NAME_POINTERS {
+                        log::error!("Too many compression pointers in name at {}", start);
+                        return Err(FormatError);
+                    }
+                    if end.is_none() {
+                        end = Some(index + 2);
+                    }
+                    index = (self.u16_at(index)? & 0x3FFF) as usize;
+                }
+                _ => {
+                    // 0x40 and 0x80 are reserved label types
+                    log::error!("Unknown label type {:x} at {}", len, index);
+                    return Err(FormatError);
+                }
+            }
+        }
+        Ok((name, end.unwrap_or(index + 1)))
+    }
+
+    fn parse_rdata(
+        &self,
+        rtype: u16,
+        start: usize,
+        len: usize,
+    ) -> Result<Option<DnsRecordData>, DnsResponseCode> {
+        use DnsResponseCode::FormatError;
+        let rdata = self.datagram.get(start..start + len).ok_or(FormatError)?;
+        let data = match FromPrimitive::from_u16(rtype) {
+            Some(DnsRecordType::A) => {
+                let octets: [u8; 4] = rdata.try_into().or(Err(FormatError))?;
+                DnsRecordData::A(Ipv4Addr::from(octets))
+            }
+            Some(DnsRecordType::Aaaa) => {
+                let octets: [u8; 16] = rdata.try_into().or(Err(FormatError))?;
+                DnsRecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            Some(DnsRecordType::Cname) => DnsRecordData::Cname(self.read_name(start)?.0),
+            Some(DnsRecordType::Txt) => {
+                let mut strings = Vec::new();
+                let mut i = 0;
+                while i < rdata.len() {
+                    let len = rdata[i] as usize;
+                    strings.push(rdata.get(i + 1..i + 1 + len).ok_or(FormatError)?.to_vec());
+                    i += 1 + len;
+                }
+                DnsRecordData::Txt(strings)
+            }
+            Some(DnsRecordType::Srv) => {
+                if len < 7 {
+                    return Err(FormatError);
+                }
+                DnsRecordData::Srv {
+                    priority: self.u16_at(start)?,
+                    weight: self.u16_at(start + 2)?,
+                    port: self.u16_at(start + 4)?,
+                    target: self.read_name(start + 6)?.0,
+                }
+            }
+            None => return Ok(None),
+        };
+        Ok(Some(data))
+    }
+
+    /// Walks the question and answer sections, noting where each answer is.
+    fn answers(&self) -> Result<Vec<Answer>, DnsResponseCode> {
+        use DnsResponseCode::FormatError;
+        log::trace!("parsing packet: {:?}", self.datagram);
+
+        let mut answers = Vec::new();
+        // ASSUME: the query ID and response bit fields have already been checked
+        // and that the rcode is valid
+        let qdcount = self.u16_at(4)?;
+        let ancount = self.u16_at(6)?;
+
+        let mut index = 12;
+        // fast forward past the questions
+        for queries in 0..qdcount {
+            log::trace!("parsing query{}, index {}", queries, index);
+            index = self.read_name(index)?.1;
+            // index is now at qtype
+            let qclass = self.u16_at(index + 2)?;
+            if qclass != QueryClass::IN as u16 {
+                log::error!("Problem parsing qname, qclass is not 1: {}", qclass);
+                return Err(FormatError);
+            }
+            index += 4;
+        }
+        // index is now at the answer section
+        for aname in 0..ancount {
+            log::trace!("parsing aname{}, index {}", aname, index);
+            let (name, next) = self.read_name(index)?;
+            index = next;
+            let rtype = self.u16_at(index)?;
+            let aclass = self.u16_at(index + 2)?;
+            let ttl = self.u32_at(index + 4)?;
+            let rdlength = self.u16_at(index + 8)? as usize;
+            index += 10;
+            if self.datagram.len() < index + rdlength {
+                return Err(FormatError);
+            }
+            if aclass != QueryClass::IN as u16 {
+                log::warn!("Skipping answer for {} in class {}", name, aclass);
+            } else {
+                answers.push(Answer {
+                    name,
+                    rtype,
+                    ttl,
+                    start: index,
+                    len: rdlength,
+                });
+            }
+            index += rdlength;
+        }
+
+        Ok(answers)
+    }
+
+    /// Returns the records in the answer section.
+    pub fn parse_response(&self) -> Result<Vec<Record>, DnsResponseCode> {
+        let mut records = Vec::new();
+        for answer in self.answers()? {
+            records.push(Record {
+                data: self.parse_rdata(answer.rtype, answer.start, answer.len)?,
+                name: answer.name,
+                ttl: answer.ttl,
+            });
+        }
+        Ok(records)
+    }
+
+    /// Returns the records in the answer section, in wire format.
+    pub fn raw_answers(&self) -> Result<Vec<RawRecord>, DnsResponseCode> {
+        use DnsResponseCode::FormatError;
+        let mut records = Vec::new();
+        for answer in self.answers()? {
+            let (start, len) = (answer.start, answer.len);
+            let rdata = match FromPrimitive::from_u16(answer.rtype) {
+                Some(DnsRecordType::Cname) => name_to_wire(&self.read_name(start)?.0),
+                Some(DnsRecordType::Srv) if len >= 7 => {
+                    let mut rdata = self.datagram[start..start + 6].to_vec();
+                    rdata.extend_from_slice(&name_to_wire(&self.read_name(start + 6)?.0));
+                    rdata
+                }
+                Some(DnsRecordType::Srv) => return Err(FormatError),
+                _ => self.datagram[start..start + len].to_vec(),
+            };
+            records.push(RawRecord {
+                name: answer.name,
+                rtype: answer.rtype,
+                ttl: answer.ttl,
+                rdata,
+            });
+        }
+        Ok(records)
+    }
+
+    /*
+         example response for: betrusted.io->185.199.111.153
+    Header:
+          61, ca,   id
+          81, 80,   header
+          0, 1,     qdcount
+          0, 4,     ancount
+          0, 0,     nscount
+          0, 0,     arcount
+    qname:
+          9,        length 9
+          62, 65, 74, 72, 75, 73, 74, 65, 64,    "betrusted"
+          2,        length 2
+          69, 6f,   "io"
+          0,        end of name
+    qtype:
+          0, 1,     type A
+    qclass:
+          0, 1,     type IN
+    aname0:
+          c0,       name is a pointer (any value > 192 is a pointer)
+          c,        offset of 12 from start of aname0
+          0, 1,     type A
+          0, 1,     class IN
+          0, 0, e, 10,   0xe10 = 3600 seconds TTL
+          0, 4,     4 bytes address
+          b9, c7, 6c, 99,  address
+    aname1:
+          c0,       name is a pointer
+          c,
+          0, 1,     type A
+          0, 1,     class IN
+          0, 0, e, 10,  TTL
+          0, 4,     4 byte address
+          b9, c7, 6d, 99,  address
+    aname2:
+          c0,
+          c,
+          0, 1,
+          0, 1,
+          0, 0, e, 10,
+          0, 4,
+          b9, c7, 6e, 99,
+    aname3:
+          c0,
+          c,
+          0, 1,
+          0, 1,
+          0, 0, e, 10,
+          0, 4,
+          b9, c7, 6f, 99
+         */
+
+    /*
+    pub fn is_query(&self) -> bool {
+        !self.is_response()
+    }
+    */
+
+    pub fn rcode(&self) -> DnsResponseCode {
+        match (self.header() >> 11) & 0xF {
+            0 => DnsResponseCode::NoError,
+            1 => DnsResponseCode::FormatError,
+            2 => DnsResponseCode::ServerFailure,
+            3 => DnsResponseCode::NameError,
+            4 => DnsResponseCode::NotImplemented,
+            5 => DnsResponseCode::Refused,
+            _ => DnsResponseCode::UnknownError,
+        }
+    }
+}
+
+/// Lays out a dotted name as labels, lower-cased, as in the canonical form of RFC 4034.
+pub(crate) fn name_to_wire(name: &str) -> Vec<u8> {
+    let mut wire = Vec::new();
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        wire.push(label.len() as u8);
+        wire.extend(label.bytes().map(|b| b.to_ascii_lowercase()));
+    }
+    wire.push(0);
+    wire
+}
+
+/// Picks the answers about `qname` out of `records`, following any CNAMEs that lead away from
+/// it. Answers reached through a CNAME have their TTL capped by the CNAME's.
+///
+/// Returns the answers, and the name the chain ended at. If there are no answers and that name
+/// isn't `qname`, the server left the rest of the chain for us to look up.
+pub(crate) fn follow_cnames(
+    records: &[Record],
+    qname: &str,
+    qtype: DnsRecordType,
+) -> (Vec<DnsRecord>, String) {
+    let mut name = qname.trim_end_matches('.').to_string();
+    let mut ttl_cap = u32::MAX;
+    for _ in 0..=MAX_CNAME_CHAIN {
+        let owned = |r: &&Record| r.name.eq_ignore_ascii_case(&name);
+        let answers: Vec<DnsRecord> = records
+            .iter()
+            .filter(owned)
+            .filter_map(|r| match &r.data {
+                Some(data) if data.record_type() == qtype => Some(DnsRecord {
+                    ttl: min(r.ttl, ttl_cap),
+                    data: data.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+        if !answers.is_empty() {
+            return (answers, name);
+        }
+        let next = records.iter().filter(owned).find_map(|r| match &r.data {
+            Some(DnsRecordData::Cname(target)) => Some((target.clone(), r.ttl)),
+            _ => None,
+        });
+        match next {
+            Some((target, ttl)) => {
+                log::trace!("{} is an alias for {}", name, target);
+                name = target;
+                ttl_cap = min(ttl_cap, ttl);
+            }
+            None => break,
+        }
+    }
+    (Vec::new(), name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// betrusted.io A, as in the example above: four answers, all compressed to the question
+    const BETRUSTED_A: [u8; 94] = [
+        0x61, 0xca, 0x81, 0x80, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, //
+        0x09, b'b', b'e', b't', b'r', b'u', b's', b't', b'e', b'd', 0x02, b'i', b'o', 0x00, //
+        0x00, 0x01, 0x00, 0x01, //
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, //
+        0xb9, 0xc7, 0x6c, 0x99, //
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, //
+        0xb9, 0xc7, 0x6d, 0x99, //
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, //
+        0xb9, 0xc7, 0x6e, 0x99, //
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, //
+        0xb9, 0xc7, 0x6f, 0x99, //
+    ];
+
+    /// www.github.com AAAA: a CNAME to github.com, whose name is a pointer into the question,
+    /// then the AAAA of github.com, named by a pointer into the CNAME's data
+    const GITHUB_CNAME_AAAA: [u8; 86] = [
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, //
+        // 12: www.github.com, with github.com at 16
+        0x03, b'w', b'w', b'w', 0x06, b'g', b'i', b't', b'h', b'u', b'b', 0x03, b'c', b'o', b'm',
+        0x00, //
+        0x00, 0x1c, 0x00, 0x01, //
+        // 32: www.github.com CNAME github.com, ttl 3600
+        0xc0, 0x0c, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x02, 0xc0, 0x10,
+        // 46: github.com AAAA 2001:db8::1, ttl 60
+        0xc0, 0x10, 0x00, 0x1c, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x10, //
+        0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, //
+        // trailing bytes of a truncated additional section, which aren't counted
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    /// _matrix._tcp.example.org SRV 10 5 8448 matrix.example.org, the target partly compressed
+    const MATRIX_SRV: [u8; 84] = [
+        0xab, 0xcd, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, //
+        // 12: _matrix._tcp.example.org, with example.org at 25
+        0x07, b'_', b'm', b'a', b't', b'r', b'i', b'x', 0x04, b'_', b't', b'c', b'p', //
+        0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'o', b'r', b'g', 0x00, //
+        0x00, 0x21, 0x00, 0x01, //
+        // 42: the answer, ttl 300
+        0xc0, 0x0c, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x01, 0x2c, 0x00, 0x0f, //
+        0x00, 0x0a, 0x00, 0x05, 0x21, 0x00, //
+        0x06, b'm', b'a', b't', b'r', b'i', b'x', 0xc0, 0x19, //
+        // the start of an authority section, which isn't counted
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    /// example.com TXT with two character-strings, one of them empty
+    const EXAMPLE_TXT: [u8; 53] = [
+        0x00, 0x01, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, //
+        0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o', b'm', 0x00, //
+        0x00, 0x10, 0x00, 0x01, //
+        0xc0, 0x0c, 0x00, 0x10, 0x00, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x0c, //
+        0x0a, b'v', b'=', b's', b'p', b'f', b'1', b' ', b'-', b'a', b'l', //
+        0x00,
+    ];
+
+    #[test]
+    fn parses_a_records() {
+        let records = Message::from(&BETRUSTED_A).parse_response().unwrap();
+        assert_eq!(records.len(), 4);
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record.name, "betrusted.io");
+            assert_eq!(record.ttl, 3600);
+            assert_eq!(
+                record.data,
+                Some(DnsRecordData::A(Ipv4Addr::new(
+                    185,
+                    199,
+                    108 + i as u8,
+                    153
+                )))
+            );
+        }
+    }
+
+    #[test]
+    fn follows_cname_to_aaaa() {
+        let records = Message::from(&GITHUB_CNAME_AAAA).parse_response().unwrap();
+        assert_eq!(
+            records[0].data,
+            Some(DnsRecordData::Cname("github.com".into()))
+        );
+        assert_eq!(records[1].name, "github.com");
+
+        let (answers, name) = follow_cnames(&records, "WWW.GitHub.com.", DnsRecordType::Aaaa);
+        assert_eq!(name, "github.com");
+        assert_eq!(
+            answers,
+            vec![DnsRecord {
+                ttl: 60,
+                data: DnsRecordData::Aaaa("2001:db8::1".parse().unwrap()),
+            }]
+        );
+        // asking for the CNAME itself stops at the first step
+        let (answers, _) = follow_cnames(&records, "www.github.com", DnsRecordType::Cname);
+        assert_eq!(answers[0].ttl, 3600);
+        // the server only gave part of the chain, so it ends somewhere else
+        let (answers, name) = follow_cnames(&records[..1], "www.github.com", DnsRecordType::Aaaa);
+        assert!(answers.is_empty());
+        assert_eq!(name, "github.com");
+    }
+
+    #[test]
+    fn cname_ttl_caps_answers() {
+        let record = |name: &str, ttl, data| Record {
+            name: name.into(),
+            ttl,
+            data: Some(data),
+        };
+        let records = [
+            record("a.example", 30, DnsRecordData::Cname("b.example".into())),
+            record(
+                "b.example",
+                600,
+                DnsRecordData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ),
+            // a loop is given up on, instead of followed forever
+            record("x.example", 600, DnsRecordData::Cname("y.example".into())),
+            record("y.example", 600, DnsRecordData::Cname("x.example".into())),
+        ];
+        let (answers, _) = follow_cnames(&records, "a.example", DnsRecordType::A);
+        assert_eq!(answers[0].ttl, 30);
+        let (answers, _) = follow_cnames(&records, "x.example", DnsRecordType::A);
+        assert!(answers.is_empty());
+    }
+
+    #[test]
+    fn parses_srv_and_txt() {
+        let records = Message::from(&MATRIX_SRV).parse_response().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "_matrix._tcp.example.org");
+        assert_eq!(
+            records[0].data,
+            Some(DnsRecordData::Srv {
+                priority: 10,
+                weight: 5,
+                port: 8448,
+                target: "matrix.example.org".into(),
+            })
+        );
+
+        let records = Message::from(&EXAMPLE_TXT).parse_response().unwrap();
+        assert_eq!(
+            records[0].data,
+            Some(DnsRecordData::Txt(vec![b"v=spf1 -al".to_vec(), vec![]]))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_names() {
+        // an answer whose name points at itself
+        let mut looped = BETRUSTED_A;
+        looped[30..32].copy_from_slice(&[0xc0, 30]);
+        assert!(matches!(
+            Message::from(&looped).parse_response(),
+            Err(DnsResponseCode::FormatError)
+        ));
+        // cut off in the middle of an answer
+        assert!(matches!(
+            Message::from(&BETRUSTED_A[..50]).parse_response(),
+            Err(DnsResponseCode::FormatError)
+        ));
+        // a reserved label type
+        let mut reserved = BETRUSTED_A;
+        reserved[30] = 0x80;
+        assert!(matches!(
+            Message::from(&reserved).parse_response(),
+            Err(DnsResponseCode::FormatError)
+        ));
+    }
+
+    #[test]
+    fn raw_answers_are_canonical() {
+        let records = Message::from(&GITHUB_CNAME_AAAA).raw_answers().unwrap();
+        assert_eq!(records[0].rtype, DnsRecordType::Cname as u16);
+        assert_eq!(records[0].rdata, name_to_wire("GitHub.com"));
+        assert_eq!(records[1].rdata.len(), 16);
+
+        let records = Message::from(&MATRIX_SRV).raw_answers().unwrap();
+        let mut srv = vec![0x00, 0x0a, 0x00, 0x05, 0x21, 0x00];
+        srv.extend_from_slice(b"\x06matrix\x07example\x03org\x00");
+        assert_eq!(records[0].rdata, srv);
+    }
+
+    #[test]
+    fn requests_dnssec_records() {
+        let mut query = Message::query("example.org", DnsRecordType::A as u16, QueryClass::IN, 7);
+        let len = query.datagram.len();
+        query.request_dnssec();
+        assert_eq!(query.u16_at(10).unwrap(), 1);
+        assert_eq!(
+            query.datagram[len..],
+            [0x00, 0x00, 0x29, 0x04, 0xd0, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn raw_records_round_trip() {
+        let mut records = Message::from(&GITHUB_CNAME_AAAA)
+            .parse_response()
+            .unwrap()
+            .into_iter()
+            .chain(Message::from(&MATRIX_SRV).parse_response().unwrap())
+            .chain(Message::from(&EXAMPLE_TXT).parse_response().unwrap())
+            .map(|r| DnsRecord {
+                ttl: r.ttl,
+                data: r.data.unwrap(),
+            })
+            .collect::<Vec<_>>();
+        records.push(DnsRecord {
+            ttl: 1,
+            data: DnsRecordData::A(Ipv4Addr::new(10, 0, 0, 1)),
+        });
+
+        let mut buf = [0u8; 4096];
+        let count = encode_records(&records, &mut buf[2..]);
+        assert_eq!(count, records.len());
+        buf[1] = count as u8;
+        assert_eq!(decode_records(&buf).unwrap(), records);
+
+        // records that don't fit are left off
+        let mut small = [0u8; 40];
+        let count = encode_records(&records, &mut small[2..]);
+        assert_eq!(count, 1);
+        small[1] = count as u8;
+        assert_eq!(decode_records(&small).unwrap(), records[..1].to_vec());
+
+        assert!(matches!(
+            decode_records(&[1, DnsResponseCode::NameError as u8]),
+            Err(DnsResponseCode::NameError)
+        ));
+    }
+}
@@ -0,0 +1,301 @@
+//! DNS over TLS (RFC 7858). Queries go to servers named in advance, over a TLS connection that
+//! has to present a certificate for that name, so nobody on the network path can read or
+//! change them.
+//!
+//! Plain DNS over TCP (RFC 7766) lives here too, for answers that don't fit in a UDP datagram.
+
+use crate::api::DnsResponseCode;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub(crate) const DOT_PORT: u16 = 853;
+
+/// Used when DNS over TLS is on and the `dns_tls_servers` preference is empty
+pub(crate) const DEFAULT_TLS_SERVERS: &str = "9.9.9.9#dns.quad9.net,\
+    149.112.112.112#dns.quad9.net,\
+    1.1.1.1#cloudflare-dns.com,\
+    1.0.0.1#cloudflare-dns.com";
+
+/// A DNS-over-TLS server, and the name its certificate has to be for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TlsServer {
+    pub addr: SocketAddr,
+    pub name: String,
+}
+
+impl TlsServer {
+    /// Parses `address[:port]#name`. The port defaults to 853, and an IPv6 address with a port
+    /// goes in brackets.
+    pub fn parse(entry: &str) -> Option<TlsServer> {
+        let (addr, name) = entry.trim().split_once('#')?;
+        let addr = addr.trim();
+        let addr = match addr.parse::<SocketAddr>() {
+            Ok(addr) => addr,
+            Err(_) => SocketAddr::new(addr.parse::<IpAddr>().ok()?, DOT_PORT),
+        };
+        let name = name.trim();
+        rustls::ServerName::try_from(name).ok()?;
+        Some(TlsServer {
+            addr,
+            name: name.to_string(),
+        })
+    }
+
+    /// Parses a comma-separated list of servers, leaving out the entries that don't parse.
+    pub fn parse_list(list: &str) -> Vec<TlsServer> {
+        list.split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .filter_map(|entry| {
+                let server = TlsServer::parse(entry);
+                if server.is_none() {
+                    log::warn!("ignoring malformed DNS-over-TLS server '{}'", entry);
+                }
+                server
+            })
+            .collect()
+    }
+}
+
+/// Builds the TLS configuration for talking to servers, trusting the CAs in `roots`.
+pub(crate) fn client_config(roots: rustls::RootCertStore) -> Arc<rustls::ClientConfig> {
+    Arc::new(
+        rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )
+}
+
+/// Sends `query` to `server` and returns the response. Each query gets its own connection,
+/// which costs a handshake, but the resolver only queries on a cache miss.
+pub(crate) fn exchange(
+    config: &Arc<rustls::ClientConfig>,
+    server: &TlsServer,
+    query: &[u8],
+    timeout: Duration,
+) -> Result<Vec<u8>, DnsResponseCode> {
+    let failed = |e: &dyn std::fmt::Display| {
+        log::warn!(
+            "DNS over TLS to {} ({}) failed: {}",
+            server.name,
+            server.addr,
+            e
+        );
+        DnsResponseCode::NetworkError
+    };
+    let name = rustls::ServerName::try_from(server.name.as_str()).map_err(|e| failed(&e))?;
+    let conn = rustls::ClientConnection::new(config.clone(), name).map_err(|e| failed(&e))?;
+    let sock = TcpStream::connect(server.addr).map_err(|e| failed(&e))?;
+    sock.set_read_timeout(Some(timeout))
+        .map_err(|e| failed(&e))?;
+    sock.set_write_timeout(Some(timeout))
+        .map_err(|e| failed(&e))?;
+    let mut tls = rustls::StreamOwned::new(conn, sock);
+    let response = framed_exchange(&mut tls, query).map_err(|e| failed(&e))?;
+
+    tls.conn.send_close_notify();
+    tls.flush().ok();
+    Ok(response)
+}
+
+/// Sends `query` to `server` over plain TCP and returns the response. This is for retrying a
+/// query whose UDP answer came back truncated.
+pub(crate) fn exchange_tcp(
+    server: SocketAddr,
+    query: &[u8],
+    timeout: Duration,
+) -> Result<Vec<u8>, DnsResponseCode> {
+    let failed = |e: &dyn std::fmt::Display| {
+        log::warn!("DNS over TCP to {} failed: {}", server, e);
+        DnsResponseCode::NetworkError
+    };
+    let mut sock = TcpStream::connect(server).map_err(|e| failed(&e))?;
+    sock.set_read_timeout(Some(timeout))
+        .map_err(|e| failed(&e))?;
+    sock.set_write_timeout(Some(timeout))
+        .map_err(|e| failed(&e))?;
+    framed_exchange(&mut sock, query).map_err(|e| failed(&e))
+}
+
+/// Writes `query` to `stream` with the two-byte length prefix DNS uses over a stream, and
+/// reads back a response framed the same way.
+fn framed_exchange<S: Read + Write>(stream: &mut S, query: &[u8]) -> std::io::Result<Vec<u8>> {
+    // the length prefix goes out in the same write as the query (RFC 7766 section 8)
+    let mut framed = (query.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(query);
+    stream.write_all(&framed)?;
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len)?;
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response)?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::DnsRecordType;
+    use crate::message::{Message, QueryClass};
+    use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig, ServerConnection};
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::thread;
+
+    // A P-256 CA, and a certificate it issued for `dns.test`, made with openssl to last a century
+    const CA_CERT: &[u8] = include_bytes!("testdata/ca.der");
+    const SERVER_CERT: &[u8] = include_bytes!("testdata/server.der");
+    const SERVER_KEY: &[u8] = include_bytes!("testdata/server.key.der");
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn roots() -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(CA_CERT.to_vec())).unwrap();
+        roots
+    }
+
+    /// A stand-in DNS-over-TLS resolver on localhost. It answers one query with 192.0.2.1, and
+    /// hands back the query it got.
+    fn stand_in() -> (SocketAddr, thread::JoinHandle<Option<Vec<u8>>>) {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(SERVER_CERT.to_vec())],
+                PrivateKey(SERVER_KEY.to_vec()),
+            )
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (sock, _) = listener.accept().unwrap();
+            let conn = ServerConnection::new(Arc::new(config)).unwrap();
+            let mut tls = rustls::StreamOwned::new(conn, sock);
+            let mut len = [0u8; 2];
+            // a client that doesn't like the certificate hangs up during the handshake
+            tls.read_exact(&mut len).ok()?;
+            let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+            tls.read_exact(&mut query).unwrap();
+            tls.write_all(&answer(&query)).unwrap();
+            tls.flush().unwrap();
+            Some(query)
+        });
+        (addr, handle)
+    }
+
+    /// The framed answer to `query`: 192.0.2.1
+    fn answer(query: &[u8]) -> Vec<u8> {
+        let mut response = query.to_vec();
+        response[2] |= 0x80; // a response
+        response[7] = 1; // with one answer, named by a pointer to the question
+        response.extend_from_slice(&[0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01]);
+        response.extend_from_slice(&[0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, 192, 0, 2, 1]);
+        let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&response);
+        framed
+    }
+
+    #[test]
+    fn queries_over_tls() {
+        let (addr, handle) = stand_in();
+        let server = TlsServer {
+            addr,
+            name: "dns.test".into(),
+        };
+        let query = Message::query(
+            "betrusted.io",
+            DnsRecordType::A as u16,
+            QueryClass::IN,
+            0x5a5a,
+        );
+        let response =
+            exchange(&client_config(roots()), &server, &query.datagram, TIMEOUT).unwrap();
+
+        assert_eq!(handle.join().unwrap(), Some(query.datagram.clone()));
+        let response = Message::from(&response);
+        assert_eq!(response.id(), 0x5a5a);
+        assert!(response.is_response());
+        let records = response.parse_response().unwrap();
+        assert_eq!(records[0].name, "betrusted.io");
+        assert_eq!(
+            records[0].data,
+            Some(crate::api::DnsRecordData::A(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+    }
+
+    #[test]
+    fn queries_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut sock, _) = listener.accept().unwrap();
+            let mut len = [0u8; 2];
+            sock.read_exact(&mut len).unwrap();
+            let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+            sock.read_exact(&mut query).unwrap();
+            sock.write_all(&answer(&query)).unwrap();
+            query
+        });
+        let query = Message::query("betrusted.io", DnsRecordType::A as u16, QueryClass::IN, 7);
+        let response = exchange_tcp(addr, &query.datagram, TIMEOUT).unwrap();
+
+        assert_eq!(handle.join().unwrap(), query.datagram);
+        let response = Message::from(&response);
+        assert_eq!(response.id(), 7);
+        assert!(!response.is_truncated());
+        assert_eq!(
+            response.parse_response().unwrap()[0].data,
+            Some(crate::api::DnsRecordData::A(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+    }
+
+    #[test]
+    fn refuses_unauthenticated_servers() {
+        let query = Message::query("betrusted.io", DnsRecordType::A as u16, QueryClass::IN, 1);
+
+        // the certificate is for another name
+        let (addr, handle) = stand_in();
+        let server = TlsServer {
+            addr,
+            name: "dns.example".into(),
+        };
+        let result = exchange(&client_config(roots()), &server, &query.datagram, TIMEOUT);
+        assert!(matches!(result, Err(DnsResponseCode::NetworkError)));
+        assert_eq!(handle.join().unwrap(), None);
+
+        // the certificate is from a CA that isn't trusted
+        let (addr, handle) = stand_in();
+        let server = TlsServer {
+            addr,
+            name: "dns.test".into(),
+        };
+        let empty = client_config(RootCertStore::empty());
+        let result = exchange(&empty, &server, &query.datagram, TIMEOUT);
+        assert!(matches!(result, Err(DnsResponseCode::NetworkError)));
+        assert_eq!(handle.join().unwrap(), None);
+    }
+
+    #[test]
+    fn parses_server_lists() {
+        let servers = TlsServer::parse_list(
+            "9.9.9.9#dns.quad9.net, [2620:fe::fe]:8853 # dns.quad9.net,\
+             9.9.9.10,dns.quad9.net#dns.quad9.net,,192.0.2.1#",
+        );
+        assert_eq!(
+            servers,
+            vec![
+                TlsServer {
+                    addr: "9.9.9.9:853".parse().unwrap(),
+                    name: "dns.quad9.net".into(),
+                },
+                TlsServer {
+                    addr: "[2620:fe::fe]:8853".parse().unwrap(),
+                    name: "dns.quad9.net".into(),
+                },
+            ]
+        );
+        assert_eq!(TlsServer::parse_list(DEFAULT_TLS_SERVERS).len(), 4);
+    }
+}
@@ -379,7 +379,8 @@ fn main() -> ! {
         match FromPrimitive::from_usize(msg.body.id()) {
             Some(Opcode::AcquireExclusive) => {
                 msg_blocking_scalar_unpack!(msg, id0, id1, id2, flags, {
-                    if client_id.is_none() && !SUSPEND_PENDING.load(Ordering::Relaxed) {
+                    // the hosted stub can't hash, so never hand it out: clients fall back to software
+                    if cfg!(target_os = "xous") && client_id.is_none() && !SUSPEND_PENDING.load(Ordering::Relaxed) {
                         client_id = Some([id0 as u32, id1 as u32, id2 as u32]);
                         //log::trace!("giving {:x?} an exclusive lock", client_id);
                         mode = Some(FromPrimitive::from_usize(flags).unwrap());
@@ -30,6 +30,8 @@ pub use blitstr2::*;
 pub mod tile;
 #[cfg(feature="ditherpunk")]
 pub use tile::*;
+#[cfg(not(target_os = "xous"))]
+pub mod testhooks;
 
 use std::hash::{Hash, Hasher};
 
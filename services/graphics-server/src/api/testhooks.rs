@@ -0,0 +1,102 @@
+//! Lets a test harness see what the graphics server has drawn in hosted mode.
+//!
+//! The hooks are only served when `XOUS_GFX_TEST_HOOKS` is set in the environment, and they
+//! listen on their own name so that they don't use up the one connection the GAM is allowed.
+
+use super::{Rectangle, LINES};
+use xous_ipc::String;
+
+pub const SERVER_NAME_GFX_TEST_HOOKS: &str = "_Graphics test hooks_";
+pub const TEST_HOOKS_ENV: &str = "XOUS_GFX_TEST_HOOKS";
+
+/// Width of a line of the framebuffer in 32-bit words
+pub const FRAME_WIDTH_WORDS: usize = 11;
+pub const FRAME_WORDS: usize = FRAME_WIDTH_WORDS * LINES as usize;
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum TestHookOpcode {
+    /// Returns the oldest text on record that was drawn after a given sequence number
+    NextText,
+    /// Returns a copy of the last frame that was flushed to the screen
+    Frame,
+    /// Returns the sequence numbers of the newest text and frame
+    Latest,
+}
+
+/// A `TextView` as the graphics server drew it.
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct DrawnText {
+    /// Set by the caller: the text returned is the oldest one drawn after this
+    pub after: u32,
+    /// Sequence number of the text, or `None` if nothing newer has been drawn
+    pub seq: Option<u32>,
+    pub text: String<3072>,
+    /// The area cleared for the text, in screen coordinates
+    pub bounds: Option<Rectangle>,
+    pub invert: bool,
+}
+impl DrawnText {
+    pub fn after(after: u32) -> DrawnText {
+        DrawnText {
+            after,
+            seq: None,
+            text: String::new(),
+            bounds: None,
+            invert: false,
+        }
+    }
+}
+
+/// A copy of the screen. Each line is `FRAME_WIDTH_WORDS` words with the leftmost pixel in bit
+/// 0 of the first word, and a set bit is a `PixelColor::Dark` pixel.
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Frame {
+    /// Counts the flushes since boot; 0 means nothing has been flushed yet
+    pub seq: u32,
+    pub fb: [u32; FRAME_WORDS],
+}
+impl Frame {
+    pub fn blank() -> Frame {
+        Frame { seq: 0, fb: [0; FRAME_WORDS] }
+    }
+    pub fn is_dark(&self, x: i16, y: i16) -> bool {
+        if x < 0 || y < 0 || x >= super::WIDTH || y >= LINES {
+            return false;
+        }
+        let (x, y) = (x as usize, y as usize);
+        self.fb[y * FRAME_WIDTH_WORDS + x / 32] & (1 << (x % 32)) != 0
+    }
+    /// Counts the dark pixels inside `region`, including its edges.
+    pub fn dark_pixels(&self, region: Rectangle) -> usize {
+        let mut count = 0;
+        for y in region.tl.y..=region.br.y {
+            for x in region.tl.x..=region.br.x {
+                if self.is_dark(x, y) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Point;
+
+    #[test]
+    fn dark_pixels_counts_inside_region() {
+        let mut frame = Frame::blank();
+        // (0, 0), (33, 1) and one pixel off the right edge of the screen
+        frame.fb[0] = 1;
+        frame.fb[FRAME_WIDTH_WORDS + 1] = 1 << 1;
+        frame.fb[FRAME_WIDTH_WORDS - 1] = 1 << 31;
+        assert!(frame.is_dark(0, 0));
+        assert!(frame.is_dark(33, 1));
+        assert!(!frame.is_dark(-1, 0));
+        assert_eq!(frame.dark_pixels(Rectangle::new(Point::new(0, 0), Point::new(33, 1))), 2);
+        assert_eq!(frame.dark_pixels(Rectangle::new(Point::new(1, 0), Point::new(32, 1))), 0);
+        assert_eq!(frame.dark_pixels(Rectangle::new(Point::new(300, 0), Point::new(400, 0))), 0);
+    }
+}
@@ -0,0 +1,293 @@
+//! Runs the hosted display without a window, so UI flows can run on machines that have no
+//! display, such as CI runners.
+//!
+//! The backend is picked at startup from the environment:
+//!
+//! * `XOUS_GFX_BACKEND=headless` selects this backend instead of the minifb window
+//! * `XOUS_GFX_CAPTURE_DIR` is where PNG captures go, `gfx-capture` by default
+//! * `XOUS_GFX_CAPTURE` is `flush` to capture every frame that is drawn (the default), or
+//!   `demand` to only capture when the key script asks for it
+//! * `XOUS_GFX_KEY_SCRIPT` names a key script to play back, which stands in for the keyboard
+//!
+//! A key script has one command per line:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! type hello world     types the rest of the line
+//! key down down enter  presses named keys: enter, backspace, left, right, up, down, home,
+//!                      space, f1 to f4, or any single character
+//! wait 500             pauses for 500ms
+//! capture main-menu    writes the screen to main-menu.png in the capture directory
+//! ```
+//!
+//! The script is read a line at a time, so it may also be a named pipe that a test harness
+//! writes commands into as it goes.
+
+use crate::api::{LINES, WIDTH};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::{FB_LINES, FB_SIZE, FB_WIDTH_PIXELS, FB_WIDTH_WORDS};
+
+pub const BACKEND_ENV: &str = "XOUS_GFX_BACKEND";
+pub const CAPTURE_DIR_ENV: &str = "XOUS_GFX_CAPTURE_DIR";
+pub const CAPTURE_ENV: &str = "XOUS_GFX_CAPTURE";
+pub const KEY_SCRIPT_ENV: &str = "XOUS_GFX_KEY_SCRIPT";
+
+const DEFAULT_CAPTURE_DIR: &str = "gfx-capture";
+
+/// Pause between injected keys, so the keyboard server's queue never overflows
+const KEY_DELAY_MS: u64 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptureMode {
+    /// Write out every frame that differs from the one before it
+    EveryFlush,
+    /// Only write the frames that the key script asks for
+    OnDemand,
+}
+
+#[derive(Clone, Debug)]
+pub struct HeadlessConfig {
+    pub capture_dir: PathBuf,
+    pub mode: CaptureMode,
+    pub key_script: Option<PathBuf>,
+}
+impl HeadlessConfig {
+    /// Returns the configuration if the headless backend was selected, or `None` if the
+    /// display should open a window as usual.
+    pub fn from_env() -> Option<HeadlessConfig> {
+        match std::env::var(BACKEND_ENV).ok().as_deref() {
+            Some("headless") => (),
+            None | Some("window") => return None,
+            Some(other) => panic!("Unknown graphics backend '{}': expected window or headless", other),
+        }
+        let mode = match std::env::var(CAPTURE_ENV).ok().as_deref() {
+            None | Some("flush") => CaptureMode::EveryFlush,
+            Some("demand") => CaptureMode::OnDemand,
+            Some(other) => panic!("Unknown capture mode '{}': expected flush or demand", other),
+        };
+        Some(HeadlessConfig {
+            capture_dir: PathBuf::from(
+                std::env::var(CAPTURE_DIR_ENV).unwrap_or_else(|_| DEFAULT_CAPTURE_DIR.to_string()),
+            ),
+            mode,
+            key_script: std::env::var(KEY_SCRIPT_ENV).ok().map(PathBuf::from),
+        })
+    }
+}
+
+/// The last frame drawn, shared with the key script so it can take captures.
+struct Screen {
+    fb: Vec<u32>,
+    dir: PathBuf,
+    /// The last frame written by `CaptureMode::EveryFlush`, so identical frames are skipped
+    last_flushed: Vec<u32>,
+    flushes: usize,
+}
+impl Screen {
+    fn capture(&self, name: &str) -> io::Result<PathBuf> {
+        let path = self.dir.join(format!("{}.png", name));
+        write_png(&path, &self.fb)?;
+        Ok(path)
+    }
+}
+
+pub struct Headless {
+    screen: Arc<Mutex<Screen>>,
+    mode: CaptureMode,
+}
+impl Headless {
+    pub fn new(config: HeadlessConfig) -> Headless {
+        std::fs::create_dir_all(&config.capture_dir).expect("GFX|headless can't create capture directory");
+        log::info!("GFX|headless: capturing {:?} to {}", config.mode, config.capture_dir.display());
+        let screen = Arc::new(Mutex::new(Screen {
+            fb: vec![0; FB_SIZE],
+            dir: config.capture_dir,
+            last_flushed: Vec::new(),
+            flushes: 0,
+        }));
+        if let Some(path) = config.key_script {
+            let screen = Arc::clone(&screen);
+            std::thread::Builder::new()
+                .name("key_script".into())
+                .spawn(move || run_key_script(&path, &screen))
+                .unwrap();
+        }
+        Headless { screen, mode: config.mode }
+    }
+
+    /// Takes note of the frame that was just drawn, and writes it out if every frame is captured.
+    pub fn update(&self, fb: &[u32]) {
+        let mut screen = self.screen.lock().unwrap();
+        screen.fb.copy_from_slice(fb);
+        if self.mode == CaptureMode::EveryFlush && screen.fb != screen.last_flushed {
+            screen.flushes += 1;
+            let name = format!("flush-{:05}", screen.flushes);
+            if let Err(e) = screen.capture(&name) {
+                log::error!("GFX|headless: couldn't capture {}: {:?}", name, e);
+            }
+            screen.last_flushed = screen.fb.clone();
+        }
+    }
+}
+
+/// Writes the framebuffer out as a 1-bit greyscale PNG, where light pixels are white.
+fn write_png(path: &Path, fb: &[u32]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), WIDTH as u32, LINES as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+    let mut writer = encoder.write_header().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    writer.write_image_data(&pack_rows(fb)).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+/// Repacks the framebuffer, where each line is a run of 32-bit words with the leftmost pixel in
+/// bit 0, into PNG rows that start with the leftmost pixel in the top bit of the first byte.
+fn pack_rows(fb: &[u32]) -> Vec<u8> {
+    const ROW_BYTES: usize = (FB_WIDTH_PIXELS + 7) / 8;
+    let mut rows = vec![0u8; ROW_BYTES * FB_LINES];
+    for (line, row) in fb.chunks(FB_WIDTH_WORDS).zip(rows.chunks_mut(ROW_BYTES)) {
+        for x in 0..FB_WIDTH_PIXELS {
+            if line[x / 32] & (1 << (x % 32)) != 0 {
+                row[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+    rows
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Keys(Vec<char>),
+    Wait(u64),
+    Capture(String),
+}
+
+/// The character the keyboard sends for a named key, following the minifb key map.
+fn named_key(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+    Some(match name {
+        "enter" => '\r',
+        "backspace" => '\u{0008}',
+        "left" => '←',
+        "right" => '→',
+        "up" => '↑',
+        "down" => '↓',
+        "home" => '∴',
+        "space" => ' ',
+        "f1" => '\u{0011}',
+        "f2" => '\u{0012}',
+        "f3" => '\u{0013}',
+        "f4" => '\u{0014}',
+        _ => return None,
+    })
+}
+
+fn parse_line(line: &str) -> Result<Option<Command>, String> {
+    let line = line.trim_end_matches(|c| c == '\r' || c == '\n');
+    if line.trim().is_empty() || line.trim_start().starts_with('#') {
+        return Ok(None);
+    }
+    let line = line.trim_start();
+    let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+    match verb {
+        "type" => Ok(Some(Command::Keys(rest.chars().collect()))),
+        "key" => rest
+            .split_whitespace()
+            .map(|name| named_key(name).ok_or_else(|| format!("unknown key '{}'", name)))
+            .collect::<Result<Vec<char>, String>>()
+            .map(|keys| Some(Command::Keys(keys))),
+        "wait" => rest
+            .trim()
+            .parse::<u64>()
+            .map(|ms| Some(Command::Wait(ms)))
+            .map_err(|_| format!("'{}' isn't a number of milliseconds", rest.trim())),
+        "capture" => {
+            let name = rest.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(format!("'{}' isn't a valid capture name", name));
+            }
+            Ok(Some(Command::Capture(name.to_string())))
+        }
+        _ => Err(format!("unknown command '{}'", verb)),
+    }
+}
+
+fn run_key_script(path: &Path, screen: &Mutex<Screen>) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let kbd = keyboard::Keyboard::new(&xns).expect("GFX|headless can't connect to KBD for the key script");
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            log::error!("GFX|headless: can't open key script {}: {:?}", path.display(), e);
+            return;
+        }
+    };
+    log::info!("GFX|headless: playing key script {}", path.display());
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                log::error!("GFX|headless: can't read key script: {:?}", e);
+                return;
+            }
+        };
+        match parse_line(&line) {
+            Ok(None) => (),
+            Ok(Some(Command::Keys(keys))) => {
+                for key in keys {
+                    kbd.hostmode_inject_key(key);
+                    std::thread::sleep(std::time::Duration::from_millis(KEY_DELAY_MS));
+                }
+            }
+            Ok(Some(Command::Wait(ms))) => std::thread::sleep(std::time::Duration::from_millis(ms)),
+            Ok(Some(Command::Capture(name))) => match screen.lock().unwrap().capture(&name) {
+                Ok(path) => log::info!("GFX|headless: captured {}", path.display()),
+                Err(e) => log::error!("GFX|headless: couldn't capture {}: {:?}", name, e),
+            },
+            Err(e) => log::error!("GFX|headless: {}:{}: {}", path.display(), number + 1, e),
+        }
+    }
+    log::info!("GFX|headless: key script finished");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_lines_parse() {
+        assert_eq!(parse_line("# a comment"), Ok(None));
+        assert_eq!(parse_line("   "), Ok(None));
+        assert_eq!(parse_line("type hi there\r"), Ok(Some(Command::Keys("hi there".chars().collect()))));
+        assert_eq!(parse_line("key down enter f1 q"), Ok(Some(Command::Keys(vec!['↓', '\r', '\u{0011}', 'q']))));
+        assert_eq!(parse_line("wait 250"), Ok(Some(Command::Wait(250))));
+        assert_eq!(parse_line("capture main-menu"), Ok(Some(Command::Capture("main-menu".to_string()))));
+        assert!(parse_line("key sideways").is_err());
+        assert!(parse_line("wait soon").is_err());
+        assert!(parse_line("capture ../escape").is_err());
+        assert!(parse_line("dance").is_err());
+    }
+
+    #[test]
+    fn rows_pack_leftmost_pixel_first() {
+        let mut fb = vec![0u32; FB_SIZE];
+        // top left pixel, and the last pixel of the second line
+        fb[0] = 1;
+        fb[FB_WIDTH_WORDS + (FB_WIDTH_PIXELS - 1) / 32] = 1 << ((FB_WIDTH_PIXELS - 1) % 32);
+        // bits past the end of the line aren't pixels
+        fb[FB_WIDTH_WORDS - 1] = 0xFFFF_0000;
+
+        let rows = pack_rows(&fb);
+        let row_bytes = FB_WIDTH_PIXELS / 8;
+        assert_eq!(rows.len(), row_bytes * FB_LINES);
+        assert_eq!(rows[0], 0x80);
+        assert!(rows[1..row_bytes].iter().all(|&b| b == 0));
+        assert_eq!(rows[2 * row_bytes - 1], 0x01);
+    }
+}
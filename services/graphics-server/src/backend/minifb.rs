@@ -1,6 +1,7 @@
 #![cfg_attr(not(target_os = "none"), allow(dead_code))]
 
 use crate::api::Point;
+use super::headless::{Headless, HeadlessConfig};
 use minifb::{Key, Window, WindowOptions};
 use crate::api::{LINES, WIDTH};
 use std::sync::{Arc, Mutex, mpsc};
@@ -65,6 +66,8 @@ pub struct XousDisplay {
     emulated_buffer: [u32; FB_SIZE],
     srfb: [u32; FB_SIZE],
     devboot: bool,
+    /// Set when running without a window, see `headless`
+    headless: Option<Headless>,
 }
 
 /// Encapsulates the data passed to the thread handling minifb screen updates
@@ -81,6 +84,19 @@ struct XousKeyboardHandler {
 
 impl XousDisplay {
     pub fn new(main_thread_token: MainThreadToken) -> XousDisplay {
+        if let Some(config) = HeadlessConfig::from_env() {
+            // Nothing is sent on the token, which leaves the main thread
+            // waiting on this one instead of running a GUI event loop.
+            drop(main_thread_token);
+            return XousDisplay {
+                native_buffer: Arc::new(Mutex::new(Vec::new())),
+                emulated_buffer: [0u32; FB_SIZE],
+                srfb: [0u32; FB_SIZE],
+                devboot: true,
+                headless: Some(Headless::new(config)),
+            };
+        }
+
         let native_buffer = vec![DARK_COLOUR; WIDTH as usize * HEIGHT as usize];
         let native_buffer = Arc::new(Mutex::new(native_buffer));
 
@@ -95,6 +111,7 @@ impl XousDisplay {
             emulated_buffer: [0u32; FB_SIZE],
             srfb: [0u32; FB_SIZE],
             devboot: true,
+            headless: None,
         }
     }
     pub fn set_devboot(&mut self, ena: bool) {
@@ -138,6 +155,10 @@ impl XousDisplay {
     }
 
     fn emulated_to_native(&mut self) {
+        if let Some(headless) = &self.headless {
+            headless.update(&self.emulated_buffer);
+            return;
+        }
         const DEVBOOT_LINE: usize = 7;
         let mut native_buffer = self.native_buffer.lock().unwrap();
         let mut row = 0;
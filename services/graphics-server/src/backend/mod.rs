@@ -1,6 +1,8 @@
 #[cfg(all(not(target_os="xous")))]
 mod minifb;
 #[cfg(all(not(target_os="xous")))]
+mod headless;
+#[cfg(all(not(target_os="xous")))]
 pub use crate::backend::minifb::*;
 
 #[cfg(any(feature="precursor", feature="renode"))]
@@ -322,3 +322,47 @@ impl Drop for Gfx {
         }
     }
 }
+
+/// Client for the hosted-mode test hooks, which report what the graphics server has drawn.
+/// The server has to be started with `XOUS_GFX_TEST_HOOKS` set, or `new()` blocks forever.
+#[cfg(not(target_os = "xous"))]
+#[derive(Debug)]
+pub struct TestHooks {
+    conn: xous::CID,
+}
+#[cfg(not(target_os = "xous"))]
+impl TestHooks {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::testhooks::SERVER_NAME_GFX_TEST_HOOKS)?;
+        Ok(TestHooks { conn })
+    }
+    /// Returns the oldest text on record that was drawn after sequence number `after`, if any.
+    pub fn next_text(&self, after: u32) -> Result<Option<api::testhooks::DrawnText>, xous::Error> {
+        use api::testhooks::{DrawnText, TestHookOpcode};
+        let mut buf = Buffer::into_buf(DrawnText::after(after)).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, TestHookOpcode::NextText.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let drawn = buf.to_original::<DrawnText, _>().unwrap();
+        Ok(if drawn.seq.is_some() { Some(drawn) } else { None })
+    }
+    /// Returns the last frame that was flushed to the screen.
+    pub fn frame(&self) -> Result<api::testhooks::Frame, xous::Error> {
+        use api::testhooks::{Frame, TestHookOpcode};
+        let mut buf = Buffer::into_buf(Frame::blank()).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, TestHookOpcode::Frame.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        Ok(buf.to_original::<Frame, _>().unwrap())
+    }
+    /// Returns the sequence numbers of the newest text drawn and the newest frame flushed.
+    pub fn latest(&self) -> Result<(u32, u32), xous::Error> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(api::testhooks::TestHookOpcode::Latest.to_usize().unwrap(), 0, 0, 0, 0),
+        )?;
+        if let xous::Result::Scalar2(text, frame) = response {
+            Ok((text as u32, frame as u32))
+        } else {
+            Err(xous::Error::InternalError)
+        }
+    }
+}
@@ -39,6 +39,9 @@ use core::ops::Add;
 #[cfg(feature = "gfx-testing")]
 mod testing;
 
+#[cfg(not(target_os = "xous"))]
+mod testhooks;
+
 fn draw_boot_logo(display: &mut XousDisplay) {
     display.blit_screen(&poweron::LOGO_MAP);
 }
@@ -146,6 +149,9 @@ fn wrapped_main(main_thread_token: backend::MainThreadToken) -> ! {
         .register_name(api::SERVER_NAME_GFX, Some(1))
         .expect("can't register server");
 
+    #[cfg(not(target_os = "xous"))]
+    let test_hooks = testhooks::TestHooks::from_env();
+
     let screen_clip = Rectangle::new(Point::new(0, 0), display.screen_size());
 
     display.redraw();
@@ -393,12 +399,22 @@ fn wrapped_main(main_thread_token: backend::MainThreadToken) -> ! {
                         clear_rect
                     );
                     log::trace!("cursor ret {:?}, bounds ret {:?}", tv.cursor, tv.bounds_computed);
+                    #[cfg(not(target_os = "xous"))]
+                    if let Some(hooks) = &test_hooks {
+                        if !tv.dry_run() {
+                            hooks.text_drawn(&tv, clear_rect);
+                        }
+                    }
                     // pack our data back into the buffer to return
                     buffer.replace(tv).unwrap();
                 }
                 Some(Opcode::Flush) => {
                     log::trace!("***gfx flush*** redraw##");
                     display.redraw();
+                    #[cfg(not(target_os = "xous"))]
+                    if let Some(hooks) = &test_hooks {
+                        hooks.flushed(display.as_slice());
+                    }
                 }
                 Some(Opcode::Clear) => {
                     let mut r = Rectangle::full_screen();
@@ -0,0 +1,105 @@
+//! Records what is drawn in hosted mode, and serves it up to a test harness. See
+//! `api::testhooks` for the client side.
+
+use crate::api::testhooks::*;
+use crate::api::{Rectangle, TextView};
+use num_traits::FromPrimitive;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use xous_ipc::Buffer;
+
+/// Texts kept on record. A harness that falls further behind than this misses the oldest ones.
+const MAX_TEXTS: usize = 512;
+
+struct Recorded {
+    texts: VecDeque<DrawnText>,
+    /// Sequence number of the newest text, starting from 1
+    text_seq: u32,
+    frame: Frame,
+}
+
+pub struct TestHooks {
+    recorded: Arc<Mutex<Recorded>>,
+}
+impl TestHooks {
+    /// Starts serving the hooks if `XOUS_GFX_TEST_HOOKS` is set.
+    pub fn from_env() -> Option<TestHooks> {
+        std::env::var(TEST_HOOKS_ENV).ok()?;
+        let recorded = Arc::new(Mutex::new(Recorded {
+            texts: VecDeque::with_capacity(MAX_TEXTS),
+            text_seq: 0,
+            frame: Frame::blank(),
+        }));
+        std::thread::Builder::new()
+            .name("test_hooks".into())
+            .spawn({
+                let recorded = Arc::clone(&recorded);
+                move || serve(&recorded)
+            })
+            .unwrap();
+        log::info!("GFX|test hooks enabled");
+        Some(TestHooks { recorded })
+    }
+
+    pub fn text_drawn(&self, tv: &TextView, bounds: Rectangle) {
+        let mut recorded = self.recorded.lock().unwrap();
+        recorded.text_seq += 1;
+        if recorded.texts.len() == MAX_TEXTS {
+            recorded.texts.pop_front();
+        }
+        let drawn = DrawnText {
+            after: 0,
+            seq: Some(recorded.text_seq),
+            text: tv.text,
+            bounds: Some(bounds),
+            invert: tv.invert,
+        };
+        recorded.texts.push_back(drawn);
+    }
+
+    pub fn flushed(&self, fb: &[u32]) {
+        let mut recorded = self.recorded.lock().unwrap();
+        recorded.frame.seq += 1;
+        recorded.frame.fb.copy_from_slice(fb);
+    }
+}
+
+fn serve(recorded: &Mutex<Recorded>) {
+    let xns = xous_names::XousNames::new().unwrap();
+    // only the harness connects
+    let sid = xns
+        .register_name(SERVER_NAME_GFX_TEST_HOOKS, Some(1))
+        .expect("can't register test hooks");
+    loop {
+        let mut msg = xous::receive_message(sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(TestHookOpcode::NextText) => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let query = buffer.to_original::<DrawnText, _>().unwrap();
+                let recorded = recorded.lock().unwrap();
+                let found = recorded
+                    .texts
+                    .iter()
+                    .find(|drawn| drawn.seq.unwrap_or(0) > query.after)
+                    .copied()
+                    .unwrap_or(query);
+                buffer.replace(DrawnText { after: query.after, ..found }).unwrap();
+            }
+            Some(TestHookOpcode::Frame) => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let frame = recorded.lock().unwrap().frame;
+                buffer.replace(frame).unwrap();
+            }
+            Some(TestHookOpcode::Latest) => {
+                let recorded = recorded.lock().unwrap();
+                xous::return_scalar2(
+                    msg.sender,
+                    recorded.text_seq as usize,
+                    recorded.frame.seq as usize,
+                )
+                .unwrap();
+            }
+            None => log::error!("GFX|test hooks: unknown opcode {}", msg.body.id()),
+        }
+    }
+}
@@ -727,12 +727,13 @@ fn main() -> ! {
     //  - status sub system (for setting the layout, autobacklight feature)
     //  - USB (for getting layout)
     //  - Preference manager
+    //  - UI test harness (hosted mode only)
     #[cfg(all(any(feature="precursor", feature="renode"), not(feature="dvt")))]
     let kbd_sid = xns.register_name(api::SERVER_NAME_KBD, Some(5)).expect("can't register server");
     #[cfg(all(any(feature="precursor", feature="renode"), feature="dvt"))] // dvt build has less in it
     let kbd_sid = xns.register_name(api::SERVER_NAME_KBD, Some(4)).expect("can't register server");
     #[cfg(not(target_os = "xous"))]
-    let kbd_sid = xns.register_name(api::SERVER_NAME_KBD, Some(5)).expect("can't register server");
+    let kbd_sid = xns.register_name(api::SERVER_NAME_KBD, Some(6)).expect("can't register server");
     log::trace!("registered with NS -- {:?}", kbd_sid);
 
     // Create a new kbd object
@@ -190,6 +190,13 @@ impl Llio {
             Message::new_scalar(Opcode::EventComEnable.to_usize().unwrap(), arg, 0, 0, 0)
         ).map(|_| ())
     }
+    /// Raises the COM event in place of the EC, for the EC that `com` emulates in hosted mode
+    #[cfg(not(target_os = "xous"))]
+    pub fn com_event_raise(&self) -> Result<(), xous::Error> {
+        send_message(self.conn,
+            Message::new_scalar(Opcode::EventComHappened.to_usize().unwrap(), 0, 0, 0, 0)
+        ).map(|_| ())
+    }
     /// GPIO IRQ hook. When using this, ensure that the WFI power saving mode is turned off.
     /// Otherwise interrupts that hit during power save mode can be missed.
     pub fn hook_gpio_event_callback(&mut self, id: u32, cid: CID) -> Result<(), xous::Error> {
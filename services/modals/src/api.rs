@@ -76,6 +76,21 @@ pub struct ManagedProgress {
     pub step: u32,
 }
 
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ManagedQrFrame {
+    pub token: [u32; 4],
+    /// One frame of a `qrstream`, as text. A full frame is a bit over 600 characters.
+    pub frame: xous_ipc::String<1024>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ManagedQrSequence {
+    pub token: [u32; 4],
+    pub message: xous_ipc::String<1024>,
+    /// how long each frame stays on the screen
+    pub interval_ms: u32,
+}
+
 /// This isn't a terribly useful notification -- it's basically read-only, no interactivity,
 /// but you can animate the text. Mainly used for testing routines. Might be modifiable
 /// into something more useful with a bit of thought, but for now, MVP.
@@ -105,6 +120,12 @@ pub(crate) enum Opcode {
     Bip39Return = 33, // ----- note op number
     SliderReturn = 34,
     Slider = 35,
+    /// add a frame to the QR sequence. Like modal items, frames are cleared once the sequence is dismissed.
+    AddQrFrame = 36,
+    /// cycle through the QR frames added so far, until a key is hit
+    QrSequence = 37,
+    /// internal: shows the next frame of a QR sequence. Sent by the ticktimer.
+    QrSequenceTick = 38,
     /// display an image
     #[cfg(feature = "ditherpunk")]
     Image = 3,
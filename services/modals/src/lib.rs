@@ -2,6 +2,7 @@
 
 pub mod api;
 use api::*;
+pub mod qrstream;
 #[cfg(feature = "ditherpunk")]
 pub mod tests;
 
@@ -223,6 +224,42 @@ impl Modals {
         Ok(())
     }
 
+    /// this blocks until the sequence has been dismissed. `data` is cut into frames as described in
+    /// `qrstream`, which are shown one after another as QR codes, each for `interval_ms`, until a key
+    /// is hit. This is for handing over more data than fits in the QR code of `show_notification()`;
+    /// `tools/src/bin/qr-reassemble.rs` puts the scanned frames back together. Returns `InvalidLimit`
+    /// without showing anything if `interval_ms` is 0, or if `data` takes more than `qrstream::MAX_FRAMES`.
+    pub fn show_qr_sequence(
+        &self,
+        message: &str,
+        data: &[u8],
+        interval_ms: u32,
+    ) -> Result<(), xous::Error> {
+        if interval_ms == 0 {
+            return Err(xous::Error::InvalidLimit);
+        }
+        let frames = qrstream::encode_frames(data).ok_or(xous::Error::InvalidLimit)?;
+        let _lock = self.lock_scoped();
+        for frame in frames {
+            let spec = ManagedQrFrame {
+                token: self.token,
+                frame: xous_ipc::String::from_str(&frame),
+            };
+            let buf = Buffer::into_buf(spec).or(Err(xous::Error::InternalError))?;
+            buf.lend(self.conn, Opcode::AddQrFrame.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+        }
+        let spec = ManagedQrSequence {
+            token: self.token,
+            message: xous_ipc::String::from_str(message),
+            interval_ms,
+        };
+        let buf = Buffer::into_buf(spec).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::QrSequence.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        Ok(())
+    }
+
     /// this blocks until the notification has been acknowledged. It will attempt to render up to 256 bits
     /// of `data` in bip39 format. Data must conform to the codeable lengths by BIP39, or else the routine
     /// will return immediately with an `InvalidString` error without showing any dialog box.
@@ -601,6 +638,12 @@ impl Modals {
     fn unlock(&self) {
         self.have_lock.set(false);
     }
+    /// Like `lock()`, but `unlock()` is called when the returned guard is dropped, so an early
+    /// return can't leave `have_lock` set.
+    fn lock_scoped(&self) -> ScopedLock<'_> {
+        self.lock();
+        ScopedLock(self)
+    }
     pub fn conn(&self) -> CID {
         self.conn
     }
@@ -611,6 +654,13 @@ impl Modals {
     }
 }
 
+struct ScopedLock<'a>(&'a Modals);
+impl Drop for ScopedLock<'_> {
+    fn drop(&mut self) {
+        self.0.unlock();
+    }
+}
+
 use core::sync::atomic::{AtomicU32, Ordering};
 static REFCOUNT: AtomicU32 = AtomicU32::new(0);
 impl Drop for Modals {
@@ -57,6 +57,7 @@ enum RendererState {
     RunBip39(ManagedBip39),
     RunBip39Input(ManagedBip39),
     RunDynamicNotification(DynamicNotification),
+    RunQrSequence(ManagedQrSequence),
     #[cfg(feature="ditherpunk")]
     RunImage(ManagedImage),
 }
@@ -88,7 +89,7 @@ fn wrapped_main() -> ! {
         .expect("can't register server");
     log::trace!("registered with NS -- {:?}", modals_sid);
 
-    #[cfg(feature = "tts")]
+    // no longer tts-only: it also paces the frames of a QR sequence
     let tt = ticktimer_server::Ticktimer::new().unwrap();
 
     // we are our own renderer now that we implement deferred responses
@@ -158,6 +159,11 @@ fn wrapped_main() -> ! {
     let mut dynamic_notification_listener: Option<xous::MessageSender> = None;
     let mut dynamic_notification_active: bool = false;
 
+    // frames of the QR sequence, the one on screen, and the timer that flips through them
+    let mut qr_frames = Vec::<String>::new();
+    let mut qr_index = 0;
+    let mut qr_timer: Option<usize> = None;
+
     loop {
         let mut msg = xous::receive_message(modals_sid).unwrap();
         let opcode: Option<Opcode> = FromPrimitive::from_usize(msg.body.id());
@@ -278,6 +284,24 @@ fn wrapped_main() -> ! {
                 )
                 .expect("couldn't initiate UX op");
             }
+            Some(Opcode::QrSequence) => {
+                let spec = {
+                    let buffer =
+                        unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                    buffer.to_original::<ManagedQrSequence, _>().unwrap()
+                };
+                if spec.token != token_lock.unwrap_or(default_nonce) {
+                    log::warn!("Attempt to access modals without a mutex lock. Ignoring.");
+                    continue;
+                }
+                op = RendererState::RunQrSequence(spec);
+                dr = Some(msg);
+                send_message(
+                    renderer_cid,
+                    Message::new_scalar(Opcode::InitiateOp.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .expect("couldn't initiate UX op");
+            }
             Some(Opcode::Bip39Input) => {
                 let spec = {
                     let buffer =
@@ -372,6 +396,16 @@ fn wrapped_main() -> ! {
                 }
                 fixed_items.push(manageditem.item);
             }
+            Some(Opcode::AddQrFrame) => {
+                let buffer =
+                    unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let managedframe = buffer.to_original::<ManagedQrFrame, _>().unwrap();
+                if managedframe.token != token_lock.unwrap_or(default_nonce) {
+                    log::warn!("Attempt to access modals without a mutex lock. Ignoring.");
+                    continue;
+                }
+                qr_frames.push(managedframe.frame.to_string());
+            }
             Some(Opcode::GetModalIndex) => {
                 xous::return_scalar(msg.sender, list_selected as usize)
                     .expect("couldn't return list selected");
@@ -561,6 +595,45 @@ fn wrapped_main() -> ! {
                         );
                         renderer_modal.activate();
                     }
+                    RendererState::RunQrSequence(config) => {
+                        if qr_frames.is_empty() {
+                            log::warn!("QR sequence started with no frames. Ignoring request.");
+                            op = RendererState::None;
+                            dr.take();
+                            token_lock = next_lock(&mut work_queue);
+                            continue;
+                        }
+                        qr_index = 0;
+                        let mut notification = gam::modal::Notification::new(
+                            renderer_cid,
+                            Opcode::NotificationReturn.to_u32().unwrap(),
+                        );
+                        notification.set_qrcode(Some(&qr_frames[qr_index]));
+                        let text = qr_sequence_text(&config, qr_index, qr_frames.len());
+                        #[cfg(feature = "tts")]
+                        tts.tts_simple(config.message.as_str().unwrap()).unwrap();
+                        renderer_modal.modify(
+                            Some(ActionType::Notification(notification)),
+                            Some(&text),
+                            false,
+                            None,
+                            true,
+                            Some(DEFAULT_STYLE),
+                        );
+                        renderer_modal.activate();
+                        if qr_frames.len() > 1 {
+                            qr_timer = tt
+                                .notify_every(
+                                    modals_sid,
+                                    Opcode::QrSequenceTick.to_u32().unwrap(),
+                                    config.interval_ms as u64,
+                                )
+                                .map_err(|e| {
+                                    log::error!("couldn't start the QR sequence timer: {:?}", e)
+                                })
+                                .ok();
+                        }
+                    }
                     RendererState::RunBip39Input(config) => {
                         let b39input = gam::modal::Bip39Entry::new(
                             false,
@@ -742,6 +815,30 @@ fn wrapped_main() -> ! {
                     token_lock = None;
                 }*/
             }),
+            Some(Opcode::QrSequenceTick) => msg_scalar_unpack!(msg, id, _, _, _, {
+                // a tick can still be queued after the sequence it was for is dismissed
+                if qr_timer != Some(id) {
+                    continue;
+                }
+                if let RendererState::RunQrSequence(config) = op {
+                    qr_index = (qr_index + 1) % qr_frames.len();
+                    let mut notification = gam::modal::Notification::new(
+                        renderer_cid,
+                        Opcode::NotificationReturn.to_u32().unwrap(),
+                    );
+                    notification.set_qrcode(Some(&qr_frames[qr_index]));
+                    renderer_modal.modify(
+                        Some(ActionType::Notification(notification)),
+                        Some(&qr_sequence_text(&config, qr_index, qr_frames.len())),
+                        false,
+                        None,
+                        true,
+                        None,
+                    );
+                    renderer_modal.redraw();
+                    xous::yield_slice();
+                }
+            }),
             Some(Opcode::DoUpdateDynamicNotification) => match op {
                 RendererState::RunDynamicNotification(config) => {
                     //log::set_max_level(log::LevelFilter::Trace);
@@ -861,6 +958,15 @@ fn wrapped_main() -> ! {
                         dr.take(); // unblocks the caller, but without any response data
                         token_lock = next_lock(&mut work_queue);
                     }
+                    RendererState::RunQrSequence(_) => {
+                        if let Some(id) = qr_timer.take() {
+                            tt.cancel_timer(id).ok();
+                        }
+                        qr_frames.clear();
+                        op = RendererState::None;
+                        dr.take();
+                        token_lock = next_lock(&mut work_queue);
+                    }
                     RendererState::None => {
                         log::warn!("Notification detected a fat finger event, ignoring.")
                     }
@@ -1055,6 +1161,16 @@ fn compute_checked_percentage(current: u32, start: u32, end: u32) -> u32 {
     }
 }
 
+/// The caption of a QR sequence, with a count of the frame on screen so the user can tell it's moving
+fn qr_sequence_text(config: &ManagedQrSequence, index: usize, count: usize) -> String {
+    format!(
+        "{}\n\n{}/{}",
+        config.message.as_str().unwrap(),
+        index + 1,
+        count
+    )
+}
+
 fn next_lock(work_queue: &mut Vec<(xous::MessageSender, [u32; 4])>) -> Option<[u32; 4]> {
     if work_queue.len() > 0 {
         /*
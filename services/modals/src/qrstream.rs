@@ -0,0 +1,203 @@
+//! Framing for payloads that are too big for a single QR code.
+//!
+//! The payload is cut into chunks of up to `CHUNK_LEN` bytes, and each chunk is shown as one
+//! QR code in a cycle of frames. Every frame carries enough of a header that a receiver can
+//! start scanning at any point in the cycle, put the frames back in order, and check the result:
+//!
+//! ```text
+//! XQ1:<index>:<count>:<length>:<crc>:<data>
+//! ```
+//!
+//! * `index` is the number of the frame, counting from 0, and `count` is the number of frames
+//! * `length` is the length of the whole payload in bytes
+//! * `crc` is the CRC-32 (IEEE) of the whole payload, as 8 upper case hex digits
+//! * `data` is the frame's chunk, in RFC 4648 base32 without padding
+//!
+//! Numbers are in decimal. Everything in a frame is in the QR alphanumeric set, so a frame
+//! packs about 5.5 bits per character instead of the 8 it would take in byte mode.
+//!
+//! This file is also built into the decoder in `tools/src/qrstream.rs`, so it may only use `std`.
+
+use std::fmt;
+
+pub const MAGIC: &str = "XQ1";
+/// Bytes of payload in each frame. A multiple of 5 keeps base32 free of padding, and a full frame
+/// fits a version 15 code with medium error correction, whose modules are still 3 pixels across
+/// on the Precursor screen.
+pub const CHUNK_LEN: usize = 375;
+/// Most frames in a sequence. A receiver allocates a slot for every frame up front, so a header
+/// can't be allowed to ask for an arbitrary number of them. This is about 375 KiB of payload, and
+/// takes eight and a half minutes to go around at two frames a second.
+pub const MAX_FRAMES: u32 = 1024;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrFrame {
+    pub index: u32,
+    pub count: u32,
+    /// Length of the whole payload
+    pub length: u32,
+    /// CRC-32 of the whole payload
+    pub crc: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// The text doesn't start with `MAGIC`, so it came from some other QR code
+    NotAFrame,
+    /// The header is missing a field or has a number that doesn't parse
+    BadHeader,
+    /// The data isn't valid base32
+    BadData,
+    /// The header claims more than `MAX_FRAMES` frames
+    TooManyFrames,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::NotAFrame => write!(f, "not a QR stream frame"),
+            FrameError::BadHeader => write!(f, "malformed frame header"),
+            FrameError::BadData => write!(f, "frame data is not valid base32"),
+            FrameError::TooManyFrames => write!(f, "sequence is longer than {} frames", MAX_FRAMES),
+        }
+    }
+}
+
+impl QrFrame {
+    pub fn encode(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{:08X}:{}",
+            MAGIC,
+            self.index,
+            self.count,
+            self.length,
+            self.crc,
+            base32_encode(&self.data)
+        )
+    }
+
+    pub fn parse(text: &str) -> Result<QrFrame, FrameError> {
+        let mut fields = text.trim().splitn(6, ':');
+        if fields.next() != Some(MAGIC) {
+            return Err(FrameError::NotAFrame);
+        }
+        let mut number = |radix| {
+            fields
+                .next()
+                .and_then(|f| u32::from_str_radix(f, radix).ok())
+                .ok_or(FrameError::BadHeader)
+        };
+        let index = number(10)?;
+        let count = number(10)?;
+        let length = number(10)?;
+        let crc = number(16)?;
+        if count > MAX_FRAMES {
+            return Err(FrameError::TooManyFrames);
+        }
+        // the count follows from the length, so a header can't claim more frames than it needs
+        if index >= count || count as usize != frame_count(length as usize) {
+            return Err(FrameError::BadHeader);
+        }
+        let data = fields
+            .next()
+            .ok_or(FrameError::BadHeader)
+            .and_then(|d| base32_decode(d).ok_or(FrameError::BadData))?;
+        Ok(QrFrame {
+            index,
+            count,
+            length,
+            crc,
+            data,
+        })
+    }
+}
+
+/// Number of frames a payload of `length` bytes is cut into. An empty payload still gets a frame,
+/// so that the receiver has something to scan.
+pub fn frame_count(length: usize) -> usize {
+    std::cmp::max(1, (length + CHUNK_LEN - 1) / CHUNK_LEN)
+}
+
+/// Cuts `payload` into the text of each frame, in order. Returns `None` if it would take more
+/// than `MAX_FRAMES` frames.
+pub fn encode_frames(payload: &[u8]) -> Option<Vec<String>> {
+    let count = frame_count(payload.len());
+    if count > MAX_FRAMES as usize {
+        return None;
+    }
+    let crc = crc32(payload);
+    let frames = (0..count)
+        .map(|index| {
+            let start = index * CHUNK_LEN;
+            let end = std::cmp::min(start + CHUNK_LEN, payload.len());
+            QrFrame {
+                index: index as u32,
+                count: count as u32,
+                length: payload.len() as u32,
+                crc,
+                data: payload[start..end].to_vec(),
+            }
+            .encode()
+        })
+        .collect();
+    Some(frames)
+}
+
+/// CRC-32 as used by zip and Ethernet (reflected, polynomial 0x04C11DB7)
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut bits = 0u32;
+    let mut nbits = 0;
+    for &b in data {
+        bits = (bits << 8) | b as u32;
+        nbits += 8;
+        while nbits >= 5 {
+            nbits -= 5;
+            out.push(BASE32_ALPHABET[((bits >> nbits) & 0x1F) as usize] as char);
+        }
+    }
+    if nbits > 0 {
+        out.push(BASE32_ALPHABET[((bits << (5 - nbits)) & 0x1F) as usize] as char);
+    }
+    out
+}
+
+/// Decodes unpadded base32. Returns `None` if there is a character outside the alphabet, or
+/// if the length can't have come from `base32_encode()`.
+pub fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let mut bits = 0u32;
+    let mut nbits = 0;
+    for c in text.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+        bits = (bits << 5) | value;
+        nbits += 5;
+        if nbits >= 8 {
+            nbits -= 8;
+            out.push((bits >> nbits) as u8);
+        }
+    }
+    // anything left over is padding from the last character, and has to be zero
+    if nbits >= 5 || bits & ((1 << nbits) - 1) != 0 {
+        return None;
+    }
+    Some(out)
+}
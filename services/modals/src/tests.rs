@@ -167,6 +167,14 @@ pub fn spawn_test() {
                 .expect("qrcode failed");
             log::info!("qrcode test done");
 
+            // 4a. test a QR sequence, long enough to need a few frames
+            log::info!("testing qr sequence");
+            let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+            modals
+                .show_qr_sequence("Scan with qr-reassemble", &data, 500)
+                .expect("qr sequence failed");
+            log::info!("qr sequence test done");
+
             // 5. test image - because it reads a local file, only makes sense on hosted mode
             #[cfg(feature = "ditherpunk")]
             {
@@ -0,0 +1,189 @@
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use xous_names::api::NameInfo;
+
+pub use hosted_test::TestResult;
+
+/// Every name the tests register starts with this, so they are easy to pick out of the name table
+const PREFIX: &str = "_names-test ";
+
+/// Registers servers with throwaway keys, and looks them up.
+pub struct Names {
+    xns: xous_names::XousNames,
+    /// The key that the tests' servers are bound to
+    key: Keypair,
+    /// A key that no server is bound to
+    other: Keypair,
+}
+impl Names {
+    pub fn new() -> Names {
+        Names {
+            xns: xous_names::XousNames::new().unwrap(),
+            key: keypair(1),
+            other: keypair(2),
+        }
+    }
+
+    /// Registers `name` bound to the public half of `key`, and answers the challenge with a
+    /// signature made by `signer`.
+    fn register(&self, name: &str, max_conns: Option<u32>, key: &Keypair, signer: &Keypair) -> Result<xous::SID, xous::Error> {
+        self.xns.register_name_authenticated(name, max_conns, key.public.as_bytes(), |msg| {
+            signer.sign(msg).to_bytes()
+        })
+    }
+
+    fn lookup(&self, name: &str, key: &Keypair) -> Result<xous::CID, xous::Error> {
+        self.xns.request_authenticated_connection(name, key.public.as_bytes())
+    }
+
+    /// What the name table says about `name`, if it has it
+    fn info(&self, name: &str) -> Option<NameInfo> {
+        self.xns.name_table().ok()?.into_iter().find(|info| info.name.as_str() == Ok(name))
+    }
+
+    /// Gives up a server's name, and then the server itself.
+    fn release(&self, sid: xous::SID) {
+        self.xns.unregister_server(sid).ok();
+        xous::destroy_server(sid).ok();
+    }
+}
+
+/// A throwaway key. The tests only need keys that differ from each other.
+fn keypair(seed: u8) -> Keypair {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public: PublicKey = (&secret).into();
+    Keypair { secret, public }
+}
+
+fn test_name(test: &str) -> String {
+    format!("{}{}", PREFIX, test)
+}
+
+/// Checks that `result` is the `AccessDenied` that the name server gives for anything it refuses.
+fn denied<T: std::fmt::Debug>(what: &str, result: Result<T, xous::Error>) -> TestResult {
+    match result {
+        Err(xous::Error::AccessDenied) => Ok(()),
+        other => Err(format!("{} was not denied: {:?}", what, other)),
+    }
+}
+
+/// Registers with a key and answers the challenge, then looks the server up with the same key.
+pub fn register_and_lookup(names: &mut Names) -> TestResult {
+    let name = test_name("register_and_lookup");
+    let sid = names.register(&name, None, &names.key, &names.key).map_err(|e| format!("registration failed: {:?}", e))?;
+    let result = (|| {
+        match names.info(&name) {
+            Some(info) if info.authenticated => (),
+            other => return Err(format!("the name table has {:?} after the challenge was answered", other)),
+        }
+        names.lookup(&name, &names.key).map_err(|e| format!("lookup with the right key failed: {:?}", e))?;
+        match names.info(&name) {
+            Some(info) if info.auth_conns == 1 => Ok(()),
+            other => Err(format!("the name table has {:?} after one authenticated lookup", other)),
+        }
+    })();
+    names.release(sid);
+    if names.info(&name).is_some() {
+        return Err("the name is still registered after it was released".into());
+    }
+    result
+}
+
+/// A lookup with a key other than the one the server proved it holds is refused, and isn't
+/// counted as a connection.
+pub fn wrong_key(names: &mut Names) -> TestResult {
+    let name = test_name("wrong_key");
+    let sid = names.register(&name, None, &names.key, &names.key).map_err(|e| format!("registration failed: {:?}", e))?;
+    let result = (|| {
+        denied("lookup with the wrong key", names.lookup(&name, &names.other))?;
+        match names.info(&name) {
+            Some(info) if info.auth_conns == 0 => Ok(()),
+            other => Err(format!("the name table has {:?} after a refused lookup", other)),
+        }
+    })();
+    names.release(sid);
+    result
+}
+
+/// A second registration of an authenticated name, with a key of its own, doesn't take the
+/// name over.
+pub fn squatter(names: &mut Names) -> TestResult {
+    let name = test_name("squatter");
+    let sid = names.register(&name, None, &names.key, &names.key).map_err(|e| format!("registration failed: {:?}", e))?;
+    let result = (|| {
+        if let Ok(squatter) = names.register(&name, None, &names.other, &names.other) {
+            names.release(squatter);
+            return Err("a squatter registered over an authenticated name".into());
+        }
+        denied("lookup with the squatter's key", names.lookup(&name, &names.other))?;
+        names.lookup(&name, &names.key).map_err(|e| format!("the owner's lookup failed after the squatter tried: {:?}", e))?;
+        Ok(())
+    })();
+    names.release(sid);
+    result
+}
+
+/// A challenge answered with a signature from a key other than the registered one doesn't
+/// complete the registration, and leaves the name free to be claimed properly.
+pub fn forged_signature(names: &mut Names) -> TestResult {
+    let name = test_name("forged_signature");
+    denied("a registration signed with the wrong key", names.register(&name, None, &names.key, &names.other))?;
+    denied("lookup of a failed registration", names.lookup(&name, &names.key))?;
+    if let Some(info) = names.info(&name) {
+        return Err(format!("a failed registration is still in the name table: {:?}", info));
+    }
+    let sid = names.register(&name, None, &names.key, &names.key).map_err(|e| format!("the name wasn't freed by the failed registration: {:?}", e))?;
+    names.release(sid);
+    Ok(())
+}
+
+/// A signature over an earlier challenge doesn't answer a new one.
+pub fn replayed_challenge(names: &mut Names) -> TestResult {
+    let name = test_name("replayed_challenge");
+    let mut signed = Vec::new();
+    let sid = names
+        .xns
+        .register_name_authenticated(&name, None, names.key.public.as_bytes(), |msg| {
+            signed = msg.to_vec();
+            names.key.sign(msg).to_bytes()
+        })
+        .map_err(|e| format!("registration failed: {:?}", e))?;
+    names.release(sid);
+
+    let replayed = names.key.sign(&signed).to_bytes();
+    denied(
+        "a registration answered with an old signature",
+        names.xns.register_name_authenticated(&name, None, names.key.public.as_bytes(), |_| replayed),
+    )
+}
+
+/// A server that registered without a key can't be reached with an authenticated lookup.
+pub fn unauthenticated_server(names: &mut Names) -> TestResult {
+    let name = test_name("unauthenticated_server");
+    let sid = names.xns.register_name(&name, None).map_err(|e| format!("registration failed: {:?}", e))?;
+    let result = denied("authenticated lookup of an unauthenticated server", names.lookup(&name, &names.key));
+    names.release(sid);
+    result
+}
+
+/// Authenticated lookups count against the connection limit, and once it's reached, lookups of
+/// either kind are refused.
+pub fn connection_limit(names: &mut Names) -> TestResult {
+    const LIMIT: u32 = 2;
+    let name = test_name("connection_limit");
+    let sid = names.register(&name, Some(LIMIT), &names.key, &names.key).map_err(|e| format!("registration failed: {:?}", e))?;
+    let result = (|| {
+        for i in 0..LIMIT {
+            names.lookup(&name, &names.key).map_err(|e| format!("lookup {} of {} failed: {:?}", i + 1, LIMIT, e))?;
+        }
+        denied("an authenticated lookup past the limit", names.lookup(&name, &names.key))?;
+        if names.xns.request_connection(&name).is_ok() {
+            return Err("a plain lookup past the limit succeeded".into());
+        }
+        match names.info(&name) {
+            Some(info) if info.auth_conns == LIMIT && info.remaining_conns() == Some(0) && info.refused == 2 => Ok(()),
+            other => Err(format!("the name table has {:?} once the limit was reached", other)),
+        }
+    })();
+    names.release(sid);
+    result
+}
@@ -0,0 +1,37 @@
+//! End-to-end tests of authenticated registration and lookup in `xous-names`, run in hosted mode
+//! by `cargo xtask names-test`.
+//!
+//! The harness registers servers of its own with throwaway keys, going through the whole
+//! register, challenge, verify round trip, and then looks them up with the right key, the wrong
+//! key, and past their connection limit. The results are reported under the `NAMESTEST` tag by
+//! `hosted_test::run()`.
+//!
+//! `XOUS_NAMES_TEST` limits the run to a comma-separated list of tests.
+
+mod flows;
+
+use flows::Names;
+use hosted_test::Test;
+
+/// Every test, in the order they run. Each one registers and releases its own names, so any of
+/// them can be run alone.
+const TESTS: [Test<Names>; 7] = [
+    ("register_and_lookup", flows::register_and_lookup),
+    ("wrong_key", flows::wrong_key),
+    ("squatter", flows::squatter),
+    ("forged_signature", flows::forged_signature),
+    ("replayed_challenge", flows::replayed_challenge),
+    ("unauthenticated_server", flows::unauthenticated_server),
+    ("connection_limit", flows::connection_limit),
+];
+const FILTER_ENV: &str = "XOUS_NAMES_TEST";
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let mut names = Names::new();
+
+    hosted_test::run("NAMESTEST", FILTER_ENV, &TESTS, &mut names)
+}
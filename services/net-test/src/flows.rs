@@ -0,0 +1,132 @@
+use crate::tcp::TcpStream;
+use com::{WlanStatus, WlanStatusIpc};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+pub use hosted_test::TestResult;
+
+/// The addresses of the two leases in `network.txt`: the renewal moves the device from the
+/// first to the second.
+const FIRST_ADDR: [u8; 4] = [10, 0, 2, 15];
+const RENEWED_ADDR: [u8; 4] = [10, 0, 2, 16];
+/// The echo port on the gateway, and a port that nothing on the switch listens on
+const ECHO: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 2, 2), 7);
+const DISCARD: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 2, 2), 9);
+/// How long to wait for the link to get to where a test expects it. The renewal is due half a
+/// minute after the first lease.
+const LINK_TIMEOUT: Duration = Duration::from_secs(60);
+const TCP_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_MS: usize = 100;
+
+/// Follows the link through the connection manager, and opens connections over it.
+pub struct Net {
+    xns: xous_names::XousNames,
+    netmgr: net::NetManager,
+    /// Status updates that the connection manager has sent since the last one was read
+    updates: Receiver<WlanStatus>,
+    tt: ticktimer_server::Ticktimer,
+}
+impl Net {
+    pub fn new() -> Net {
+        let sid = xous::create_server().unwrap();
+        let (send, updates) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let msg = xous::receive_message(sid).unwrap();
+            let buffer = unsafe { xous_ipc::Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+            let status = WlanStatus::from_ipc(buffer.to_original::<WlanStatusIpc, _>().unwrap());
+            if send.send(status).is_err() {
+                break;
+            }
+        });
+        let mut netmgr = net::NetManager::new();
+        netmgr
+            .wifi_state_subscribe(xous::connect(sid).unwrap(), 0)
+            .expect("NETTEST can't follow the WLAN state");
+        Net {
+            xns: xous_names::XousNames::new().unwrap(),
+            netmgr,
+            updates,
+            tt: ticktimer_server::Ticktimer::new().unwrap(),
+        }
+    }
+
+    pub fn sleep_ms(&self, ms: usize) {
+        self.tt.sleep_ms(ms).unwrap();
+    }
+
+    /// The address that `net` has configured, if it has one
+    fn address(&self) -> Option<[u8; 4]> {
+        self.netmgr.get_ipv4_config().map(|config| config.addr).filter(|addr| *addr != [0; 4])
+    }
+
+    /// Waits for the connection manager to pass on a status update that `matches`.
+    fn wait_for_update(&self, what: &str, matches: impl Fn(&WlanStatus) -> bool) -> TestResult {
+        let deadline = Instant::now() + LINK_TIMEOUT;
+        loop {
+            match self.updates.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(status) if matches(&status) => return Ok(()),
+                Ok(status) => log::info!("NETTEST|waiting for {}, got {:?}", what, status),
+                Err(RecvTimeoutError::Timeout) => return Err(format!("timed out waiting for {}", what)),
+                Err(RecvTimeoutError::Disconnected) => return Err("status updates stopped".into()),
+            }
+        }
+    }
+}
+
+/// Waits for `net` to take the first lease.
+pub fn lease(net: &mut Net) -> TestResult {
+    let deadline = Instant::now() + LINK_TIMEOUT;
+    loop {
+        match net.address() {
+            Some(FIRST_ADDR) => return Ok(()),
+            Some(addr) => return Err(format!("leased {:?} instead of {:?}", addr, FIRST_ADDR)),
+            None if Instant::now() >= deadline => return Err("timed out waiting for a lease".into()),
+            None => net.sleep_ms(POLL_MS),
+        }
+    }
+}
+
+/// Waits for the lease to be renewed, and checks that the connection manager passes the new
+/// address on, and that `net` moves over to it.
+pub fn lease_renewal(net: &mut Net) -> TestResult {
+    net.wait_for_update("the renewed lease", |status| {
+        status.link_state == com_rs::LinkState::Connected && status.ipv4.addr == RENEWED_ADDR
+    })?;
+    match net.address() {
+        Some(RENEWED_ADDR) => Ok(()),
+        other => Err(format!("net is on {:?} after the renewal", other)),
+    }
+}
+
+/// Connects to the echo stand-in, and checks that what's sent comes back.
+pub fn tcp_echo(net: &mut Net) -> TestResult {
+    const MESSAGE: &[u8] = b"hello from the net-test harness";
+    let mut stream = TcpStream::connect(&net.xns, ECHO, TCP_TIMEOUT)?;
+    let sent = stream.write(MESSAGE)?;
+    if sent != MESSAGE.len() {
+        return Err(format!("sent {} of {} bytes", sent, MESSAGE.len()));
+    }
+    let mut echo = Vec::new();
+    let mut buf = [0u8; 64];
+    while echo.len() < MESSAGE.len() {
+        match stream.read(&mut buf, TCP_TIMEOUT)? {
+            0 => return Err(format!("connection closed after {} bytes of the echo", echo.len())),
+            len => echo.extend_from_slice(&buf[..len]),
+        }
+    }
+    if echo != MESSAGE {
+        return Err(format!("echo was {:?}", String::from_utf8_lossy(&echo)));
+    }
+    Ok(())
+}
+
+/// Connects to a port that nobody listens on, which should fail straight away.
+pub fn tcp_refused(net: &mut Net) -> TestResult {
+    let start = Instant::now();
+    match TcpStream::connect(&net.xns, DISCARD, TCP_TIMEOUT) {
+        Ok(_) => Err("connected to a closed port".into()),
+        Err(_) if start.elapsed() >= TCP_TIMEOUT => Err("connecting to a closed port timed out".into()),
+        Err(_) => Ok(()),
+    }
+}
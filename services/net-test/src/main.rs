@@ -0,0 +1,35 @@
+//! End-to-end tests of the network stack, run in hosted mode by `cargo xtask net-test`.
+//!
+//! The emulated EC in `com` puts the WLAN link on its virtual switch, on the network described
+//! in `network.txt`. The harness follows the lease and its renewal through the connection
+//! manager, then talks to the switch's TCP echo stand-in through the `StdTcp*` opcodes, the way
+//! libstd does. The results are reported under the `NETTEST` tag by `hosted_test::run()`.
+//!
+//! `XOUS_NET_TEST` limits the run to a comma-separated list of tests.
+
+mod flows;
+mod tcp;
+
+use flows::Net;
+use hosted_test::Test;
+
+/// Every test, in the order they run. Later tests rely on the link that the earlier ones waited
+/// for, so a filtered run should keep `lease` in it.
+const TESTS: [Test<Net>; 4] = [
+    ("lease", flows::lease),
+    ("lease_renewal", flows::lease_renewal),
+    ("tcp_echo", flows::tcp_echo),
+    ("tcp_refused", flows::tcp_refused),
+];
+const FILTER_ENV: &str = "XOUS_NET_TEST";
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    // the first lease comes early in boot, so start following the link straight away
+    let mut net = Net::new();
+
+    hosted_test::run("NETTEST", FILTER_ENV, &TESTS, &mut net)
+}
@@ -0,0 +1,113 @@
+//! Just enough of a TCP client to drive the `StdTcp*` opcodes of the `net` service. The messages
+//! are laid out the way libstd lays them out, as documented on `Opcode` in `net`'s `api.rs`, so
+//! this goes through the same code in `net` that `std::net::TcpStream` does on a device.
+
+use std::net::SocketAddrV4;
+use std::time::Duration;
+use xous::{MemoryAddress, MemoryFlags, MemoryMessage, MemoryRange, MemorySize, Message, CID};
+
+/// The name libstd looks the `net` server up by
+const SERVER_NAME_NET: &str = "_Middleware Network Server_";
+const STD_TCP_CONNECT: usize = 30;
+const STD_TCP_TX: usize = 31;
+const STD_TCP_RX: usize = 33;
+const STD_TCP_CLOSE: usize = 34;
+/// The connection index goes in the top half of the opcode
+const CONNECTION_SHIFT: usize = 16;
+const PAGE_SIZE: usize = 4096;
+
+/// An open connection, and the page that its messages are lent in
+pub struct TcpStream {
+    conn: CID,
+    index: usize,
+    page: MemoryRange,
+}
+
+impl TcpStream {
+    pub fn connect(xns: &xous_names::XousNames, addr: SocketAddrV4, timeout: Duration) -> Result<TcpStream, String> {
+        let conn = xns
+            .request_connection_blocking(SERVER_NAME_NET)
+            .map_err(|e| format!("can't connect to net: {:?}", e))?;
+        let mut page = xous::map_memory(None, None, PAGE_SIZE, MemoryFlags::R | MemoryFlags::W)
+            .map_err(|e| format!("can't map a page: {:?}", e))?;
+        let request = page.as_slice_mut::<u8>();
+        request[0..2].copy_from_slice(&addr.port().to_le_bytes());
+        request[2..10].copy_from_slice(&(timeout.as_millis() as u64).to_le_bytes());
+        request[10] = 4;
+        request[11..15].copy_from_slice(&addr.ip().octets());
+        let connected = lend_mut(conn, STD_TCP_CONNECT, page, None, None).and_then(|_| match error_code(&page) {
+            Some(code) => Err(format!("connect failed with error {}", code)),
+            None => Ok(page.as_slice::<u16>()[1] as usize),
+        });
+        match connected {
+            Ok(index) => Ok(TcpStream { conn, index, page }),
+            Err(e) => {
+                xous::unmap_memory(page).ok();
+                Err(e)
+            }
+        }
+    }
+
+    /// Sends as much of `data` as fits in the socket's buffer, and returns how much that was.
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, String> {
+        self.page.as_slice_mut::<u8>()[..data.len()].copy_from_slice(data);
+        lend_mut(self.conn, self.opcode(STD_TCP_TX), self.page, None, MemorySize::new(data.len()))?;
+        if let Some(code) = error_code(&self.page) {
+            return Err(format!("send failed with error {}", code));
+        }
+        Ok(self.page.as_slice::<u32>()[1] as usize)
+    }
+
+    /// Waits up to `timeout` for data, and returns how much of `data` it filled.
+    pub fn read(&mut self, data: &mut [u8], timeout: Duration) -> Result<usize, String> {
+        let (offset, valid) = lend_mut(
+            self.conn,
+            self.opcode(STD_TCP_RX),
+            self.page,
+            MemoryAddress::new(timeout.as_millis() as usize),
+            MemorySize::new(data.len()),
+        )?;
+        // the offset comes back empty if there was an error, and the length if nothing was received
+        if offset.is_none() {
+            return Err(format!("receive failed with error {}", self.page.as_slice::<u8>()[4]));
+        }
+        let len = valid.map(|v| v.get()).unwrap_or(0);
+        data[..len].copy_from_slice(&self.page.as_slice::<u8>()[..len]);
+        Ok(len)
+    }
+
+    fn opcode(&self, opcode: usize) -> usize {
+        opcode | (self.index << CONNECTION_SHIFT)
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        xous::send_message(self.conn, Message::new_blocking_scalar(self.opcode(STD_TCP_CLOSE), 0, 0, 0, 0)).ok();
+        xous::unmap_memory(self.page).ok();
+    }
+}
+
+fn lend_mut(
+    conn: CID,
+    opcode: usize,
+    page: MemoryRange,
+    offset: Option<MemoryAddress>,
+    valid: Option<MemorySize>,
+) -> Result<(Option<MemoryAddress>, Option<MemorySize>), String> {
+    let message = MemoryMessage { id: opcode, buf: page, offset, valid };
+    match xous::send_message(conn, Message::MutableBorrow(message)) {
+        Ok(xous::Result::MemoryReturned(offset, valid)) => Ok((offset, valid)),
+        other => Err(format!("net answered {:?}", other)),
+    }
+}
+
+/// Failures are flagged by the first four bytes all being 1, followed by the error code
+fn error_code(page: &MemoryRange) -> Option<u8> {
+    let response = page.as_slice::<u8>();
+    if response[0..4] == [1, 1, 1, 1] {
+        Some(response[4])
+    } else {
+        None
+    }
+}
@@ -1,7 +1,7 @@
 pub(crate) mod ping;
 pub(crate) use ping::*;
 pub(crate) mod tcp;
-pub use ping::NetPingCallback;
+pub use ping::{NetPingCallback, NET_PING_IPV6};
 // needed to keep hosted mode quiet, since the Tcp implementation is a bodge
 #[allow(unused_imports)]
 pub(crate) use tcp::*;
@@ -18,13 +18,18 @@ use std::io::Write;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use xous_semver::SemVer;
 
-// republish this so we can decode the icmpv4 error codes
-pub use smoltcp::wire::Icmpv4DstUnreachable;
+// republish these so we can decode the icmp error codes
+pub use smoltcp::wire::{Icmpv4DstUnreachable, Icmpv6DstUnreachable};
 
 // note: this name cannot be changed, because it is baked into `libstd`
 pub(crate) const SERVER_NAME_NET: &str = "_Middleware Network Server_";
 #[allow(dead_code)]
 pub const AP_DICT_NAME: &'static str = "wlan.networks";
+/// Packet captures written to the PDDB go in this key, each one replacing the one before
+#[allow(dead_code)]
+pub const PCAP_DICT: &'static str = "net.pcap";
+#[allow(dead_code)]
+pub const PCAP_KEY: &'static str = "capture.pcapng";
 
 #[allow(dead_code)]
 /// minimum revision required for compatibility with Net crate
@@ -364,6 +369,13 @@ pub(crate) enum Opcode {
 
     LoopbackRx = 47,
 
+    /// Start capturing frames into the packet capture ring buffer, with the `PcapFilter` lent
+    /// in the buffer. Replaces any capture that is already running.
+    PcapStart = 48,
+    /// Stop the packet capture and write it out as pcapng, to the sink in the `PcapStopIpc`
+    /// that is lent mutably. The stats or error are returned in the same structure.
+    PcapStop = 49,
+
     // do not use any numbers higher than 0x8000 as that is reserved for the nonblocking flag
 }
 #[allow(dead_code)]
@@ -387,6 +399,52 @@ pub(crate) struct SsidList {
     pub(crate) state: ScanState,
 }
 
+/// The protocols a packet capture can be narrowed down to
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum PcapProtocol {
+    Arp,
+    /// ICMP over IPv4, or ICMPv6
+    Icmp,
+    Tcp,
+    Udp,
+}
+/// Picks the frames a packet capture keeps. A frame has to match every field that is set.
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PcapFilter {
+    pub protocol: Option<PcapProtocol>,
+    /// Matches TCP and UDP segments to or from this port
+    pub port: Option<u16>,
+}
+/// Where a stopped packet capture is written
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum PcapSink {
+    /// `PCAP_DICT:PCAP_KEY` in the PDDB
+    Pddb,
+    /// Out of the USB serial port, which has to be the connected USB core
+    UsbSerial,
+}
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum PcapError {
+    /// There was no capture running
+    NotRunning,
+    /// The PDDB isn't mounted, or the USB serial port isn't connected
+    SinkUnavailable,
+    WriteFailed,
+}
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, Default)]
+pub struct PcapStats {
+    /// Frames written out
+    pub frames: u32,
+    /// Frames that were pushed out of the ring buffer by newer ones
+    pub dropped: u32,
+}
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone)]
+pub(crate) struct PcapStopIpc {
+    pub(crate) sink: PcapSink,
+    pub(crate) stats: PcapStats,
+    pub(crate) error: Option<PcapError>,
+}
+
 /// These opcodes are reserved for private SIDs shared from a DNS server to
 /// reconfigure DNS on IP change/update.
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
@@ -3,7 +3,7 @@ use com::api::NET_MTU;
 
 use smoltcp::Result;
 use smoltcp::phy::{self, DeviceCapabilities, Medium};
-use smoltcp::wire::{ArpPacket, ArpRepr, ArpOperation, Ipv4Address, EthernetAddress, EthernetFrame, EthernetProtocol};
+use smoltcp::wire::{ArpPacket, ArpRepr, ArpOperation, Ipv4Address, Ipv6Address, EthernetAddress, EthernetFrame, EthernetProtocol};
 use num_traits::*;
 use std::convert::TryInto;
 
@@ -12,6 +12,8 @@ use smoltcp::{
 };
 
 use crate::{MAC_ADDRESS_LSB, MAC_ADDRESS_MSB};
+use crate::api::PcapFilter;
+use crate::pcap::{Capture, Direction};
 use core::sync::atomic::Ordering;
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
@@ -24,6 +26,8 @@ pub struct NetPhy {
     loopback_conn: xous::CID,
     // tracks the length (and count) of the loopback packets pending
     loopback_pending: Arc::<Mutex::<VecDeque<u16>>>,
+    // the packet capture, if one is running
+    capture: Arc<Mutex<Option<Capture>>>,
 }
 
 impl<'a> NetPhy {
@@ -35,6 +39,7 @@ impl<'a> NetPhy {
             rx_avail: None,
             loopback_conn,
             loopback_pending: Arc::new(Mutex::new(VecDeque::new())),
+            capture: Arc::new(Mutex::new(None)),
         }
     }
     // returns None if there was a slot to put the availability into
@@ -47,6 +52,23 @@ impl<'a> NetPhy {
             Some(len)
         }
     }
+    /// Starts teeing the frames that match `filter` into a new capture, discarding any capture
+    /// that was already running
+    pub fn pcap_start(&mut self, filter: PcapFilter) {
+        *self.capture.lock().unwrap() = Some(Capture::new(filter));
+    }
+    /// Stops the capture and hands it back, or None if there wasn't one running, along with
+    /// the slot that it ran in, so whichever thread writes it out can resume it
+    pub fn pcap_stop(&mut self) -> (Option<Capture>, Arc<Mutex<Option<Capture>>>) {
+        (self.capture.lock().unwrap().take(), self.capture.clone())
+    }
+}
+
+/// Tees a frame into the packet capture, if there is one running
+fn capture(capture: &Mutex<Option<Capture>>, timestamp: Instant, direction: Direction, frame: &[u8]) {
+    if let Some(capture) = capture.lock().unwrap().as_mut() {
+        capture.record(timestamp.total_micros() as u64, direction, frame);
+    }
 }
 
 impl<'a> phy::Device<'a> for NetPhy {
@@ -58,14 +80,14 @@ impl<'a> phy::Device<'a> for NetPhy {
             // loopback takes precedence
             self.com.wlan_fetch_loopback_packet(&mut self.rx_buffer[..rx_len as usize]).expect("Couldn't call wlan_fetch_packet in device adapter");
 
-            Some((NetPhyRxToken{buf: &mut self.rx_buffer[..rx_len as usize]},
-            NetPhyTxToken{buf: &mut self.tx_buffer[..], com: & self.com, loopback_conn: self.loopback_conn, loopback_count: self.loopback_pending.clone()}))
+            Some((NetPhyRxToken{buf: &mut self.rx_buffer[..rx_len as usize], capture: &self.capture},
+            NetPhyTxToken{buf: &mut self.tx_buffer[..], com: & self.com, loopback_conn: self.loopback_conn, loopback_count: self.loopback_pending.clone(), capture: &self.capture}))
         } else {
             if let Some(rx_len) = self.rx_avail.take() {
                 self.com.wlan_fetch_packet(&mut self.rx_buffer[..rx_len as usize]).expect("Couldn't call wlan_fetch_packet in device adapter");
 
-                Some((NetPhyRxToken{buf: &mut self.rx_buffer[..rx_len as usize]},
-                NetPhyTxToken{buf: &mut self.tx_buffer[..], com: & self.com, loopback_conn: self.loopback_conn, loopback_count: self.loopback_pending.clone()}))
+                Some((NetPhyRxToken{buf: &mut self.rx_buffer[..rx_len as usize], capture: &self.capture},
+                NetPhyTxToken{buf: &mut self.tx_buffer[..], com: & self.com, loopback_conn: self.loopback_conn, loopback_count: self.loopback_pending.clone(), capture: &self.capture}))
             } else {
                 None
             }
@@ -73,7 +95,7 @@ impl<'a> phy::Device<'a> for NetPhy {
     }
 
     fn transmit(&'a mut self) -> Option<Self::TxToken> {
-        Some(NetPhyTxToken{buf: &mut self.tx_buffer[..], com: &self.com, loopback_conn: self.loopback_conn, loopback_count: self.loopback_pending.clone()})
+        Some(NetPhyTxToken{buf: &mut self.tx_buffer[..], com: &self.com, loopback_conn: self.loopback_conn, loopback_count: self.loopback_pending.clone(), capture: &self.capture})
     }
 
     fn capabilities(&self) -> DeviceCapabilities {
@@ -87,12 +109,14 @@ impl<'a> phy::Device<'a> for NetPhy {
 
 pub struct NetPhyRxToken<'a> {
     buf: &'a mut [u8],
+    capture: &'a Mutex<Option<Capture>>,
 }
 
 impl<'a, 'c> phy::RxToken for NetPhyRxToken<'a> {
-    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> Result<R>
+    fn consume<R, F>(mut self, timestamp: Instant, f: F) -> Result<R>
         where F: FnOnce(&mut [u8]) -> Result<R>
     {
+        capture(self.capture, timestamp, Direction::Inbound, self.buf);
         let result = f(&mut self.buf);
         //log::info!("rx: {:x?}", self.buf);
         result
@@ -104,6 +128,7 @@ pub struct NetPhyTxToken<'a> {
     com: &'a Com,
     loopback_conn: xous::CID,
     loopback_count: Arc::<Mutex::<VecDeque<u16>>>,
+    capture: &'a Mutex<Option<Capture>>,
 }
 impl <'a> NetPhyTxToken<'a> {
     /// Initiates the Rx side of things to read out the loopback packet that was queued
@@ -149,10 +174,32 @@ impl <'a> NetPhyTxToken<'a> {
         self.com.wlan_queue_loopback(&pkt);
         self.loopback_rx(pkt.len());
     }
+
+    // the IPv6 half of the hack: IPv6 finds neighbors with neighbor solicitations instead of ARP,
+    // so this creates the neighbor advertisements that answer them
+    fn wlan_queue_localhost_na(&self,
+        target_mac: EthernetAddress,
+        target_addr: Ipv6Address,
+        remote_hw_addr: EthernetAddress,
+        remote_ip_addr: Ipv6Address,
+    ) {
+        let advert = crate::ipv6::neighbor_advert(remote_ip_addr, target_addr, remote_hw_addr.0);
+        let mut eth_bytes = vec![0u8; 14 + advert.len()];
+
+        let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+        frame.set_dst_addr(target_mac);
+        frame.set_src_addr(remote_hw_addr);
+        frame.set_ethertype(EthernetProtocol::Ipv6);
+        frame.payload_mut().copy_from_slice(&advert);
+        let pkt = frame.into_inner().to_vec();
+        log::debug!("stuffing neighbor advertisement {:?}", pkt);
+        self.com.wlan_queue_loopback(&pkt);
+        self.loopback_rx(pkt.len());
+    }
 }
 
 impl<'a> phy::TxToken for NetPhyTxToken<'a> {
-    fn consume<R, F>(mut self, _timestamp: Instant, len: usize, f: F) -> Result<R>
+    fn consume<R, F>(mut self, timestamp: Instant, len: usize, f: F) -> Result<R>
         where F: FnOnce(&mut [u8]) -> Result<R>
     {
         let result = f(&mut self.buf[..len]);
@@ -180,9 +227,11 @@ impl<'a> phy::TxToken for NetPhyTxToken<'a> {
 
             if result.is_ok() {
                 if loopback {
+                    // the frame comes straight back in, and is captured then
                     self.com.wlan_queue_loopback(&self.buf[..len]);
                     self.loopback_rx(len);
                 } else {
+                    capture(self.capture, timestamp, Direction::Outbound, &self.buf[..len]);
                     {
                         // this is a hack to make loopbacks work on smoltcp. Work-around taken from Redox, but tracking this issue as well:
                         // https://github.com/smoltcp-rs/smoltcp/issues/50 and https://github.com/smoltcp-rs/smoltcp/issues/55
@@ -223,6 +272,29 @@ impl<'a> phy::TxToken for NetPhyTxToken<'a> {
                                     }
                                     _ => {}, // pass it on
                                 }
+                            } else if frame.ethertype() == EthernetProtocol::Ipv6 {
+                                if let Some((source_addr, target_addr)) = crate::ipv6::neighbor_solicit(frame.payload()) {
+                                    if target_addr == Ipv6Address::LOOPBACK {
+                                        log::trace!("intercepted outgoing neighbor solicitation for ::1: {:?}", pkt);
+                                        self.wlan_queue_localhost_na(
+                                            frame.src_addr(),
+                                            source_addr,
+                                            EthernetAddress([0, 0, 0, 0, 0, 0,]),
+                                            Ipv6Address::LOOPBACK,
+                                        );
+                                        return result;
+                                    } else if target_addr == source_addr {
+                                        // reverse lookup case
+                                        log::trace!("intercepted outgoing neighbor solicitation for own IP: {:?} {:?}", target_addr, pkt);
+                                        self.wlan_queue_localhost_na(
+                                            frame.src_addr(),
+                                            source_addr,
+                                            frame.src_addr(),
+                                            target_addr,
+                                        );
+                                        return result;
+                                    }
+                                }
                             }
                         }
                     }
@@ -0,0 +1,683 @@
+//! IPv6 address autoconfiguration (RFC 4862), which smoltcp 0.8 doesn't do on its own.
+//!
+//! Routers on the link advertise the prefixes hosts can make addresses from, whether they can
+//! be used as the default route, and (RFC 8106) which DNS servers to use. The messages are
+//! parsed and built by hand here, because smoltcp's `NdiscRepr` drops the DNS server option.
+//!
+//! The autoconfigured address gets a random interface identifier instead of one made from the
+//! MAC address, so that the address doesn't follow the device from network to network. Only
+//! one autoconfigured address and one default router are kept, and duplicate address detection
+//! isn't done.
+
+use smoltcp::wire::{IpCidr, Ipv6Address, Ipv6Cidr};
+use std::convert::TryInto;
+
+const IPV6_HEADER_LEN: usize = 40;
+const NEXT_HEADER_ICMPV6: u8 = 58;
+/// Neighbor discovery messages are sent with this hop limit, and any that arrive with less
+/// have come through a router, from off the link
+const NDISC_HOP_LIMIT: u8 = 255;
+
+const ROUTER_SOLICIT: u8 = 133;
+const ROUTER_ADVERT: u8 = 134;
+const NEIGHBOR_SOLICIT: u8 = 135;
+const NEIGHBOR_ADVERT: u8 = 136;
+
+const OPT_SOURCE_LLADDR: u8 = 1;
+const OPT_TARGET_LLADDR: u8 = 2;
+const OPT_PREFIX_INFO: u8 = 3;
+const OPT_RDNSS: u8 = 25;
+
+const PREFIX_AUTONOMOUS: u8 = 0x40;
+const ADVERT_SOLICITED: u8 = 0x40;
+const ADVERT_OVERRIDE: u8 = 0x20;
+
+/// A lifetime that never runs out
+const INFINITE: u32 = 0xffff_ffff;
+/// An advertisement can only cut an address' remaining lifetime down to two hours, so that a
+/// forged one can't take the address away (RFC 4862 section 5.5.3)
+const MIN_CUT_LIFETIME_MS: u64 = 2 * 60 * 60 * 1000;
+/// RFC 8106 section 5.3.1 suggests keeping three DNS servers
+const MAX_DNS_SERVERS: usize = 3;
+
+/// `ff02::2`, the routers on the link
+pub(crate) const ALL_ROUTERS: Ipv6Address =
+    Ipv6Address([0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02]);
+
+/// The modified EUI-64 interface identifier made from `mac` (RFC 4291 appendix A)
+pub(crate) fn eui64(mac: [u8; 6]) -> [u8; 8] {
+    [
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]
+}
+
+/// The link-local address for `mac`
+pub(crate) fn link_local(mac: [u8; 6]) -> Ipv6Address {
+    with_iid(
+        Ipv6Address([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+        eui64(mac),
+    )
+}
+
+fn with_iid(prefix: Ipv6Address, iid: [u8; 8]) -> Ipv6Address {
+    let mut addr = prefix.0;
+    addr[8..].copy_from_slice(&iid);
+    Ipv6Address(addr)
+}
+
+/// Whether `addr` is in `fe80::/10`
+pub(crate) fn is_link_local(addr: Ipv6Address) -> bool {
+    addr.0[0] == 0xfe && addr.0[1] & 0xc0 == 0x80
+}
+
+/// Whether `addr` can only be reached on this link: a link-local address, or a multicast group
+/// of link-local scope
+fn is_link_scope(addr: Ipv6Address) -> bool {
+    is_link_local(addr) || (addr.0[0] == 0xff && addr.0[1] & 0x0f == 0x02)
+}
+
+/// Picks which of `addrs` packets to `remote` go out from: the loopback address to the
+/// loopback, the link-local address to the rest of the link, and otherwise the autoconfigured
+/// address, if there is one yet.
+pub(crate) fn source_for(addrs: &[IpCidr], remote: Ipv6Address) -> Option<Ipv6Address> {
+    let mut ours = addrs.iter().filter_map(|cidr| match cidr {
+        IpCidr::Ipv6(cidr) => Some(cidr.address()),
+        _ => None,
+    });
+    if remote == Ipv6Address::LOOPBACK {
+        ours.find(|addr| *addr == Ipv6Address::LOOPBACK)
+    } else if is_link_scope(remote) {
+        ours.find(|addr| is_link_local(*addr))
+    } else {
+        ours.find(|addr| {
+            !is_link_local(*addr) && *addr != Ipv6Address::LOOPBACK && !addr.is_unspecified()
+        })
+    }
+}
+
+/// The ICMPv6 checksum of `icmp`, over the pseudo-header for `src` and `dst` (RFC 4443
+/// section 2.3). Over a message with its checksum already filled in, it comes to 0.
+fn checksum(src: Ipv6Address, dst: Ipv6Address, icmp: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut add = |bytes: &[u8]| {
+        for pair in bytes.chunks(2) {
+            sum += u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32;
+        }
+    };
+    add(&src.0);
+    add(&dst.0);
+    add(&(icmp.len() as u32).to_be_bytes());
+    add(&[0, 0, 0, NEXT_HEADER_ICMPV6]);
+    add(icmp);
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Fills in the checksum of the neighbor discovery message `icmp`, and puts it in an IPv6
+/// packet from `src` to `dst`.
+fn ndisc_packet(src: Ipv6Address, dst: Ipv6Address, mut icmp: Vec<u8>) -> Vec<u8> {
+    let sum = checksum(src, dst, &icmp);
+    icmp[2..4].copy_from_slice(&sum.to_be_bytes());
+    let mut packet = Vec::with_capacity(IPV6_HEADER_LEN + icmp.len());
+    packet.extend_from_slice(&[0x60, 0, 0, 0]);
+    packet.extend_from_slice(&(icmp.len() as u16).to_be_bytes());
+    packet.push(NEXT_HEADER_ICMPV6);
+    packet.push(NDISC_HOP_LIMIT);
+    packet.extend_from_slice(&src.0);
+    packet.extend_from_slice(&dst.0);
+    packet.extend_from_slice(&icmp);
+    packet
+}
+
+/// The neighbor discovery message in the IPv6 packet `packet`, with the addresses it's from
+/// and to. `None` if the packet isn't one, or the message fails the checks of RFC 4861 that
+/// apply to every type.
+fn ndisc_message(packet: &[u8]) -> Option<(Ipv6Address, Ipv6Address, &[u8])> {
+    if packet.len() < IPV6_HEADER_LEN
+        || packet[0] >> 4 != 6
+        || packet[6] != NEXT_HEADER_ICMPV6
+        || packet[7] != NDISC_HOP_LIMIT
+    {
+        return None;
+    }
+    let len = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+    let icmp = packet[IPV6_HEADER_LEN..].get(..len)?;
+    let src = Ipv6Address::from_bytes(&packet[8..24]);
+    let dst = Ipv6Address::from_bytes(&packet[24..40]);
+    if icmp.len() < 8 || icmp[1] != 0 || checksum(src, dst, icmp) != 0 {
+        return None;
+    }
+    Some((src, dst, icmp))
+}
+
+/// A router solicitation from `src`, asking the routers on the link to advertise now rather
+/// than in a few minutes
+pub(crate) fn router_solicitation(src: Ipv6Address, mac: [u8; 6]) -> Vec<u8> {
+    let mut icmp = vec![ROUTER_SOLICIT, 0, 0, 0, 0, 0, 0, 0, OPT_SOURCE_LLADDR, 1];
+    icmp.extend_from_slice(&mac);
+    ndisc_packet(src, ALL_ROUTERS, icmp)
+}
+
+/// A neighbor advertisement to `dst`, answering its solicitation with `target` being at `mac`
+pub(crate) fn neighbor_advert(target: Ipv6Address, dst: Ipv6Address, mac: [u8; 6]) -> Vec<u8> {
+    let mut icmp = vec![
+        NEIGHBOR_ADVERT,
+        0,
+        0,
+        0,
+        ADVERT_SOLICITED | ADVERT_OVERRIDE,
+        0,
+        0,
+        0,
+    ];
+    icmp.extend_from_slice(&target.0);
+    icmp.extend_from_slice(&[OPT_TARGET_LLADDR, 1]);
+    icmp.extend_from_slice(&mac);
+    ndisc_packet(target, dst, icmp)
+}
+
+/// If `packet` is a neighbor solicitation, the address it's from and the address it asks about
+pub(crate) fn neighbor_solicit(packet: &[u8]) -> Option<(Ipv6Address, Ipv6Address)> {
+    let (src, _, icmp) = ndisc_message(packet)?;
+    if icmp[0] != NEIGHBOR_SOLICIT || icmp.len() < 24 {
+        return None;
+    }
+    Some((src, Ipv6Address::from_bytes(&icmp[8..24])))
+}
+
+/// A prefix that a router advertises as on the link
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Prefix {
+    pub prefix: Ipv6Cidr,
+    /// Whether hosts can make addresses in the prefix for themselves
+    pub autonomous: bool,
+    /// Seconds
+    pub valid_lifetime: u32,
+    /// Seconds
+    pub preferred_lifetime: u32,
+}
+
+/// The parts of a router advertisement that autoconfiguration goes by
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RouterAdvert {
+    /// The router's link-local address
+    pub router: Ipv6Address,
+    /// Seconds the router can be the default route for, 0 if it shouldn't be
+    pub router_lifetime: u16,
+    pub prefixes: Vec<Prefix>,
+    /// Recursive DNS servers, with the seconds each can be used for
+    pub dns_servers: Vec<(Ipv6Address, u32)>,
+}
+
+impl RouterAdvert {
+    /// Parses the IPv6 packet `packet` as a router advertisement. `None` if it isn't one, or
+    /// it's malformed, or it doesn't come from a router on the link.
+    pub fn parse(packet: &[u8]) -> Option<RouterAdvert> {
+        let (router, _, icmp) = ndisc_message(packet)?;
+        if icmp[0] != ROUTER_ADVERT || icmp.len() < 16 || !is_link_local(router) {
+            return None;
+        }
+        let mut advert = RouterAdvert {
+            router,
+            router_lifetime: u16::from_be_bytes([icmp[6], icmp[7]]),
+            prefixes: Vec::new(),
+            dns_servers: Vec::new(),
+        };
+        let mut options = &icmp[16..];
+        while !options.is_empty() {
+            // option lengths are in units of 8 bytes, and an option of length 0 spoils the lot
+            let len = *options.get(1)? as usize * 8;
+            if len == 0 || len > options.len() {
+                return None;
+            }
+            let (option, rest) = options.split_at(len);
+            options = rest;
+            match option[0] {
+                OPT_PREFIX_INFO if len == 32 && option[2] <= 128 => {
+                    advert.prefixes.push(Prefix {
+                        prefix: Ipv6Cidr::new(Ipv6Address::from_bytes(&option[16..32]), option[2]),
+                        autonomous: option[3] & PREFIX_AUTONOMOUS != 0,
+                        valid_lifetime: u32_at(option, 4),
+                        preferred_lifetime: u32_at(option, 8),
+                    });
+                }
+                OPT_RDNSS if len >= 24 => {
+                    let lifetime = u32_at(option, 4);
+                    for addr in option[8..].chunks_exact(16) {
+                        advert
+                            .dns_servers
+                            .push((Ipv6Address::from_bytes(addr), lifetime));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(advert)
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// When something advertised for `lifetime` seconds at `now` runs out, in ms
+fn expiry(now: u64, lifetime: u32) -> u64 {
+    if lifetime == INFINITE {
+        u64::MAX
+    } else {
+        now + lifetime as u64 * 1000
+    }
+}
+
+/// What an advertisement, or time passing, changed about the configuration
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Changes {
+    pub address: bool,
+    pub router: bool,
+    pub dns_servers: bool,
+}
+
+impl Changes {
+    pub fn merge(&mut self, other: Changes) {
+        self.address |= other.address;
+        self.router |= other.router;
+        self.dns_servers |= other.dns_servers;
+    }
+}
+
+/// The configuration router advertisements have given, each part with when it runs out (in
+/// ms, on the same clock as the `now` passed in)
+pub(crate) struct Slaac {
+    iid: [u8; 8],
+    address: Option<(Ipv6Cidr, u64)>,
+    router: Option<(Ipv6Address, u64)>,
+    dns_servers: Vec<(Ipv6Address, u64)>,
+}
+
+impl Slaac {
+    /// `iid` is the interface identifier for the autoconfigured address
+    pub fn new(iid: [u8; 8]) -> Slaac {
+        Slaac {
+            iid,
+            address: None,
+            router: None,
+            dns_servers: Vec::new(),
+        }
+    }
+
+    pub fn address(&self) -> Option<Ipv6Cidr> {
+        self.address.map(|(cidr, _)| cidr)
+    }
+
+    pub fn router(&self) -> Option<Ipv6Address> {
+        self.router.map(|(router, _)| router)
+    }
+
+    pub fn dns_servers(&self) -> Vec<Ipv6Address> {
+        self.dns_servers.iter().map(|(server, _)| *server).collect()
+    }
+
+    /// Takes in what `advert` says.
+    pub fn process(&mut self, advert: &RouterAdvert, now: u64) -> Changes {
+        let mut changes = Changes::default();
+
+        match self.router {
+            Some((router, _)) if router != advert.router => {}
+            _ if advert.router_lifetime == 0 => {
+                changes.router = self.router.take().is_some();
+            }
+            current => {
+                changes.router = current.is_none();
+                self.router = Some((advert.router, expiry(now, advert.router_lifetime as u32)));
+            }
+        }
+
+        for prefix in advert.prefixes.iter() {
+            if !prefix.autonomous
+                || prefix.prefix.prefix_len() != 64
+                || is_link_local(prefix.prefix.address())
+                || prefix.preferred_lifetime > prefix.valid_lifetime
+            {
+                continue;
+            }
+            let cidr = Ipv6Cidr::new(with_iid(prefix.prefix.address(), self.iid), 64);
+            let valid_until = expiry(now, prefix.valid_lifetime);
+            match self.address.as_mut() {
+                None if prefix.valid_lifetime != 0 => {
+                    self.address = Some((cidr, valid_until));
+                    changes.address = true;
+                }
+                Some((current, expires)) if *current == cidr => {
+                    let remaining = expires.saturating_sub(now);
+                    if valid_until - now > MIN_CUT_LIFETIME_MS || valid_until > *expires {
+                        *expires = valid_until;
+                    } else if remaining > MIN_CUT_LIFETIME_MS {
+                        *expires = now + MIN_CUT_LIFETIME_MS;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for &(server, lifetime) in advert.dns_servers.iter() {
+            let known = self.dns_servers.iter().position(|(s, _)| *s == server);
+            match known {
+                Some(index) if lifetime == 0 => {
+                    self.dns_servers.remove(index);
+                    changes.dns_servers = true;
+                }
+                Some(index) => self.dns_servers[index].1 = expiry(now, lifetime),
+                None if lifetime != 0 && self.dns_servers.len() < MAX_DNS_SERVERS => {
+                    self.dns_servers.push((server, expiry(now, lifetime)));
+                    changes.dns_servers = true;
+                }
+                None => {}
+            }
+        }
+        changes
+    }
+
+    /// Drops whatever has run out by `now`.
+    pub fn expire(&mut self, now: u64) -> Changes {
+        let mut changes = Changes::default();
+        if matches!(self.address, Some((_, expires)) if expires <= now) {
+            self.address = None;
+            changes.address = true;
+        }
+        if matches!(self.router, Some((_, expires)) if expires <= now) {
+            self.router = None;
+            changes.router = true;
+        }
+        let servers = self.dns_servers.len();
+        self.dns_servers.retain(|&(_, expires)| expires > now);
+        changes.dns_servers = self.dns_servers.len() != servers;
+        changes
+    }
+
+    /// Forgets everything, as when leaving a network, and takes `iid` for the next one.
+    pub fn reset(&mut self, iid: [u8; 8]) {
+        *self = Slaac::new(iid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde];
+    const IID: [u8; 8] = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0];
+    const ROUTER: Ipv6Address =
+        Ipv6Address([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+    const PREFIX: [u8; 8] = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0x01];
+    const DNS: Ipv6Address = Ipv6Address([
+        0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x53,
+    ]);
+
+    fn addr(prefix: [u8; 8], iid: [u8; 8]) -> Ipv6Address {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&prefix);
+        bytes[8..].copy_from_slice(&iid);
+        Ipv6Address(bytes)
+    }
+
+    fn prefix_option(prefix: [u8; 8], flags: u8, valid: u32, preferred: u32) -> Vec<u8> {
+        let mut option = vec![OPT_PREFIX_INFO, 4, 64, flags];
+        option.extend_from_slice(&valid.to_be_bytes());
+        option.extend_from_slice(&preferred.to_be_bytes());
+        option.extend_from_slice(&[0; 4]);
+        option.extend_from_slice(&addr(prefix, [0; 8]).0);
+        option
+    }
+
+    fn rdnss_option(servers: &[Ipv6Address], lifetime: u32) -> Vec<u8> {
+        let mut option = vec![OPT_RDNSS, 1 + 2 * servers.len() as u8, 0, 0];
+        option.extend_from_slice(&lifetime.to_be_bytes());
+        for server in servers {
+            option.extend_from_slice(&server.0);
+        }
+        option
+    }
+
+    fn advert_packet(router_lifetime: u16, options: &[Vec<u8>]) -> Vec<u8> {
+        let mut icmp = vec![ROUTER_ADVERT, 0, 0, 0, 64, 0];
+        icmp.extend_from_slice(&router_lifetime.to_be_bytes());
+        icmp.extend_from_slice(&[0; 8]);
+        for option in options {
+            icmp.extend_from_slice(option);
+        }
+        let all_nodes = Ipv6Address([0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+        ndisc_packet(ROUTER, all_nodes, icmp)
+    }
+
+    fn advert(router_lifetime: u16, options: &[Vec<u8>]) -> RouterAdvert {
+        RouterAdvert::parse(&advert_packet(router_lifetime, options)).unwrap()
+    }
+
+    #[test]
+    fn makes_link_local_addresses() {
+        // the example from RFC 4291 appendix A
+        assert_eq!(eui64(MAC), [0x36, 0x56, 0x78, 0xff, 0xfe, 0x9a, 0xbc, 0xde]);
+        assert_eq!(
+            link_local(MAC),
+            Ipv6Address::new(0xfe80, 0, 0, 0, 0x3656, 0x78ff, 0xfe9a, 0xbcde)
+        );
+    }
+
+    #[test]
+    fn builds_neighbor_discovery_messages() {
+        let src = link_local(MAC);
+        let solicit = router_solicitation(src, MAC);
+        let (from, to, icmp) = ndisc_message(&solicit).unwrap();
+        assert_eq!((from, to), (src, ALL_ROUTERS));
+        assert_eq!(icmp[0], ROUTER_SOLICIT);
+        assert_eq!(
+            &icmp[8..],
+            &[OPT_SOURCE_LLADDR, 1, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde]
+        );
+        // the checksum, worked out independently
+        assert_eq!(&icmp[2..4], &[0xa7, 0x8f]);
+
+        let advert = neighbor_advert(Ipv6Address::LOOPBACK, Ipv6Address::LOOPBACK, [0; 6]);
+        let (from, _, icmp) = ndisc_message(&advert).unwrap();
+        assert_eq!(from, Ipv6Address::LOOPBACK);
+        assert_eq!(icmp[0], NEIGHBOR_ADVERT);
+        assert_eq!(&icmp[8..24], &Ipv6Address::LOOPBACK.0);
+
+        let mut icmp = vec![NEIGHBOR_SOLICIT, 0, 0, 0, 0, 0, 0, 0];
+        icmp.extend_from_slice(&Ipv6Address::LOOPBACK.0);
+        let solicited_node = Ipv6Address::new(0xff02, 0, 0, 0, 0, 1, 0xff00, 0x0001);
+        let mut packet = ndisc_packet(Ipv6Address::LOOPBACK, solicited_node, icmp);
+        assert_eq!(
+            neighbor_solicit(&packet),
+            Some((Ipv6Address::LOOPBACK, Ipv6Address::LOOPBACK))
+        );
+        assert_eq!(neighbor_solicit(&solicit), None);
+        packet[IPV6_HEADER_LEN + 8] ^= 1;
+        assert_eq!(neighbor_solicit(&packet), None);
+    }
+
+    #[test]
+    fn parses_router_adverts() {
+        let advert = advert(
+            1800,
+            &[
+                vec![OPT_SOURCE_LLADDR, 1, 0, 0, 0x5e, 0, 0x53, 0x01],
+                prefix_option(PREFIX, PREFIX_AUTONOMOUS | 0x80, 86400, 14400),
+                rdnss_option(&[DNS, ROUTER], 600),
+            ],
+        );
+        assert_eq!(
+            advert,
+            RouterAdvert {
+                router: ROUTER,
+                router_lifetime: 1800,
+                prefixes: vec![Prefix {
+                    prefix: Ipv6Cidr::new(addr(PREFIX, [0; 8]), 64),
+                    autonomous: true,
+                    valid_lifetime: 86400,
+                    preferred_lifetime: 14400,
+                }],
+                dns_servers: vec![(DNS, 600), (ROUTER, 600)],
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_router_adverts() {
+        let good = advert_packet(1800, &[prefix_option(PREFIX, PREFIX_AUTONOMOUS, 600, 600)]);
+        assert!(RouterAdvert::parse(&good).is_some());
+
+        // forwarded by a router
+        let mut packet = good.clone();
+        packet[7] = 64;
+        assert_eq!(RouterAdvert::parse(&packet), None);
+        // corrupted
+        let mut packet = good.clone();
+        packet[IPV6_HEADER_LEN + 20] ^= 0x80;
+        assert_eq!(RouterAdvert::parse(&packet), None);
+        // cut short
+        let mut packet = good.clone();
+        packet.truncate(packet.len() - 1);
+        assert_eq!(RouterAdvert::parse(&packet), None);
+        // an option of length 0
+        let packet = advert_packet(1800, &[vec![OPT_PREFIX_INFO, 0, 0, 0, 0, 0, 0, 0]]);
+        assert_eq!(RouterAdvert::parse(&packet), None);
+        // not from a link-local address
+        let mut icmp = good[IPV6_HEADER_LEN..].to_vec();
+        icmp[2..4].copy_from_slice(&[0, 0]);
+        let packet = ndisc_packet(DNS, ALL_ROUTERS, icmp);
+        assert_eq!(RouterAdvert::parse(&packet), None);
+    }
+
+    #[test]
+    fn configures_from_adverts() {
+        let mut slaac = Slaac::new(IID);
+        let options = [
+            prefix_option([0xfe, 0x80, 0, 0, 0, 0, 0, 0], PREFIX_AUTONOMOUS, 600, 600),
+            prefix_option([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0x02], 0, 600, 600),
+            prefix_option(PREFIX, PREFIX_AUTONOMOUS, 600, 300),
+            rdnss_option(&[DNS], 300),
+        ];
+        let changes = slaac.process(&advert(1800, &options), 1000);
+        assert_eq!(
+            changes,
+            Changes {
+                address: true,
+                router: true,
+                dns_servers: true
+            }
+        );
+        assert_eq!(slaac.address(), Some(Ipv6Cidr::new(addr(PREFIX, IID), 64)));
+        assert_eq!(slaac.router(), Some(ROUTER));
+        assert_eq!(slaac.dns_servers(), vec![DNS]);
+
+        // the same again changes nothing, and refreshes the lifetimes
+        assert_eq!(
+            slaac.process(&advert(1800, &options), 200_000),
+            Changes::default()
+        );
+        assert_eq!(slaac.expire(400_000), Changes::default());
+        // the DNS server runs out first, then the address, then the router
+        let changes = slaac.expire(500_000);
+        assert_eq!(
+            changes,
+            Changes {
+                dns_servers: true,
+                ..Changes::default()
+            }
+        );
+        let changes = slaac.expire(800_000);
+        assert_eq!(
+            changes,
+            Changes {
+                address: true,
+                ..Changes::default()
+            }
+        );
+        assert_eq!(
+            slaac.expire(2_000_000),
+            Changes {
+                router: true,
+                ..Changes::default()
+            }
+        );
+        assert_eq!((slaac.address(), slaac.router()), (None, None));
+        assert!(slaac.dns_servers().is_empty());
+    }
+
+    #[test]
+    fn withdraws_what_adverts_withdraw() {
+        let mut slaac = Slaac::new(IID);
+        let options = [
+            prefix_option(PREFIX, PREFIX_AUTONOMOUS, INFINITE, INFINITE),
+            rdnss_option(&[DNS], INFINITE),
+        ];
+        slaac.process(&advert(1800, &options), 0);
+
+        // another router doesn't take over the default route
+        let mut other = advert(1800, &[]);
+        other.router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+        assert_eq!(slaac.process(&other, 0), Changes::default());
+        assert_eq!(slaac.router(), Some(ROUTER));
+
+        // a zero lifetime takes away the router and the DNS server at once, but only cuts the
+        // address' lifetime down to two hours
+        let options = [
+            prefix_option(PREFIX, PREFIX_AUTONOMOUS, 0, 0),
+            rdnss_option(&[DNS], 0),
+        ];
+        let changes = slaac.process(&advert(0, &options), 1000);
+        assert_eq!(
+            changes,
+            Changes {
+                address: false,
+                router: true,
+                dns_servers: true
+            }
+        );
+        assert_eq!(slaac.router(), None);
+        assert!(slaac.dns_servers().is_empty());
+        assert_eq!(
+            slaac.expire(1000 + MIN_CUT_LIFETIME_MS - 1),
+            Changes::default()
+        );
+        let changes = slaac.expire(1000 + MIN_CUT_LIFETIME_MS);
+        assert_eq!(
+            changes,
+            Changes {
+                address: true,
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn picks_source_addresses() {
+        let link_local = link_local(MAC);
+        let global = addr(PREFIX, IID);
+        let mut addrs = vec![
+            IpCidr::new(Ipv6Address::LOOPBACK.into(), 128),
+            IpCidr::new(link_local.into(), 64),
+        ];
+        let remote = Ipv6Address::new(0x2001, 0x0db8, 0, 0x02, 0, 0, 0, 1);
+        assert_eq!(source_for(&addrs, remote), None);
+        addrs.insert(0, IpCidr::new(global.into(), 64));
+
+        assert_eq!(source_for(&addrs, remote), Some(global));
+        assert_eq!(source_for(&addrs, ROUTER), Some(link_local));
+        assert_eq!(source_for(&addrs, ALL_ROUTERS), Some(link_local));
+        assert_eq!(
+            source_for(&addrs, Ipv6Address::LOOPBACK),
+            Some(Ipv6Address::LOOPBACK)
+        );
+    }
+}
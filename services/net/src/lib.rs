@@ -184,6 +184,29 @@ impl NetManager {
             Message::new_scalar(Opcode::ConnMgrStartStop.to_usize().unwrap(), 4, 0,0, 0)
         ).map(|_| ())
     }
+    /// Starts capturing the frames that match `filter` into a ring buffer. A capture that is
+    /// already running is thrown away.
+    pub fn pcap_start(&self, filter: PcapFilter) -> Result<(), xous::Error> {
+        let buf = Buffer::into_buf(filter).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.netconn.conn(), Opcode::PcapStart.to_u32().unwrap()).map(|_| ())
+    }
+    /// Stops the capture and writes it to `sink` as a pcapng file. This blocks until the file
+    /// is written, which can take a few seconds, but the net stack carries on in the meantime.
+    /// If the file can't be written, or the USB host stops reading it, the capture carries on.
+    pub fn pcap_stop(&self, sink: PcapSink) -> Result<PcapStats, PcapError> {
+        let stop = PcapStopIpc {
+            sink,
+            stats: PcapStats::default(),
+            error: None,
+        };
+        let mut buf = Buffer::into_buf(stop).expect("Couldn't convert to memory structure");
+        buf.lend_mut(self.netconn.conn(), Opcode::PcapStop.to_u32().unwrap()).expect("Couldn't execute PcapStop opcode");
+        let stop = buf.to_original::<PcapStopIpc, _>().expect("couldn't restore packet capture result");
+        match stop.error {
+            Some(e) => Err(e),
+            None => Ok(stop.stats),
+        }
+    }
 }
 impl Drop for NetManager {
     fn drop(&mut self) {
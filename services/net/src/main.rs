@@ -17,6 +17,8 @@ use num_traits::*;
 
 mod connection_manager;
 mod device;
+mod ipv6;
+mod pcap;
 
 #[cfg(test)]
 mod tests;
@@ -30,10 +32,12 @@ use xous_ipc::Buffer;
 
 use byteorder::{ByteOrder, NetworkEndian};
 use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache, Routes};
-use smoltcp::phy::{Device, Medium};
+use smoltcp::phy::{ChecksumCapabilities, Device, Medium};
 use smoltcp::socket::{IcmpEndpoint, IcmpPacketMetadata, IcmpSocket, IcmpSocketBuffer};
+use smoltcp::socket::{RawPacketMetadata, RawSocket, RawSocketBuffer};
 use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr, IpEndpoint};
 use smoltcp::wire::{Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr};
+use smoltcp::wire::{IpProtocol, IpVersion, Ipv6Address, Ipv6Cidr};
 use crate::device::NetPhy;
 
 use core::num::NonZeroU64;
@@ -76,6 +80,69 @@ where
     });
 }
 
+/// Replaces the autoconfigured IPv6 address, if there is one, with `cidr`. It goes ahead of
+/// the link-local and loopback addresses.
+fn set_ipv6_addr<DeviceT>(iface: &mut Interface<'_, DeviceT>, cidr: Option<Ipv6Cidr>)
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    log::info!("updating to IPv6 address {:?}", cidr);
+    iface.update_ip_addrs(|addrs| {
+        let mut updated: Vec<IpCidr> = addrs
+            .iter()
+            .filter(|addr| match addr {
+                IpCidr::Ipv6(addr) => {
+                    ipv6::is_link_local(addr.address()) || addr.address() == Ipv6Address::LOOPBACK
+                }
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        if let Some(cidr) = cidr {
+            let first_v6 = updated
+                .iter()
+                .position(|addr| matches!(addr, IpCidr::Ipv6(_)))
+                .unwrap_or(updated.len());
+            updated.insert(first_v6, IpCidr::Ipv6(cidr));
+        }
+        *addrs = updated.into();
+    });
+}
+
+/// A fresh interface identifier for the autoconfigured IPv6 address
+fn random_iid(trng: &trng::Trng) -> [u8; 8] {
+    trng.get_u64().unwrap().to_be_bytes()
+}
+
+/// Gives the DNS resolver the servers we know of: the ones from DHCP, and the ones IPv6 routers
+/// advertise. There's no hook for taking away a single server, so the whole list goes again.
+fn announce_dns_servers(
+    allclear_hook: &mut XousScalarEndpoint,
+    ipv4_hook: &mut XousScalarEndpoint,
+    ipv6_hook: &mut XousScalarEndpoint,
+    config: Option<&Ipv4Conf>,
+    ipv6_servers: &[Ipv6Address],
+) {
+    allclear_hook.notify();
+    if let Some(config) = config {
+        ipv4_hook.notify_custom_args([Some(u32::from_be_bytes(config.dns1)), None, None, None]);
+        // the current implementation always returns 0.0.0.0 as the second dns,
+        // ignore this if that's what we've got; otherwise, pass it on.
+        if config.dns2 != [0, 0, 0, 0] {
+            ipv4_hook.notify_custom_args([Some(u32::from_be_bytes(config.dns2)), None, None, None]);
+        }
+    }
+    for server in ipv6_servers {
+        let words = server.0;
+        ipv6_hook.notify_custom_args([
+            Some(u32::from_be_bytes(words[0..4].try_into().unwrap())),
+            Some(u32::from_be_bytes(words[4..8].try_into().unwrap())),
+            Some(u32::from_be_bytes(words[8..12].try_into().unwrap())),
+            Some(u32::from_be_bytes(words[12..16].try_into().unwrap())),
+        ]);
+    }
+}
+
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 enum WaitOp {
     WaitMs,
@@ -103,6 +170,16 @@ pub struct PingConnection {
     retop: usize,
 }
 
+impl PingConnection {
+    /// The first argument of a `callback` message about this connection
+    fn callback_op(&self, callback: NetPingCallback) -> usize {
+        match self.remote {
+            IpAddress::Ipv6(_) => callback.to_usize().unwrap() | NET_PING_IPV6,
+            _ => callback.to_usize().unwrap(),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct WaitingSocket {
     env: xous::MessageEnvelope,
@@ -191,6 +268,28 @@ fn setup_icmp(iface: &mut Interface::<NetPhy>) -> SocketHandle {
     icmp_handle
 }
 
+/// The socket that IPv6 router advertisements come in on, and router solicitations go out on
+fn setup_ndisc(iface: &mut Interface::<NetPhy>) -> SocketHandle {
+    // every ICMPv6 packet comes through here, most of which we throw away
+    let ndisc_rx_buffer = RawSocketBuffer::new(
+        vec![
+            RawPacketMetadata::EMPTY,
+            RawPacketMetadata::EMPTY,
+            RawPacketMetadata::EMPTY,
+            RawPacketMetadata::EMPTY,
+        ],
+        vec![0; 2048],
+    );
+    let ndisc_tx_buffer = RawSocketBuffer::new(vec![RawPacketMetadata::EMPTY], vec![0; 128]);
+    let ndisc_socket = RawSocket::new(
+        IpVersion::Ipv6,
+        IpProtocol::Icmpv6,
+        ndisc_rx_buffer,
+        ndisc_tx_buffer,
+    );
+    iface.add_socket(ndisc_socket)
+}
+
 fn main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Info);
@@ -271,10 +370,6 @@ fn main() -> ! {
 
     // --------------- other link storage -------------
     let neighbor_cache = NeighborCache::new(BTreeMap::new());
-    let ip_addrs = [
-        IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0),
-        IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8)
-    ];
     let routes = Routes::new(BTreeMap::new());
 
     // build the device
@@ -296,6 +391,13 @@ fn main() -> ! {
     log::debug!("My MAC address is: {:x?}", hw_config.mac);
     MAC_ADDRESS_LSB.store(u32::from_be_bytes(hw_config.mac[2..6].try_into().unwrap()), Ordering::SeqCst);
     MAC_ADDRESS_MSB.store(u16::from_be_bytes(hw_config.mac[0..2].try_into().unwrap()), Ordering::SeqCst);
+    // the autoconfigured IPv6 address goes in ahead of the link-local one, once a router advertises a prefix
+    let ip_addrs = vec![
+        IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0),
+        IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8),
+        IpCidr::new(ipv6::link_local(hw_config.mac).into(), 64),
+        IpCidr::new(Ipv6Address::LOOPBACK.into(), 128),
+    ];
     let device = device::NetPhy::new(&xns, net_cid);
     // needed by ICMP to determine if we should compute checksums
     let device_caps = device.capabilities();
@@ -317,6 +419,10 @@ fn main() -> ! {
     let mut ping_destinations = HashMap::<PingConnection, HashMap<u16, u64>>::new();
     let mut ping_timeout_ms = PING_DEFAULT_TIMEOUT_MS;
 
+    // IPv6 address autoconfiguration
+    let ndisc_handle = setup_ndisc(&mut iface);
+    let mut slaac = ipv6::Slaac::new(random_iid(&trng));
+
     // DNS hooks - the DNS server can ask the Net crate to tickle it when IP configs change using these hooks
     // Currently, we assume there is only one DNS server in Xous. I suppose you could
     // upgrade the code to handle multiple DNS servers, but...why???
@@ -426,8 +532,10 @@ fn main() -> ! {
                     Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
                 };
                 let mut pkt = buf.to_original::<NetPingPacket, _>().unwrap();
+                // an IPv6 ping goes nowhere until we have an address in the right scope to send it from
+                let source = source_address(&iface, IpAddress::from(pkt.endpoint));
                 let socket = iface.get_socket::<IcmpSocket>(icmp_handle);
-                if socket.can_send() {
+                if let (true, Some(source)) = (socket.can_send(), source) {
                     log::debug!("sending ping to {:?}", pkt.endpoint);
                     let remote = IpAddress::from(pkt.endpoint);
                     // we take advantage of the fact that the same CID is always returned for repeated connect requests to the same SID.
@@ -489,8 +597,6 @@ fn main() -> ! {
                             icmp_repr.emit(&mut icmp_packet, &device_caps.checksum);
                         }
                         IpAddress::Ipv6(_) => {
-                            // not sure if this is a valid thing to do, to just assign the source some number like this??
-                            let src_ipv6 = IpAddress::v6(0xfdaa, 0, 0, 0, 0, 0, 0, 1);
                             let icmp_repr = Icmpv6Repr::EchoRequest {
                                 ident: PING_IDENT,
                                 seq_no: seq,
@@ -499,7 +605,7 @@ fn main() -> ! {
                             let icmp_payload = socket.send(icmp_repr.buffer_len(), remote).unwrap();
                             let mut icmp_packet = Icmpv6Packet::new_unchecked(icmp_payload);
                             icmp_repr.emit(
-                                &src_ipv6,
+                                &source,
                                 &remote,
                                 &mut icmp_packet,
                                 &device_caps.checksum,
@@ -1133,8 +1239,8 @@ fn main() -> ! {
                                     log::warn!("Battery is critical! TODO: go into SHIP mode");
                                 }
                                 ComIntSources::WlanIpConfigUpdate => {
-                                    // right now the WLAN implementation only does IPV4. So IPV6 compatibility ends here.
-                                    // if IPV6 gets added to the EC/COM bus, ideally this is one of a couple spots in Xous that needs a tweak.
+                                    // the WLAN implementation only does DHCP for IPV4. IPV6 is configured on our side, from
+                                    // the router advertisements solicited below.
                                     let config = match com
                                     .wlan_get_config() {
                                         Ok(config) => config,
@@ -1180,23 +1286,29 @@ fn main() -> ! {
                                         ),
                                         Err(e) => log::error!("routing table update error: {}", e),
                                     }
-                                    dns_allclear_hook.notify();
-                                    dns_ipv4_hook.notify_custom_args([
-                                        Some(u32::from_be_bytes(config.dns1)),
-                                        None,
-                                        None,
-                                        None,
-                                    ]);
-                                    // the current implementation always returns 0.0.0.0 as the second dns,
-                                    // ignore this if that's what we've got; otherwise, pass it on.
-                                    if config.dns2 != [0, 0, 0, 0] {
-                                        dns_ipv4_hook.notify_custom_args([
-                                            Some(u32::from_be_bytes(config.dns2)),
-                                            None,
-                                            None,
-                                            None,
-                                        ]);
+                                    announce_dns_servers(
+                                        &mut dns_allclear_hook,
+                                        &mut dns_ipv4_hook,
+                                        &mut dns_ipv6_hook,
+                                        Some(&config),
+                                        &slaac.dns_servers(),
+                                    );
+
+                                    // IPv6 comes up alongside, once a router answers this
+                                    let solicitation = ipv6::router_solicitation(
+                                        ipv6::link_local(config.mac),
+                                        config.mac,
+                                    );
+                                    let socket = iface.get_socket::<RawSocket>(ndisc_handle);
+                                    match socket.send_slice(&solicitation) {
+                                        Ok(_) => log::debug!("soliciting IPv6 router advertisements"),
+                                        Err(e) => log::warn!("couldn't solicit IPv6 router advertisements: {:?}", e),
                                     }
+                                    xous::try_send_message(
+                                        net_conn,
+                                        Message::new_scalar(Opcode::NetPump.to_usize().unwrap(), 0, 0, 0, 0),
+                                    )
+                                    .ok();
                                 }
                                 ComIntSources::WlanRxReady => {
                                     activity_interval.store(0, Ordering::Relaxed); // reset the activity interval to 0
@@ -1742,15 +1854,15 @@ fn main() -> ! {
                     }
 
                     if socket.can_recv() {
-                        let (payload, _) = socket
+                        let (payload, from) = socket
                             .recv()
                             .expect("couldn't receive on socket despite asserting availability");
                         log::trace!("icmp payload: {:x?}", payload);
 
                         for (connection, waiting_queue) in ping_destinations.iter_mut() {
                             let remote_addr = connection.remote;
-                            match remote_addr {
-                                IpAddress::Ipv4(_) => {
+                            match (remote_addr, from) {
+                                (IpAddress::Ipv4(_), IpAddress::Ipv4(_)) => {
                                     let icmp_packet = Icmpv4Packet::new_checked(&payload).unwrap();
                                     let icmp_repr =
                                         Icmpv4Repr::parse(&icmp_packet, &device_caps.checksum)
@@ -1770,7 +1882,7 @@ fn main() -> ! {
                                                 connection.cid,
                                                 Message::new_scalar(
                                                     connection.retop,
-                                                    NetPingCallback::NoErr.to_usize().unwrap(),
+                                                    connection.callback_op(NetPingCallback::NoErr),
                                                     u32::from_be_bytes(
                                                         remote_addr.as_bytes().try_into().unwrap(),
                                                     )
@@ -1807,7 +1919,7 @@ fn main() -> ! {
                                             connection.cid,
                                             Message::new_scalar(
                                                 connection.retop,
-                                                NetPingCallback::Unreachable.to_usize().unwrap()
+                                                connection.callback_op(NetPingCallback::Unreachable)
                                                     | (reason_code as usize) << 24,
                                                 u32::from_be_bytes(
                                                     remote_addr.as_bytes().try_into().unwrap(),
@@ -1830,17 +1942,22 @@ fn main() -> ! {
                                     }
                                 }
 
-                                IpAddress::Ipv6(_) => {
-                                    // NOTE: actually not sure what src_ipv6 should be. This is just from an example.
-                                    let src_ipv6 = IpAddress::v6(0xfdaa, 0, 0, 0, 0, 0, 0, 1);
+                                (IpAddress::Ipv6(_), IpAddress::Ipv6(_)) => {
+                                    // the interface checked the checksum on the way in, back when it knew
+                                    // which of our addresses the packet was sent to
                                     let icmp_packet = Icmpv6Packet::new_checked(&payload).unwrap();
-                                    let icmp_repr = Icmpv6Repr::parse(
-                                        &remote_addr,
-                                        &src_ipv6,
+                                    let icmp_repr = match Icmpv6Repr::parse(
+                                        &from,
+                                        &IpAddress::Ipv6(Ipv6Address::UNSPECIFIED),
                                         &icmp_packet,
-                                        &device_caps.checksum,
-                                    )
-                                    .unwrap();
+                                        &ChecksumCapabilities::ignored(),
+                                    ) {
+                                        Ok(repr) => repr,
+                                        Err(e) => {
+                                            log::warn!("couldn't parse ICMPv6 packet from {:?}: {:?}", from, e);
+                                            continue;
+                                        }
+                                    };
                                     let ra = remote_addr.as_bytes();
                                     if let Icmpv6Repr::EchoReply { seq_no, data, .. } = icmp_repr {
                                         if let Some(_) = waiting_queue.get(&seq_no) {
@@ -1850,11 +1967,10 @@ fn main() -> ! {
                                                 connection.cid,
                                                 Message::new_scalar(
                                                     connection.retop,
-                                                    NetPingCallback::NoErr.to_usize().unwrap(),
+                                                    connection.callback_op(NetPingCallback::NoErr),
                                                     u32::from_be_bytes(ra[..4].try_into().unwrap())
                                                         as usize,
-                                                    u32::from_be_bytes(ra[12..].try_into().unwrap())
-                                                        as usize,
+                                                    seq_no as usize,
                                                     (now as i64 - packet_timestamp_ms) as usize,
                                                 ),
                                             ) {
@@ -1886,14 +2002,12 @@ fn main() -> ! {
                                             connection.cid,
                                             Message::new_scalar(
                                                 connection.retop,
-                                                NetPingCallback::Unreachable.to_usize().unwrap()
+                                                connection.callback_op(NetPingCallback::Unreachable)
                                                     | (reason_code as usize) << 24,
                                                 u32::from_be_bytes(ra[..4].try_into().unwrap())
                                                     as usize,
-                                                u32::from_be_bytes(ra[8..12].try_into().unwrap())
-                                                    as usize,
-                                                u32::from_be_bytes(ra[12..].try_into().unwrap())
-                                                    as usize,
+                                                0,
+                                                0,
                                             ),
                                         ) {
                                             Ok(_) => {}
@@ -1908,11 +2022,47 @@ fn main() -> ! {
                                         log::error!("got unhandled ICMP type, ignoring!");
                                     }
                                 }
-                                _ => unimplemented!(),
+                                _ => {} // a reply to a ping of the other address family
                             }
                         }
                     }
                 }
+                // this block takes in IPv6 router advertisements, and ages out what they configured
+                log::trace!("pump: ndisc");
+                {
+                    let mut changes = slaac.expire(now);
+                    let socket = iface.get_socket::<RawSocket>(ndisc_handle);
+                    while socket.can_recv() {
+                        let packet = socket
+                            .recv()
+                            .expect("couldn't receive on socket despite asserting availability");
+                        if let Some(advert) = ipv6::RouterAdvert::parse(packet) {
+                            log::debug!("router advertisement: {:?}", advert);
+                            changes.merge(slaac.process(&advert, now));
+                        }
+                    }
+                    if changes.address {
+                        set_ipv6_addr(&mut iface, slaac.address());
+                    }
+                    if changes.router {
+                        iface.routes_mut().remove_default_ipv6_route();
+                        if let Some(router) = slaac.router() {
+                            match iface.routes_mut().add_default_ipv6_route(router) {
+                                Ok(route) => log::info!("IPv6 routing table updated successfully [{:?}]", route),
+                                Err(e) => log::error!("IPv6 routing table update error: {}", e),
+                            }
+                        }
+                    }
+                    if changes.dns_servers {
+                        announce_dns_servers(
+                            &mut dns_allclear_hook,
+                            &mut dns_ipv4_hook,
+                            &mut dns_ipv6_hook,
+                            net_config.as_ref(),
+                            &slaac.dns_servers(),
+                        );
+                    }
+                }
                 // this block handles ICMP retirement; it runs everytime we pump the block
                 log::trace!("pump: icmp retirement");
                 {
@@ -1926,7 +2076,7 @@ fn main() -> ! {
                             match xous::send_message(conn.cid,
                                 Message::new_scalar( // we should wait if the queue is full, as the "Drop" message is important
                                     conn.retop,
-                                    NetPingCallback::Drop.to_usize().unwrap(),
+                                    conn.callback_op(NetPingCallback::Drop),
                                     u32::from_be_bytes(ra[..4].try_into().unwrap()) as usize,
                                     if ra.len() == 16 {u32::from_be_bytes(ra[12..16].try_into().unwrap()) as usize} else {0},
                                     0,
@@ -1964,7 +2114,7 @@ fn main() -> ! {
                                 match xous::try_send_message(conn.cid,
                                     Message::new_scalar( // we should wait if the queue is full, as the "Drop" message is important
                                         conn.retop,
-                                        NetPingCallback::Timeout.to_usize().unwrap(),
+                                        conn.callback_op(NetPingCallback::Timeout),
                                         u32::from_be_bytes(ra[..4].try_into().unwrap()) as usize,
                                         seq as usize,
                                         (now - start_time) as usize,
@@ -2164,6 +2314,41 @@ fn main() -> ! {
                     log::error!("Got incorrect start/stop code: {}", code);
                 }
             }),
+            Some(Opcode::PcapStart) => {
+                let buffer = unsafe {
+                    Buffer::from_memory_message(msg.body.memory_message().unwrap())
+                };
+                let filter = buffer.to_original::<PcapFilter, _>().unwrap();
+                log::info!("starting packet capture: {:?}", filter);
+                iface.device_mut().pcap_start(filter);
+            }
+            Some(Opcode::PcapStop) => {
+                let (capture, slot) = iface.device_mut().pcap_stop();
+                // writing out can take a while, and the USB serial port waits on the host, so
+                // it's done on a thread of its own rather than holding up the stack. The caller
+                // stays blocked until the message is dropped at the end of the thread.
+                thread::spawn(move || {
+                    let mut msg = msg;
+                    let mut buffer = unsafe {
+                        Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
+                    };
+                    let mut stop = buffer.to_original::<PcapStopIpc, _>().unwrap();
+                    match capture {
+                        Some(capture) => {
+                            stop.stats = capture.stats();
+                            log::info!("writing packet capture to {:?}: {:?}", stop.sink, stop.stats);
+                            stop.error = pcap::write(&capture, stop.sink).err();
+                            if stop.error.is_some() {
+                                // carry on, so the capture can be stopped again to another sink,
+                                // unless a new capture was started in the meantime
+                                slot.lock().unwrap().get_or_insert(capture);
+                            }
+                        }
+                        None => stop.error = Some(PcapError::NotRunning),
+                    }
+                    buffer.replace(stop).expect("couldn't return packet capture result");
+                });
+            }
             Some(Opcode::Reset) => {
                 // reset the DHCP address
                 IPV4_ADDRESS.store(0, Ordering::SeqCst);
@@ -2178,6 +2363,10 @@ fn main() -> ! {
 
                 // note: ARP cache isn't reset
                 iface.routes_mut().remove_default_ipv4_route();
+                // the next network gets a new IPv6 address
+                slaac.reset(random_iid(&trng));
+                set_ipv6_addr(&mut iface, None);
+                iface.routes_mut().remove_default_ipv6_route();
                 dns_allclear_hook.notify();
 
                 send_message(
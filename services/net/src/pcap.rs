@@ -0,0 +1,381 @@
+//! Packet capture, for when the link misbehaves and the logs don't say why.
+//!
+//! While a capture runs, the smoltcp device tees every frame that passes the filter into a
+//! ring buffer. Stopping the capture writes the ring buffer out as a pcapng file, either into
+//! the PDDB or out of the USB serial port. The ring buffer is bounded, so a long capture keeps
+//! the newest frames and counts the ones that were pushed out.
+//!
+//! Timestamps count from boot rather than from the epoch, because that's the clock smoltcp
+//! runs on.
+
+use crate::api::{PcapError, PcapFilter, PcapProtocol, PcapSink, PcapStats, PCAP_DICT, PCAP_KEY};
+use com::api::NET_MTU;
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// How many bytes of frames the ring buffer holds
+const RING_CAPACITY: usize = 128 * 1024;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const IP_ICMP: u8 = 1;
+const IP_TCP: u8 = 6;
+const IP_UDP: u8 = 17;
+const IP_ICMPV6: u8 = 58;
+
+// pcapng blocks and options, from draft-ietf-opsawg-pcapng
+const SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION: u32 = 1;
+const ENHANCED_PACKET: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const LINKTYPE_ETHERNET: u16 = 1;
+const OPT_END: u16 = 0;
+const OPT_EPB_FLAGS: u16 = 2;
+const FLAGS_INBOUND: u32 = 1;
+const FLAGS_OUTBOUND: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Inbound,
+    Outbound,
+}
+
+struct Record {
+    timestamp_us: u64,
+    direction: Direction,
+    frame: Vec<u8>,
+}
+
+pub(crate) struct Capture {
+    filter: PcapFilter,
+    records: VecDeque<Record>,
+    /// Bytes of frames in `records`
+    len: usize,
+    dropped: u32,
+}
+impl Capture {
+    pub(crate) fn new(filter: PcapFilter) -> Capture {
+        Capture {
+            filter,
+            records: VecDeque::new(),
+            len: 0,
+            dropped: 0,
+        }
+    }
+    /// Keeps a copy of `frame`, if it passes the filter
+    pub(crate) fn record(&mut self, timestamp_us: u64, direction: Direction, frame: &[u8]) {
+        if !matches(&self.filter, frame) {
+            return;
+        }
+        while self.len + frame.len() > RING_CAPACITY {
+            match self.records.pop_front() {
+                Some(oldest) => {
+                    self.len -= oldest.frame.len();
+                    self.dropped += 1;
+                }
+                None => break,
+            }
+        }
+        self.len += frame.len();
+        self.records.push_back(Record {
+            timestamp_us,
+            direction,
+            frame: frame.to_vec(),
+        });
+    }
+    pub(crate) fn stats(&self) -> PcapStats {
+        PcapStats {
+            frames: self.records.len() as u32,
+            dropped: self.dropped,
+        }
+    }
+    /// The capture as a pcapng file: a section header, the one Ethernet interface, and then
+    /// a packet block for each frame, oldest first
+    pub(crate) fn to_pcapng(&self) -> Vec<u8> {
+        let mut file = Vec::with_capacity(self.len + self.records.len() * 48 + 64);
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes()); // version 1.0
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&u64::MAX.to_le_bytes()); // the section length isn't given
+        block(&mut file, SECTION_HEADER, &body);
+
+        body.clear();
+        body.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&(NET_MTU as u32).to_le_bytes()); // the snap length
+        block(&mut file, INTERFACE_DESCRIPTION, &body);
+
+        for record in self.records.iter() {
+            body.clear();
+            body.extend_from_slice(&0u32.to_le_bytes()); // the interface
+            body.extend_from_slice(&((record.timestamp_us >> 32) as u32).to_le_bytes());
+            body.extend_from_slice(&(record.timestamp_us as u32).to_le_bytes());
+            body.extend_from_slice(&(record.frame.len() as u32).to_le_bytes()); // captured...
+            body.extend_from_slice(&(record.frame.len() as u32).to_le_bytes()); // ...of this many
+            body.extend_from_slice(&record.frame);
+            body.resize((body.len() + 3) & !3, 0);
+            let flags = match record.direction {
+                Direction::Inbound => FLAGS_INBOUND,
+                Direction::Outbound => FLAGS_OUTBOUND,
+            };
+            body.extend_from_slice(&OPT_EPB_FLAGS.to_le_bytes());
+            body.extend_from_slice(&4u16.to_le_bytes());
+            body.extend_from_slice(&flags.to_le_bytes());
+            body.extend_from_slice(&OPT_END.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+            block(&mut file, ENHANCED_PACKET, &body);
+        }
+        file
+    }
+}
+
+/// Writes a stopped capture out to `sink`
+pub(crate) fn write(capture: &Capture, sink: PcapSink) -> Result<(), PcapError> {
+    let file = capture.to_pcapng();
+    match sink {
+        PcapSink::Pddb => {
+            if !pddb::PddbMountPoller::new().is_mounted_nonblocking() {
+                return Err(PcapError::SinkUnavailable);
+            }
+            let pddb = pddb::Pddb::new();
+            pddb.delete_key(PCAP_DICT, PCAP_KEY, None).ok();
+            let mut key = pddb
+                .get(
+                    PCAP_DICT,
+                    PCAP_KEY,
+                    None,
+                    true,
+                    true,
+                    Some(file.len()),
+                    None::<fn()>,
+                )
+                .map_err(|e| {
+                    log::error!("couldn't create {}:{}: {:?}", PCAP_DICT, PCAP_KEY, e);
+                    PcapError::WriteFailed
+                })?;
+            key.write_all(&file).and_then(|_| key.flush()).map_err(|e| {
+                log::error!("couldn't write {}:{}: {:?}", PCAP_DICT, PCAP_KEY, e);
+                PcapError::WriteFailed
+            })?;
+            // the capture is usually wanted after a reboot, so don't leave it in the cache
+            pddb.sync().map_err(|e| {
+                log::error!("couldn't sync {}:{}: {:?}", PCAP_DICT, PCAP_KEY, e);
+                PcapError::WriteFailed
+            })
+        }
+        PcapSink::UsbSerial => {
+            let usb = usb_device_xous::UsbHid::new();
+            if !matches!(
+                usb.get_current_core(),
+                Ok(usb_device_xous::UsbDeviceType::Serial)
+            ) {
+                return Err(PcapError::SinkUnavailable);
+            }
+            usb.serial_send_binary(&file).map_err(|e| {
+                log::error!("couldn't send the capture over USB serial: {:?}", e);
+                PcapError::WriteFailed
+            })
+        }
+    }
+}
+
+/// Appends a pcapng block, whose body has to be padded out to 32 bits already
+fn block(file: &mut Vec<u8>, kind: u32, body: &[u8]) {
+    let len = (12 + body.len()) as u32;
+    file.extend_from_slice(&kind.to_le_bytes());
+    file.extend_from_slice(&len.to_le_bytes());
+    file.extend_from_slice(body);
+    file.extend_from_slice(&len.to_le_bytes());
+}
+
+fn matches(filter: &PcapFilter, frame: &[u8]) -> bool {
+    if filter.protocol.is_none() && filter.port.is_none() {
+        return true;
+    }
+    let (protocol, ports) = classify(frame);
+    let port_matches = match filter.port {
+        Some(port) => matches!(ports, Some((src, dst)) if src == port || dst == port),
+        None => true,
+    };
+    (filter.protocol.is_none() || filter.protocol == protocol) && port_matches
+}
+
+/// Works out which protocol a frame carries, and for TCP and UDP, its source and destination
+/// ports. IPv6 extension headers aren't followed.
+fn classify(frame: &[u8]) -> (Option<PcapProtocol>, Option<(u16, u16)>) {
+    if frame.len() < ETHERNET_HEADER_LEN {
+        return (None, None);
+    }
+    let packet = &frame[ETHERNET_HEADER_LEN..];
+    let (next_header, icmp, transport) = match u16::from_be_bytes([frame[12], frame[13]]) {
+        ETHERTYPE_ARP => return (Some(PcapProtocol::Arp), None),
+        ETHERTYPE_IPV4 if packet.len() >= IPV4_HEADER_LEN => {
+            let header_len = (packet[0] & 0xf) as usize * 4;
+            // only the first fragment has the transport header
+            let first_fragment = u16::from_be_bytes([packet[6], packet[7]]) & 0x1fff == 0;
+            (
+                packet[9],
+                IP_ICMP,
+                if first_fragment {
+                    packet.get(header_len..)
+                } else {
+                    None
+                },
+            )
+        }
+        ETHERTYPE_IPV6 if packet.len() >= IPV6_HEADER_LEN => {
+            (packet[6], IP_ICMPV6, packet.get(IPV6_HEADER_LEN..))
+        }
+        _ => return (None, None),
+    };
+    let ports = transport
+        .filter(|segment| segment.len() >= 4)
+        .map(|segment| {
+            (
+                u16::from_be_bytes([segment[0], segment[1]]),
+                u16::from_be_bytes([segment[2], segment[3]]),
+            )
+        });
+    match next_header {
+        IP_TCP => (Some(PcapProtocol::Tcp), ports),
+        IP_UDP => (Some(PcapProtocol::Udp), ports),
+        _ if next_header == icmp => (Some(PcapProtocol::Icmp), None),
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ipv4(protocol: u8, segment: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; ETHERNET_HEADER_LEN + IPV4_HEADER_LEN];
+        frame[12..14].copy_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        frame[14] = 0x45;
+        frame[23] = protocol;
+        frame.extend_from_slice(segment);
+        frame
+    }
+
+    fn ipv6(next_header: u8, segment: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; ETHERNET_HEADER_LEN + IPV6_HEADER_LEN];
+        frame[12..14].copy_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+        frame[14] = 0x60;
+        frame[20] = next_header;
+        frame.extend_from_slice(segment);
+        frame
+    }
+
+    fn arp() -> Vec<u8> {
+        let mut frame = vec![0u8; ETHERNET_HEADER_LEN + 28];
+        frame[12..14].copy_from_slice(&ETHERTYPE_ARP.to_be_bytes());
+        frame
+    }
+
+    fn word(file: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([file[at], file[at + 1], file[at + 2], file[at + 3]])
+    }
+
+    #[test]
+    fn filters_by_protocol_and_port() {
+        let dns = ipv4(IP_UDP, &[0xc0, 0x00, 0, 53, 0, 8, 0, 0]);
+        let https = ipv6(IP_TCP, &[0x01, 0xbb, 0xc0, 0x01, 0, 0, 0, 0]);
+        let ping = ipv4(IP_ICMP, &[8, 0, 0, 0]);
+        let ping6 = ipv6(IP_ICMPV6, &[128, 0, 0, 0]);
+        let mut fragment = ipv4(IP_UDP, &[0, 53, 0, 53]);
+        fragment[20] = 0x01;
+
+        let filter = |protocol, port| PcapFilter { protocol, port };
+        let kept = |filter: PcapFilter| {
+            [&dns, &https, &ping, &ping6, &fragment, &arp()]
+                .iter()
+                .map(|f| matches(&filter, f))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kept(filter(None, None)), [true; 6]);
+        assert_eq!(
+            kept(filter(Some(PcapProtocol::Udp), None)),
+            [true, false, false, false, true, false]
+        );
+        assert_eq!(
+            kept(filter(Some(PcapProtocol::Icmp), None)),
+            [false, false, true, true, false, false]
+        );
+        assert_eq!(
+            kept(filter(Some(PcapProtocol::Arp), None)),
+            [false, false, false, false, false, true]
+        );
+        assert_eq!(
+            kept(filter(None, Some(53))),
+            [true, false, false, false, false, false]
+        );
+        assert_eq!(
+            kept(filter(None, Some(443))),
+            [false, true, false, false, false, false]
+        );
+        assert_eq!(kept(filter(Some(PcapProtocol::Tcp), Some(53))), [false; 6]);
+    }
+
+    #[test]
+    fn ring_keeps_the_newest_frames() {
+        let mut capture = Capture::new(PcapFilter::default());
+        let frame = vec![0u8; 1000];
+        for t in 0..200 {
+            capture.record(t, Direction::Inbound, &frame);
+        }
+        let stats = capture.stats();
+        assert_eq!(stats.frames as usize, RING_CAPACITY / frame.len());
+        assert_eq!(stats.frames + stats.dropped, 200);
+        assert_eq!(
+            capture.records.front().unwrap().timestamp_us,
+            stats.dropped as u64
+        );
+    }
+
+    #[test]
+    fn writes_pcapng() {
+        let mut capture = Capture::new(PcapFilter::default());
+        capture.record(0x1_0000_0002, Direction::Outbound, &arp());
+        capture.record(3, Direction::Inbound, &[0xaa; 15]);
+        let file = capture.to_pcapng();
+
+        assert_eq!(
+            (word(&file, 0), word(&file, 4), word(&file, 8)),
+            (SECTION_HEADER, 28, BYTE_ORDER_MAGIC)
+        );
+        assert_eq!(word(&file, 24), 28);
+        assert_eq!(
+            (word(&file, 28), word(&file, 32), word(&file, 36)),
+            (INTERFACE_DESCRIPTION, 20, 1)
+        );
+        assert_eq!(word(&file, 40) as usize, NET_MTU);
+
+        // the 42 byte ARP frame is padded to 44 bytes, then come the flags and the end of options
+        let first = 48;
+        assert_eq!(
+            (word(&file, first), word(&file, first + 4)),
+            (ENHANCED_PACKET, 32 + 44 + 12)
+        );
+        assert_eq!((word(&file, first + 12), word(&file, first + 16)), (1, 2));
+        assert_eq!((word(&file, first + 20), word(&file, first + 24)), (42, 42));
+        assert_eq!(
+            (word(&file, first + 72), word(&file, first + 76)),
+            (OPT_EPB_FLAGS as u32 | 4 << 16, FLAGS_OUTBOUND)
+        );
+        assert_eq!(word(&file, first + 84), 32 + 44 + 12);
+
+        let second = first + 88;
+        let mut padded = vec![0xaa; 15];
+        padded.push(0);
+        assert_eq!(word(&file, second + 4), 32 + 16 + 12);
+        assert_eq!(&file[second + 28..second + 44], &padded[..]);
+        assert_eq!(word(&file, second + 48), FLAGS_INBOUND);
+        assert_eq!(file.len(), second + 60);
+    }
+}
@@ -1,5 +1,6 @@
 use smoltcp::wire::IpAddress;
 use crate::*;
+use crate::device::NetPhy;
 
 
 pub(crate) fn parse_address(data: &[u8]) -> Option<smoltcp::wire::IpAddress> {
@@ -38,7 +39,7 @@ pub(crate) fn write_address(address: IpAddress, data: &mut [u8]) -> Option<usize
             for (dest, src) in i.zip(a.as_bytes().iter()) {
                 *dest = *src;
             }
-            Some(16)
+            Some(17)
         }
         _ => {
             *i.next()? = 0;
@@ -47,6 +48,17 @@ pub(crate) fn write_address(address: IpAddress, data: &mut [u8]) -> Option<usize
     }
 }
 
+/// The address of ours that packets to `remote` go out from: the DHCP address for IPv4, and
+/// whichever of the loopback, link-local or autoconfigured addresses suits `remote` for IPv6.
+pub(crate) fn source_address(iface: &Interface::<NetPhy>, remote: IpAddress) -> Option<IpAddress> {
+    match remote {
+        IpAddress::Ipv6(remote) => {
+            crate::ipv6::source_for(iface.ip_addrs(), remote).map(IpAddress::Ipv6)
+        }
+        _ => iface.ipv4_addr().map(IpAddress::Ipv4),
+    }
+}
+
 pub(crate) fn respond_with_error(mut env: xous::MessageEnvelope, code: NetError) -> Option<()> {
     // If it's not a memory message, don't fill in the return information.
     let body = match env.body.memory_message_mut() {
@@ -33,9 +33,13 @@ pub(crate) fn std_tcp_listen(
             return;
         }
     };
-    if address.as_bytes() != [0, 0, 0, 0]
-    && address.as_bytes() != [127, 0, 0, 1]
-    && address.as_bytes() != IPV4_ADDRESS.load(Ordering::SeqCst).to_be_bytes() {
+    let ours = match address {
+        IpAddress::Ipv6(v6) => v6.is_unspecified() || iface.has_ip_addr(address),
+        _ => address.as_bytes() == [0, 0, 0, 0]
+            || address.as_bytes() == [127, 0, 0, 1]
+            || address.as_bytes() == IPV4_ADDRESS.load(Ordering::SeqCst).to_be_bytes(),
+    };
+    if !ours {
         std_failure(msg, NetError::Invalid);
         return;
     }
@@ -109,24 +109,29 @@ pub(crate) fn std_udp_rx(
     };
     let do_peek = body.offset.is_some();
     log::debug!("udp rx from fd {}", connection_handle_index);
-    let local_addr = match iface.ipv4_addr() {
-        Some(addr) => addr,
-        None => {
-            std_failure(msg, NetError::Unaddressable);
-            return;
-        }
+    let bound_addr = iface.get_socket::<UdpSocket>(*handle).endpoint().addr;
+    let local_addr = match bound_addr {
+        // IPv6 sockets keep whatever address they were bound to, or last sent from
+        IpAddress::Ipv6(_) => bound_addr,
+        _ => match iface.ipv4_addr() {
+            Some(addr) => IpAddress::Ipv4(addr),
+            None => {
+                std_failure(msg, NetError::Unaddressable);
+                return;
+            }
+        },
     };
     let socket = iface.get_socket::<UdpSocket>(*handle);
     let port = socket.endpoint().port;
-    // force the local address to correspond to our (one and only) IP address
+    // force the local address of IPv4 sockets to correspond to our (one and only) IPv4 address
     // the underlying smoltcp library can't handle unspecified source addresses
     // because the library itself works with multiple interfaces and has no default resolution mechanism
     // this may eventually get fixed see https://github.com/smoltcp-rs/smoltcp/issues/599
-    if socket.endpoint().addr != IpAddress::Ipv4(local_addr) {
+    if socket.endpoint().addr != local_addr {
         if socket.is_open() {
             socket.close();
         }
-        if let Err(e) = socket.bind(IpEndpoint{addr: IpAddress::Ipv4(local_addr), port})
+        if let Err(e) = socket.bind(IpEndpoint{addr: local_addr, port})
         .map_err(|e| match e {
             smoltcp::Error::Illegal => NetError::SocketInUse,
             smoltcp::Error::Unaddressable => NetError::Unaddressable,
@@ -219,7 +224,7 @@ pub(crate) fn std_udp_tx(
     let len = u16::from_le_bytes([bytes[19], bytes[20]]);
     // attempt the tx
     log::debug!("udp tx to fd {} -> {:?}:{} {:?}", connection_handle_index, address, remote_port, &bytes[21..21 + len as usize]);
-    let local_addr = match iface.ipv4_addr() {
+    let local_addr = match source_address(iface, address) {
         Some(addr) => addr,
         None => {
             std_failure(msg, NetError::Unaddressable);
@@ -228,15 +233,16 @@ pub(crate) fn std_udp_tx(
     };
     let socket = iface.get_socket::<UdpSocket>(*handle);
     let port = socket.endpoint().port;
-    // force the local address to correspond to our (one and only) IP address
+    // force the local address to correspond to our IP address for the destination: the one and only
+    // IPv4 address, or the IPv6 address in the destination's scope
     // the underlying smoltcp library can't handle unspecified source addresses
     // because the library itself works with multiple interfaces and has no default resolution mechanism
     // this may eventually get fixed see https://github.com/smoltcp-rs/smoltcp/issues/599
-    if socket.endpoint().addr != IpAddress::Ipv4(local_addr) {
+    if socket.endpoint().addr != local_addr {
         if socket.is_open() {
             socket.close();
         }
-        if let Err(e) = socket.bind(IpEndpoint{addr: IpAddress::Ipv4(local_addr), port})
+        if let Err(e) = socket.bind(IpEndpoint{addr: local_addr, port})
         .map_err(|e| match e {
             smoltcp::Error::Illegal => NetError::SocketInUse,
             smoltcp::Error::Unaddressable => NetError::Unaddressable,
@@ -28,6 +28,11 @@ fn each_ip(f: &mut dyn FnMut(SocketAddr)) {
     f(next_test_ip6());
 }
 
+fn each_ip_pair(f: &mut dyn FnMut(SocketAddr, SocketAddr)) {
+    f(next_test_ip4(), next_test_ip4());
+    f(next_test_ip6(), next_test_ip6());
+}
+
 macro_rules! t {
     ($e:expr) => {
         match $e {
@@ -45,6 +50,14 @@ fn bind_error() {
     }
 }
 
+#[test]
+fn bind_error_v6() {
+    match TcpListener::bind("[2001:db8::1]:9999") {
+        Ok(..) => panic!(),
+        Err(e) => assert_eq!(e.kind(), ErrorKind::AddrNotAvailable),
+    }
+}
+
 #[test]
 fn connect_error() {
     match TcpStream::connect("0.0.0.0:1") {
@@ -827,3 +840,63 @@ fn connect_timeout_valid() {
     let addr = listener.local_addr().unwrap();
     TcpStream::connect_timeout(&addr, Duration::from_secs(2)).unwrap();
 }
+
+#[test]
+fn udp_smoke_test() {
+    each_ip_pair(&mut |server_ip, client_ip| {
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+
+        let _t = thread::spawn(move || {
+            let client = t!(UdpSocket::bind(&client_ip));
+            rx1.recv().unwrap();
+            t!(client.send_to(&[99], &server_ip));
+            tx2.send(()).unwrap();
+        });
+
+        let server = t!(UdpSocket::bind(&server_ip));
+        tx1.send(()).unwrap();
+        let mut buf = [0];
+        let (nread, src) = t!(server.recv_from(&mut buf));
+        assert_eq!(nread, 1);
+        assert_eq!(buf[0], 99);
+        assert_eq!(src, client_ip);
+        rx2.recv().unwrap();
+    })
+}
+
+#[test]
+fn udp_connect_send_recv() {
+    each_ip(&mut |addr| {
+        let socket = t!(UdpSocket::bind(&addr));
+        t!(socket.connect(addr));
+
+        t!(socket.send(b"hello world"));
+
+        let mut buf = [0; 11];
+        t!(socket.recv(&mut buf));
+        assert_eq!(b"hello world", &buf[..]);
+    })
+}
+
+#[test]
+fn udp_unspecified_v6() {
+    // a socket bound to [::] answers from ::1 when it talks to ::1
+    let server_ip = next_test_ip6();
+    let server = t!(UdpSocket::bind(&server_ip));
+    let client = t!(UdpSocket::bind(&SocketAddr::new(
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        next_test_ip6().port()
+    )));
+    t!(client.send_to(&[6], &server_ip));
+
+    let mut buf = [0];
+    let (nread, src) = t!(server.recv_from(&mut buf));
+    assert_eq!((nread, buf[0]), (1, 6));
+    assert_eq!(src.ip(), IpAddr::V6(Ipv6Addr::LOCALHOST));
+
+    t!(server.send_to(&[7], &src));
+    let (nread, src) = t!(client.recv_from(&mut buf));
+    assert_eq!((nread, buf[0]), (1, 7));
+    assert_eq!(src, server_ip);
+}
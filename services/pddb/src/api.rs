@@ -1,7 +1,10 @@
 mod rkyv_enum;
 pub use rkyv_enum::*;
+mod txn;
+pub(crate) use txn::*;
+mod format;
+pub use format::*;
 
-use bitfield::bitfield;
 use std::num::NonZeroU32;
 use core::ops::{Deref, DerefMut};
 
@@ -22,32 +25,20 @@ pub(crate) const SERVER_NAME_PDDB_POLLER: &str     = "_PDDB Mount Poller_";
 /// depend upon this constant.
 pub const TIME_SERVER_PDDB: &'static str = "_dedicated pddb timeserver connection_";
 
-#[allow(dead_code)]
-pub(crate) const BASIS_NAME_LEN: usize = 64; // don't want this too long anyways, because it's not recorded anywhere - users have to type it in.
-#[allow(dead_code)]
-pub(crate) const DICT_NAME_LEN: usize = 127 - 4 - 4 - 4 - 4; // u32: flags, age, free index, numkeys = 111
-#[allow(dead_code)]
-pub(crate) const KEY_NAME_LEN: usize = 127 - 8 - 8 - 8 - 4 - 4; // u64: vaddr/len/resvd, u32: flags, age = 95
 #[allow(dead_code)]
 pub(crate) const PASSWORD_LEN: usize = 72; // this is actually set by bcrypt
 #[allow(dead_code)]
-pub(crate) const PDDB_MAGIC: [u8; 4] = [0x50, 0x44, 0x44, 0x42];
-/// migrateable version pairs
-/// PDDB_MIGRATE_1:
-///   00.00.01.01 - xous 0.9.7 release (original base release)
-///   00.00.02.01 - xous 0.9.8 migration -> hkdf added on basis key derivation to make separate PT/data keys
-#[allow(dead_code)]
-pub(crate) const PDDB_MIGRATE_1: (u32, u32) = (0x00_00_01_01, 0x00_00_02_01);
-#[allow(dead_code)]
-pub(crate) const PDDB_VERSION: u32 = 0x00_00_02_01;
-#[allow(dead_code)]
 // PDDB_A_LEN may be shorter than xous::PDDB_LEN, to speed up testing.
 #[allow(dead_code)]
 #[cfg(not(any(feature="pddbtest",feature="autobasis",feature="ci",feature="smalldb")))]
 pub(crate) const PDDB_A_LEN: usize = xous::PDDB_LEN as usize;
 #[allow(dead_code)]
-#[cfg(any(feature="pddbtest",feature="autobasis",feature="ci",feature="smalldb"))]
+#[cfg(all(any(feature="pddbtest",feature="autobasis",feature="smalldb"),not(feature="ci")))]
 pub const PDDB_A_LEN: usize = 4 * 1024 * 1024;
+// the CI suite leaves too little free space in 4MiB for the tests at its end
+#[allow(dead_code)]
+#[cfg(feature="ci")]
+pub const PDDB_A_LEN: usize = 8 * 1024 * 1024;
 
 /// range for the starting point of a journal number, picked from a random seed
 /// the goal is to reduce info leakage about the age of structures relative to each other
@@ -100,12 +91,6 @@ pub(crate) const FSCB_FILL_UNCERTAINTY: f32 = 0.1;
 #[allow(dead_code)]
 pub(crate) const FAST_REKEY_CHANCE: u32 = 26;
 
-#[allow(dead_code)]
-pub const PDDB_DEFAULT_SYSTEM_BASIS: &'static str = ".System";
-// this isn't an "official" basis, but it is used for the AAD for encrypting the FastSpace structure
-#[allow(dead_code)]
-pub(crate) const PDDB_FAST_SPACE_SYSTEM_BASIS: &'static str = ".FastSpace";
-
 #[allow(dead_code)]
 // TODO: add hardware acceleration for BCRYPT so we can hit the OWASP target without excessive UX delay
 pub(crate) const BCRYPT_COST: u32 = 7;   // 10 is the minimum recommended by OWASP; takes 5696 ms to verify @ 10 rounds; 804 ms to verify 7 rounds
@@ -160,8 +145,6 @@ pub(crate) enum Opcode {
     /// Write debug dump (only available in hosted mode)
     #[cfg(not(target_os = "xous"))]
     DangerousDebug = 25,
-    #[cfg(all(feature="pddbtest", feature="autobasis"))]
-    BasisTesting = 26,
 
     ListBasisStd = 26,
     CreateBasisStd = 27,
@@ -248,6 +231,26 @@ pub(crate) enum Opcode {
     /// Prune the cache. Used mainly for diagnostics.
     Prune = 56,
 
+    /// Compact the dictionary index of all open Basis.
+    DictCompact = 57,
+
+    /// Delete a key, and immediately overwrite its data on disk.
+    DeleteKeyParanoid = 58,
+
+    /// Subscribe to changes of a dictionary, or of a single key within it
+    WatchRequest = 59,
+    /// libstd equivalent of `WatchRequest`
+    WatchRequestStd = 60,
+    /// Cancel a subscription made with either `WatchRequest` or `WatchRequestStd`
+    WatchDrop = 61,
+
+    /// Atomically apply a set of key writes and deletes within one basis
+    TxnCommit = 62,
+
+    /// Exercise basis handling (test builds only)
+    #[cfg(all(feature="pddbtest", feature="autobasis"))]
+    BasisTesting = 63,
+
     /// This key type could not be decoded
     InvalidOpcode = u32::MAX as _,
 }
@@ -332,6 +335,54 @@ pub struct PddbKeyRequest {
     pub result: PddbRequestCode,
 }
 
+/// A structure for subscribing to changes of a dictionary, or of one key within it
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PddbWatchRequest {
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String::<DICT_NAME_LEN>,
+    /// if `None`, every key in the dictionary is watched
+    pub key: Option<xous_ipc::String::<KEY_NAME_LEN>>,
+    /// token chosen by the caller, so its callback can be registered before any event can arrive
+    pub token: ApiToken,
+    pub cb_sid: [u32; 4],
+    pub result: PddbRequestCode,
+}
+
+/// What happened to a watched dictionary or key
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WatchEventKind {
+    KeyCreated = 0,
+    KeyUpdated = 1,
+    KeyDeleted = 2,
+    /// The whole dictionary was removed from the basis
+    DictDeleted = 3,
+    /// A basis containing the watched dictionary (or key) was unlocked, so its contents may have changed
+    BasisUnlocked = 4,
+    /// A basis containing the watched dictionary (or key) was locked, so its contents may have changed
+    BasisLocked = 5,
+}
+
+/// Sent by the server to a watcher's callback server with `CbOp::Watch`
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct PddbWatchEvent {
+    pub token: ApiToken,
+    pub kind: WatchEventKind,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String::<DICT_NAME_LEN>,
+    /// empty for basis events on a dictionary-wide watch, and for `DictDeleted`
+    pub key: xous_ipc::String::<KEY_NAME_LEN>,
+}
+
+/// A change to a watched dictionary or key, as delivered to a `Pddb::watch()` callback
+#[derive(Debug, Clone)]
+pub struct WatchEvent {
+    pub kind: WatchEventKind,
+    pub basis: String,
+    pub dict: String,
+    pub key: Option<String>,
+}
+
 pub(crate) const MAX_PDDBKLISTLEN: usize = 4064;
 /// A structure for requesting a token to access a particular key/value pair
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -353,6 +404,16 @@ pub (crate) struct PddbDeleteList {
     pub retcode: PddbRetcode,
 }
 
+/// A structure for committing a transaction. `data` holds the ops, packed as described in `api/txn.rs`.
+pub(crate) const MAX_PDDB_TXN_LEN: usize = 3800;
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub (crate) struct PddbTxnList {
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String::<BASIS_NAME_LEN>,
+    pub data: [u8; MAX_PDDB_TXN_LEN],
+    pub retcode: PddbRetcode,
+}
+
 /// Return codes for Read/Write API calls to the main server
 #[repr(u8)]
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Eq, PartialEq)]
@@ -462,16 +523,6 @@ impl PddbBuf {
     }
 }
 
-bitfield! {
-    #[derive(Copy, Clone, PartialEq, Eq)]
-    pub struct KeyFlags(u32);
-    impl Debug;
-    /// set if the entry is valid -- in the cache, an invalid entry means it was previously allocated but then deleted, and needs a sync
-    pub valid, set_valid: 0;
-    /// resolved indicates that the "start" address isn't fully resolved yet in the cache
-    pub unresolved, set_unresolved: 1;
-}
-
 /// A structure for passing around key metadata
 #[derive(Debug)]
 pub struct KeyAttributes {
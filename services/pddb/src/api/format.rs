@@ -0,0 +1,38 @@
+// Identifiers and name limits baked into the on-disk format. These are shared by the `lib` and
+// `bin` views of the API, and are also pulled into `tools/src/pddb.rs` with `#[path]`, so this
+// file must not depend on anything else in the crate.
+
+use bitfield::bitfield;
+
+#[allow(dead_code)]
+pub(crate) const BASIS_NAME_LEN: usize = 64; // don't want this too long anyways, because it's not recorded anywhere - users have to type it in.
+#[allow(dead_code)]
+pub(crate) const DICT_NAME_LEN: usize = 127 - 4 - 4 - 4 - 4; // u32: flags, age, free index, numkeys = 111
+#[allow(dead_code)]
+pub(crate) const KEY_NAME_LEN: usize = 127 - 8 - 8 - 8 - 4 - 4; // u64: vaddr/len/resvd, u32: flags, age = 95
+#[allow(dead_code)]
+pub(crate) const PDDB_MAGIC: [u8; 4] = [0x50, 0x44, 0x44, 0x42];
+/// migrateable version pairs
+/// PDDB_MIGRATE_1:
+///   00.00.01.01 - xous 0.9.7 release (original base release)
+///   00.00.02.01 - xous 0.9.8 migration -> hkdf added on basis key derivation to make separate PT/data keys
+#[allow(dead_code)]
+pub(crate) const PDDB_MIGRATE_1: (u32, u32) = (0x00_00_01_01, 0x00_00_02_01);
+#[allow(dead_code)]
+pub(crate) const PDDB_VERSION: u32 = 0x00_00_02_01;
+
+#[allow(dead_code)]
+pub const PDDB_DEFAULT_SYSTEM_BASIS: &'static str = ".System";
+// this isn't an "official" basis, but it is used for the AAD for encrypting the FastSpace structure
+#[allow(dead_code)]
+pub(crate) const PDDB_FAST_SPACE_SYSTEM_BASIS: &'static str = ".FastSpace";
+
+bitfield! {
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub struct KeyFlags(u32);
+    impl Debug;
+    /// set if the entry is valid -- in the cache, an invalid entry means it was previously allocated but then deleted, and needs a sync
+    pub valid, set_valid: 0;
+    /// resolved indicates that the "start" address isn't fully resolved yet in the cache
+    pub unresolved, set_unresolved: 1;
+}
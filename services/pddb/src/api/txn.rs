@@ -0,0 +1,149 @@
+// Encoding of transaction operations. The same packed format is used by the client to send a
+// `TxnCommit` request, and by the server to record the commit journal on disk, so it is shared
+// between the `lib` and `bin` views of the API.
+//
+// Each operation is packed as:
+//   u8 tag | u8 dict len | dict | u8 key len | key | (writes only) u32 data len, LE | data
+// `RemoveDict` carries no key. A tag of 0, or the end of the buffer, terminates the list.
+
+#![allow(dead_code)]
+use std::convert::TryInto;
+
+const TAG_END: u8 = 0;
+const TAG_WRITE: u8 = 1;
+const TAG_DELETE: u8 = 2;
+const TAG_REMOVE_DICT: u8 = 3;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum TxnOp {
+    /// Replaces the entire contents of a key, creating the key (and its dictionary) if needed.
+    Write { dict: String, key: String, data: Vec<u8> },
+    /// Removes a key. Removing a key that does not exist is not an error.
+    Delete { dict: String, key: String },
+    /// Removes a dictionary. Only generated by the server, to undo the creation of a dictionary.
+    RemoveDict { dict: String },
+}
+
+impl TxnOp {
+    pub(crate) fn dict(&self) -> &str {
+        match self {
+            TxnOp::Write { dict, .. } | TxnOp::Delete { dict, .. } | TxnOp::RemoveDict { dict } => dict,
+        }
+    }
+    pub(crate) fn key(&self) -> Option<&str> {
+        match self {
+            TxnOp::Write { key, .. } | TxnOp::Delete { key, .. } => Some(key),
+            TxnOp::RemoveDict { .. } => None,
+        }
+    }
+    /// Number of bytes this op occupies once encoded.
+    pub(crate) fn encoded_len(&self) -> usize {
+        let mut len = 2 + self.dict().len();
+        if let Some(key) = self.key() {
+            len += 1 + key.len();
+        }
+        if let TxnOp::Write { data, .. } = self {
+            len += 4 + data.len();
+        }
+        len
+    }
+    /// Appends the encoded op to `out`. Names must already be checked against the PDDB limits,
+    /// which are all shorter than a u8.
+    pub(crate) fn encode(&self, out: &mut Vec<u8>) {
+        out.push(match self {
+            TxnOp::Write { .. } => TAG_WRITE,
+            TxnOp::Delete { .. } => TAG_DELETE,
+            TxnOp::RemoveDict { .. } => TAG_REMOVE_DICT,
+        });
+        out.push(self.dict().len() as u8);
+        out.extend_from_slice(self.dict().as_bytes());
+        if let Some(key) = self.key() {
+            out.push(key.len() as u8);
+            out.extend_from_slice(key.as_bytes());
+        }
+        if let TxnOp::Write { data, .. } = self {
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(data);
+        }
+    }
+}
+
+pub(crate) fn txn_encode(ops: &[TxnOp]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(ops.iter().map(|op| op.encoded_len()).sum());
+    for op in ops.iter() {
+        op.encode(&mut out);
+    }
+    out
+}
+
+/// Decodes a packed list of ops. Returns `None` if the data is malformed in any way.
+pub(crate) fn txn_decode(data: &[u8]) -> Option<Vec<TxnOp>> {
+    fn take<'a>(data: &'a [u8], index: &mut usize, len: usize) -> Option<&'a [u8]> {
+        let slice = data.get(*index..index.checked_add(len)?)?;
+        *index += len;
+        Some(slice)
+    }
+    fn take_str(data: &[u8], index: &mut usize) -> Option<String> {
+        let len = *take(data, index, 1)?.first()? as usize;
+        std::str::from_utf8(take(data, index, len)?).ok().map(String::from)
+    }
+    let mut ops = Vec::new();
+    let mut index = 0;
+    while index < data.len() {
+        let tag = data[index];
+        index += 1;
+        match tag {
+            TAG_END => break,
+            TAG_WRITE => {
+                let dict = take_str(data, &mut index)?;
+                let key = take_str(data, &mut index)?;
+                let len = u32::from_le_bytes(take(data, &mut index, 4)?.try_into().unwrap()) as usize;
+                let data = take(data, &mut index, len)?.to_vec();
+                ops.push(TxnOp::Write { dict, key, data });
+            }
+            TAG_DELETE => {
+                let dict = take_str(data, &mut index)?;
+                let key = take_str(data, &mut index)?;
+                ops.push(TxnOp::Delete { dict, key });
+            }
+            TAG_REMOVE_DICT => {
+                let dict = take_str(data, &mut index)?;
+                ops.push(TxnOp::RemoveDict { dict });
+            }
+            _ => return None,
+        }
+    }
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let ops = vec![
+            TxnOp::Write { dict: "vault.passwords".into(), key: "example.com".into(), data: vec![1, 2, 3] },
+            TxnOp::Write { dict: "vault.index".into(), key: "e".into(), data: vec![] },
+            TxnOp::Delete { dict: "vault.passwords".into(), key: "old.example.com".into() },
+            TxnOp::RemoveDict { dict: "vault.index".into() },
+        ];
+        let encoded = txn_encode(&ops);
+        assert_eq!(encoded.len(), ops.iter().map(|op| op.encoded_len()).sum::<usize>());
+        assert_eq!(txn_decode(&encoded), Some(ops.clone()));
+        // trailing padding, as found in a fixed-size IPC buffer, is ignored
+        let mut padded = encoded.clone();
+        padded.resize(encoded.len() + 16, 0);
+        assert_eq!(txn_decode(&padded), Some(ops));
+    }
+
+    #[test]
+    fn truncated() {
+        let ops = vec![TxnOp::Write { dict: "d".into(), key: "k".into(), data: vec![0xAA; 40] }];
+        let encoded = txn_encode(&ops);
+        for len in 1..encoded.len() {
+            assert_eq!(txn_decode(&encoded[..len]), None, "accepted a truncation to {} bytes", len);
+        }
+        assert_eq!(txn_decode(&[9, 1, b'd']), None);
+    }
+}
@@ -1,3 +1,5 @@
+mod layout;
+pub use layout::*;
 mod basis;
 pub use basis::*;
 mod dictionary;
@@ -12,8 +14,11 @@ mod types;
 pub use types::*;
 mod bcrypt;
 pub use bcrypt::*;
+mod txn;
+pub use txn::*;
 
 // local to the backend
+use crate::api::{BASIS_NAME_LEN, DICT_NAME_LEN, KEY_NAME_LEN, PDDB_VERSION, KeyFlags};
 mod murmur3;
 pub(crate) use murmur3::*;
 mod trngpool;
@@ -149,16 +149,11 @@ use std::cmp::Reverse;
 use std::cmp::Ordering;
 use core::num::NonZeroU32;
 
-pub(crate) const SMALL_POOL_START: u64 = 0x0000_003F_8000_0000;
-pub(crate) const SMALL_POOL_END: u64 = 0x0000_007E_FF02_0000;
-pub(crate) const SMALL_POOL_STRIDE: u64 = 0xFE_0000;
 /// we don't want this bigger than VPAGE_SIZE, because a key goal of the small pool is to
 /// reduce # of writes to the disk of small data. While we could get some gain in memory efficiency
 /// if we made this larger than a VPAGE_SIZE, we don't get much gain in terms of write reduction,
 /// and it greatly complicates the implementation. So, SMALL_CAPACITY should be less than VPAGE_SIZE.
 pub(crate) const SMALL_CAPACITY: usize = VPAGE_SIZE;
-pub(crate) const LARGE_POOL_START: u64 = 0x0000_FE00_0000_0000;
-pub(crate) const KEY_MAXCOUNT: usize = 131_071; // 2^17 - 1
 /// This is a size limit on the biggest file you can create. It's currently 32GiB. No, this is not
 /// web scale, but it's big enough to hold a typical blu-ray movie as a single file. You can adjust
 /// this constant up or down, and the trade-off is, you get more or less total number of large files
@@ -188,62 +183,19 @@ pub(crate) const KEY_MAXCOUNT: usize = 131_071; // 2^17 - 1
 /// because the usize type isn't big enough. Recompiling for a 64-bit target, however, should give
 /// you access to the 32GiB file size limit.
 pub(crate) const LARGE_FILE_MAX_SIZE: u64 = 0x0000_0008_0000_0000;
+/// Number of decrypted pages of a large key that are read in when a page misses the cache: the page
+/// itself, plus read-ahead. Large keys are never held in RAM in their entirety. Writes go through to
+/// disk, so cached pages are always clean and can be dropped at any time by `cache_prune()`.
+pub(crate) const LARGE_CACHE_PAGES: usize = 4;
+/// Upper bound in bytes on the decrypted large-pool pages cached across all keys and bases. Pages
+/// beyond this are evicted least recently used first, by `BasisCache::large_cache_trim()`.
+pub(crate) const LARGE_CACHE_SIZE: usize = 64 * 1024;
 
-/// The chosen "stride" of a dict/key entry. Drives a lot of key parameters in the database's characteristics.
-/// This is chosen such that 32 of these entries fit evenly into a VPAGE.
-pub(crate) const DK_STRIDE: usize = 127;
-//// DK_STRIDES per VPAGE
-pub(crate) const DK_PER_VPAGE: usize = VPAGE_SIZE / DK_STRIDE; // should be 32 - use this for computing modulus on dictionary indices
-/// size of a dictionary region in virtual memory
-pub(crate) const DICT_VSIZE: u64 = 0xFE_0000;
-/// maximum number of dictionaries in a system
-pub(crate) const DICT_MAXCOUNT: usize = 16383;
 /// default alloc hint, if none is given (needs to be non-zero)
 /// this would be the typical "minimum space" reserved for a key
 /// users are of course allowed to specify something smaller, but it should be non-zero
 pub(crate) const DEFAULT_ALLOC_HINT: usize = 8;
 
-/// This is the format of the Basis as stored on disk
-#[derive(PartialEq, Debug, Default)]
-#[repr(C, align(8))]
-pub(crate) struct BasisRoot {
-    pub(crate) magic: [u8; 4],
-    pub(crate) version: u32,
-    /// increments every time the BasisRoot is modified. This field must saturate, not roll over.
-    pub(crate) age: u32,
-    /// number of dictionaries.
-    pub(crate) num_dictionaries: u32,
-    /* at this point, we are aligned to a 64-bit boundary. All data must stay aligned to this boundary from here out! */
-    /// 64-byte name; aligns to 64-bits
-    pub(crate) name: BasisRootName,
-}
-impl BasisRoot {
-    pub(crate) fn aad(&self, dna: u64) -> Vec::<u8> {
-        let mut aad = Vec::<u8>::new();
-        aad.extend_from_slice(&self.name.data[..self.name.len as usize]);
-        aad.extend_from_slice(&PDDB_VERSION.to_le_bytes());
-        aad.extend_from_slice(&dna.to_le_bytes());
-        aad
-    }
-}
-impl Deref for BasisRoot {
-    type Target = [u8];
-    fn deref(&self) -> &[u8] {
-        unsafe {
-            core::slice::from_raw_parts(self as *const BasisRoot as *const u8, core::mem::size_of::<BasisRoot>())
-                as &[u8]
-        }
-    }
-}
-impl DerefMut for BasisRoot {
-    fn deref_mut(&mut self) -> &mut [u8] {
-        unsafe {
-            core::slice::from_raw_parts_mut(self as *mut BasisRoot as *mut u8, core::mem::size_of::<BasisRoot>())
-                as &mut [u8]
-        }
-    }
-}
-
 /// A list of open Basis that we can use to search and operate upon. Sort of the "root" data structure of the PDDB.
 ///
 /// Note to self: it's tempting to integrate the "hw" parameter (the pointer to the PddbOs structure). However, this
@@ -443,6 +395,40 @@ impl BasisCache {
         }
     }
 
+    /// Compacts the dictionary index of a basis, so that its dictionaries occupy the lowest slots
+    /// without gaps. If `basis_name` is None, every open basis is compacted. Returns the total
+    /// number of dictionaries that were relocated.
+    pub(crate) fn dict_compact(&mut self, hw: &mut PddbOs, basis_name: Option<&str>) -> Result<usize> {
+        let targets: Vec<usize> = if basis_name.is_some() {
+            if let Some(basis_index) = self.select_basis(basis_name) {
+                vec![basis_index]
+            } else {
+                return Err(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))
+            }
+        } else {
+            (0..self.cache.len()).collect()
+        };
+        let mut relocated = 0;
+        for basis_index in targets {
+            let plan = self.cache[basis_index].dict_compact_plan(hw)?;
+            log::info!("compacting {}: {} dicts to relocate", self.cache[basis_index].name, plan.len());
+            for (name, target, pages) in plan {
+                if !hw.ensure_fast_space_alloc(pages, &self.cache) {
+                    return Err(Error::new(ErrorKind::OutOfMemory, "No free space to relocate dict"));
+                }
+                self.cache[basis_index].dict_relocate(hw, &name, target)?;
+                relocated += 1;
+            }
+            let basis = &mut self.cache[basis_index];
+            if (basis.num_dicts as usize) < DICT_MAXCOUNT {
+                basis.free_dict_offset = Some(basis.num_dicts + 1);
+            }
+        }
+        // physical pages were recycled, so the plaintext cache may refer to stale data
+        self.data_cache = PlaintextCache { data: None, tag: None };
+        Ok(relocated)
+    }
+
     pub(crate) fn key_read(&mut self, hw: &mut PddbOs, dict: &str, key: &str, data: &mut [u8],
         offset: Option<usize>, basis_name:Option<&str>
     ) -> Result<usize> {
@@ -499,22 +485,26 @@ impl BasisCache {
                             let mut abs_cursor = offset.unwrap_or(0) as u64;
                             let mut blocks_read = 0;
                             let mut bytes_read = 0;
+                            // copied out so the page borrowed from the key's read-ahead window below doesn't conflict
+                            let key_len = kcache.len;
                             loop {
-                                let start_vpage_addr = ((kcache.start + abs_cursor) / VPAGE_SIZE as u64) * VPAGE_SIZE as u64;
+                                let page_offset = (abs_cursor / VPAGE_SIZE as u64) * VPAGE_SIZE as u64;
 
-                                if let Some(pp) = basis.v2p_map.get(&VirtAddr::new(start_vpage_addr).unwrap()) {
+                                if basis.v2p_map.contains_key(&VirtAddr::new(kcache.start + page_offset).unwrap()) {
                                     let block_start_pos = (abs_cursor % VPAGE_SIZE as u64) as usize;
-                                    assert!(pp.valid(), "v2p returned an invalid page");
-                                    let pt_data = hw.data_decrypt_page(&basis.cipher, &basis.aad, pp).expect("Decryption auth error");
+                                    // a page that doesn't decrypt was never written, e.g. if power was lost partway through
+                                    // an update; report it rather than taking down the whole PDDB
+                                    let pt_data = kcache.large_page(hw, &basis.v2p_map, &basis.cipher, &basis.aad, page_offset)
+                                        .ok_or(Error::new(ErrorKind::InvalidData, "Decryption auth error"))?;
                                     if blocks_read != 0 {
                                         assert!(block_start_pos == 0, "algorithm error in handling offset data");
                                     }
                                     if blocks_read == 0 {
                                         log::debug!("reading {} abs: {}, block_start: {}, block: {}, data.len:{} kcache.len:{}",
-                                            key, abs_cursor, block_start_pos, blocks_read, data.len(), kcache.len);
+                                            key, abs_cursor, block_start_pos, blocks_read, data.len(), key_len);
                                     } else {
                                         log::debug!("  reading {} abs: {}, block_start: {}, block: {}, remaining.data:{} kcache.len:{}",
-                                            key, abs_cursor, block_start_pos, blocks_read, data.len() - bytes_read, kcache.len);
+                                            key, abs_cursor, block_start_pos, blocks_read, data.len() - bytes_read, key_len);
                                     }
                                     let data_offset = bytes_read;
                                     for (&src, dst) in
@@ -527,7 +517,7 @@ impl BasisCache {
                                         // it'd be computationally more efficient to figure out what this should be going into
                                         // every copy loop, but it's logically easier to think about in this form. Without this
                                         // check, a user could read past the allocated space for a block...
-                                        if abs_cursor >= kcache.len {
+                                        if abs_cursor >= key_len {
                                             break;
                                         }
                                         abs_cursor += 1;
@@ -536,15 +526,17 @@ impl BasisCache {
                                     blocks_read += 1;
                                 } else {
                                     log::warn!("Not enough bytes available to read for key {}:{} ({}/{})", dict, key, abs_cursor, data.len());
+                                    self.large_cache_trim();
                                     return Ok(bytes_read as usize)
                                 }
-                                if abs_cursor >= kcache.len {
+                                if abs_cursor >= key_len {
                                     break;
                                 }
                                 if bytes_read >= data.len() {
                                     break;
                                 }
                             }
+                            self.large_cache_trim();
                             return Ok(bytes_read as usize)
                         }
                         // note that from this point forward, either the "if" or the "else" branch returns;
@@ -573,11 +565,15 @@ impl BasisCache {
                 basis.age = basis.age.saturating_add(1);
                 basis.clean = false;
                 if dict_entry.ensure_key_entry(hw, &mut basis.v2p_map, &basis.cipher, key) {
-                    if !paranoid {
-                        dict_entry.key_remove(hw, &mut basis.v2p_map, &basis.cipher, key, false);
-                    } else {
-                        // this implementation is still in progress
-                        dict_entry.key_erase(key);
+                    dict_entry.key_remove(hw, &mut basis.v2p_map, &basis.cipher, key, paranoid);
+                    if paranoid {
+                        // the plaintext cache may be holding the decrypted page the key used to live in
+                        if let Some(data) = self.data_cache.data.as_mut() {
+                            for b in data.iter_mut() {
+                                *b = 0;
+                            }
+                        }
+                        self.data_cache = PlaintextCache { data: None, tag: None };
                     }
                     assert!(dict_entry.clean == false, "dictionary entry should have been marked unclean");
 
@@ -780,6 +776,7 @@ impl BasisCache {
             } else {
                 return Err(Error::new(ErrorKind::NotFound, "Requested dictionary not found, or could not be allocated."));
             }
+            self.large_cache_trim();
 
             Ok(())
         } else {
@@ -1086,6 +1083,52 @@ impl BasisCache {
         }
         total_size
     }
+    /// returns the number of bytes held by decrypted large-pool pages, across all keys and bases.
+    pub(crate) fn large_cache_size(&self) -> usize {
+        let mut total_size = 0;
+        for basis in self.cache.iter() {
+            for dict in basis.dicts.values() {
+                for key in dict.keys.values() {
+                    if let Some(KeyCacheData::Large(kld)) = &key.data {
+                        total_size += kld.size();
+                    }
+                }
+            }
+        }
+        total_size
+    }
+    /// Evicts decrypted large-pool pages, least recently used first, until the pages cached across all
+    /// keys fit in LARGE_CACHE_SIZE. Returns the number of bytes freed.
+    pub(crate) fn large_cache_trim(&mut self) -> usize {
+        let total = self.large_cache_size();
+        if total <= LARGE_CACHE_SIZE {
+            return 0;
+        }
+        let mut pages = Vec::new();
+        for (basis_index, basis) in self.cache.iter().enumerate() {
+            for (dict_name, dict) in basis.dicts.iter() {
+                for (key_name, key) in dict.keys.iter() {
+                    if let Some(KeyCacheData::Large(kld)) = &key.data {
+                        for (&page_offset, page) in kld.pages.iter() {
+                            pages.push((page.atime, basis_index, dict_name.to_string(), key_name.to_string(), page_offset));
+                        }
+                    }
+                }
+            }
+        }
+        pages.sort_unstable_by_key(|page| page.0);
+        let mut pruned = 0;
+        for (_, basis_index, dict_name, key_name, page_offset) in pages {
+            if total - pruned <= LARGE_CACHE_SIZE {
+                break;
+            }
+            if let Some(kcache) = self.cache[basis_index].dicts.get_mut(&dict_name).and_then(|dict| dict.keys.get_mut(&key_name)) {
+                pruned += kcache.large_page_evict(page_offset);
+            }
+        }
+        log::debug!("large page cache trimmed by {} bytes", pruned);
+        pruned
+    }
     /// attempts to prune `target_bytes` out of the cached data set
     pub(crate) fn cache_prune(&mut self, hw: &mut PddbOs, target_bytes: usize) -> usize {
         let mut pruned = 0;
@@ -1321,11 +1364,18 @@ impl BasisCacheEntry {
                     if let Some(dict) = self.dict_decrypt(hw, &pp) {
                         if dict.flags.valid() {
                             let dict_name = std::str::from_utf8(&dict.name.data[..dict.name.len as usize]).expect("dict name is not valid utf-8").to_string();
-                            let dict_present_and_valid = if let Some(d) = self.dicts.get(&dict_name) {
-                                d.flags.valid()
+                            let (dict_present_and_valid, dict_is_stale) = if let Some(d) = self.dicts.get(&dict_name) {
+                                (d.flags.valid(), d.flags.valid() && d.index.get() != try_entry as u32)
                             } else {
-                                false
+                                (false, false)
                             };
+                            if dict_is_stale {
+                                // a duplicate left behind by an interrupted compaction: the lower slot is the live copy.
+                                // Don't count it, so the scan continues on to find any remaining dictionaries.
+                                log::warn!("Ignoring stale copy of dict {} at slot {}", dict_name, try_entry);
+                                try_entry += 1;
+                                continue;
+                            }
                             if !dict_present_and_valid {
                                 let mut dcache = DictCacheEntry::new(dict, try_entry, &self.aad);
                                 let max_large_alloc = dcache.fill(hw, &self.v2p_map, &self.cipher, false);
@@ -1355,12 +1405,11 @@ impl BasisCacheEntry {
         }
     }
 
-    /// If `paranoid` is true, it recurses through each key and replaces its data with random junk.
-    /// Otherwise, it does a "shallow" delete and just removes the directory entry, which is much
+    /// If `paranoid` is true, it recurses through each key and overwrites its data on disk as it is
+    /// removed. Otherwise, it does a "shallow" delete and just removes the directory entry, which is much
     /// more performant. Note that the intended "fast" way to secure-erase data is to store sensitive
     /// data in its own Basis, and then remove the Basis itself. This is much faster than picking
-    /// through compounded data and re-writing partias sectors, and because of this, initially,
-    /// the `paranoid` erase is `unimplemented`.
+    /// through compounded data and re-writing partial sectors.
     pub(crate) fn dict_delete(&mut self, hw: &mut PddbOs, name: &str, paranoid: bool) -> Result<()> {
         if self.ensure_dict_in_cache(hw, name) {
             let dcache = self.dicts.get_mut(name).expect("entry was ensured, but somehow missing");
@@ -1377,7 +1426,7 @@ impl BasisCacheEntry {
             }
             for key in key_list {
                 log::debug!("removing {}:{}", name, key);
-                // this will wipe any large pools if paranoid is set
+                // large pools are always wiped; small pool entries are also scrubbed if paranoid is set
                 dcache.key_remove(hw, &mut self.v2p_map, &self.cipher, &key, paranoid);
             }
             // wipe & de-allocate any small pages
@@ -1464,6 +1513,8 @@ impl BasisCacheEntry {
     pub(crate) fn dict_deep_search(&mut self, hw: &mut PddbOs, name: &str) -> Option<(u32, Dictionary)> {
         let mut try_entry = 1;
         let mut dict_count = 0;
+        // names seen so far; stale duplicates from an interrupted compaction should not count toward the total
+        let mut seen = HashSet::<String>::new();
         while try_entry <= DICT_MAXCOUNT && dict_count < self.num_dicts {
             let dict_vaddr = VirtAddr::new(try_entry as u64 * DICT_VSIZE).unwrap();
             if let Some(pp) = self.v2p_map.get(&dict_vaddr) {
@@ -1473,7 +1524,9 @@ impl BasisCacheEntry {
                     if (dict_name == name) && dict.flags.valid() {
                         return Some((try_entry as u32, dict))
                     }
-                    dict_count += 1;
+                    if seen.insert(dict_name.to_string()) {
+                        dict_count += 1;
+                    }
                 } else {
                     // this is an empty dictionary entry. we could stick a dictionary in here later on, take note if we haven't already computed that
                     if self.free_dict_offset.is_none() {
@@ -1710,12 +1763,197 @@ impl BasisCacheEntry {
         }
     }
 
-    /// Runs through the dictionary listing in a basis and compacts them. Call when the
-    /// the dictionary space becomes sufficiently fragmented that accesses are becoming
-    /// inefficient.
-    #[allow(dead_code)]
-    pub(crate) fn dict_compact(&self, _basis_name: Option<&str>) -> Result<()> {
-        unimplemented!();
+    /// Runs through the dictionary listing in a basis and computes a compaction plan. The plan is a list of
+    /// (dictionary name, target slot, pages required) tuples, ordered such that executing the moves in
+    /// sequence always targets a free slot: dictionaries are only ever moved to a lower slot, and the lowest
+    /// dictionaries move first. The "pages required" is an estimate to pass to `ensure_fast_space_alloc()`
+    /// prior to calling `dict_relocate()`.
+    ///
+    /// This also brings the entire basis into a synced state, and cleans up any stale copies of dictionaries
+    /// left on disk by a previously interrupted compaction.
+    pub(crate) fn dict_compact_plan(&mut self, hw: &mut PddbOs) -> Result<Vec<(String, u32, usize)>> {
+        // the plan is only correct if every dictionary in the basis is in the cache
+        self.populate_caches(hw);
+        let mut num_valid = 0;
+        for dict in self.dicts.values() {
+            if dict.flags.valid() {
+                num_valid += 1;
+            }
+        }
+        if num_valid != self.num_dicts {
+            log::error!("Can't compact {}: found {} of {} dictionaries", self.name, num_valid, self.num_dicts);
+            return Err(Error::new(ErrorKind::InvalidData, "Dictionary count inconsistency, can't compact"));
+        }
+        // make sure the disk reflects the cache before we start moving things around
+        self.sync(hw, false)?;
+        self.dict_purge_stale(hw);
+
+        let mut by_index = Vec::<(u32, String)>::new();
+        for (name, dict) in self.dicts.iter() {
+            if dict.flags.valid() {
+                by_index.push((dict.index.get(), name.to_string()));
+            }
+        }
+        by_index.sort();
+        let mut plan = Vec::<(String, u32, usize)>::new();
+        for (slot, (index, name)) in by_index.into_iter().enumerate() {
+            let target = slot as u32 + 1;
+            if index != target {
+                let dict = self.dicts.get(&name).expect("dict disappeared while planning compaction");
+                // every descriptor page up to the highest key in use, plus one page per occupied small pool
+                let mut pages = 1;
+                for key in dict.keys.values() {
+                    if key.flags.valid() && key.descriptor_vpage_num() + 1 > pages {
+                        pages = key.descriptor_vpage_num() + 1;
+                    }
+                }
+                pages += dict.small_pool.iter().filter(|ksp| !ksp.contents.is_empty()).count();
+                plan.push((name, target, pages));
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Moves the dictionary `name` to the dictionary slot `target`, which must be free. The move is
+    /// a copy-then-retire operation: the dictionary and its small pool are first written to the new
+    /// slot, and its header page is committed to the page table last, so a power loss before that point
+    /// leaves the original untouched. After the new copy is committed, the original is erased, header last.
+    /// A power loss during the erase leaves a stale duplicate at a higher slot than the new copy; the
+    /// mount path ignores such duplicates, and the next compaction removes them.
+    ///
+    /// Assumes the caller has called `ensure_fast_space_alloc()` with the estimate from `dict_compact_plan()`.
+    pub(crate) fn dict_relocate(&mut self, hw: &mut PddbOs, name: &str, target: u32) -> Result<()> {
+        let new_index = NonZeroU32::new(target).ok_or(Error::new(ErrorKind::InvalidInput, "dictionary slot 0 is invalid"))?;
+        if target as usize > DICT_MAXCOUNT {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary slot out of range"));
+        }
+        if !self.ensure_dict_in_cache(hw, name) {
+            return Err(Error::new(ErrorKind::NotFound, "Dictionary not found"));
+        }
+        for dict in self.dicts.values() {
+            if dict.flags.valid() && dict.index == new_index {
+                return Err(Error::new(ErrorKind::AlreadyExists, "Target dictionary slot is occupied"));
+            }
+        }
+        let old_index = self.dicts.get(name).expect("entry was ensured, but somehow missing").index;
+        if old_index == new_index {
+            return Ok(());
+        }
+        log::info!("relocating dict {} from slot {} to slot {}", name, old_index, new_index);
+        // clear out anything left over in the target slot, e.g. pages of an undecryptable dictionary
+        self.dict_erase_region(hw, new_index);
+        self.pt_sync(hw);
+
+        let dcache = self.dicts.get_mut(name).expect("entry was ensured, but somehow missing");
+        // every key and all small pool data has to be in RAM, as the data gets written to a new location
+        dcache.fill(hw, &self.v2p_map, &self.cipher, false);
+        let mut data_cache = PlaintextCache { data: None, tag: None };
+        let mut evicted = Vec::<String>::new();
+        for (key_name, key) in dcache.keys.iter() {
+            if key.flags.valid() && key.data.is_none() && small_storage_index_from_key(key, old_index).is_some() {
+                evicted.push(key_name.to_string());
+            }
+        }
+        for key_name in evicted {
+            dcache.refill_small_key(hw, &self.v2p_map, &self.cipher, &mut data_cache, &key_name);
+        }
+
+        // re-base the small pool pointers to the new slot, and dirty everything so it is written out
+        let old_pool_base = small_storage_base_vaddr_from_indices(old_index, 0);
+        let new_pool_base = small_storage_base_vaddr_from_indices(new_index, 0);
+        for key in dcache.keys.values_mut() {
+            if key.flags.valid() && small_storage_index_from_key(key, old_index).is_some() {
+                key.start = key.start - old_pool_base + new_pool_base;
+            }
+            key.clean = false;
+        }
+        for ksp in dcache.small_pool.iter_mut() {
+            // empty pools have nothing to preserve; they are written out if they are ever used again
+            ksp.clean = ksp.contents.is_empty();
+        }
+        dcache.index = new_index;
+        dcache.clean = false;
+        if !dcache.sync_small_pool(hw, &mut self.v2p_map, &self.cipher) {
+            return Err(Error::new(ErrorKind::OutOfMemory, "Ran out of memory relocating small pool"));
+        }
+        self.dict_sync(hw, name, false)?;
+
+        // commit the new copy: everything but the header first, then the header on its own, so the new
+        // copy only becomes visible once it is complete.
+        let new_header = VirtAddr::new(new_index.get() as u64 * DICT_VSIZE).unwrap();
+        let header_pp = self.v2p_map.remove(&new_header).expect("dict_sync did not allocate a header page");
+        self.pt_sync(hw);
+        self.v2p_map.insert(new_header, header_pp);
+        self.pt_sync(hw);
+
+        // retire the original copy
+        self.dict_erase_region(hw, old_index);
+        self.pt_sync(hw);
+        // the cached free offset may have pointed at the slot we just filled; force a re-scan
+        self.free_dict_offset = None;
+        Ok(())
+    }
+
+    /// Overwrites and de-allocates every page mapped into the dictionary slot `index`, including its
+    /// small pool. The dictionary header page is erased last, so an interrupted erase leaves a dictionary
+    /// that is still recognizable as a stale copy. Callers must follow this with a `pt_sync()`.
+    fn dict_erase_region(&mut self, hw: &mut PddbOs, index: NonZeroU32) {
+        let dict_base = index.get() as u64 * DICT_VSIZE;
+        let pool_base = small_storage_base_vaddr_from_indices(index, 0);
+        let mut victims = Vec::<VirtAddr>::new();
+        for &vaddr in self.v2p_map.keys() {
+            let va = vaddr.get();
+            if (va > dict_base && va < dict_base + DICT_VSIZE)
+            || (va >= pool_base && va < pool_base + SMALL_POOL_STRIDE) {
+                victims.push(vaddr);
+            }
+        }
+        victims.push(VirtAddr::new(dict_base).unwrap());
+        for vaddr in victims {
+            if let Some(pp) = self.v2p_map.get_mut(&vaddr) {
+                if pp.valid() {
+                    log::debug!("erasing dict region page 0x{:x}/0x{:x}", vaddr, pp.page_number() as usize * PAGE_SIZE);
+                    let mut random = [0u8; PAGE_SIZE];
+                    hw.trng_slice(&mut random);
+                    hw.patch_data(&random, pp.page_number() * PAGE_SIZE as u32);
+                    hw.fast_space_free(pp);
+                }
+            }
+        }
+    }
+
+    /// Scans every mapped dictionary slot for valid dictionaries that duplicate the name of a cached
+    /// dictionary at a different slot. These can only be created by a compaction that was interrupted
+    /// while retiring the original copy; the cached copy is the authoritative one, so the duplicate is erased.
+    fn dict_purge_stale(&mut self, hw: &mut PddbOs) {
+        let mut headers = Vec::<(VirtAddr, PhysPage)>::new();
+        for (&vaddr, pp) in self.v2p_map.iter() {
+            let va = vaddr.get();
+            if va % DICT_VSIZE == 0 && va / DICT_VSIZE >= 1 && va / DICT_VSIZE <= DICT_MAXCOUNT as u64 && pp.valid() {
+                headers.push((vaddr, *pp));
+            }
+        }
+        let mut stale = Vec::<NonZeroU32>::new();
+        for (vaddr, pp) in headers {
+            if let Some(dict) = self.dict_decrypt(hw, &pp) {
+                if dict.flags.valid() {
+                    let index = (vaddr.get() / DICT_VSIZE) as u32;
+                    let dict_name = std::str::from_utf8(&dict.name.data[..dict.name.len as usize]).unwrap_or("");
+                    if let Some(cached) = self.dicts.get(dict_name) {
+                        if cached.flags.valid() && cached.index.get() != index {
+                            log::warn!("Found stale copy of dict {} at slot {} (live copy at {}), erasing", dict_name, index, cached.index);
+                            stale.push(NonZeroU32::new(index).unwrap());
+                        }
+                    }
+                }
+            }
+        }
+        if stale.len() > 0 {
+            for index in stale {
+                self.dict_erase_region(hw, index);
+            }
+            self.pt_sync(hw);
+        }
     }
 
     /// Syncs *only* the basis header to disk.
@@ -1831,34 +2069,3 @@ impl BasisCacheEntry {
 // Beginning of serializers for the data structures in this file.
 // ****
 
-/// Newtype for BasisRootName so we can give it a default initializer.
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
-pub struct BasisRootName {
-    pub len: u8,
-    pub data: [u8; BASIS_NAME_LEN - 1],
-}
-impl BasisRootName {
-    pub fn try_from_str(name: &str) -> Result<BasisRootName> {
-        let mut alloc = [0u8; BASIS_NAME_LEN - 1];
-        let bytes = name.as_bytes();
-        if bytes.len() > (BASIS_NAME_LEN - 1) {
-            Err(Error::new(ErrorKind::InvalidInput, "basis name is too long")) // FileNameTooLong is still nightly :-/
-        } else {
-            for (&src, dst) in bytes.iter().zip(alloc.iter_mut()) {
-                *dst = src;
-            }
-            Ok(BasisRootName {
-                len: bytes.len() as u8, // this as checked above to be short enough
-                data: alloc,
-            })
-        }
-    }
-}
-impl Default for BasisRootName {
-    fn default() -> BasisRootName {
-        BasisRootName{
-            len: 0,
-            data: [0; BASIS_NAME_LEN - 1]
-        }
-    }
-}
@@ -2,7 +2,7 @@ use crate::api::*;
 use super::*;
 
 use std::num::NonZeroU32;
-use core::ops::{Deref, DerefMut};
+use core::ops::DerefMut;
 use core::mem::size_of;
 use aes_gcm_siv::Aes256GcmSiv;
 #[cfg(feature="perfcounter")]
@@ -11,16 +11,8 @@ use perflib::{PERFMETA_STARTBLOCK, PERFMETA_NONE, PERFMETA_ENDBLOCK};
 use crate::FILE_ID_SERVICES_PDDB_SRC_DICTIONARY;
 use std::collections::{HashMap, BinaryHeap, BTreeSet};
 use std::io::{Result, Error, ErrorKind};
-use bitfield::bitfield;
 use std::cmp::{Ordering, Reverse};
 
-bitfield! {
-    #[derive(Copy, Clone, PartialEq, Eq)]
-    pub struct DictFlags(u32);
-    impl Debug;
-    pub valid, set_valid: 0;
-}
-
 /// RAM based copy of the dictionary structures on disk. Most of the methods on this function operate on
 /// keys within the Dictionary. Operations on the Dictionary itself originate from the containing Basis
 /// structure.
@@ -516,11 +508,7 @@ impl DictCacheEntry {
                         (kcache.start + kcache.reserved)..new_reservation_abs_addr
                     ).step_by(VPAGE_SIZE) {
                         // ensures that a physical page entry exists for every new virtual address required by the extended key
-                        v2p_map.entry(VirtAddr::new(vpage_addr).unwrap()).or_insert_with(|| {
-                            let mut ap = hw.try_fast_space_alloc().expect("No free space to allocate additional large key storage");
-                            ap.set_valid(true);
-                            ap
-                        });
+                        large_page_mapping(hw, v2p_map, vpage_addr);
                     }
                     kcache.reserved = new_reservation_abs_addr - kcache.start; // convert absolute address to an actual length
                     kcache.clean = false;
@@ -561,21 +549,29 @@ impl DictCacheEntry {
                         let data_vaddr = small_storage_base_vaddr_from_indices(self.index, pool_index);
                         let mut data_cache = PlaintextCache { data: None, tag: None };
                         data_cache.fill(hw, v2p_map, cipher, &self.aad, VirtAddr::new(data_vaddr).unwrap());
-                        if let Some(page) = data_cache.data.as_ref() {
+                        let mut cache_data = if let Some(page) = data_cache.data.as_ref() {
                             let start_offset = size_of::<JournalType>() + (kcache.start % VPAGE_SIZE as u64) as usize;
-                            let mut data = page[start_offset..start_offset + kcache.len as usize].to_vec();
-                            data.reserve_exact((kcache.reserved - kcache.len) as usize);
-                            kcache.data = Some(KeyCacheData::Small(
-                                KeySmallData {
-                                    clean: true,
-                                    data
-                                }
-                            ));
+                            page[start_offset..start_offset + kcache.len as usize].to_vec()
                         } else {
-                            let error =
-                                format!("Key {}'s data region at pp: {:x?} va: {:x} is unreadable", name, data_cache.tag, kcache.start);
-                            panic!("{}", error);
+                            // the key entry made it to disk but its data didn't, which happens if power is lost in between.
+                            // Those contents are gone, so the update goes over blank data.
+                            log::error!("Key {}'s data region at pp: {:x?} va: {:x} is unreadable", name, data_cache.tag, kcache.start);
+                            vec![0u8; kcache.len as usize]
+                        };
+                        cache_data.reserve_exact((kcache.reserved - kcache.len) as usize);
+                        // now apply the update, as for a key that was in cache
+                        while cache_data.len() < data.len() + offset {
+                            cache_data.push(0);
                         }
+                        for (&src, dst) in data.iter().zip(cache_data[offset..].iter_mut()) {
+                            *dst = src;
+                        }
+                        kcache.data = Some(KeyCacheData::Small(
+                            KeySmallData {
+                                clean: false,
+                                data: cache_data
+                            }
+                        ));
                     }
                 }
                 // check if we grew the length
@@ -589,25 +585,19 @@ impl DictCacheEntry {
                 self.small_pool[pool_index].clean = false;
                 // note: there is no need to update small_pool_free because the reserved size did not change.
             } else {
-                // it's a large key
-                if let Some(_kcd) = &kcache.data {
-                    unimplemented!("caching is not yet implemented for large data sets");
-                } else {
+                // it's a large key. Writes go through to disk; any pages in the read-ahead window are updated to match.
+                {
                     kcache.age = kcache.age.saturating_add(1);
                     kcache.clean = false;
-                    /* // this was for debugging a patching bug -- OK to remove
-                    if data.len() == 4 {
-                        use std::convert::TryInto;
-                        log::info!("patching checksum: {:x} at offset {}", u32::from_le_bytes(data.try_into().unwrap()), offset);
-                    }*/
                     // 1. handle unaligned start offsets
                     let mut written: usize = 0;
                     if ((kcache.start + offset as u64 + written as u64) % VPAGE_SIZE as u64) != 0 {
-                        let start_vpage_addr = ((kcache.start + offset as u64) / VPAGE_SIZE as u64) * VPAGE_SIZE as u64;
-                        let pp = v2p_map.get(&VirtAddr::new(start_vpage_addr).unwrap()).expect("large key data allocation missing");
+                        let page_offset = (offset as u64 / VPAGE_SIZE as u64) * VPAGE_SIZE as u64;
+                        let start_vpage_addr = kcache.start + page_offset;
+                        let pp = large_page_mapping(hw, v2p_map, start_vpage_addr);
                         assert!(pp.valid(), "v2p returned an invalid page");
-                        let mut pt_data = match hw.data_decrypt_page(&cipher, &self.aad, pp) {
-                            Some(data) => data,
+                        let mut pt_data = match kcache.large_page(hw, v2p_map, cipher, &self.aad, page_offset) {
+                            Some(data) => data.to_vec(),
                             None => {
                                 // this case is triggered by the following circumstance:
                                 //  - we reserved data that includes this current page
@@ -634,11 +624,13 @@ impl DictCacheEntry {
                             assert!((kcache.start + offset as u64 + written as u64) % VPAGE_SIZE as u64 == 0, "alignment algorithm failed");
                         }
                         hw.data_encrypt_and_patch_page(cipher, &self.aad, &mut pt_data, &pp);
+                        kcache.large_page_written(page_offset, &pt_data);
                     }
                     // 2. do the rest
                     while written < data.len() {
-                        let vpage_addr = ((kcache.start + written as u64 + offset as u64) / VPAGE_SIZE as u64) * VPAGE_SIZE as u64;
-                        let pp = v2p_map.get(&VirtAddr::new(vpage_addr).unwrap()).expect("large key data allocation missing");
+                        let page_offset = ((written as u64 + offset as u64) / VPAGE_SIZE as u64) * VPAGE_SIZE as u64;
+                        let vpage_addr = kcache.start + page_offset;
+                        let pp = large_page_mapping(hw, v2p_map, vpage_addr);
                         assert!(pp.valid(), "v2p returned an invalid page");
                         if data.len() - written >= VPAGE_SIZE {
                             // overwrite whole pages without decryption
@@ -650,27 +642,27 @@ impl DictCacheEntry {
                                 *dst = src;
                                 written += 1;
                             }
-                            hw.data_encrypt_and_patch_page(cipher, &self.aad, &mut block, pp);
+                            hw.data_encrypt_and_patch_page(cipher, &self.aad, &mut block, &pp);
+                            kcache.large_page_written(page_offset, &block);
                         } else {
                             // handle partial trailing pages
-                            if let Some(pt_data) = hw.data_decrypt_page(&cipher, &self.aad, pp).as_mut() {
-                                for (&src, dst) in data[written..].iter().zip(pt_data[size_of::<JournalType>()..].iter_mut()) {
-                                    *dst = src;
-                                    written += 1;
-                                }
-                                hw.data_encrypt_and_patch_page(cipher, &self.aad, pt_data, pp);
-                            } else {
-                                // page didn't exist, initialize it with 0's and merge the tail end.
-                                let mut pt_data = [0u8; VPAGE_SIZE + size_of::<JournalType>()];
-                                for (&src, dst) in (hw.trng_u32() % JOURNAL_RAND_RANGE).to_le_bytes().iter().zip(pt_data[..size_of::<JournalType>()].iter_mut()) {
-                                    *dst = src;
+                            let mut pt_data = match kcache.large_page(hw, v2p_map, cipher, &self.aad, page_offset) {
+                                Some(page) => page.to_vec(),
+                                None => {
+                                    // page didn't exist, initialize it with 0's and merge the tail end.
+                                    let mut d = vec![0u8; VPAGE_SIZE + size_of::<JournalType>()];
+                                    for (&src, dst) in (hw.trng_u32() % JOURNAL_RAND_RANGE).to_le_bytes().iter().zip(d[..size_of::<JournalType>()].iter_mut()) {
+                                        *dst = src;
+                                    }
+                                    d
                                 }
-                                for (&src, dst) in data[written..].iter().zip(pt_data[size_of::<JournalType>()..].iter_mut()) {
-                                    *dst = src;
-                                    written += 1;
-                                }
-                                hw.data_encrypt_and_patch_page(cipher, &self.aad, &mut pt_data, pp);
+                            };
+                            for (&src, dst) in data[written..].iter().zip(pt_data[size_of::<JournalType>()..].iter_mut()) {
+                                *dst = src;
+                                written += 1;
                             }
+                            hw.data_encrypt_and_patch_page(cipher, &self.aad, &mut pt_data, &pp);
+                            kcache.large_page_written(page_offset, &pt_data);
                         }
                     }
                     log::trace!("data written: {}, data requested to write: {}", written, data.len());
@@ -682,9 +674,11 @@ impl DictCacheEntry {
                     } else if truncate {
                         // discard all whole pages after written+offset, and reset the reserved field to the smaller size.
                         log::trace!("PageAligned VA components: {}, {}", written, offset);
-                        let vpage_end_offset = PageAlignedVa::from((written + offset) as u64);
-                        if (vpage_end_offset.as_u64() - kcache.start) > kcache.reserved {
-                            for vpage in (vpage_end_offset.as_u64()..kcache.start + kcache.reserved).step_by(VPAGE_SIZE) {
+                        kcache.len = (data.len() + offset) as u64;
+                        // `start` is page aligned, so this is the first page past the end of the data
+                        let vpage_end = PageAlignedVa::from(kcache.start + (written + offset) as u64).as_u64();
+                        if kcache.start + kcache.reserved > vpage_end {
+                            for vpage in (vpage_end..kcache.start + kcache.reserved).step_by(VPAGE_SIZE) {
                                 if let Some(pp) = v2p_map.get_mut(&VirtAddr::new(vpage).unwrap()) {
                                     assert!(pp.valid(), "v2p returned an invalid page");
                                     log::trace!("fast_space_free key_update {} before", pp.journal());
@@ -692,9 +686,10 @@ impl DictCacheEntry {
                                     assert!(pp.valid() == false, "pp is still marked as valid!");
                                 }
                             }
-                            kcache.reserved = vpage_end_offset.as_u64() - kcache.start;
+                            kcache.reserved = vpage_end - kcache.start;
                             kcache.clean = false;
-                            kcache.len = (data.len() + offset) as u64;
+                            // the freed pages may have been in the read-ahead window
+                            kcache.data = None;
                         }
                     }
                 }
@@ -833,20 +828,18 @@ impl DictCacheEntry {
         }
     }
     /// Used to remove a key from the dictionary. If you call it with a non-existent key,
-    /// the routine has no effect, and does not report an error. Small keys are not immediately
-    /// overwritten in paranoid mode, but large keys are.
+    /// the routine has no effect, and does not report an error. Large keys are always overwritten
+    /// on removal. Small keys are only overwritten immediately in paranoid mode; otherwise, their
+    /// data lingers in the small pool page until the pool is next synced.
     pub fn key_remove(&mut self, hw: &mut PddbOs, v2p_map: &mut HashMap::<VirtAddr, PhysPage>, cipher: &Aes256GcmSiv,
         name_str: &str, paranoid: bool) {
         log::debug!("removing key {}", name_str);
-        if paranoid {
-            // large records are paranoid-erased, by default, because of the pool-reuse problem.
-            unimplemented!("Paranoid erase for small records not yet implemented. Calling sync after an update, however, effectively does a paranoid erase.");
-        }
         // this call will check the disk to see if there's key data that's not in cache.
         if self.ensure_key_entry(hw, v2p_map, cipher, name_str) {
             let name = String::from(name_str);
             let mut need_rebuild = false;
             let mut need_free_key: Option<u32> = None;
+            let mut need_scrub: Option<(u64, u64)> = None;
             if let Some(kcache) = self.keys.get_mut(&name) {
                 if !kcache.flags.valid() {
                     log::debug!("ensure of invalid key: {}", name_str);
@@ -873,6 +866,19 @@ impl DictCacheEntry {
                     assert!(ksp.avail <= SMALL_CAPACITY as u16, "bookkeeping error in small pool capacity");
                     ksp.clean = false; // this will also effectively cause the record to be deleted on disk once the small pool data is synchronized
                     need_rebuild = true;
+                    if paranoid {
+                        // don't leave a plaintext copy in RAM, either
+                        if let Some(KeyCacheData::Small(ksd)) = kcache.data.as_mut() {
+                            for b in ksd.data.iter_mut() {
+                                *b = 0;
+                            }
+                        }
+                        kcache.data = None;
+                        // unresolved keys were never written to the pool page, so there is nothing on disk to scrub
+                        if !kcache.flags.unresolved() {
+                            need_scrub = Some((kcache.start, kcache.reserved));
+                        }
+                    }
 
                 } else {
                     // handle the large pool case
@@ -895,6 +901,9 @@ impl DictCacheEntry {
                 }
                 need_free_key = Some(kcache.descriptor_index.get());
             }
+            if let Some((start, reserved)) = need_scrub {
+                self.small_key_scrub(hw, v2p_map, cipher, start, reserved);
+            }
             // free up the key index in the dictionary, if necessary
             if let Some(key_to_free) = need_free_key {
                 log::debug!("freeing key: {}", key_to_free);
@@ -917,12 +926,26 @@ impl DictCacheEntry {
         }
         // if there's no key....we're done!
     }
-    /// used to remove a key from the dictionary, syncing 0's to the disk in the key's place
-    /// sort of less relevant now that the large keys have a paranoid mode; probably this routine should actually
-    /// be a higher-level function that catches the paranoid request and does an "update" of 0's to the key
-    /// then does a disk sync and then calls remove
-    pub fn key_erase(&mut self, _name: &str) {
-        unimplemented!();
+    /// Overwrites a small key's data with 0's in its small pool page, in place. The rest of the page is
+    /// re-encrypted as-is, so the other keys in the pool don't move and their descriptors remain valid
+    /// even if we lose power before the next sync. Because the whole page is re-encrypted under a fresh
+    /// nonce, none of the old ciphertext survives on disk.
+    fn small_key_scrub(&self, hw: &mut PddbOs, v2p_map: &HashMap::<VirtAddr, PhysPage>, cipher: &Aes256GcmSiv,
+        start: u64, reserved: u64) {
+        let pool_vaddr = VirtAddr::new((start / VPAGE_SIZE as u64) * VPAGE_SIZE as u64).unwrap();
+        if let Some(pp) = v2p_map.get(&pool_vaddr) {
+            assert!(pp.valid(), "v2p returned an invalid page");
+            if let Some(mut page) = hw.data_decrypt_page(cipher, &self.aad, pp) {
+                let offset = size_of::<JournalType>() + (start % VPAGE_SIZE as u64) as usize;
+                for b in page[offset..offset + reserved as usize].iter_mut() {
+                    *b = 0;
+                }
+                hw.data_encrypt_and_patch_page(cipher, &self.aad, &mut page, pp);
+                log::debug!("scrubbed {} bytes at {:x}", reserved, start);
+            } else {
+                log::warn!("small pool page at {:x} is unreadable, can't scrub key data", pool_vaddr.get());
+            }
+        }
     }
     /// estimates the amount of space needed to sync the dict cache. Pass this to ensure_fast_space_alloc() before calling a sync.
     /// estimate can be inaccurate under pathological allocation conditions.
@@ -1048,7 +1071,8 @@ impl DictCacheEntry {
         true
     }
 
-    /// No data cache to flush yet...large pool caches not implemented!
+    /// Nothing to flush: large pool writes go straight to disk, and the read-ahead
+    /// window in each `KeyCacheEntry` is kept in sync with those writes.
     pub(crate) fn sync_large_pool(&self) {
     }
 
@@ -1167,12 +1191,20 @@ impl DictCacheEntry {
     /// because it was dirty; you need to call sync before evicting anything from the cache)
     pub(crate) fn evict_keycache_entry(&mut self, key: &str) -> usize {
         if let Some(kcache) = self.keys.get_mut(key) {
+            // large record caches are write-through, so the read-ahead window can always be dropped
+            // only the data is freed; the entry itself stays in the cache, so it doesn't count toward what was pruned
+            if let Some(KeyCacheData::Large(kld)) = &kcache.data {
+                let pruned = kld.size();
+                kcache.data.take();
+                log::debug!("pruned {} bytes from large key {}", pruned, key);
+                return pruned;
+            }
             // if the cache entry is dirty, abort
             if kcache.flags.valid() && !kcache.clean {
                 return 0;
             }
-            if let Some(KeyCacheData::Small(_)) = kcache.data { // can only prune small records
-                let pruned = kcache.size();
+            if let Some(KeyCacheData::Small(ksd)) = &kcache.data {
+                let pruned = ksd.data.len();
                 kcache.data.take(); // this effectively frees up the key cache data
                 // mark the key's pool as unclean, so it is processed for filling
                 let pool_index = small_storage_index_from_key(&kcache, self.index).expect("index missing");
@@ -1197,6 +1229,16 @@ fn dict_indices_to_vaddr(dict_index: NonZeroU32, key_meta_index: usize) -> u64 {
     assert!(key_meta_index != 0, "key metadata index is 1-offset");
     dict_index.get() as u64 * DICT_VSIZE + ((key_meta_index / DK_PER_VPAGE) as u64) * VPAGE_SIZE as u64
 }
+/// Returns the physical page behind `vpage_addr` in a large key's reservation, allocating one if it was never
+/// mapped. Normally the pages are mapped as the reservation is made, but a power loss between writing the key
+/// entry and writing its page table entries leaves a reservation with nothing behind it.
+fn large_page_mapping(hw: &mut PddbOs, v2p_map: &mut HashMap::<VirtAddr, PhysPage>, vpage_addr: u64) -> PhysPage {
+    *v2p_map.entry(VirtAddr::new(vpage_addr).unwrap()).or_insert_with(|| {
+        let mut ap = hw.try_fast_space_alloc().expect("No free space to allocate additional large key storage");
+        ap.set_valid(true);
+        ap
+    })
+}
 /// Derives the index of a Small Pool storage block given the key cache entry and the dictionary index.
 /// The index maps into the small_pool array, which itself maps 1:1 onto blocks inside the small pool
 /// memory space.
@@ -1215,81 +1257,6 @@ pub(crate) fn small_storage_base_vaddr_from_indices(dict_index: NonZeroU32, base
     SMALL_POOL_START + (dict_index.get()-1) as u64 * DICT_VSIZE + base_index as u64 * SMALL_CAPACITY as u64
 }
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
-#[repr(C, align(8))]
-pub struct DictName {
-    pub len: u8,
-    pub data: [u8; DICT_NAME_LEN - 1],
-}
-impl DictName {
-    pub fn try_from_str(name: &str) -> Result<DictName> {
-        let mut alloc = [0u8; DICT_NAME_LEN - 1];
-        let bytes = name.as_bytes();
-        if bytes.len() > (DICT_NAME_LEN - 1) {
-            Err(Error::new(ErrorKind::InvalidInput, "dict name is too long"))
-        } else {
-            for (&src, dst) in bytes.iter().zip(alloc.iter_mut()) {
-                *dst = src;
-            }
-            Ok(DictName {
-                len: bytes.len() as u8, // this as checked above to be short enough
-                data: alloc,
-            })
-        }
-    }
-}
-impl Default for DictName {
-    fn default() -> DictName {
-        DictName {
-            len: 0,
-            data: [0; DICT_NAME_LEN - 1]
-        }
-    }
-}
-
-#[derive(Debug)]
-/// On-disk representation of the dictionary header. This structure is mainly for archival/unarchival
-/// purposes. To "functionalize" a stored disk entry, it needs to be deserialized into a DictionaryCacheEntry.
-#[repr(C, align(8))]
-pub(crate) struct Dictionary {
-    /// Reserved for flags on the record entry
-    pub(crate) flags: DictFlags,
-    /// Access count to the dicitionary
-    pub(crate) age: u32,
-    /// Number of keys in the dictionary
-    pub(crate) num_keys: u32,
-    /// Free index starting space. While this is a derived parameter, its value is recorded to avoid
-    /// an expensive, long search operation during the creation of a dictionary cache record. 0 is an invalid index,
-    /// as this is where the header goes. Maybe this should be a NonZeroU32.
-    pub(crate) free_key_index: u32,
-    /// Name. Length should pad out the record to exactly 127 bytes.
-    pub(crate) name: DictName,
-}
-impl Default for Dictionary {
-    fn default() -> Dictionary {
-        let mut flags = DictFlags(0);
-        flags.set_valid(true);
-        Dictionary { flags, age: 0, num_keys: 0, free_key_index: 1, name: DictName::default() }
-    }
-}
-impl Deref for Dictionary {
-    type Target = [u8];
-    fn deref(&self) -> &[u8] {
-        unsafe {
-            core::slice::from_raw_parts(self as *const Dictionary as *const u8, core::mem::size_of::<Dictionary>())
-                as &[u8]
-        }
-    }
-}
-impl DerefMut for Dictionary {
-    fn deref_mut(&mut self) -> &mut [u8] {
-        unsafe {
-            core::slice::from_raw_parts_mut(self as *mut Dictionary as *mut u8, core::mem::size_of::<Dictionary>())
-                as &mut [u8]
-        }
-    }
-}
-
 #[allow(dead_code)]
 #[derive(Debug)]
 pub(crate) struct DictAttributes {
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 use crate::api::*;
+use super::KeyExport;
 
 use std::sync::Once;
 use std::mem::MaybeUninit;
@@ -8,6 +9,89 @@ use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+/// Default location of the emulated FLASH image that backs the PDDB in hosted mode.
+pub const HOSTED_IMAGE_PATH: &'static str = "../tools/pddb-images/hosted.bin";
+/// Overrides the image path. Also settable with `--pddb-image <path>` on the command line.
+pub const HOSTED_IMAGE_ENV: &'static str = "XOUS_PDDB_IMAGE";
+/// Selects how the image is used: `disk`, `snapshot` or `memory`. Also settable with
+/// `--pddb-mode <mode>` on the command line.
+pub const HOSTED_MODE_ENV: &'static str = "XOUS_PDDB_MODE";
+/// Overrides where `dbg_dump` writes its `.bin`/`.key` files. Defaults to the directory the
+/// image is in. Also settable with `--pddb-dump-dir <path>` on the command line.
+pub const HOSTED_DUMP_DIR_ENV: &'static str = "XOUS_PDDB_DUMP_DIR";
+
+/// How the emulated FLASH is backed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HostedImage {
+    /// The image file is read on start, and every write goes through to it.
+    Disk(PathBuf),
+    /// The image file is read on start if it exists, but is never written. Changes live in
+    /// memory only, and are discarded on exit. Use this to run against a known-good image.
+    Snapshot(PathBuf),
+    /// Starts from a blank image and never touches the disk. Intended for unit tests.
+    Memory,
+}
+impl HostedImage {
+    /// Picks the image from the command line, falling back to the environment, falling back to
+    /// read-write access to `HOSTED_IMAGE_PATH`. Unit tests default to `Memory`.
+    pub fn from_env() -> HostedImage {
+        let path = PathBuf::from(
+            arg_value("--pddb-image")
+            .or_else(|| std::env::var(HOSTED_IMAGE_ENV).ok())
+            .unwrap_or_else(|| HOSTED_IMAGE_PATH.to_string())
+        );
+        let mode = arg_value("--pddb-mode").or_else(|| std::env::var(HOSTED_MODE_ENV).ok());
+        match mode.as_deref() {
+            Some("disk") => HostedImage::Disk(path),
+            Some("snapshot") => HostedImage::Snapshot(path),
+            Some("memory") => HostedImage::Memory,
+            Some(other) => panic!("Unknown PDDB image mode '{}': expected disk, snapshot or memory", other),
+            #[cfg(test)]
+            None => HostedImage::Memory,
+            #[cfg(not(test))]
+            None => HostedImage::Disk(path),
+        }
+    }
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            HostedImage::Disk(path) | HostedImage::Snapshot(path) => Some(path),
+            HostedImage::Memory => None,
+        }
+    }
+    /// Where debug dumps of the image are written.
+    pub fn dump_dir(&self) -> PathBuf {
+        if let Some(dir) = arg_value("--pddb-dump-dir").or_else(|| std::env::var(HOSTED_DUMP_DIR_ENV).ok()) {
+            return PathBuf::from(dir);
+        }
+        self.path()
+            .and_then(|p| p.parent())
+            .map(|p| p.to_path_buf())
+            .unwrap_or(Path::new(HOSTED_IMAGE_PATH).parent().unwrap().to_path_buf())
+    }
+}
+
+/// Returns the argument following `flag` on the command line, or the value in `flag=value`.
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(flag).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+static mut IMAGE_SELECTION: Option<HostedImage> = None;
+/// Selects the image backing the emulated FLASH, overriding the command line and environment.
+/// Must be called before the PDDB is first accessed; later calls have no effect.
+pub fn select_hosted_image(image: HostedImage) {
+    unsafe { IMAGE_SELECTION = Some(image); }
+}
 
 // This is considered bad practice for Rust to use a global singleton.
 // However, this hack puts the burden of emulation on the emulator, while
@@ -18,7 +102,23 @@ use std::io::SeekFrom;
 // Note that this is a concurrently accessed, unsafe, unchecked vector.
 struct FlashSingleton {
     memory: Vec::<u8>,
-    disk: File,
+    /// only present in `HostedImage::Disk` mode
+    disk: Option<File>,
+    image: HostedImage,
+    /// when present, every write is recorded as (offset, data); see `hosted_write_log_start()`
+    write_log: Option<Vec<(usize, Vec::<u8>)>>,
+}
+impl FlashSingleton {
+    /// Writes `data` at `offset` (relative to the start of the PDDB) through to the image file, if any.
+    fn persist(&mut self, offset: u64, data: &[u8]) {
+        if let Some(log) = self.write_log.as_mut() {
+            log.push((offset as usize, data.to_vec()));
+        }
+        if let Some(disk) = self.disk.as_mut() {
+            disk.seek(SeekFrom::Start(offset)).expect("couldn't seek PDDB");
+            disk.write_all(data).expect("couldn't write PDDB");
+        }
+    }
 }
 
 fn flashmem() -> &'static mut FlashSingleton {
@@ -27,35 +127,46 @@ fn flashmem() -> &'static mut FlashSingleton {
 
     unsafe {
         ONCE.call_once(|| {
-            let mut disk = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("../tools/pddb-images/hosted.bin")
-            .expect("Can't open a PDDB image file for writing");
-
+            let image = IMAGE_SELECTION.take().unwrap_or_else(HostedImage::from_env);
+            log::info!("PDDB hosted image: {:?}", image);
             let mut memory = Vec::<u8>::with_capacity(PDDB_A_LEN);
-            if disk.metadata().unwrap().len() == 0 {
-                for _ in 0..PDDB_A_LEN {
-                    memory.push(0xFF);
+            let disk = match &image {
+                HostedImage::Disk(path) => {
+                    let mut disk = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .open(path)
+                    .expect("Can't open a PDDB image file for writing");
+                    if disk.metadata().unwrap().len() == 0 {
+                        memory.resize(PDDB_A_LEN, 0xFF);
+                        disk.write_all(&memory).expect("couldn't create initial disk image");
+                    } else {
+                        read_image(&mut disk, &mut memory);
+                    }
+                    Some(disk)
                 }
-                disk.write(&memory).expect("couldn't create initial disk image");
-            } else {
-                match disk.read_to_end(&mut memory) {
-                    Ok(bytes_read) => {
-                        if bytes_read != PDDB_A_LEN {
-                            log::warn!("PDDB disk image is of an incorrect size: got {}, expected {}", bytes_read, PDDB_A_LEN);
+                HostedImage::Snapshot(path) => {
+                    match File::open(path) {
+                        Ok(mut f) => read_image(&mut f, &mut memory),
+                        Err(_) => {
+                            log::warn!("PDDB snapshot image {:?} not found, starting from a blank image", path);
+                            memory.resize(PDDB_A_LEN, 0xFF);
                         }
                     }
-                    _ => {
-                        panic!("Can't read PDDB disk image, refusing to run!");
-                    }
+                    None
                 }
-            }
+                HostedImage::Memory => {
+                    memory.resize(PDDB_A_LEN, 0xFF);
+                    None
+                }
+            };
 
             let flashmem = FlashSingleton {
                 memory,
                 disk,
+                image,
+                write_log: None,
             };
             SINGLETON.write(flashmem);
         });
@@ -63,14 +174,59 @@ fn flashmem() -> &'static mut FlashSingleton {
     }
 }
 
-#[derive(Copy, Clone)]
-pub struct KeyExport {
-    pub basis_name: [u8; 64],
-    /// data key
-    pub key: [u8; 32],
-    /// page table key
-    pub pt_key: [u8; 32],
+fn read_image(f: &mut File, memory: &mut Vec::<u8>) {
+    match f.read_to_end(memory) {
+        Ok(bytes_read) => {
+            if bytes_read != PDDB_A_LEN {
+                log::warn!("PDDB disk image is of an incorrect size: got {}, expected {}", bytes_read, PDDB_A_LEN);
+                // short images read back as erased FLASH
+                memory.resize(PDDB_A_LEN, 0xFF);
+            }
+        }
+        _ => {
+            panic!("Can't read PDDB disk image, refusing to run!");
+        }
+    }
+}
+
+/// Returns the contents of the image as it stands on the backing store: re-read from the file in
+/// `Disk` mode, so tests can inspect what actually hit the disk; a copy of the emulated FLASH otherwise.
+pub fn hosted_raw_image() -> std::io::Result<Vec::<u8>> {
+    match &flashmem().image {
+        HostedImage::Disk(path) => std::fs::read(path),
+        _ => Ok(flashmem().memory.clone()),
+    }
+}
+
+/// Overwrites the emulated FLASH with `image`, e.g. to roll back to a copy taken with `hosted_raw_image()`.
+pub fn hosted_restore_image(image: &[u8]) {
+    assert!(image.len() == flashmem().memory.len(), "restored image is of an incorrect size");
+    flashmem().memory.copy_from_slice(image);
+    let log = flashmem().write_log.take();
+    flashmem().persist(0, image);
+    flashmem().write_log = log;
+}
+
+/// Starts recording every write made to the emulated FLASH. Used by power-loss tests to replay a
+/// partial sequence of writes on top of an earlier image.
+pub fn hosted_write_log_start() {
+    flashmem().write_log = Some(Vec::new());
+}
+
+/// Stops recording, and returns the writes made since `hosted_write_log_start()` in the order they
+/// happened, as (offset, data) pairs. Offsets are relative to the start of the image.
+pub fn hosted_write_log_take() -> Vec<(usize, Vec::<u8>)> {
+    flashmem().write_log.take().unwrap_or_default()
 }
+
+/// Applies writes previously returned by `hosted_write_log_take()`.
+pub fn hosted_replay_writes(writes: &[(usize, Vec::<u8>)]) {
+    for (offset, data) in writes.iter() {
+        flashmem().memory[*offset..*offset + data.len()].copy_from_slice(data);
+        flashmem().persist(*offset as u64, data);
+    }
+}
+
 pub struct EmuStorage {
 }
 impl EmuStorage {
@@ -94,18 +250,20 @@ impl EmuStorage {
         for b in flashmem().memory.as_mut_slice() {
             *b = 0xFF;
         }
+        let blank = vec![0xFFu8; flashmem().memory.len()];
+        flashmem().persist(0, &blank);
     }
     pub fn dump_fs(&self, name: &Option<String>) {
         let defaultname = String::from("pddb");
         let rootname = name.as_ref().unwrap_or(&defaultname);
-        let mut f = File::create(format!("../tools/pddb-images/{}.bin", rootname)).unwrap();
+        let mut f = File::create(flashmem().image.dump_dir().join(format!("{}.bin", rootname))).unwrap();
         f.write_all(flashmem().memory.as_slice()).unwrap();
         f.flush().unwrap();
     }
     pub fn dump_keys(&self, known_keys: &[KeyExport], name: &Option<String>) {
         let defaultname = String::from("pddb");
         let rootname = name.as_ref().unwrap_or(&defaultname);
-        let mut f = File::create(format!("../tools/pddb-images/{}.key", rootname)).unwrap();
+        let mut f = File::create(flashmem().image.dump_dir().join(format!("{}.key", rootname))).unwrap();
         f.write_all(&(known_keys.len() as u32).to_le_bytes()).unwrap();
         for key in known_keys {
             f.write_all(&key.basis_name).unwrap();
@@ -130,20 +288,15 @@ impl HostedSpinor {
         ) {
             *dst = src;
         }
-        flashmem().disk.seek(SeekFrom::Start(offset as u64)).expect("couldn't seek PDDB");
-        flashmem().disk.write(data).expect("couldn't write PDDB");
+        flashmem().persist(offset as u64, data);
         Ok(())
     }
     pub fn bulk_erase(&self, start: u32, len: u32) -> Result<(), xous::Error> {
         for b in flashmem().memory.as_mut_slice()[(start - xous::PDDB_LOC) as usize .. (start - xous::PDDB_LOC + len) as usize].iter_mut() {
             *b = 0xFF;
         }
-        flashmem().disk.seek(SeekFrom::Start(start as u64)).expect("couldn't seek PDDB");
-        let mut blank = Vec::<u8>::with_capacity(len as usize);
-        for _ in 0..len {
-            blank.push(0xFF);
-        }
-        flashmem().disk.write(&blank).expect("couldn't write PDDB");
+        let blank = vec![0xFFu8; len as usize];
+        flashmem().persist((start - xous::PDDB_LOC) as u64, &blank);
         Ok(())
     }
 }
\ No newline at end of file
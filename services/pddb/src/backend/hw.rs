@@ -41,19 +41,8 @@ use perflib::*;
 #[cfg(feature="perfcounter")]
 use utralib::AtomicCsr;
 
-/// Implementation-specific PDDB structures: for Precursor/Xous OS pair
-pub(crate) const MBBB_PAGES: usize = 10;
-pub(crate) const FSCB_PAGES: usize = 16;
-
-/// size of a physical page
-pub const PAGE_SIZE: usize = spinor::SPINOR_ERASE_SIZE as usize;
-/// size of a virtual page -- after the AES encryption and journaling overhead is subtracted
-pub const VPAGE_SIZE: usize = PAGE_SIZE - size_of::<Nonce>() - size_of::<Tag>() - size_of::<JournalType>();
-
-/// length of the ciphertext in an AES-GCM-SIV page with key commitments
-/// equal to the total plaintext to be encrypted, including the journal number
-/// does not include the MAC overhead
-pub const KCOM_CT_LEN: usize = 4004;
+// `PAGE_SIZE` is fixed in `layout.rs` so the offline tools can share it; it has to match the FLASH erase size.
+const _: () = assert!(PAGE_SIZE == spinor::SPINOR_ERASE_SIZE as usize);
 
 pub(crate) const WRAPPED_AES_KEYSIZE: usize = AES_KEYSIZE + 8;
 const SCD_VERSION: u32 = 2;
@@ -202,7 +191,7 @@ impl PddbOs {
         // the mbbb is located one page off from the Page Table
         let key_phys_base = PageAlignedPa::from(size_of::<PageTableInFlash>());
         log::debug!("key_phys_base: {:x?}", key_phys_base);
-        let mbbb_phys_base = key_phys_base + PageAlignedPa::from(PAGE_SIZE);
+        let mbbb_phys_base = key_phys_base + PageAlignedPa::from(KEY_PAGES * PAGE_SIZE);
         log::debug!("mbbb_phys_base: {:x?}", mbbb_phys_base);
         let fscb_phys_base = PageAlignedPa::from(mbbb_phys_base.as_u32() + MBBB_PAGES as u32 * PAGE_SIZE as u32);
         log::debug!("fscb_phys_base: {:x?}", fscb_phys_base);
@@ -316,6 +305,11 @@ impl PddbOs {
         }
         self.pddb_mr.dump_keys(&export, &name);
     }
+    /// Offset of the data region within the PDDB image, for tests that inspect the raw image.
+    #[cfg(not(target_os = "xous"))]
+    pub(crate) fn dbg_data_base(&self) -> usize {
+        self.data_phys_base.as_usize()
+    }
     #[allow(dead_code)]
     #[cfg(any(feature="precursor", feature="renode"))]
     pub fn dbg_dump(&self, _name: Option<String>) {
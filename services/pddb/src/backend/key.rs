@@ -2,89 +2,10 @@ use crate::api::*;
 use super::*;
 
 use std::num::NonZeroU32;
-use core::ops::{Deref, DerefMut};
 use std::cmp::Ordering;
-use std::io::{Result, Error, ErrorKind};
-
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
-#[repr(C, align(8))]
-pub struct KeyName {
-    pub len: u8,
-    pub data: [u8; KEY_NAME_LEN - 1],
-}
-impl KeyName {
-    pub fn try_from_str(name: &str) -> Result<KeyName> {
-        let mut alloc = [0u8; KEY_NAME_LEN - 1];
-        let bytes = name.as_bytes();
-        if bytes.len() > (KEY_NAME_LEN - 1) {
-            Err(Error::new(ErrorKind::InvalidInput, "key name is too long"))
-        } else {
-            for (&src, dst) in bytes.iter().zip(alloc.iter_mut()) {
-                *dst = src;
-            }
-            Ok(KeyName {
-                len: bytes.len() as u8, // this as checked above to be short enough
-                data: alloc,
-            })
-        }
-    }
-}
-impl Default for KeyName {
-    fn default() -> KeyName {
-        KeyName {
-            len: 0,
-            data: [0; KEY_NAME_LEN - 1]
-        }
-    }
-}
-
-/// On-disk representation of the Key. Note that the storage on disk is mis-aligned relative
-/// to Rust's expecatation of in-RAM format, so any deserialization must essentially come with
-/// a copy step to re-align the record to meet Rust's placement rules.
-#[repr(C, align(8))]
-pub(crate) struct KeyDescriptor {
-    /// virtual address of the key's start
-    pub(crate) start: u64,
-    /// length of the key's stored data
-    pub(crate) len: u64,
-    /// amount of space reserved for the key. Must be >= len.
-    pub(crate) reserved: u64,
-    /// Reserved for flags on the record entry
-    pub(crate) flags: KeyFlags,
-    /// Access count to the key
-    pub(crate) age: u32,
-    /// Name. Length should pad out the record to exactly 127 bytes.
-    pub(crate) name: KeyName,
-}
-impl Default for KeyDescriptor {
-    fn default() -> Self {
-        KeyDescriptor {
-            start: 0,
-            len: 0,
-            reserved: 0,
-            flags: KeyFlags(0),
-            age: 0,
-            name: KeyName::default(),
-        }
-    }
-}
-impl Deref for KeyDescriptor {
-    type Target = [u8];
-    fn deref(&self) -> &[u8] {
-        unsafe {
-            core::slice::from_raw_parts(self as *const KeyDescriptor as *const u8, core::mem::size_of::<KeyDescriptor>())
-                as &[u8]
-        }
-    }
-}
-impl DerefMut for KeyDescriptor {
-    fn deref_mut(&mut self) -> &mut [u8] {
-        unsafe {
-            core::slice::from_raw_parts_mut(self as *mut KeyDescriptor as *mut u8, core::mem::size_of::<KeyDescriptor>())
-                as &mut [u8]
-        }
-    }
-}
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use aes_gcm_siv::Aes256GcmSiv;
 
 /// In-RAM representation of a key. This file defines the storage for the KeyCacheEntry; most of the structure
 /// manipulations happen inside `dictionary.rs`, in part because to locate a Key in absolute memory space you need
@@ -142,19 +63,87 @@ impl KeyCacheEntry {
             None => 0,
             Some(kcd) => match kcd {
                 KeyCacheData::Small(ksd) => ksd.data.len(),
-                KeyCacheData::Large(kld) => kld.data.len(),
+                KeyCacheData::Large(kld) => kld.size(),
             }
         };
         core::mem::size_of::<KeyCacheEntry>() + data_size
     }
     pub(crate) fn atime(&self) -> u64 { self.atime }
     pub(crate) fn set_atime(&mut self, atime: u64) { self.atime = atime; }
+
+    /// Returns the decrypted large-pool page at `page_offset`, which is a VPAGE-aligned offset relative to
+    /// the start of the key. The returned slice includes the journal number at the top of the page.
+    ///
+    /// On a miss, the requested page and up to LARGE_CACHE_PAGES - 1 pages after it are read in, stopping
+    /// early at the end of the reservation or at the first page that has not been initialized. Nothing is
+    /// evicted here: the total across all keys is bounded by `BasisCache::large_cache_trim()`. Returns None
+    /// if the requested page is not mapped, or has never been written.
+    pub(crate) fn large_page(&mut self, hw: &mut PddbOs, v2p_map: &HashMap::<VirtAddr, PhysPage>, cipher: &Aes256GcmSiv,
+        aad: &[u8], page_offset: u64
+    ) -> Option<&[u8]> {
+        assert!(page_offset % VPAGE_SIZE as u64 == 0, "large page requests must be VPAGE aligned");
+        if !matches!(&self.data, Some(KeyCacheData::Large(_))) {
+            self.data = Some(KeyCacheData::Large(KeyLargeData { clean: true, pages: BTreeMap::new() }));
+        }
+        let kld = match &mut self.data {
+            Some(KeyCacheData::Large(kld)) => kld,
+            _ => unreachable!(),
+        };
+        if !kld.pages.contains_key(&page_offset) {
+            let mut vpage = page_offset;
+            while vpage < self.reserved && vpage < page_offset + (LARGE_CACHE_PAGES * VPAGE_SIZE) as u64 {
+                if !kld.pages.contains_key(&vpage) {
+                    let pp = match v2p_map.get(&VirtAddr::new(self.start + vpage).unwrap()) {
+                        Some(pp) => pp,
+                        None => break,
+                    };
+                    assert!(pp.valid(), "v2p returned an invalid page");
+                    match hw.data_decrypt_page(cipher, aad, pp) {
+                        Some(page) => kld.pages.insert(vpage, LargePage { atime: large_page_tick(), data: page }),
+                        None => break,
+                    };
+                }
+                vpage += VPAGE_SIZE as u64;
+            }
+        }
+        let page = kld.pages.get_mut(&page_offset)?;
+        page.atime = large_page_tick();
+        Some(&page.data)
+    }
+    /// Call after a large-pool page at `page_offset` has been written to disk, with the plaintext
+    /// (including the updated journal number) that was written. If the page is cached, the cached copy
+    /// is replaced, keeping the cache coherent with the disk.
+    pub(crate) fn large_page_written(&mut self, page_offset: u64, page: &[u8]) {
+        if let Some(KeyCacheData::Large(kld)) = &mut self.data {
+            if let Some(cached) = kld.pages.get_mut(&page_offset) {
+                cached.data.copy_from_slice(page);
+                cached.atime = large_page_tick();
+            }
+        }
+    }
+    /// Drops the cached large-pool page at `page_offset`, returning the number of bytes freed.
+    pub(crate) fn large_page_evict(&mut self, page_offset: u64) -> usize {
+        if let Some(KeyCacheData::Large(kld)) = &mut self.data {
+            if let Some(page) = kld.pages.remove(&page_offset) {
+                if kld.pages.is_empty() {
+                    self.data = None;
+                }
+                return page.data.len();
+            }
+        }
+        0
+    }
+}
+
+/// Orders accesses to large-pool pages across all keys, so the least recently used pages can be evicted first.
+static LARGE_PAGE_CLOCK: AtomicU64 = AtomicU64::new(0);
+fn large_page_tick() -> u64 {
+    LARGE_PAGE_CLOCK.fetch_add(1, AtomicOrdering::SeqCst)
 }
 
 pub (crate) enum KeyCacheData {
     Small(KeySmallData),
     // the "Medium" type has a region reserved for it, but we haven't coded a handler for it.
-    #[allow(dead_code)] // Large data caching isn't implemented, so of course, we don't ever create this type
     Large(KeyLargeData),
 }
 /// Small data is optimized for low overhead, and always represent a complete copy of the data.
@@ -162,13 +151,23 @@ pub(crate) struct KeySmallData {
     pub clean: bool,
     pub(crate) data: Vec::<u8>,
 }
-/// This can hold just a portion of a large key's data. For now, we now essentially manually
-/// encode a sub-slice in parts, but, later on we could get more clever and start to cache
-/// multiple disjoint portions of a large key's data...
-#[allow(dead_code)] // large key caching is not yet implemented
+/// This holds just a portion of a large key's data: whichever of its decrypted pages are still in the
+/// cache. The pages of all large keys share one LRU, bounded by LARGE_CACHE_SIZE.
 pub(crate) struct KeyLargeData {
+    /// writes to large keys go straight through to disk, so this is currently always `true`
     pub clean: bool,
-    pub(crate) start: u64,
+    /// the cached pages, indexed by their VPAGE-aligned offset relative to the start of the key
+    pub(crate) pages: BTreeMap::<u64, LargePage>,
+}
+impl KeyLargeData {
+    pub(crate) fn size(&self) -> usize {
+        self.pages.values().map(|page| page.data.len()).sum()
+    }
+}
+/// One decrypted large-pool page: VPAGE_SIZE plus the journal number at the top of the page.
+pub(crate) struct LargePage {
+    /// position on `LARGE_PAGE_CLOCK` of the last access, for LRU eviction
+    pub(crate) atime: u64,
     pub(crate) data: Vec::<u8>,
 }
 
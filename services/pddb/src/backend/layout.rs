@@ -0,0 +1,579 @@
+//! On-disk layout of the PDDB: page geometry, the virtual memory map of a basis, and the records
+//! that are stored in FLASH. This file is also pulled into `tools/src/pddb.rs` with `#[path]`, so
+//! that the offline image decoder can't drift from the backend. Keep it free of anything that
+//! needs the Xous runtime: the only things it takes from its parent module are `murmur3_32()`
+//! and the name limits and version number from `api/format.rs`.
+
+use super::{murmur3_32, BASIS_NAME_LEN, DICT_NAME_LEN, KEY_NAME_LEN, PDDB_VERSION, KeyFlags};
+use core::mem::size_of;
+use core::num::NonZeroU64;
+use core::ops::{Deref, DerefMut};
+use core::convert::TryInto;
+use std::hash::{Hash, Hasher};
+use std::cmp::Ordering;
+use std::io::{Result, Error, ErrorKind};
+use aes_gcm_siv::{Nonce, Tag};
+use bitfield::bitfield;
+use bitflags::bitflags;
+
+/// Implementation-specific PDDB structures: for Precursor/Xous OS pair
+/// the key store, between the page table and the MBBB: the wrapped system basis keys and the basis salt
+pub(crate) const KEY_PAGES: usize = 1;
+pub(crate) const MBBB_PAGES: usize = 10;
+pub(crate) const FSCB_PAGES: usize = 16;
+
+/// size of a physical page
+/// (`hw.rs` checks that this matches `spinor::SPINOR_ERASE_SIZE`)
+pub const PAGE_SIZE: usize = 4096;
+/// size of a virtual page -- after the AES encryption and journaling overhead is subtracted
+pub const VPAGE_SIZE: usize = PAGE_SIZE - size_of::<Nonce>() - size_of::<Tag>() - size_of::<JournalType>();
+
+/// length of the ciphertext in an AES-GCM-SIV page with key commitments
+/// equal to the total plaintext to be encrypted, including the journal number
+/// does not include the MAC overhead
+pub const KCOM_CT_LEN: usize = 4004;
+
+/// Each free_pool entry takes about 4 bytes, so give-or-take we have about 1000 free_pool
+/// entries per page of storage for the free_pool, or 4k * 1000 ~ 4MiB per page, when PhysAddr is a u32
+pub(crate) const FASTSPACE_PAGES: usize = 2;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u8)]
+pub enum SpaceState {
+    /// pages that are completely un-spoken for
+    Free = 0,
+    /// pages that are in the process of being used, but the journal has yet to be committed
+    /// in other words, these are pages that might be in the RAM cache.
+    MaybeUsed = 1,
+    /// pages that are confirm plus chop fully used
+    Used = 2,
+    /// pages that are no longer used and need to be erased
+    Dirty = 3,
+}
+impl From<u8> for SpaceState {
+    fn from(arg: u8) -> Self {
+        match arg & 0x3 {
+            0 => SpaceState::Free,
+            1 => SpaceState::MaybeUsed,
+            2 => SpaceState::Used,
+            _ => SpaceState::Dirty,
+        }
+    }
+}
+impl From<SpaceState> for u8 {
+    fn from(arg: SpaceState) -> Self {
+        arg as u8
+    }
+}
+
+pub(crate) const SMALL_POOL_START: u64 = 0x0000_003F_8000_0000;
+pub(crate) const SMALL_POOL_END: u64 = 0x0000_007E_FF02_0000;
+pub(crate) const SMALL_POOL_STRIDE: u64 = 0xFE_0000;
+pub(crate) const LARGE_POOL_START: u64 = 0x0000_FE00_0000_0000;
+pub(crate) const KEY_MAXCOUNT: usize = 131_071; // 2^17 - 1
+
+/// The chosen "stride" of a dict/key entry. Drives a lot of key parameters in the database's characteristics.
+/// This is chosen such that 32 of these entries fit evenly into a VPAGE.
+pub(crate) const DK_STRIDE: usize = 127;
+//// DK_STRIDES per VPAGE
+pub(crate) const DK_PER_VPAGE: usize = VPAGE_SIZE / DK_STRIDE; // should be 32 - use this for computing modulus on dictionary indices
+/// size of a dictionary region in virtual memory
+pub(crate) const DICT_VSIZE: u64 = 0xFE_0000;
+/// maximum number of dictionaries in a system
+pub(crate) const DICT_MAXCOUNT: usize = 16383;
+
+/// This has to be manually synchronized with the bit range of the `journal` field below. It doesn't look like
+/// there is a good way to automatically derive this.
+pub(crate) const PHYS_PAGE_JOURNAL_MAX: u8 = 15;
+/// We should be able to change this to a u64 and everything should "just work", but
+/// we'd end up using 2x the amount of data for overhead and bookkeeping.
+#[cfg(not(feature = "u64_pa"))]
+pub type PhysAddr = u32;
+#[cfg(feature = "u64_pa")]
+pub type PhysAddr = u64;
+const BITFIELD_PAGE_WIDTH: usize = core::mem::size_of::<PhysAddr>() * 8 - 12; // "12" should be log2(PAGE_SIZE) but https://github.com/rust-lang/rust/issues/70887
+// Physical page information, coded as a bitfield, because space is a premium!
+bitfield! {
+    #[derive(Copy, Clone, Eq)]
+    pub struct PhysPage(PhysAddr);
+    impl Debug;
+    pub page_number, set_page_number: BITFIELD_PAGE_WIDTH - 1, 0;
+    // this is only used by the page table mechanism
+    pub clean, set_clean: BITFIELD_PAGE_WIDTH + 0;
+    // when set, indicates that the record contents are valid and should be used
+    // when cleared, the record contents are invalid and should be ignored.
+    // valid is used by both FastSpace and the page table mechanism. Note that we rely upon the mapping of 0->not valid.
+    pub valid, set_valid: BITFIELD_PAGE_WIDTH + 1;
+    // these are only used by the FastSpace mechanism; they have no meaning in other contexts
+    pub u8, from into SpaceState, space_state, set_space_state: BITFIELD_PAGE_WIDTH + 3, BITFIELD_PAGE_WIDTH + 2;
+    // 4 bits for a journal revision. Intended for the FastSpace mechanism
+    pub u8, journal, set_journal: BITFIELD_PAGE_WIDTH + 7, BITFIELD_PAGE_WIDTH + 4;
+}
+// hashes should only key off of the page number, not the metadata
+impl Hash for PhysPage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.page_number().hash(state);
+    }
+}
+impl PartialEq for PhysPage {
+    fn eq(&self, other: &Self) -> bool {
+        self.page_number() == other.page_number()
+    }
+}
+impl Ord for PhysPage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.page_number().cmp(&other.page_number())
+    }
+}
+impl PartialOrd for PhysPage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Storage for journal revisions.
+pub type JournalType = u32;
+
+/// A Virtual Address is 48 bits long. The top 16 bits are required to be blank
+/// so that they may be used as flags in the on-disk storage format.
+/// Virtual pages are shorter than physical pages, due to the overhead of
+/// the nonce + tag + journal entry used to store data on disk.
+/// We make the VirtAddr a NonZeroU64 so that we can apply a None option to it "for free"
+/// in packed disk representations.
+pub type VirtAddr = NonZeroU64;
+
+/// a 128-bit record that stores an encrypted update to the FastSpace pool, facilitating the "rarely" update property of the structure.
+#[repr(C, packed)]
+#[cfg(not(feature = "u64_pa"))]
+pub (crate) struct SpaceUpdate {
+    nonce: u64,
+    page_number: PhysPage,
+    // this checksum is "weak" but we are protecting against two scenarios:
+    // 1. partially written SpaceUpdate record (so the last bytes or so are FF)
+    // 2. a malicious attacker
+    // In the case of (1), the occurence should be diminishingly small (expected to never happen, maybe
+    // a very unstable system that's "blinking" power constantly would have it occure a few times)
+    // In the case of (2), an attacker has a chance of generating a collision, but the result is
+    // also unlikely to generate a valid PhysAddr, and if it does, the consequence is some valid data
+    // being treated as free space and getting erased (data loss, not disclosure).
+    checksum: [u8; 4],
+}
+#[cfg(not(feature = "u64_pa"))]
+impl SpaceUpdate {
+    pub fn try_into_phys_page(slice: &[u8]) -> Option<PhysPage> {
+        // note that the seed uses big-endian re-encoding of a portion of the nonce!
+        let computed_sum = murmur3_32(&slice[..12], u32::from_be_bytes(slice[4..8].try_into().unwrap()));
+        if u32::from_le_bytes(slice[12..].try_into().unwrap()) == computed_sum {
+            let pp = u32::from_le_bytes(slice[8..12].try_into().unwrap());
+            Some(PhysPage(pp))
+        } else {
+            None
+        }
+    }
+    pub fn new(nonce: u64, page_number: PhysPage) -> Self {
+        let mut hashbuf: [u8; 12] = [0; 12];
+        for (&src, dst) in nonce.to_le_bytes().iter().zip(hashbuf[..8].iter_mut()) {
+            *dst = src;
+        }
+        for (&src, dst) in page_number.0.to_le_bytes().iter().zip(hashbuf[8..12].iter_mut()) {
+            *dst = src;
+        }
+        let computed_sum = murmur3_32(&hashbuf[..12], u32::from_be_bytes(hashbuf[4..8].try_into().unwrap()));
+        SpaceUpdate {
+            nonce,
+            page_number,
+            checksum: computed_sum.to_le_bytes(),
+        }
+    }
+}
+
+#[cfg(feature = "u64_pa")]
+pub (crate) struct SpaceUpdate {
+    nonce: u64,
+    page_number: PhysPage,
+    // consider: using the top 12 bits of the PhysAddr as a checksum
+}
+
+impl Deref for SpaceUpdate {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self as *const SpaceUpdate as *const u8, core::mem::size_of::<SpaceUpdate>())
+                as &[u8]
+        }
+    }
+}
+impl DerefMut for SpaceUpdate {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self as *mut SpaceUpdate as *mut u8, core::mem::size_of::<SpaceUpdate>())
+                as &mut [u8]
+        }
+    }
+}
+
+bitflags! {
+    /// flags used by the page table
+    pub struct PtFlags: u8 {
+        /// Pages that don't decrypt properly are marked as INVALID in the cache.
+        const  INVALID            = 0b0000_0000;
+        /// set for records that are synced to the copy in Flash. Every valid record
+        /// from Flash should have this set; it should only be cleared for blocks in Cache.
+        const  CLEAN              = 0b0000_0001;
+        /// set for records that are confirmed to be valid through a subsequent decryption op.
+        /// This flag exists because there is a chance that the 32-bit checksum used to protect
+        /// a page table entry experiences a collision.
+        const CHECKED             = 0b0000_0010;
+    }
+}
+impl Default for PtFlags {
+    fn default() -> PtFlags {PtFlags::INVALID}
+}
+
+/// A Page Table Entry. Must be equal in length to one AES block size (128 bits).
+/// This is stored in the FLASH itself, so size is not as much of a constraint.
+///
+/// Contains the address map of the corresponding entry,
+/// plus a nonce, and a checksum. Due to the Page Table being deliberately
+/// srtuctured to have invalid entries that don't decrypt correctly, you
+/// can't use a chaining approach. Thus these entries are encrypted closer to
+/// an ECB-style, thus an embedded nonce is necessary to keep identical entries
+/// from appearing the same in the ciphertext domain.
+///
+/// It's not clear at all if the nonce is large enough to prevent random collisions;
+/// however, the sheer bulk of the page table demands a compact representation. Thus,
+/// any routines downstream of the Pte shall be coded to handle potentially a much larger
+/// nonce and checksum structure.
+#[repr(packed)]
+#[derive(Default)]
+pub(crate) struct Pte {
+    /// the virtual page number is 52 bits long (52 + 12 = 64). 4 bits are wasted in this representation.
+    /// The storage format is in *page numbers* but the API accepts *addresses*. Therefore a division and
+    /// multiplication by VPAGE_SIZE wraps the getters and setters for this field.
+    pub(crate) pddb_addr: [u8; 7],
+    /// this maps to a u8
+    pub(crate) flags: PtFlags,
+    /// 32-bit strength of a nonce, but can be varied
+    pub(crate) nonce: [u8; 4],
+    /// 32-bit "weak" checksum, used only for quick scans of the PTE to determine a coarse "in" or "out" classifier
+    /// checksum is computed on all of the bits prior, so checksum(pddb_addr, flags, nonce)
+    pub(crate) checksum: [u8; 4],
+}
+impl Pte {
+    pub fn vaddr(&self) -> VirtAddr {
+        let mut full_addr = [0u8; 8];
+        // LSB encoded, so this loop deposits the partial pddb_addr in the LSBs, and the MSBs are correctly 0 from above initializer
+        for (&src, dst) in self.pddb_addr.iter().zip(full_addr.iter_mut()) {
+            *dst = src;
+        }
+        VirtAddr::new(u64::from_le_bytes(full_addr) * VPAGE_SIZE as u64).unwrap()
+    }
+
+    #[allow(dead_code)]
+    pub fn flags(&self) -> PtFlags {
+        self.flags
+    }
+
+    pub fn try_from_slice(slice: &[u8]) -> Option<Self> {
+        if slice.len() == size_of::<Pte>() {
+            let mut maybe_pt = Pte::default();
+            for (&src, dst) in slice.iter().zip(maybe_pt.deref_mut().iter_mut()) {
+                *dst = src;
+            }
+            let nonce_u32 = u32::from_le_bytes(maybe_pt.nonce);
+            if u32::from_le_bytes(maybe_pt.checksum) == murmur3_32(&slice[..12], nonce_u32) {
+                Some(maybe_pt)
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+}
+impl Deref for Pte {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self as *const Pte as *const u8, core::mem::size_of::<Pte>())
+                as &[u8]
+        }
+    }
+}
+
+impl DerefMut for Pte {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self as *mut Pte as *mut u8, core::mem::size_of::<Pte>())
+                as &mut [u8]
+        }
+    }
+}
+
+/// This is the format of the Basis as stored on disk
+#[derive(PartialEq, Debug, Default)]
+#[repr(C, align(8))]
+pub(crate) struct BasisRoot {
+    pub(crate) magic: [u8; 4],
+    pub(crate) version: u32,
+    /// increments every time the BasisRoot is modified. This field must saturate, not roll over.
+    pub(crate) age: u32,
+    /// number of dictionaries.
+    pub(crate) num_dictionaries: u32,
+    /* at this point, we are aligned to a 64-bit boundary. All data must stay aligned to this boundary from here out! */
+    /// 64-byte name; aligns to 64-bits
+    pub(crate) name: BasisRootName,
+}
+impl BasisRoot {
+    pub(crate) fn aad(&self, dna: u64) -> Vec::<u8> {
+        let mut aad = Vec::<u8>::new();
+        aad.extend_from_slice(&self.name.data[..self.name.len as usize]);
+        aad.extend_from_slice(&PDDB_VERSION.to_le_bytes());
+        aad.extend_from_slice(&dna.to_le_bytes());
+        aad
+    }
+}
+impl Deref for BasisRoot {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self as *const BasisRoot as *const u8, core::mem::size_of::<BasisRoot>())
+                as &[u8]
+        }
+    }
+}
+impl DerefMut for BasisRoot {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self as *mut BasisRoot as *mut u8, core::mem::size_of::<BasisRoot>())
+                as &mut [u8]
+        }
+    }
+}
+
+/// Newtype for BasisRootName so we can give it a default initializer.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct BasisRootName {
+    pub len: u8,
+    pub data: [u8; BASIS_NAME_LEN - 1],
+}
+impl BasisRootName {
+    pub fn try_from_str(name: &str) -> Result<BasisRootName> {
+        let mut alloc = [0u8; BASIS_NAME_LEN - 1];
+        let bytes = name.as_bytes();
+        if bytes.len() > (BASIS_NAME_LEN - 1) {
+            Err(Error::new(ErrorKind::InvalidInput, "basis name is too long")) // FileNameTooLong is still nightly :-/
+        } else {
+            for (&src, dst) in bytes.iter().zip(alloc.iter_mut()) {
+                *dst = src;
+            }
+            Ok(BasisRootName {
+                len: bytes.len() as u8, // this as checked above to be short enough
+                data: alloc,
+            })
+        }
+    }
+}
+impl Default for BasisRootName {
+    fn default() -> BasisRootName {
+        BasisRootName{
+            len: 0,
+            data: [0; BASIS_NAME_LEN - 1]
+        }
+    }
+}
+
+bitfield! {
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub struct DictFlags(u32);
+    impl Debug;
+    pub valid, set_valid: 0;
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[repr(C, align(8))]
+pub struct DictName {
+    pub len: u8,
+    pub data: [u8; DICT_NAME_LEN - 1],
+}
+impl DictName {
+    pub fn try_from_str(name: &str) -> Result<DictName> {
+        let mut alloc = [0u8; DICT_NAME_LEN - 1];
+        let bytes = name.as_bytes();
+        if bytes.len() > (DICT_NAME_LEN - 1) {
+            Err(Error::new(ErrorKind::InvalidInput, "dict name is too long"))
+        } else {
+            for (&src, dst) in bytes.iter().zip(alloc.iter_mut()) {
+                *dst = src;
+            }
+            Ok(DictName {
+                len: bytes.len() as u8, // this as checked above to be short enough
+                data: alloc,
+            })
+        }
+    }
+}
+impl Default for DictName {
+    fn default() -> DictName {
+        DictName {
+            len: 0,
+            data: [0; DICT_NAME_LEN - 1]
+        }
+    }
+}
+
+#[derive(Debug)]
+/// On-disk representation of the dictionary header. This structure is mainly for archival/unarchival
+/// purposes. To "functionalize" a stored disk entry, it needs to be deserialized into a DictionaryCacheEntry.
+#[repr(C, align(8))]
+pub(crate) struct Dictionary {
+    /// Reserved for flags on the record entry
+    pub(crate) flags: DictFlags,
+    /// Access count to the dicitionary
+    pub(crate) age: u32,
+    /// Number of keys in the dictionary
+    pub(crate) num_keys: u32,
+    /// Free index starting space. While this is a derived parameter, its value is recorded to avoid
+    /// an expensive, long search operation during the creation of a dictionary cache record. 0 is an invalid index,
+    /// as this is where the header goes. Maybe this should be a NonZeroU32.
+    pub(crate) free_key_index: u32,
+    /// Name. Length should pad out the record to exactly 127 bytes.
+    pub(crate) name: DictName,
+}
+impl Default for Dictionary {
+    fn default() -> Dictionary {
+        let mut flags = DictFlags(0);
+        flags.set_valid(true);
+        Dictionary { flags, age: 0, num_keys: 0, free_key_index: 1, name: DictName::default() }
+    }
+}
+impl Deref for Dictionary {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self as *const Dictionary as *const u8, core::mem::size_of::<Dictionary>())
+                as &[u8]
+        }
+    }
+}
+impl DerefMut for Dictionary {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self as *mut Dictionary as *mut u8, core::mem::size_of::<Dictionary>())
+                as &mut [u8]
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[repr(C, align(8))]
+pub struct KeyName {
+    pub len: u8,
+    pub data: [u8; KEY_NAME_LEN - 1],
+}
+impl KeyName {
+    pub fn try_from_str(name: &str) -> Result<KeyName> {
+        let mut alloc = [0u8; KEY_NAME_LEN - 1];
+        let bytes = name.as_bytes();
+        if bytes.len() > (KEY_NAME_LEN - 1) {
+            Err(Error::new(ErrorKind::InvalidInput, "key name is too long"))
+        } else {
+            for (&src, dst) in bytes.iter().zip(alloc.iter_mut()) {
+                *dst = src;
+            }
+            Ok(KeyName {
+                len: bytes.len() as u8, // this as checked above to be short enough
+                data: alloc,
+            })
+        }
+    }
+}
+impl Default for KeyName {
+    fn default() -> KeyName {
+        KeyName {
+            len: 0,
+            data: [0; KEY_NAME_LEN - 1]
+        }
+    }
+}
+
+/// On-disk representation of the Key. Note that the storage on disk is mis-aligned relative
+/// to Rust's expecatation of in-RAM format, so any deserialization must essentially come with
+/// a copy step to re-align the record to meet Rust's placement rules.
+#[repr(C, align(8))]
+pub(crate) struct KeyDescriptor {
+    /// virtual address of the key's start
+    pub(crate) start: u64,
+    /// length of the key's stored data
+    pub(crate) len: u64,
+    /// amount of space reserved for the key. Must be >= len.
+    pub(crate) reserved: u64,
+    /// Reserved for flags on the record entry
+    pub(crate) flags: KeyFlags,
+    /// Access count to the key
+    pub(crate) age: u32,
+    /// Name. Length should pad out the record to exactly 127 bytes.
+    pub(crate) name: KeyName,
+}
+impl Default for KeyDescriptor {
+    fn default() -> Self {
+        KeyDescriptor {
+            start: 0,
+            len: 0,
+            reserved: 0,
+            flags: KeyFlags(0),
+            age: 0,
+            name: KeyName::default(),
+        }
+    }
+}
+impl Deref for KeyDescriptor {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self as *const KeyDescriptor as *const u8, core::mem::size_of::<KeyDescriptor>())
+                as &[u8]
+        }
+    }
+}
+impl DerefMut for KeyDescriptor {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self as *mut KeyDescriptor as *mut u8, core::mem::size_of::<KeyDescriptor>())
+                as &mut [u8]
+        }
+    }
+}
+
+/// One record of the `.key` file written next to an image by `dbg_dump()` in hosted mode.
+#[cfg(not(target_os = "xous"))]
+#[derive(Copy, Clone)]
+pub struct KeyExport {
+    pub basis_name: [u8; 64],
+    /// data key
+    pub key: [u8; 32],
+    /// page table key
+    pub pt_key: [u8; 32],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_journal_range() {
+        let pp = PhysPage(u32::MAX);
+        println!("pp.journal(): {}", pp.journal());
+        assert!(pp.journal() == PHYS_PAGE_JOURNAL_MAX, "PHYS_PAGE_JOURNAL_MAX is incorrect");
+    }
+    #[test]
+    fn test_record_sizes() {
+        assert_eq!(size_of::<Pte>(), 16);
+        #[cfg(not(feature = "u64_pa"))]
+        assert_eq!(size_of::<SpaceUpdate>(), 16);
+        assert_eq!(DK_PER_VPAGE * DK_STRIDE, VPAGE_SIZE);
+        // the records are padded out to an 8-byte alignment in RAM, but only DK_STRIDE bytes go to disk
+        assert!(size_of::<Dictionary>() >= DK_STRIDE && size_of::<Dictionary>() < DK_STRIDE + 8);
+        assert!(size_of::<KeyDescriptor>() >= DK_STRIDE && size_of::<KeyDescriptor>() < DK_STRIDE + 8);
+        assert_eq!(KCOM_CT_LEN + size_of::<Nonce>() + 32 + 32 + size_of::<Tag>(), PAGE_SIZE);
+    }
+}
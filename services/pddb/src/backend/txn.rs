@@ -0,0 +1,236 @@
+use crate::api::*;
+use super::*;
+
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::io::{Result, Error, ErrorKind};
+
+/// Dictionary that holds the journal of a transaction while it is being applied. Finding it in a
+/// basis at mount time means a commit was interrupted.
+pub(crate) const TXN_JOURNAL_DICT: &'static str = "__pddb.txn";
+pub(crate) const TXN_JOURNAL_KEY: &'static str = "journal";
+const TXN_JOURNAL_MAGIC: [u8; 4] = *b"PTXN";
+
+/*
+Transactions are implemented with a redo journal, stored as an ordinary key in the basis being
+modified. The journal contains both the ops to apply (redo) and the before-images of every key
+they touch (undo):
+
+  magic | u32 redo len | redo ops | u32 undo len | undo ops | u32 murmur3 of everything preceding
+
+Commit goes like this:
+  1. The before-images are captured.
+  2. The journal is written and synced. This is the commit point: a journal that reads back with a
+     good checksum is always completed. One that doesn't was torn before the commit point, and at
+     that point no user data has been touched yet, so it is simply discarded.
+  3. The ops are applied, each with the usual dict_sync/basis_sync/pt_sync sequence. Every op
+     replaces or removes a key outright, so re-applying them after an interruption is harmless.
+  4. The journal dictionary is removed.
+
+If applying the ops fails (e.g. the disk fills up) the undo ops are applied instead, so the basis
+is left as it was before the commit.
+*/
+
+fn journal_encode(redo: &[TxnOp], undo: &[TxnOp]) -> Vec<u8> {
+    let redo = txn_encode(redo);
+    let undo = txn_encode(undo);
+    let mut journal = Vec::with_capacity(TXN_JOURNAL_MAGIC.len() + 4 + redo.len() + 4 + undo.len() + 4);
+    journal.extend_from_slice(&TXN_JOURNAL_MAGIC);
+    journal.extend_from_slice(&(redo.len() as u32).to_le_bytes());
+    journal.extend_from_slice(&redo);
+    journal.extend_from_slice(&(undo.len() as u32).to_le_bytes());
+    journal.extend_from_slice(&undo);
+    let checksum = journal_checksum(&journal);
+    journal.extend_from_slice(&checksum.to_le_bytes());
+    journal
+}
+
+fn journal_decode(journal: &[u8]) -> Option<(Vec<TxnOp>, Vec<TxnOp>)> {
+    if journal.len() < TXN_JOURNAL_MAGIC.len() + 12 || journal[..TXN_JOURNAL_MAGIC.len()] != TXN_JOURNAL_MAGIC {
+        return None;
+    }
+    let (body, checksum) = journal.split_at(journal.len() - 4);
+    if journal_checksum(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return None;
+    }
+    let mut index = TXN_JOURNAL_MAGIC.len();
+    let redo_len = u32::from_le_bytes(body.get(index..index + 4)?.try_into().unwrap()) as usize;
+    index += 4;
+    let redo = txn_decode(body.get(index..index.checked_add(redo_len)?)?)?;
+    index += redo_len;
+    let undo_len = u32::from_le_bytes(body.get(index..index + 4)?.try_into().unwrap()) as usize;
+    index += 4;
+    let undo = txn_decode(body.get(index..index.checked_add(undo_len)?)?)?;
+    Some((redo, undo))
+}
+
+fn journal_checksum(data: &[u8]) -> u32 {
+    // murmur3 only takes whole words
+    let mut padded = data.to_vec();
+    while padded.len() % 4 != 0 {
+        padded.push(0);
+    }
+    murmur3_32(&padded, 0)
+}
+
+impl BasisCache {
+    /// Applies `ops` to a single basis, atomically: after a crash or power loss at any point, the
+    /// basis will either show all of the ops, or none of them, once `txn_recover()` has run. If
+    /// `basis_name` is None, the most recently opened basis is used.
+    pub(crate) fn txn_commit(&mut self, hw: &mut PddbOs, ops: &[TxnOp], basis_name: Option<&str>) -> Result<()> {
+        let basis = match basis_name.or(self.basis_latest()) {
+            Some(name) if self.basis_contains(name) => name.to_string(),
+            _ => return Err(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted.")),
+        };
+        if ops.iter().any(|op| op.dict() == TXN_JOURNAL_DICT) {
+            return Err(Error::new(ErrorKind::InvalidInput, "transactions can't modify the transaction journal"));
+        }
+        // a leftover journal has to be resolved first, as there is only room for one
+        self.txn_recover(hw, &basis)?;
+
+        let undo = self.txn_undo_log(hw, ops, &basis)?;
+        let journal = journal_encode(ops, &undo);
+        if let Err(e) = self.key_update(hw, TXN_JOURNAL_DICT, TXN_JOURNAL_KEY, &journal, None, None, Some(&basis), true) {
+            // nothing has been applied yet, so there is nothing to roll back
+            self.dict_remove(hw, TXN_JOURNAL_DICT, Some(&basis), true).ok();
+            return Err(e);
+        }
+        let result = match self.txn_apply(hw, ops, &basis) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                log::error!("Transaction could not be applied to basis {}, rolling back: {:?}", basis, e);
+                // if the rollback itself fails, the journal stays put and the next mount will retry
+                self.txn_apply(hw, &undo, &basis)?;
+                Err(e)
+            }
+        };
+        // the journal holds before-images of the keys, so it gets the paranoid treatment
+        self.dict_remove(hw, TXN_JOURNAL_DICT, Some(&basis), true)?;
+        result
+    }
+
+    /// Completes or discards a transaction that was interrupted in `basis_name`. This should be called
+    /// every time a basis is mounted, before it is handed out to clients. Returns `true` if there was
+    /// a journal to resolve.
+    pub(crate) fn txn_recover(&mut self, hw: &mut PddbOs, basis_name: &str) -> Result<bool> {
+        if self.dict_attributes(hw, TXN_JOURNAL_DICT, Some(basis_name)).is_err() {
+            return Ok(false);
+        }
+        let journal = match self.key_attributes(hw, TXN_JOURNAL_DICT, TXN_JOURNAL_KEY, Some(basis_name)) {
+            Ok(attr) => {
+                let mut data = vec![0u8; attr.len];
+                match self.key_read(hw, TXN_JOURNAL_DICT, TXN_JOURNAL_KEY, &mut data, Some(0), Some(basis_name)) {
+                    Ok(len) => {
+                        data.truncate(len);
+                        journal_decode(&data)
+                    }
+                    Err(_) => None,
+                }
+            }
+            Err(_) => None,
+        };
+        match journal {
+            Some((redo, undo)) => {
+                log::warn!("Completing an interrupted transaction in basis {}", basis_name);
+                if let Err(e) = self.txn_apply(hw, &redo, basis_name) {
+                    log::error!("Couldn't complete the interrupted transaction, rolling it back: {:?}", e);
+                    self.txn_apply(hw, &undo, basis_name)?;
+                }
+            }
+            None => log::warn!("Discarding an incomplete transaction journal in basis {}", basis_name),
+        }
+        self.dict_remove(hw, TXN_JOURNAL_DICT, Some(basis_name), true)?;
+        Ok(true)
+    }
+
+    /// Captures the state of every key touched by `ops`, as a list of ops that restores it.
+    fn txn_undo_log(&mut self, hw: &mut PddbOs, ops: &[TxnOp], basis_name: &str) -> Result<Vec<TxnOp>> {
+        let mut undo = Vec::<TxnOp>::new();
+        let mut new_dicts = Vec::<String>::new();
+        let mut seen = HashSet::<(String, String)>::new();
+        for op in ops.iter() {
+            let key = match op.key() {
+                Some(key) => key,
+                None => return Err(Error::new(ErrorKind::InvalidInput, "dictionaries can't be removed in a transaction")),
+            };
+            let dict = op.dict();
+            // only the state before the first op on a key matters
+            if !seen.insert((dict.to_string(), key.to_string())) {
+                continue;
+            }
+            if self.dict_attributes(hw, dict, Some(basis_name)).is_err() {
+                // removing the dictionary also takes care of any keys created in it
+                if !new_dicts.iter().any(|d| d == dict) {
+                    new_dicts.push(dict.to_string());
+                }
+                continue;
+            }
+            match self.key_attributes(hw, dict, key, Some(basis_name)) {
+                Ok(attr) => {
+                    let mut data = vec![0u8; attr.len];
+                    let len = self.key_read(hw, dict, key, &mut data, Some(0), Some(basis_name))?;
+                    data.truncate(len);
+                    undo.push(TxnOp::Write { dict: dict.to_string(), key: key.to_string(), data });
+                }
+                Err(_) => undo.push(TxnOp::Delete { dict: dict.to_string(), key: key.to_string() }),
+            }
+        }
+        for dict in new_dicts {
+            undo.push(TxnOp::RemoveDict { dict });
+        }
+        Ok(undo)
+    }
+
+    fn txn_apply(&mut self, hw: &mut PddbOs, ops: &[TxnOp], basis_name: &str) -> Result<()> {
+        for op in ops.iter() {
+            let result = match op {
+                TxnOp::Write { dict, key, data } =>
+                    self.key_update(hw, dict, key, data, None, None, Some(basis_name), true),
+                TxnOp::Delete { dict, key } =>
+                    self.key_remove(hw, dict, key, Some(basis_name), false),
+                TxnOp::RemoveDict { dict } =>
+                    self.dict_remove(hw, dict, Some(basis_name), false),
+            };
+            match result {
+                Ok(()) => {}
+                // already gone, which is the desired outcome
+                Err(e) if e.kind() == ErrorKind::NotFound && !matches!(op, TxnOp::Write { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_roundtrip() {
+        let redo = vec![
+            TxnOp::Write { dict: "vault.passwords".into(), key: "example.com".into(), data: vec![7; 33] },
+            TxnOp::Delete { dict: "vault.index".into(), key: "x".into() },
+        ];
+        let undo = vec![
+            TxnOp::Delete { dict: "vault.passwords".into(), key: "example.com".into() },
+            TxnOp::RemoveDict { dict: "vault.index".into() },
+        ];
+        let journal = journal_encode(&redo, &undo);
+        assert_eq!(journal_decode(&journal), Some((redo, undo)));
+    }
+
+    #[test]
+    fn torn_journal_is_rejected() {
+        let redo = vec![TxnOp::Write { dict: "d".into(), key: "k".into(), data: vec![1, 2, 3, 4, 5] }];
+        let journal = journal_encode(&redo, &[]);
+        for len in 0..journal.len() {
+            assert_eq!(journal_decode(&journal[..len]), None, "accepted a journal torn at {} bytes", len);
+        }
+        let mut corrupt = journal.clone();
+        corrupt[10] ^= 1;
+        assert_eq!(journal_decode(&corrupt), None);
+        // erased FLASH
+        assert_eq!(journal_decode(&vec![0xFF; journal.len()]), None);
+    }
+}
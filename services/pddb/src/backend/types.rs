@@ -1,10 +1,7 @@
-use core::num::NonZeroU64;
 use core::ops::Add;
-use super::{PAGE_SIZE, VPAGE_SIZE};
-use crate::SpaceState;
+use super::{PAGE_SIZE, VPAGE_SIZE, PhysAddr, VirtAddr};
+#[cfg(test)]
 use bitfield::bitfield;
-use std::hash::{Hash, Hasher};
-use std::cmp::Ordering;
 
 /// for the life of me, I can't figure out how to query the AES crate to give me the length of a 256-bit key.
 /// I mean, we know what it is, it's well-defined and never changes. But it'd just be nice to you know,
@@ -15,66 +12,6 @@ use std::cmp::Ordering;
 /// but maybe that's because it's constant regardless of the key size so it's easy to do.
 pub(crate) const AES_KEYSIZE: usize = 32;
 
-/// This has to be manually synchronized with the bit range of the `journal` field below. It doesn't look like
-/// there is a good way to automatically derive this.
-pub(crate) const PHYS_PAGE_JOURNAL_MAX: u8 = 15;
-/// We should be able to change this to a u64 and everything should "just work", but
-/// we'd end up using 2x the amount of data for overhead and bookkeeping.
-#[cfg(not(feature = "u64_pa"))]
-pub type PhysAddr = u32;
-#[cfg(feature = "u64_pa")]
-pub type PhysAddr = u64;
-const BITFIELD_PAGE_WIDTH: usize = core::mem::size_of::<PhysAddr>() * 8 - 12; // "12" should be log2(PAGE_SIZE) but https://github.com/rust-lang/rust/issues/70887
-// Physical page information, coded as a bitfield, because space is a premium!
-bitfield! {
-    #[derive(Copy, Clone, Eq)]
-    pub struct PhysPage(PhysAddr);
-    impl Debug;
-    pub page_number, set_page_number: BITFIELD_PAGE_WIDTH - 1, 0;
-    // this is only used by the page table mechanism
-    pub clean, set_clean: BITFIELD_PAGE_WIDTH + 0;
-    // when set, indicates that the record contents are valid and should be used
-    // when cleared, the record contents are invalid and should be ignored.
-    // valid is used by both FastSpace and the page table mechanism. Note that we rely upon the mapping of 0->not valid.
-    pub valid, set_valid: BITFIELD_PAGE_WIDTH + 1;
-    // these are only used by the FastSpace mechanism; they have no meaning in other contexts
-    pub u8, from into SpaceState, space_state, set_space_state: BITFIELD_PAGE_WIDTH + 3, BITFIELD_PAGE_WIDTH + 2;
-    // 4 bits for a journal revision. Intended for the FastSpace mechanism
-    pub u8, journal, set_journal: BITFIELD_PAGE_WIDTH + 7, BITFIELD_PAGE_WIDTH + 4;
-}
-// hashes should only key off of the page number, not the metadata
-impl Hash for PhysPage {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.page_number().hash(state);
-    }
-}
-impl PartialEq for PhysPage {
-    fn eq(&self, other: &Self) -> bool {
-        self.page_number() == other.page_number()
-    }
-}
-impl Ord for PhysPage {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.page_number().cmp(&other.page_number())
-    }
-}
-impl PartialOrd for PhysPage {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-/// Storage for journal revisions.
-pub type JournalType = u32;
-
-/// A Virtual Address is 48 bits long. The top 16 bits are required to be blank
-/// so that they may be used as flags in the on-disk storage format.
-/// Virtual pages are shorter than physical pages, due to the overhead of
-/// the nonce + tag + journal entry used to store data on disk.
-/// We make the VirtAddr a NonZeroU64 so that we can apply a None option to it "for free"
-/// in packed disk representations.
-pub type VirtAddr = NonZeroU64;
-
 /// A PageAlignedVa is guaranteed to be an address that's at least big enough to hold
 /// the constructing address. Thus it will tend to "round up" to the nearest page,
 /// unless the given address happens to be exactly one page in size.
@@ -213,10 +150,4 @@ mod tests {
         assert!(t.0 == 0x2, "polarity of boolean bit is not as expected");
         assert!(t.test() == true, "bool getter did not work as expected");
     }
-    #[test]
-    fn test_journal_range() {
-        let pp = PhysPage(u32::MAX);
-        println!("pp.journal(): {}", pp.journal());
-        assert!(pp.journal() == PHYS_PAGE_JOURNAL_MAX, "PHYS_PAGE_JOURNAL_MAX is incorrect");
-    }
 }
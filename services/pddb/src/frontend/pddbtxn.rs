@@ -0,0 +1,77 @@
+use crate::*;
+use xous_ipc::Buffer;
+
+use num_traits::*;
+use std::io::{Result, Error, ErrorKind};
+
+/// A set of writes and deletes that are applied to one basis all at once, or not at all. Nothing
+/// is sent to the PDDB until `commit()`; dropping a transaction without committing it discards it.
+/// Obtained with `Pddb::transaction()`.
+///
+/// Each write replaces the entire contents of its key, creating the key and its dictionary if
+/// necessary. Deleting a key that doesn't exist is not an error. The total size of a transaction,
+/// including names, is limited to a little under `MAX_PDDB_TXN_LEN` bytes, so it is meant for
+/// small, related records (such as a record and its index entries) rather than bulk data.
+pub struct PddbTransaction<'a> {
+    pub(crate) pddb: &'a Pddb,
+    pub(crate) basis: Option<String>,
+    pub(crate) ops: Vec<TxnOp>,
+}
+impl<'a> PddbTransaction<'a> {
+    /// Stages a write of `data` to `key_name` in `dict_name`.
+    pub fn write(&mut self, dict_name: &str, key_name: &str, data: &[u8]) -> Result<()> {
+        check_names(dict_name, key_name)?;
+        self.push(TxnOp::Write { dict: dict_name.to_string(), key: key_name.to_string(), data: data.to_vec() })
+    }
+    /// Stages the removal of `key_name` from `dict_name`.
+    pub fn delete(&mut self, dict_name: &str, key_name: &str) -> Result<()> {
+        check_names(dict_name, key_name)?;
+        self.push(TxnOp::Delete { dict: dict_name.to_string(), key: key_name.to_string() })
+    }
+    fn push(&mut self, op: TxnOp) -> Result<()> {
+        let staged: usize = self.ops.iter().map(|op| op.encoded_len()).sum();
+        if staged + op.encoded_len() > MAX_PDDB_TXN_LEN {
+            return Err(Error::new(ErrorKind::OutOfMemory, "Transaction exceeds MAX_PDDB_TXN_LEN"));
+        }
+        self.ops.push(op);
+        Ok(())
+    }
+    /// Applies every staged op. On error, none of them have been applied.
+    pub fn commit(self) -> Result<()> {
+        if self.ops.len() == 0 {
+            return Ok(())
+        }
+        let mut request = PddbTxnList {
+            basis_specified: self.basis.is_some(),
+            basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(self.basis.as_deref().unwrap_or("")),
+            data: [0u8; MAX_PDDB_TXN_LEN],
+            retcode: PddbRetcode::Uninit,
+        };
+        let encoded = txn_encode(&self.ops);
+        request.data[..encoded.len()].copy_from_slice(&encoded);
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.pddb.conn, Opcode::TxnCommit.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        let response = buf.as_flat::<PddbTxnList, _>().unwrap();
+        match response.retcode {
+            ArchivedPddbRetcode::Ok => Ok(()),
+            ArchivedPddbRetcode::AccessDenied => Err(Error::new(ErrorKind::NotFound, "Basis not found, or PDDB not mounted")),
+            ArchivedPddbRetcode::DiskFull => Err(Error::new(ErrorKind::OutOfMemory, "Out of space committing transaction")),
+            ArchivedPddbRetcode::Uninit => Err(Error::new(ErrorKind::ConnectionAborted, "Return code not set committing transaction, server aborted?")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error committing transaction")),
+        }
+    }
+    /// Discards every staged op. This is equivalent to dropping the transaction.
+    pub fn rollback(self) {}
+}
+
+fn check_names(dict_name: &str, key_name: &str) -> Result<()> {
+    if key_name.len() > (KEY_NAME_LEN - 1) {
+        return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
+    }
+    if dict_name.len() > (DICT_NAME_LEN - 1) {
+        return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+    }
+    Ok(())
+}
@@ -34,7 +34,9 @@ use rkyv::{
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 pub enum CbOp {
     Change,
-    Quit
+    Quit,
+    /// A `PddbWatchEvent` for a subscription made with `Pddb::watch()`
+    Watch,
 }
 
 pub struct PddbMountPoller {
@@ -98,6 +100,10 @@ pub struct Pddb {
     /// in the case of a basis change. Basis changes are thought to be rare; so, big changes
     /// like this are probably OK.
     keys: Arc<Mutex<HashMap<ApiToken, Box<dyn Fn() + 'static + Send> >>>,
+    /// Callbacks for subscriptions made with `watch()`. These run on the same thread as the key change callbacks.
+    /// They are reference counted so the responder can call one without holding the lock, which leaves the
+    /// callback free to call `watch()` or `unwatch()` itself.
+    watches: Arc<Mutex<HashMap<ApiToken, Arc<dyn Fn(WatchEvent) + 'static + Send + Sync> >>>,
     trng: trng::Trng,
     /// These are temporary fields only to be used by the consistency check feature.
     key_count: RefCell<u32>,
@@ -114,6 +120,7 @@ impl Pddb {
             cb: RefCell::new(None),
             cb_handle: RefCell::new(None),
             keys,
+            watches: Arc::new(Mutex::new(HashMap::new())),
             trng: trng::Trng::new(&xns).unwrap(),
             /// These are record the result of the most recent call to list_keys()
             key_count: RefCell::new(0),
@@ -125,6 +132,7 @@ impl Pddb {
             let sid = xous::create_server().unwrap();
             let handle = thread::spawn({
                 let keys = Arc::clone(&self.keys);
+                let watches = Arc::clone(&self.watches);
                 let sid = sid.clone();
                 move || {
                     loop {
@@ -138,6 +146,22 @@ impl Pddb {
                                     log::warn!("Key changed but no callback was hooked to receive it");
                                 }
                             }),
+                            Some(CbOp::Watch) => {
+                                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                                let event = buffer.to_original::<PddbWatchEvent, _>().unwrap();
+                                let cb = watches.lock().unwrap().get(&event.token).cloned();
+                                if let Some(cb) = cb {
+                                    let key = event.key.as_str().unwrap_or("");
+                                    cb(WatchEvent {
+                                        kind: event.kind,
+                                        basis: event.basis.as_str().unwrap_or("").to_string(),
+                                        dict: event.dict.as_str().unwrap_or("").to_string(),
+                                        key: if key.len() > 0 {Some(key.to_string())} else {None},
+                                    });
+                                } else {
+                                    log::warn!("Watch event arrived but no callback was hooked to receive it");
+                                }
+                            }
                             Some(CbOp::Quit) => { // blocking scalar
                                 xous::return_scalar(msg.sender, 0).unwrap();
                                 break;
@@ -484,8 +508,92 @@ impl Pddb {
         }
     }
 
+    /// Subscribes to changes in `dict_name`. `callback` is invoked whenever a key in the dictionary is
+    /// created, updated or deleted, when the dictionary itself is deleted, and when a basis that
+    /// contains the dictionary is unlocked or locked. If `key_name` is specified, only changes to that
+    /// key are reported; if `basis_name` is specified, only changes within that basis are reported.
+    ///
+    /// The callback runs on the same helper thread as the key change callbacks handed to `get()`, so it
+    /// should return quickly. Returns a token that can be handed to `unwatch()` to cancel the subscription.
+    pub fn watch(&self, dict_name: &str, key_name: Option<&str>, basis_name: Option<&str>,
+        callback: impl Fn(WatchEvent) + 'static + Send + Sync) -> Result<ApiToken> {
+        if dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        if key_name.map(|k| k.len() > (KEY_NAME_LEN - 1)).unwrap_or(false) {
+            return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
+        }
+        if basis_name.map(|b| b.len() > (BASIS_NAME_LEN - 1)).unwrap_or(false) {
+            return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+        }
+        self.ensure_async_responder();
+        let cb_sid = self.cb.borrow().as_ref().expect("async responder was not started").to_array();
+
+        // The token is picked here rather than by the server, so the callback can be in place before
+        // an event could possibly arrive for it.
+        let token: ApiToken = [self.trng.get_u32().unwrap(), self.trng.get_u32().unwrap(), self.trng.get_u32().unwrap()];
+        self.watches.lock().unwrap().insert(token, Arc::new(callback));
+
+        let request = PddbWatchRequest {
+            basis_specified: basis_name.is_some(),
+            basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name.unwrap_or("")),
+            dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict_name),
+            key: key_name.map(|k| xous_ipc::String::<KEY_NAME_LEN>::from_str(k)),
+            token,
+            cb_sid,
+            result: PddbRequestCode::Uninit,
+        };
+        let result = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))
+            .and_then(|mut buf| {
+                buf.lend_mut(self.conn, Opcode::WatchRequest.to_u32().unwrap())
+                    .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+                Ok(buf.to_original::<PddbWatchRequest, _>().unwrap().result)
+            });
+        match result {
+            Ok(PddbRequestCode::NoErr) => Ok(token),
+            Ok(code) => {
+                self.watches.lock().unwrap().remove(&token);
+                match code {
+                    PddbRequestCode::DuplicateEntry => Err(Error::new(ErrorKind::AlreadyExists, "Watch token collision")),
+                    _ => Err(Error::new(ErrorKind::Other, format!("Unhandled return code: {:?}", code))),
+                }
+            }
+            Err(e) => {
+                self.watches.lock().unwrap().remove(&token);
+                Err(e)
+            }
+        }
+    }
+    /// Cancels a subscription made with `watch()`.
+    pub fn unwatch(&self, token: ApiToken) -> Result<()> {
+        self.watches.lock().unwrap().remove(&token);
+        let ret = send_message(self.conn, Message::new_blocking_scalar(
+            Opcode::WatchDrop.to_usize().unwrap(), token[0] as usize, token[1] as usize, token[2] as usize, 0)
+        ).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        match ret {
+            xous::Result::Scalar1(rcode) => {
+                match FromPrimitive::from_u8(rcode as u8) {
+                    Some(PddbRetcode::Ok) => Ok(()),
+                    Some(PddbRetcode::AccessDenied) => Err(Error::new(ErrorKind::NotFound, "No such watch")),
+                    _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+                }
+            }
+            _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+        }
+    }
+
     /// deletes a key within the dictionary
     pub fn delete_key(&self, dict_name: &str, key_name: &str, basis_name: Option<&str>) -> Result<()> {
+        self.delete_key_inner(dict_name, key_name, basis_name, Opcode::DeleteKey)
+    }
+    /// deletes a key within the dictionary, and overwrites its data on disk before returning. Use this
+    /// for secrets such as passwords: a plain `delete_key` on a small record only unlinks it, and the
+    /// ciphertext lingers in its pool until the pool is next rewritten.
+    pub fn delete_key_paranoid(&self, dict_name: &str, key_name: &str, basis_name: Option<&str>) -> Result<()> {
+        self.delete_key_inner(dict_name, key_name, basis_name, Opcode::DeleteKeyParanoid)
+    }
+    fn delete_key_inner(&self, dict_name: &str, key_name: &str, basis_name: Option<&str>, op: Opcode) -> Result<()> {
         if key_name.len() > (KEY_NAME_LEN - 1) {
             return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
         }
@@ -523,7 +631,7 @@ impl Pddb {
         };
         let mut buf = Buffer::into_buf(request)
             .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
-        buf.lend_mut(self.conn, Opcode::DeleteKey.to_u32().unwrap())
+        buf.lend_mut(self.conn, op.to_u32().unwrap())
             .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
 
         let response = buf.to_original::<PddbKeyRequest, _>().unwrap();
@@ -629,6 +737,20 @@ impl Pddb {
             _ => Err(Error::new(ErrorKind::Other, "Internal error"))
         }
     }
+    /// Starts a transaction against `basis_name`, or the most recently unlocked basis if `None`.
+    /// See `PddbTransaction` for details.
+    pub fn transaction(&self, basis_name: Option<&str>) -> Result<PddbTransaction> {
+        if let Some(bname) = basis_name {
+            if bname.len() > BASIS_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+            }
+        }
+        Ok(PddbTransaction {
+            pddb: self,
+            basis: basis_name.map(String::from),
+            ops: Vec::new(),
+        })
+    }
 
     pub fn sync(&self) -> Result<()> {
         let response = send_message(
@@ -863,6 +985,25 @@ impl Pddb {
             Message::new_blocking_scalar(Opcode::Prune.to_usize().unwrap(), 0, 0, 0, 0)
         ).expect("couldn't send FlushSpaceUpdate");
     }
+    /// Compacts the dictionary index of every open Basis, so that the dictionaries are stored
+    /// densely in the lowest slots. Worth doing after many dictionaries have been created and deleted.
+    /// Returns the number of dictionaries that were relocated.
+    pub fn compact_dicts(&self) -> Result<usize> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::DictCompact.to_usize().unwrap(), 0, 0, 0, 0)
+        ).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        if let xous::Result::Scalar2(rcode, relocated) = response {
+            match FromPrimitive::from_u8(rcode as u8) {
+                Some(PddbRetcode::Ok) => Ok(relocated),
+                Some(PddbRetcode::BasisLost) => Err(Error::new(ErrorKind::BrokenPipe, "Basis lost")),
+                Some(PddbRetcode::DiskFull) => Err(Error::new(ErrorKind::OutOfMemory, "Out of disk space, compaction incomplete")),
+                _ => Err(Error::new(ErrorKind::Interrupted, "Compaction failed for unspecified reasons")),
+            }
+        } else {
+            Err(Error::new(ErrorKind::Other, "Xous internal error"))
+        }
+    }
     /// Rekey the PDDB. This can be a very long-running blocking operation that will definitely.
     /// interrupt normal user flow.
     pub fn rekey_pddb(&self, op: PddbRekeyOp) -> Result<()> {
@@ -1161,6 +1302,11 @@ impl Pddb {
 
 impl Drop for Pddb {
     fn drop(&mut self) {
+        // cancel any subscriptions before the callback server goes away
+        let tokens: Vec<ApiToken> = self.watches.lock().unwrap().keys().cloned().collect();
+        for token in tokens {
+            self.unwatch(token).ok();
+        }
         if let Some(cb_sid) = self.cb.take() {
             let handle = self.cb_handle.take().unwrap(); // we guarantee this is always set when cb is set
             let cid = xous::connect(cb_sid).unwrap();
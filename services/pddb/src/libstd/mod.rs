@@ -3,7 +3,9 @@ mod utils;
 
 use crate::backend::BasisCache;
 use crate::backend::PddbOs;
+use crate::watch::{WatchRecord, Watchers};
 use crate::FileHandle;
+use crate::WatchEventKind;
 
 use senres::{Senres, SenresMut};
 
@@ -271,6 +273,7 @@ pub(crate) fn open_key(
     pddb_os: &mut PddbOs,
     basis_cache: &mut BasisCache,
     fds: &mut Vec<Option<crate::FileHandle>>,
+    watchers: &mut Watchers,
 ) -> Result<(), crate::PddbRetcode> {
     // Convert the memory to a Senres buffer
     let mut backing = senres::Message::from_mut_slice(mem.buf.as_slice_mut())
@@ -413,6 +416,7 @@ pub(crate) fn open_key(
                     crate::PddbRetcode::InternalError
                 })?;
             len = 0;
+            watchers.notify(WatchEventKind::KeyCreated, basis, requested_dict, Some(requested_key));
         } else if create_new {
             log::error!(
                 "user requested to create {}{}{} with `create_new` set, but that file already exists",
@@ -445,6 +449,7 @@ pub(crate) fn open_key(
                     );
                     crate::PddbRetcode::InternalError
                 })?;
+            watchers.notify(WatchEventKind::KeyUpdated, basis, requested_dict, Some(requested_key));
         }
 
         // The basis exists for sure.
@@ -503,6 +508,7 @@ pub(crate) fn open_key(
 pub(crate) fn close_key(
     fds: &mut Vec<Option<crate::FileHandle>>,
     fd: usize,
+    watchers: &mut Watchers,
 ) -> Result<(), crate::PddbRetcode> {
     let file = fds.get_mut(fd).ok_or_else(|| {
         log::info!("file handle {} is out of range", fd);
@@ -511,7 +517,10 @@ pub(crate) fn close_key(
 
     // Remove the file from the list, replacing it with None
     let conn = match file.take() {
-        Some(s) => s.conn,
+        Some(s) => {
+            watchers.finish_updates(&s.dict, &s.key);
+            s.conn
+        }
         None => {
             log::info!("file handle {} was closed already", fd);
             return Err(crate::PddbRetcode::UnexpectedEof);
@@ -530,7 +539,7 @@ pub(crate) fn close_key(
                 }
             }
         }
-        if !found_duplicate {
+        if !found_duplicate && !watchers.uses_conn(cid) {
             unsafe { xous::disconnect(cid).or(Err(crate::PddbRetcode::InternalError))? };
         }
     }
@@ -548,6 +557,7 @@ pub(crate) fn delete_key(
     pddb_os: &mut PddbOs,
     basis_cache: &mut BasisCache,
     all_fds: &mut std::collections::HashMap<Option<xous::PID>, Vec<Option<FileHandle>>>,
+    watchers: &mut Watchers,
 ) -> Result<(), crate::PddbRetcode> {
     // Convert the memory to a Senres buffer
     let backing = senres::Message::from_mut_slice(mem.buf.as_slice_mut())
@@ -584,6 +594,9 @@ pub(crate) fn delete_key(
             );
             Err(crate::PddbRetcode::UnexpectedEof)
         })?;
+    if let Some(removed_from) = bname.or(basis_cache.basis_latest()) {
+        watchers.notify(WatchEventKind::KeyDeleted, removed_from, dict, Some(key));
+    }
 
     // Mark the entry as deleted in all remaining file handles in the entire system
     for fds in all_fds.values_mut() {
@@ -607,6 +620,7 @@ pub(crate) fn write_key(
     basis_cache: &mut BasisCache,
     fds: &mut Vec<Option<crate::FileHandle>>,
     fd: usize,
+    watchers: &mut Watchers,
 ) -> Result<(), crate::PddbRetcode> {
     let file = get_fd(fds, fd)?;
     let mut retcode = crate::PddbRetcode::InternalError;
@@ -645,6 +659,7 @@ pub(crate) fn write_key(
         {
             file.offset += length_to_write as u64;
             mem.valid = xous::MemorySize::new(length_to_write);
+            watchers.key_written(file.basis.as_ref().unwrap_or(basis), &file.dict, &file.key);
             return Ok(());
         }
     }
@@ -832,6 +847,7 @@ pub(crate) fn delete_dict(
     mem: &mut xous::MemoryMessage,
     pddb_os: &mut PddbOs,
     basis_cache: &mut BasisCache,
+    watchers: &mut Watchers,
 ) -> Result<(), crate::PddbRetcode> {
     let backing = senres::Message::from_mut_slice(mem.buf.as_slice_mut())
         .or(Err(crate::PddbRetcode::InternalError))?;
@@ -868,6 +884,9 @@ pub(crate) fn delete_dict(
         log::error!("error removing dict {} in basis {:?}", dict, bname);
         return Err(crate::PddbRetcode::InternalError);
     }
+    if let Some(removed_from) = bname.as_deref().or(basis_cache.basis_latest()) {
+        watchers.notify(WatchEventKind::DictDeleted, removed_from, &dict, None);
+    }
 
     Ok(())
 }
@@ -905,3 +924,124 @@ pub(crate) fn create_dict(
 
     Ok(())
 }
+
+/// Subscribes to changes of a dict, or of a single key within it. The request carries the
+/// path, a flag that says if the final path component is a key, the SID of the server that
+/// receives the events, and the message ID those events are sent with. Events are
+/// senres messages tagged `WtcE`; see `send_watch_event()`.
+pub(crate) fn watch_path(
+    mem: &mut xous::MemoryMessage,
+    pddb_os: &mut PddbOs,
+    basis_cache: &BasisCache,
+    watchers: &mut Watchers,
+    pid: Option<xous::PID>,
+) -> Result<(), crate::PddbRetcode> {
+    let mut backing = senres::Message::from_mut_slice(mem.buf.as_slice_mut())
+        .or(Err(crate::PddbRetcode::InternalError))?;
+
+    let (basis, path, is_key, cb_sid, event_id) = {
+        let reader = backing
+            .reader(*b"WtcQ")
+            .ok_or(crate::PddbRetcode::InternalError)?;
+        let path = reader
+            .try_get_ref_from::<str>()
+            .or(Err(crate::PddbRetcode::InternalError))?;
+        let is_key: bool = reader
+            .try_get_from()
+            .or(Err(crate::PddbRetcode::InternalError))?;
+        let cb_sid: [u32; 4] = reader
+            .try_get_from()
+            .or(Err(crate::PddbRetcode::InternalError))?;
+        let event_id: u32 = reader
+            .try_get_from()
+            .or(Err(crate::PddbRetcode::InternalError))?;
+        let (basis, path) =
+            utils::split_basis_and_dict(path, || basis_cache.basis_latest().map(|m| m.to_owned()))
+                .or(Err(crate::PddbRetcode::AccessDenied))?;
+        (basis, path.ok_or(crate::PddbRetcode::AccessDenied)?, is_key, cb_sid, event_id)
+    };
+    let (dict, key) = if is_key {
+        let (dict, key) = path
+            .rsplit_once(std::path::MAIN_SEPARATOR)
+            .ok_or(crate::PddbRetcode::AccessDenied)?;
+        (dict.to_owned(), Some(key.to_owned()))
+    } else {
+        (path, None)
+    };
+
+    let token: crate::ApiToken = [pddb_os.trng_u32(), pddb_os.trng_u32(), pddb_os.trng_u32()];
+    let conn = xous::connect(xous::SID::from_array(cb_sid)).or(Err(crate::PddbRetcode::AccessDenied))?;
+    let record = WatchRecord {
+        basis,
+        dict,
+        key,
+        conn,
+        owner: pid,
+        std_event_id: Some(event_id as usize),
+    };
+    if !watchers.add(token, record) {
+        return Err(crate::PddbRetcode::InternalError);
+    }
+
+    let mut writer = backing
+        .writer(*b"WtcR")
+        .ok_or(crate::PddbRetcode::InternalError)?;
+    writer.append(token);
+    Ok(())
+}
+
+/// Sends a change notification to a libstd subscriber. The message holds the token returned by
+/// `watch_path()`, the `WatchEventKind` as a `u8`, then the basis, dict and optional key names.
+pub(crate) fn send_watch_event(
+    conn: xous::CID,
+    event_id: usize,
+    token: crate::ApiToken,
+    kind: WatchEventKind,
+    basis: &str,
+    dict: &str,
+    key: Option<&str>,
+) -> Result<(), xous::Error> {
+    let mut buf = xous_ipc::Buffer::new(4096);
+    encode_watch_event(buf.as_mut(), token, kind, basis, dict, key)?;
+    crate::watch::try_send(buf, conn, event_id as u32)
+}
+
+fn encode_watch_event(
+    buf: &mut [u8],
+    token: crate::ApiToken,
+    kind: WatchEventKind,
+    basis: &str,
+    dict: &str,
+    key: Option<&str>,
+) -> Result<(), xous::Error> {
+    let mut backing =
+        senres::Message::from_mut_slice(buf).or(Err(xous::Error::InternalError))?;
+    let mut writer = backing
+        .writer(*b"WtcE")
+        .ok_or(xous::Error::InternalError)?;
+    writer.append(token);
+    writer.append(kind as u8);
+    writer.append(basis);
+    writer.append(dict);
+    writer.append(key);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watch_event_encoding() {
+        let mut buf = [0u8; 4096];
+        encode_watch_event(&mut buf, [1, 2, 3], WatchEventKind::KeyDeleted, ".System", "wlan.networks", Some("home"))
+            .unwrap();
+        let backing = senres::Message::from_slice(&buf).unwrap();
+        let reader = backing.reader(*b"WtcE").unwrap();
+        assert_eq!(reader.try_get_from::<[u32; 3]>().unwrap(), [1, 2, 3]);
+        assert_eq!(reader.try_get_from::<u8>().unwrap(), WatchEventKind::KeyDeleted as u8);
+        assert_eq!(reader.try_get_from::<String>().unwrap(), ".System");
+        assert_eq!(reader.try_get_from::<String>().unwrap(), "wlan.networks");
+        assert_eq!(reader.try_get_from::<Option<String>>().unwrap().as_deref(), Some("home"));
+    }
+}
@@ -372,6 +372,8 @@ mod ux;
 use ux::*;
 mod menu;
 use menu::*;
+mod watch;
+use watch::*;
 
 mod libstd;
 
@@ -499,6 +501,8 @@ fn wrapped_main() -> ! {
 
     // Process-indexed map of file descriptors to token records
     let mut fd_mapping = HashMap::<Option<xous::PID>, Vec<Option<FileHandle>>>::new();
+    // subscriptions to key and dictionary changes
+    let mut watchers = Watchers::default();
 
     // mount poller thread
     let is_mounted = Arc::new(AtomicBool::new(false));
@@ -542,7 +546,10 @@ fn wrapped_main() -> ! {
         not(target_os = "xous"),
         feature = "ci"
     ))]
-    ci_tests(&mut pddb_os).map_err(|e| log::error!("{}", e)).ok();
+    {
+        let result = ci_tests(&mut pddb_os).map_err(|e| e.to_string());
+        thread::spawn(move || ci_server_tests(result));
+    }
 
     if false { // this will re-init the PDDB and do a simple key query. Really useful only for early shake-down testing, eliminate this reminder stub once we have some confidence in the code
         hw_testcase(&mut pddb_os);
@@ -880,7 +887,13 @@ fn wrapped_main() -> ! {
                                         basis_cache.basis_unmount(&mut pddb_os, &basis.name).expect("couldn't unmount previously mounted basis of same name");
                                         modals.show_notification(t!("pddb.unmount_previous", locales::LANG), None).expect("notification failed");
                                     }
+                                    let unlocked = basis.name.to_string();
                                     basis_cache.basis_add(basis);
+                                    if let Err(e) = basis_cache.txn_recover(&mut pddb_os, &unlocked) {
+                                        log::error!("Couldn't recover interrupted transaction in basis {}: {:?}", unlocked, e);
+                                    }
+                                    let affected = watchers.affected_by_basis(&mut pddb_os, &mut basis_cache, &unlocked);
+                                    watchers.notify_basis(affected, WatchEventKind::BasisUnlocked, &unlocked);
                                     finished = true;
                                     log::info!("{}PDDB.UNLOCKOK,{},{}", xous::BOOKEND_START, mgmt.name.as_str().unwrap(), xous::BOOKEND_END);
                                     if basis_monitor_notifications.len() > 0 {
@@ -925,8 +938,11 @@ fn wrapped_main() -> ! {
                 notify_of_disconnect(&mut pddb_os, &token_dict, &mut basis_cache);
                 match mgmt.code {
                     PddbRequestCode::Close => {
-                        match basis_cache.basis_unmount(&mut pddb_os, mgmt.name.as_str().expect("name is not valid utf-8")) {
+                        let name = mgmt.name.as_str().expect("name is not valid utf-8");
+                        let affected = watchers.affected_by_basis(&mut pddb_os, &mut basis_cache, name);
+                        match basis_cache.basis_unmount(&mut pddb_os, name) {
                             Ok(_) => {
+                                watchers.notify_basis(affected, WatchEventKind::BasisLocked, name);
                                 mgmt.code = PddbRequestCode::NoErr;
                                 if basis_monitor_notifications.len() > 0 {
                                     notify_basis_change(&mut basis_monitor_notifications, basis_cache.basis_list());
@@ -950,8 +966,13 @@ fn wrapped_main() -> ! {
                 notify_of_disconnect(&mut pddb_os, &token_dict, &mut basis_cache);
                 match mgmt.code {
                     PddbRequestCode::Delete => {
-                        match basis_cache.basis_delete(&mut pddb_os, mgmt.name.as_str().expect("name is not valid utf-8")) {
-                            Ok(_) => mgmt.code = PddbRequestCode::NoErr,
+                        let name = mgmt.name.as_str().expect("name is not valid utf-8");
+                        let affected = watchers.affected_by_basis(&mut pddb_os, &mut basis_cache, name);
+                        match basis_cache.basis_delete(&mut pddb_os, name) {
+                            Ok(_) => {
+                                watchers.notify_basis(affected, WatchEventKind::BasisLocked, name);
+                                mgmt.code = PddbRequestCode::NoErr
+                            }
                             Err(e) => match e.kind() {
                                 ErrorKind::NotFound => mgmt.code = PddbRequestCode::NotFound,
                                 _ => mgmt.code = PddbRequestCode::InternalError,
@@ -1012,7 +1033,7 @@ fn wrapped_main() -> ! {
                                 // don't truncate if we've been given an explicit size hint.
                                 alloc_hint.is_none()
                             ) {
-                                Ok(_) => {},
+                                Ok(_) => watchers.notify(WatchEventKind::KeyCreated, bname.unwrap(), dict, Some(key)),
                                 Err(e) => {
                                     log::error!("Couldn't allocate key: {:?}", e);
                                     match e.kind() {
@@ -1053,7 +1074,7 @@ fn wrapped_main() -> ! {
             Opcode::OpenKeyStd => {
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
-                    if let Err(e) = libstd::open_key(mem, &mut pddb_os, &mut basis_cache, fd_mapping.entry(msg.sender.pid()).or_default()) {
+                    if let Err(e) = libstd::open_key(mem, &mut pddb_os, &mut basis_cache, fd_mapping.entry(msg.sender.pid()).or_default(), &mut watchers) {
                         mem.offset = xous::MemoryAddress::new(e as usize);
                     }
                 }
@@ -1062,6 +1083,7 @@ fn wrapped_main() -> ! {
             Opcode::KeyDrop => msg_blocking_scalar_unpack!(msg, t0, t1, t2, _, {
                 let token: ApiToken = [t0 as u32, t1 as u32, t2 as u32];
                 if let Some(rec) = token_dict.remove(&token) {
+                    watchers.finish_updates(&rec.dict, &rec.key);
                     // now check if we can safely disconnect and recycle our connection number.
                     // This is important because we can only have 32 outgoing connections...
                     if let Some(conn_to_remove) = rec.conn {
@@ -1076,7 +1098,7 @@ fn wrapped_main() -> ! {
                             }
                         }
                         // if nobody else had my connection number, disconnect it.
-                        if !still_needs_cid {
+                        if !still_needs_cid && !watchers.uses_conn(conn_to_remove) {
                             unsafe{xous::disconnect(conn_to_remove).expect("couldn't disconnect from callback server")};
                         }
                     } else {
@@ -1091,7 +1113,7 @@ fn wrapped_main() -> ! {
             Opcode::CloseKeyStd => {
                 let fd = (msg.body.id() >> 16) & 0xffff;
                 if msg.body.scalar_message().is_some() {
-                    let result = libstd::close_key(fd_mapping.entry(msg.sender.pid()).or_default(), fd);
+                    let result = libstd::close_key(fd_mapping.entry(msg.sender.pid()).or_default(), fd, &mut watchers);
                     if msg.body.is_blocking() {
                         if let Err(e) = result {
                             xous::return_scalar(msg.sender, e as usize)
@@ -1102,7 +1124,8 @@ fn wrapped_main() -> ! {
                 }
             }
 
-            Opcode::DeleteKey => {
+            op @ (Opcode::DeleteKey | Opcode::DeleteKeyParanoid) => {
+                let paranoid = matches!(op, Opcode::DeleteKeyParanoid);
                 let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut req: PddbKeyRequest = buffer.to_original::<PddbKeyRequest, _>().unwrap();
                 let bname = if req.basis_specified {
@@ -1112,8 +1135,11 @@ fn wrapped_main() -> ! {
                 };
                 let dict = req.dict.as_str().expect("dict utf-8 decode error");
                 let key = req.key.as_str().expect("key utf-8 decode error");
-                match basis_cache.key_remove(&mut pddb_os, dict, key, bname, false) {
+                match basis_cache.key_remove(&mut pddb_os, dict, key, bname, paranoid) {
                     Ok(_) => {
+                        if let Some(removed_from) = bname.or(basis_cache.basis_latest()) {
+                            watchers.notify(WatchEventKind::KeyDeleted, removed_from, dict, Some(key));
+                        }
                         let mut evict_list = Vec::<ApiToken>::new();
                         // check to see if we need to eliminate any ApiTokens as a result of this.
                         for (token, rec) in token_dict.iter() {
@@ -1184,8 +1210,24 @@ fn wrapped_main() -> ! {
                     None
                 };
                 let dict = req.dict.as_str().expect("dict utf-8 decode error");
+                // keys that weren't there to begin with aren't reported
+                let removed: Vec<String> = if watchers.watches_dict(dict) {
+                    key_list.iter()
+                        .filter(|k| basis_cache.key_attributes(&mut pddb_os, dict, k, bname).is_ok())
+                        .cloned()
+                        .collect()
+                } else {
+                    Vec::new()
+                };
                 match basis_cache.key_list_remove(&mut pddb_os, dict, key_list, bname) {
-                    Ok(_) => req.retcode = PddbRetcode::Ok,
+                    Ok(_) => {
+                        req.retcode = PddbRetcode::Ok;
+                        if let Some(removed_from) = bname.or(basis_cache.basis_latest()) {
+                            for key in removed.iter() {
+                                watchers.notify(WatchEventKind::KeyDeleted, removed_from, dict, Some(key.as_str()));
+                            }
+                        }
+                    }
                     Err(e) => match e.kind() {
                         std::io::ErrorKind::NotFound => req.retcode = PddbRetcode::AccessDenied,
                         _ => req.retcode = PddbRetcode::InternalError,
@@ -1197,7 +1239,7 @@ fn wrapped_main() -> ! {
             Opcode::DeleteKeyStd => {
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
-                    if let Err(err) = libstd::delete_key(mem, &mut pddb_os, &mut basis_cache, &mut fd_mapping) {
+                    if let Err(err) = libstd::delete_key(mem, &mut pddb_os, &mut basis_cache, &mut fd_mapping, &mut watchers) {
                         mem.offset = xous::MemoryAddress::new(err as usize);
                     }
                 }
@@ -1214,6 +1256,9 @@ fn wrapped_main() -> ! {
                 log::debug!("attempting to remove dict {} basis {:?}", dict, bname);
                 match basis_cache.dict_remove(&mut pddb_os, dict, bname, false) {
                     Ok(_) => {
+                        if let Some(removed_from) = bname.or(basis_cache.basis_latest()) {
+                            watchers.notify(WatchEventKind::DictDeleted, removed_from, dict, None);
+                        }
                         let mut evict_list = Vec::<ApiToken>::new();
                         // check to see if we need to eliminate any ApiTokens as a result of this.
                         for (token, rec) in token_dict.iter() {
@@ -1255,7 +1300,7 @@ fn wrapped_main() -> ! {
             Opcode::DeleteDictStd => {
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
-                    if let Err(err) = libstd::delete_dict(mem, &mut pddb_os, &mut basis_cache) {
+                    if let Err(err) = libstd::delete_dict(mem, &mut pddb_os, &mut basis_cache, &mut watchers) {
                         mem.offset = xous::MemoryAddress::new(err as usize);
                     }
                 }
@@ -1763,6 +1808,7 @@ fn wrapped_main() -> ! {
                         ) {
                             Ok(_) => {
                                 pbuf.retcode = PddbRetcode::Ok;
+                                watchers.key_written(temp.unwrap(), &rec.dict, &rec.key);
                                 break;
                             }
                             Err(e) => match e.kind() {
@@ -1786,13 +1832,14 @@ fn wrapped_main() -> ! {
                 let fd = (msg.body.id() >> 16) & 0xffff;
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
-                    if let Err(e) = libstd::write_key(mem, &mut pddb_os, &mut basis_cache, fd_mapping.entry(msg.sender.pid()).or_default(), fd) {
+                    if let Err(e) = libstd::write_key(mem, &mut pddb_os, &mut basis_cache, fd_mapping.entry(msg.sender.pid()).or_default(), fd, &mut watchers) {
                         mem.offset = xous::MemoryAddress::new(e as usize);
                     }
                 }
             }
 
             Opcode::WriteKeyFlush => msg_blocking_scalar_unpack!(msg, cleanup, _, _, _, {
+                watchers.finish_all_updates();
                 match basis_cache.sync(&mut pddb_os, None, if cleanup == 1 { true } else { false }) {
                     Ok(_) => xous::return_scalar(msg.sender, PddbRetcode::Ok.to_usize().unwrap()).unwrap(),
                     Err(e) => match e.kind() {
@@ -1803,6 +1850,101 @@ fn wrapped_main() -> ! {
                 };
             }),
 
+            Opcode::WatchRequest => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbWatchRequest, _>().unwrap();
+                let record = WatchRecord {
+                    basis: if req.basis_specified {Some(String::from(req.basis.as_str().unwrap()))} else {None},
+                    dict: String::from(req.dict.as_str().expect("dict utf-8 decode error")),
+                    key: req.key.as_ref().map(|k| String::from(k.as_str().expect("key utf-8 decode error"))),
+                    conn: xous::connect(xous::SID::from_array(req.cb_sid)).expect("couldn't connect for callback"),
+                    owner: msg.sender.pid(),
+                    std_event_id: None,
+                };
+                req.result = if watchers.add(req.token, record) {
+                    PddbRequestCode::NoErr
+                } else {
+                    PddbRequestCode::DuplicateEntry
+                };
+                buffer.replace(req).unwrap();
+            }
+            Opcode::WatchRequestStd => {
+                let pid = msg.sender.pid();
+                if let Some(mem) = msg.body.memory_message_mut() {
+                    mem.offset = None;
+                    if let Err(e) = libstd::watch_path(mem, &mut pddb_os, &basis_cache, &mut watchers, pid) {
+                        mem.offset = xous::MemoryAddress::new(e as usize);
+                    }
+                }
+            }
+            Opcode::WatchDrop => msg_blocking_scalar_unpack!(msg, t0, t1, t2, _, {
+                let token: ApiToken = [t0 as u32, t1 as u32, t2 as u32];
+                if let Some(rec) = watchers.remove(&token, msg.sender.pid()) {
+                    // the connection may be shared with key callbacks, file handles or other watches
+                    let still_needs_cid = watchers.uses_conn(rec.conn)
+                        || token_dict.values().any(|r| r.conn == Some(rec.conn))
+                        || fd_mapping.values().flatten().flatten().any(|f| f.conn == Some(rec.conn));
+                    if !still_needs_cid {
+                        unsafe{xous::disconnect(rec.conn).expect("couldn't disconnect from callback server")};
+                    }
+                    xous::return_scalar(msg.sender, PddbRetcode::Ok as usize).expect("couldn't ack WatchDrop");
+                } else {
+                    xous::return_scalar(msg.sender, PddbRetcode::AccessDenied as usize).expect("couldn't ack WatchDrop");
+                }
+            }),
+            Opcode::TxnCommit => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbTxnList, _>().unwrap();
+                let ops = match txn_decode(&req.data) {
+                    // dictionary removal is reserved for rolling back
+                    Some(ops) if !ops.iter().any(|op| matches!(op, TxnOp::RemoveDict { .. })) => ops,
+                    _ => {
+                        log::error!("Malformed transaction, aborting");
+                        req.retcode = PddbRetcode::InternalError;
+                        buffer.replace(req).ok();
+                        continue;
+                    }
+                };
+                let bname = if req.basis_specified {
+                    Some(req.basis.as_str().unwrap())
+                } else {
+                    None
+                };
+                // figure out how to report each op to watchers before the ops change the answer
+                let mut events = Vec::<(WatchEventKind, &str, &str)>::new();
+                for op in ops.iter() {
+                    let (dict, key) = (op.dict(), op.key().unwrap());
+                    if !watchers.watches_dict(dict) || events.iter().any(|(_, d, k)| *d == dict && *k == key) {
+                        continue;
+                    }
+                    let existed = basis_cache.key_attributes(&mut pddb_os, dict, key, bname).is_ok();
+                    // only the final op on a key is visible once the transaction is done
+                    match ops.iter().rev().find(|o| o.dict() == dict && o.key() == Some(key)) {
+                        Some(TxnOp::Write { .. }) if existed => events.push((WatchEventKind::KeyUpdated, dict, key)),
+                        Some(TxnOp::Write { .. }) => events.push((WatchEventKind::KeyCreated, dict, key)),
+                        _ if existed => events.push((WatchEventKind::KeyDeleted, dict, key)),
+                        _ => {}
+                    }
+                }
+                log::debug!("committing transaction of {} ops to basis {:?}", ops.len(), bname);
+                match basis_cache.txn_commit(&mut pddb_os, &ops, bname) {
+                    Ok(_) => {
+                        req.retcode = PddbRetcode::Ok;
+                        if let Some(committed_to) = bname.or(basis_cache.basis_latest()) {
+                            for (kind, dict, key) in events {
+                                watchers.notify(kind, committed_to, dict, Some(key));
+                            }
+                        }
+                    }
+                    Err(e) => match e.kind() {
+                        std::io::ErrorKind::NotFound => req.retcode = PddbRetcode::AccessDenied,
+                        std::io::ErrorKind::OutOfMemory => req.retcode = PddbRetcode::DiskFull,
+                        _ => req.retcode = PddbRetcode::InternalError,
+                    }
+                }
+                buffer.replace(req).ok();
+            }
+
             Opcode::MenuListBasis => {
                 let bases = basis_cache.basis_list();
                 let mut note = String::from(t!("pddb.menu.listbasis_response", locales::LANG));
@@ -1849,6 +1991,23 @@ fn wrapped_main() -> ! {
                 log::info!("{} pruned, now: {} heap, {} cache", pruned, latest_heap, basis_cache.cache_size());
                 xous::return_scalar(msg.sender, 1).ok();
             }
+            Opcode::DictCompact => {
+                match basis_cache.dict_compact(&mut pddb_os, None) {
+                    Ok(relocated) => {
+                        log::info!("PDDB dict compaction relocated {} dicts", relocated);
+                        xous::return_scalar2(msg.sender, PddbRetcode::Ok.to_usize().unwrap(), relocated).ok();
+                    }
+                    Err(e) => {
+                        log::error!("PDDB dict compaction failed: {:?}", e);
+                        let code = match e.kind() {
+                            std::io::ErrorKind::OutOfMemory => PddbRetcode::DiskFull,
+                            std::io::ErrorKind::NotFound => PddbRetcode::BasisLost,
+                            _ => PddbRetcode::InternalError,
+                        };
+                        xous::return_scalar2(msg.sender, code.to_usize().unwrap(), 0).ok();
+                    }
+                }
+            }
             #[cfg(not(target_os = "xous"))]
             Opcode::DangerousDebug => {
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -1870,6 +2029,9 @@ fn wrapped_main() -> ! {
                         if let Some(sys_basis) = pddb_os.pddb_mount() {
                             log::info!("remount successful");
                             basis_cache.basis_add(sys_basis);
+                            if let Err(e) = basis_cache.txn_recover(&mut pddb_os, PDDB_DEFAULT_SYSTEM_BASIS) {
+                                log::error!("Couldn't recover interrupted transaction: {:?}", e);
+                            }
                         } else {
                             log::info!("remount failed");
                         }
@@ -2050,6 +2212,9 @@ fn try_mount_or_format(
         if let Some(sys_basis) = pddb_os.pddb_mount() {
             log::info!("PDDB mount operation finished successfully");
             basis_cache.basis_add(sys_basis);
+            if let Err(e) = basis_cache.txn_recover(pddb_os, PDDB_DEFAULT_SYSTEM_BASIS) {
+                log::error!("Couldn't recover interrupted transaction: {:?}", e);
+            }
             if basis_monitor_notifications.len() > 0 {
                 notify_basis_change(basis_monitor_notifications, basis_cache.basis_list());
             }
@@ -3,7 +3,7 @@ use rand_chacha::rand_core::RngCore;
 use rand_chacha::rand_core::SeedableRng;
 use crate::*;
 use core::sync::atomic::{AtomicU64, Ordering};
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::io::Result;
 
 const UPPER_BOUND: usize = 9000;
@@ -379,6 +379,522 @@ pub(crate) fn list_all(hw: &mut PddbOs, basis_cache: &mut BasisCache) {
     }
 }
 
+/// Fragments the dictionary index by creating a run of dictionaries and then deleting every other one,
+/// compacts the basis, and checks that every key in the basis reads back identically. Returns a snapshot
+/// of all the key data, so the caller can re-check it after a remount.
+pub(crate) fn compaction_test(hw: &mut PddbOs, basis_cache: &mut BasisCache,
+    maybe_num_dicts: Option<usize>, maybe_num_keys: Option<usize>,
+) -> Result<HashMap<(String, String), Vec<u8>>> {
+    let num_dicts = maybe_num_dicts.unwrap_or(8);
+    let num_keys = maybe_num_keys.unwrap_or(20);
+
+    for dictnum in 1..=num_dicts {
+        let dictname = format!("compact{}", dictnum);
+        for keynum in 1..=num_keys {
+            let (keyname, keydata) = gen_key(&dictname, keynum, LOWER_BOUND, UPPER_BOUND - 4);
+            basis_cache.key_update(hw, &dictname, &keyname, &keydata, None, None, None, false)?;
+        }
+    }
+    // punch holes in the dictionary index
+    for dictnum in (1..=num_dicts).step_by(2) {
+        let dictname = format!("compact{}", dictnum);
+        log::info!("fragmenting: removing {}", dictname);
+        basis_cache.dict_remove(hw, &dictname, None, false)?;
+    }
+    basis_cache.sync(hw, None, false)?;
+
+    let snapshot = snapshot_all(hw, basis_cache);
+    log::info!("compacting with {} keys in the snapshot", snapshot.len());
+    let relocated = basis_cache.dict_compact(hw, None)?;
+    log::info!("compaction relocated {} dicts", relocated);
+    assert!(relocated > 0, "fragmented basis did not have any dictionaries relocated");
+    verify_snapshot(hw, basis_cache, &snapshot);
+
+    // a second pass over a dense index should have nothing to do
+    assert!(basis_cache.dict_compact(hw, None)? == 0, "compaction was not idempotent");
+
+    // the freed slots should be re-usable
+    let dictname = format!("compact{}", num_dicts + 1);
+    let (keyname, keydata) = gen_key(&dictname, 1, LOWER_BOUND, UPPER_BOUND - 4);
+    basis_cache.key_update(hw, &dictname, &keyname, &keydata, None, None, None, false)?;
+    basis_cache.sync(hw, None, false)?;
+    verify_snapshot(hw, basis_cache, &snapshot);
+
+    Ok(snapshot_all(hw, basis_cache))
+}
+
+/// Streams a multi-page record into the large pool in odd-sized chunks, then reads it back and patches it
+/// at offsets that straddle page boundaries, checking every access against an in-memory model of the record.
+/// Also checks that the read-ahead window for the record stays bounded, and survives a cache prune.
+pub(crate) fn large_stream_test(hw: &mut PddbOs, basis_cache: &mut BasisCache,
+    maybe_num_pages: Option<usize>, maybe_chunk_size: Option<usize>,
+) -> Result<()> {
+    const DICT: &'static str = "largestream";
+    const KEY: &'static str = "stream";
+    let num_pages = maybe_num_pages.unwrap_or(5);
+    let chunk_size = maybe_chunk_size.unwrap_or(1237); // deliberately not a divisor of VPAGE_SIZE
+    let total_len = num_pages * VPAGE_SIZE + 333;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(RNG_LOCAL_STATE.load(Ordering::SeqCst) + xous::TESTING_RNG_SEED.load(core::sync::atomic::Ordering::SeqCst));
+    let mut model = vec![0u8; total_len];
+    rng.fill_bytes(&mut model);
+    RNG_LOCAL_STATE.store(rng.next_u64(), Ordering::SeqCst);
+
+    // stream the record in; reserve the full length up front so it lands in the large pool
+    let mut written = 0;
+    while written < total_len {
+        let end = (written + chunk_size).min(total_len);
+        basis_cache.key_update(hw, DICT, KEY, &model[written..end], Some(written), Some(total_len), None, false)?;
+        written = end;
+    }
+    basis_cache.sync(hw, None, false)?;
+    let base_size = basis_cache.cache_size();
+
+    // read it back in chunks that are offset so they straddle page boundaries
+    let mut readback = vec![0u8; total_len];
+    let mut cursor = 0;
+    while cursor < total_len {
+        let end = (cursor + chunk_size).min(total_len);
+        let readlen = basis_cache.key_read(hw, DICT, KEY, &mut readback[cursor..end], Some(cursor), None)?;
+        assert!(readlen == end - cursor, "short read at offset {}: {} of {}", cursor, readlen, end - cursor);
+        cursor = end;
+    }
+    assert!(readback == model, "large record streamed read mismatch");
+
+    // the cached pages are the only thing that should have grown
+    let window_size = basis_cache.cache_size() - base_size;
+    log::info!("large record page cache: {} bytes", window_size);
+    assert!(window_size <= LARGE_CACHE_SIZE, "large page cache is larger than LARGE_CACHE_SIZE");
+
+    // patch across a page boundary, and check both the patch and its neighbors
+    let patch_offset = VPAGE_SIZE - 3;
+    let mut patch = [0u8; 17];
+    rng.fill_bytes(&mut patch);
+    basis_cache.key_update(hw, DICT, KEY, &patch, Some(patch_offset), None, None, false)?;
+    model[patch_offset..patch_offset + patch.len()].copy_from_slice(&patch);
+    let mut region = [0u8; 64];
+    let readlen = basis_cache.key_read(hw, DICT, KEY, &mut region, Some(patch_offset - 20), None)?;
+    assert!(readlen == region.len(), "short read around the patch");
+    assert!(&region[..] == &model[patch_offset - 20..patch_offset + 44], "patch across page boundary mismatch");
+
+    // drop everything that can be dropped, and confirm the record reloads from disk identically
+    basis_cache.sync(hw, None, false)?;
+    let cache_size = basis_cache.cache_size();
+    let pruned = basis_cache.cache_prune(hw, cache_size);
+    log::info!("pruned {} bytes", pruned);
+    assert!(basis_cache.cache_size() == cache_size - pruned,
+        "prune reported {} bytes, but the cache shrank by {}", pruned, cache_size - basis_cache.cache_size());
+    assert!(basis_cache.large_cache_size() == 0, "large pages survived a full prune");
+    // patch the tail end, which forces a read-modify-write of the trailing partial page from disk
+    let tail_offset = total_len - 100;
+    rng.fill_bytes(&mut patch);
+    basis_cache.key_update(hw, DICT, KEY, &patch, Some(tail_offset), None, None, false)?;
+    model[tail_offset..tail_offset + patch.len()].copy_from_slice(&patch);
+    let mut readback = vec![0u8; total_len];
+    let readlen = basis_cache.key_read(hw, DICT, KEY, &mut readback, Some(0), None)?;
+    assert!(readlen == total_len, "short read after prune: {} of {}", readlen, total_len);
+    assert!(readback == model, "large record mismatch after prune");
+
+    basis_cache.dict_remove(hw, DICT, None, false)?;
+    basis_cache.sync(hw, None, false)?;
+    Ok(())
+}
+
+/// Reads two large records that together don't fit in the large page cache, interleaving the reads
+/// so the LRU has to evict pages from one record to make room for the other.
+pub(crate) fn large_lru_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const DICT: &'static str = "largelru";
+    let num_pages = LARGE_CACHE_SIZE / VPAGE_SIZE + 2;
+    let total_len = num_pages * VPAGE_SIZE;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(RNG_LOCAL_STATE.load(Ordering::SeqCst) + xous::TESTING_RNG_SEED.load(core::sync::atomic::Ordering::SeqCst));
+    let mut models = Vec::new();
+    for key in ["first", "second"] {
+        let mut model = vec![0u8; total_len];
+        rng.fill_bytes(&mut model);
+        basis_cache.key_update(hw, DICT, key, &model, None, Some(total_len), None, false)?;
+        models.push((key, model));
+    }
+    RNG_LOCAL_STATE.store(rng.next_u64(), Ordering::SeqCst);
+    basis_cache.sync(hw, None, false)?;
+
+    let mut page = vec![0u8; VPAGE_SIZE];
+    for round in 0..2 {
+        for pagenum in 0..num_pages {
+            for (key, model) in models.iter() {
+                let offset = pagenum * VPAGE_SIZE;
+                let readlen = basis_cache.key_read(hw, DICT, key, &mut page, Some(offset), None)?;
+                assert!(readlen == VPAGE_SIZE, "short read of {} at offset {}", key, offset);
+                assert!(&page[..] == &model[offset..offset + VPAGE_SIZE], "{} mismatch at offset {} in round {}", key, offset, round);
+                assert!(basis_cache.large_cache_size() <= LARGE_CACHE_SIZE,
+                    "large page cache grew to {} bytes", basis_cache.large_cache_size());
+            }
+        }
+    }
+    // evicting everything frees exactly what the page cache was holding
+    let large_size = basis_cache.large_cache_size();
+    let cache_size = basis_cache.cache_size();
+    let pruned = basis_cache.cache_prune(hw, cache_size);
+    assert!(pruned >= large_size, "prune freed {} bytes, but {} were in large pages", pruned, large_size);
+    assert!(basis_cache.cache_size() == cache_size - pruned, "prune accounting is off");
+
+    basis_cache.dict_remove(hw, DICT, None, false)?;
+    basis_cache.sync(hw, None, false)?;
+    Ok(())
+}
+
+/// Deletes a small record with the paranoid flag set, and then inspects the raw hosted image to confirm
+/// that none of the data pages written on behalf of the record survive on disk. The other keys sharing
+/// the record's small pool page must still read back correctly.
+pub(crate) fn paranoid_erase_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const DICT: &'static str = "paranoid";
+    const VICTIM: &'static str = "victim";
+    let data_base = hw.dbg_data_base();
+
+    let mut keepers = Vec::<(String, Vec<u8>)>::new();
+    for keynum in 1..=4 {
+        // small records only, so they share a pool page with the victim
+        let keyname = format!("keeper{}", keynum);
+        let keydata = vec![keynum as u8; 50 + keynum * 13];
+        basis_cache.key_update(hw, DICT, &keyname, &keydata, None, None, None, false)?;
+        keepers.push((keyname, keydata));
+    }
+    basis_cache.sync(hw, None, false)?;
+    let before = hosted_raw_image()?;
+
+    let secret = [0x5Au8; 97];
+    basis_cache.key_update(hw, DICT, VICTIM, &secret, None, None, None, false)?;
+    basis_cache.sync(hw, None, false)?;
+    let written = hosted_raw_image()?;
+
+    // the data pages that changed are the ones that hold ciphertext derived from the victim record
+    let mut touched = Vec::<usize>::new();
+    for offset in (data_base..written.len()).step_by(PAGE_SIZE) {
+        if written[offset..offset + PAGE_SIZE] != before[offset..offset + PAGE_SIZE] {
+            touched.push(offset);
+        }
+    }
+    assert!(touched.len() > 0, "writing the victim record did not touch any data pages");
+    log::info!("victim record touched {} data pages", touched.len());
+
+    basis_cache.key_remove(hw, DICT, VICTIM, None, true)?;
+    let erased = hosted_raw_image()?;
+    for &offset in touched.iter() {
+        let residual = &written[offset..offset + PAGE_SIZE];
+        for check in (data_base..erased.len()).step_by(PAGE_SIZE) {
+            assert!(&erased[check..check + PAGE_SIZE] != residual,
+                "ciphertext from page at {:x} survived paranoid erase at {:x}", offset, check);
+        }
+    }
+
+    let (key_list, _, _) = basis_cache.key_list(hw, DICT, None)?;
+    assert!(!key_list.contains(VICTIM), "paranoid erased key is still listed");
+    for (keyname, keydata) in keepers.iter() {
+        let mut readback = vec![0u8; keydata.len()];
+        let readlen = basis_cache.key_read(hw, DICT, keyname, &mut readback, Some(0), None)?;
+        assert!(readlen == keydata.len() && &readback == keydata, "paranoid erase damaged neighbor key {}", keyname);
+    }
+    basis_cache.dict_remove(hw, DICT, None, false)?;
+    basis_cache.sync(hw, None, false)?;
+    Ok(())
+}
+
+/// Returns `true` if every (dict, key) in `expected` reads back with the given contents, where `None`
+/// means the key must not exist.
+fn txn_state_matches(hw: &mut PddbOs, basis_cache: &mut BasisCache, expected: &[(&str, &str, Option<Vec<u8>>)]) -> bool {
+    for (dict, key, data) in expected.iter() {
+        let found = match basis_cache.key_attributes(hw, dict, key, None) {
+            Ok(attr) => {
+                let mut readback = vec![0u8; attr.len];
+                match basis_cache.key_read(hw, dict, key, &mut readback, Some(0), None) {
+                    Ok(len) if len == attr.len => Some(readback),
+                    _ => return false,
+                }
+            }
+            Err(_) => None,
+        };
+        if &found != data {
+            return false;
+        }
+    }
+    true
+}
+
+/// Commits a transaction that updates, creates and deletes keys across several dictionaries, recording
+/// every write it makes to the FLASH. Power loss is then simulated at each write: the image from before
+/// the commit is restored, the writes leading up to that point are replayed on top of it, and the PDDB
+/// is remounted. After recovery, every replay must show either none or all of the transaction.
+pub(crate) fn txn_power_loss_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const RECORDS: &'static str = "txn.records";
+    const INDEX: &'static str = "txn.index";
+    const CREATED: &'static str = "txn.created";
+    basis_cache.key_update(hw, RECORDS, "alpha", &[0xA1; 80], None, None, None, false)?;
+    basis_cache.key_update(hw, RECORDS, "beta", &[0xB2; 5000], None, None, None, false)?;
+    basis_cache.key_update(hw, INDEX, "a", b"alpha", None, None, None, false)?;
+    basis_cache.sync(hw, None, false)?;
+
+    let old_state = [
+        (RECORDS, "alpha", Some(vec![0xA1; 80])),
+        (RECORDS, "beta", Some(vec![0xB2; 5000])),
+        (RECORDS, "gamma", None),
+        (INDEX, "a", Some(b"alpha".to_vec())),
+        (INDEX, "g", None),
+        (CREATED, "first", None),
+    ];
+    let ops = vec![
+        TxnOp::Write { dict: RECORDS.into(), key: "alpha".into(), data: vec![0xC3; 300] },
+        TxnOp::Write { dict: RECORDS.into(), key: "gamma".into(), data: vec![0xD4; 6000] },
+        TxnOp::Delete { dict: RECORDS.into(), key: "beta".into() },
+        TxnOp::Delete { dict: INDEX.into(), key: "a".into() },
+        TxnOp::Write { dict: INDEX.into(), key: "g".into(), data: b"gamma".to_vec() },
+        TxnOp::Write { dict: CREATED.into(), key: "first".into(), data: vec![0xE5; 12] },
+    ];
+    let new_state = [
+        (RECORDS, "alpha", Some(vec![0xC3; 300])),
+        (RECORDS, "beta", None),
+        (RECORDS, "gamma", Some(vec![0xD4; 6000])),
+        (INDEX, "a", None),
+        (INDEX, "g", Some(b"gamma".to_vec())),
+        (CREATED, "first", Some(vec![0xE5; 12])),
+    ];
+    assert!(txn_state_matches(hw, basis_cache, &old_state), "test setup did not produce the expected state");
+
+    let baseline = hosted_raw_image()?;
+    hosted_write_log_start();
+    basis_cache.txn_commit(hw, &ops, Some(PDDB_DEFAULT_SYSTEM_BASIS))?;
+    let writes = hosted_write_log_take();
+    assert!(txn_state_matches(hw, basis_cache, &new_state), "committed transaction did not read back");
+    log::info!("transaction commit made {} writes", writes.len());
+
+    let mut outcomes = (0, 0); // (old, new)
+    for cut in 0..=writes.len() {
+        hosted_restore_image(&baseline);
+        hosted_replay_writes(&writes[..cut]);
+        let mut remounted = BasisCache::new();
+        remounted.basis_add(hw.pddb_mount().expect("PDDB did not mount after simulated power loss"));
+        remounted.txn_recover(hw, PDDB_DEFAULT_SYSTEM_BASIS)?;
+        if txn_state_matches(hw, &mut remounted, &old_state) {
+            outcomes.0 += 1;
+        } else if txn_state_matches(hw, &mut remounted, &new_state) {
+            outcomes.1 += 1;
+        } else {
+            panic!("power loss after write {} of {} left a partially applied transaction", cut, writes.len());
+        }
+        assert!(remounted.dict_attributes(hw, TXN_JOURNAL_DICT, None).is_err(), "journal survived recovery");
+        if cut == writes.len() {
+            // the last replay is the fully committed image; carry on with it
+            *basis_cache = remounted;
+        }
+    }
+    log::info!("power loss outcomes: {} rolled back, {} completed", outcomes.0, outcomes.1);
+    assert!(outcomes.0 > 0 && outcomes.1 > 0, "power loss never landed on one side of the commit point");
+
+    for dict in [RECORDS, INDEX, CREATED].iter() {
+        basis_cache.dict_remove(hw, dict, None, false)?;
+    }
+    basis_cache.sync(hw, None, false)?;
+    Ok(())
+}
+
+/// Reads every key in every dictionary into a map of (dict, key) -> data.
+pub(crate) fn snapshot_all(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> HashMap<(String, String), Vec<u8>> {
+    let mut snapshot = HashMap::<(String, String), Vec<u8>>::new();
+    for dict in basis_cache.dict_list(hw, None).iter() {
+        let (key_list, _, _) = basis_cache.key_list(hw, dict, None).unwrap();
+        for key in key_list.iter() {
+            let attr = basis_cache.key_attributes(hw, dict, key, None).unwrap();
+            let mut data = vec![0u8; attr.len];
+            let readlen = basis_cache.key_read(hw, dict, key, &mut data, Some(0), None).unwrap();
+            assert!(readlen == attr.len, "short read on {}:{}", dict, key);
+            snapshot.insert((dict.to_string(), key.to_string()), data);
+        }
+    }
+    snapshot
+}
+
+/// Checks that every key in `snapshot` is present in `basis_cache` with identical contents.
+pub(crate) fn verify_snapshot(hw: &mut PddbOs, basis_cache: &mut BasisCache, snapshot: &HashMap<(String, String), Vec<u8>>) {
+    for ((dict, key), expected) in snapshot.iter() {
+        let attr = match basis_cache.key_attributes(hw, dict, key, None) {
+            Ok(a) => a,
+            Err(e) => panic!("key {}:{} went missing: {:?}", dict, key, e),
+        };
+        assert!(attr.len == expected.len(), "length mismatch on {}:{}: {} vs {}", dict, key, attr.len, expected.len());
+        let mut data = vec![0u8; attr.len];
+        basis_cache.key_read(hw, dict, key, &mut data, Some(0), None).unwrap();
+        assert!(&data == expected, "data mismatch on {}:{}", dict, key);
+    }
+    log::info!("verified {} keys", snapshot.len());
+}
+
+/// Subscribes to a dictionary in `basis_name` and to one of its keys, reporting each change to the
+/// `Watchers` the way the server loop does, and checks which subscriptions hear about it. Events land on
+/// a callback server in this process, and are drained after every step. The basis is locked and unlocked
+/// along the way, so it must be mounted on entry; it is mounted again on exit.
+pub(crate) fn watch_test(hw: &mut PddbOs, basis_cache: &mut BasisCache, basis_name: &str, basis_pw: &str) -> Result<()> {
+    const DICT: &'static str = "watchtest";
+    const DICT_TOKEN: ApiToken = [1, 0, 0];
+    const KEY_TOKEN: ApiToken = [2, 0, 0];
+    const ABSENT_TOKEN: ApiToken = [3, 0, 0];
+
+    let sid = xous::create_server().unwrap();
+    let conn = xous::connect(sid).unwrap();
+    let mut watchers = Watchers::default();
+    for (token, dict, key) in [(DICT_TOKEN, DICT, None), (KEY_TOKEN, DICT, Some("watched")), (ABSENT_TOKEN, "watchabsent", None)] {
+        assert!(watchers.add(token, WatchRecord {
+            basis: None,
+            dict: dict.to_string(),
+            key: key.map(String::from),
+            conn,
+            owner: None,
+            std_event_id: None,
+        }));
+    }
+    let drain = || {
+        let mut events = Vec::<(ApiToken, WatchEventKind, String)>::new();
+        while let Some(msg) = xous::try_receive_message(sid).unwrap() {
+            let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+            let event = buffer.to_original::<PddbWatchEvent, _>().unwrap();
+            assert!(event.basis.as_str().unwrap() == basis_name, "event reported against the wrong basis");
+            events.push((event.token, event.kind, event.key.as_str().unwrap().to_string()));
+        }
+        events.sort_by_key(|(token, _, key)| (*token, key.clone()));
+        events
+    };
+    let expect = |events: Vec<(ApiToken, WatchEventKind, String)>, expected: &[(ApiToken, WatchEventKind, &str)], step: &str| {
+        let expected: Vec<(ApiToken, WatchEventKind, String)> = expected.iter()
+            .map(|(token, kind, key)| (*token, *kind, key.to_string()))
+            .collect();
+        assert!(events == expected, "{}: got {:?}, expected {:?}", step,
+            events.iter().map(|(t, k, key)| (t[0], *k, key)).collect::<Vec<_>>(),
+            expected.iter().map(|(t, k, key)| (t[0], *k, key)).collect::<Vec<_>>());
+    };
+
+    for key in ["watched", "other"] {
+        basis_cache.key_update(hw, DICT, key, key.as_bytes(), None, None, Some(basis_name), false)?;
+        watchers.notify(WatchEventKind::KeyCreated, basis_name, DICT, Some(key));
+    }
+    basis_cache.sync(hw, Some(basis_name), false)?;
+    expect(drain(), &[
+        (DICT_TOKEN, WatchEventKind::KeyCreated, "other"),
+        (DICT_TOKEN, WatchEventKind::KeyCreated, "watched"),
+        (KEY_TOKEN, WatchEventKind::KeyCreated, "watched"),
+    ], "create");
+
+    // locking reports to every subscription that could see something in the basis, including
+    // the key watch, even though none of its data changed
+    let affected = watchers.affected_by_basis(hw, basis_cache, basis_name);
+    basis_cache.basis_unmount(hw, basis_name)?;
+    watchers.notify_basis(affected, WatchEventKind::BasisLocked, basis_name);
+    expect(drain(), &[
+        (DICT_TOKEN, WatchEventKind::BasisLocked, ""),
+        (KEY_TOKEN, WatchEventKind::BasisLocked, "watched"),
+    ], "lock");
+    assert!(watchers.affected_by_basis(hw, basis_cache, basis_name).is_empty(), "a locked basis still affects watches");
+
+    let basis = basis_cache.basis_unlock(hw, basis_name, basis_pw, BasisRetentionPolicy::Persist)
+        .expect("couldn't unlock basis");
+    basis_cache.basis_add(basis);
+    let affected = watchers.affected_by_basis(hw, basis_cache, basis_name);
+    watchers.notify_basis(affected, WatchEventKind::BasisUnlocked, basis_name);
+    expect(drain(), &[
+        (DICT_TOKEN, WatchEventKind::BasisUnlocked, ""),
+        (KEY_TOKEN, WatchEventKind::BasisUnlocked, "watched"),
+    ], "unlock");
+
+    basis_cache.key_remove(hw, DICT, "other", Some(basis_name), false)?;
+    watchers.notify(WatchEventKind::KeyDeleted, basis_name, DICT, Some("other"));
+    expect(drain(), &[(DICT_TOKEN, WatchEventKind::KeyDeleted, "other")], "delete");
+
+    basis_cache.dict_remove(hw, DICT, Some(basis_name), false)?;
+    basis_cache.sync(hw, Some(basis_name), false)?;
+    watchers.notify(WatchEventKind::DictDeleted, basis_name, DICT, None);
+    expect(drain(), &[
+        (DICT_TOKEN, WatchEventKind::DictDeleted, ""),
+        (KEY_TOKEN, WatchEventKind::DictDeleted, ""),
+    ], "dict delete");
+
+    for token in [DICT_TOKEN, KEY_TOKEN, ABSENT_TOKEN] {
+        assert!(watchers.remove(&token, None).is_some());
+    }
+    assert!(!watchers.uses_conn(conn));
+    unsafe { xous::disconnect(conn).ok() };
+    xous::destroy_server(sid).unwrap();
+    Ok(())
+}
+
+/// Watches a dictionary through the running server with the client API, the way an application
+/// would. A value written in several chunks is reported once, and a subscriber that falls so far
+/// behind that its queue fills up misses events, but keeps its watch and doesn't hold up the PDDB.
+pub(crate) fn watch_server_test() {
+    use pddb::WatchEventKind;
+    use std::io::Write;
+    use std::sync::{Arc, Condvar, Mutex};
+    const DICT: &'static str = "watchservertest";
+    // well past the depth of a server's queue
+    const FLOOD: usize = 300;
+
+    let pddb = pddb::Pddb::new();
+    pddb.is_mounted_blocking();
+    let events = Arc::new(Mutex::new(Vec::<(WatchEventKind, String)>::new()));
+    // while the gate is closed, the callback sits on the event it was given, and the rest queue up
+    let gate = Arc::new((Mutex::new(true), Condvar::new()));
+    let token = pddb.watch(DICT, None, None, {
+        let events = events.clone();
+        let gate = gate.clone();
+        move |e| {
+            let (open, cv) = &*gate;
+            drop(cv.wait_while(open.lock().unwrap(), |open| !*open).unwrap());
+            events.lock().unwrap().push((e.kind, e.key.unwrap_or_default()));
+        }
+    }).unwrap();
+    let set_gate = |to: bool| {
+        let (open, cv) = &*gate;
+        *open.lock().unwrap() = to;
+        cv.notify_all();
+    };
+    // events arrive asynchronously, so wait until the last one expected shows up, then a moment longer
+    // for any that shouldn't be there
+    let wait_for = |kind: WatchEventKind, key: &str| -> Vec<(WatchEventKind, String)> {
+        for _ in 0..200 {
+            if events.lock().unwrap().iter().any(|(k, name)| *k == kind && name == key) {
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                return events.lock().unwrap().drain(..).collect();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        panic!("no {:?} for {} arrived, got {:?}", kind, key, events.lock().unwrap());
+    };
+
+    {
+        let mut key = pddb.get(DICT, "chunked", None, true, true, None, None::<fn()>).unwrap();
+        // several times what fits in one WriteKey message
+        key.write_all(&[0x5a; 10_000]).unwrap();
+    }
+    let got = wait_for(WatchEventKind::KeyUpdated, "chunked");
+    assert!(got == [(WatchEventKind::KeyCreated, "chunked".to_string()), (WatchEventKind::KeyUpdated, "chunked".to_string())],
+        "a chunked write was reported as {:?}", got);
+
+    set_gate(false);
+    {
+        let mut key = pddb.get(DICT, "flood", None, true, true, None, None::<fn()>).unwrap();
+        // every flush reports an update; if a full queue held up the PDDB, this would never finish
+        for i in 0..FLOOD {
+            key.write_all(&[i as u8]).unwrap();
+            key.flush().unwrap();
+        }
+    }
+    set_gate(true);
+    pddb.get(DICT, "after", None, true, true, None, None::<fn()>).unwrap();
+    let got = wait_for(WatchEventKind::KeyCreated, "after");
+    let updates = got.iter().filter(|(kind, key)| *kind == WatchEventKind::KeyUpdated && key == "flood").count();
+    log::info!("{} of {} updates got through a full queue", updates, FLOOD);
+    assert!(updates > 0 && updates < FLOOD, "expected some of the flood to be dropped, got {} of {}", updates, FLOOD);
+
+    pddb.unwatch(token).expect("a subscriber with a full queue lost its watch");
+    pddb.delete_dict(DICT, None).unwrap();
+}
+
 /* list of test cases:
     - [done] genenral integrity: allocate 4 dictionaries, each with 34 keys of various sizes ranging from 1k-9k.
     - [done] delete/add consistency: general integrity, delete a dictionary, then add a dictionary.
@@ -389,6 +905,12 @@ pub(crate) fn list_all(hw: &mut PddbOs, basis_cache: &mut BasisCache) {
         note: for faster stress-testing, we dialed the FSCB_PAGES to 4 and the FASTSPACE_PAGES to 1.
     - [done] basis search: create basis A, populate with general integrity. create basis B, add test entries.
         hide basis B, confirm original A; mount basis B, confirm B overlay.
+    - [done] dictionary compaction: fragment the dictionary index, compact, confirm all keys survive, including across a remount.
+    - [done] large record streaming: chunked writes and reads of a multi-page record across page boundaries, with a bounded cache.
+    - [done] paranoid erase: delete a small record with the paranoid flag, confirm no trace of its ciphertext in the raw image.
+    - [done] watches: report key changes, a basis lock and unlock, and a dictionary removal to dictionary and key watches.
+    - [done] watches through the server: one report per chunked write, and a subscriber with a full queue keeps its watch.
+    - [done] transaction power loss: replay every prefix of a commit's writes, confirm recovery yields all-or-nothing.
 */
 
 #[allow(dead_code)]
@@ -474,6 +996,23 @@ pub(crate) fn ci_tests(pddb_os: &mut PddbOs) -> Result<()> {
         pddb_os.dbg_dump(Some("dachecke4".to_string()), None);
         test_prune(pddb_os, &mut basis_cache);
 
+        log::info!("Doing large record streaming test");
+        large_stream_test(pddb_os, &mut basis_cache, None, None)?;
+        pddb_os.dbg_dump(Some("largee".to_string()), None);
+
+        log::info!("Doing large page cache LRU test");
+        large_lru_test(pddb_os, &mut basis_cache)?;
+
+        log::info!("Doing paranoid erase test");
+        paranoid_erase_test(pddb_os, &mut basis_cache)?;
+
+        log::info!("Doing transaction power loss test");
+        txn_power_loss_test(pddb_os, &mut basis_cache)?;
+
+        log::info!("Doing dictionary compaction test");
+        let compact_snapshot = compaction_test(pddb_os, &mut basis_cache, None, None)?;
+        pddb_os.dbg_dump(Some("compacte".to_string()), None);
+
         let mut pre_list = HashSet::<String>::new();
         for dict in basis_cache.dict_list(pddb_os, None).iter() {
             let (key_list, _, _) = basis_cache.key_list(pddb_os, dict, None).unwrap();
@@ -489,6 +1028,8 @@ pub(crate) fn ci_tests(pddb_os: &mut PddbOs) -> Result<()> {
             list_all(pddb_os, &mut basis_cache);
             pddb_os.dbg_dump(Some("remounte".to_string()), Some(&export));
         }
+        log::info!("Checking compacted dictionaries after remount");
+        verify_snapshot(pddb_os, &mut basis_cache, &compact_snapshot);
 
         log::info!("Mounting the second basis");
         if let Some(basis2) = basis_cache.basis_unlock(pddb_os,
@@ -560,12 +1101,42 @@ pub(crate) fn ci_tests(pddb_os: &mut PddbOs) -> Result<()> {
         assert!(merge2_list.difference(&merge_list).count() == 0, "merged list is different from the original list after remount");
         list_all(pddb_os, &mut basis_cache);
 
-        log::info!("CI done");
-        xous::rsyscall(xous::SysCall::Shutdown).unwrap();
+        log::info!("Doing watch test");
+        watch_test(pddb_os, &mut basis_cache, EXTRA_BASIS, EXTRA_BASIS_PW)?;
         Ok(())
     }
 }
 
+/// The CI tests that go through the server. They run on a thread of their own alongside the main loop,
+/// after `ci_tests()`, whose `result` they report along with their own, and shut the system down when
+/// they are done. `cargo xtask pddb-ci` picks the reports out of the log; a test that panics never
+/// reports `DONE`, which fails the run.
+#[allow(dead_code)]
+pub(crate) fn ci_server_tests(result: core::result::Result<(), String>) {
+    let (mut passed, mut failed) = (0, 0);
+    match result {
+        Ok(()) => {
+            passed += 1;
+            log::info!("{}PDDBCI.PASS,ci_tests,{}", xous::BOOKEND_START, xous::BOOKEND_END);
+        }
+        Err(e) => {
+            failed += 1;
+            log::info!("{}PDDBCI.FAIL,ci_tests,{}{}", xous::BOOKEND_START, e.replace('\n', " "), xous::BOOKEND_END);
+        }
+    }
+
+    log::info!("Doing watch server test");
+    watch_server_test();
+    passed += 1;
+    log::info!("{}PDDBCI.PASS,watch_server_test,{}", xous::BOOKEND_START, xous::BOOKEND_END);
+
+    log::info!("CI done");
+    log::info!("{}PDDBCI.DONE,{},{}{}", xous::BOOKEND_START, passed, failed, xous::BOOKEND_END);
+    // give the log a moment to drain before everything goes away
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    xous::rsyscall(xous::SysCall::Shutdown).unwrap();
+}
+
 fn test_prune(hw: &mut PddbOs, basis_cache: &mut BasisCache) {
     const TARGET_SIZE: usize = 150*1024;
     let cache_size = basis_cache.cache_size();
@@ -0,0 +1,259 @@
+use crate::api::*;
+use crate::backend::{BasisCache, PddbOs};
+use num_traits::*;
+use xous_ipc::Buffer;
+use std::collections::HashMap;
+
+pub(crate) struct WatchRecord {
+    pub(crate) basis: Option<String>,
+    pub(crate) dict: String,
+    /// if `None`, every key in the dictionary is watched
+    pub(crate) key: Option<String>,
+    pub(crate) conn: xous::CID,
+    /// only the process that made the subscription may cancel it
+    pub(crate) owner: Option<xous::PID>,
+    /// libstd subscribers get senres-encoded events with this message ID; native ones get `CbOp::Watch`
+    pub(crate) std_event_id: Option<usize>,
+}
+
+impl WatchRecord {
+    /// Returns `true` if a change to `key` of `dict` in `basis` concerns this watch. `key` is `None`
+    /// for changes that apply to the whole dictionary.
+    fn matches(&self, basis: &str, dict: &str, key: Option<&str>) -> bool {
+        if self.dict != dict {
+            return false;
+        }
+        if let Some(watched_basis) = &self.basis {
+            if watched_basis != basis {
+                return false;
+            }
+        }
+        match (&self.key, key) {
+            (Some(watched), Some(changed)) => watched == changed,
+            // dictionary-wide changes concern every watch on the dictionary, and
+            // dictionary-wide watches see every key
+            _ => true,
+        }
+    }
+}
+
+/// The set of active `WatchRequest`/`WatchRequestStd` subscriptions.
+#[derive(Default)]
+pub(crate) struct Watchers {
+    records: HashMap<ApiToken, WatchRecord>,
+    /// keys written to since their writer last flushed or closed them, as (basis, dict, key)
+    pending_updates: Vec<(String, String, String)>,
+}
+
+impl Watchers {
+    /// Returns `false` if `token` is already in use.
+    pub(crate) fn add(&mut self, token: ApiToken, record: WatchRecord) -> bool {
+        if self.records.contains_key(&token) {
+            return false;
+        }
+        self.records.insert(token, record);
+        true
+    }
+    /// Removes a subscription, provided it is owned by `pid`. The record is handed back so the caller
+    /// can decide if its connection can be recycled.
+    pub(crate) fn remove(&mut self, token: &ApiToken, pid: Option<xous::PID>) -> Option<WatchRecord> {
+        if self.records.get(token)?.owner != pid {
+            return None;
+        }
+        self.records.remove(token)
+    }
+    pub(crate) fn watches_dict(&self, dict: &str) -> bool {
+        self.records.values().any(|r| r.dict == dict)
+    }
+    pub(crate) fn uses_conn(&self, conn: xous::CID) -> bool {
+        self.records.values().any(|r| r.conn == conn)
+    }
+    /// Reports a change to `key` of `dict` in `basis`; `key` is `None` for `DictDeleted`.
+    pub(crate) fn notify(&mut self, kind: WatchEventKind, basis: &str, dict: &str, key: Option<&str>) {
+        // anything written before the change is reported first
+        self.report_updates(|_, d, k| d == dict && key.map(|key| key == k).unwrap_or(true));
+        self.report(kind, basis, dict, key);
+    }
+    /// Notes a write to `key` of `dict` in `basis`. A write arrives one chunk per message, so
+    /// `KeyUpdated` is held back until the writer is done with the key, and then reported once by
+    /// `finish_updates()` or `finish_all_updates()`.
+    pub(crate) fn key_written(&mut self, basis: &str, dict: &str, key: &str) {
+        if !self.records.values().any(|r| r.matches(basis, dict, Some(key))) {
+            return;
+        }
+        if !self.pending_updates.iter().any(|(b, d, k)| b == basis && d == dict && k == key) {
+            self.pending_updates.push((basis.to_string(), dict.to_string(), key.to_string()));
+        }
+    }
+    /// Reports the writes to `key` of `dict`, once the writer has closed it.
+    pub(crate) fn finish_updates(&mut self, dict: &str, key: &str) {
+        self.report_updates(|_, d, k| d == dict && k == key);
+    }
+    /// Reports every write held back so far, once the writes have been flushed.
+    pub(crate) fn finish_all_updates(&mut self) {
+        self.report_updates(|_, _, _| true);
+    }
+    fn report_updates(&mut self, done: impl Fn(&str, &str, &str) -> bool) {
+        if self.pending_updates.is_empty() {
+            return;
+        }
+        let (finished, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_updates)
+            .into_iter()
+            .partition(|(b, d, k)| done(b, d, k));
+        self.pending_updates = pending;
+        for (basis, dict, key) in finished {
+            self.report(WatchEventKind::KeyUpdated, &basis, &dict, Some(&key));
+        }
+    }
+    fn report(&mut self, kind: WatchEventKind, basis: &str, dict: &str, key: Option<&str>) {
+        let targets: Vec<ApiToken> = self.records.iter()
+            .filter(|(_, r)| r.matches(basis, dict, key))
+            .map(|(token, _)| *token)
+            .collect();
+        for token in targets {
+            self.send(token, kind, basis, dict, key);
+        }
+    }
+    /// Finds the subscriptions whose dictionary (and key, if one was given) exist in `basis`. This has to
+    /// be called while `basis` is mounted, so: after an unlock, but before a lock.
+    pub(crate) fn affected_by_basis(&self, pddb_os: &mut PddbOs, basis_cache: &mut BasisCache, basis: &str) -> Vec<ApiToken> {
+        self.records.iter()
+            .filter(|(_, r)| {
+                if r.basis.as_deref().map(|b| b != basis).unwrap_or(false) {
+                    return false;
+                }
+                match &r.key {
+                    Some(key) => basis_cache.key_attributes(pddb_os, &r.dict, key, Some(basis)).is_ok(),
+                    None => basis_cache.dict_attributes(pddb_os, &r.dict, Some(basis)).is_ok(),
+                }
+            })
+            .map(|(token, _)| *token)
+            .collect()
+    }
+    /// Reports a basis lock or unlock to the subscriptions previously found with `affected_by_basis()`.
+    pub(crate) fn notify_basis(&mut self, tokens: Vec<ApiToken>, kind: WatchEventKind, basis: &str) {
+        self.report_updates(|b, _, _| b == basis);
+        for token in tokens {
+            if let Some(r) = self.records.get(&token) {
+                let dict = r.dict.clone();
+                let key = r.key.clone();
+                self.send(token, kind, basis, &dict, key.as_deref());
+            }
+        }
+    }
+    fn send(&mut self, token: ApiToken, kind: WatchEventKind, basis: &str, dict: &str, key: Option<&str>) {
+        let record = match self.records.get(&token) {
+            Some(r) => r,
+            None => return,
+        };
+        let result = if let Some(id) = record.std_event_id {
+            crate::libstd::send_watch_event(record.conn, id, token, kind, basis, dict, key)
+        } else {
+            let event = PddbWatchEvent {
+                token,
+                kind,
+                basis: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis),
+                dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict),
+                key: xous_ipc::String::<KEY_NAME_LEN>::from_str(key.unwrap_or("")),
+            };
+            Buffer::into_buf(event).or(Err(xous::Error::InternalError))
+                .and_then(|buf| try_send(buf, record.conn, pddb::CbOp::Watch.to_u32().unwrap()))
+        };
+        match result {
+            Ok(()) => (),
+            // the subscriber is behind: it misses this event, but keeps its watch
+            Err(xous::Error::ServerQueueFull) => {
+                log::warn!("Watch on {}:{:?} is backed up, dropped {:?}", dict, key, kind);
+            }
+            // the subscriber went away without cancelling; stop reporting to it
+            Err(e @ xous::Error::ServerNotFound) | Err(e @ xous::Error::ProcessNotFound) => {
+                log::warn!("Watch on {}:{:?} is gone, removing it: {:?}", dict, key, e);
+                self.records.remove(&token);
+            }
+            Err(e) => log::warn!("Couldn't report {:?} on {}:{:?}: {:?}", kind, dict, key, e),
+        }
+    }
+}
+
+/// Moves `buf` to `conn` like `Buffer::send()`, except that a full queue fails with `ServerQueueFull`
+/// rather than stalling the PDDB until a slow subscriber catches up.
+pub(crate) fn try_send(buf: Buffer, conn: xous::CID, id: u32) -> Result<(), xous::Error> {
+    let (address, len, offset) = unsafe { buf.to_raw_parts() };
+    let msg = xous::MemoryMessage {
+        id: id as usize,
+        buf: unsafe { xous::MemoryRange::new(address, len)? },
+        offset: xous::MemoryAddress::new(offset),
+        valid: xous::MemorySize::new(len),
+    };
+    xous::try_send_message(conn, xous::Message::Move(msg))?;
+    // the memory belongs to the subscriber now; if the send failed, dropping `buf` frees it
+    core::mem::forget(buf);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(basis: Option<&str>, dict: &str, key: Option<&str>) -> WatchRecord {
+        WatchRecord {
+            basis: basis.map(String::from),
+            dict: String::from(dict),
+            key: key.map(String::from),
+            conn: 0,
+            owner: None,
+            std_event_id: None,
+        }
+    }
+
+    #[test]
+    fn dict_watch_sees_every_key() {
+        let r = record(None, "wlan.networks", None);
+        assert!(r.matches(".System", "wlan.networks", Some("home")));
+        assert!(r.matches("secret", "wlan.networks", Some("work")));
+        assert!(r.matches(".System", "wlan.networks", None));
+        assert!(!r.matches(".System", "wlan.config", Some("home")));
+    }
+
+    #[test]
+    fn key_watch_sees_only_its_key() {
+        let r = record(None, "vault.passwords", Some("example.com"));
+        assert!(r.matches(".System", "vault.passwords", Some("example.com")));
+        assert!(!r.matches(".System", "vault.passwords", Some("example.org")));
+        // removing the dictionary removes the key, too
+        assert!(r.matches(".System", "vault.passwords", None));
+    }
+
+    #[test]
+    fn basis_filter() {
+        let r = record(Some("secret"), "vault.passwords", None);
+        assert!(r.matches("secret", "vault.passwords", Some("example.com")));
+        assert!(!r.matches(".System", "vault.passwords", Some("example.com")));
+    }
+
+    #[test]
+    fn remove_checks_owner() {
+        let mut w = Watchers::default();
+        let token = [1, 2, 3];
+        let mut rec = record(None, "d", None);
+        rec.owner = xous::PID::new(5);
+        assert!(w.add(token, rec));
+        assert!(!w.add(token, record(None, "d", None)));
+        assert!(w.remove(&token, xous::PID::new(6)).is_none());
+        assert!(w.uses_conn(0));
+        assert!(w.remove(&token, xous::PID::new(5)).is_some());
+        assert!(!w.uses_conn(0));
+    }
+
+    #[test]
+    fn writes_are_held_back_once_per_key() {
+        let mut w = Watchers::default();
+        assert!(w.add([1, 2, 3], record(None, "d", Some("watched"))));
+        for _ in 0..3 {
+            w.key_written(".System", "d", "watched");
+        }
+        // nobody is told about a key that isn't watched
+        w.key_written(".System", "d", "other");
+        assert_eq!(w.pending_updates, [(".System".to_string(), "d".to_string(), "watched".to_string())]);
+    }
+}
@@ -48,7 +48,6 @@ pub struct CommonEnv {
     cb_registrations: HashMap::<u32, String::<256>>,
     trng: Trng,
     netmgr: net::NetManager,
-    #[allow(dead_code)]
     xns: xous_names::XousNames,
     boot_instant: std::time::Instant,
     /// make this communal so any number of commands can trigger or reset the performance counter, and/or perform logging
@@ -110,6 +109,8 @@ mod jtag_cmd; use jtag_cmd::*;
 mod net_cmd;  use net_cmd::*;
 mod pddb_cmd; use pddb_cmd::*;
 mod usb; use usb::*;
+mod top;      use top::*;
+mod names;    use names::*;
 
 #[cfg(not(feature="no-codec"))]
 mod test;
@@ -157,6 +158,8 @@ pub struct CmdEnv {
     pddb_cmd: PddbCmd,
     wlan_cmd: Wlan,
     usb_cmd: Usb,
+    top_cmd: Top,
+    names_cmd: Names,
 
     #[cfg(not(feature="no-codec"))]
     test_cmd: Test,
@@ -252,6 +255,8 @@ impl CmdEnv {
             pddb_cmd: {log::debug!("pddb"); PddbCmd::new(&xns)},
             wlan_cmd: {log::debug!("wlan"); Wlan::new()},
             usb_cmd: {log::debug!("usb"); Usb::new()},
+            top_cmd: {log::debug!("top"); Top::new()},
+            names_cmd: {log::debug!("names"); Names::new()},
 
             #[cfg(not(feature="no-codec"))]
             test_cmd: {log::debug!("test"); Test::new(&xns)},
@@ -301,6 +306,8 @@ impl CmdEnv {
             &mut self.net_cmd,
             &mut self.pddb_cmd,
             &mut self.usb_cmd,
+            &mut self.top_cmd,
+            &mut self.names_cmd,
 
             #[cfg(not(feature="no-codec"))]
             &mut self.test_cmd,
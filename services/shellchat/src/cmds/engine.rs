@@ -299,7 +299,7 @@ impl<'a> ShellCmdApi<'a> for Engine {
     fn process(&mut self, args: String::<1024>, env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
         use core::fmt::Write;
         let mut ret = String::<1024>::new();
-        let helpstring = "engine [check] [bench] [benchdh] [susres] [dh] [ed] [wycheproof]";
+        let helpstring = "engine [check] [bench] [benchdh] [susres] [dh] [ed] [wycheproof] [xnsauth]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
 
@@ -514,6 +514,79 @@ impl<'a> ShellCmdApi<'a> for Engine {
                         write!(ret, "Passed ed25519 simple check").unwrap();
                     }
                 }
+                "xnsauth" => {
+                    // exercises authenticated registration and lookup in xous-names with throwaway keys
+                    use ed25519_dalek::*;
+                    let mut sk_bytes = [0u8; SECRET_KEY_LENGTH];
+                    env.trng.fill_bytes_via_next(&mut sk_bytes);
+                    let secret = SecretKey::from_bytes(&sk_bytes).unwrap();
+                    let public: PublicKey = (&secret).into();
+                    let keypair = Keypair { secret, public };
+                    env.trng.fill_bytes_via_next(&mut sk_bytes);
+                    let other_secret = SecretKey::from_bytes(&sk_bytes).unwrap();
+                    let other_public: PublicKey = (&other_secret).into();
+                    let other_keypair = Keypair { secret: other_secret, public: other_public };
+                    let name = format!("_xnsauth test {:08x}_", env.trng.get_u32().unwrap());
+                    let mut pass = true;
+
+                    let sid = match env.xns.register_name_authenticated(&name, None, public.as_bytes(),
+                        |msg| keypair.sign(msg).to_bytes()
+                    ) {
+                        Ok(sid) => Some(sid),
+                        Err(e) => {
+                            write!(ret, "Authenticated registration failed: {:?}\n", e).unwrap();
+                            pass = false;
+                            None
+                        }
+                    };
+                    match env.xns.request_authenticated_connection(&name, public.as_bytes()) {
+                        Ok(cid) => unsafe { xous::disconnect(cid).ok(); },
+                        Err(e) => {
+                            write!(ret, "Lookup with the right key failed: {:?}\n", e).unwrap();
+                            pass = false;
+                        }
+                    }
+                    if env.xns.request_authenticated_connection(&name, other_public.as_bytes()).is_ok() {
+                        write!(ret, "Lookup with the wrong key succeeded\n").unwrap();
+                        pass = false;
+                    }
+                    if env.xns.register_name_authenticated(&name, None, other_public.as_bytes(),
+                        |msg| other_keypair.sign(msg).to_bytes()
+                    ).is_ok() {
+                        write!(ret, "Squatter registered over an authenticated name\n").unwrap();
+                        pass = false;
+                    }
+                    // a signature from the wrong key must not complete a registration
+                    let forged_name = format!("{}forged", name);
+                    match env.xns.register_name_authenticated(&forged_name, None, public.as_bytes(),
+                        |msg| other_keypair.sign(msg).to_bytes()
+                    ) {
+                        Err(xous::Error::AccessDenied) => (),
+                        r => {
+                            write!(ret, "Forged registration was not denied: {:?}\n", r).unwrap();
+                            pass = false;
+                        }
+                    }
+                    if env.xns.request_authenticated_connection(&forged_name, public.as_bytes()).is_ok() {
+                        write!(ret, "Lookup of a failed registration succeeded\n").unwrap();
+                        pass = false;
+                    }
+                    // unauthenticated servers can't be reached with an authenticated lookup
+                    if env.xns.request_authenticated_connection(crate::SERVER_NAME_SHELLCHAT, public.as_bytes()).is_ok() {
+                        write!(ret, "Authenticated lookup of an unauthenticated server succeeded\n").unwrap();
+                        pass = false;
+                    }
+                    if let Some(sid) = sid {
+                        env.xns.unregister_server(sid).ok();
+                        xous::destroy_server(sid).ok();
+                    }
+                    if pass {
+                        write!(ret, "Passed xous-names authentication check").unwrap();
+                        log::info!("{}BENCH,XNSAUTH,PASS,{}", xous::BOOKEND_START, xous::BOOKEND_END);
+                    } else {
+                        log::info!("{}BENCH,XNSAUTH,FAIL,{}", xous::BOOKEND_START, xous::BOOKEND_END);
+                    }
+                }
                 "wycheproof" => {
                     use wycheproof::*;
                     use hex::ToHex;
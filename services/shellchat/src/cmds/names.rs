@@ -0,0 +1,110 @@
+use crate::{ShellCmdApi, CommonEnv};
+use xous_ipc::String;
+use xous_names::api::{ConnectionEventKind, NameInfo};
+
+/// Names and log entries printed per report, so the result fits in the response string.
+const NAME_ROWS: usize = 16;
+const LOG_ROWS: usize = 16;
+
+#[derive(Debug)]
+pub struct Names {
+}
+impl Names {
+    pub fn new() -> Self {
+        Names {
+        }
+    }
+}
+
+/// The name server sends PID 0 where it withholds who connected to an authenticated server.
+fn pid(pid: u8) -> std::string::String {
+    if pid == 0 { "-".to_string() } else { pid.to_string() }
+}
+
+/// Connections made out of the maximum, with the authenticated ones split out: `2+1/4`.
+fn conns(info: &NameInfo) -> std::string::String {
+    let made = if info.auth_conns > 0 {
+        format!("{}+{}", info.current_conns, info.auth_conns)
+    } else {
+        info.current_conns.to_string()
+    };
+    match info.max_conns {
+        Some(max) => format!("{}/{}", made, max),
+        None => format!("{}/-", made),
+    }
+}
+
+impl<'a> ShellCmdApi<'a> for Names {
+    cmd_api!(names);
+
+    fn process(&mut self, args: String::<1024>, env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::<1024>::new();
+        let helpstring = "names [limited] [log] [<name>]";
+
+        let mut tokens = args.as_str().unwrap().split(' ');
+
+        let sub_cmd = tokens.next().unwrap_or("");
+        match sub_cmd {
+            "" | "limited" => {
+                let limited = sub_cmd == "limited";
+                let table = env.xns.name_table()?;
+                write!(ret, "{} names, * = authenticated\n", table.len()).unwrap();
+                write!(ret, "pid conns  refused name\n").unwrap();
+                let rows: Vec<&NameInfo> = table.iter().filter(|info| !limited || info.max_conns.is_some()).collect();
+                for info in rows.iter().take(NAME_ROWS) {
+                    write!(ret, "{:<3} {:<6} {:<7} {}{}\n",
+                        info.owner, conns(info), info.refused,
+                        info.name, if info.authenticated { "*" } else { "" }
+                    ).unwrap();
+                }
+                if rows.len() > NAME_ROWS {
+                    write!(ret, "... {} more\n", rows.len() - NAME_ROWS).unwrap();
+                }
+            }
+            "log" => {
+                let log = env.xns.connection_log()?;
+                let skip = log.len().saturating_sub(LOG_ROWS);
+                if skip > 0 {
+                    write!(ret, "... {} earlier\n", skip).unwrap();
+                }
+                write!(ret, "seq   pid event    name\n").unwrap();
+                for event in log.iter().skip(skip) {
+                    let kind = match event.kind {
+                        ConnectionEventKind::Connected => "connect",
+                        ConnectionEventKind::AuthenticatedConnected => "auth",
+                        ConnectionEventKind::Refused => "REFUSED",
+                        ConnectionEventKind::Disconnected => "disconn",
+                    };
+                    write!(ret, "{:<5} {:<3} {:<8} {}\n", event.seq, pid(event.pid), kind, event.name).unwrap();
+                }
+            }
+            _ => {
+                // names may contain spaces, so take everything that was typed
+                let name = args.as_str().unwrap();
+                let table = env.xns.name_table()?;
+                match table.iter().find(|info| info.name.as_str().unwrap_or("") == name) {
+                    Some(info) => {
+                        write!(ret, "{}\nowner: PID {}\n", info.name, info.owner).unwrap();
+                        write!(ret, "connections: {}", conns(info)).unwrap();
+                        if let Some(remaining) = info.remaining_conns() {
+                            write!(ret, ", {} remaining", remaining).unwrap();
+                        }
+                        write!(ret, "\nauthenticated: {}, {} connections\n",
+                            if info.authenticated { "yes" } else { "no" }, info.auth_conns
+                        ).unwrap();
+                        write!(ret, "refused: {}\nconnected PIDs:", info.refused).unwrap();
+                        if info.authenticated && info.connected_pids().next().is_none() {
+                            write!(ret, " -").unwrap();
+                        }
+                        for pid in info.connected_pids() {
+                            write!(ret, " {}", pid.get()).unwrap();
+                        }
+                    }
+                    None => write!(ret, "{}", helpstring).unwrap(),
+                }
+            }
+        }
+        Ok(Some(ret))
+    }
+}
@@ -78,10 +78,10 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
         use core::fmt::Write;
         let mut ret = String::<1024>::new();
         #[cfg(any(feature="precursor", feature="renode"))]
-        let helpstring = "net [udp [rx socket] [tx dest socket]] [ping [host] [count]] [tcpget host/path]";
+        let helpstring = "net [udp [rx socket] [tx dest socket]] [ping [host] [count]] [tcpget host/path] [pcap start [protocol] [port] | stop [pddb | usb]]";
         // no ping in hosted mode -- why would you need it? we're using the host's network connection.
         #[cfg(not(target_os = "xous"))]
-        let helpstring = "net [udp [port]] [count]] [tcpget host/path]";
+        let helpstring = "net [udp [port]] [count]] [tcpget host/path] [pcap start [protocol] [port] | stop [pddb | usb]]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
 
@@ -308,6 +308,55 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                         }
                     }
                 }
+                "pcap" => {
+                    match tokens.next() {
+                        Some("start") => {
+                            let mut filter = net::PcapFilter::default();
+                            let mut usage = false;
+                            for token in tokens {
+                                match token {
+                                    "arp" => filter.protocol = Some(net::PcapProtocol::Arp),
+                                    "icmp" => filter.protocol = Some(net::PcapProtocol::Icmp),
+                                    "tcp" => filter.protocol = Some(net::PcapProtocol::Tcp),
+                                    "udp" => filter.protocol = Some(net::PcapProtocol::Udp),
+                                    _ => match token.parse::<u16>() {
+                                        Ok(port) => filter.port = Some(port),
+                                        Err(_) => usage = true,
+                                    }
+                                }
+                            }
+                            if usage {
+                                write!(ret, "Usage: net pcap start [arp | icmp | tcp | udp] [port]").unwrap();
+                            } else {
+                                match env.netmgr.pcap_start(filter) {
+                                    Ok(_) => write!(ret, "Packet capture started"),
+                                    Err(e) => write!(ret, "Couldn't start packet capture: {:?}", e),
+                                }.unwrap();
+                            }
+                        }
+                        Some("stop") => {
+                            let sink = match tokens.next() {
+                                None | Some("pddb") => Some(net::PcapSink::Pddb),
+                                Some("usb") => Some(net::PcapSink::UsbSerial),
+                                Some(_) => None,
+                            };
+                            match sink.map(|sink| env.netmgr.pcap_stop(sink)) {
+                                Some(Ok(stats)) => {
+                                    write!(ret, "Captured {} frames ({} dropped)", stats.frames, stats.dropped).unwrap();
+                                    if sink == Some(net::PcapSink::Pddb) {
+                                        write!(ret, " to {}:{}", net::PCAP_DICT, net::PCAP_KEY).unwrap();
+                                    }
+                                }
+                                Some(Err(net::PcapError::SinkUnavailable)) => {
+                                    write!(ret, "Couldn't write packet capture: the PDDB must be mounted, or the USB serial core connected").unwrap()
+                                }
+                                Some(Err(e)) => write!(ret, "Couldn't write packet capture: {:?}", e).unwrap(),
+                                None => write!(ret, "Usage: net pcap stop [pddb | usb]").unwrap(),
+                            }
+                        }
+                        _ => write!(ret, "Usage: net pcap start [protocol] [port] | stop [pddb | usb]").unwrap(),
+                    }
+                }
                 #[cfg(feature="nettest")]
                 "test" => {
                     crate::nettests::start_batch_tests();
@@ -658,6 +707,8 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                     None => {
                         // rebind the scalar args to the Ping convention
                         let op = arg1;
+                        // only the top 4 bytes of an IPv6 address fit, so those aren't shown
+                        let ipv6 = op & net::NET_PING_IPV6 != 0;
                         let addr = IpAddr::from((*arg2 as u32).to_be_bytes());
                         let seq_or_addr = *arg3;
                         let timestamp = *arg4;
@@ -668,31 +719,37 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                                 return Ok(None);
                             }
                             Some(NetPingCallback::NoErr) => {
-                                match addr {
-                                    IpAddr::V4(_) => {
-                                        write!(ret, "Pong from {:?} seq {} received: {} ms",
+                                if ipv6 {
+                                    write!(ret, "Ipv6 pong seq {} received: {} ms", seq_or_addr, timestamp).unwrap();
+                                } else {
+                                    write!(ret, "Pong from {:?} seq {} received: {} ms",
+                                    addr,
+                                    seq_or_addr,
+                                    timestamp).unwrap();
+                                    log::info!("{}NET.PONG,{:?},{},{},{}",
+                                        xous::BOOKEND_START,
                                         addr,
                                         seq_or_addr,
-                                        timestamp).unwrap();
-                                        log::info!("{}NET.PONG,{:?},{},{},{}",
-                                            xous::BOOKEND_START,
-                                            addr,
-                                            seq_or_addr,
-                                            timestamp,
-                                            xous::BOOKEND_END
-                                        );
-                                    },
-                                    IpAddr::V6(_) => {
-                                        write!(ret, "Ipv6 pong received: {} ms", timestamp).unwrap();
-                                    },
+                                        timestamp,
+                                        xous::BOOKEND_END
+                                    );
                                 }
                             }
                             Some(NetPingCallback::Timeout) => {
-                                write!(ret, "Ping to {:?} timed out", addr).unwrap();
+                                if ipv6 {
+                                    write!(ret, "Ipv6 ping seq {} timed out", seq_or_addr).unwrap();
+                                } else {
+                                    write!(ret, "Ping to {:?} timed out", addr).unwrap();
+                                }
                             }
                             Some(NetPingCallback::Unreachable) => {
-                                let code = net::Icmpv4DstUnreachable::from((op >> 24) as u8);
-                                write!(ret, "Ping to {:?} unreachable: {:?}", addr, code).unwrap();
+                                if ipv6 {
+                                    let code = net::Icmpv6DstUnreachable::from((op >> 24) as u8);
+                                    write!(ret, "Ipv6 ping unreachable: {:?}", code).unwrap();
+                                } else {
+                                    let code = net::Icmpv4DstUnreachable::from((op >> 24) as u8);
+                                    write!(ret, "Ping to {:?} unreachable: {:?}", addr, code).unwrap();
+                                }
                             }
                             None => {
                                 log::error!("Unknown opcode received in NetCmd callback: {:?}", op);
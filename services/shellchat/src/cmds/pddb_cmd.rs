@@ -58,9 +58,9 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
     fn process(&mut self, args: String::<1024>, _env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
         let mut ret = String::<1024>::new();
         #[cfg(not(feature="pddbtest"))]
-        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [write] [writeover] [query] [copy] [dictdelete] [keydelete] [churn] [flush] [sync]";
+        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [write] [writeover] [query] [copy] [dictdelete] [keydelete] [churn] [flush] [sync] [compact]";
         #[cfg(feature="pddbtest")]
-        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [write] [writeover] [query] [copy] [dictdelete] [keydelete] [churn] [flush] [sync]\n[test]";
+        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [write] [writeover] [query] [copy] [dictdelete] [keydelete] [churn] [flush] [sync] [compact]\n[test]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
         if let Some(sub_cmd) = tokens.next() {
@@ -414,6 +414,12 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
                     write!(ret, "Sync result code: {:?}\n", self.pddb.sync()).ok();
                     log::info!("{}PDDB.SYNCDONE,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                 }
+                "compact" => {
+                    match self.pddb.compact_dicts() {
+                        Ok(relocated) => write!(ret, "Compaction relocated {} dictionaries", relocated).ok(),
+                        Err(e) => write!(ret, "Compaction error: {:?}", e).ok(),
+                    };
+                }
                 "hwtest" => {
                     let mut args = [0u32; 4];
                     for (index, token) in tokens.enumerate() {
@@ -1303,6 +1309,69 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
                     log::info!("deserialized: {:?}", deserialized);
                 }
                 #[cfg(not(target_os = "xous"))]
+                "watchtest" => {
+                    use pddb::WatchEventKind;
+                    use std::sync::{Arc, Mutex};
+                    const WATCH_DICT: &'static str = "watchtest";
+                    let dict_events = Arc::new(Mutex::new(Vec::<pddb::WatchEvent>::new()));
+                    let key_events = Arc::new(Mutex::new(Vec::<pddb::WatchEvent>::new()));
+                    let dict_watch = self.pddb.watch(WATCH_DICT, None, None, {
+                        let dict_events = dict_events.clone();
+                        move |e| dict_events.lock().unwrap().push(e)
+                    }).unwrap();
+                    let key_watch = self.pddb.watch(WATCH_DICT, Some("watched"), None, {
+                        let key_events = key_events.clone();
+                        move |e| key_events.lock().unwrap().push(e)
+                    }).unwrap();
+
+                    // native API: create, write and delete two keys
+                    for name in ["watched", "other"] {
+                        let mut key = self.pddb.get(WATCH_DICT, name, None, true, true, None, None::<fn()>).unwrap();
+                        key.write(name.as_bytes()).unwrap();
+                    }
+                    self.pddb.delete_key(WATCH_DICT, "other", None).ok();
+                    self.pddb.delete_key(WATCH_DICT, "watched", None).ok();
+                    self.pddb.delete_dict(WATCH_DICT, None).ok();
+                    self.pddb.sync().ok();
+                    // events are delivered asynchronously; give the callback thread a moment to drain them
+                    std::thread::sleep(std::time::Duration::from_millis(500));
+
+                    let kinds = |events: &Arc<Mutex<Vec<pddb::WatchEvent>>>, key: &str| -> Vec<WatchEventKind> {
+                        events.lock().unwrap().iter()
+                            .filter(|e| e.key.as_deref() == Some(key))
+                            .map(|e| e.kind)
+                            .collect()
+                    };
+                    let mut passing = true;
+                    for (events, key, expected) in [
+                        (&dict_events, "watched", vec![WatchEventKind::KeyCreated, WatchEventKind::KeyUpdated, WatchEventKind::KeyDeleted]),
+                        (&dict_events, "other", vec![WatchEventKind::KeyCreated, WatchEventKind::KeyUpdated, WatchEventKind::KeyDeleted]),
+                        (&key_events, "watched", vec![WatchEventKind::KeyCreated, WatchEventKind::KeyUpdated, WatchEventKind::KeyDeleted]),
+                        (&key_events, "other", vec![]),
+                    ] {
+                        let got = kinds(events, key);
+                        if got != expected {
+                            log::info!("watch on {} saw {:?}, expected {:?}", key, got, expected);
+                            passing = false;
+                        }
+                    }
+                    if !dict_events.lock().unwrap().iter().any(|e| e.kind == WatchEventKind::DictDeleted) {
+                        log::info!("dictionary deletion was not reported");
+                        passing = false;
+                    }
+                    self.pddb.unwatch(dict_watch).unwrap();
+                    self.pddb.unwatch(key_watch).unwrap();
+                    if self.pddb.unwatch(key_watch).is_ok() {
+                        log::info!("a cancelled watch could be cancelled twice");
+                        passing = false;
+                    }
+                    if passing {
+                        write!(ret, "watch test passed").ok();
+                    } else {
+                        write!(ret, "watch test failed, see log").ok();
+                    }
+                }
+                #[cfg(not(target_os = "xous"))]
                 "bulktest" => {
                     let bulk_read = self.pddb.read_dict(TEST_DICT, None, Some(131072)).unwrap();
                     log::info!("read {} records", bulk_read.len());
@@ -0,0 +1,129 @@
+use crate::{ShellCmdApi, CommonEnv};
+use xous_ipc::String;
+use std::collections::HashMap;
+
+/// Rows printed per report, so the result fits in the response string.
+const TOP_ROWS: usize = 12;
+
+#[derive(Debug)]
+pub struct Top {
+    /// Counters from the previous `top`, so each report covers the time since then.
+    last: HashMap<u8, xous::ProcessUsage>,
+    last_ms: u64,
+}
+impl Top {
+    pub fn new() -> Self {
+        Top {
+            last: HashMap::new(),
+            last_ms: 0,
+        }
+    }
+}
+
+fn all_processes() -> Vec<(u8, xous::ProcessUsage)> {
+    let mut list = Vec::new();
+    for pid in 1..=u8::MAX {
+        if let Ok(usage) = xous::process_usage(xous::PID::new(pid).unwrap()) {
+            list.push((pid, usage));
+        }
+    }
+    list
+}
+
+impl<'a> ShellCmdApi<'a> for Top {
+    cmd_api!(top);
+
+    fn process(&mut self, args: String::<1024>, env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::<1024>::new();
+        let helpstring = "top [total] [threads <pid>] [servers]";
+
+        let mut tokens = args.as_str().unwrap().split(' ');
+
+        let sub_cmd = tokens.next().unwrap_or("");
+        match sub_cmd {
+            "" | "total" => {
+                let total = sub_cmd == "total";
+                let now = env.ticktimer.elapsed_ms();
+                let current = all_processes();
+                let mut rows: Vec<(u8, xous::ProcessUsage)> = current.iter().map(|(pid, usage)| {
+                    let since = if total { None } else { self.last.get(pid) };
+                    match since {
+                        Some(prev) => (*pid, xous::ProcessUsage {
+                            quanta: usage.quanta.wrapping_sub(prev.quanta),
+                            context_switches: usage.context_switches.wrapping_sub(prev.context_switches),
+                            messages_sent: usage.messages_sent.wrapping_sub(prev.messages_sent),
+                            messages_received: usage.messages_received.wrapping_sub(prev.messages_received),
+                            bytes_lent: usage.bytes_lent.wrapping_sub(prev.bytes_lent),
+                        }),
+                        None => (*pid, *usage),
+                    }
+                }).collect();
+                rows.sort_by(|a, b| b.1.quanta.cmp(&a.1.quanta).then(b.1.context_switches.cmp(&a.1.context_switches)));
+
+                if total || self.last.is_empty() {
+                    write!(ret, "Totals since boot, {} processes\n", current.len()).unwrap();
+                } else {
+                    write!(ret, "Last {}ms, {} processes\n", now - self.last_ms, current.len()).unwrap();
+                }
+                write!(ret, "pid quanta  switch  sent    recv    lent(k)\n").unwrap();
+                for (pid, usage) in rows.iter().take(TOP_ROWS) {
+                    write!(ret, "{:<3} {:<7} {:<7} {:<7} {:<7} {}\n",
+                        pid, usage.quanta, usage.context_switches,
+                        usage.messages_sent, usage.messages_received, usage.bytes_lent / 1024
+                    ).unwrap();
+                }
+
+                if !total {
+                    self.last = current.into_iter().collect();
+                    self.last_ms = now;
+                }
+            }
+            "threads" => {
+                let pid = match tokens.next().and_then(|p| p.parse::<u8>().ok()).and_then(xous::PID::new) {
+                    Some(pid) => pid,
+                    None => {
+                        write!(ret, "{}", helpstring).unwrap();
+                        return Ok(Some(ret));
+                    }
+                };
+                match xous::process_usage(pid) {
+                    Ok(usage) => {
+                        write!(ret, "PID {}: {} quanta, {} switches\n", pid, usage.quanta, usage.context_switches).unwrap();
+                        write!(ret, "tid quanta  switch\n").unwrap();
+                        let mut tid = 0;
+                        while let Ok(thread) = xous::thread_usage(pid, tid) {
+                            if thread.context_switches != 0 {
+                                write!(ret, "{:<3} {:<7} {}\n", tid, thread.quanta, thread.context_switches).unwrap();
+                            }
+                            tid += 1;
+                        }
+                    }
+                    Err(e) => write!(ret, "PID {}: {:?}", pid, e).unwrap(),
+                }
+            }
+            "servers" => {
+                let mut servers = Vec::new();
+                let mut slot = 0;
+                while let Ok(maybe_server) = xous::server_usage(slot) {
+                    if let Some(server) = maybe_server {
+                        servers.push((slot, server));
+                    }
+                    slot += 1;
+                }
+                servers.sort_by(|a, b| b.1.messages_received.cmp(&a.1.messages_received));
+                write!(ret, "{} servers, busiest first\n", servers.len()).unwrap();
+                write!(ret, "idx pid recv    lent(k)\n").unwrap();
+                for (slot, server) in servers.iter().take(TOP_ROWS) {
+                    write!(ret, "{:<3} {:<3} {:<7} {}\n",
+                        slot, server.pid, server.messages_received, server.bytes_lent / 1024
+                    ).unwrap();
+                }
+            }
+            _ => {
+                write!(ret, "{}", helpstring).unwrap();
+            }
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,112 @@
+use crate::ui::*;
+use locales::t;
+use std::io::ErrorKind;
+
+const BASIS_NAME: &str = "uitest";
+const BASIS_PASSWORD: &str = "correct horse";
+
+fn open_main_menu(ui: &mut Ui) -> TestResult {
+    ui.settle()?;
+    ui.key(HOME);
+    ui.wait_for_text(t!("mainmenu.closemenu", locales::LANG))
+        .map(|_| ())
+}
+
+/// Opens the main menu, checks its items are on screen, then closes it again.
+pub fn main_menu(ui: &mut Ui) -> TestResult {
+    ui.settle()?;
+    ui.key(HOME);
+    // the items are drawn top to bottom
+    ui.wait_for_text(t!("mainmenu.sleep", locales::LANG))?;
+    ui.wait_for_text(t!("mainmenu.app", locales::LANG))?;
+    ui.wait_for_text(t!("mainmenu.pddb", locales::LANG))?;
+    let close = ui.wait_for_text(t!("mainmenu.closemenu", locales::LANG))?;
+
+    // the item has to have made it to the screen, and not as a solid block
+    let bounds = close.bounds.ok_or("menu item has no bounds")?;
+    let area = (bounds.br.x - bounds.tl.x + 1) as usize * (bounds.br.y - bounds.tl.y + 1) as usize;
+    let dark = ui.frame()?.dark_pixels(bounds);
+    if dark == 0 || dark == area {
+        return Err(format!(
+            "menu item drawn as {} dark pixels out of {}",
+            dark, area
+        ));
+    }
+
+    ui.select_menu_item(t!("mainmenu.closemenu", locales::LANG))?;
+    ui.settle()
+}
+
+/// Runs a PDDB basis call on its own thread, as it blocks until the password is typed in.
+fn basis_call(
+    f: impl FnOnce(&pddb::Pddb) -> std::io::Result<()> + Send + 'static,
+) -> std::thread::JoinHandle<std::io::Result<()>> {
+    std::thread::spawn(move || f(&pddb::Pddb::new()))
+}
+
+fn join(call: std::thread::JoinHandle<std::io::Result<()>>) -> std::io::Result<()> {
+    call.join()
+        .unwrap_or_else(|_| Err(std::io::Error::new(ErrorKind::Other, "basis call panicked")))
+}
+
+/// Creates a secret basis, then unlocks it with a wrong and then the right password.
+pub fn pddb_unlock(ui: &mut Ui) -> TestResult {
+    ui.settle()?;
+    let create = basis_call(|pddb| pddb.create_basis(BASIS_NAME));
+    ui.wait_for_text(t!("pddb.password", locales::LANG))?;
+    ui.enter_text(BASIS_PASSWORD);
+    join(create).map_err(|e| format!("couldn't create the basis: {:?}", e))?;
+
+    // a wrong password offers another try, which is turned down
+    let unlock = basis_call(|pddb| pddb.unlock_basis(BASIS_NAME, None));
+    ui.wait_for_text(t!("pddb.password", locales::LANG))?;
+    ui.enter_text("not the password");
+    ui.wait_for_text(t!("pddb.badpass", locales::LANG))?;
+    ui.select_radio(1, 2)?;
+    match join(unlock) {
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => (),
+        other => return Err(format!("wrong password gave {:?}", other)),
+    }
+
+    let unlock = basis_call(|pddb| pddb.unlock_basis(BASIS_NAME, None));
+    ui.wait_for_text(t!("pddb.password", locales::LANG))?;
+    ui.enter_text(BASIS_PASSWORD);
+    join(unlock).map_err(|e| format!("couldn't unlock the basis: {:?}", e))?;
+
+    let pddb = pddb::Pddb::new();
+    if !pddb.list_basis().iter().any(|name| name == BASIS_NAME) {
+        return Err("unlocked basis isn't listed".into());
+    }
+    pddb.lock_basis(BASIS_NAME)
+        .map_err(|e| format!("couldn't lock the basis: {:?}", e))?;
+    ui.settle()
+}
+
+/// Switches to the vault, and adds a password through its menu.
+pub fn vault_new_item(ui: &mut Ui) -> TestResult {
+    const DESCRIPTION: &str = "uitest.example.com";
+
+    open_main_menu(ui)?;
+    ui.select_menu_item(t!("mainmenu.app", locales::LANG))?;
+    ui.wait_for_text(t!("appmenu.vault", locales::LANG))?;
+    ui.select_menu_item(t!("appmenu.vault", locales::LANG))?;
+    ui.settle()?;
+
+    // F3 shows the passwords, F4 raises the vault's menu
+    ui.key(F3);
+    ui.settle()?;
+    ui.key(F4);
+    ui.wait_for_text(t!("vault.menu_addnew", locales::LANG))?;
+    ui.select_menu_item(t!("vault.menu_addnew", locales::LANG))?;
+
+    ui.wait_for_text(t!("vault.newitem.name", locales::LANG))?;
+    ui.enter_text(DESCRIPTION);
+    ui.wait_for_text(t!("vault.newitem.username", locales::LANG))?;
+    ui.enter_text("uitest");
+    // take the suggested password
+    ui.wait_for_text(t!("vault.newitem.password", locales::LANG))?;
+    ui.key(ENTER);
+
+    ui.wait_for_text(DESCRIPTION).map(|_| ())?;
+    ui.settle()
+}
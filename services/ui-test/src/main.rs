@@ -0,0 +1,39 @@
+//! End-to-end tests of the UI, run in hosted mode by `cargo xtask ui-test`.
+//!
+//! The harness boots along with the rest of the services, types into the keyboard server with
+//! `InjectKey`, and follows what the graphics server draws through its test hooks. The results
+//! are reported under the `UITEST` tag by `hosted_test::run()`.
+//!
+//! `XOUS_UI_TEST` limits the run to a comma-separated list of tests.
+
+mod flows;
+mod ui;
+
+use hosted_test::Test;
+use ui::Ui;
+
+/// Every test, in the order they run. Later tests start from wherever the earlier ones left the
+/// UI, so each one should leave it as it found it.
+const TESTS: [Test<Ui>; 3] = [
+    ("main_menu", flows::main_menu),
+    ("pddb_unlock", flows::pddb_unlock),
+    ("vault_new_item", flows::vault_new_item),
+];
+const FILTER_ENV: &str = "XOUS_UI_TEST";
+
+/// Time for the UI to finish coming up once the PDDB is mounted
+const BOOT_SETTLE_MS: usize = 2000;
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    // the main menu is only allowed once the PDDB is up
+    pddb::Pddb::new().is_mounted_blocking();
+    let mut ui = Ui::new(&xns);
+    ui.sleep_ms(BOOT_SETTLE_MS);
+
+    hosted_test::run("UITEST", FILTER_ENV, &TESTS, &mut ui)
+}
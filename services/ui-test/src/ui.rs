@@ -0,0 +1,169 @@
+use graphics_server::api::testhooks::{DrawnText, Frame};
+use graphics_server::TestHooks;
+use std::time::{Duration, Instant};
+
+pub use hosted_test::TestResult;
+
+pub const HOME: char = '∴';
+pub const UP: char = '↑';
+pub const DOWN: char = '↓';
+pub const ENTER: char = '\r';
+pub const F3: char = '\u{0013}';
+pub const F4: char = '\u{0014}';
+
+/// The marker the GAM draws in front of the selected menu item
+const MENU_MARKER: char = '\u{25B6}';
+/// Pause between injected keys, so the keyboard server's queue never overflows
+const KEY_DELAY_MS: usize = 20;
+const POLL_MS: usize = 20;
+/// How long to wait for the screen to show what a test expects
+const WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The screen is taken to have settled once nothing has been drawn for this long
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+/// No menu is longer than this, so scrolling further means the item isn't there
+const MAX_MENU_ITEMS: usize = 24;
+
+/// Drives the UI from the keyboard, and watches what the graphics server draws in response.
+///
+/// Texts are read in the order they were drawn. Each `wait_for_*` call reads on from where the
+/// last one stopped, so a test expects the screen to change in the order it would see it.
+pub struct Ui {
+    kbd: keyboard::Keyboard,
+    hooks: TestHooks,
+    tt: ticktimer_server::Ticktimer,
+    /// Sequence number of the last text that was read
+    cursor: u32,
+    /// The menu item most recently drawn as selected
+    selected: Option<String>,
+}
+impl Ui {
+    pub fn new(xns: &xous_names::XousNames) -> Ui {
+        let hooks = TestHooks::new(xns).expect("UITEST can't connect to the graphics test hooks");
+        // what was drawn during boot is of no interest
+        let (cursor, _frame) = hooks
+            .latest()
+            .expect("UITEST can't read from the graphics test hooks");
+        Ui {
+            kbd: keyboard::Keyboard::new(xns).expect("UITEST can't connect to the keyboard"),
+            hooks,
+            tt: ticktimer_server::Ticktimer::new().unwrap(),
+            cursor,
+            selected: None,
+        }
+    }
+
+    pub fn sleep_ms(&self, ms: usize) {
+        self.tt.sleep_ms(ms).unwrap();
+    }
+
+    pub fn key(&self, key: char) {
+        self.kbd.hostmode_inject_key(key);
+        self.sleep_ms(KEY_DELAY_MS);
+    }
+
+    pub fn keys(&self, key: char, times: usize) {
+        for _ in 0..times {
+            self.key(key);
+        }
+    }
+
+    /// Types `text`, then presses enter.
+    pub fn enter_text(&self, text: &str) {
+        for c in text.chars() {
+            self.key(c);
+        }
+        self.key(ENTER);
+    }
+
+    /// Reads the next text drawn, if there is one.
+    fn next_text(&mut self) -> Result<Option<DrawnText>, String> {
+        let drawn = self
+            .hooks
+            .next_text(self.cursor)
+            .map_err(|e| format!("couldn't read from the test hooks: {:?}", e))?;
+        if let Some(drawn) = drawn {
+            self.cursor = drawn.seq.unwrap();
+            let text = drawn.text.as_str().unwrap_or("");
+            if let Some(item) = text.strip_prefix(MENU_MARKER) {
+                self.selected = Some(item.to_string());
+            }
+        }
+        Ok(drawn)
+    }
+
+    /// Waits for a text that contains `needle` to be drawn.
+    pub fn wait_for_text(&mut self, needle: &str) -> Result<DrawnText, String> {
+        self.wait_for(needle, WAIT_TIMEOUT)
+    }
+
+    fn wait_for(&mut self, needle: &str, timeout: Duration) -> Result<DrawnText, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            while let Some(drawn) = self.next_text()? {
+                if drawn.text.as_str().unwrap_or("").contains(needle) {
+                    return Ok(drawn);
+                }
+            }
+            if Instant::now() > deadline {
+                return Err(format!("timed out waiting for '{}'", needle));
+            }
+            self.sleep_ms(POLL_MS);
+        }
+    }
+
+    /// Reads everything drawn until the screen has been still for a moment.
+    pub fn settle(&mut self) -> TestResult {
+        let mut quiet_since = Instant::now();
+        while quiet_since.elapsed() < SETTLE_TIME {
+            if self.next_text()?.is_some() {
+                quiet_since = Instant::now();
+            } else {
+                self.sleep_ms(POLL_MS);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the selection of the open menu to `item` and picks it.
+    pub fn select_menu_item(&mut self, item: &str) -> TestResult {
+        self.settle()?;
+        let mut direction = DOWN;
+        for _ in 0..MAX_MENU_ITEMS * 2 {
+            if self.selected.as_deref() == Some(item) {
+                self.key(HOME);
+                return Ok(());
+            }
+            self.key(direction);
+            // nothing is redrawn when the selection is already at the end of the menu
+            if self
+                .wait_for(&MENU_MARKER.to_string(), SETTLE_TIME)
+                .is_err()
+            {
+                if direction == UP {
+                    break;
+                }
+                direction = UP;
+            }
+        }
+        Err(format!("menu item '{}' not found", item))
+    }
+
+    /// Picks entry `index` of the radio buttons on screen, then confirms with the OK button.
+    pub fn select_radio(&mut self, index: usize, items: usize) -> TestResult {
+        self.settle()?;
+        self.keys(UP, items);
+        self.keys(DOWN, index);
+        self.key(HOME);
+        self.keys(DOWN, items - index);
+        self.key(HOME);
+        Ok(())
+    }
+
+    /// Returns what is on the screen, once it has settled.
+    pub fn frame(&mut self) -> Result<Frame, String> {
+        self.settle()?;
+        self.hooks
+            .frame()
+            .map_err(|e| format!("couldn't read the frame: {:?}", e))
+    }
+}
@@ -51,6 +51,8 @@ pub(crate) enum Opcode {
     SerialClearHooks = 517,
     /// TRNG send poll
     SerialTrngPoll = 518,
+    /// Send binary data over serial. `len` comes back as the number of bytes written.
+    SerialSendBinary = 519,
 
     #[cfg(feature="mass-storage")]
     SetBlockDevice = 1024,
@@ -374,6 +374,25 @@ impl UsbHid {
         let resp = buf.to_original::<UsbSerialBinary, _>().unwrap();
         resp.d[..resp.len].to_vec()
     }
+    /// Blocks until all of `data` has been written out of the serial port. The data is dropped
+    /// if the serial core isn't the one that's connected. Fails with `Timeout` if the host stops
+    /// reading for a couple of seconds, in which case only part of `data` may have been sent.
+    pub fn serial_send_binary(&self, data: &[u8]) -> Result<(), xous::Error> {
+        for chunk in data.chunks(SERIAL_BINARY_BUFLEN) {
+            let mut req = UsbSerialBinary {
+                d: [0u8; SERIAL_BINARY_BUFLEN],
+                len: chunk.len(),
+            };
+            req.d[..chunk.len()].copy_from_slice(chunk);
+            let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.conn, Opcode::SerialSendBinary.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+            let sent = buf.to_original::<UsbSerialBinary, _>().or(Err(xous::Error::InternalError))?;
+            if sent.len < chunk.len() {
+                return Err(xous::Error::Timeout);
+            }
+        }
+        Ok(())
+    }
     /// Non-blocking call that issues a serial flush command to the USB stack
     pub fn serial_flush(&self) -> Result<(), xous::Error> {
         send_message(
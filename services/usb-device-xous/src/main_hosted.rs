@@ -274,6 +274,10 @@ pub(crate) fn main_hosted() -> ! {
             Some(Opcode::GetLedState) => {
                 xous::return_scalar(msg.sender, 0).unwrap();
             }
+            Some(Opcode::SerialSendBinary) => {
+                // there is no serial port in hosted mode; the data is dropped, and `len` goes back
+                // unchanged, as if it had all been sent
+            }
             Some(Opcode::Quit) => {
                 log::warn!("Quit received, goodbye world!");
                 break;
@@ -50,6 +50,8 @@ impl Clock for EmbeddedClock {
 /// Time allowed for switchover between device core types. It's longer because some hosts
 /// get really confused when you have the same VID/PID show up with a different set of endpoints.
 const EXTENDED_CORE_RESET_MS: usize = 4000;
+/// How long a binary serial send waits for the host to read before it gives up
+const SERIAL_SEND_TIMEOUT_MS: u64 = 2000;
 #[derive(Eq, PartialEq)]
 #[repr(usize)]
 enum Views {
@@ -1209,6 +1211,44 @@ pub(crate) fn main_hw() -> ! {
                     trng.set_test_mode(trng::api::TrngTestMode::None);
                 }
             }
+            Some(Opcode::SerialSendBinary) => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut usb_send = buffer.to_original::<UsbSerialBinary, _>().unwrap();
+                match view {
+                    Views::Serial => {
+                        // this is implemented as a "blocking write": the routine will block until the data has all been written,
+                        // or until the host hasn't read anything for SERIAL_SEND_TIMEOUT_MS, so a missing host can't hang the USB stack.
+                        let mut sent = 0;
+                        let mut last_progress = tt.elapsed_ms();
+                        while sent < usb_send.len {
+                            match serial_port.write(&usb_send.d[sent..usb_send.len]) {
+                                Ok(written) => {
+                                    sent += written;
+                                    last_progress = tt.elapsed_ms();
+                                }
+                                Err(_) => {
+                                    if tt.elapsed_ms() - last_progress >= SERIAL_SEND_TIMEOUT_MS {
+                                        log::warn!("Serial send timed out after {} of {} bytes", sent, usb_send.len);
+                                        break;
+                                    }
+                                    log::warn!("Serial send is blocking. Delaying and trying again.");
+                                    tt.sleep_ms(100).ok();
+                                }
+                            }
+                            match serial_port.flush() {
+                                Ok(_) => {},
+                                Err(_) => {
+                                    log::warn!("Serial port reported WouldBlock on flush");
+                                    tt.sleep_ms(100).ok();
+                                }
+                            }
+                        }
+                        usb_send.len = sent;
+                    }
+                    _ => {} // do nothing; the data is dropped
+                }
+                buffer.replace(usb_send).unwrap();
+            }
             Some(Opcode::SerialFlush) => msg_scalar_unpack!(msg, _, _, _, _, {
                 // this will hardware flush any pending items in usb_serial driver
                 serial_port.flush().ok();
@@ -4,13 +4,15 @@
 use xous_api_names::*;
 use xous_api_names::api::*;
 
-use num_traits::FromPrimitive;
-use xous::{msg_blocking_scalar_unpack, MessageEnvelope};
+use num_traits::{FromPrimitive, ToPrimitive};
+use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack, MessageEnvelope};
 use xous_ipc::{Buffer, String};
 
 use log::{error, info};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
 
 #[derive(PartialEq)]
 #[repr(C)]
@@ -109,23 +111,51 @@ I'll take safety over speed in this case.
 Eventually, we shall endeavor to remove Heapless entirely, once we have a `libstd` in place
 and we can use heap-allocated Rust primitives...
 */
+/// Progress of a server that registered with a public key. Connections are only brokered
+/// to servers that are either unauthenticated, or that have completed verification.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum AuthState {
+    /// registered without a public key
+    None,
+    /// a challenge was issued, waiting for the signed response
+    Challenged([u32; 4]),
+    /// the signed response is with the verification thread
+    Verifying,
+    /// the server proved ownership of its public key
+    Verified,
+}
 #[derive(Debug, Copy, Clone)]
 struct Connection {
     pub sid: xous::SID,
     pub current_conns: u32, // number of unauthenticated (inherently trusted) connections
     pub max_conns: Option<u32>, // if None, unlimited connections allowed
-    pub _allow_authenticate: bool,
-    pub _auth_conns: u32,        // number of authenticated connections
+    pub pubkey: Option<[u8; 32]>,
+    pub auth: AuthState,
+    pub challenged_at: Option<Instant>, // when the outstanding challenge was issued
+    pub auth_conns: u32,         // number of authenticated connections
     pub token: Option<[u32; 4]>, // a random number that must be presented to allow for disconnection
+    pub owner: Option<xous::PID>, // the process that registered the name
+    pub refused: u32,            // connections turned away because max_conns was reached
+    pub connected: [u32; 8],     // bitmap of every PID that has been brokered a connection
 }
+
+/// How many connection events are kept around for `ConnectionLog`
+const CONNECTION_LOG_LEN: usize = 64;
+/// How long a server has to answer its registration challenge before the name is released again
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Debug)]
 struct CheckedHashMap {
     pub map: HashMap<XousServerName, Connection>,
+    log: VecDeque<ConnectionEvent>,
+    log_seq: u32,
 }
 impl CheckedHashMap {
     pub fn new() -> Self {
         CheckedHashMap {
             map: HashMap::new(),
+            log: VecDeque::with_capacity(CONNECTION_LOG_LEN),
+            log_seq: 0,
         }
     }
     pub fn insert(
@@ -133,6 +163,8 @@ impl CheckedHashMap {
         name: XousServerName,
         sid: xous::SID,
         max_conns: Option<u32>,
+        pubkey: Option<[u8; 32]>,
+        owner: Option<xous::PID>,
     ) -> Result<(), xous::Error> {
         let token =
             // for use with 1-connection servers, provision a one-time use token for disconnects
@@ -151,13 +183,84 @@ impl CheckedHashMap {
                 sid,
                 current_conns: 0,
                 max_conns,
-                _allow_authenticate: false, // for now, we don't support authenticated connections
-                _auth_conns: 0,
+                pubkey,
+                auth: if pubkey.is_some() {
+                    let (c1, c2, c3, c4) = xous::create_server_id().expect("couldn't create challenge").to_u32();
+                    AuthState::Challenged([c1, c2, c3, c4])
+                } else {
+                    AuthState::None
+                },
+                challenged_at: if pubkey.is_some() { Some(Instant::now()) } else { None },
+                auth_conns: 0,
                 token,
+                owner,
+                refused: 0,
+                connected: [0; 8],
             },
         );
         Ok(())
     }
+
+    /// Adds an entry to the connection log, dropping the oldest one if it's full. A process
+    /// that connects to `name` for the first time is also noted in its table entry.
+    fn record(&mut self, name: &XousServerName, pid: xous::PID, kind: ConnectionEventKind) {
+        if let Some(entry) = self.map.get_mut(name) {
+            match kind {
+                ConnectionEventKind::Connected | ConnectionEventKind::AuthenticatedConnected => {
+                    let pid = pid.get() as usize;
+                    entry.connected[pid / 32] |= 1 << (pid % 32);
+                }
+                ConnectionEventKind::Refused => entry.refused += 1,
+                ConnectionEventKind::Disconnected => (),
+            }
+        }
+        if self.log.len() == CONNECTION_LOG_LEN {
+            self.log.pop_front();
+        }
+        self.log.push_back(ConnectionEvent {
+            seq: self.log_seq,
+            name: String::<64>::from_str(name.to_str()),
+            pid: pid.get(),
+            kind,
+        });
+        self.log_seq = self.log_seq.wrapping_add(1);
+    }
+
+    /// Whether `requester` may see which processes connect to `name`. Servers that authenticated
+    /// are the ones guarding secrets, so who their clients are is only told to the server itself.
+    fn may_see_clients(&self, name: &XousServerName, requester: xous::PID) -> bool {
+        match self.map.get(name) {
+            Some(entry) => entry.auth != AuthState::Verified || entry.owner == Some(requester),
+            None => true,
+        }
+    }
+
+    /// Describes the `index`th registered name, in name order, as seen by `requester`.
+    pub fn info(&self, index: usize, requester: xous::PID) -> Option<NameInfo> {
+        let mut names: Vec<&XousServerName> = self.map.keys().collect();
+        names.sort_by(|a, b| a.to_str().cmp(b.to_str()));
+        let name = names.get(index)?;
+        let entry = &self.map[*name];
+        Some(NameInfo {
+            name: String::<64>::from_str(name.to_str()),
+            owner: entry.owner.map(|pid| pid.get()).unwrap_or_default(),
+            max_conns: entry.max_conns,
+            current_conns: entry.current_conns,
+            auth_conns: entry.auth_conns,
+            authenticated: entry.auth == AuthState::Verified,
+            refused: entry.refused,
+            connected: if self.may_see_clients(name, requester) { entry.connected } else { [0; 8] },
+        })
+    }
+
+    /// Returns the `index`th entry of the connection log, oldest first, as seen by `requester`.
+    pub fn log_event(&self, index: usize, requester: xous::PID) -> Option<ConnectionEvent> {
+        let mut event = self.log.get(index).cloned()?;
+        if !self.may_see_clients(&XousServerName::from_str(event.name.as_str().unwrap_or("")), requester) {
+            event.pid = 0;
+        }
+        Some(event)
+    }
     pub fn remove(&mut self, sid: xous::SID) -> Option<XousServerName> {
         // remove is expensive, because we have to do a full search for the sid, which is not our usual key
         // however, for security reasons, you have to let us know your sid (which is a secret) in order to delete
@@ -180,12 +283,103 @@ impl CheckedHashMap {
         self.map.contains_key(name)
     }
 
-    pub fn connect(&mut self, name: &XousServerName) -> (Option<xous::SID>, Option<[u32; 4]>) {
+    /// Releases names whose registration challenge went unanswered for longer than
+    /// `CHALLENGE_TIMEOUT`, so a server that dies mid-registration doesn't hold its name forever.
+    pub fn expire_challenges(&mut self) {
+        self.map.retain(|name, entry| match (entry.auth, entry.challenged_at) {
+            (AuthState::Challenged(_), Some(issued)) if issued.elapsed() > CHALLENGE_TIMEOUT => {
+                log::warn!("challenge for '{}' was never answered, releasing the name", name);
+                false
+            }
+            _ => true,
+        });
+    }
+
+    /// Returns the outstanding challenge for a server that registered with a public key.
+    pub fn challenge(&self, name: &XousServerName) -> Option<[u32; 4]> {
+        match self.map.get(name).map(|entry| entry.auth) {
+            Some(AuthState::Challenged(challenge)) => Some(challenge),
+            _ => None,
+        }
+    }
+
+    /// Hands out the public key to check a signed challenge against, if `challenge` is the one that
+    /// was issued to `name`. A challenge can only be answered once.
+    pub fn start_verify(&mut self, name: &XousServerName, challenge: [u32; 4]) -> Option<[u8; 32]> {
         if let Some(entry) = self.map.get_mut(name) {
+            if entry.auth == AuthState::Challenged(challenge) {
+                entry.auth = AuthState::Verifying;
+                return entry.pubkey;
+            }
+        }
+        None
+    }
+
+    /// Records the verdict on a signed challenge. On success, returns the SID to hand to the server;
+    /// on failure, the registration is discarded so the name can be claimed again.
+    pub fn finish_verify(&mut self, name: &XousServerName, verified: bool) -> Option<xous::SID> {
+        if let Some(entry) = self.map.get_mut(name) {
+            if entry.auth == AuthState::Verifying {
+                if verified {
+                    entry.auth = AuthState::Verified;
+                    return Some(entry.sid);
+                } else {
+                    self.map.remove(name);
+                }
+            }
+        }
+        None
+    }
+
+    /// Connects to a server that proved ownership of `pubkey`. These connections count against
+    /// `max_conns` like any other. The disconnect token is not handed out, as it is single-use
+    /// and belongs to the connection that `max_conns` was sized for.
+    pub fn authenticated_connect(&mut self, name: &XousServerName, pubkey: &[u8; 32], pid: xous::PID) -> Option<xous::SID> {
+        let entry = self.map.get_mut(name)?;
+        if entry.auth != AuthState::Verified || entry.pubkey.as_ref() != Some(pubkey) {
+            return None;
+        }
+        if let Some(max) = entry.max_conns {
+            if entry.current_conns + entry.auth_conns >= max {
+                log::warn!("Attempt to connect, but no connections available: {:?}", name.to_str());
+                self.record(name, pid, ConnectionEventKind::Refused);
+                return None;
+            }
+        }
+        entry.auth_conns += 1;
+        let sid = entry.sid;
+        self.record(name, pid, ConnectionEventKind::AuthenticatedConnected);
+        Some(sid)
+    }
+
+    /// Connects `pid` to the server `name`, if it has connections to spare. The outcome is
+    /// recorded in the connection log.
+    pub fn connect(&mut self, name: &XousServerName, pid: xous::PID) -> (Option<xous::SID>, Option<[u32; 4]>) {
+        let result = self.connect_inner(name);
+        // Servers that haven't proven who they are yet aren't connected to at all, so there's
+        // nothing to record for them
+        if let Some(AuthState::None) | Some(AuthState::Verified) = self.map.get(name).map(|entry| entry.auth) {
+            let kind = if result.0.is_some() {
+                ConnectionEventKind::Connected
+            } else {
+                ConnectionEventKind::Refused
+            };
+            self.record(name, pid, kind);
+        }
+        result
+    }
+
+    fn connect_inner(&mut self, name: &XousServerName) -> (Option<xous::SID>, Option<[u32; 4]>) {
+        if let Some(entry) = self.map.get_mut(name) {
+            match entry.auth {
+                AuthState::None | AuthState::Verified => (),
+                // don't broker connections until the server has proven who it is
+                _ => return (None, None),
+            }
             match entry.max_conns {
                 // single-connection case
                 Some(1) => {
-                    if entry.current_conns < 1 {
+                    if entry.current_conns + entry.auth_conns < 1 {
                         (*entry).current_conns = 1;
                         (Some(entry.sid), entry.token)
                     } else {
@@ -193,7 +387,7 @@ impl CheckedHashMap {
                     }
                 }
                 Some(max) => {
-                    if entry.current_conns < max {
+                    if entry.current_conns + entry.auth_conns < max {
                         (*entry).current_conns += 1;
                         (Some(entry.sid), entry.token)
                     } else {
@@ -224,11 +418,11 @@ impl CheckedHashMap {
         let mut trusted_done = true;
         for (name, entry) in self.map.iter() {
             if let Some(max) = entry.max_conns {
-                if max != entry.current_conns {
+                if max != entry.current_conns + entry.auth_conns {
                     log::info!(
                         "server {} has {} conns but expects {}",
                         name,
-                        entry.current_conns,
+                        entry.current_conns + entry.auth_conns,
                         max
                     );
                     trusted_done = false;
@@ -248,6 +442,8 @@ impl CheckedHashMap {
             if mapping.sid == sid {
                 if mapping.current_conns > 0 {
                     mapping.current_conns -= 1;
+                } else if mapping.auth_conns > 0 {
+                    mapping.auth_conns -= 1;
                 }
                 return Some(*name);
             }
@@ -257,7 +453,7 @@ impl CheckedHashMap {
 
     // this is a safer version of disconnect. we track servers that allow exactly one connection at a time
     // and give them a one-time-use token that a connector can use to disconnect.
-    pub fn disconnect_with_token(&mut self, name: &XousServerName, token: [u32; 4]) -> bool {
+    pub fn disconnect_with_token(&mut self, name: &XousServerName, token: [u32; 4], pid: xous::PID) -> bool {
         if let Some(entry) = self.map.get_mut(name) {
             if let Some(old_token) = entry.token {
                 if token == old_token {
@@ -271,6 +467,7 @@ impl CheckedHashMap {
                             .expect("couldn't create token")
                             .to_array(),
                     );
+                    self.record(name, pid, ConnectionEventKind::Disconnected);
                     return true;
                 }
             }
@@ -314,7 +511,7 @@ fn blocking_connect(
 
     // If the server already exists, attempt to make the connection. The connection can
     // only succeed if the server is in the name_table.
-    if let (Some(server_sid), token) = name_table.connect(&name) {
+    if let (Some(server_sid), token) = name_table.connect(&name, sender_pid) {
         log::trace!("Found entry in the table (sid: {:?}, token: {:?}) -- attempting to call connect_for_process()", server_sid, token);
         let result = xous::connect_for_process(sender_pid, server_sid);
         if let Ok(xous::Result::ConnectionID(connection_id)) = result {
@@ -370,6 +567,75 @@ fn respond_connect_success(mut msg: MessageEnvelope, cid: xous::CID, disc: Optio
     mem.offset = None;
 }
 
+/// Connects any requests that were blocked waiting for the server `name` to exist.
+fn connect_waiters(
+    name: &XousServerName,
+    waiting_connections: &mut Vec<MessageEnvelope>,
+    name_table: &mut CheckedHashMap,
+) {
+    // Note that this could be replaced by `drain_filter()` when that is stabilized
+    let mut i = waiting_connections.len() as isize - 1;
+    while i >= 0 {
+        if name_from_msg(&waiting_connections[i as usize]) == Ok(*name) {
+            let mut msg = waiting_connections.remove(i as usize);
+            match blocking_connect(&mut msg, name_table) {
+                Err(e) => respond_connect_error(msg, e),
+                Ok(ConnectSuccess::Connected(cid, disc)) => {
+                    respond_connect_success(msg, cid, disc)
+                }
+                Ok(ConnectSuccess::Wait) => {
+                    panic!("message connection attempt resulted in `Wait` even though it ought to exist");
+                }
+            }
+        }
+        i -= 1;
+    }
+}
+
+/// A signed challenge, queued up for the verification thread.
+struct VerifyJob {
+    id: u32,
+    pubkey: [u8; 32],
+    message: [u8; AUTH_MESSAGE_LEN],
+    signature: [u8; 64],
+}
+
+fn verify_signature(pubkey: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+    use ed25519_dalek::{PublicKey, Signature};
+    let pubkey = match PublicKey::from_bytes(pubkey) {
+        Ok(pk) => pk,
+        Err(_) => return false,
+    };
+    let signature = match Signature::from_bytes(&signature[..]) {
+        Ok(sig) => sig,
+        Err(_) => return false,
+    };
+    pubkey.verify_strict(message, &signature).is_ok()
+}
+
+/// Signature checks run on their own thread. The hardware-accelerated curve and hash engines are
+/// themselves servers, located through us: calling them from the main loop would deadlock.
+fn verifier_thread(jobs: Receiver<VerifyJob>) {
+    for job in jobs.iter() {
+        let verified = verify_signature(&job.pubkey, &job.message, &job.signature);
+        // The engines are found with a `XousNames` of their own, which closes this process's
+        // connection to the name server when it's dropped, so connect again for every report.
+        let conn = xous::connect(xous::SID::from_bytes(b"xous-name-server").unwrap())
+            .expect("verifier couldn't connect to the name server");
+        xous::send_message(
+            conn,
+            xous::Message::new_scalar(
+                api::Opcode::AuthenticateResult.to_usize().unwrap(),
+                job.id as usize,
+                if verified { 1 } else { 0 },
+                0,
+                0,
+            ),
+        )
+        .expect("couldn't report verification result");
+    }
+}
+
 fn main() -> ! {
     use implementation::*;
     log_server::init_wait().unwrap();
@@ -389,6 +655,12 @@ fn main() -> ! {
     //let mut name_table = FnvIndexMap::<XousServerName, xous::SID, 128>::new();
     let mut name_table = CheckedHashMap::new();
 
+    // AuthenticatedRegister requests parked while their signature is checked, indexed by job ID
+    let mut verifying: HashMap<u32, (XousServerName, MessageEnvelope)> = HashMap::new();
+    let mut next_verify_id: u32 = 0;
+    let (verify_tx, verify_rx) = channel::<VerifyJob>();
+    std::thread::spawn(move || verifier_thread(verify_rx));
+
     info!("started");
     loop {
         let mut msg = xous::receive_message(name_server).unwrap();
@@ -409,15 +681,32 @@ fn main() -> ! {
                 let mut should_connect = false;
 
                 log::trace!("registration request for '{}'", name);
+                name_table.expire_challenges();
                 if !name_table.contains_key(&name) {
                     let new_sid =
                         xous::create_server_id().expect("create server failed, maybe OOM?");
                     name_table
-                        .insert(name, new_sid, registration.conn_limit)
+                        .insert(
+                            name,
+                            new_sid,
+                            registration.conn_limit,
+                            registration.pubkey,
+                            msg.sender.pid(),
+                        )
                         .expect("register name failure, maybe out of HashMap capacity?");
-                    log::trace!("request successful, SID is {:?}", new_sid);
-                    should_connect = true;
-                    response = api::Return::SID(new_sid.into());
+                    if let Some(pubkey) = registration.pubkey {
+                        // the SID is withheld until the server proves it holds the private key
+                        log::trace!("request requires authentication, issuing challenge");
+                        response = api::Return::AuthenticateRequest(AuthenticateRequest {
+                            name: registration.name,
+                            pubkey,
+                            challenge: name_table.challenge(&name).expect("challenge was not issued"),
+                        });
+                    } else {
+                        log::trace!("request successful, SID is {:?}", new_sid);
+                        should_connect = true;
+                        response = api::Return::SID(new_sid.into());
+                    }
                 } else {
                     info!("request failed, waiting for deterministic timeout");
                     d11ctimeout.deterministic_busy_wait();
@@ -436,26 +725,68 @@ fn main() -> ! {
 
                 if should_connect {
                     // See if we have any requests matching this server ID. If so, make the
-                    // connection. Note that this could be replaced by `drain_filter()` when
-                    // that is stabilized
-                    let mut i = waiting_connections.len() as isize - 1;
-                    while i >= 0 {
-                        if name_from_msg(&waiting_connections[i as usize]) == Ok(name) {
-                            let mut msg = waiting_connections.remove(i as usize);
-                            match blocking_connect(&mut msg, &mut name_table) {
-                                Err(e) => respond_connect_error(msg, e),
-                                Ok(ConnectSuccess::Connected(cid, disc)) => {
-                                    respond_connect_success(msg, cid, disc)
-                                }
-                                Ok(ConnectSuccess::Wait) => {
-                                    panic!("message connection attempt resulted in `Wait` even though it ought to exist");
-                                }
-                            }
-                        }
-                        i -= 1;
-                    }
+                    // connection.
+                    connect_waiters(&name, &mut waiting_connections, &mut name_table);
                 }
             }
+            Some(api::Opcode::AuthenticatedRegister) => {
+                let mem = msg.body.memory_message_mut().unwrap();
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let auth_response = buffer.to_original::<AuthenticateResponse, _>().unwrap();
+                let name_str = auth_response
+                    .name
+                    .as_str()
+                    .expect("couldn't convert server name to string");
+                let name = XousServerName::from_str(name_str);
+                log::trace!("authenticated registration response for '{}'", name);
+                name_table.expire_challenges();
+                if let Some(pubkey) = name_table.start_verify(&name, auth_response.challenge) {
+                    let id = next_verify_id;
+                    next_verify_id = next_verify_id.wrapping_add(1);
+                    verify_tx
+                        .send(VerifyJob {
+                            id,
+                            pubkey,
+                            message: auth_message(name_str, &auth_response.challenge),
+                            signature: auth_response.signature,
+                        })
+                        .expect("verification thread died");
+                    // park the message; it's responded to when the verdict comes back
+                    drop(buffer);
+                    verifying.insert(id, (name, msg));
+                } else {
+                    info!("unsolicited challenge response, waiting for deterministic timeout");
+                    d11ctimeout.deterministic_busy_wait();
+                    buffer
+                        .replace(api::Return::Failure)
+                        .expect("AuthenticatedRegister can't serialize return value");
+                }
+            }
+            Some(api::Opcode::AuthenticateResult) => msg_scalar_unpack!(msg, id, verified, _, _, {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::error!("AuthenticateResult from outside the name server, ignoring");
+                    continue;
+                }
+                if let Some((name, mut parked)) = verifying.remove(&(id as u32)) {
+                    let response = if let Some(sid) = name_table.finish_verify(&name, verified != 0) {
+                        info!("{} proved ownership of its public key", name);
+                        api::Return::SID(sid.into())
+                    } else {
+                        log::warn!("{} failed to prove ownership of its public key", name);
+                        api::Return::Failure
+                    };
+                    let mem = parked.body.memory_message_mut().unwrap();
+                    let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                    buffer
+                        .replace(response)
+                        .expect("AuthenticatedRegister can't serialize return value");
+                    // Drop the message, which sends it back to the server so it can create itself
+                    // before any waiters are connected.
+                    drop(buffer);
+                    drop(parked);
+                    connect_waiters(&name, &mut waiting_connections, &mut name_table);
+                }
+            }),
             Some(api::Opcode::Unregister) => msg_blocking_scalar_unpack!(msg, s0, s1, s2, s3, {
                 let gid = xous::SID::from_u32(s0 as u32, s1 as u32, s2 as u32, s3 as u32);
                 if let Some(name) = name_table.remove(gid) {
@@ -502,12 +833,12 @@ fn main() -> ! {
                         .expect("couldn't convert server name to string"),
                 );
                 log::trace!("Lookup request for '{}'", name);
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on Lookup");
                 let response: api::Return;
-                if let (Some(server_sid), token) = name_table.connect(&name) {
-                    let sender_pid = msg
-                        .sender
-                        .pid()
-                        .expect("can't extract sender PID on Lookup");
+                if let (Some(server_sid), token) = name_table.connect(&name, sender_pid) {
                     match xous::connect_for_process(sender_pid, server_sid)
                         .expect("can't broker connection")
                     {
@@ -529,19 +860,8 @@ fn main() -> ! {
                     for (_name, conn) in name_table.map.iter() {
                         log::debug!("{:?}", conn);
                     }
-                    // no authenticate remedy currently supported, but we'd put that code somewhere around here eventually.
-                    let (c1, c2, c3, c4) = xous::create_server_id().unwrap().to_u32();
-                    let auth_request = AuthenticateRequest {
-                        name: String::<64>::from_str(
-                            name_string
-                                .as_str()
-                                .expect("couldn't convert server name to string"),
-                        ),
-                        pubkey_id: [0; 20], // placeholder
-                        challenge: [c1, c2, c3, c4],
-                    };
                     d11ctimeout.hosted_delay();
-                    response = api::Return::AuthenticateRequest(auth_request) // this code just exists to exercise the return path
+                    response = api::Return::Failure
                 }
                 buffer
                     .replace(response)
@@ -549,11 +869,40 @@ fn main() -> ! {
             }
             Some(api::Opcode::AuthenticatedLookup) => {
                 let mem = msg.body.memory_message_mut().unwrap();
-                let buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
                 let auth_lookup: AuthenticatedLookup = buffer.to_original().unwrap();
-                info!("AuthenticatedLookup request {:?}", auth_lookup);
-                error!("AuthenticatedLookup not yet implemented");
-                unimplemented!("AuthenticatedLookup not yet implemented");
+                let name = XousServerName::from_str(
+                    auth_lookup
+                        .name
+                        .as_str()
+                        .expect("couldn't convert server name to string"),
+                );
+                log::trace!("AuthenticatedLookup request for '{}'", name);
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on AuthenticatedLookup");
+                let response = if let Some(server_sid) =
+                    name_table.authenticated_connect(&name, &auth_lookup.pubkey, sender_pid)
+                {
+                    match xous::connect_for_process(sender_pid, server_sid)
+                        .expect("can't broker connection")
+                    {
+                        xous::Result::ConnectionID(connection_id) => {
+                            api::Return::CID((connection_id, None))
+                        }
+                        _ => api::Return::Failure,
+                    }
+                } else {
+                    // the caller may be probing for the key, so don't return quickly
+                    info!("AuthenticatedLookup for '{}' refused, waiting for deterministic timeout", name);
+                    d11ctimeout.deterministic_busy_wait();
+                    d11ctimeout.hosted_delay();
+                    api::Return::Failure
+                };
+                buffer
+                    .replace(response)
+                    .expect("AuthenticatedLookup can't serialize return value");
             }
             Some(api::Opcode::TrustedInitDone) => {
                 if name_table.trusted_init_done() {
@@ -567,13 +916,38 @@ fn main() -> ! {
                 let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
                 let disconnect = buffer.to_original::<Disconnect, _>().unwrap();
                 let name = XousServerName::from_str(disconnect.name.as_str().unwrap());
-                let response = if name_table.disconnect_with_token(&name, disconnect.token) {
-                    api::Return::Success
-                } else {
-                    api::Return::Failure
-                };
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on Disconnect");
+                let response =
+                    if name_table.disconnect_with_token(&name, disconnect.token, sender_pid) {
+                        api::Return::Success
+                    } else {
+                        api::Return::Failure
+                    };
                 buffer.replace(response).expect("Can't return buffer");
             }
+            Some(api::Opcode::ListNames) => {
+                let mem = msg.body.memory_message_mut().unwrap();
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let mut query = buffer.to_original::<NameInfoQuery, _>().unwrap();
+                let sender_pid = msg.sender.pid().expect("can't extract sender PID on ListNames");
+                query.info = name_table.info(query.index as usize, sender_pid);
+                buffer
+                    .replace(query)
+                    .expect("ListNames can't serialize return value");
+            }
+            Some(api::Opcode::ConnectionLog) => {
+                let mem = msg.body.memory_message_mut().unwrap();
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let mut query = buffer.to_original::<ConnectionLogQuery, _>().unwrap();
+                let sender_pid = msg.sender.pid().expect("can't extract sender PID on ConnectionLog");
+                query.event = name_table.log_event(query.index as usize, sender_pid);
+                buffer
+                    .replace(query)
+                    .expect("ConnectionLog can't serialize return value");
+            }
             None => {
                 error!("couldn't decode message: {:?}", msg);
                 break;
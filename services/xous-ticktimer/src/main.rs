@@ -14,9 +14,21 @@ mod platform;
 use platform::implementation::*;
 use platform::*;
 
+mod timers;
+
 #[cfg(not(any(target_arch = "arm", feature="cramium-soc", feature="cramium-fpga")))]
 use susres::SuspendOrder;
 
+/// A sleep heap entry that fires notification timer `id` in `msec` milliseconds.
+fn notify_request(id: usize, msec: i64) -> TimerRequest {
+    TimerRequest {
+        msec,
+        sender: xous::MessageSender::from_usize(0),
+        kind: RequestKind::Notify,
+        data: id,
+    }
+}
+
 fn main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Info);
@@ -103,6 +115,10 @@ fn main() -> ! {
     let mut mutex_hash: HashMap<Option<xous::PID>, HashMap<usize, VecDeque<xous::MessageSender>>> =
         HashMap::new();
 
+    // Timers that send a message to a server when they are due. Each one has
+    // an entry in the sleep heap for the next time it fires.
+    let mut notify_timers = timers::Timers::new();
+
     let mut msg_opt = None;
     let mut return_type = 0;
     loop {
@@ -217,6 +233,35 @@ fn main() -> ! {
                     // );
                     // log::trace!("new entries for PID {:?}/condvar {:08x}: {:?}", sender_pid, condvar, notify_hash.get(&sender_pid).unwrap().get(&condvar));
                 }
+
+                // A notification timer is due, so let its server know and queue up the next tick.
+                if request_kind == RequestKind::Notify as usize {
+                    let id = args.arg3;
+                    let now = ticktimer.elapsed_ms() as i64;
+                    if let Some(notification) = notify_timers.fire(id, now) {
+                        let result = xous::try_send_message(
+                            notification.cid,
+                            xous::Message::new_scalar(
+                                notification.opcode,
+                                notification.id,
+                                notification.ticks,
+                                0,
+                                0,
+                            ),
+                        )
+                        .map(|_| ());
+                        if let Err(e) = &result {
+                            log::trace!("couldn't deliver timer {} notification: {:?}", id, e);
+                        }
+                        if let Some(deadline) = notify_timers.delivered(id, result, now) {
+                            ticktimer.recalculate_sleep(
+                                &mut sleep_heap,
+                                Some(notify_request(id, deadline - now)),
+                            );
+                            continue;
+                        }
+                    }
+                }
                 ticktimer.recalculate_sleep(&mut sleep_heap, None);
             }
 
@@ -557,6 +602,65 @@ fn main() -> ! {
                 ticktimer.start_sleep(&mut sleep_heap);
             }
 
+            api::Opcode::RegisterTimer => {
+                let owner = msg.sender.pid();
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("sender tried to register a timer using a non-memory message");
+                    continue;
+                };
+                let mut buf = unsafe { xous_ipc::Buffer::from_memory_message_mut(mem) };
+                let mut registration = buf.to_original::<api::TimerRegistration, _>().unwrap();
+
+                registration.id = match xous::try_connect(xous::SID::from_array(registration.sid)) {
+                    Ok(cid) => {
+                        let delay = registration.delay_ms as i64;
+                        let period = match registration.period_ms {
+                            0 => None,
+                            period => Some(period as i64),
+                        };
+                        let id = notify_timers.add(timers::Timer::new(
+                            owner,
+                            cid,
+                            registration.opcode as usize,
+                            ticktimer.elapsed_ms() as i64 + delay,
+                            period,
+                        ));
+                        ticktimer.recalculate_sleep(
+                            &mut sleep_heap,
+                            Some(notify_request(id, delay)),
+                        );
+                        id as u32
+                    }
+                    Err(e) => {
+                        log::error!("couldn't connect to the server for a timer: {:?}", e);
+                        0
+                    }
+                };
+                buf.replace(registration).unwrap();
+            }
+
+            api::Opcode::CancelTimer => {
+                let owner = msg.sender.pid();
+                let Some(scalar) = msg.body.scalar_message_mut() else {
+                    log::error!("sender tried to cancel a timer using a non-scalar message");
+                    continue;
+                };
+
+                let id = scalar.arg1;
+                let result = notify_timers.cancel(owner, id);
+                if result.is_ok() {
+                    // Remove the timer's pending entry, if it hasn't already fired
+                    ticktimer.stop_sleep(&mut sleep_heap);
+                    sleep_heap.retain(|_, v| v.kind != RequestKind::Notify || v.data != id);
+                    ticktimer.start_sleep(&mut sleep_heap);
+                }
+
+                // The API expects a `Scalar1` of 0, or the error code
+                scalar.arg1 = result.err().map(|e| e.to_usize()).unwrap_or(0);
+                scalar.id = 0;
+                return_type = 1;
+            }
+
             api::Opcode::InvalidCall => {
                 error!("couldn't convert opcode");
             }
@@ -31,7 +31,9 @@ fn handle_irq(_irq_no: usize, arg: *mut usize) {
     // enabled when this value is not None.
     let response = xtt.current_response.take();
     if let Some(response) = response {
-        xous::return_scalar(response.sender, response.kind as usize).ok();
+        if response.kind != crate::RequestKind::Notify {
+            xous::return_scalar(response.sender, response.kind as usize).ok();
+        }
 
         // This is dangerous and may return an error if the queue is full.
         // Which is fine, because the queue is always recalculated any time a message arrives.
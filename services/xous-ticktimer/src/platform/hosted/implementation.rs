@@ -10,8 +10,7 @@ use std::collections::BTreeMap;
 enum SleepComms {
     InterruptSleep,
     StartSleep(
-        xous::MessageSender,
-        i64, /* ms */
+        TimerRequest,
         u64, /* elapsed */
     ),
 }
@@ -40,8 +39,10 @@ impl XousTickTimer {
                         let response = current_response.take().unwrap();
                         #[cfg(feature = "debug-print")]
                         log::info!("Returning scalar to {}", response.sender);
-                        xous::return_scalar(response.sender, response.kind as usize)
-                            .expect("couldn't send response");
+                        if response.kind != RequestKind::Notify {
+                            xous::return_scalar(response.sender, response.kind as usize)
+                                .expect("couldn't send response");
+                        }
 
                         // This is dangerous and may panic if the queue is full.
                         xous::try_send_message(
@@ -64,33 +65,28 @@ impl XousTickTimer {
                         timeout = None;
                         time_remaining_sender.send(current_response.take()).unwrap()
                     }
-                    Ok(SleepComms::StartSleep(new_sender, expiry, elapsed)) => {
-                        let mut duration = expiry - (elapsed as i64);
+                    Ok(SleepComms::StartSleep(request, elapsed)) => {
+                        let mut duration = request.msec - (elapsed as i64);
                         if duration > 0 {
                             #[cfg(feature = "debug-print")]
                             log::info!(
                                     "Starting sleep for {} ms, returning to {}",
                                     duration,
-                                    new_sender
+                                    request.sender
                                 );
                         } else {
                             #[cfg(feature = "debug-print")]
                             log::info!(
                                     "Clamping duration to 0 (was: {})m returning to {}",
                                     duration,
-                                    new_sender
+                                    request.sender
                                 );
                             duration = 0;
                         }
                         timeout = Some(std::time::Duration::from_millis(
                             duration.try_into().unwrap(),
                         ));
-                        current_response = Some(TimerRequest {
-                            sender: new_sender,
-                            msec: expiry,
-                            kind: RequestKind::Sleep,
-                            data: 0,
-                        });
+                        current_response = Some(request);
                     }
                 }
             }
@@ -131,11 +127,7 @@ impl XousTickTimer {
                 request.sender
             );
         self.sleep_comms
-            .send(SleepComms::StartSleep(
-                request.sender,
-                request.msec as i64,
-                self.elapsed_ms(),
-            ))
+            .send(SleepComms::StartSleep(request, self.elapsed_ms()))
             .unwrap();
     }
 
@@ -31,6 +31,8 @@ pub(crate) type TimeoutExpiry = i64;
 pub enum RequestKind {
     Sleep = 0,
     Timeout = 1,
+    /// A notification timer is due. Nobody is waiting on a response, and `data` is the timer ID.
+    Notify = 2,
 }
 
 #[derive(Eq)]
@@ -25,7 +25,9 @@ fn handle_irq(_irq_no: usize, arg: *mut usize) {
     // Safe because we're in an interrupt, and this interrupt is only
     // enabled when this value is not None.
     let response = xtt.current_response.take().unwrap();
-    xous::return_scalar(response.sender, response.kind as usize).ok();
+    if response.kind != crate::RequestKind::Notify {
+        xous::return_scalar(response.sender, response.kind as usize).ok();
+    }
 
     // Disable the timer
     xtt.csr.wfo(utra::ticktimer::EV_ENABLE_ALARM, 0);
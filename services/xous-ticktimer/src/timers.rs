@@ -0,0 +1,308 @@
+//! Notification timers. A timer delivers a scalar message to a server once it
+//! is due, and again every `period` milliseconds if it repeats.
+//!
+//! Deadlines are kept in ticktimer time, which stands still while the system is
+//! suspended. Periodic deadlines advance by exactly one period from the previous
+//! deadline rather than from when the timer actually fired, so notifications
+//! don't drift. If the ticktimer falls behind, or a notification can't be
+//! delivered because the server's queue is full, the missed ticks are folded
+//! into the next notification instead of being sent as a burst.
+
+use std::collections::BTreeMap;
+
+/// How long to wait before trying again to deliver a one-shot notification
+/// that the server had no room for.
+pub const RETRY_MS: i64 = 10;
+
+pub struct Timer {
+    /// The process that registered the timer, which is the only one allowed to cancel it
+    pub owner: Option<xous::PID>,
+    pub cid: xous::CID,
+    pub opcode: usize,
+
+    /// Milliseconds between notifications, or `None` for a one-shot timer
+    pub period: Option<i64>,
+
+    /// The time at which the timer is next due
+    pub deadline: i64,
+
+    /// Ticks that have come due but have not been delivered yet
+    pending: usize,
+}
+
+impl Timer {
+    pub fn new(
+        owner: Option<xous::PID>,
+        cid: xous::CID,
+        opcode: usize,
+        deadline: i64,
+        period: Option<i64>,
+    ) -> Self {
+        Timer {
+            owner,
+            cid,
+            opcode,
+            period,
+            deadline,
+            pending: 0,
+        }
+    }
+}
+
+/// A notification that should be sent now.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub cid: xous::CID,
+    pub opcode: usize,
+    pub id: usize,
+
+    /// How many times the timer came due since the last notification
+    pub ticks: usize,
+}
+
+#[derive(Default)]
+pub struct Timers {
+    timers: BTreeMap<usize, Timer>,
+    last_id: usize,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a timer, returning its ID. IDs are never reused, so a timer that
+    /// fires just after being cancelled cannot be mistaken for a newer one.
+    pub fn add(&mut self, timer: Timer) -> usize {
+        self.last_id += 1;
+        self.timers.insert(self.last_id, timer);
+        self.last_id
+    }
+
+    pub fn deadline(&self, id: usize) -> Option<i64> {
+        self.timers.get(&id).map(|timer| timer.deadline)
+    }
+
+    /// Remove timer `id` on behalf of `owner`. Fails with `DoubleFree` if the
+    /// timer has already finished or been cancelled.
+    pub fn cancel(&mut self, owner: Option<xous::PID>, id: usize) -> Result<Timer, xous::Error> {
+        match self.timers.get(&id) {
+            Some(timer) if timer.owner == owner => Ok(self.timers.remove(&id).unwrap()),
+            Some(_) => Err(xous::Error::AccessDenied),
+            None => Err(xous::Error::DoubleFree),
+        }
+    }
+
+    /// Timer `id` fired at `now`. Returns the notification to send, if the
+    /// timer still exists. Rounding may make a timer fire a millisecond early,
+    /// which still counts as being due.
+    pub fn fire(&mut self, id: usize, now: i64) -> Option<Notification> {
+        let timer = self.timers.get_mut(&id)?;
+        let mut ticks = 1;
+        if let Some(period) = timer.period {
+            let missed = ((now - timer.deadline).max(0) / period) as usize;
+            ticks += missed;
+            timer.deadline += period * (missed as i64 + 1);
+        }
+        timer.pending += ticks;
+        Some(Notification {
+            cid: timer.cid,
+            opcode: timer.opcode,
+            id,
+            ticks: timer.pending,
+        })
+    }
+
+    /// Record the outcome of sending the notification for timer `id`.
+    ///
+    /// # Returns
+    ///
+    /// * **Some(deadline)**: The time at which the timer should fire next
+    /// * **None**: The timer is finished and has been removed
+    pub fn delivered(
+        &mut self,
+        id: usize,
+        result: Result<(), xous::Error>,
+        now: i64,
+    ) -> Option<i64> {
+        let timer = self.timers.get_mut(&id)?;
+        match result {
+            Ok(()) if timer.period.is_none() => {
+                self.timers.remove(&id);
+                return None;
+            }
+            Ok(()) => timer.pending = 0,
+            // Periodic timers hold on to the ticks and add them to the next
+            // notification, while one-shot timers try again shortly.
+            Err(xous::Error::ServerQueueFull) => {
+                if timer.period.is_none() {
+                    timer.deadline = now + RETRY_MS;
+                    timer.pending = 0;
+                }
+            }
+            // The server has gone away, so nobody is listening anymore
+            Err(_) => {
+                self.timers.remove(&id);
+                return None;
+            }
+        }
+        Some(timer.deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `timers` until `end`, always firing the earliest timer
+    /// next the way the sleep heap would, `latency` milliseconds late.
+    /// Returns the `(time, id, ticks)` of each notification.
+    fn run(timers: &mut Timers, ids: &[usize], end: i64, latency: i64) -> Vec<(i64, usize, usize)> {
+        let mut heap: BTreeMap<(i64, usize), usize> = ids
+            .iter()
+            .map(|&id| ((timers.deadline(id).unwrap(), id), id))
+            .collect();
+        let mut delivered = vec![];
+        while let Some(((deadline, _), id)) = heap.pop_first() {
+            let now = deadline + latency;
+            if now > end {
+                break;
+            }
+            let notification = timers.fire(id, now).unwrap();
+            delivered.push((now, id, notification.ticks));
+            if let Some(next) = timers.delivered(id, Ok(()), now) {
+                heap.insert((next, id), id);
+            }
+        }
+        delivered
+    }
+
+    #[test]
+    fn timers_fire_in_deadline_order() {
+        let mut timers = Timers::new();
+        let once = timers.add(Timer::new(None, 1, 0, 50, None));
+        let fast = timers.add(Timer::new(None, 1, 1, 20, Some(20)));
+        let slow = timers.add(Timer::new(None, 1, 2, 30, Some(30)));
+
+        let order: Vec<(i64, usize)> = run(&mut timers, &[once, fast, slow], 90, 0)
+            .into_iter()
+            .map(|(now, id, _)| (now, id))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (20, fast),
+                (30, slow),
+                (40, fast),
+                (50, once),
+                (60, fast),
+                (60, slow),
+                (80, fast),
+                (90, slow),
+            ]
+        );
+
+        // The one-shot timer is gone once it has been delivered
+        assert!(timers.deadline(once).is_none());
+    }
+
+    #[test]
+    fn periodic_timers_do_not_drift() {
+        let mut timers = Timers::new();
+        let id = timers.add(Timer::new(None, 1, 0, 35, Some(30)));
+
+        // Every notification is handled a little late, but the lateness
+        // doesn't add up over time.
+        let delivered = run(&mut timers, &[id], 35 + 30 * 999 + 3, 3);
+        assert_eq!(delivered.len(), 1000);
+        for (n, &(now, _, ticks)) in delivered.iter().enumerate() {
+            assert_eq!(now, 35 + 30 * n as i64 + 3);
+            assert_eq!(ticks, 1);
+        }
+        assert_eq!(timers.deadline(id), Some(35 + 30 * 1000));
+    }
+
+    #[test]
+    fn late_timers_coalesce_missed_ticks() {
+        let mut timers = Timers::new();
+        let id = timers.add(Timer::new(None, 1, 0, 100, Some(100)));
+
+        // Fire two and a half periods late: three ticks were missed
+        let notification = timers.fire(id, 350).unwrap();
+        assert_eq!(notification.ticks, 3);
+
+        // The next deadline stays on the original schedule
+        assert_eq!(timers.delivered(id, Ok(()), 350), Some(400));
+        assert_eq!(timers.fire(id, 400).unwrap().ticks, 1);
+    }
+
+    #[test]
+    fn undelivered_ticks_are_coalesced() {
+        let mut timers = Timers::new();
+        let id = timers.add(Timer::new(None, 1, 0, 10, Some(10)));
+
+        timers.fire(id, 10).unwrap();
+        assert_eq!(
+            timers.delivered(id, Err(xous::Error::ServerQueueFull), 10),
+            Some(20)
+        );
+        timers.fire(id, 20).unwrap();
+        assert_eq!(
+            timers.delivered(id, Err(xous::Error::ServerQueueFull), 20),
+            Some(30)
+        );
+
+        // Once the server catches up it hears about everything it missed
+        assert_eq!(timers.fire(id, 30).unwrap().ticks, 3);
+        assert_eq!(timers.delivered(id, Ok(()), 30), Some(40));
+        assert_eq!(timers.fire(id, 40).unwrap().ticks, 1);
+    }
+
+    #[test]
+    fn one_shot_timers_retry_until_delivered() {
+        let mut timers = Timers::new();
+        let id = timers.add(Timer::new(None, 1, 0, 10, None));
+
+        assert_eq!(timers.fire(id, 10).unwrap().ticks, 1);
+        assert_eq!(
+            timers.delivered(id, Err(xous::Error::ServerQueueFull), 10),
+            Some(10 + RETRY_MS)
+        );
+        assert_eq!(timers.fire(id, 10 + RETRY_MS).unwrap().ticks, 1);
+        assert_eq!(timers.delivered(id, Ok(()), 10 + RETRY_MS), None);
+        assert!(timers.fire(id, 100).is_none());
+    }
+
+    #[test]
+    fn timers_go_away_with_their_server() {
+        let mut timers = Timers::new();
+        let id = timers.add(Timer::new(None, 1, 0, 10, Some(10)));
+
+        timers.fire(id, 10).unwrap();
+        assert_eq!(
+            timers.delivered(id, Err(xous::Error::ServerNotFound), 10),
+            None
+        );
+        assert!(timers.deadline(id).is_none());
+    }
+
+    #[test]
+    fn only_the_owner_may_cancel() {
+        let owner = xous::PID::new(5);
+        let mut timers = Timers::new();
+        let id = timers.add(Timer::new(owner, 1, 0, 10, Some(10)));
+
+        assert_eq!(
+            timers.cancel(xous::PID::new(6), id).err(),
+            Some(xous::Error::AccessDenied)
+        );
+        assert!(timers.cancel(owner, id).is_ok());
+        assert_eq!(
+            timers.cancel(owner, id).err(),
+            Some(xous::Error::DoubleFree)
+        );
+
+        // A cancelled timer that was already in flight is ignored
+        assert!(timers.fire(id, 10).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use tools::pddb::{parse_key_export, Basis, BasisKeys, FastSpace, PddbImage, Severity, SYSTEM_BASIS};
+
+/// Key and dictionary names may contain path separators; flatten them for use as file names.
+fn file_name(name: &str) -> String {
+    name.replace(|c: char| c == '/' || c == '\\', "_")
+}
+
+fn list(image: &PddbImage, bases: &[Basis], verbose: bool) {
+    println!(
+        "Image: {} pages, {} data pages @ 0x{:x}",
+        image.size_pages,
+        image.data_pages(),
+        image.data_base
+    );
+    for basis in bases {
+        println!(
+            "Basis {} (journal {}, age {}, {} dicts, {} pages mapped)",
+            basis.name(),
+            basis.root.journal,
+            basis.root.age,
+            basis.root.num_dictionaries,
+            basis.map.v2p.len()
+        );
+        for dict in basis.dicts.iter() {
+            println!(
+                "  {} (index {}, {} keys, age {}, free index {})",
+                dict.name, dict.index, dict.num_keys, dict.age, dict.free_key_index
+            );
+            for key in dict.keys.iter() {
+                println!(
+                    "    {} len {}/{} @ 0x{:x}{} age {}",
+                    key.name,
+                    key.len,
+                    key.reserved,
+                    key.start,
+                    if key.is_large() { " (large)" } else { "" },
+                    key.age
+                );
+                if verbose {
+                    match image.read_key(basis, key) {
+                        Ok(data) => {
+                            let preview: Vec<String> = data.iter().take(32).map(|b| format!("{:02x}", b)).collect();
+                            println!(
+                                "      {}{}",
+                                preview.join(""),
+                                if data.len() > 32 { "..." } else { "" }
+                            );
+                        }
+                        Err(e) => println!("      <{}>", e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn extract(image: &PddbImage, bases: &[Basis], matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = matches.value_of("all") {
+        for basis in bases {
+            for dict in basis.dicts.iter() {
+                let path = Path::new(dir).join(file_name(basis.name())).join(file_name(&dict.name));
+                fs::create_dir_all(&path)?;
+                for key in dict.keys.iter() {
+                    match image.read_key(basis, key) {
+                        Ok(data) => fs::write(path.join(file_name(&key.name)), data)?,
+                        Err(e) => eprintln!("skipping {}:{}: {}", dict.name, key.name, e),
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let dict_name = matches.value_of("dict").ok_or("--dict is required unless --all is given")?;
+    let key_name = matches.value_of("key").ok_or("--key is required unless --all is given")?;
+    // later bases take precedence over earlier ones, matching the PDDB's own lookup order
+    let found = bases
+        .iter()
+        .rev()
+        .filter(|b| matches.value_of("basis").map(|n| n == b.name()).unwrap_or(true))
+        .find_map(|b| b.dict(dict_name).and_then(|d| d.keys.iter().find(|k| k.name == key_name)).map(|k| (b, k)))
+        .ok_or_else(|| format!("key {}:{} not found", dict_name, key_name))?;
+    let data = image.read_key(found.0, found.1)?;
+    match matches.value_of("output") {
+        Some(output) => fs::write(output, data)?,
+        None => std::io::stdout().write_all(&data)?,
+    }
+    Ok(())
+}
+
+fn verify(image: &PddbImage, bases: &[Basis], keys: &[BasisKeys]) -> bool {
+    let fscb = match keys.iter().find(|k| k.name == SYSTEM_BASIS) {
+        Some(system) => {
+            let fscb = image.fast_space(system);
+            if let Ok(fscb) = &fscb {
+                println!("FastSpace: {} entries, {} updates", fscb.pages.len(), fscb.updates);
+            }
+            fscb
+        }
+        None => {
+            eprintln!("warning: no {} key available, skipping free space checks", SYSTEM_BASIS);
+            Ok(FastSpace::default())
+        }
+    };
+    let issues = image.verify(bases, fscb.as_ref());
+    for issue in issues.iter() {
+        println!("{}", issue);
+    }
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    println!("{} errors, {} warnings", errors, issues.len() - errors);
+    errors == 0
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let matches = App::new("pddb-inspect")
+        .version(crate_version!())
+        .about("Decode, list, extract and verify hosted-mode PDDB images")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("image")
+                .long("image")
+                .short("i")
+                .takes_value(true)
+                .value_name("IMAGE")
+                .default_value("tools/pddb-images/pddb.bin")
+                .help("PDDB image, as written by dump_fs()"),
+        )
+        .arg(
+            Arg::with_name("keys")
+                .long("keys")
+                .short("k")
+                .takes_value(true)
+                .value_name("KEYFILE")
+                .help("Basis keys, as written by dump_keys(). Defaults to the image path with a .key extension"),
+        )
+        .arg(
+            Arg::with_name("dna")
+                .long("dna")
+                .takes_value(true)
+                .value_name("DNA")
+                .default_value("0")
+                .help("Device DNA folded into the AAD, in hex. Hosted mode uses 0"),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("List bases, dictionaries and keys")
+                .arg(Arg::with_name("verbose").long("verbose").short("v").help("Show a preview of each key's contents")),
+        )
+        .subcommand(
+            SubCommand::with_name("extract")
+                .about("Extract key contents")
+                .arg(Arg::with_name("basis").long("basis").short("b").takes_value(true).help("Only search this basis"))
+                .arg(Arg::with_name("dict").long("dict").short("d").takes_value(true).help("Dictionary name"))
+                .arg(Arg::with_name("key").long("key").takes_value(true).help("Key name"))
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("Write to this file instead of stdout"),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .conflicts_with_all(&["dict", "key", "output"])
+                        .help("Extract every key into DIR/<basis>/<dict>/<key>"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Check for orphaned pages, unreadable keys and free space table inconsistencies"),
+        )
+        .get_matches();
+
+    let image_path = PathBuf::from(matches.value_of("image").unwrap());
+    let key_path = matches
+        .value_of("keys")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| image_path.with_extension("key"));
+    let dna = u64::from_str_radix(matches.value_of("dna").unwrap().trim_start_matches("0x"), 16)?;
+
+    let keys = parse_key_export(&fs::read(&key_path)?)?;
+    let image = PddbImage::new(fs::read(&image_path)?, dna)?;
+    let mut bases = Vec::new();
+    for basis_keys in keys.iter() {
+        match image.open_basis(basis_keys) {
+            Ok(basis) => bases.push(basis),
+            Err(e) => eprintln!("error: couldn't open basis {}: {}", basis_keys.name, e),
+        }
+    }
+
+    match matches.subcommand() {
+        ("list", Some(sub)) => list(&image, &bases, sub.is_present("verbose")),
+        ("extract", Some(sub)) => extract(&image, &bases, sub)?,
+        ("verify", Some(_)) => {
+            if !verify(&image, &bases, &keys) || bases.len() != keys.len() {
+                process::exit(1);
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use clap::{crate_version, App, Arg};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+use tools::qrstream::{Reassembler, ReassemblyError};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let matches = App::new("qr-reassemble")
+        .version(crate_version!())
+        .about("Reassemble a payload shown as a QR sequence by the modals server")
+        .after_help(
+            "Frames are read one per line, as a QR scanner prints them. Lines that aren't frames \
+             are skipped. Reading stops as soon as every frame is in, so the output of a scanner \
+             watching the screen can be piped straight in, e.g. `zbarcam --raw | qr-reassemble -o backup.bin`",
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the payload to this file instead of stdout"),
+        )
+        .arg(
+            Arg::with_name("input")
+                .multiple(true)
+                .value_name("INPUT")
+                .help("Files of scanned frames. Reads stdin if none are given"),
+        )
+        .get_matches();
+
+    let inputs: Vec<Box<dyn BufRead>> = match matches.values_of("input") {
+        Some(paths) => paths
+            .map(|p| File::open(p).map(|f| Box::new(BufReader::new(f)) as Box<dyn BufRead>))
+            .collect::<Result<_, _>>()?,
+        None => vec![Box::new(BufReader::new(io::stdin()))],
+    };
+
+    let mut r = Reassembler::new();
+    'inputs: for input in inputs {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match r.push(&line) {
+                Ok(true) => {
+                    let count = r.count().unwrap();
+                    eprintln!("{}/{} frames", count as usize - r.missing().len(), count);
+                    if r.is_complete() {
+                        break 'inputs;
+                    }
+                }
+                Ok(false) => (),
+                Err(e @ ReassemblyError::Frame(_)) => log::info!("skipping line: {}", e),
+                Err(e) => eprintln!("warning: {}", e),
+            }
+        }
+    }
+
+    let payload = match r.finish() {
+        Ok(payload) => payload,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    };
+    match matches.value_of("output") {
+        Some(path) => File::create(path)?.write_all(&payload)?,
+        None => io::stdout().write_all(&payload)?,
+    }
+    eprintln!("{} bytes reassembled", payload.len());
+    Ok(())
+}
@@ -3,6 +3,8 @@ extern crate log;
 #[macro_use]
 pub mod xous_arguments;
 pub mod elf;
+pub mod pddb;
+pub mod qrstream;
 pub mod sign_image;
 pub mod tags;
 pub mod utils;
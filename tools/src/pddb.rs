@@ -0,0 +1,719 @@
+//! Offline decoder for PDDB disk images.
+//!
+//! The page geometry and the on-disk records (`Pte`, `SpaceUpdate`, `PhysPage`, `BasisRoot`, the
+//! `Dictionary` header and `KeyDescriptor`) are not declared here: `backend/layout.rs` and
+//! `api/format.rs` are pulled in from the PDDB with `#[path]`, along with the murmur3 hash used for
+//! the page table and space update checksums, so a change to the layout in the backend is picked
+//! up here on the next build.
+//!
+//! Images are the `.bin` files written by `EmuStorage::dump_fs()` in hosted mode, and keys are
+//! the matching `.key` files written by `EmuStorage::dump_keys()`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fmt;
+use std::mem::size_of;
+use std::ops::DerefMut;
+
+use aes::Aes256;
+use aes::cipher::{BlockDecrypt, KeyInit, generic_array::GenericArray};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use aes_gcm_siv::aead::{Aead, Payload};
+use ring::digest;
+
+#[path = "../../services/pddb/src/backend/murmur3.rs"]
+mod murmur3;
+use murmur3::murmur3_32;
+#[path = "../../services/pddb/src/api/format.rs"]
+#[allow(dead_code)]
+mod format;
+use format::{BASIS_NAME_LEN, DICT_NAME_LEN, KEY_NAME_LEN, KeyFlags, PDDB_FAST_SPACE_SYSTEM_BASIS, PDDB_MAGIC, PDDB_VERSION};
+pub use format::PDDB_DEFAULT_SYSTEM_BASIS as SYSTEM_BASIS;
+#[path = "../../services/pddb/src/backend/layout.rs"]
+#[allow(dead_code)]
+mod layout;
+use layout::{
+    JournalType, KeyExport, Pte, SpaceUpdate, DICT_MAXCOUNT, DICT_VSIZE, DK_PER_VPAGE, DK_STRIDE, FASTSPACE_PAGES,
+    FSCB_PAGES, KEY_MAXCOUNT, KEY_PAGES, LARGE_POOL_START, MBBB_PAGES, SMALL_POOL_START, SMALL_POOL_STRIDE,
+};
+pub use layout::{PhysPage, SpaceState, KCOM_CT_LEN, PAGE_SIZE, VPAGE_SIZE};
+
+const JOURNAL_LEN: usize = size_of::<JournalType>();
+const PTE_LEN: usize = size_of::<Pte>();
+const SPACE_UPDATE_LEN: usize = size_of::<SpaceUpdate>();
+const KEY_EXPORT_LEN: usize = size_of::<KeyExport>();
+
+#[derive(Debug)]
+pub enum InspectError {
+    /// the image or key file doesn't have the expected shape
+    Format(String),
+    /// the basis has no mapping for its root record
+    NoBasisRoot(String),
+    /// a page failed to authenticate
+    Decrypt { basis: String, vaddr: u64, page: u32 },
+    /// a key refers to a virtual page that has no physical page behind it
+    Unmapped { basis: String, vaddr: u64 },
+    /// no FastSpace record could be found or decrypted
+    NoFastSpace,
+}
+impl fmt::Display for InspectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InspectError::Format(s) => write!(f, "malformed input: {}", s),
+            InspectError::NoBasisRoot(name) => write!(f, "basis {} has no root record mapped", name),
+            InspectError::Decrypt { basis, vaddr, page } => {
+                write!(f, "basis {}: vpage {:x} @ pp {:x} does not decrypt", basis, vaddr, page)
+            }
+            InspectError::Unmapped { basis, vaddr } => write!(f, "basis {}: vpage {:x} is not mapped", basis, vaddr),
+            InspectError::NoFastSpace => write!(f, "no valid FastSpace record found"),
+        }
+    }
+}
+impl std::error::Error for InspectError {}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+/// decodes one of the backend's length-prefixed name records; a corrupt length is clamped rather than trusted
+fn name_of(len: u8, data: &[u8]) -> String {
+    String::from_utf8_lossy(&data[..(len as usize).min(data.len())]).to_string()
+}
+/// copies a record out of a page into a backend structure, the same way the backend deserializes it
+fn record<T: Default + DerefMut<Target = [u8]>>(data: &[u8]) -> T {
+    let mut rec = T::default();
+    for (&src, dst) in data.iter().zip(rec.deref_mut().iter_mut()) {
+        *dst = src;
+    }
+    rec
+}
+fn is_blank(data: &[u8]) -> bool { data.iter().all(|&b| b == 0xFF) }
+
+/// One entry of a `.key` file, as written by `EmuStorage::dump_keys()`.
+#[derive(Clone)]
+pub struct BasisKeys {
+    pub name: String,
+    /// AES-GCM-SIV key for the data pages
+    pub data: [u8; 32],
+    /// AES key for the page table entries
+    pub pt: [u8; 32],
+}
+impl BasisKeys {
+    fn aad(&self, dna: u64) -> Vec<u8> {
+        let mut aad = Vec::new();
+        aad.extend_from_slice(self.name.as_bytes());
+        aad.extend_from_slice(&PDDB_VERSION.to_le_bytes());
+        aad.extend_from_slice(&dna.to_le_bytes());
+        aad
+    }
+}
+
+/// Parses a `.key` export: a u32 count, followed by count `KeyExport` records of a zero-padded
+/// basis name, the data key and the page table key.
+pub fn parse_key_export(raw: &[u8]) -> Result<Vec<BasisKeys>, InspectError> {
+    if raw.len() < 4 {
+        return Err(InspectError::Format("key file is too short".to_string()));
+    }
+    let count = u32_at(raw, 0) as usize;
+    let records = &raw[4..];
+    if records.len() < count * KEY_EXPORT_LEN {
+        return Err(InspectError::Format(format!("key file claims {} keys but is only {} bytes long", count, raw.len())));
+    }
+    let mut keys = Vec::new();
+    for record in records.chunks_exact(KEY_EXPORT_LEN).take(count) {
+        // `dump_keys()` writes the fields back to back, in declaration order
+        let mut export = KeyExport { basis_name: [0; 64], key: [0; 32], pt_key: [0; 32] };
+        let (name, keys_part) = record.split_at(export.basis_name.len());
+        let (key, pt_key) = keys_part.split_at(export.key.len());
+        export.basis_name.copy_from_slice(name);
+        export.key.copy_from_slice(key);
+        export.pt_key.copy_from_slice(pt_key);
+        let name_len = export.basis_name.iter().position(|&b| b == 0).unwrap_or(export.basis_name.len());
+        keys.push(BasisKeys {
+            name: String::from_utf8_lossy(&export.basis_name[..name_len]).to_string(),
+            data: export.key,
+            pt: export.pt_key,
+        });
+    }
+    Ok(keys)
+}
+
+/// Decodes a decrypted page table entry, returning the virtual address it maps if the checksum matches.
+fn pte_vaddr(pte: &[u8]) -> Option<u64> {
+    // the backend never writes an entry for vpage 0, and `Pte::vaddr()` can't represent it
+    Pte::try_from_slice(pte).filter(|pte| pte.pddb_addr != [0; 7]).map(|pte| pte.vaddr().get())
+}
+
+/// Virtual to physical mapping of one basis, as recovered from the page table.
+#[derive(Default)]
+pub struct PageMap {
+    /// virtual page address -> physical page number within the data region
+    pub v2p: BTreeMap<u64, u32>,
+    /// mappings that lost journal arbitration against another entry for the same virtual page
+    pub stale: Vec<(u64, u32)>,
+    /// mappings whose physical page is outside of the data region
+    pub out_of_range: Vec<(u64, u32)>,
+}
+
+pub struct BasisRoot {
+    pub journal: u32,
+    pub magic: [u8; 4],
+    pub version: u32,
+    pub age: u32,
+    pub num_dictionaries: u32,
+    pub name: String,
+}
+impl BasisRoot {
+    fn parse(page: &[u8]) -> Self {
+        let root: layout::BasisRoot = record(&page[JOURNAL_LEN..]);
+        BasisRoot {
+            journal: u32_at(page, 0),
+            magic: root.magic,
+            version: root.version,
+            age: root.age,
+            num_dictionaries: root.num_dictionaries,
+            name: name_of(root.name.len, &root.name.data),
+        }
+    }
+}
+
+pub struct KeyDescriptor {
+    /// 1-offset index of the descriptor slot within its dictionary
+    pub descriptor_index: usize,
+    pub start: u64,
+    pub len: u64,
+    pub reserved: u64,
+    pub flags: u32,
+    pub age: u32,
+    pub name: String,
+}
+impl KeyDescriptor {
+    fn parse(descriptor_index: usize, data: &[u8]) -> Self {
+        let desc: layout::KeyDescriptor = record(&data[..DK_STRIDE]);
+        KeyDescriptor {
+            descriptor_index,
+            start: desc.start,
+            len: desc.len,
+            reserved: desc.reserved,
+            flags: desc.flags.0,
+            age: desc.age,
+            name: name_of(desc.name.len, &desc.name.data),
+        }
+    }
+    pub fn valid(&self) -> bool { KeyFlags(self.flags).valid() }
+    pub fn unresolved(&self) -> bool { KeyFlags(self.flags).unresolved() }
+    pub fn is_large(&self) -> bool { self.start >= LARGE_POOL_START }
+}
+
+pub struct Dictionary {
+    /// 1-offset index of the dictionary within its basis
+    pub index: u32,
+    pub journal: u32,
+    pub flags: u32,
+    pub age: u32,
+    pub num_keys: u32,
+    pub free_key_index: u32,
+    pub name: String,
+    pub keys: Vec<KeyDescriptor>,
+}
+impl Dictionary {
+    fn parse(index: u32, page: &[u8]) -> Self {
+        let dict: layout::Dictionary = record(&page[JOURNAL_LEN..JOURNAL_LEN + DK_STRIDE]);
+        Dictionary {
+            index,
+            journal: u32_at(page, 0),
+            flags: dict.flags.0,
+            age: dict.age,
+            num_keys: dict.num_keys,
+            free_key_index: dict.free_key_index,
+            name: name_of(dict.name.len, &dict.name.data),
+            keys: Vec::new(),
+        }
+    }
+    pub fn valid(&self) -> bool { layout::DictFlags(self.flags).valid() }
+    pub fn header_vaddr(&self) -> u64 { self.index as u64 * DICT_VSIZE }
+    pub fn small_pool_vaddr(&self) -> u64 { (self.index - 1) as u64 * SMALL_POOL_STRIDE + SMALL_POOL_START }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+impl Issue {
+    fn warn(message: String) -> Self { Issue { severity: Severity::Warning, message } }
+    fn error(message: String) -> Self { Issue { severity: Severity::Error, message } }
+}
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// A decoded basis: its root record, page map, and every dictionary and key descriptor that could
+/// be recovered. Problems found while walking the structures are recorded in `issues` instead of
+/// aborting the walk, so a damaged basis can still be inspected.
+pub struct Basis {
+    pub keys: BasisKeys,
+    pub root: BasisRoot,
+    pub map: PageMap,
+    pub dicts: Vec<Dictionary>,
+    pub issues: Vec<Issue>,
+}
+impl Basis {
+    pub fn name(&self) -> &str { &self.keys.name }
+    pub fn dict(&self, name: &str) -> Option<&Dictionary> { self.dicts.iter().find(|d| d.name == name) }
+}
+
+/// FastSpace free page table, after merging in the `SpaceUpdate` journal.
+#[derive(Default)]
+pub struct FastSpace {
+    /// physical page number -> free space record
+    pub pages: BTreeMap<u32, PhysPage>,
+    pub updates: usize,
+}
+
+pub struct PddbImage {
+    raw: Vec<u8>,
+    dna: u64,
+    pub size_pages: usize,
+    pub key_base: usize,
+    pub mbbb_base: usize,
+    pub fscb_base: usize,
+    pub data_base: usize,
+}
+impl PddbImage {
+    /// Wraps a raw image. `dna` is the device DNA that is folded into every AAD; hosted mode uses 0.
+    pub fn new(raw: Vec<u8>, dna: u64) -> Result<Self, InspectError> {
+        if raw.is_empty() || raw.len() % PAGE_SIZE != 0 {
+            return Err(InspectError::Format(format!("image length 0x{:x} is not a whole number of pages", raw.len())));
+        }
+        let size_pages = raw.len() / PAGE_SIZE;
+        let key_base = (size_pages * PTE_LEN + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        let mbbb_base = key_base + KEY_PAGES * PAGE_SIZE;
+        let fscb_base = mbbb_base + MBBB_PAGES * PAGE_SIZE;
+        let data_base = fscb_base + FSCB_PAGES * PAGE_SIZE;
+        if data_base >= raw.len() {
+            return Err(InspectError::Format("image is too small to hold a data region".to_string()));
+        }
+        Ok(PddbImage { raw, dna, size_pages, key_base, mbbb_base, fscb_base, data_base })
+    }
+    pub fn data_pages(&self) -> usize { (self.raw.len() - self.data_base) / PAGE_SIZE }
+
+    fn data_page(&self, page: u32) -> &[u8] {
+        let start = self.data_base + page as usize * PAGE_SIZE;
+        &self.raw[start..start + PAGE_SIZE]
+    }
+    /// Decrypts a data page, returning the journal number followed by the virtual page contents.
+    fn decrypt_page(&self, keys: &BasisKeys, page: u32) -> Option<Vec<u8>> {
+        let ct = self.data_page(page);
+        let cipher = Aes256GcmSiv::new(GenericArray::from_slice(&keys.data));
+        cipher.decrypt(Nonce::from_slice(&ct[..12]), Payload { aad: &keys.aad(self.dna), msg: &ct[12..] }).ok()
+    }
+    /// Decrypts a page that carries a key commitment (the basis root). See `data_decrypt_page_with_commit()`
+    /// in the backend for the layout.
+    fn decrypt_root_page(&self, keys: &BasisKeys, page: u32) -> Option<Vec<u8>> {
+        let ct = self.data_page(page);
+        let kcom_nonce = &ct[12 + KCOM_CT_LEN..12 + KCOM_CT_LEN + 32];
+        let kcom_stored = &ct[12 + KCOM_CT_LEN + 32..12 + KCOM_CT_LEN + 64];
+        let derive = |domain: u8| {
+            let mut h = digest::Context::new(&digest::SHA512_256);
+            h.update(&keys.data);
+            h.update(&[0x43, 0x6f, 0x6, 0xd6, 0xd, 0x69, 0x74, 0x01, domain]);
+            h.update(kcom_nonce);
+            h.finish()
+        };
+        if derive(0x02).as_ref() != kcom_stored {
+            return None;
+        }
+        let mut msg = Vec::with_capacity(KCOM_CT_LEN + 16);
+        msg.extend_from_slice(&ct[12..12 + KCOM_CT_LEN]);
+        msg.extend_from_slice(&ct[PAGE_SIZE - 16..]);
+        let cipher = Aes256GcmSiv::new(GenericArray::from_slice(derive(0x01).as_ref()));
+        cipher.decrypt(Nonce::from_slice(&ct[..12]), Payload { aad: &keys.aad(self.dna), msg: &msg }).ok()
+    }
+    fn page_journal(&self, keys: &BasisKeys, vaddr: u64, page: u32) -> Option<u32> {
+        let data = if vaddr == VPAGE_SIZE as u64 {
+            self.decrypt_root_page(keys, page)
+        } else {
+            self.decrypt_page(keys, page)
+        };
+        data.map(|d| u32_at(&d, 0))
+    }
+
+    /// Page table pages that were mid-update when the image was taken are blank in the table and
+    /// live in the MBBB instead.
+    fn mbbb_page(&self) -> Option<&[u8]> {
+        let mbbb = &self.raw[self.mbbb_base..self.fscb_base];
+        let mut candidates = mbbb.chunks_exact(PAGE_SIZE).filter(|p| !is_blank(&p[..16]));
+        let page = candidates.next();
+        if candidates.next().is_some() {
+            log::warn!("more than one MBBB page found, using the first one");
+        }
+        page
+    }
+
+    /// Recovers the virtual to physical map of a basis. Where two entries claim the same virtual
+    /// page, the one whose contents carry the higher journal number wins.
+    pub fn page_map(&self, keys: &BasisKeys) -> PageMap {
+        let cipher = Aes256::new(GenericArray::from_slice(&keys.pt));
+        let mbbb = self.mbbb_page();
+        let data_pages = self.data_pages() as u32;
+        let mut map = PageMap::default();
+        for (pt_index, pt_page) in self.raw[..self.size_pages * PTE_LEN].chunks(PAGE_SIZE).enumerate() {
+            let pt_page = match mbbb {
+                Some(mbbb) if is_blank(&pt_page[..16]) => {
+                    log::debug!("blank page table page {}, falling back to the MBBB", pt_index);
+                    &mbbb[..pt_page.len()]
+                }
+                _ => pt_page,
+            };
+            for (i, entry) in pt_page.chunks_exact(PTE_LEN).enumerate() {
+                let page = (pt_index * (PAGE_SIZE / PTE_LEN) + i) as u32;
+                let mut block = GenericArray::clone_from_slice(entry);
+                cipher.decrypt_block(&mut block);
+                let vaddr = match pte_vaddr(&block) {
+                    Some(vaddr) => vaddr,
+                    None => continue,
+                };
+                if page >= data_pages {
+                    map.out_of_range.push((vaddr, page));
+                    continue;
+                }
+                match map.v2p.get(&vaddr).copied() {
+                    None => {
+                        map.v2p.insert(vaddr, page);
+                    }
+                    Some(prev) => {
+                        let prev_journal = self.page_journal(keys, vaddr, prev);
+                        let new_journal = self.page_journal(keys, vaddr, page);
+                        log::debug!(
+                            "duplicate mapping for {:x}: pp {:x} (j {:?}) vs pp {:x} (j {:?})",
+                            vaddr, prev, prev_journal, page, new_journal
+                        );
+                        match (prev_journal, new_journal) {
+                            (Some(p), Some(n)) if n > p => {
+                                map.stale.push((vaddr, prev));
+                                map.v2p.insert(vaddr, page);
+                            }
+                            (None, Some(_)) => {
+                                map.stale.push((vaddr, prev));
+                                map.v2p.insert(vaddr, page);
+                            }
+                            _ => map.stale.push((vaddr, page)),
+                        }
+                    }
+                }
+            }
+        }
+        map
+    }
+
+    /// Decodes the FastSpace table with the system basis keys and replays the `SpaceUpdate` journal on top of it.
+    pub fn fast_space(&self, system: &BasisKeys) -> Result<FastSpace, InspectError> {
+        let region = &self.raw[self.fscb_base..self.data_base];
+        let mut fscb_start = None;
+        let mut update_pages = Vec::new();
+        for (i, page) in region.chunks_exact(PAGE_SIZE).enumerate() {
+            if is_blank(&page[..32]) {
+                continue;
+            } else if is_blank(&page[..16]) {
+                update_pages.push(&page[16..]);
+            } else if fscb_start.is_none() {
+                fscb_start = Some(i);
+            }
+        }
+        let start = fscb_start.ok_or(InspectError::NoFastSpace)? * PAGE_SIZE;
+        let enc = region.get(start..start + FASTSPACE_PAGES * PAGE_SIZE).ok_or(InspectError::NoFastSpace)?;
+        let mut aad = PDDB_FAST_SPACE_SYSTEM_BASIS.as_bytes().to_vec();
+        aad.extend_from_slice(&PDDB_VERSION.to_le_bytes());
+        aad.extend_from_slice(&self.dna.to_le_bytes());
+        let cipher = Aes256GcmSiv::new(GenericArray::from_slice(&system.data));
+        let pt = cipher
+            .decrypt(Nonce::from_slice(&enc[..12]), Payload { aad: &aad, msg: &enc[12..] })
+            .map_err(|_| InspectError::NoFastSpace)?;
+
+        let mut pages = BTreeMap::new();
+        for raw in pt.chunks_exact(4) {
+            let pp = PhysPage(u32_at(raw, 0));
+            if pp.valid() {
+                pages.insert(pp.page_number(), pp);
+            }
+        }
+        let cipher = Aes256::new(GenericArray::from_slice(&system.pt));
+        let mut updates = 0;
+        for update_page in update_pages {
+            for entry in update_page.chunks_exact(SPACE_UPDATE_LEN) {
+                if is_blank(entry) {
+                    break;
+                }
+                let mut block = GenericArray::clone_from_slice(entry);
+                cipher.decrypt_block(&mut block);
+                if let Some(pp) = SpaceUpdate::try_into_phys_page(&block) {
+                    updates += 1;
+                    // updates to pages that are not in the table are normal after the FSCB is regenerated
+                    if let Some(current) = pages.get_mut(&pp.page_number()) {
+                        if pp.valid() && current.journal() < pp.journal() {
+                            *current = pp;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(FastSpace { pages, updates })
+    }
+
+    /// Decodes a basis and walks all of its dictionaries and key descriptors.
+    pub fn open_basis(&self, keys: &BasisKeys) -> Result<Basis, InspectError> {
+        let map = self.page_map(keys);
+        let root_vaddr = VPAGE_SIZE as u64;
+        let root_page = *map.v2p.get(&root_vaddr).ok_or_else(|| InspectError::NoBasisRoot(keys.name.clone()))?;
+        let root = self.decrypt_root_page(keys, root_page).ok_or_else(|| InspectError::Decrypt {
+            basis: keys.name.clone(),
+            vaddr: root_vaddr,
+            page: root_page,
+        })?;
+        let root = BasisRoot::parse(&root);
+        let mut issues = Vec::new();
+        if root.magic != PDDB_MAGIC {
+            issues.push(Issue::error(format!("basis {}: bad magic {:x?}", keys.name, root.magic)));
+        }
+        if root.version != PDDB_VERSION {
+            issues.push(Issue::error(format!("basis {}: version {:x} (expected {:x})", keys.name, root.version, PDDB_VERSION)));
+        }
+        if root.name != keys.name {
+            issues.push(Issue::warn(format!("basis {}: root record is named {}", keys.name, root.name)));
+        }
+
+        let mut dicts = Vec::new();
+        for index in 1..=DICT_MAXCOUNT as u32 {
+            if dicts.len() >= root.num_dictionaries as usize {
+                break;
+            }
+            let vaddr = index as u64 * DICT_VSIZE;
+            let page = match map.v2p.get(&vaddr) {
+                Some(&page) => page,
+                None => continue,
+            };
+            match self.decrypt_page(keys, page) {
+                Some(data) => {
+                    let mut dict = Dictionary::parse(index, &data);
+                    if dict.valid() {
+                        self.read_descriptors(keys, &map, &mut dict, &mut issues);
+                        dicts.push(dict);
+                    }
+                }
+                None => issues.push(Issue::error(format!(
+                    "basis {}: dictionary header {:x} @ pp {:x} does not decrypt",
+                    keys.name, vaddr, page
+                ))),
+            }
+        }
+        if dicts.len() != root.num_dictionaries as usize {
+            issues.push(Issue::error(format!(
+                "basis {}: expected {} dictionaries, found {}",
+                keys.name,
+                root.num_dictionaries,
+                dicts.len()
+            )));
+        }
+        Ok(Basis { keys: keys.clone(), root, map, dicts, issues })
+    }
+
+    fn read_descriptors(&self, keys: &BasisKeys, map: &PageMap, dict: &mut Dictionary, issues: &mut Vec<Issue>) {
+        let mut page_vaddr = None;
+        let mut page: Option<Vec<u8>> = None;
+        let mut index = 1;
+        while dict.keys.len() < dict.num_keys as usize && index <= KEY_MAXCOUNT {
+            let vaddr = dict.header_vaddr() + (index / DK_PER_VPAGE) as u64 * VPAGE_SIZE as u64;
+            if page_vaddr != Some(vaddr) {
+                page_vaddr = Some(vaddr);
+                page = map.v2p.get(&vaddr).and_then(|&pp| {
+                    let data = self.decrypt_page(keys, pp);
+                    if data.is_none() {
+                        issues.push(Issue::error(format!(
+                            "basis {}: descriptor page {:x} @ pp {:x} of dictionary {} does not decrypt",
+                            keys.name, vaddr, pp, dict.name
+                        )));
+                    }
+                    data
+                });
+            }
+            match &page {
+                Some(data) => {
+                    let start = JOURNAL_LEN + (index % DK_PER_VPAGE) * DK_STRIDE;
+                    let desc = KeyDescriptor::parse(index, &data[start..start + DK_STRIDE]);
+                    if desc.valid() {
+                        dict.keys.push(desc);
+                    }
+                    index += 1;
+                }
+                // an unmapped descriptor page holds no keys; skip to the next one
+                None => index = (index / DK_PER_VPAGE + 1) * DK_PER_VPAGE,
+            }
+        }
+        if dict.keys.len() != dict.num_keys as usize {
+            issues.push(Issue::error(format!(
+                "basis {}: dictionary {} expected {} keys, found {}",
+                keys.name,
+                dict.name,
+                dict.num_keys,
+                dict.keys.len()
+            )));
+        }
+    }
+
+    /// Reads the full contents of a key.
+    pub fn read_key(&self, basis: &Basis, key: &KeyDescriptor) -> Result<Vec<u8>, InspectError> {
+        let mut data = Vec::with_capacity(key.len as usize);
+        let end = key.start + key.len;
+        let mut addr = key.start;
+        while addr < end {
+            let offset = (addr % VPAGE_SIZE as u64) as usize;
+            let vaddr = addr - offset as u64;
+            let take = ((VPAGE_SIZE - offset) as u64).min(end - addr) as usize;
+            let page = *basis.map.v2p.get(&vaddr).ok_or_else(|| InspectError::Unmapped {
+                basis: basis.name().to_string(),
+                vaddr,
+            })?;
+            let pt = self.decrypt_page(&basis.keys, page).ok_or_else(|| InspectError::Decrypt {
+                basis: basis.name().to_string(),
+                vaddr,
+                page,
+            })?;
+            data.extend_from_slice(&pt[JOURNAL_LEN + offset..JOURNAL_LEN + offset + take]);
+            addr += take as u64;
+        }
+        Ok(data)
+    }
+
+    /// Checks the structural integrity of the given bases against each other and, if available, the
+    /// FastSpace table. This reads every key, so it touches every mapped page at least once.
+    pub fn verify(&self, bases: &[Basis], fscb: Result<&FastSpace, &InspectError>) -> Vec<Issue> {
+        let mut issues = Vec::new();
+        let mut owners: HashMap<u32, &str> = HashMap::new();
+        for basis in bases {
+            let name = basis.name();
+            for issue in basis.issues.iter() {
+                issues.push(Issue { severity: issue.severity, message: issue.message.clone() });
+            }
+            for &(vaddr, page) in basis.map.out_of_range.iter() {
+                issues.push(Issue::error(format!("basis {}: vpage {:x} maps to pp {:x}, past the end of the data region", name, vaddr, page)));
+            }
+            for &(vaddr, page) in basis.map.stale.iter() {
+                issues.push(Issue::warn(format!("basis {}: stale mapping of vpage {:x} @ pp {:x}", name, vaddr, page)));
+            }
+            for (&vaddr, &page) in basis.map.v2p.iter() {
+                if let Some(other) = owners.insert(page, name) {
+                    issues.push(Issue::error(format!("pp {:x} is mapped by both basis {} and basis {} (vpage {:x})", page, other, name, vaddr)));
+                }
+            }
+
+            // every mapped page must be the root, a dictionary descriptor page, or back some key's storage
+            let mut extents = BTreeMap::new();
+            for dict in basis.dicts.iter() {
+                extents.insert(dict.header_vaddr(), dict.header_vaddr() + DICT_VSIZE);
+                for key in dict.keys.iter() {
+                    extents.insert(key.start, key.start + key.reserved.max(key.len));
+                    if let Err(e) = self.read_key(basis, key) {
+                        issues.push(Issue::error(format!("key {}:{}: {}", dict.name, key.name, e)));
+                    }
+                    if key.unresolved() {
+                        issues.push(Issue::warn(format!("basis {}: key {}:{} was never resolved to storage", name, dict.name, key.name)));
+                    }
+                }
+            }
+            for (&vaddr, &page) in basis.map.v2p.iter() {
+                if vaddr == VPAGE_SIZE as u64 {
+                    continue;
+                }
+                let referenced = extents
+                    .range(..vaddr + VPAGE_SIZE as u64)
+                    .next_back()
+                    .map(|(_, &end)| end > vaddr)
+                    .unwrap_or(false);
+                if !referenced {
+                    issues.push(Issue::warn(format!("basis {}: orphaned vpage {:x} @ pp {:x}", name, vaddr, page)));
+                }
+            }
+        }
+
+        match fscb {
+            Ok(fscb) => {
+                for (&page, pp) in fscb.pages.iter() {
+                    if page as usize >= self.data_pages() {
+                        issues.push(Issue::error(format!("free space entry for pp {:x} is past the end of the data region", page)));
+                    } else if pp.space_state() == SpaceState::Free {
+                        if let Some(owner) = owners.get(&page) {
+                            issues.push(Issue::error(format!("pp {:x} is listed as free but is mapped by basis {}", page, owner)));
+                        }
+                    }
+                }
+            }
+            Err(e) => issues.push(Issue::error(format!("free space table: {}", e))),
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// `basis2` is the last image `ci_tests()` dumps: both bases are mounted, and its `.key` file also
+    /// carries the key for `Basis2`.
+    fn hosted_image() -> (PathBuf, PathBuf) {
+        let base = match std::env::var("PDDB_TEST_IMAGE") {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("pddb-images").join("basis2.bin"),
+        };
+        let keys = base.with_extension("key");
+        (base, keys)
+    }
+
+    /// Decodes an image written by the hosted PDDB. `cargo xtask pddb-ci` runs this once its tests have
+    /// dumped the images; to run it by hand afterwards, use `cargo test -p tools --lib -- --ignored`, and
+    /// set `PDDB_TEST_IMAGE` to check a different dump.
+    #[test]
+    #[ignore]
+    fn test_hosted_image() {
+        let (image_path, key_path) = hosted_image();
+        let raw = std::fs::read(&image_path).expect("no image: run `cargo xtask pddb-ci` first");
+        let keys = parse_key_export(&std::fs::read(&key_path).expect("no key file next to the image")).unwrap();
+        let image = PddbImage::new(raw, 0).unwrap();
+
+        let system = keys.iter().find(|k| k.name == SYSTEM_BASIS).expect("no system basis key");
+        let fscb = image.fast_space(system);
+        let bases: Vec<Basis> = keys.iter().map(|k| image.open_basis(k).unwrap()).collect();
+        assert!(bases.iter().any(|b| b.name() == "Basis2"), "Basis2 did not decode");
+        let issues = image.verify(&bases, fscb.as_ref());
+        let errors: Vec<String> =
+            issues.iter().filter(|i| i.severity == Severity::Error).map(|i| i.to_string()).collect();
+        assert!(errors.is_empty(), "{}", errors.join("\n"));
+
+        // `gen_key()` names test keys "sanitycheck|<dict>|key<n>|len<len>", and ends their data with a
+        // murmur3 of the rest of the data, zero-padded to a whole word
+        let mut checked = 0;
+        for basis in bases.iter() {
+            for dict in basis.dicts.iter() {
+                for key in dict.keys.iter().filter(|k| k.name.starts_with("sanitycheck|")) {
+                    assert_eq!(key.name.split('|').nth(1), Some(dict.name.as_str()), "{} is in the wrong dictionary", key.name);
+                    let data = image.read_key(basis, key).unwrap();
+                    let (body, checksum) = data.split_at(data.len() - 4);
+                    let mut padded = body.to_vec();
+                    padded.resize((body.len() + 3) & !3, 0);
+                    assert_eq!(murmur3_32(&padded, 0).to_le_bytes(), checksum, "{}:{} fails its checksum", dict.name, key.name);
+                    checked += 1;
+                }
+            }
+        }
+        assert!(checked > 0, "no test keys found");
+    }
+}
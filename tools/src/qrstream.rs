@@ -0,0 +1,253 @@
+//! Reassembles a payload shown by the modals server as a sequence of QR codes.
+//!
+//! The frame format is defined in `services/modals/src/qrstream.rs`, which is built in here so
+//! that the encoder on the device and this decoder can't drift apart. Frames can be pushed in any
+//! order and any number of times, as they come off a scanner watching the cycle go around.
+
+use std::fmt;
+
+#[path = "../../services/modals/src/qrstream.rs"]
+mod format;
+pub use format::{crc32, encode_frames, FrameError, QrFrame, MAX_FRAMES};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReassemblyError {
+    Frame(FrameError),
+    /// The frame's header doesn't match the frames before it, so it is from another payload
+    OtherStream,
+    /// A frame was seen twice with different data
+    Conflict(u32),
+    /// Frames are still missing; holds their indices
+    Incomplete(Vec<u32>),
+    /// All the frames are in, but they don't add up to the length in the header
+    BadLength {
+        expected: u32,
+        actual: usize,
+    },
+    /// All the frames are in, but the payload doesn't match the CRC in the header
+    BadCrc {
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl fmt::Display for ReassemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReassemblyError::Frame(e) => write!(f, "{}", e),
+            ReassemblyError::OtherStream => write!(f, "frame belongs to a different payload"),
+            ReassemblyError::Conflict(index) => {
+                write!(f, "frame {} was read twice with different contents", index)
+            }
+            ReassemblyError::Incomplete(missing) => {
+                write!(f, "{} frame(s) missing: {:?}", missing.len(), missing)
+            }
+            ReassemblyError::BadLength { expected, actual } => {
+                write!(f, "payload is {} bytes, expected {}", actual, expected)
+            }
+            ReassemblyError::BadCrc { expected, actual } => {
+                write!(
+                    f,
+                    "payload CRC is {:08X}, expected {:08X}",
+                    actual, expected
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReassemblyError {}
+
+impl From<FrameError> for ReassemblyError {
+    fn from(e: FrameError) -> Self {
+        ReassemblyError::Frame(e)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    /// `(count, length, crc)` from the first frame
+    header: Option<(u32, u32, u32)>,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl Reassembler {
+    pub fn new() -> Reassembler {
+        Reassembler::default()
+    }
+
+    /// Adds the text of one scanned frame. Returns `true` if the frame hadn't been seen before.
+    pub fn push(&mut self, text: &str) -> Result<bool, ReassemblyError> {
+        let frame = QrFrame::parse(text)?;
+        let header = (frame.count, frame.length, frame.crc);
+        match self.header {
+            None => {
+                self.header = Some(header);
+                self.chunks = vec![None; frame.count as usize];
+            }
+            Some(h) if h != header => return Err(ReassemblyError::OtherStream),
+            Some(_) => (),
+        }
+        match &self.chunks[frame.index as usize] {
+            Some(data) if *data == frame.data => Ok(false),
+            Some(_) => Err(ReassemblyError::Conflict(frame.index)),
+            None => {
+                self.chunks[frame.index as usize] = Some(frame.data);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Number of frames in the payload, once a frame has been seen
+    pub fn count(&self) -> Option<u32> {
+        self.header.map(|(count, _, _)| count)
+    }
+
+    /// Indices of the frames that haven't been seen yet
+    pub fn missing(&self) -> Vec<u32> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.is_none())
+            .map(|(index, _)| index as u32)
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.header.is_some() && self.chunks.iter().all(|chunk| chunk.is_some())
+    }
+
+    /// Puts the payload back together and checks it against the header.
+    pub fn finish(self) -> Result<Vec<u8>, ReassemblyError> {
+        let missing = self.missing();
+        let (_, length, crc) = match self.header {
+            Some(header) if missing.is_empty() => header,
+            _ => return Err(ReassemblyError::Incomplete(missing)),
+        };
+        let payload: Vec<u8> = self.chunks.into_iter().flatten().flatten().collect();
+        if payload.len() != length as usize {
+            return Err(ReassemblyError::BadLength {
+                expected: length,
+                actual: payload.len(),
+            });
+        }
+        let actual = crc32(&payload);
+        if actual != crc {
+            return Err(ReassemblyError::BadCrc {
+                expected: crc,
+                actual,
+            });
+        }
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + i / 256) as u8).collect()
+    }
+
+    #[test]
+    fn crc_matches_ieee() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let data = payload(1000);
+        assert_eq!(crc32(&data), crc::crc32::checksum_ieee(&data));
+    }
+
+    #[test]
+    fn frames_are_qr_alphanumeric() {
+        const ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+        for frame in encode_frames(&payload(1000)).unwrap() {
+            assert!(frame.chars().all(|c| ALPHANUMERIC.contains(c)), "{}", frame);
+        }
+    }
+
+    #[test]
+    fn base32_round_trips_every_length() {
+        for len in 0..=12 {
+            let data = payload(len);
+            let frame = QrFrame {
+                index: 0,
+                count: 1,
+                length: len as u32,
+                crc: crc32(&data),
+                data: data.clone(),
+            };
+            assert_eq!(QrFrame::parse(&frame.encode()).unwrap(), frame);
+        }
+        // a single trailing character can't come from a whole byte
+        assert_eq!(QrFrame::parse("XQ1:0:1:1:0:AAA"), Err(FrameError::BadData));
+    }
+
+    #[test]
+    fn reassembles_out_of_order_with_repeats() {
+        let data = payload(format::CHUNK_LEN * 3 + 10);
+        let frames = encode_frames(&data).unwrap();
+        assert_eq!(frames.len(), 4);
+
+        let mut r = Reassembler::new();
+        assert_eq!(r.push(&frames[2]), Ok(true));
+        assert_eq!(r.push(&frames[0]), Ok(true));
+        assert_eq!(r.push(&frames[2]), Ok(false));
+        assert_eq!(r.missing(), vec![1, 3]);
+        assert!(!r.is_complete());
+        r.push(&frames[3]).unwrap();
+        r.push(&frames[1]).unwrap();
+        assert!(r.is_complete());
+        assert_eq!(r.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn empty_payload_is_one_frame() {
+        let frames = encode_frames(&[]).unwrap();
+        assert_eq!(frames.len(), 1);
+        let mut r = Reassembler::new();
+        r.push(&frames[0]).unwrap();
+        assert_eq!(r.finish().unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn rejects_mixed_and_damaged_streams() {
+        let frames = encode_frames(&payload(800)).unwrap();
+        let other = encode_frames(&payload(801)).unwrap();
+        let mut r = Reassembler::new();
+        r.push(&frames[0]).unwrap();
+        assert_eq!(r.push(&other[1]), Err(ReassemblyError::OtherStream));
+        assert_eq!(
+            r.push("WIFI:S:example;;"),
+            Err(ReassemblyError::Frame(FrameError::NotAFrame))
+        );
+        assert_eq!(r.finish(), Err(ReassemblyError::Incomplete(vec![1, 2])));
+
+        // flip a bit in the data of the last frame, keeping its header
+        let mut r = Reassembler::new();
+        r.push(&frames[0]).unwrap();
+        r.push(&frames[1]).unwrap();
+        let mut last = QrFrame::parse(&frames[2]).unwrap();
+        last.data[0] ^= 1;
+        r.push(&last.encode()).unwrap();
+        assert!(matches!(r.finish(), Err(ReassemblyError::BadCrc { .. })));
+    }
+
+    #[test]
+    fn rejects_oversized_sequences() {
+        let max_len = MAX_FRAMES as usize * format::CHUNK_LEN;
+        assert!(encode_frames(&payload(max_len)).is_some());
+        assert!(encode_frames(&payload(max_len + 1)).is_none());
+
+        // a header can't ask for more slots than MAX_FRAMES, or than its length needs
+        let mut r = Reassembler::new();
+        assert_eq!(
+            r.push(&format!("XQ1:0:{}:10:00000000:AAAA", u32::MAX)),
+            Err(ReassemblyError::Frame(FrameError::TooManyFrames))
+        );
+        assert_eq!(
+            r.push(&format!("XQ1:0:{}:10:00000000:AAAA", MAX_FRAMES)),
+            Err(ReassemblyError::Frame(FrameError::BadHeader))
+        );
+        assert_eq!(r.count(), None);
+    }
+}
@@ -0,0 +1,790 @@
+//! Generate the IPC plumbing for a Xous server from one trait.
+//!
+//! ```ignore
+//! #[xous_ipc_derive::ipc]
+//! pub trait Counter {
+//!     /// Add `amount` to the count
+//!     #[scalar(0)]
+//!     fn add(&mut self, amount: u32);
+//!
+//!     /// Return the count
+//!     #[blocking_scalar(1)]
+//!     fn get(&mut self) -> u32;
+//!
+//!     /// Fill in a report about the counter
+//!     #[lend_mut(2)]
+//!     fn report(&mut self, report: &mut Report);
+//!
+//!     /// Record an event without waiting for the server
+//!     #[send(3)]
+//!     fn record(&mut self, event: Event);
+//! }
+//! ```
+//!
+//! The trait is kept as written, minus the opcode attributes, and is what the server implements.
+//! Alongside it the macro generates:
+//!
+//! * `CounterOpcode`, an enum of the opcodes with `from_usize()` and `to_usize()`
+//! * `CounterClient`, which wraps a `CID` and has one method per opcode that sends the message
+//!   and decodes the response
+//! * `Counter::dispatch()`, which decodes a `MessageEnvelope`, calls the method for its opcode
+//!   and sends the response
+//!
+//! Each method is tagged with the kind of message it is sent as, and its opcode:
+//!
+//! * `#[scalar(N)]`: a non-blocking scalar message with up to four arguments and no return value
+//! * `#[blocking_scalar(N)]`: a blocking scalar message with up to four arguments, returning
+//!   nothing, a value or a pair of values
+//! * `#[lend_mut(N)]`: a mutable lend of one rkyv type, which the server may modify in place. The
+//!   client passes the value in and gets the modified value back.
+//! * `#[send(N)]`: a non-blocking move of one rkyv type
+//!
+//! Scalar arguments and return values may be `bool`, `u8`, `u16`, `u32`, `usize`, `i8`, `i16`,
+//! `i32` or `isize`, which fit into a register on every target. Using the same opcode twice is a
+//! compile error.
+//!
+//! The generated code refers to `xous`, and also to `xous_ipc` if there are memory messages, so
+//! the crate using the macro has to depend on them.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, Attribute, FnArg, Ident, ItemTrait, LitInt, Pat, ReturnType, TraitItem, Type,
+};
+
+#[proc_macro_attribute]
+pub fn ipc(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    expand(attr.into(), item.into())
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Scalar,
+    BlockingScalar,
+    LendMut,
+    Send,
+}
+
+impl Kind {
+    fn from_attr(attr: &Attribute) -> Option<Kind> {
+        let ident = attr.path.get_ident()?;
+        match ident.to_string().as_str() {
+            "scalar" => Some(Kind::Scalar),
+            "blocking_scalar" => Some(Kind::BlockingScalar),
+            "lend_mut" => Some(Kind::LendMut),
+            "send" => Some(Kind::Send),
+            _ => None,
+        }
+    }
+}
+
+/// One method of the trait, and how it travels over IPC.
+struct Method {
+    kind: Kind,
+    opcode: u32,
+    ident: Ident,
+    variant: Ident,
+    docs: Vec<Attribute>,
+    /// For memory messages, this is the single rkyv argument with any `&mut` removed
+    args: Vec<(Ident, Type)>,
+    /// The values a blocking scalar returns, which may be none, one or two
+    ret: Vec<Type>,
+    ret_ty: Type,
+}
+
+/// Types that fit into a message register on both 32- and 64-bit targets.
+const SCALAR_TYPES: &[&str] = &[
+    "bool", "u8", "u16", "u32", "usize", "i8", "i16", "i32", "isize",
+];
+
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            path.path.get_ident().map(|ident| ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn check_scalar(ty: &Type) -> syn::Result<()> {
+    match type_name(ty) {
+        Some(name) if SCALAR_TYPES.contains(&name.as_str()) => Ok(()),
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "scalar messages can only carry bool, u8, u16, u32, usize, i8, i16, i32 or isize",
+        )),
+    }
+}
+
+fn to_register(value: TokenStream, ty: &Type) -> TokenStream {
+    match type_name(ty).as_deref() {
+        Some("usize") => value,
+        _ => quote! { #value as usize },
+    }
+}
+
+fn from_register(value: TokenStream, ty: &Type) -> TokenStream {
+    match type_name(ty).as_deref() {
+        Some("usize") => value,
+        Some("bool") => quote! { #value != 0 },
+        _ => quote! { #value as #ty },
+    }
+}
+
+fn camel_case(ident: &Ident) -> Ident {
+    let name: String = ident
+        .to_string()
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    Ident::new(&name, ident.span())
+}
+
+/// Removes the opcode attribute from `method` and works out how it is sent.
+fn parse_method(method: &mut syn::TraitItemMethod) -> syn::Result<Method> {
+    let mut tagged = None;
+    let mut attrs = Vec::new();
+    for attr in method.attrs.drain(..) {
+        match Kind::from_attr(&attr) {
+            Some(_) if tagged.is_some() => {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "a method can only have one opcode",
+                ));
+            }
+            Some(kind) => {
+                let opcode = attr.parse_args::<LitInt>()?.base10_parse::<u32>()?;
+                tagged = Some((kind, opcode));
+            }
+            None => attrs.push(attr),
+        }
+    }
+    method.attrs = attrs;
+    let sig = &method.sig;
+    let (kind, opcode) = tagged.ok_or_else(|| {
+        syn::Error::new_spanned(
+            &sig.ident,
+            "tag this method with #[scalar(N)], #[blocking_scalar(N)], #[lend_mut(N)] or #[send(N)]",
+        )
+    })?;
+    if let Some(default) = &method.default {
+        return Err(syn::Error::new_spanned(
+            default,
+            "the server provides the implementation",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "methods can't be generic",
+        ));
+    }
+
+    let mut inputs = sig.inputs.iter();
+    let mut_self = matches!(
+        inputs.next(),
+        Some(FnArg::Receiver(receiver)) if receiver.reference.is_some() && receiver.mutability.is_some()
+    );
+    if !mut_self {
+        return Err(syn::Error::new_spanned(
+            sig,
+            "the first argument must be `&mut self`",
+        ));
+    }
+    let mut args = Vec::new();
+    for input in inputs {
+        match input {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) => args.push((pat.ident.clone(), (*arg.ty).clone())),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &arg.pat,
+                        "arguments must be plain names",
+                    ))
+                }
+            },
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(receiver, "unexpected `self`"))
+            }
+        }
+    }
+
+    let (ret, ret_ty) = match &sig.output {
+        ReturnType::Default => (vec![], parse_quote! { () }),
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Tuple(tuple) => (tuple.elems.iter().cloned().collect(), (**ty).clone()),
+            _ => (vec![(**ty).clone()], (**ty).clone()),
+        },
+    };
+
+    match kind {
+        Kind::Scalar | Kind::BlockingScalar => {
+            if args.len() > 4 {
+                return Err(syn::Error::new_spanned(
+                    &sig.inputs,
+                    "scalar messages have room for four arguments",
+                ));
+            }
+            for (_, ty) in &args {
+                check_scalar(ty)?;
+            }
+            if kind == Kind::Scalar && !ret.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    &sig.output,
+                    "non-blocking messages can't return anything, use #[blocking_scalar(N)]",
+                ));
+            }
+            if ret.len() > 2 {
+                return Err(syn::Error::new_spanned(
+                    &sig.output,
+                    "blocking scalars return at most two values",
+                ));
+            }
+            for ty in &ret {
+                check_scalar(ty)?;
+            }
+        }
+        Kind::LendMut | Kind::Send => {
+            if args.len() != 1 {
+                return Err(syn::Error::new_spanned(
+                    &sig.inputs,
+                    "memory messages carry exactly one argument",
+                ));
+            }
+            if !ret.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    &sig.output,
+                    "memory messages can't return anything",
+                ));
+            }
+            let ty = &mut args[0].1;
+            match (kind, ty.clone()) {
+                (Kind::LendMut, Type::Reference(reference)) if reference.mutability.is_some() => {
+                    *ty = *reference.elem
+                }
+                (Kind::LendMut, _) => {
+                    return Err(syn::Error::new_spanned(
+                        ty,
+                        "#[lend_mut(N)] takes its argument as `&mut T`",
+                    ));
+                }
+                (_, Type::Reference(_)) => {
+                    return Err(syn::Error::new_spanned(
+                        ty,
+                        "#[send(N)] takes its argument by value",
+                    ));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    Ok(Method {
+        kind,
+        opcode,
+        ident: sig.ident.clone(),
+        variant: camel_case(&sig.ident),
+        docs: method
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("doc"))
+            .cloned()
+            .collect(),
+        args,
+        ret,
+        ret_ty,
+    })
+}
+
+fn client_method(method: &Method) -> TokenStream {
+    let Method {
+        ident,
+        docs,
+        args,
+        ret,
+        ret_ty,
+        ..
+    } = method;
+    let opcode = Literal::u32_unsuffixed(method.opcode);
+    let names: Vec<&Ident> = args.iter().map(|(name, _)| name).collect();
+    let types: Vec<&Type> = args.iter().map(|(_, ty)| ty).collect();
+    let mut registers: Vec<TokenStream> = args
+        .iter()
+        .map(|(name, ty)| to_register(quote! { #name }, ty))
+        .collect();
+    registers.resize(4, quote! { 0 });
+
+    match method.kind {
+        Kind::Scalar => quote! {
+            #(#docs)*
+            pub fn #ident(&self, #(#names: #types),*) -> Result<(), xous::Error> {
+                xous::send_message(self.conn, xous::Message::new_scalar(#opcode, #(#registers),*)).map(|_| ())
+            }
+        },
+        Kind::BlockingScalar => {
+            let decode = match ret.as_slice() {
+                [] => quote! { Ok(()) },
+                [ty] => {
+                    let value = from_register(quote! { value }, ty);
+                    quote! {
+                        match response {
+                            xous::Result::Scalar1(value) => Ok(#value),
+                            _ => Err(xous::Error::InternalError),
+                        }
+                    }
+                }
+                [ty1, ty2] => {
+                    let (value1, value2) = (
+                        from_register(quote! { value1 }, ty1),
+                        from_register(quote! { value2 }, ty2),
+                    );
+                    quote! {
+                        match response {
+                            xous::Result::Scalar2(value1, value2) => Ok((#value1, #value2)),
+                            _ => Err(xous::Error::InternalError),
+                        }
+                    }
+                }
+                _ => unreachable!(),
+            };
+            let response = if ret.is_empty() {
+                quote! { _response }
+            } else {
+                quote! { response }
+            };
+            quote! {
+                #(#docs)*
+                pub fn #ident(&self, #(#names: #types),*) -> Result<#ret_ty, xous::Error> {
+                    let #response = xous::send_message(self.conn, xous::Message::new_blocking_scalar(#opcode, #(#registers),*))?;
+                    #decode
+                }
+            }
+        }
+        Kind::LendMut => {
+            let (name, ty) = &args[0];
+            quote! {
+                #(#docs)*
+                ///
+                /// The server may modify the value, and the modified value is returned.
+                pub fn #ident(&self, #name: #ty) -> Result<#ty, xous::Error> {
+                    let mut buf = xous_ipc::Buffer::into_buf(#name).or(Err(xous::Error::InternalError))?;
+                    buf.lend_mut(self.conn, #opcode)?;
+                    buf.to_original::<#ty, _>().or(Err(xous::Error::InternalError))
+                }
+            }
+        }
+        Kind::Send => {
+            let (name, ty) = &args[0];
+            quote! {
+                #(#docs)*
+                pub fn #ident(&self, #name: #ty) -> Result<(), xous::Error> {
+                    let buf = xous_ipc::Buffer::into_buf(#name).or(Err(xous::Error::InternalError))?;
+                    buf.send(self.conn, #opcode).map(|_| ())
+                }
+            }
+        }
+    }
+}
+
+fn dispatch_arm(method: &Method, opcode_ident: &Ident) -> TokenStream {
+    let Method {
+        ident,
+        variant,
+        args,
+        ret,
+        ..
+    } = method;
+    let scalar = if args.is_empty() {
+        quote! { _ }
+    } else {
+        quote! { scalar }
+    };
+    let values: Vec<TokenStream> = args
+        .iter()
+        .zip(["arg1", "arg2", "arg3", "arg4"].iter())
+        .map(|((_, ty), register)| {
+            let register = format_ident!("{}", register);
+            from_register(quote! { scalar.#register }, ty)
+        })
+        .collect();
+
+    let handler = match method.kind {
+        Kind::Scalar => quote! {
+            match &msg.body {
+                xous::Message::Scalar(#scalar) => {
+                    self.#ident(#(#values),*);
+                    Ok(())
+                }
+                _ => Err(xous::Error::InvalidSyscall),
+            }
+        },
+        Kind::BlockingScalar => {
+            let respond = match ret.as_slice() {
+                [] => quote! {
+                    self.#ident(#(#values),*);
+                    xous::return_scalar(msg.sender, 0)
+                },
+                [ty] => {
+                    let value = to_register(quote! { value }, ty);
+                    quote! {
+                        let value = self.#ident(#(#values),*);
+                        xous::return_scalar(msg.sender, #value)
+                    }
+                }
+                [ty1, ty2] => {
+                    let (value1, value2) = (
+                        to_register(quote! { value1 }, ty1),
+                        to_register(quote! { value2 }, ty2),
+                    );
+                    quote! {
+                        let (value1, value2) = self.#ident(#(#values),*);
+                        xous::return_scalar2(msg.sender, #value1, #value2)
+                    }
+                }
+                _ => unreachable!(),
+            };
+            quote! {
+                match &msg.body {
+                    xous::Message::BlockingScalar(#scalar) => {
+                        #respond
+                    }
+                    _ => Err(xous::Error::InvalidSyscall),
+                }
+            }
+        }
+        Kind::LendMut => {
+            let ty = &args[0].1;
+            quote! {
+                match &mut msg.body {
+                    xous::Message::MutableBorrow(mem) => {
+                        let mut buffer = unsafe { xous_ipc::Buffer::from_memory_message_mut(mem) };
+                        let mut value = buffer.to_original::<#ty, _>().or(Err(xous::Error::InternalError))?;
+                        self.#ident(&mut value);
+                        buffer.replace(value).or(Err(xous::Error::InternalError))
+                    }
+                    _ => Err(xous::Error::InvalidSyscall),
+                }
+            }
+        }
+        Kind::Send => {
+            let ty = &args[0].1;
+            quote! {
+                match &msg.body {
+                    xous::Message::Move(mem) => {
+                        let buffer = unsafe { xous_ipc::Buffer::from_memory_message(mem) };
+                        let value = buffer.to_original::<#ty, _>().or(Err(xous::Error::InternalError))?;
+                        self.#ident(value);
+                        Ok(())
+                    }
+                    _ => Err(xous::Error::InvalidSyscall),
+                }
+            }
+        }
+    };
+    quote! {
+        Some(#opcode_ident::#variant) => #handler,
+    }
+}
+
+fn expand(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    if !attr.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "#[ipc] doesn't take any arguments",
+        ));
+    }
+    let mut item: ItemTrait = syn::parse2(item)?;
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "IPC traits can't be generic",
+        ));
+    }
+
+    let mut methods = Vec::new();
+    let mut errors: Option<syn::Error> = None;
+    let mut add_error = |err: syn::Error| match &mut errors {
+        Some(errors) => errors.combine(err),
+        None => errors = Some(err),
+    };
+    let mut opcodes: HashMap<u32, Ident> = HashMap::new();
+    for trait_item in item.items.iter_mut() {
+        let method = match trait_item {
+            TraitItem::Method(method) => method,
+            other => {
+                add_error(syn::Error::new_spanned(
+                    other,
+                    "IPC traits can only contain methods",
+                ));
+                continue;
+            }
+        };
+        if method.sig.ident == "dispatch" {
+            add_error(syn::Error::new_spanned(
+                &method.sig.ident,
+                "`dispatch` is generated by #[ipc]",
+            ));
+            continue;
+        }
+        let method = match parse_method(method) {
+            Ok(method) => method,
+            Err(err) => {
+                add_error(err);
+                continue;
+            }
+        };
+        if let Some(first) = opcodes.get(&method.opcode) {
+            add_error(syn::Error::new(
+                method.ident.span(),
+                format!("opcode {} is already used by `{}`", method.opcode, first),
+            ));
+            continue;
+        }
+        opcodes.insert(method.opcode, method.ident.clone());
+        methods.push(method);
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+    if methods.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.ident,
+            "IPC traits need at least one method",
+        ));
+    }
+
+    let vis = &item.vis;
+    let trait_ident = &item.ident;
+    let opcode_ident = format_ident!("{}Opcode", trait_ident);
+    let client_ident = format_ident!("{}Client", trait_ident);
+    let variants: Vec<&Ident> = methods.iter().map(|method| &method.variant).collect();
+    let values: Vec<Literal> = methods
+        .iter()
+        .map(|method| Literal::u32_unsuffixed(method.opcode))
+        .collect();
+    let docs: Vec<&Vec<Attribute>> = methods.iter().map(|method| &method.docs).collect();
+    let client_methods = methods.iter().map(client_method);
+    let arms = methods
+        .iter()
+        .map(|method| dispatch_arm(method, &opcode_ident));
+
+    let opcode_doc = format!("Opcodes understood by [`{}`]", trait_ident);
+    let client_doc = format!(
+        "Sends messages to a server that implements [`{}`]",
+        trait_ident
+    );
+    item.items.push(parse_quote! {
+        /// Decode `msg`, call the method for its opcode and send the response, if the sender is
+        /// waiting for one.
+        ///
+        /// Fails with `UnhandledSyscall` if the opcode isn't part of this interface, and with
+        /// `InvalidSyscall` if the message is the wrong kind for its opcode. In either case a
+        /// sender blocked on a scalar is sent 0, and one blocked on a borrow gets its memory back
+        /// once `msg` is dropped.
+        fn dispatch(&mut self, msg: &mut xous::MessageEnvelope) -> Result<(), xous::Error> {
+            let result = match #opcode_ident::from_usize(msg.body.id()) {
+                #(#arms)*
+                None => Err(xous::Error::UnhandledSyscall),
+            };
+            if let Err(xous::Error::UnhandledSyscall) | Err(xous::Error::InvalidSyscall) = result {
+                // nothing else will answer a scalar, so don't leave the sender waiting forever
+                if msg.body.is_blocking() && msg.body.is_scalar() {
+                    xous::return_scalar(msg.sender, 0).ok();
+                }
+            }
+            result
+        }
+    });
+
+    Ok(quote! {
+        #item
+
+        #[doc = #opcode_doc]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        #vis enum #opcode_ident {
+            #(
+                #(#docs)*
+                #variants = #values,
+            )*
+        }
+
+        impl #opcode_ident {
+            pub fn from_usize(opcode: usize) -> Option<Self> {
+                match opcode {
+                    #(#values => Some(#opcode_ident::#variants),)*
+                    _ => None,
+                }
+            }
+
+            pub fn to_usize(self) -> usize {
+                self as usize
+            }
+        }
+
+        #[doc = #client_doc]
+        #[derive(Debug, Copy, Clone)]
+        #vis struct #client_ident {
+            conn: xous::CID,
+        }
+
+        impl #client_ident {
+            pub fn new(conn: xous::CID) -> Self {
+                #client_ident { conn }
+            }
+
+            pub fn conn(&self) -> xous::CID {
+                self.conn
+            }
+
+            #(#client_methods)*
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_err(item: TokenStream) -> String {
+        expand(TokenStream::new(), item).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn generates_opcodes_client_and_dispatcher() {
+        let output = expand(
+            TokenStream::new(),
+            quote! {
+                pub trait Counter {
+                    /// Add to the count
+                    #[scalar(0)]
+                    fn add(&mut self, amount: u32, reset: bool);
+                    #[blocking_scalar(1)]
+                    fn get(&mut self) -> u32;
+                    #[blocking_scalar(7)]
+                    fn get_range(&mut self) -> (usize, i32);
+                    #[lend_mut(2)]
+                    fn describe_all(&mut self, report: &mut Report);
+                    #[send(3)]
+                    fn record(&mut self, event: Event);
+                }
+            },
+        )
+        .unwrap();
+        let file: syn::File = syn::parse2(output).unwrap();
+
+        let counter = file.items.iter().find_map(|item| match item {
+            syn::Item::Trait(item) if item.ident == "Counter" => Some(item),
+            _ => None,
+        });
+        let counter = counter.expect("trait is missing");
+        assert_eq!(counter.items.len(), 6);
+        for trait_item in &counter.items {
+            if let TraitItem::Method(method) = trait_item {
+                // only the doc comments survive
+                assert!(method.attrs.iter().all(|attr| attr.path.is_ident("doc")));
+            }
+        }
+
+        let opcodes = file.items.iter().find_map(|item| match item {
+            syn::Item::Enum(item) if item.ident == "CounterOpcode" => Some(item),
+            _ => None,
+        });
+        let variants: Vec<String> = opcodes
+            .expect("opcodes are missing")
+            .variants
+            .iter()
+            .map(|variant| variant.ident.to_string())
+            .collect();
+        assert_eq!(
+            variants,
+            ["Add", "Get", "GetRange", "DescribeAll", "Record"]
+        );
+
+        assert!(file
+            .items
+            .iter()
+            .any(|item| matches!(item, syn::Item::Struct(item) if item.ident == "CounterClient")));
+    }
+
+    #[test]
+    fn duplicate_opcodes_are_rejected() {
+        let err = expand_err(quote! {
+            trait Pddb {
+                #[scalar(26)]
+                fn basis_testing(&mut self, op: usize, valid: usize);
+                #[lend_mut(26)]
+                fn list_basis_std(&mut self, list: &mut BasisList);
+            }
+        });
+        assert_eq!(err, "opcode 26 is already used by `basis_testing`");
+    }
+
+    #[test]
+    fn every_method_needs_an_opcode() {
+        let err = expand_err(quote! {
+            trait Counter {
+                fn add(&mut self, amount: u32);
+            }
+        });
+        assert!(err.starts_with("tag this method"));
+    }
+
+    #[test]
+    fn scalars_fit_in_registers() {
+        let err = expand_err(quote! {
+            trait Counter {
+                #[scalar(0)]
+                fn add(&mut self, a: u32, b: u32, c: u32, d: u32, e: u32);
+            }
+        });
+        assert_eq!(err, "scalar messages have room for four arguments");
+
+        let err = expand_err(quote! {
+            trait Counter {
+                #[blocking_scalar(0)]
+                fn elapsed(&mut self) -> u64;
+            }
+        });
+        assert!(err.starts_with("scalar messages can only carry"));
+
+        let err = expand_err(quote! {
+            trait Counter {
+                #[scalar(0)]
+                fn get(&mut self) -> u32;
+            }
+        });
+        assert!(err.starts_with("non-blocking messages can't return anything"));
+    }
+
+    #[test]
+    fn memory_arguments_are_checked() {
+        let err = expand_err(quote! {
+            trait Counter {
+                #[lend_mut(0)]
+                fn describe(&mut self, report: Report);
+            }
+        });
+        assert_eq!(err, "#[lend_mut(N)] takes its argument as `&mut T`");
+
+        let err = expand_err(quote! {
+            trait Counter {
+                #[send(0)]
+                fn record(&mut self, event: &mut Event);
+            }
+        });
+        assert_eq!(err, "#[send(N)] takes its argument by value");
+    }
+}
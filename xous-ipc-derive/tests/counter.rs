@@ -0,0 +1,259 @@
+//! Expands `#[ipc]` on a trait with every kind of message and builds both halves against `xous`
+//! and `xous_ipc`. Sending needs a kernel, so the client is only compiled; the dispatcher is run
+//! on scalar messages. Answering a blocking one is a syscall, so the tests that do it first start
+//! a stand-in kernel with `kernel()`.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Mutex, Once};
+
+use xous::{Message, MessageEnvelope, MessageSender, ScalarMessage, SysCall};
+use xous_ipc_derive::ipc;
+
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+pub struct Report {
+    pub count: u32,
+    pub events: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Event {
+    pub code: u16,
+}
+
+#[ipc]
+pub trait Counter {
+    /// Add `amount` to the count, or start again from it
+    #[scalar(0)]
+    fn add(&mut self, amount: u32, reset: bool);
+
+    /// Return the count
+    #[blocking_scalar(1)]
+    fn get(&mut self) -> u32;
+
+    /// Return how many times `add` was called, and the last amount added
+    #[blocking_scalar(2)]
+    fn stats(&mut self) -> (usize, i32);
+
+    /// Fill in a report about the counter
+    #[lend_mut(3)]
+    fn report(&mut self, report: &mut Report);
+
+    /// Record an event without waiting for the server
+    #[send(4)]
+    fn record(&mut self, event: Event);
+}
+
+#[derive(Default)]
+struct Server {
+    count: u32,
+    adds: usize,
+    last: i32,
+    events: u32,
+}
+
+impl Counter for Server {
+    fn add(&mut self, amount: u32, reset: bool) {
+        if reset {
+            self.count = 0;
+        }
+        self.count += amount;
+        self.adds += 1;
+        self.last = amount as i32;
+    }
+
+    fn get(&mut self) -> u32 {
+        self.count
+    }
+
+    fn stats(&mut self) -> (usize, i32) {
+        (self.adds, self.last)
+    }
+
+    fn report(&mut self, report: &mut Report) {
+        report.count = self.count;
+        report.events = self.events;
+    }
+
+    fn record(&mut self, _event: Event) {
+        self.events += 1;
+    }
+}
+
+/// Every `ReturnScalar1` the stand-in kernel was sent, as (sender, value)
+static RETURNED: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+/// Points `xous` at a stand-in kernel on a local socket, which records the scalars returned to
+/// blocked senders and answers every syscall with success.
+fn kernel() {
+    static START: Once = Once::new();
+    START.call_once(|| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        std::env::set_var("XOUS_SERVER", listener.local_addr().unwrap().to_string());
+        std::env::set_var("XOUS_PROCESS_KEY", "000102030405060708090a0b0c0d0e0f");
+        std::env::set_var("XOUS_PID", "2");
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut key = [0u8; 16];
+            stream.read_exact(&mut key).unwrap();
+            stream.write_all(&[2]).unwrap();
+
+            let mut next_tid = 1;
+            loop {
+                // the thread ID, then the eight words of the call
+                let mut words = [0usize; 9];
+                for word in words.iter_mut() {
+                    let mut bytes = [0u8; core::mem::size_of::<usize>()];
+                    stream.read_exact(&mut bytes).unwrap();
+                    *word = usize::from_le_bytes(bytes);
+                }
+                let [tid, a0, a1, a2, a3, a4, a5, a6, a7] = words;
+                let result = match SysCall::from_args(a0, a1, a2, a3, a4, a5, a6, a7) {
+                    Ok(SysCall::CreateThread(_)) => {
+                        next_tid += 1;
+                        xous::Result::ThreadID(next_tid)
+                    }
+                    Ok(SysCall::ReturnScalar1(sender, value)) => {
+                        RETURNED.lock().unwrap().push((sender.to_usize(), value));
+                        xous::Result::Ok
+                    }
+                    _ => xous::Result::Ok,
+                };
+                stream.write_all(&tid.to_le_bytes()).unwrap();
+                for word in result.to_args().iter() {
+                    stream.write_all(&word.to_le_bytes()).unwrap();
+                }
+            }
+        });
+    });
+}
+
+/// What the stand-in kernel was told to return to `sender`
+fn returned_to(sender: usize) -> Vec<usize> {
+    RETURNED
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(to, _)| *to == sender)
+        .map(|(_, value)| *value)
+        .collect()
+}
+
+fn envelope(body: Message) -> MessageEnvelope {
+    envelope_from(0, body)
+}
+
+fn envelope_from(sender: usize, body: Message) -> MessageEnvelope {
+    MessageEnvelope {
+        sender: MessageSender::from_usize(sender),
+        body,
+    }
+}
+
+fn scalar(id: usize, arg1: usize, arg2: usize) -> ScalarMessage {
+    ScalarMessage {
+        id,
+        arg1,
+        arg2,
+        arg3: 0,
+        arg4: 0,
+    }
+}
+
+#[test]
+fn opcodes_round_trip() {
+    for (opcode, value) in [
+        (CounterOpcode::Add, 0),
+        (CounterOpcode::Get, 1),
+        (CounterOpcode::Stats, 2),
+        (CounterOpcode::Report, 3),
+        (CounterOpcode::Record, 4),
+    ] {
+        assert_eq!(opcode.to_usize(), value);
+        assert_eq!(CounterOpcode::from_usize(value), Some(opcode));
+    }
+    assert_eq!(CounterOpcode::from_usize(5), None);
+}
+
+#[test]
+fn client_wraps_connection() {
+    // every method has to have been generated with the right signature for this to build
+    type Add = fn(&CounterClient, u32, bool) -> Result<(), xous::Error>;
+    type Get = fn(&CounterClient) -> Result<u32, xous::Error>;
+    type Stats = fn(&CounterClient) -> Result<(usize, i32), xous::Error>;
+    type ReportFn = fn(&CounterClient, Report) -> Result<Report, xous::Error>;
+    type Record = fn(&CounterClient, Event) -> Result<(), xous::Error>;
+    let _: (Add, Get, Stats, ReportFn, Record) = (
+        CounterClient::add,
+        CounterClient::get,
+        CounterClient::stats,
+        CounterClient::report,
+        CounterClient::record,
+    );
+
+    assert_eq!(CounterClient::new(7).conn(), 7);
+}
+
+#[test]
+fn dispatches_scalars() {
+    let mut server = Server::default();
+    let mut msg = envelope(Message::Scalar(scalar(0, 5, 0)));
+    server.dispatch(&mut msg).unwrap();
+    let mut msg = envelope(Message::Scalar(scalar(0, 3, 0)));
+    server.dispatch(&mut msg).unwrap();
+    assert_eq!(server.count, 8);
+    assert_eq!(server.stats(), (2, 3));
+
+    let mut msg = envelope(Message::Scalar(scalar(0, 2, 1)));
+    server.dispatch(&mut msg).unwrap();
+    assert_eq!(server.count, 2);
+}
+
+#[test]
+fn rejects_unknown_opcodes_and_wrong_kinds() {
+    kernel();
+    let mut server = Server::default();
+    let mut msg = envelope(Message::Scalar(scalar(9, 0, 0)));
+    assert_eq!(
+        server.dispatch(&mut msg),
+        Err(xous::Error::UnhandledSyscall)
+    );
+
+    // `add` is non-blocking, so a blocking scalar for it is the wrong kind of message
+    let mut msg = envelope(Message::BlockingScalar(scalar(0, 1, 0)));
+    assert_eq!(server.dispatch(&mut msg), Err(xous::Error::InvalidSyscall));
+    // and `get` is blocking
+    let mut msg = envelope(Message::Scalar(scalar(1, 0, 0)));
+    assert_eq!(server.dispatch(&mut msg), Err(xous::Error::InvalidSyscall));
+    assert_eq!(server.count, 0);
+}
+
+#[test]
+fn releases_blocked_senders_on_errors() {
+    kernel();
+    let mut server = Server::default();
+
+    let mut msg = envelope_from(0x11, Message::BlockingScalar(scalar(9, 0, 0)));
+    assert_eq!(
+        server.dispatch(&mut msg),
+        Err(xous::Error::UnhandledSyscall)
+    );
+    assert_eq!(returned_to(0x11), [0]);
+
+    let mut msg = envelope_from(0x12, Message::BlockingScalar(scalar(0, 1, 0)));
+    assert_eq!(server.dispatch(&mut msg), Err(xous::Error::InvalidSyscall));
+    assert_eq!(returned_to(0x12), [0]);
+
+    // nobody is waiting on a non-blocking message
+    let mut msg = envelope_from(0x13, Message::Scalar(scalar(1, 0, 0)));
+    assert_eq!(server.dispatch(&mut msg), Err(xous::Error::InvalidSyscall));
+    assert!(returned_to(0x13).is_empty());
+
+    // and a handled message is answered once, by its method
+    server.count = 5;
+    let mut msg = envelope_from(0x14, Message::BlockingScalar(scalar(1, 0, 0)));
+    server.dispatch(&mut msg).unwrap();
+    assert_eq!(returned_to(0x14), [5]);
+}
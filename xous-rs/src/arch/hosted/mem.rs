@@ -13,13 +13,20 @@ pub fn map_memory_pre(
     Ok(())
 }
 
+/// The kernel records the mapping at the address `syscall()` allocated for
+/// it, so the range it returns is already valid in this process.
 pub fn map_memory_post(
     _phys: Option<MemoryAddress>,
     _virt: Option<MemoryAddress>,
-    size: usize,
+    _size: usize,
     _flags: MemoryFlags,
-    _range: MemoryRange,
+    range: MemoryRange,
 ) -> core::result::Result<MemoryRange, Error> {
+    Ok(range)
+}
+
+/// Allocate page-aligned memory in this process, to be freed with `unmap_memory_post()`.
+pub fn alloc_range(size: usize) -> core::result::Result<MemoryRange, Error> {
     // let rounded_size = (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
     let layout = Layout::from_size_align(size, PAGE_SIZE)
         .unwrap()
@@ -5,7 +5,7 @@ use std::mem::size_of;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
 use std::sync::{Arc, Condvar, Mutex};
 
-use crate::{Result, SysCall, SysCallResult, PID, TID};
+use crate::{MemoryAddress, Result, SysCall, SysCallResult, PID, TID};
 
 mod mem;
 pub use mem::*;
@@ -133,6 +133,25 @@ struct ServerConnection {
 
 /// Perform a synchronous syscall to the kernel.
 pub fn syscall(call: SysCall) -> SysCallResult {
+    // The kernel can't hand us memory from its own address space, so allocate
+    // it here and have the kernel record the mapping at that address instead.
+    if let SysCall::MapMemory(phys, _virt, size, flags) = call {
+        let range = mem::alloc_range(size.get())?;
+        let result = syscall_inner(SysCall::MapMemory(
+            phys,
+            MemoryAddress::new(range.as_ptr() as usize),
+            size,
+            flags,
+        ));
+        if result.is_err() {
+            mem::unmap_memory_post(range).ok();
+        }
+        return result;
+    }
+    syscall_inner(call)
+}
+
+fn syscall_inner(call: SysCall) -> SysCallResult {
     let tid = thread_id();
 
     // If this call has memory attached to it, save that memory information
@@ -238,14 +257,8 @@ fn read_next_syscall_result(
                 crate::Message::Move(ref mut memory_message)
                 | crate::Message::Borrow(ref mut memory_message)
                 | crate::Message::MutableBorrow(ref mut memory_message) => {
-                    memory_message.buf = mem::map_memory_post(
-                        None,
-                        None,
-                        memory_message.buf.len(),
-                        crate::MemoryFlags::R | crate::MemoryFlags::W,
-                        memory_message.buf,
-                    )
-                    .expect("couldn't allocate range");
+                    memory_message.buf = mem::alloc_range(memory_message.buf.len())
+                        .expect("couldn't allocate range");
                     if let Err(e) = stream.read_exact(memory_message.buf.as_slice_mut()) {
                         eprintln!("Server shut down: {}", e);
                         std::process::exit(0);
@@ -258,6 +271,10 @@ fn read_next_syscall_result(
         // If the original call contained memory, then the server will send a copy of the
         // buffer back to us. Ensure the memory we get back is correct.
         if let Some((mem, kind)) = call_mem_tracker.lock().unwrap().remove(&msg_thread_id) {
+            // A failed call leaves the memory with us, and nothing follows the response.
+            if let Result::Error(_) = response {
+                return (msg_thread_id, response);
+            }
             if response == Result::RetryCall {
             } else if kind == CallMemoryKind::Borrow || kind == CallMemoryKind::MutableBorrow {
                 // Read the buffer back from the remote host.
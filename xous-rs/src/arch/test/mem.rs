@@ -12,6 +12,8 @@ pub fn map_memory_pre(
     Ok(())
 }
 
+/// The kernel records the mapping at the address `syscall()` allocated for
+/// it, so the range it returns is already valid in this process.
 pub fn map_memory_post(
     _phys: Option<MemoryAddress>,
     _virt: Option<MemoryAddress>,
@@ -19,9 +21,14 @@ pub fn map_memory_post(
     _flags: MemoryFlags,
     range: MemoryRange,
 ) -> core::result::Result<MemoryRange, Error> {
-    let layout = Layout::from_size_align(range.len(), 4096).unwrap();
+    Ok(range)
+}
+
+/// Allocate page-aligned memory in this process, to be freed with `unmap_memory_post()`.
+pub fn alloc_range(size: usize) -> core::result::Result<MemoryRange, Error> {
+    let layout = Layout::from_size_align(size, 4096).unwrap();
     let new_mem = MemoryAddress::new(unsafe { alloc(layout) } as usize).ok_or(Error::BadAddress)?;
-    Ok(unsafe { MemoryRange::new(new_mem.get(), range.len()).unwrap() })
+    Ok(unsafe { MemoryRange::new(new_mem.get(), size).unwrap() })
 }
 
 pub fn unmap_memory_pre(_range: &MemoryRange) -> core::result::Result<(), Error> {
@@ -7,7 +7,7 @@ use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
 use std::sync::{Arc, Mutex};
 use std::thread_local;
 
-use crate::{Result, SysCall, SysCallResult, PID, TID};
+use crate::{MemoryAddress, Result, SysCall, SysCallResult, PID, TID};
 
 mod mem;
 pub use mem::*;
@@ -617,6 +617,26 @@ fn xous_connect_impl(
 }
 
 pub fn syscall(call: SysCall) -> SysCallResult {
+    // Processes are threads of the test harness rather than real address
+    // spaces, so allocate memory here and have the kernel record the mapping
+    // at that address.
+    if let SysCall::MapMemory(phys, _virt, size, flags) = call {
+        let range = mem::alloc_range(size.get())?;
+        let result = syscall_inner(SysCall::MapMemory(
+            phys,
+            MemoryAddress::new(range.as_ptr() as usize),
+            size,
+            flags,
+        ));
+        if result.is_err() {
+            mem::unmap_memory_post(range).ok();
+        }
+        return result;
+    }
+    syscall_inner(call)
+}
+
+fn syscall_inner(call: SysCall) -> SysCallResult {
     let mut ret = Result::Ok;
     XOUS_SERVER_CONNECTION.with(|xsc| {
         THREAD_ID.with(|tid| {
@@ -780,7 +800,11 @@ fn _xous_syscall_result(ret: &mut Result, thread_id: TID, server_connection: &Se
         }
 
         // If the original call contained memory, then ensure the memory we get back is correct.
-        if let Some(mem) = call.memory() {
+        // A failed call leaves the memory with us, and nothing follows the response.
+        if let Some(mem) = call
+            .memory()
+            .filter(|_| !matches!(response, Result::Error(_)))
+        {
             if call.is_borrow() || call.is_mutableborrow() {
                 // Read the buffer back from the remote host.
                 use core::slice;
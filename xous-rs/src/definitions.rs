@@ -383,6 +383,67 @@ impl core::fmt::Display for MemoryType {
     }
 }
 
+/// How eagerly the scheduler runs a thread. When several threads are ready,
+/// the one with the highest priority runs first. Threads that are repeatedly
+/// passed over in favour of higher-priority threads are eventually run anyway,
+/// so a busy high-priority thread cannot starve the rest of the system.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThreadPriority {
+    /// Background work such as scrubbing or housekeeping.
+    Low = 0,
+
+    /// The priority threads start with.
+    Normal = 1,
+
+    /// Interactive work such as the UI or USB.
+    High = 2,
+
+    /// Work with hard deadlines, such as feeding audio buffers.
+    Realtime = 3,
+}
+
+impl ThreadPriority {
+    pub fn from_usize(arg: usize) -> Option<Self> {
+        match arg {
+            0 => Some(ThreadPriority::Low),
+            1 => Some(ThreadPriority::Normal),
+            2 => Some(ThreadPriority::High),
+            3 => Some(ThreadPriority::Realtime),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ThreadPriority {
+    fn default() -> Self {
+        ThreadPriority::Normal
+    }
+}
+
+/// Which set of counters `SysCall::GetUsage` reads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UsageKind {
+    /// Totals for a process.
+    Process = 1,
+
+    /// One thread within a process.
+    Thread = 2,
+
+    /// One slot in the kernel's server table.
+    Server = 3,
+}
+
+impl UsageKind {
+    pub fn from_usize(arg: usize) -> Option<Self> {
+        match arg {
+            1 => Some(UsageKind::Process),
+            2 => Some(UsageKind::Thread),
+            3 => Some(UsageKind::Server),
+            _ => None,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub enum Result {
@@ -1,7 +1,8 @@
 use crate::{
     pid_from_usize, CpuID, Error, MemoryAddress, MemoryFlags, MemoryMessage, MemoryRange,
     MemorySize, MemoryType, Message, MessageEnvelope, MessageSender, ProcessArgs, ProcessInit,
-    Result, ScalarMessage, SysCallResult, ThreadInit, CID, PID, SID, TID,
+    Result, ScalarMessage, SysCallResult, ThreadInit, ThreadPriority, UsageKind, CID, PID, SID,
+    TID,
 };
 use core::convert::{TryFrom, TryInto};
 /* https://github.com/betrusted-io/xous-core/issues/90
@@ -531,6 +532,59 @@ pub enum SysCall {
     #[cfg(feature = "v2p")]
     VirtToPhysPid(PID /* Process ID */, usize /* virtual address */),
 
+    /// Read the usage counters the kernel keeps for processes, threads,
+    /// and servers. All counters start at zero when the process or server
+    /// is created, and wrap on overflow.
+    ///
+    /// ## Arguments
+    ///
+    /// * **Kind**: Which counters to read:
+    ///                     `UsageKind::Process`: Process totals for the process **Target**
+    ///                     `UsageKind::Thread`: Thread **Index** within the process **Target**
+    ///                     `UsageKind::Server`: Server at slot **Target** in the server table
+    /// * **Target**: A PID for `Process` and `Thread`, or a server slot for `Server`
+    /// * **Index**: The thread ID for `Thread`, otherwise ignored
+    ///
+    /// ## Returns
+    ///
+    /// Returns a Scalar5 whose contents depend on **Kind**:
+    ///                     `Process`: `(quanta, context switches, messages sent, messages received, bytes lent)`
+    ///                     `Thread`: `(quanta, context switches, 0, 0, 0)`
+    ///                     `Server`: `(owning PID, messages received, bytes lent to it, 0, 0)`,
+    ///                        where an owning PID of 0 indicates an unused slot
+    ///
+    /// ## Errors
+    ///
+    /// * **ProcessNotFound**: The target process does not exist
+    /// * **InvalidThread**: The thread index is out of range
+    /// * **ServerNotFound**: The server slot is past the end of the server table
+    /// * **InvalidLimit**: The specified kind was not valid
+    GetUsage(
+        UsageKind,
+        usize, /* PID or server slot */
+        usize, /* thread ID */
+    ),
+
+    /// Set the scheduling priority of a thread in this process. New threads
+    /// start with the priority of the thread that created them.
+    ///
+    /// ## Arguments
+    ///
+    /// * **TID**: The thread to adjust
+    /// * **Priority**: The new priority
+    ///
+    /// ## Returns
+    ///
+    /// Returns a Scalar1 containing the previous priority.
+    ///
+    /// ## Errors
+    ///
+    /// * **ThreadNotAvailable**: The thread does not exist in this process
+    /// * **AccessDenied**: The priority is above `Normal` and this process
+    ///                     was not started by the kernel
+    /// * **InvalidLimit**: The priority is not a valid `ThreadPriority`
+    SetThreadPriority(TID, ThreadPriority),
+
     /// This syscall does not exist. It captures all possible
     /// arguments so detailed analysis can be performed.
     Invalid(usize, usize, usize, usize, usize, usize, usize),
@@ -581,6 +635,8 @@ pub enum SysCallNumber {
     ReplyAndReceiveNext = 41,
     #[cfg(feature = "v2p")]
     VirtToPhysPid = 42,
+    GetUsage = 43,
+    SetThreadPriority = 44,
     Invalid,
 }
 
@@ -631,6 +687,8 @@ impl SysCallNumber {
             41 => ReplyAndReceiveNext,
             #[cfg(feature = "v2p")]
             42 => VirtToPhysPid,
+            43 => GetUsage,
+            44 => SetThreadPriority,
             _ => Invalid,
         }
     }
@@ -1021,9 +1079,36 @@ impl SysCall {
                 0,
             ],
             #[cfg(feature = "v2p")]
-            SysCall::VirtToPhysPid(pid, vaddr) => {
-                [SysCallNumber::VirtToPhysPid as usize, pid.get() as usize, *vaddr, 0, 0, 0, 0, 0]
-            }
+            SysCall::VirtToPhysPid(pid, vaddr) => [
+                SysCallNumber::VirtToPhysPid as usize,
+                pid.get() as usize,
+                *vaddr,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::GetUsage(kind, target, index) => [
+                SysCallNumber::GetUsage as usize,
+                *kind as usize,
+                *target,
+                *index,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::SetThreadPriority(tid, priority) => [
+                SysCallNumber::SetThreadPriority as usize,
+                *tid,
+                *priority as usize,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
             SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7) => [
                 SysCallNumber::Invalid as usize,
                 *a1,
@@ -1195,6 +1280,15 @@ impl SysCall {
             SysCallNumber::VirtToPhys => SysCall::VirtToPhys(a1 as _),
             #[cfg(feature = "v2p")]
             SysCallNumber::VirtToPhysPid => SysCall::VirtToPhysPid(pid_from_usize(a1)?, a2 as _),
+            SysCallNumber::GetUsage => SysCall::GetUsage(
+                UsageKind::from_usize(a1).ok_or(Error::InvalidLimit)?,
+                a2,
+                a3,
+            ),
+            SysCallNumber::SetThreadPriority => SysCall::SetThreadPriority(
+                a1 as _,
+                ThreadPriority::from_usize(a2).ok_or(Error::InvalidLimit)?,
+            ),
             SysCallNumber::ReturnScalar5 => {
                 SysCall::ReturnScalar5(MessageSender::from_usize(a1), a2, a3, a4, a5, a6)
             }
@@ -2091,10 +2185,116 @@ pub fn virt_to_phys_pid(pid: PID, va: usize) -> core::result::Result<usize, Erro
     })
 }
 
+/// Counters the kernel keeps for each process. See `SysCall::GetUsage`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ProcessUsage {
+    /// Number of scheduler quanta given to this process
+    pub quanta: usize,
+    /// Number of times one of this process' threads was switched to
+    pub context_switches: usize,
+    /// Messages sent by this process to any server
+    pub messages_sent: usize,
+    /// Messages received by servers owned by this process
+    pub messages_received: usize,
+    /// Bytes of memory this process has lent out
+    pub bytes_lent: usize,
+}
+
+/// Counters the kernel keeps for each thread. See `SysCall::GetUsage`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ThreadUsage {
+    pub quanta: usize,
+    pub context_switches: usize,
+}
+
+/// Counters the kernel keeps for each server. See `SysCall::GetUsage`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ServerUsage {
+    /// The process that owns this server
+    pub pid: PID,
+    pub messages_received: usize,
+    pub bytes_lent: usize,
+}
+
+/// Return the usage counters for the given process.
+pub fn process_usage(pid: PID) -> core::result::Result<ProcessUsage, Error> {
+    rsyscall(SysCall::GetUsage(UsageKind::Process, pid.get() as usize, 0)).and_then(|result| {
+        if let Result::Scalar5(
+            quanta,
+            context_switches,
+            messages_sent,
+            messages_received,
+            bytes_lent,
+        ) = result
+        {
+            Ok(ProcessUsage {
+                quanta,
+                context_switches,
+                messages_sent,
+                messages_received,
+                bytes_lent,
+            })
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Return the usage counters for thread `tid` in the given process.
+pub fn thread_usage(pid: PID, tid: TID) -> core::result::Result<ThreadUsage, Error> {
+    rsyscall(SysCall::GetUsage(
+        UsageKind::Thread,
+        pid.get() as usize,
+        tid,
+    ))
+    .and_then(|result| {
+        if let Result::Scalar5(quanta, context_switches, _, _, _) = result {
+            Ok(ThreadUsage {
+                quanta,
+                context_switches,
+            })
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Return the usage counters for the server in slot `slot` of the kernel's
+/// server table, or `None` if that slot is unused. Returns `ServerNotFound`
+/// once `slot` is past the end of the table.
+pub fn server_usage(slot: usize) -> core::result::Result<Option<ServerUsage>, Error> {
+    rsyscall(SysCall::GetUsage(UsageKind::Server, slot, 0)).and_then(|result| {
+        if let Result::Scalar5(pid, messages_received, bytes_lent, _, _) = result {
+            Ok(PID::new(pid as u8).map(|pid| ServerUsage {
+                pid,
+                messages_received,
+                bytes_lent,
+            }))
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Set the scheduling priority of thread `tid` in this process, returning
+/// its previous priority. Only processes started by the kernel may use
+/// `High` or `Realtime`.
+pub fn set_thread_priority(
+    tid: TID,
+    priority: ThreadPriority,
+) -> core::result::Result<ThreadPriority, Error> {
+    rsyscall(SysCall::SetThreadPriority(tid, priority)).and_then(|result| {
+        if let Result::Scalar1(previous) = result {
+            ThreadPriority::from_usize(previous).ok_or(Error::InternalError)
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
 pub fn increase_heap(bytes: usize, flags: MemoryFlags) -> core::result::Result<MemoryRange, ()> {
     let res = crate::arch::syscall(SysCall::IncreaseHeap(bytes, flags));
-    if let Ok(Result::MemoryRange(range)) = res
-    {
+    if let Ok(Result::MemoryRange(range)) = res {
         return Ok(range);
     }
 
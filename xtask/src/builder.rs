@@ -130,6 +130,15 @@ pub(crate) struct Builder {
     locale_stash: String,
     /// when set to true, hosted mode builds but does not run
     dry_run: bool,
+    /// when set to true, hosted mode runs the UI tests headless and reports on them
+    ui_test: bool,
+    /// when set to true, hosted mode runs the network tests on the virtual switch and reports on them
+    net_test: bool,
+    /// when set to true, hosted mode runs the authenticated name server tests and reports on them
+    names_test: bool,
+    /// when set to true, hosted mode runs the PDDB CI tests headless, reports on them, and checks the
+    /// images they leave behind
+    pddb_ci: bool,
 }
 
 impl Builder {
@@ -154,6 +163,10 @@ impl Builder {
             locale_override: None,
             locale_stash: String::new(),
             dry_run: false,
+            ui_test: false,
+            net_test: false,
+            names_test: false,
+            pddb_ci: false,
         }
     }
     /// Specify an alternate loader key, as a String that can encode a file name
@@ -344,6 +357,33 @@ impl Builder {
         self.dry_run = true;
         self
     }
+    /// run hosted mode without a window, collect the results of the UI tests from its log, and
+    /// fail the build if any of them failed. The `ui-test` service has to be added separately.
+    pub fn hosted_ui_test<'a>(&'a mut self) -> &'a mut Builder {
+        self.ui_test = true;
+        self
+    }
+    /// run hosted mode without a window, with the WLAN link on the virtual switch in `com`, collect
+    /// the results of the network tests from its log, and fail the build if any of them failed.
+    /// The `net-test` service has to be added separately.
+    pub fn hosted_net_test<'a>(&'a mut self) -> &'a mut Builder {
+        self.net_test = true;
+        self
+    }
+    /// run hosted mode without a window, collect the results of the authenticated name server
+    /// tests from its log, and fail the build if any of them failed. The `names-test` service has
+    /// to be added separately.
+    pub fn hosted_names_test<'a>(&'a mut self) -> &'a mut Builder {
+        self.names_test = true;
+        self
+    }
+    /// run hosted mode without a window, collect the results of the PDDB CI tests from its log, and
+    /// then check that the offline tools can read the images the tests dumped. The PDDB has to be
+    /// built with its `ci` feature separately.
+    pub fn hosted_pddb_ci<'a>(&'a mut self) -> &'a mut Builder {
+        self.pddb_ci = true;
+        self
+    }
 
     /// The builder sets up all the cargo arguments to build a set of packages with features for a respective
     /// target and stream. It also runs the build as well. It's meant to be called only by the `build()`
@@ -635,12 +675,43 @@ impl Builder {
                     print!(" {}", arg);
                 }
                 println!();
-                let status = Command::new(cargo())
-                    .current_dir(dir)
-                    .args(&hosted_args)
-                    .status()?;
-                if !status.success() {
-                    return Err("cargo run failed to launch hosted mode".into());
+                let mut command = Command::new(cargo());
+                command.current_dir(dir).args(&hosted_args);
+                if self.ui_test {
+                    let mut capture_dir = project_root();
+                    capture_dir.push("target");
+                    capture_dir.push("ui-test");
+                    command
+                        .env("XOUS_GFX_BACKEND", "headless")
+                        .env("XOUS_GFX_TEST_HOOKS", "1")
+                        .env("XOUS_GFX_CAPTURE_DIR", capture_dir)
+                        .env("XOUS_PDDB_MODE", "memory");
+                    crate::hosted_test::run_hosted_tests(command, "UITEST", "UI")?;
+                } else if self.net_test {
+                    let mut script = project_root();
+                    script.push("services");
+                    script.push("net-test");
+                    script.push("network.txt");
+                    command
+                        .env("XOUS_GFX_BACKEND", "headless")
+                        .env("XOUS_PDDB_MODE", "memory")
+                        .env("XOUS_NET_BACKEND", "switch")
+                        .env("XOUS_NET_SCRIPT", script);
+                    crate::hosted_test::run_hosted_tests(command, "NETTEST", "network")?;
+                } else if self.names_test {
+                    command
+                        .env("XOUS_GFX_BACKEND", "headless")
+                        .env("XOUS_PDDB_MODE", "memory");
+                    crate::hosted_test::run_hosted_tests(command, "NAMESTEST", "name server")?;
+                } else if self.pddb_ci {
+                    command.env("XOUS_GFX_BACKEND", "headless");
+                    crate::hosted_test::run_hosted_tests(command, "PDDBCI", "PDDB")?;
+                    crate::hosted_test::check_pddb_images()?;
+                } else {
+                    let status = command.status()?;
+                    if !status.success() {
+                        return Err("cargo run failed to launch hosted mode".into());
+                    }
                 }
             } else {
                 // confirm the kernel can build before quitting
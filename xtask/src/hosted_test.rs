@@ -0,0 +1,122 @@
+//! Runs hosted mode with a test harness in it, such as the `ui-test`, `net-test` or `names-test`
+//! service or the PDDB's CI tests, and picks the results out of the log.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::builder::{cargo, project_root};
+use crate::DynError;
+
+/// A run that takes longer than this is taken to be hung
+const RUN_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+/// How long hosted mode gets to shut down once the tests are done
+const EXIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const BOOKEND_START: &str = "_|TT|_";
+const BOOKEND_END: &str = "_|TE|_";
+
+#[derive(Debug, PartialEq)]
+enum Report {
+    Pass(String),
+    Fail(String, String),
+    Done,
+}
+
+/// Picks a report of the `suite` out of a line of the log, if it has one.
+fn parse_report(line: &str, suite: &str) -> Option<Report> {
+    let start = line.find(BOOKEND_START)? + BOOKEND_START.len();
+    let end = start + line[start..].find(BOOKEND_END)?;
+    let mut fields = line[start..end]
+        .strip_prefix(suite)?
+        .strip_prefix('.')?
+        .splitn(3, ',');
+    match (fields.next()?, fields.next()) {
+        ("PASS", Some(name)) => Some(Report::Pass(name.to_string())),
+        ("FAIL", Some(name)) => Some(Report::Fail(
+            name.to_string(),
+            fields.next().unwrap_or("").to_string(),
+        )),
+        ("DONE", _) => Some(Report::Done),
+        _ => None,
+    }
+}
+
+/// Runs `command`, and collects the results that the harness logs with the `suite` tag, such as
+/// `UITEST`. `name` is what the tests are called in the summary.
+pub(crate) fn run_hosted_tests(mut command: Command, suite: &str, name: &str) -> Result<(), DynError> {
+    let mut child = command.stdout(Stdio::piped()).spawn()?;
+    let stdout = child.stdout.take().unwrap();
+    let (send, recv) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            match line {
+                Ok(line) => {
+                    if send.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    let mut passed = Vec::new();
+    let mut failed = Vec::new();
+    let mut deadline = Instant::now() + RUN_TIMEOUT;
+    let mut done = false;
+    loop {
+        match recv.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(line) => {
+                // pass the log through, so the run can be followed as usual
+                println!("{}", line);
+                match parse_report(&line, suite) {
+                    Some(Report::Pass(name)) => passed.push(name),
+                    Some(Report::Fail(name, reason)) => failed.push((name, reason)),
+                    Some(Report::Done) => {
+                        done = true;
+                        deadline = Instant::now() + EXIT_TIMEOUT;
+                    }
+                    None => (),
+                }
+            }
+            // hosted mode closed its output, so it has exited
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                child.kill().ok();
+                if !done {
+                    return Err(format!("{} tests timed out", name).into());
+                }
+                break;
+            }
+        }
+    }
+    child.wait()?;
+
+    println!("{} tests: {} passed, {} failed", name, passed.len(), failed.len());
+    for (name, reason) in failed.iter() {
+        println!("    FAILED {}: {}", name, reason);
+    }
+    if !done {
+        Err(format!("hosted mode exited before the {} tests were done", name).into())
+    } else if !failed.is_empty() {
+        Err(format!("{} {} test(s) failed", failed.len(), name).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Runs the `tools` tests that decode the images the PDDB CI tests dump into `tools/pddb-images`.
+/// They are ignored by a plain `cargo test`, as they need a fresh dump to read.
+pub(crate) fn check_pddb_images() -> Result<(), DynError> {
+    println!("Checking the PDDB images with the offline tools...");
+    let status = Command::new(cargo())
+        .current_dir(project_root())
+        .args(["test", "-p", "tools", "--lib", "--", "--ignored", "test_hosted_image"])
+        .status()?;
+    if !status.success() {
+        return Err("the offline tools could not read the PDDB images".into());
+    }
+    Ok(())
+}
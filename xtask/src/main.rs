@@ -7,6 +7,7 @@ mod builder;
 use builder::*;
 mod verifier;
 use verifier::*;
+mod hosted_test;
 
 use std::env;
 
@@ -227,7 +228,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             builder.target_hosted()
                    .add_services(&user_pkgs.into_iter().map(String::from).collect())
                    .add_feature("pddb/ci")
-                   .add_feature("pddb/deterministic");
+                   .add_feature("pddb/deterministic")
+                   .hosted_pddb_ci();
         }
         Some("pddb-btest") => {
             builder.target_hosted()
@@ -253,6 +255,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                    .add_services(&get_cratespecs())
                    .add_feature("graphics-server/testing");
         },
+        Some("ui-test") => {
+            builder.target_hosted()
+                   .add_services(&user_pkgs.into_iter().map(String::from).collect())
+                   .add_service("ui-test", false)
+                   .hosted_ui_test();
+            // the tests drive the vault, so it has to be in the image
+            if !extra_apps.iter().any(|app| app == "vault") {
+                builder.add_app("vault", false);
+            }
+        }
+        Some("net-test") => {
+            builder.target_hosted()
+                   .add_services(&user_pkgs.into_iter().map(String::from).collect())
+                   .add_service("net-test", false)
+                   .hosted_net_test();
+        }
+        Some("names-test") => {
+            builder.target_hosted()
+                   .add_services(&user_pkgs.into_iter().map(String::from).collect())
+                   .add_service("names-test", false)
+                   .hosted_names_test();
+        }
         Some("hosted-ci") => {
             builder.target_hosted()
                    .add_services(&user_pkgs.into_iter().map(String::from).collect())
@@ -482,10 +506,13 @@ Hardware images:
 
 Hosted emulation:
  run                     Run user image in hosted mode with release flags. [cratespecs] are apps
- pddb-ci                 PDDB config for CI testing (eg: TRNG->deterministic for reproducible errors). [cratespecs] ignored.
+ pddb-ci                 Runs the PDDB CI tests headless (TRNG->deterministic for reproducible errors), then checks the images they dump. [cratespecs] ignored.
  pddb-btest              PDDB stress tester for secret basis creation/deletion [cratespecs] ignored.
  hosted-debug            Run user image in hosted mode with debug flags. [cratespecs] are apps
  gfx-dev                 Testing mode for graphics primitives. [cratespecs] are services
+ ui-test                 Runs the end-to-end UI tests headless, and fails if any of them fail. [cratespecs] ignored.
+ net-test                Runs the network tests headless on the virtual WLAN switch, and fails if any of them fail. [cratespecs] ignored.
+ names-test              Runs the authenticated name server tests headless, and fails if any of them fail. [cratespecs] ignored.
  pddb-dev                Testing for compilation errors on hardware targets on the PDDB.
 
 Renode emulation: